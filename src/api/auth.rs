@@ -0,0 +1,529 @@
+use crate::api::ApiError;
+use crate::config::{ApiConfig, ApiKeyConfig, ApiRole};
+use crate::core::security::SecurityManager;
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const SECRET_LEN: usize = 32;
+// 只支持 HS256，不接受其他算法 (包括 none)
+const JWT_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+// 静态 API 密钥对应的令牌主体
+const API_KEY_SUBJECT: &str = "api-key";
+// 认证失败按客户端 IP 计数，SecurityManager 中的用户名固定为该值
+const API_CLIENT: &str = "api";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    Scan,
+    Update,
+    Quarantine,
+    Admin,
+    ReadOnly,
+}
+
+impl std::str::FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scan" => Ok(Scope::Scan),
+            "update" => Ok(Scope::Update),
+            "quarantine" => Ok(Scope::Quarantine),
+            "admin" => Ok(Scope::Admin),
+            "read-only" | "read_only" | "readonly" => Ok(Scope::ReadOnly),
+            other => Err(anyhow::anyhow!("未知的令牌权限: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub iss: String,
+    pub scopes: Vec<Scope>,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: String,
+}
+
+impl Claims {
+    // admin 拥有全部权限；只读接口任何有效令牌都可以访问
+    pub fn allows(&self, scope: Scope) -> bool {
+        scope == Scope::ReadOnly || self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+
+    // 静态 API 密钥和命名密钥没有令牌 ID，不能刷新或吊销
+    pub fn is_api_key(&self) -> bool {
+        self.jti.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct IssuedToken {
+    pub token: String,
+    pub subject: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ApiKeyInfo {
+    pub name: String,
+    pub role: ApiRole,
+    pub revoked: bool,
+    // 配置文件中的密钥通过 API 吊销后只在本次运行中有效
+    pub persistent: bool,
+}
+
+// 明文密钥只在创建时返回一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct CreatedApiKey {
+    pub name: String,
+    pub role: ApiRole,
+    pub key: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKeyConfig>,
+}
+
+struct NamedKey {
+    config: ApiKeyConfig,
+    digest: Vec<u8>,
+    // 来自 keys_file，修改后写回该文件
+    from_file: bool,
+}
+
+impl NamedKey {
+    fn new(config: ApiKeyConfig, from_file: bool) -> Option<Self> {
+        if config.name.is_empty() || config.name == API_KEY_SUBJECT {
+            log::warn!("忽略名称无效的 API 密钥: {:?}", config.name);
+            return None;
+        }
+        match hex::decode(config.key_sha256.trim()) {
+            Ok(digest) if digest.len() == 32 => Some(Self { config, digest, from_file }),
+            _ => {
+                log::warn!("忽略 API 密钥 {}: key_sha256 不是有效的 SHA256 摘要", config.name);
+                None
+            }
+        }
+    }
+
+    fn info(&self) -> ApiKeyInfo {
+        ApiKeyInfo {
+            name: self.config.name.clone(),
+            role: self.config.role,
+            revoked: self.config.revoked,
+            persistent: self.from_file,
+        }
+    }
+}
+
+pub fn role_scopes(role: ApiRole) -> Vec<Scope> {
+    match role {
+        ApiRole::Viewer => vec![Scope::ReadOnly],
+        ApiRole::Operator => vec![Scope::Scan, Scope::Update, Scope::Quarantine],
+        ApiRole::Admin => vec![Scope::Admin],
+    }
+}
+
+struct SigningKeys {
+    current: Vec<u8>,
+    // 轮换前的密钥，在其签发的令牌全部过期之前仍可用于验证
+    previous: Option<(Vec<u8>, i64)>,
+}
+
+// 签发和验证 API 令牌 (HS256 JWT)
+pub struct TokenAuthority {
+    issuer: String,
+    default_ttl: Duration,
+    max_ttl: Duration,
+    secret_file: Option<PathBuf>,
+    keys: RwLock<SigningKeys>,
+    api_key: Option<String>,
+    api_keys: RwLock<Vec<NamedKey>>,
+    keys_file: Option<PathBuf>,
+    // 已刷新或吊销的令牌 ID 及其过期时间，过期后清除
+    revoked: Mutex<HashMap<String, i64>>,
+    // 设置后认证失败次数过多的客户端被锁定
+    security: Option<Arc<SecurityManager>>,
+}
+
+impl TokenAuthority {
+    pub fn new(secret: Vec<u8>, config: &ApiConfig) -> Self {
+        Self {
+            issuer: config.auth.issuer.clone(),
+            default_ttl: Duration::from_secs(config.auth.token_ttl_secs.max(1)),
+            max_ttl: Duration::from_secs(config.auth.max_token_ttl_secs.max(config.auth.token_ttl_secs).max(1)),
+            secret_file: None,
+            keys: RwLock::new(SigningKeys {
+                current: secret,
+                previous: None,
+            }),
+            api_key: (!config.api_key.is_empty()).then(|| config.api_key.clone()),
+            api_keys: RwLock::new(config.keys.iter().cloned().filter_map(|key| NamedKey::new(key, false)).collect()),
+            keys_file: None,
+            revoked: Mutex::new(HashMap::new()),
+            security: None,
+        }
+    }
+
+    pub fn with_security_manager(mut self, security: Arc<SecurityManager>) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    // 从配置的密钥文件加载签名密钥，文件不存在时生成新密钥并以 0600 权限保存
+    pub fn from_config(config: &ApiConfig) -> Result<Self, anyhow::Error> {
+        let path = &config.auth.jwt_secret_file;
+        let secret = if path.exists() {
+            let secret = std::fs::read(path).with_context(|| format!("无法读取令牌签名密钥: {:?}", path))?;
+            if secret.len() < SECRET_LEN {
+                return Err(anyhow::anyhow!("令牌签名密钥过短: 至少需要 {} 字节", SECRET_LEN));
+            }
+            secret
+        } else {
+            let secret = generate_secret();
+            write_secret(path, &secret)?;
+            log::info!("已生成令牌签名密钥: {:?}", path);
+            secret
+        };
+
+        let mut authority = Self::new(secret, config);
+        authority.secret_file = Some(path.clone());
+        if let Some(ref keys_file) = config.keys_file {
+            authority.load_keys_file(keys_file)?;
+        }
+        Ok(authority)
+    }
+
+    // 文件不存在时视为空。文件中的密钥与配置文件中的同名时以配置文件为准
+    pub fn load_keys_file(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        self.keys_file = Some(path.to_path_buf());
+        if !path.exists() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(path).with_context(|| format!("无法读取 API 密钥文件: {:?}", path))?;
+        let file: KeysFile = serde_yaml::from_str(&content).with_context(|| format!("API 密钥文件格式错误: {:?}", path))?;
+        let mut keys = self.api_keys.write().unwrap();
+        for key in file.keys {
+            if keys.iter().any(|existing| existing.config.name == key.name) {
+                log::warn!("API 密钥文件中的 {} 与配置文件重名，已忽略", key.name);
+                continue;
+            }
+            keys.extend(NamedKey::new(key, true));
+        }
+        Ok(())
+    }
+
+    // 使用随机密钥，重启后之前签发的令牌全部失效
+    pub fn ephemeral(config: &ApiConfig) -> Self {
+        Self::new(generate_secret(), config)
+    }
+
+    // subject 用于审计日志；ttl 未指定时使用默认有效期，超过最长有效期时截断
+    pub fn issue(&self, subject: &str, scopes: Vec<Scope>, ttl: Option<Duration>) -> Result<IssuedToken, anyhow::Error> {
+        if subject.is_empty() || subject == API_KEY_SUBJECT {
+            return Err(anyhow::anyhow!("无效的令牌主体: {:?}", subject));
+        }
+        if scopes.is_empty() {
+            return Err(anyhow::anyhow!("令牌至少需要一个权限"));
+        }
+        let ttl = ttl.unwrap_or(self.default_ttl).min(self.max_ttl);
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: subject.to_string(),
+            iss: self.issuer.clone(),
+            scopes,
+            iat: now,
+            exp: now + ttl.as_secs() as i64,
+            jti: hex::encode(rand::thread_rng().gen::<[u8; 16]>()),
+        };
+
+        let payload = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(JWT_HEADER),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+        let signature = sign(&self.keys.read().unwrap().current, payload.as_bytes())?;
+        Ok(IssuedToken {
+            token: format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature)),
+            subject: claims.sub,
+            scopes: claims.scopes,
+            expires_at: DateTime::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now),
+        })
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, ApiError> {
+        let mut parts = token.splitn(3, '.');
+        let (Some(header), Some(body), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(ApiError::Unauthorized);
+        };
+        let signed = &token.as_bytes()[..header.len() + 1 + body.len()];
+        let header = URL_SAFE_NO_PAD.decode(header).map_err(|_| ApiError::Unauthorized)?;
+        let header: serde_json::Value = serde_json::from_slice(&header).map_err(|_| ApiError::Unauthorized)?;
+        if header["alg"] != "HS256" {
+            return Err(ApiError::Unauthorized);
+        }
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ApiError::Unauthorized)?;
+
+        let now = Utc::now().timestamp();
+        let valid = {
+            let keys = self.keys.read().unwrap();
+            let previous = keys.previous.as_ref().filter(|(_, until)| now < *until).map(|(key, _)| key);
+            std::iter::once(&keys.current)
+                .chain(previous)
+                .any(|key| verify_signature(key, signed, &signature))
+        };
+        if !valid {
+            return Err(ApiError::Unauthorized);
+        }
+
+        let claims: Claims = URL_SAFE_NO_PAD
+            .decode(body)
+            .ok()
+            .and_then(|body| serde_json::from_slice(&body).ok())
+            .ok_or(ApiError::Unauthorized)?;
+        if claims.iss != self.issuer || claims.exp <= now || claims.jti.is_empty() {
+            return Err(ApiError::Unauthorized);
+        }
+        if self.revoked.lock().unwrap().contains_key(&claims.jti) {
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(claims)
+    }
+
+    // client 为客户端 IP，用于锁定暴力破解。未提供凭据的请求不计入失败次数
+    pub fn authenticate(&self, bearer: Option<&str>, api_key: Option<&str>, client: &str) -> Result<Claims, ApiError> {
+        if let Some(ref security) = self.security {
+            if security.is_locked_out(API_CLIENT, client) {
+                return Err(ApiError::Locked);
+            }
+        }
+        let result = self.check_credentials(bearer, api_key);
+        if result.is_err() && (bearer.is_some() || api_key.is_some()) {
+            self.record_failure(client);
+        }
+        result
+    }
+
+    // 记录到审计日志，未设置安全管理器时不记录
+    pub fn audit(&self, operation: &str, user: &str, details: &str) {
+        if let Some(ref security) = self.security {
+            security.log_operation(operation, user, details);
+        }
+    }
+
+    fn record_failure(&self, client: &str) {
+        let Some(ref security) = self.security else {
+            return;
+        };
+        security.record_failed_attempt(API_CLIENT, client);
+        // 只在达到阈值的这一次记录，锁定期间的请求不再计数
+        if security.is_locked_out(API_CLIENT, client) {
+            log::warn!("客户端 {} 认证失败次数过多，已锁定", client);
+            security.log_operation("API_LOCKOUT", API_CLIENT, &format!("IP: {}", client));
+        }
+    }
+
+    // 优先使用 Bearer 令牌；静态 API 密钥视为 admin 权限，命名密钥按角色授权，审计日志中记录密钥名称
+    fn check_credentials(&self, bearer: Option<&str>, api_key: Option<&str>) -> Result<Claims, ApiError> {
+        if let Some(token) = bearer {
+            return self.verify(token.trim());
+        }
+        let Some(key) = api_key else {
+            return Err(ApiError::Unauthorized);
+        };
+        if let Some(ref expected) = self.api_key {
+            if constant_time_eq(key.as_bytes(), expected.as_bytes()) {
+                return Ok(self.static_claims(API_KEY_SUBJECT, vec![Scope::Admin]));
+            }
+        }
+        let digest = openssl::sha::sha256(key.as_bytes());
+        let keys = self.api_keys.read().unwrap();
+        let named = keys.iter().find(|named| constant_time_eq(&named.digest, &digest)).ok_or(ApiError::Unauthorized)?;
+        if named.config.revoked {
+            log::warn!("已吊销的 API 密钥 {} 尝试访问", named.config.name);
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(self.static_claims(&named.config.name, role_scopes(named.config.role)))
+    }
+
+    fn static_claims(&self, subject: &str, scopes: Vec<Scope>) -> Claims {
+        Claims {
+            sub: subject.to_string(),
+            iss: self.issuer.clone(),
+            scopes,
+            iat: 0,
+            exp: i64::MAX,
+            jti: String::new(),
+        }
+    }
+
+    pub fn list_keys(&self) -> Vec<ApiKeyInfo> {
+        self.api_keys.read().unwrap().iter().map(NamedKey::info).collect()
+    }
+
+    // 生成新密钥并保存摘要到 keys_file
+    pub fn create_key(&self, name: &str, role: ApiRole) -> Result<CreatedApiKey, anyhow::Error> {
+        let Some(ref keys_file) = self.keys_file else {
+            return Err(anyhow::anyhow!("未配置 keys_file，无法保存新密钥"));
+        };
+        if name.is_empty() || name == API_KEY_SUBJECT {
+            return Err(anyhow::anyhow!("无效的密钥名称: {:?}", name));
+        }
+        let key = format!("vsk_{}", hex::encode(generate_secret()));
+        let config = ApiKeyConfig {
+            name: name.to_string(),
+            key_sha256: hex::encode(openssl::sha::sha256(key.as_bytes())),
+            role,
+            revoked: false,
+        };
+
+        let mut keys = self.api_keys.write().unwrap();
+        if keys.iter().any(|named| named.config.name == name) {
+            return Err(anyhow::anyhow!("密钥 {} 已存在", name));
+        }
+        keys.extend(NamedKey::new(config, true));
+        if let Err(e) = save_keys_file(keys_file, &keys) {
+            keys.pop();
+            return Err(e);
+        }
+        log::info!("已创建 API 密钥 {} ({:?})", name, role);
+        Ok(CreatedApiKey {
+            name: name.to_string(),
+            role,
+            key,
+        })
+    }
+
+    // 吊销后该密钥的请求立即被拒绝。返回吊销后的密钥信息，密钥不存在时返回 None
+    pub fn revoke_key(&self, name: &str) -> Result<Option<ApiKeyInfo>, anyhow::Error> {
+        let mut keys = self.api_keys.write().unwrap();
+        let Some(index) = keys.iter().position(|named| named.config.name == name) else {
+            return Ok(None);
+        };
+        keys[index].config.revoked = true;
+        if keys[index].from_file {
+            if let Some(ref keys_file) = self.keys_file {
+                save_keys_file(keys_file, &keys)?;
+            }
+        } else {
+            log::warn!("API 密钥 {} 来自配置文件，重启前需要在配置文件中设置 revoked: true", name);
+        }
+        log::info!("已吊销 API 密钥 {}", name);
+        Ok(Some(keys[index].info()))
+    }
+
+    // 令牌到期前换取新令牌，主体和权限不变，旧令牌立即失效
+    pub fn refresh(&self, claims: &Claims) -> Result<IssuedToken, anyhow::Error> {
+        if claims.is_api_key() {
+            return Err(anyhow::anyhow!("静态 API 密钥不能刷新"));
+        }
+        let ttl = Duration::from_secs((claims.exp - claims.iat).max(1) as u64);
+        let token = self.issue(&claims.sub, claims.scopes.clone(), Some(ttl))?;
+        self.revoke(claims);
+        Ok(token)
+    }
+
+    pub fn revoke(&self, claims: &Claims) {
+        if claims.is_api_key() {
+            return;
+        }
+        let now = Utc::now().timestamp();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.retain(|_, exp| *exp > now);
+        revoked.insert(claims.jti.clone(), claims.exp);
+    }
+
+    // 生成新的签名密钥。旧密钥签发的令牌在最长有效期内仍然有效，之后需要重新签发
+    pub fn rotate_key(&self) -> Result<(), anyhow::Error> {
+        let secret = generate_secret();
+        if let Some(ref path) = self.secret_file {
+            write_secret(path, &secret)?;
+        }
+        let mut keys = self.keys.write().unwrap();
+        let old = std::mem::replace(&mut keys.current, secret);
+        keys.previous = Some((old, Utc::now().timestamp() + self.max_ttl.as_secs() as i64));
+        log::info!("令牌签名密钥已轮换");
+        Ok(())
+    }
+
+    pub fn max_ttl(&self) -> Duration {
+        self.max_ttl
+    }
+}
+
+fn generate_secret() -> Vec<u8> {
+    rand::thread_rng().gen::<[u8; SECRET_LEN]>().to_vec()
+}
+
+fn save_keys_file(path: &Path, keys: &[NamedKey]) -> Result<(), anyhow::Error> {
+    let file = KeysFile {
+        keys: keys.iter().filter(|named| named.from_file).map(|named| named.config.clone()).collect(),
+    };
+    write_private(path, serde_yaml::to_string(&file)?.as_bytes(), "API 密钥文件")
+}
+
+fn write_secret(path: &Path, secret: &[u8]) -> Result<(), anyhow::Error> {
+    write_private(path, secret, "令牌签名密钥")
+}
+
+// 以 0600 权限先写临时文件再改名，不会留下写了一半的文件
+fn write_private(path: &Path, data: &[u8], what: &str) -> Result<(), anyhow::Error> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .with_context(|| format!("无法写入{}: {:?}", what, tmp))?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("无法写入{}: {:?}", what, path))?;
+    Ok(())
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    Ok(signer.sign_to_vec()?)
+}
+
+fn verify_signature(key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    match sign(key, data) {
+        Ok(expected) => constant_time_eq(&expected, signature),
+        Err(_) => false,
+    }
+}
+
+// memcmp::eq 要求长度相同，长度不同时直接返回 false
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && openssl::memcmp::eq(a, b)
+}
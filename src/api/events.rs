@@ -0,0 +1,114 @@
+use crate::api::jobs::JobInfo;
+use crate::config::MonitorDaemonConfig;
+use crate::monitor::control;
+use crate::monitor::MonitorEvent;
+use crate::report::StoredThreat;
+use crate::update::UpdateEvent;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+// 客户端落后超过该数量的事件时丢弃最早的事件，客户端收到 lagged 事件
+const EVENT_BUFFER: usize = 1024;
+// 监控进程未运行或连接断开后重新订阅的间隔
+const MONITOR_RETRY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum LiveEvent {
+    Update(UpdateEvent),
+    Monitor(MonitorEvent),
+    ScanProgress(JobInfo),
+    // 扫描任务或实时监控发现的威胁
+    Threat(StoredThreat),
+}
+
+impl LiveEvent {
+    pub const KINDS: [&'static str; 4] = ["update", "monitor", "scan_progress", "threat"];
+
+    // 与序列化时的 type 字段相同，也用作 SSE 的事件名
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LiveEvent::Update(_) => "update",
+            LiveEvent::Monitor(_) => "monitor",
+            LiveEvent::ScanProgress(_) => "scan_progress",
+            LiveEvent::Threat(_) => "threat",
+        }
+    }
+}
+
+// 病毒库更新、监控事件、扫描进度和威胁的广播，/api/v1/events 的每个连接各自订阅
+pub struct EventHub {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    // 没有订阅者时事件直接丢弃
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    // 转发病毒库更新事件，返回的接收端收到同样的事件，交给原来的消费者 (如病毒库重新加载)
+    pub fn tee_updates(self: &Arc<Self>, mut events: mpsc::Receiver<UpdateEvent>) -> mpsc::Receiver<UpdateEvent> {
+        let (tx, rx) = mpsc::channel(16);
+        let hub = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                hub.publish(LiveEvent::Update(event.clone()));
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    // 监控运行在单独的进程中，通过控制套接字订阅其事件。监控进程未运行或重启时定期重新连接
+    pub fn relay_monitor_events(self: &Arc<Self>, daemon: MonitorDaemonConfig) -> tokio::task::JoinHandle<()> {
+        let hub = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                if control::running_pid(&daemon.pid_file).is_some() {
+                    match control::subscribe(&daemon.control_socket).await {
+                        Ok(mut subscription) => {
+                            log::info!("已订阅监控事件");
+                            loop {
+                                match subscription.next().await {
+                                    Ok(Some(event)) => hub.publish(LiveEvent::Monitor(event)),
+                                    Ok(None) => break,
+                                    Err(e) => {
+                                        log::warn!("监控事件流中断: {:#}", e);
+                                        break;
+                                    }
+                                }
+                            }
+                            log::info!("监控事件订阅已结束");
+                        }
+                        Err(e) => log::debug!("无法订阅监控事件: {:#}", e),
+                    }
+                }
+                tokio::time::sleep(MONITOR_RETRY).await;
+            }
+        })
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
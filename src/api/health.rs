@@ -0,0 +1,246 @@
+use crate::config::{HealthCheckConfig, MonitorDaemonConfig};
+use crate::monitor::{control, MonitorLiveness};
+use crate::scanner::SignatureDatabase;
+use crate::utils::disk::available_space;
+use crate::utils::format_bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct HealthCheck {
+    // signature_database、directory:<名称>、disk_space 或 monitor
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct HealthReport {
+    // 所有检查项中最差的状态
+    pub status: CheckStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.status != CheckStatus::Fail
+    }
+}
+
+enum MonitorSource {
+    // 同一进程中运行的监控
+    InProcess(MonitorLiveness),
+    // 单独运行的监控进程，通过控制套接字查询
+    Daemon(MonitorDaemonConfig),
+}
+
+// /health 的各项检查。未设置的项不检查
+pub struct HealthChecker {
+    config: HealthCheckConfig,
+    signature_db: Option<(Arc<SignatureDatabase>, PathBuf)>,
+    directories: Vec<(String, PathBuf)>,
+    monitor: Option<MonitorSource>,
+}
+
+impl HealthChecker {
+    pub fn new(config: HealthCheckConfig) -> Self {
+        Self {
+            config,
+            signature_db: None,
+            directories: Vec::new(),
+            monitor: None,
+        }
+    }
+
+    // database_path 为本地病毒库目录，没有 CVD 文件头时按其中文件的修改时间判断是否过期
+    pub fn set_signature_database(&mut self, signature_db: Arc<SignatureDatabase>, database_path: PathBuf) {
+        self.signature_db = Some((signature_db, database_path));
+    }
+
+    // 检查目录可写，并检查所在文件系统的可用空间
+    pub fn add_directory(&mut self, name: &str, path: PathBuf) {
+        self.directories.push((name.to_string(), path));
+    }
+
+    pub fn set_monitor_liveness(&mut self, liveness: MonitorLiveness) {
+        self.monitor = Some(MonitorSource::InProcess(liveness));
+    }
+
+    pub fn set_monitor_daemon(&mut self, daemon: MonitorDaemonConfig) {
+        self.monitor = Some(MonitorSource::Daemon(daemon));
+    }
+
+    pub async fn run(&self) -> HealthReport {
+        let mut checks = Vec::new();
+        if let Some((ref signature_db, ref database_path)) = self.signature_db {
+            checks.push(self.check_signature_database(signature_db, database_path).await);
+        }
+        for (name, path) in &self.directories {
+            checks.push(check_writable(name, path));
+        }
+        if let Some(check) = self.check_disk_space() {
+            checks.push(check);
+        }
+        if let Some(ref monitor) = self.monitor {
+            checks.push(self.check_monitor(monitor).await);
+        }
+
+        let status = checks.iter().map(|check| check.status).max().unwrap_or(CheckStatus::Pass);
+        HealthReport { status, checks }
+    }
+
+    async fn check_signature_database(&self, signature_db: &SignatureDatabase, database_path: &PathBuf) -> HealthCheck {
+        let count = signature_db.get_signature_count().await;
+        let built = signature_db
+            .get_database_headers()
+            .values()
+            .filter_map(|header| header.build_timestamp)
+            .max()
+            .and_then(|secs| DateTime::from_timestamp(secs as i64, 0))
+            .or_else(|| newest_modification(database_path));
+        let Some(built) = built else {
+            return HealthCheck::new("signature_database", CheckStatus::Fail, format!("未加载本地病毒库 {:?}，只有内置特征码", database_path));
+        };
+        if count == 0 {
+            return HealthCheck::new("signature_database", CheckStatus::Fail, "病毒库中没有特征码".to_string());
+        }
+
+        let age = Utc::now().signed_duration_since(built);
+        let message = format!("特征码 {} 条，版本 {}，构建于 {}", count, signature_db.get_version(), built.to_rfc3339());
+        let max_age = self.config.max_database_age_hours;
+        if max_age > 0 && age > chrono::Duration::hours(max_age as i64) {
+            return HealthCheck::new(
+                "signature_database",
+                CheckStatus::Fail,
+                format!("病毒库已过期 ({} 小时未更新，上限 {} 小时)，{}", age.num_hours(), max_age, message),
+            );
+        }
+        HealthCheck::new("signature_database", CheckStatus::Pass, message)
+    }
+
+    // 同一文件系统上的目录只检查一次
+    fn check_disk_space(&self) -> Option<HealthCheck> {
+        let mut paths: Vec<&PathBuf> = self.directories.iter().map(|(_, path)| path).collect();
+        if let Some((_, ref database_path)) = self.signature_db {
+            paths.push(database_path);
+        }
+        if paths.is_empty() {
+            return None;
+        }
+
+        let minimum = self.config.min_free_space_mb.saturating_mul(1024 * 1024);
+        let mut status = CheckStatus::Pass;
+        let mut details = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for path in paths {
+            if let Some(device) = device_of(path) {
+                if !seen.insert(device) {
+                    continue;
+                }
+            }
+            match available_space(path) {
+                Ok(available) => {
+                    let level = if available < minimum {
+                        CheckStatus::Fail
+                    } else if available < minimum.saturating_mul(2) {
+                        CheckStatus::Warn
+                    } else {
+                        CheckStatus::Pass
+                    };
+                    status = status.max(level);
+                    details.push(format!("{:?} 可用 {}", path, format_bytes(available)));
+                }
+                Err(e) => {
+                    status = CheckStatus::Fail;
+                    details.push(e.to_string());
+                }
+            }
+        }
+        Some(HealthCheck::new(
+            "disk_space",
+            status,
+            format!("{} (最小 {})", details.join("; "), format_bytes(minimum)),
+        ))
+    }
+
+    async fn check_monitor(&self, monitor: &MonitorSource) -> HealthCheck {
+        let last_heartbeat = match monitor {
+            MonitorSource::InProcess(liveness) => {
+                if !liveness.is_running() {
+                    return HealthCheck::new("monitor", CheckStatus::Fail, "文件监控未运行".to_string());
+                }
+                liveness.last_heartbeat()
+            }
+            MonitorSource::Daemon(daemon) => match control::query_status(daemon).await {
+                Ok(Some(status)) => match status.last_heartbeat {
+                    Some(heartbeat) => Some(heartbeat),
+                    None => {
+                        return HealthCheck::new("monitor", CheckStatus::Warn, format!("监控进程 {} 未报告心跳", status.pid));
+                    }
+                },
+                Ok(None) => return HealthCheck::new("monitor", CheckStatus::Fail, "监控进程未运行".to_string()),
+                Err(e) => return HealthCheck::new("monitor", CheckStatus::Fail, format!("无法查询监控进程: {:#}", e)),
+            },
+        };
+
+        let stall = chrono::Duration::seconds(self.config.monitor_stall_secs.max(1) as i64);
+        match last_heartbeat {
+            Some(heartbeat) if Utc::now().signed_duration_since(heartbeat) <= stall => {
+                HealthCheck::new("monitor", CheckStatus::Pass, format!("最近心跳 {}", heartbeat.to_rfc3339()))
+            }
+            Some(heartbeat) => HealthCheck::new("monitor", CheckStatus::Fail, format!("监控线程已停止响应，最近心跳 {}", heartbeat.to_rfc3339())),
+            None => HealthCheck::new("monitor", CheckStatus::Fail, "监控线程没有心跳".to_string()),
+        }
+    }
+}
+
+impl HealthCheck {
+    fn new(name: &str, status: CheckStatus, message: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+// 创建并删除一个临时文件，不存在的目录视为不可写
+fn check_writable(name: &str, path: &PathBuf) -> HealthCheck {
+    let name = format!("directory:{}", name);
+    if !path.is_dir() {
+        return HealthCheck::new(&name, CheckStatus::Fail, format!("目录不存在: {:?}", path));
+    }
+    match tempfile::tempfile_in(path) {
+        Ok(_) => HealthCheck::new(&name, CheckStatus::Pass, format!("{:?} 可写", path)),
+        Err(e) => HealthCheck::new(&name, CheckStatus::Fail, format!("{:?} 不可写: {}", path, e)),
+    }
+}
+
+fn device_of(path: &PathBuf) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    std::fs::metadata(existing).ok().map(|metadata| metadata.dev())
+}
+
+fn newest_modification(dir: &PathBuf) -> Option<DateTime<Utc>> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map_or(false, |kind| kind.is_file()))
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+        .map(DateTime::<Utc>::from)
+}
@@ -0,0 +1,420 @@
+use crate::api::events::{EventHub, LiveEvent};
+use crate::core::alerts::AlertDispatcher;
+use crate::core::security::QuarantineManager;
+use crate::report::{DetectionLogger, StoredThreat, ThreatReport, ThreatStore};
+use crate::scanner::engine::ScanProgress;
+use crate::scanner::{persistence_locations, Allowlist, ScanControl, ScanMode, ScanOptions, ScanResult, ScanState, ScannerEngine, SignatureDatabase, VerdictCache};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, Semaphore};
+
+// 保留的已结束任务数，超出后删除最早结束的任务
+const MAX_FINISHED_JOBS: usize = 100;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Cancelled | JobStatus::Failed)
+    }
+}
+
+// 提交扫描任务时的参数，未指定的项使用配置文件中的值
+#[derive(Debug, Clone, Default)]
+pub struct JobRequest {
+    pub scan_mode: Option<ScanMode>,
+    pub paths: Vec<PathBuf>,
+    pub exclude_paths: Vec<PathBuf>,
+    pub thread_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct JobInfo {
+    pub scan_id: String,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub files_scanned: usize,
+    pub files_total: usize,
+    // 目录遍历尚未结束时总数只是目前发现的文件数
+    pub total_is_estimate: bool,
+    pub percent: f64,
+    pub bytes_scanned: usize,
+    pub current_path: Option<String>,
+    pub eta_seconds: Option<u64>,
+    pub threats_found: usize,
+    pub error: Option<String>,
+}
+
+struct JobState {
+    status: JobStatus,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    progress: Option<ScanProgress>,
+    results: Option<Vec<ScanResult>>,
+    error: Option<String>,
+}
+
+pub struct ScanJob {
+    id: String,
+    created_at: DateTime<Utc>,
+    control: ScanControl,
+    // 排队中的任务被取消时唤醒等待
+    cancelled: Notify,
+    state: Mutex<JobState>,
+}
+
+impl ScanJob {
+    fn new(id: String) -> Self {
+        Self {
+            id,
+            created_at: Utc::now(),
+            control: ScanControl::new(),
+            cancelled: Notify::new(),
+            state: Mutex::new(JobState {
+                status: JobStatus::Queued,
+                started_at: None,
+                finished_at: None,
+                progress: None,
+                results: None,
+                error: None,
+            }),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn info(&self) -> JobInfo {
+        let state = self.state.lock().unwrap();
+        let status = match state.status {
+            JobStatus::Running if self.control.state() == ScanState::Paused => JobStatus::Paused,
+            status => status,
+        };
+        let progress = state.progress.as_ref();
+        JobInfo {
+            scan_id: self.id.clone(),
+            status,
+            created_at: self.created_at,
+            started_at: state.started_at,
+            finished_at: state.finished_at,
+            files_scanned: progress.map(|p| p.files_done).unwrap_or(0),
+            files_total: progress.map(|p| p.files_total).unwrap_or(0),
+            total_is_estimate: progress.map(|p| p.total_is_estimate).unwrap_or(true),
+            percent: match status {
+                JobStatus::Completed => 100.0,
+                _ => progress.map(|p| p.percent).unwrap_or(0.0),
+            },
+            bytes_scanned: progress.map(|p| p.bytes_scanned).unwrap_or(0),
+            current_path: progress.and_then(|p| p.current_path.as_ref()).map(|p| p.display().to_string()),
+            eta_seconds: progress.and_then(|p| p.eta).map(|eta| eta.as_secs()),
+            threats_found: state.results.as_ref().map(|r| r.len()).unwrap_or(0),
+            error: state.error.clone(),
+        }
+    }
+
+    // 任务结束前返回 None；取消的任务返回取消前已完成部分的结果
+    pub fn results(&self) -> Option<Vec<ScanResult>> {
+        let state = self.state.lock().unwrap();
+        if !state.status.is_finished() {
+            return None;
+        }
+        Some(state.results.clone().unwrap_or_default())
+    }
+
+    pub fn control(&self) -> &ScanControl {
+        &self.control
+    }
+
+    // 返回 false 表示任务已经结束或已取消
+    pub fn cancel(&self) -> bool {
+        if self.state.lock().unwrap().status.is_finished() {
+            return false;
+        }
+        let accepted = self.control.cancel();
+        self.cancelled.notify_one();
+        accepted
+    }
+
+    fn finish(&self, status: JobStatus, results: Option<Vec<ScanResult>>, error: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.status = status;
+        state.finished_at = Some(Utc::now());
+        if results.is_some() {
+            state.results = results;
+        }
+        state.error = error;
+    }
+}
+
+// 后台扫描任务队列。任务按提交顺序获得执行许可，同时运行的扫描数受 max_concurrent 限制
+pub struct ScanJobManager {
+    signature_db: Arc<SignatureDatabase>,
+    base_options: std::sync::RwLock<ScanOptions>,
+    allowlist: Option<Arc<Allowlist>>,
+    quarantine: Option<Arc<QuarantineManager>>,
+    verdict_cache: Option<Arc<VerdictCache>>,
+    // 设置后任务状态变化和扫描进度发布到事件流
+    event_hub: Option<Arc<EventHub>>,
+    threat_store: Option<Arc<ThreatStore>>,
+    detection_logger: Option<Arc<DetectionLogger>>,
+    alerts: Option<Arc<AlertDispatcher>>,
+    permits: Arc<Semaphore>,
+    jobs: Mutex<HashMap<String, Arc<ScanJob>>>,
+}
+
+impl ScanJobManager {
+    pub fn new(signature_db: Arc<SignatureDatabase>, base_options: ScanOptions, max_concurrent: usize) -> Self {
+        Self {
+            signature_db,
+            base_options: std::sync::RwLock::new(base_options),
+            allowlist: None,
+            quarantine: None,
+            verdict_cache: None,
+            event_hub: None,
+            threat_store: None,
+            detection_logger: None,
+            alerts: None,
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_allowlist(&mut self, allowlist: Arc<Allowlist>) {
+        self.allowlist = Some(allowlist);
+    }
+
+    pub fn set_quarantine_manager(&mut self, quarantine: Arc<QuarantineManager>) {
+        self.quarantine = Some(quarantine);
+    }
+
+    pub fn set_verdict_cache(&mut self, cache: Arc<VerdictCache>) {
+        self.verdict_cache = Some(cache);
+    }
+
+    pub fn set_event_hub(&mut self, event_hub: Arc<EventHub>) {
+        self.event_hub = Some(event_hub);
+    }
+
+    // 配置变更后调用，只影响之后提交的任务
+    pub fn set_base_options(&self, options: ScanOptions) {
+        *self.base_options.write().unwrap() = options;
+    }
+
+    // 任务发现的威胁以任务 ID 记录
+    pub fn set_threat_store(&mut self, threat_store: Arc<ThreatStore>) {
+        self.threat_store = Some(threat_store);
+    }
+
+    // 任务发现的威胁以任务 ID 写入检测日志
+    pub fn set_detection_logger(&mut self, detection_logger: Arc<DetectionLogger>) {
+        self.detection_logger = Some(detection_logger);
+    }
+
+    // 任务发现威胁时发送告警，同时发布到事件流
+    pub fn set_alert_dispatcher(&mut self, alerts: Arc<AlertDispatcher>) {
+        self.alerts = Some(alerts);
+    }
+
+    pub fn options_for(&self, request: &JobRequest) -> Result<ScanOptions, anyhow::Error> {
+        let mut options = self.base_options.read().unwrap().clone();
+        let scan_mode = request.scan_mode.unwrap_or(ScanMode::Custom);
+        options.scan_mode = scan_mode;
+        options.custom_paths = match scan_mode {
+            ScanMode::Custom if request.paths.is_empty() => {
+                return Err(anyhow::anyhow!("自定义扫描需要指定路径"));
+            }
+            ScanMode::Custom => request.paths.clone(),
+            ScanMode::Quick => options.quick_scan_paths.clone(),
+            ScanMode::Full => vec![PathBuf::from("/")],
+            ScanMode::Persistence => persistence_locations(),
+        };
+        if let Some(path) = request.paths.iter().find(|p| !p.is_absolute()) {
+            return Err(anyhow::anyhow!("扫描路径必须是绝对路径: {:?}", path));
+        }
+        if !request.exclude_paths.is_empty() {
+            options.exclude_paths = request.exclude_paths.clone();
+        }
+        if let Some(threads) = request.thread_count {
+            options.thread_count = threads.max(1);
+        }
+        Ok(options)
+    }
+
+    // 需要在 tokio 运行时中调用。立即返回任务，扫描在后台进行
+    pub fn submit(&self, request: &JobRequest) -> Result<Arc<ScanJob>, anyhow::Error> {
+        let options = self.options_for(request)?;
+
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            Self::prune(&mut jobs);
+            let id = loop {
+                let id = format!("SCN{:08}", rand::thread_rng().gen::<u32>());
+                if !jobs.contains_key(&id) {
+                    break id;
+                }
+            };
+            let job = Arc::new(ScanJob::new(id.clone()));
+            jobs.insert(id, Arc::clone(&job));
+            job
+        };
+
+        let mut engine = ScannerEngine::new(Arc::clone(&self.signature_db), options);
+        engine.set_scan_control(job.control.clone());
+        engine.set_progress_interval(PROGRESS_INTERVAL);
+        if let Some(allowlist) = &self.allowlist {
+            engine.set_allowlist(Arc::clone(allowlist));
+        }
+        if let Some(quarantine) = &self.quarantine {
+            engine.set_quarantine_manager(Arc::clone(quarantine));
+        }
+        if let Some(cache) = &self.verdict_cache {
+            engine.set_verdict_cache(Arc::clone(cache));
+        }
+        let progress_job = Arc::clone(&job);
+        let progress_hub = self.event_hub.clone();
+        engine.set_progress_callback(move |progress| {
+            progress_job.state.lock().unwrap().progress = Some(progress.clone());
+            publish(&progress_hub, &progress_job);
+        });
+
+        let permits = Arc::clone(&self.permits);
+        let task_job = Arc::clone(&job);
+        let hub = self.event_hub.clone();
+        let threat_store = self.threat_store.clone();
+        let detection_logger = self.detection_logger.clone();
+        let alerts = self.alerts.clone();
+        publish(&hub, &job);
+        tokio::spawn(async move {
+            let job = task_job;
+            let _permit = tokio::select! {
+                permit = permits.acquire_owned() => match permit {
+                    Ok(permit) => permit,
+                    Err(_) => return,
+                },
+                _ = job.cancelled.notified() => {
+                    job.finish(JobStatus::Cancelled, None, None);
+                    publish(&hub, &job);
+                    return;
+                }
+            };
+            if job.control.is_cancelled() {
+                job.finish(JobStatus::Cancelled, None, None);
+                publish(&hub, &job);
+                return;
+            }
+
+            {
+                let mut state = job.state.lock().unwrap();
+                state.status = JobStatus::Running;
+                state.started_at = Some(Utc::now());
+            }
+            publish(&hub, &job);
+            log::info!("扫描任务 {} 开始", job.id);
+            match engine.start_scan().await {
+                Ok(results) => {
+                    let status = if job.control.is_cancelled() { JobStatus::Cancelled } else { JobStatus::Completed };
+                    log::info!("扫描任务 {} 结束: {:?}，发现 {} 个威胁", job.id, status, results.len());
+                    if !results.is_empty() && (threat_store.is_some() || detection_logger.is_some() || alerts.is_some() || hub.is_some()) {
+                        let scan_id = job.id.clone();
+                        let detections = results.clone();
+                        // 计算文件摘要需要读取文件
+                        let stored = tokio::task::spawn_blocking(move || {
+                            let threats: Vec<ThreatReport> = detections
+                                .iter()
+                                .map(|result| ThreatReport::from_result(String::new(), result, true))
+                                .collect();
+                            if let Some(detection_logger) = detection_logger {
+                                detection_logger.log_threats(&scan_id, &threats);
+                            }
+                            match threat_store {
+                                Some(store) => store.record_or_warn(&scan_id, "api", &threats),
+                                None => threats.iter().map(|threat| StoredThreat::new(&scan_id, "api", threat)).collect(),
+                            }
+                        })
+                        .await
+                        .unwrap_or_default();
+                        if let Some(ref alerts) = alerts {
+                            alerts.notify(&stored);
+                        }
+                        if let Some(ref hub) = hub {
+                            for threat in stored {
+                                hub.publish(LiveEvent::Threat(threat));
+                            }
+                        }
+                    }
+                    job.finish(status, Some(results), None);
+                }
+                Err(e) => {
+                    log::warn!("扫描任务 {} 失败: {:#}", job.id, e);
+                    job.finish(JobStatus::Failed, None, Some(format!("{:#}", e)));
+                }
+            }
+            publish(&hub, &job);
+        });
+
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<ScanJob>> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    // 按创建时间从新到旧排列
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.lock().unwrap().values().map(|job| job.info()).collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| !job.state.lock().unwrap().status.is_finished())
+            .count()
+    }
+
+    fn prune(jobs: &mut HashMap<String, Arc<ScanJob>>) {
+        let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+            .values()
+            .filter_map(|job| {
+                let state = job.state.lock().unwrap();
+                state.finished_at.filter(|_| state.status.is_finished()).map(|at| (at, job.id.clone()))
+            })
+            .collect();
+        if finished.len() < MAX_FINISHED_JOBS {
+            return;
+        }
+        finished.sort();
+        for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+            jobs.remove(id);
+        }
+    }
+}
+
+fn publish(hub: &Option<Arc<EventHub>>, job: &ScanJob) {
+    if let Some(hub) = hub {
+        hub.publish(LiveEvent::ScanProgress(job.info()));
+    }
+}
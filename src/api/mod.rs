@@ -1,10 +1,33 @@
+pub mod auth;
+pub mod events;
+pub mod health;
+pub mod jobs;
+pub mod openapi;
+pub mod settings;
+pub mod updates;
+
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use warp::{Filter, Rejection, Reply};
 use rand::Rng;
+use crate::config::{ApiRateLimitConfig, ApiRole, MonitorDaemonConfig};
+use crate::monitor::control::{self, ControlRequest};
+use crate::monitor::MonitorStatus;
+use crate::report::{ThreatQuery, ThreatStore};
+use crate::scanner::{Allowlist, ScanControl, ScanMode, ScanResult, ScanState};
+use auth::{Claims, IssuedToken, Scope, TokenAuthority};
+use events::{EventHub, LiveEvent};
+use jobs::{JobInfo, JobRequest, ScanJobManager};
+use health::{CheckStatus, HealthChecker, HealthReport};
+use settings::ConfigManager;
+use updates::UpdateJobManager;
+use crate::utils::{format_duration, KeyedRateLimiter};
+
+// 限流表超过该数量的客户端时清理已回满的桶
+const RATE_LIMIT_CLEANUP_THRESHOLD: usize = 4096;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiResponse<T> {
@@ -15,6 +38,7 @@ pub struct ApiResponse<T> {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct ScanRequest {
     pub scan_type: String,
     pub paths: Vec<String>,
@@ -24,6 +48,7 @@ pub struct ScanRequest {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct ScanResponse {
     pub scan_id: String,
     pub status: String,
@@ -31,23 +56,84 @@ pub struct ScanResponse {
     pub files_scanned: usize,
     pub scan_speed_mb_s: f64,
     pub duration_seconds: f64,
+    pub duration: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UpdateRequest {
-    pub force: Option<bool>,
-    pub check_only: Option<bool>,
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ScanResultsResponse {
+    pub job: JobInfo,
+    pub results: Vec<ScanResult>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UpdateResponse {
-    pub success: bool,
-    pub version: String,
-    pub signatures_added: u32,
-    pub signatures_removed: u32,
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ScanControlResponse {
+    pub action: String,
+    pub accepted: bool,
+    pub state: ScanState,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct AllowlistRequest {
+    pub sha256: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct AllowlistResponse {
+    pub sha256: Vec<String>,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct WatchRequest {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct WatchListResponse {
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventStreamQuery {
+    // 逗号分隔的事件类型 (update、monitor、scan_progress、threat)，未指定时推送全部
+    pub types: Option<String>,
+    // 浏览器的 EventSource 不能设置请求头，事件流也接受查询参数中的令牌
+    pub access_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct TokenRequest {
+    pub subject: String,
+    pub scopes: Vec<Scope>,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub role: ApiRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct UpdateRequest {
+    pub force: Option<bool>,
+    pub check_only: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct StatusResponse {
     pub scanner_status: String,
     pub database_version: String,
@@ -56,144 +142,1055 @@ pub struct StatusResponse {
     pub last_scan: Option<String>,
     pub last_update: Option<String>,
     pub active_scans: usize,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ThreatInfo {
-    pub id: String,
-    pub file_path: String,
-    pub threat_type: String,
-    pub risk_level: String,
-    pub signature_id: String,
+    // 监控未运行时为 None
+    pub monitor: Option<MonitorStatus>,
 }
 
 pub struct ApiServer {
     addr: SocketAddr,
-    api_key: String,
+    auth: Arc<TokenAuthority>,
+    scan_control: ScanControl,
+    allowlist: Arc<Allowlist>,
+    monitor_daemon: MonitorDaemonConfig,
+    scan_jobs: Option<Arc<ScanJobManager>>,
+    event_hub: Option<Arc<EventHub>>,
+    update_jobs: Option<Arc<UpdateJobManager>>,
+    threat_store: Option<Arc<ThreatStore>>,
+    config_manager: Option<Arc<ConfigManager>>,
+    health_checker: Option<Arc<HealthChecker>>,
+    rate_limiter: Option<Arc<KeyedRateLimiter<IpAddr>>>,
 }
 
 impl ApiServer {
-    pub fn new(addr: SocketAddr, api_key: String) -> Self {
-        Self { addr, api_key }
+    pub fn new(addr: SocketAddr, auth: Arc<TokenAuthority>) -> Self {
+        Self {
+            addr,
+            auth,
+            scan_control: ScanControl::new(),
+            allowlist: Arc::new(Allowlist::new()),
+            monitor_daemon: MonitorDaemonConfig::default(),
+            scan_jobs: None,
+            event_hub: None,
+            update_jobs: None,
+            threat_store: None,
+            config_manager: None,
+            health_checker: None,
+            rate_limiter: None,
+        }
+    }
+
+    // 按客户端 IP 限制请求速率，超出时返回 429
+    pub fn with_rate_limit(mut self, config: &ApiRateLimitConfig) -> Self {
+        self.rate_limiter = (config.requests_per_sec > 0).then(|| {
+            Arc::new(KeyedRateLimiter::new(
+                config.burst.max(config.requests_per_sec),
+                config.requests_per_sec as f64,
+            ))
+        });
+        self
+    }
+
+    pub fn with_scan_control(mut self, handle: ScanControl) -> Self {
+        self.scan_control = handle;
+        self
+    }
+
+    pub fn with_allowlist(mut self, allowlist: Arc<Allowlist>) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
+    // 未设置时 POST /api/v1/scan 返回错误
+    pub fn with_scan_jobs(mut self, scan_jobs: Arc<ScanJobManager>) -> Self {
+        self.scan_jobs = Some(scan_jobs);
+        self
+    }
+
+    // 未设置时 /api/v1/config 返回错误
+    pub fn with_config_manager(mut self, config_manager: Arc<ConfigManager>) -> Self {
+        self.config_manager = Some(config_manager);
+        self
+    }
+
+    // 未设置时 /health 不做任何检查，只表示服务在运行
+    pub fn with_health_checker(mut self, health_checker: Arc<HealthChecker>) -> Self {
+        self.health_checker = Some(health_checker);
+        self
+    }
+
+    // 未设置时 /api/v1/threats 返回错误
+    pub fn with_threat_store(mut self, threat_store: Arc<ThreatStore>) -> Self {
+        self.threat_store = Some(threat_store);
+        self
+    }
+
+    // 未设置时病毒库更新接口返回错误
+    pub fn with_update_jobs(mut self, update_jobs: Arc<UpdateJobManager>) -> Self {
+        self.update_jobs = Some(update_jobs);
+        self
+    }
+
+    // 未设置时 /api/v1/events 返回错误；启动后同时转发监控进程的事件
+    pub fn with_event_hub(mut self, event_hub: Arc<EventHub>) -> Self {
+        self.event_hub = Some(event_hub);
+        self
+    }
+
+    // 监控目录接口通过该控制套接字转发给运行中的监控进程
+    pub fn with_monitor_daemon(mut self, monitor_daemon: MonitorDaemonConfig) -> Self {
+        self.monitor_daemon = monitor_daemon;
+        self
     }
 
     pub async fn start<T>(&self, state: Arc<T>) -> Result<(), anyhow::Error>
     where
         T: Clone + Send + Sync + 'static,
     {
-        let api_key = self.api_key.clone();
+        self.start_until(state, std::future::pending()).await
+    }
+
+    // shutdown 完成后停止监听并返回，未完成的请求直接断开 (事件流不会自行结束)
+    pub async fn start_until<T, F>(&self, state: Arc<T>, shutdown: F) -> Result<(), anyhow::Error>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.bind(state, shutdown)?.await;
+        Ok(())
+    }
+
+    // 监听失败时立即返回错误，返回的 future 运行服务器直到 shutdown 完成
+    pub fn bind<T, F>(
+        &self,
+        state: Arc<T>,
+        shutdown: F,
+    ) -> Result<impl std::future::Future<Output = ()> + Send + 'static, anyhow::Error>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
         let state = Arc::clone(&state);
 
         let log = warp::log("virus_scanner::api");
 
-        let routes = Self::routes(state, api_key)
-            .or(Self::health_routes())
+        // 业务路由的过滤器类型嵌套很深，装箱后再和限流器组合，避免超出编译器的类型深度限制
+        let api_routes = Self::routes(
+            state,
+            Arc::clone(&self.auth),
+            self.scan_control.clone(),
+            Arc::clone(&self.allowlist),
+            self.monitor_daemon.clone(),
+            self.scan_jobs.clone(),
+            self.event_hub.clone(),
+            self.update_jobs.clone(),
+            self.threat_store.clone(),
+            self.config_manager.clone(),
+        )
+        .boxed();
+        let routes = Self::rate_limit(self.rate_limiter.clone())
+            .and(api_routes)
+            .or(Self::health_routes(self.health_checker.clone()))
+            .or(Self::metrics_routes(self.monitor_daemon.clone()))
+            .or(Self::docs_routes())
+            .recover(Self::handle_rejection)
             .with(log);
 
-        log::info!("API服务器启动，监听: {}", self.addr);
-        warp::serve(routes).run(self.addr).await;
+        let (bound, server) = warp::serve(routes)
+            .try_bind_ephemeral(self.addr)
+            .with_context(|| format!("API服务器无法监听 {}", self.addr))?;
+        log::info!("API服务器启动，监听: {}", bound);
+        let monitor_relay = self
+            .event_hub
+            .as_ref()
+            .map(|hub| hub.relay_monitor_events(self.monitor_daemon.clone()));
 
-        Ok(())
+        Ok(async move {
+            tokio::select! {
+                _ = server => {}
+                _ = shutdown => log::info!("API服务器已停止"),
+            }
+            if let Some(relay) = monitor_relay {
+                relay.abort();
+            }
+        })
     }
 
     fn routes<T>(
         state: Arc<T>,
-        api_key: String,
-    ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone
+        auth: Arc<TokenAuthority>,
+        scan_control: ScanControl,
+        allowlist: Arc<Allowlist>,
+        monitor_daemon: MonitorDaemonConfig,
+        scan_jobs: Option<Arc<ScanJobManager>>,
+        event_hub: Option<Arc<EventHub>>,
+        update_jobs: Option<Arc<UpdateJobManager>>,
+        threat_store: Option<Arc<ThreatStore>>,
+        config_manager: Option<Arc<ConfigManager>>,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
     where
         T: Clone + Send + Sync + 'static,
     {
         let state_filter = warp::any().map(move || state.clone());
-        let auth_filter = warp::header::optional("X-API-Key")
-            .and(warp::any().map(move || api_key.clone()))
-            .and_then(|key: Option<String>, expected_key: String| async move {
-                if key.as_ref() == Some(&expected_key) {
-                    Ok::<_, Rejection>(())
-                } else {
-                    Err(warp::reject::custom(ApiError::Unauthorized))
-                }
-            });
-
+        let jobs_filter = warp::any().map(move || scan_jobs.clone()).and_then(|jobs: Option<Arc<ScanJobManager>>| async move {
+            jobs.ok_or_else(|| warp::reject::custom(ApiError::InternalError("扫描任务不可用".to_string())))
+        });
         let scan_routes = warp::path!("api" / "v1" / "scan")
             .and(warp::post())
             .and(warp::body::json())
-            .and(state_filter.clone())
-            .and(auth_filter.clone())
+            .and(jobs_filter.clone())
+            .and(Self::require(&auth, Scope::Scan))
             .and_then(Self::handle_scan);
 
+        let job_list = warp::path!("api" / "v1" / "scan")
+            .and(warp::get())
+            .and(jobs_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_job_list);
+
+        let job_progress = warp::path!("api" / "v1" / "scan" / String)
+            .and(warp::get())
+            .and(jobs_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_job_progress);
+
+        let job_results = warp::path!("api" / "v1" / "scan" / String / "results")
+            .and(warp::get())
+            .and(jobs_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_job_results);
+
+        let job_cancel = warp::path!("api" / "v1" / "scan" / String)
+            .and(warp::delete())
+            .and(jobs_filter)
+            .and(Self::require(&auth, Scope::Scan))
+            .and_then(Self::handle_job_cancel);
+
+        let control_routes = warp::path!("api" / "v1" / "scan" / String)
+            .and(warp::post())
+            .and(warp::any().map(move || scan_control.clone()))
+            .and(Self::require(&auth, Scope::Scan))
+            .and_then(Self::handle_scan_control);
+
+        let allowlist_filter = warp::any().map(move || Arc::clone(&allowlist));
+        let allowlist_list = warp::path!("api" / "v1" / "allowlist")
+            .and(warp::get())
+            .and(allowlist_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_allowlist_list);
+
+        let allowlist_modify = warp::path!("api" / "v1" / "allowlist")
+            .and(warp::post().map(|| true).or(warp::delete().map(|| false)).unify())
+            .and(warp::body::json())
+            .and(allowlist_filter)
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_allowlist_modify);
+
+        let monitor_filter = warp::any().map(move || monitor_daemon.clone());
+        let watch_list = warp::path!("api" / "v1" / "monitor" / "watches")
+            .and(warp::get())
+            .and(monitor_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_watch_list);
+
+        let watch_modify = warp::path!("api" / "v1" / "monitor" / "watches")
+            .and(warp::post().map(|| true).or(warp::delete().map(|| false)).unify())
+            .and(warp::body::json())
+            .and(monitor_filter.clone())
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_watch_modify);
+
+        let updates_filter = warp::any().map(move || update_jobs.clone()).and_then(|jobs: Option<Arc<UpdateJobManager>>| async move {
+            jobs.ok_or_else(|| warp::reject::custom(ApiError::InternalError("病毒库更新不可用".to_string())))
+        });
         let update_routes = warp::path!("api" / "v1" / "update")
             .and(warp::post())
             .and(warp::body::json())
-            .and(state_filter.clone())
-            .and(auth_filter.clone())
+            .and(updates_filter.clone())
+            .and(Self::require(&auth, Scope::Update))
             .and_then(Self::handle_update);
 
+        let update_status = warp::path!("api" / "v1" / "update" / "status")
+            .and(warp::get())
+            .and(updates_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_update_status);
+
+        let update_history = warp::path!("api" / "v1" / "update" / "history")
+            .and(warp::get())
+            .and(updates_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_update_history);
+
+        let update_jobs_list = warp::path!("api" / "v1" / "update" / "jobs")
+            .and(warp::get())
+            .and(updates_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_update_jobs);
+
+        let update_job = warp::path!("api" / "v1" / "update" / "jobs" / String)
+            .and(warp::get())
+            .and(updates_filter)
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_update_job);
+
         let status_routes = warp::path!("api" / "v1" / "status")
             .and(warp::get())
             .and(state_filter.clone())
-            .and(auth_filter.clone())
+            .and(monitor_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
             .and_then(Self::handle_status);
 
+        let config_filter = warp::any().map(move || config_manager.clone()).and_then(|manager: Option<Arc<ConfigManager>>| async move {
+            manager.ok_or_else(|| warp::reject::custom(ApiError::InternalError("配置管理不可用".to_string())))
+        });
+        let config_get = warp::path!("api" / "v1" / "config")
+            .and(warp::get())
+            .and(config_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_config_get);
+
+        let audit_auth = Arc::clone(&auth);
+        let config_patch = warp::path!("api" / "v1" / "config")
+            .and(warp::patch())
+            .and(warp::body::json())
+            .and(config_filter)
+            .and(warp::any().map(move || Arc::clone(&audit_auth)))
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_config_patch);
+
+        let threats_filter = warp::any().map(move || threat_store.clone()).and_then(|store: Option<Arc<ThreatStore>>| async move {
+            store.ok_or_else(|| warp::reject::custom(ApiError::InternalError("威胁记录不可用".to_string())))
+        });
         let threats_routes = warp::path!("api" / "v1" / "threats")
             .and(warp::get())
-            .and(state_filter.clone())
-            .and(auth_filter.clone())
+            .and(warp::query::<ThreatQuery>())
+            .and(threats_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
             .and_then(Self::handle_threats);
 
+        let threat_detail = warp::path!("api" / "v1" / "threats" / String)
+            .and(warp::get())
+            .and(threats_filter)
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_threat);
+
+        let events_routes = warp::path!("api" / "v1" / "events")
+            .and(warp::get())
+            .and(warp::query::<EventStreamQuery>())
+            .and(warp::any().map(move || event_hub.clone()).and_then(|hub: Option<Arc<EventHub>>| async move {
+                hub.ok_or_else(|| warp::reject::custom(ApiError::InternalError("事件流不可用".to_string())))
+            }))
+            .and(Self::require_stream(&auth))
+            .and_then(Self::handle_events);
+
+        let authority = Arc::clone(&auth);
+        let auth_filter = warp::any().map(move || Arc::clone(&authority));
+        let token_issue = warp::path!("api" / "v1" / "auth" / "token")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(auth_filter.clone())
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_token_issue);
+
+        let token_refresh = warp::path!("api" / "v1" / "auth" / "refresh")
+            .and(warp::post())
+            .and(auth_filter.clone())
+            .and(Self::require(&auth, Scope::ReadOnly))
+            .and_then(Self::handle_token_refresh);
+
+        let key_rotate = warp::path!("api" / "v1" / "auth" / "rotate-key")
+            .and(warp::post())
+            .and(auth_filter.clone())
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_key_rotate);
+
+        let api_key_list = warp::path!("api" / "v1" / "auth" / "keys")
+            .and(warp::get())
+            .and(auth_filter.clone())
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_api_key_list);
+
+        let api_key_create = warp::path!("api" / "v1" / "auth" / "keys")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(auth_filter.clone())
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_api_key_create);
+
+        let api_key_revoke = warp::path!("api" / "v1" / "auth" / "keys" / String)
+            .and(warp::delete())
+            .and(auth_filter)
+            .and(Self::require(&auth, Scope::Admin))
+            .and_then(Self::handle_api_key_revoke);
+
         scan_routes
+            .or(job_list)
+            .or(job_progress)
+            .or(job_results)
+            .or(job_cancel)
+            .or(control_routes)
+            .or(allowlist_list)
+            .or(allowlist_modify)
+            .or(watch_list)
+            .or(watch_modify)
             .or(update_routes)
+            .or(update_status)
+            .or(update_history)
+            .or(update_jobs_list)
+            .or(update_job)
             .or(status_routes)
             .or(threats_routes)
+            .or(config_get)
+            .or(config_patch)
+            .or(threat_detail)
+            .or(events_routes)
+            .or(token_issue)
+            .or(token_refresh)
+            .or(key_rotate)
+            .or(api_key_list)
+            .or(api_key_create)
+            .or(api_key_revoke)
+    }
+
+    fn rate_limit(
+        limiter: Option<Arc<KeyedRateLimiter<IpAddr>>>,
+    ) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        warp::addr::remote()
+            .and(warp::any().map(move || limiter.clone()))
+            .and_then(|addr: Option<SocketAddr>, limiter: Option<Arc<KeyedRateLimiter<IpAddr>>>| async move {
+                if let (Some(limiter), Some(addr)) = (limiter, addr) {
+                    if limiter.len() > RATE_LIMIT_CLEANUP_THRESHOLD {
+                        limiter.cleanup();
+                    }
+                    if !limiter.try_acquire(&addr.ip(), 1) {
+                        return Err(warp::reject::custom(ApiError::TooManyRequests));
+                    }
+                }
+                Ok::<_, Rejection>(())
+            })
+            .untuple_one()
+    }
+
+    // 接受 Authorization: Bearer 令牌或 X-API-Key，令牌缺少所需权限时返回 Forbidden
+    fn require(auth: &Arc<TokenAuthority>, scope: Scope) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone {
+        let auth = Arc::clone(auth);
+        warp::header::optional::<String>("Authorization")
+            .and(warp::header::optional::<String>("X-API-Key"))
+            .and(warp::addr::remote())
+            .and(warp::method())
+            .and(warp::path::full())
+            .and(warp::any().map(move || Arc::clone(&auth)))
+            .and_then(
+                move |authorization: Option<String>,
+                      api_key: Option<String>,
+                      addr: Option<SocketAddr>,
+                      method: warp::http::Method,
+                      path: warp::path::FullPath,
+                      auth: Arc<TokenAuthority>| async move {
+                    let bearer = authorization.as_deref().and_then(|value| value.strip_prefix("Bearer "));
+                    let client = Self::client_ip(addr);
+                    let claims = auth
+                        .authenticate(bearer, api_key.as_deref(), &client)
+                        .map_err(warp::reject::custom)?;
+                    Self::check_scope(&auth, claims, scope, &format!("{} {} IP: {}", method, path.as_str(), client))
+                },
+            )
+    }
+
+    // 事件流只读，另外接受 access_token 查询参数
+    fn require_stream(auth: &Arc<TokenAuthority>) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone {
+        let auth = Arc::clone(auth);
+        warp::header::optional::<String>("Authorization")
+            .and(warp::header::optional::<String>("X-API-Key"))
+            .and(warp::query::<EventStreamQuery>())
+            .and(warp::addr::remote())
+            .and(warp::any().map(move || Arc::clone(&auth)))
+            .and_then(|authorization: Option<String>, api_key: Option<String>, query: EventStreamQuery, addr: Option<SocketAddr>, auth: Arc<TokenAuthority>| async move {
+                let bearer = authorization
+                    .as_deref()
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .or(query.access_token.as_deref());
+                let client = Self::client_ip(addr);
+                let claims = auth
+                    .authenticate(bearer, api_key.as_deref(), &client)
+                    .map_err(warp::reject::custom)?;
+                Self::check_scope(&auth, claims, Scope::ReadOnly, &format!("GET /api/v1/events IP: {}", client))
+            })
+    }
+
+    fn client_ip(addr: Option<SocketAddr>) -> String {
+        addr.map(|addr| addr.ip().to_string()).unwrap_or_else(|| "unknown".to_string())
     }
 
-    fn health_routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    // 所有错误都以 ApiResponse 返回，error 为错误说明。ApiError 优先于 warp 自身的拒绝原因，
+    // 例如路径匹配但令牌无效时返回 401 而不是其他路由的 405
+    async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+        use warp::http::StatusCode;
+
+        let (status, message) = if let Some(e) = err.find::<ApiError>() {
+            if matches!(e, ApiError::InternalError(_)) {
+                log::error!("API 请求失败: {}", e);
+            }
+            (e.status_code(), e.to_string())
+        } else if err.is_not_found() {
+            (StatusCode::NOT_FOUND, "接口不存在".to_string())
+        } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+            (StatusCode::BAD_REQUEST, format!("请求体格式错误: {}", e))
+        } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+            (StatusCode::BAD_REQUEST, "查询参数格式错误".to_string())
+        } else if let Some(e) = err.find::<warp::reject::MissingHeader>() {
+            (StatusCode::BAD_REQUEST, format!("缺少请求头: {}", e.name()))
+        } else if let Some(e) = err.find::<warp::reject::InvalidHeader>() {
+            (StatusCode::BAD_REQUEST, format!("请求头无效: {}", e.name()))
+        } else if err.find::<warp::reject::UnsupportedMediaType>().is_some() {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "请求体必须是 JSON".to_string())
+        } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+            (StatusCode::PAYLOAD_TOO_LARGE, "请求体过大".to_string())
+        } else if err.find::<warp::reject::LengthRequired>().is_some() {
+            (StatusCode::LENGTH_REQUIRED, "缺少 Content-Length".to_string())
+        } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+            (StatusCode::METHOD_NOT_ALLOWED, "不支持的请求方法".to_string())
+        } else {
+            log::error!("未处理的 API 错误: {:?}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "内部错误".to_string())
+        };
+
+        let mut response = warp::reply::with_status(
+            warp::reply::json(&ApiResponse::<()> {
+                success: false,
+                data: None,
+                error: Some(message),
+                timestamp: chrono::Utc::now(),
+            }),
+            status,
+        )
+        .into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(warp::http::header::WWW_AUTHENTICATE, warp::http::HeaderValue::from_static("Bearer"));
+        }
+        Ok(response)
+    }
+
+    // 通过认证的请求都记录到审计日志，用户为令牌主体或 API 密钥名称
+    fn check_scope(auth: &TokenAuthority, claims: Claims, scope: Scope, request: &str) -> Result<Claims, Rejection> {
+        if claims.allows(scope) {
+            auth.audit("API_REQUEST", &claims.sub, request);
+            Ok(claims)
+        } else {
+            log::warn!("令牌 {} 缺少权限 {:?}", claims.sub, scope);
+            auth.audit("API_FORBIDDEN", &claims.sub, request);
+            Err(warp::reject::custom(ApiError::Forbidden(format!("需要 {:?} 权限", scope))))
+        }
+    }
+
+    // 不需要认证，供负载均衡和编排系统探测。任一检查失败时返回 503，data 中包含每项检查的结果
+    fn health_routes(health_checker: Option<Arc<HealthChecker>>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
         warp::path!("health")
             .and(warp::get())
-            .map(|| {
-                let response = ApiResponse::<()> {
-                    success: true,
-                    data: None,
-                    error: None,
-                    timestamp: chrono::Utc::now(),
+            .and(warp::any().map(move || health_checker.clone()))
+            .and_then(|health_checker: Option<Arc<HealthChecker>>| async move {
+                let report = match health_checker {
+                    Some(checker) => checker.run().await,
+                    None => HealthReport {
+                        status: CheckStatus::Pass,
+                        checks: Vec::new(),
+                    },
                 };
-                warp::reply::json(&response)
+                let (status, error) = if report.is_healthy() {
+                    (warp::http::StatusCode::OK, None)
+                } else {
+                    let failed: Vec<&str> = report
+                        .checks
+                        .iter()
+                        .filter(|check| check.status == CheckStatus::Fail)
+                        .map(|check| check.name.as_str())
+                        .collect();
+                    log::warn!("健康检查失败: {}", failed.join(", "));
+                    (warp::http::StatusCode::SERVICE_UNAVAILABLE, Some(format!("检查失败: {}", failed.join(", "))))
+                };
+                Ok::<_, Infallible>(warp::reply::with_status(
+                    warp::reply::json(&ApiResponse {
+                        success: error.is_none(),
+                        data: Some(report),
+                        error,
+                        timestamp: chrono::Utc::now(),
+                    }),
+                    status,
+                ))
             })
     }
 
-    async fn handle_scan<T>(
+    // 接口文档不需要 API 密钥，/api/docs 为 Swagger UI，/api/docs/openapi.json 为 OpenAPI 3 文档
+    fn docs_routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+        let spec = Arc::new(openapi::spec());
+        let ui = warp::path!("api" / "docs")
+            .and(warp::get())
+            .map(|| warp::reply::html(openapi::SWAGGER_UI));
+        let json = warp::path!("api" / "docs" / "openapi.json")
+            .and(warp::get())
+            .map(move || warp::reply::json(&*spec));
+        ui.or(json)
+    }
+
+    // Prometheus 文本格式，与 /health 一样不需要 API 密钥，只包含计数不包含路径
+    fn metrics_routes(monitor_daemon: MonitorDaemonConfig) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+        warp::path!("metrics")
+            .and(warp::get())
+            .and(warp::any().map(move || monitor_daemon.clone()))
+            .and_then(|monitor_daemon: MonitorDaemonConfig| async move {
+                let status = Self::monitor_status(&monitor_daemon).await;
+                Ok::<_, Infallible>(warp::reply::with_header(
+                    render_metrics(status.as_ref()),
+                    "Content-Type",
+                    "text/plain; version=0.0.4",
+                ))
+            })
+    }
+
+    async fn monitor_status(monitor_daemon: &MonitorDaemonConfig) -> Option<MonitorStatus> {
+        match control::query_status(monitor_daemon).await {
+            Ok(status) => status,
+            Err(e) => {
+                log::warn!("无法查询监控状态: {:#}", e);
+                None
+            }
+        }
+    }
+
+    // 扫描在后台进行，立即返回 scan_id，通过 GET /api/v1/scan/{id} 查询进度
+    async fn handle_scan(
         request: ScanRequest,
-        _state: Arc<T>,
-        _auth: (),
+        scan_jobs: Arc<ScanJobManager>,
+        _auth: Claims,
     ) -> Result<impl Reply, Rejection> {
-        let scan_id = format!("SCN{:08}", rand::thread_rng().gen::<u32>());
+        let scan_mode = match request.scan_type.as_str() {
+            "quick" | "fast" => ScanMode::Quick,
+            "full" => ScanMode::Full,
+            "custom" => ScanMode::Custom,
+            "persistence" => ScanMode::Persistence,
+            other => {
+                return Err(warp::reject::custom(ApiError::ValidationError(format!("无效的扫描类型: {}", other))));
+            }
+        };
+        let job_request = JobRequest {
+            scan_mode: Some(scan_mode),
+            paths: request.paths.iter().map(std::path::PathBuf::from).collect(),
+            exclude_paths: request.exclude_paths.iter().map(std::path::PathBuf::from).collect(),
+            thread_count: request.thread_count,
+        };
+        let job = scan_jobs
+            .submit(&job_request)
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?;
+        let info = job.info();
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse {
+                success: true,
+                data: Some(ScanResponse {
+                    scan_id: info.scan_id,
+                    status: "queued".to_string(),
+                    threats_found: 0,
+                    files_scanned: 0,
+                    scan_speed_mb_s: 0.0,
+                    duration_seconds: 0.0,
+                    duration: format_duration(std::time::Duration::ZERO),
+                }),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            }),
+            warp::http::StatusCode::ACCEPTED,
+        ))
+    }
+
+    async fn handle_job_list(scan_jobs: Arc<ScanJobManager>, _auth: Claims) -> Result<impl Reply, Rejection> {
         Ok(warp::reply::json(&ApiResponse {
             success: true,
-            data: Some(ScanResponse {
-                scan_id,
-                status: "started".to_string(),
-                threats_found: 0,
-                files_scanned: 0,
-                scan_speed_mb_s: 0.0,
-                duration_seconds: 0.0,
+            data: Some(scan_jobs.list()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_job_progress(
+        scan_id: String,
+        scan_jobs: Arc<ScanJobManager>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let job = scan_jobs.get(&scan_id).ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(job.info()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_job_results(
+        scan_id: String,
+        scan_jobs: Arc<ScanJobManager>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let job = scan_jobs.get(&scan_id).ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        let results = job.results().ok_or_else(|| {
+            warp::reject::custom(ApiError::ValidationError(format!("扫描任务 {} 尚未结束", scan_id)))
+        })?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(ScanResultsResponse {
+                job: job.info(),
+                results,
             }),
             error: None,
             timestamp: chrono::Utc::now(),
         }))
     }
 
-    async fn handle_update<T>(
-        request: UpdateRequest,
-        _state: Arc<T>,
-        _auth: (),
+    // 排队中的任务直接取消；运行中的任务在当前文件扫描完成后停止，已完成部分的结果保留
+    async fn handle_job_cancel(
+        scan_id: String,
+        scan_jobs: Arc<ScanJobManager>,
+        _auth: Claims,
     ) -> Result<impl Reply, Rejection> {
+        let job = scan_jobs.get(&scan_id).ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        let accepted = job.cancel();
+        Ok(warp::reply::json(&ApiResponse {
+            success: accepted,
+            data: Some(job.info()),
+            error: (!accepted).then(|| "扫描任务已结束或已取消".to_string()),
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_scan_control(
+        action: String,
+        scan_control: ScanControl,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let (accepted, message) = match action.as_str() {
+            "cancel" => {
+                let accepted = scan_control.cancel();
+                (accepted, if accepted { "已请求取消扫描，已完成部分的结果将被保留" } else { "扫描已处于取消状态" })
+            }
+            "pause" => {
+                let accepted = scan_control.pause();
+                (accepted, if accepted { "扫描已暂停" } else { "只有运行中的扫描可以暂停" })
+            }
+            "resume" => {
+                let accepted = scan_control.resume();
+                (accepted, if accepted { "扫描已恢复" } else { "只有已暂停的扫描可以恢复" })
+            }
+            _ => return Err(warp::reject::custom(ApiError::NotFound)),
+        };
+
         Ok(warp::reply::json(&ApiResponse {
+            success: accepted,
+            data: Some(ScanControlResponse {
+                action,
+                accepted,
+                state: scan_control.state(),
+                message: message.to_string(),
+            }),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    // Server-Sent Events 推送实时事件，事件名为事件类型，数据为 LiveEvent 的 JSON。
+    // 客户端处理过慢丢失事件时收到 lagged 事件，数据为丢失的数量，此时应重新查询完整状态
+    async fn handle_events(
+        query: EventStreamQuery,
+        event_hub: Arc<EventHub>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let types: Option<Vec<String>> = query.types.as_ref().map(|types| {
+            types.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+        });
+        if let Some(unknown) = types.iter().flatten().find(|t| !LiveEvent::KINDS.iter().any(|kind| *kind == t.as_str())) {
+            return Err(warp::reject::custom(ApiError::ValidationError(format!("未知的事件类型: {}", unknown))));
+        }
+
+        let stream = futures_util::stream::unfold(event_hub.subscribe(), move |mut events| {
+            let types = types.clone();
+            async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            let lagged = warp::sse::Event::default().event("lagged").data(skipped.to_string());
+                            return Some((Ok::<_, Infallible>(lagged), events));
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                    };
+                    if let Some(ref types) = types {
+                        if !types.iter().any(|t| t == event.kind()) {
+                            continue;
+                        }
+                    }
+                    match warp::sse::Event::default().event(event.kind()).json_data(&event) {
+                        Ok(sse) => return Some((Ok(sse), events)),
+                        Err(e) => log::warn!("无法序列化实时事件: {}", e),
+                    }
+                }
+            }
+        });
+        Ok(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
+    }
+
+    // 签发新令牌，只有 admin 可以调用，因此可以签发任意权限
+    async fn handle_token_issue(
+        request: TokenRequest,
+        auth: Arc<TokenAuthority>,
+        caller: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let token = auth
+            .issue(&request.subject, request.scopes, request.ttl_secs.map(std::time::Duration::from_secs))
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?;
+        log::info!("{} 为 {} 签发了令牌，权限: {:?}，有效期至 {}", caller.sub, token.subject, token.scopes, token.expires_at);
+        Ok(Self::token_reply(token))
+    }
+
+    // 用当前令牌换取有效期相同的新令牌，当前令牌随即失效
+    async fn handle_token_refresh(auth: Arc<TokenAuthority>, caller: Claims) -> Result<impl Reply, Rejection> {
+        let token = auth
+            .refresh(&caller)
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?;
+        Ok(Self::token_reply(token))
+    }
+
+    async fn handle_key_rotate(auth: Arc<TokenAuthority>, caller: Claims) -> Result<impl Reply, Rejection> {
+        auth.rotate_key()
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?;
+        log::info!("{} 轮换了令牌签名密钥", caller.sub);
+        Ok(warp::reply::json(&ApiResponse::<()> {
             success: true,
-            data: Some(UpdateResponse {
+            data: None,
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_api_key_list(auth: Arc<TokenAuthority>, _caller: Claims) -> Result<impl Reply, Rejection> {
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(auth.list_keys()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    // 明文密钥只在响应中出现一次，之后只保存摘要
+    async fn handle_api_key_create(
+        request: ApiKeyRequest,
+        auth: Arc<TokenAuthority>,
+        caller: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let key = auth
+            .create_key(&request.name, request.role)
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(format!("{:#}", e))))?;
+        log::info!("{} 创建了 API 密钥 {} ({:?})", caller.sub, key.name, key.role);
+        auth.audit("API_KEY_CREATE", &caller.sub, &format!("name={} role={:?}", key.name, key.role));
+        Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse {
                 success: true,
-                version: "1.0.0".to_string(),
-                signatures_added: 0,
-                signatures_removed: 0,
+                data: Some(key),
+                error: None,
+                timestamp: chrono::Utc::now(),
             }),
+            warp::http::StatusCode::CREATED,
+        ))
+    }
+
+    async fn handle_api_key_revoke(
+        name: String,
+        auth: Arc<TokenAuthority>,
+        caller: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let info = auth
+            .revoke_key(&name)
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?
+            .ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        log::info!("{} 吊销了 API 密钥 {}", caller.sub, name);
+        auth.audit("API_KEY_REVOKE", &caller.sub, &format!("name={} persistent={}", name, info.persistent));
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(info),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    fn token_reply(token: IssuedToken) -> warp::reply::Json {
+        warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(token),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    async fn handle_allowlist_list(
+        allowlist: Arc<Allowlist>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(AllowlistResponse {
+                sha256: allowlist.hashes(),
+                paths: allowlist.path_patterns(),
+            }),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    // POST 添加条目，DELETE 删除条目；修改只保存在内存中，重启后以配置文件为准
+    async fn handle_allowlist_modify(
+        add: bool,
+        request: AllowlistRequest,
+        allowlist: Arc<Allowlist>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        if request.sha256.is_none() && request.path.is_none() {
+            return Err(warp::reject::custom(ApiError::ValidationError(
+                "需要提供 sha256 或 path".to_string(),
+            )));
+        }
+
+        let mut changed = false;
+        if let Some(hash) = &request.sha256 {
+            changed |= if add {
+                allowlist
+                    .add_hash(hash)
+                    .map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?
+            } else {
+                allowlist.remove_hash(hash)
+            };
+        }
+        if let Some(pattern) = &request.path {
+            let result = if add { allowlist.add_path(pattern) } else { allowlist.remove_path(pattern) };
+            changed |= result.map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?;
+        }
+        if changed {
+            log::info!("白名单已{}: {:?}", if add { "添加" } else { "删除" }, request);
+        }
+
+        Ok(warp::reply::json(&ApiResponse {
+            success: changed,
+            data: Some(AllowlistResponse {
+                sha256: allowlist.hashes(),
+                paths: allowlist.path_patterns(),
+            }),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_watch_list(
+        monitor_daemon: MonitorDaemonConfig,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let paths = control::request_watches(&monitor_daemon, &ControlRequest::ListWatches)
+            .await
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?;
+        Ok(Self::watch_list_reply(paths))
+    }
+
+    // POST 添加监控目录，DELETE 移除；由运行中的监控进程立即生效并写回配置文件
+    async fn handle_watch_modify(
+        add: bool,
+        request: WatchRequest,
+        monitor_daemon: MonitorDaemonConfig,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let path = std::path::PathBuf::from(&request.path);
+        if !path.is_absolute() {
+            return Err(warp::reject::custom(ApiError::ValidationError(
+                "监控路径必须是绝对路径".to_string(),
+            )));
+        }
+        let control_request = if add {
+            ControlRequest::AddWatch { path }
+        } else {
+            ControlRequest::RemoveWatch { path }
+        };
+        let paths = control::request_watches(&monitor_daemon, &control_request)
+            .await
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(format!("{:#}", e))))?;
+        log::info!("监控目录已{}: {}", if add { "添加" } else { "移除" }, request.path);
+        Ok(Self::watch_list_reply(paths))
+    }
+
+    fn watch_list_reply(paths: Vec<std::path::PathBuf>) -> warp::reply::Json {
+        warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(WatchListResponse {
+                paths: paths.iter().map(|p| p.display().to_string()).collect(),
+            }),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    // 更新在后台进行，立即返回任务，通过 GET /api/v1/update/jobs/{id} 查询结果
+    async fn handle_update(
+        request: UpdateRequest,
+        update_jobs: Arc<UpdateJobManager>,
+        caller: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let job = update_jobs
+            .submit(request.check_only.unwrap_or(false), request.force.unwrap_or(false))
+            .map_err(|e| warp::reject::custom(ApiError::ValidationError(e.to_string())))?;
+        log::info!("{} 触发了病毒库更新任务 {}", caller.sub, job.job_id);
+        Ok(warp::reply::with_status(
+            warp::reply::json(&ApiResponse {
+                success: true,
+                data: Some(job),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            }),
+            warp::http::StatusCode::ACCEPTED,
+        ))
+    }
+
+    async fn handle_update_status(update_jobs: Arc<UpdateJobManager>, _auth: Claims) -> Result<impl Reply, Rejection> {
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(update_jobs.status()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    // 按时间从旧到新排列
+    async fn handle_update_history(update_jobs: Arc<UpdateJobManager>, _auth: Claims) -> Result<impl Reply, Rejection> {
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(update_jobs.history()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_update_jobs(update_jobs: Arc<UpdateJobManager>, _auth: Claims) -> Result<impl Reply, Rejection> {
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(update_jobs.list()),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_update_job(
+        job_id: String,
+        update_jobs: Arc<UpdateJobManager>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let job = update_jobs.get(&job_id).ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(job),
             error: None,
             timestamp: chrono::Utc::now(),
         }))
@@ -201,8 +1198,10 @@ impl ApiServer {
 
     async fn handle_status<T>(
         _state: Arc<T>,
-        _auth: (),
+        monitor_daemon: MonitorDaemonConfig,
+        _auth: Claims,
     ) -> Result<impl Reply, Rejection> {
+        let monitor = Self::monitor_status(&monitor_daemon).await;
         Ok(warp::reply::json(&ApiResponse {
             success: true,
             data: Some(StatusResponse {
@@ -213,44 +1212,180 @@ impl ApiServer {
                 last_scan: None,
                 last_update: None,
                 active_scans: 0,
+                monitor,
             }),
             error: None,
             timestamp: chrono::Utc::now(),
         }))
     }
 
-    async fn handle_threats<T>(
-        _state: Arc<T>,
-        _auth: (),
+    // 密钥字段已隐藏
+    async fn handle_config_get(config_manager: Arc<ConfigManager>, _auth: Claims) -> Result<impl Reply, Rejection> {
+        let config = config_manager
+            .current()
+            .await
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(config),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    // 请求体为 JSON Merge Patch，只需包含要修改的项
+    async fn handle_config_patch(
+        patch: serde_json::Value,
+        config_manager: Arc<ConfigManager>,
+        auth: Arc<TokenAuthority>,
+        caller: Claims,
     ) -> Result<impl Reply, Rejection> {
+        let update = match config_manager.apply_patch(&patch).await {
+            Ok(update) => update,
+            Err(e) => {
+                auth.audit("CONFIG_UPDATE_REJECTED", &caller.sub, &format!("{:#}", e));
+                return Err(warp::reject::custom(ApiError::ValidationError(format!("{:#}", e))));
+            }
+        };
+        if !update.changed.is_empty() {
+            log::info!("{} 修改了配置: {}", caller.sub, update.changed.join(", "));
+            auth.audit(
+                "CONFIG_UPDATE",
+                &caller.sub,
+                &format!(
+                    "changed={} reloaded={} restart_required={}",
+                    update.changed.join(","),
+                    update.reloaded.join(","),
+                    update.restart_required.join(",")
+                ),
+            );
+        }
         Ok(warp::reply::json(&ApiResponse {
             success: true,
-            data: Some(vec![] as Vec<ThreatInfo>),
+            data: Some(update),
             error: None,
             timestamp: chrono::Utc::now(),
         }))
     }
+
+    async fn handle_threats(
+        query: ThreatQuery,
+        threat_store: Arc<ThreatStore>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        if let (Some(since), Some(until)) = (query.since, query.until) {
+            if since >= until {
+                return Err(warp::reject::custom(ApiError::ValidationError("since 必须早于 until".to_string())));
+            }
+        }
+        let page = tokio::task::spawn_blocking(move || threat_store.query(&query))
+            .await
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(e.to_string())))?
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(page),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+
+    async fn handle_threat(
+        threat_id: String,
+        threat_store: Arc<ThreatStore>,
+        _auth: Claims,
+    ) -> Result<impl Reply, Rejection> {
+        let threat = tokio::task::spawn_blocking(move || threat_store.get(&threat_id))
+            .await
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(e.to_string())))?
+            .map_err(|e| warp::reject::custom(ApiError::InternalError(format!("{:#}", e))))?
+            .ok_or_else(|| warp::reject::custom(ApiError::NotFound))?;
+        Ok(warp::reply::json(&ApiResponse {
+            success: true,
+            data: Some(threat),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }))
+    }
+}
+
+fn push_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    out.push_str(&format!("# HELP virus_scanner_{} {}\n", name, help));
+    out.push_str(&format!("# TYPE virus_scanner_{} {}\n", name, kind));
+    out.push_str(&format!("virus_scanner_{} {}\n", name, value));
+}
+
+pub fn render_metrics(monitor: Option<&MonitorStatus>) -> String {
+    let mut out = String::new();
+    push_metric(&mut out, "monitor_up", "gauge", "Whether the file monitor is running", monitor.is_some() as u8);
+    let Some(monitor) = monitor else {
+        return out;
+    };
+
+    let stats = &monitor.stats;
+    let counters = [
+        ("monitor_events_received_total", "Events delivered by the monitor backend", stats.events_received),
+        ("monitor_events_filtered_total", "Events discarded by filter rules", stats.events_filtered),
+        ("monitor_events_rate_limited_total", "Events discarded by the per-watch rate limit", stats.events_rate_limited),
+        ("monitor_events_dropped_total", "Events dropped because the scan queue was full", stats.events_dropped),
+        ("monitor_scans_total", "On-access scans triggered by monitor events", stats.scans_triggered),
+        ("monitor_threats_total", "Threats found by on-access scans", stats.threats_found),
+    ];
+    for (name, help, value) in counters {
+        push_metric(&mut out, name, "counter", help, value);
+    }
+    push_metric(&mut out, "monitor_start_time_seconds", "gauge", "Monitor start time in Unix seconds", monitor.started_at.timestamp());
+    push_metric(&mut out, "monitor_watches", "gauge", "Directories currently watched", stats.watches);
+    if let Some(limit) = stats.max_user_watches {
+        push_metric(&mut out, "monitor_inotify_max_user_watches", "gauge", "Kernel inotify watch limit per user", limit);
+    }
+    out
 }
 
 #[derive(Debug)]
 pub enum ApiError {
     Unauthorized,
+    Forbidden(String),
     NotFound,
     InternalError(String),
     ValidationError(String),
+    TooManyRequests,
+    Locked,
     None,
 }
 
 impl warp::reject::Reject for ApiError {}
 
+impl ApiError {
+    pub fn status_code(&self) -> warp::http::StatusCode {
+        use warp::http::StatusCode;
+
+        match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ApiError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Locked => StatusCode::LOCKED,
+            ApiError::InternalError(_) | ApiError::None => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ApiError::Unauthorized => write!(f, "未授权访问"),
+            ApiError::Forbidden(e) => write!(f, "权限不足: {}", e),
             ApiError::NotFound => write!(f, "资源不存在"),
             ApiError::InternalError(e) => write!(f, "内部错误: {}", e),
             ApiError::ValidationError(e) => write!(f, "验证错误: {}", e),
+            ApiError::TooManyRequests => write!(f, "请求过于频繁，请稍后重试"),
+            ApiError::Locked => write!(f, "认证失败次数过多，客户端已被暂时锁定"),
             ApiError::None => write!(f, "无错误"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
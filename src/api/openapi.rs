@@ -0,0 +1,267 @@
+use crate::api::auth::{ApiKeyInfo, CreatedApiKey, IssuedToken, Scope};
+use crate::api::health::HealthReport;
+use crate::api::jobs::JobInfo;
+use crate::api::settings::ConfigUpdate;
+use crate::api::updates::{UpdateJobInfo, UpdateStatusReport};
+use crate::api::{
+    AllowlistRequest, AllowlistResponse, ApiKeyRequest, ScanControlResponse, ScanRequest, ScanResponse, ScanResultsResponse,
+    StatusResponse, TokenRequest, UpdateRequest, WatchListResponse, WatchRequest,
+};
+use crate::report::{StoredThreat, ThreatPage};
+use crate::update::UpdateInfo;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+// Swagger UI 从 CDN 加载，无法访问外网时仍可直接下载 /api/docs/openapi.json
+pub const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+  <meta charset="utf-8">
+  <title>Virus Scanner API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/api/docs/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+// 接口说明，请求和响应的结构由类型定义生成
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    // None 表示不需要认证
+    scope: Option<Scope>,
+    parameters: Vec<Value>,
+    request: Option<Value>,
+    status: u16,
+    // 响应中 data 字段的结构，None 表示 data 为 null
+    response: Option<Value>,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &'static str, summary: &'static str, scope: Option<Scope>) -> Self {
+        Self {
+            method,
+            path,
+            summary,
+            scope,
+            parameters: Vec::new(),
+            request: None,
+            status: 200,
+            response: None,
+        }
+    }
+
+    fn request(mut self, schema: Value) -> Self {
+        self.request = Some(schema);
+        self
+    }
+
+    fn response(mut self, schema: Value) -> Self {
+        self.response = Some(schema);
+        self
+    }
+
+    fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    fn path_param(mut self, name: &str, description: &str) -> Self {
+        self.parameters.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": { "type": "string" },
+        }));
+        self
+    }
+
+    fn query_param(mut self, name: &str, schema: Value, description: &str) -> Self {
+        self.parameters.push(json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "description": description,
+            "schema": schema,
+        }));
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        let mut responses = Map::new();
+        responses.insert(
+            self.status.to_string(),
+            json!({
+                "description": "成功",
+                "content": { "application/json": { "schema": envelope(self.response.clone()) } },
+            }),
+        );
+        let mut operation = json!({
+            "summary": self.summary,
+            "parameters": self.parameters,
+        });
+        if let Some(scope) = self.scope {
+            let scope = serde_json::to_value(scope).unwrap_or(Value::Null);
+            operation["description"] = Value::String(format!("需要 {} 权限 (admin 令牌拥有全部权限)", scope.as_str().unwrap_or("")));
+            operation["x-required-scope"] = scope;
+            operation["security"] = json!([{ "bearerAuth": [] }, { "apiKey": [] }]);
+            for (status, description) in [("401", "未认证"), ("403", "权限不足"), ("423", "认证失败次数过多，已锁定"), ("429", "请求过于频繁")] {
+                responses.insert(
+                    status.to_string(),
+                    json!({
+                        "description": description,
+                        "content": { "application/json": { "schema": envelope(None) } },
+                    }),
+                );
+            }
+        } else {
+            operation["security"] = json!([]);
+        }
+        if let Some(ref request) = self.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": request } },
+            });
+        }
+        operation["responses"] = Value::Object(responses);
+        operation
+    }
+}
+
+// 所有 JSON 响应都包装在 ApiResponse 中
+fn envelope(data: Option<Value>) -> Value {
+    json!({
+        "type": "object",
+        "required": ["success", "timestamp"],
+        "properties": {
+            "success": { "type": "boolean" },
+            "data": data.unwrap_or_else(|| json!({ "nullable": true })),
+            "error": { "type": "string", "nullable": true },
+            "timestamp": { "type": "string", "format": "date-time" },
+        },
+    })
+}
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    serde_json::to_value(gen.subschema_for::<T>()).unwrap_or(Value::Null)
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+// 生成 OpenAPI 3 文档。新增接口时同时在这里登记
+pub fn spec() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let config_object = json!({ "type": "object", "additionalProperties": true, "description": "与配置文件结构相同" });
+
+    let operations = vec![
+        Operation::new("post", "/api/v1/scan", "提交扫描任务", Some(Scope::Scan))
+            .request(schema::<ScanRequest>(&mut gen))
+            .status(202)
+            .response(schema::<ScanResponse>(&mut gen)),
+        Operation::new("get", "/api/v1/scan", "列出扫描任务", Some(Scope::ReadOnly)).response(array_of(schema::<JobInfo>(&mut gen))),
+        Operation::new("get", "/api/v1/scan/{id}", "查询扫描进度", Some(Scope::ReadOnly))
+            .path_param("id", "扫描任务 ID")
+            .response(schema::<JobInfo>(&mut gen)),
+        Operation::new("post", "/api/v1/scan/{action}", "暂停、继续或取消当前扫描", Some(Scope::Scan))
+            .path_param("action", "pause、resume 或 cancel")
+            .response(schema::<ScanControlResponse>(&mut gen)),
+        Operation::new("delete", "/api/v1/scan/{id}", "取消扫描任务", Some(Scope::Scan))
+            .path_param("id", "扫描任务 ID")
+            .response(schema::<JobInfo>(&mut gen)),
+        Operation::new("get", "/api/v1/scan/{id}/results", "获取扫描结果", Some(Scope::ReadOnly))
+            .path_param("id", "扫描任务 ID")
+            .response(schema::<ScanResultsResponse>(&mut gen)),
+        Operation::new("get", "/api/v1/allowlist", "列出白名单", Some(Scope::ReadOnly)).response(schema::<AllowlistResponse>(&mut gen)),
+        Operation::new("post", "/api/v1/allowlist", "添加白名单条目", Some(Scope::Admin))
+            .request(schema::<AllowlistRequest>(&mut gen))
+            .response(schema::<AllowlistResponse>(&mut gen)),
+        Operation::new("delete", "/api/v1/allowlist", "删除白名单条目", Some(Scope::Admin))
+            .request(schema::<AllowlistRequest>(&mut gen))
+            .response(schema::<AllowlistResponse>(&mut gen)),
+        Operation::new("get", "/api/v1/monitor/watches", "列出监控目录", Some(Scope::ReadOnly)).response(schema::<WatchListResponse>(&mut gen)),
+        Operation::new("post", "/api/v1/monitor/watches", "添加监控目录", Some(Scope::Admin))
+            .request(schema::<WatchRequest>(&mut gen))
+            .response(schema::<WatchListResponse>(&mut gen)),
+        Operation::new("delete", "/api/v1/monitor/watches", "删除监控目录", Some(Scope::Admin))
+            .request(schema::<WatchRequest>(&mut gen))
+            .response(schema::<WatchListResponse>(&mut gen)),
+        Operation::new("post", "/api/v1/update", "提交病毒库更新任务", Some(Scope::Update))
+            .request(schema::<UpdateRequest>(&mut gen))
+            .status(202)
+            .response(schema::<UpdateJobInfo>(&mut gen)),
+        Operation::new("get", "/api/v1/update/status", "查询病毒库更新状态", Some(Scope::ReadOnly)).response(schema::<UpdateStatusReport>(&mut gen)),
+        Operation::new("get", "/api/v1/update/history", "病毒库更新历史", Some(Scope::ReadOnly)).response(array_of(schema::<UpdateInfo>(&mut gen))),
+        Operation::new("get", "/api/v1/update/jobs", "列出更新任务", Some(Scope::ReadOnly)).response(array_of(schema::<UpdateJobInfo>(&mut gen))),
+        Operation::new("get", "/api/v1/update/jobs/{id}", "查询更新任务", Some(Scope::ReadOnly))
+            .path_param("id", "更新任务 ID")
+            .response(schema::<UpdateJobInfo>(&mut gen)),
+        Operation::new("get", "/api/v1/status", "扫描器和监控状态", Some(Scope::ReadOnly)).response(schema::<StatusResponse>(&mut gen)),
+        Operation::new("get", "/api/v1/config", "获取运行中的配置，密钥已隐藏", Some(Scope::ReadOnly)).response(config_object.clone()),
+        Operation::new("patch", "/api/v1/config", "修改配置 (JSON Merge Patch)", Some(Scope::Admin))
+            .request(config_object)
+            .response(schema::<ConfigUpdate>(&mut gen)),
+        Operation::new("get", "/api/v1/threats", "查询威胁记录", Some(Scope::ReadOnly))
+            .query_param("since", json!({ "type": "string", "format": "date-time" }), "发现时间不早于")
+            .query_param("until", json!({ "type": "string", "format": "date-time" }), "发现时间早于")
+            .query_param("risk_level", json!({ "type": "string" }), "风险等级，如 High")
+            .query_param("threat_type", json!({ "type": "string" }), "威胁类型，如 Trojan")
+            .query_param("path_prefix", json!({ "type": "string" }), "文件路径前缀")
+            .query_param("limit", json!({ "type": "integer", "minimum": 1, "maximum": 500 }), "每页数量，默认 50")
+            .query_param("offset", json!({ "type": "integer", "minimum": 0 }), "跳过的记录数")
+            .response(schema::<ThreatPage>(&mut gen)),
+        Operation::new("get", "/api/v1/threats/{id}", "查询威胁详情", Some(Scope::ReadOnly))
+            .path_param("id", "威胁记录 ID")
+            .response(schema::<StoredThreat>(&mut gen)),
+        Operation::new("get", "/api/v1/events", "实时事件流 (text/event-stream)", Some(Scope::ReadOnly))
+            .query_param("types", json!({ "type": "string" }), "逗号分隔的事件类型: update、monitor、scan_progress、threat")
+            .query_param("access_token", json!({ "type": "string" }), "无法设置请求头时使用的令牌"),
+        Operation::new("post", "/api/v1/auth/token", "签发令牌", Some(Scope::Admin))
+            .request(schema::<TokenRequest>(&mut gen))
+            .response(schema::<IssuedToken>(&mut gen)),
+        Operation::new("post", "/api/v1/auth/refresh", "刷新当前令牌", Some(Scope::ReadOnly)).response(schema::<IssuedToken>(&mut gen)),
+        Operation::new("post", "/api/v1/auth/rotate-key", "轮换令牌签名密钥", Some(Scope::Admin)),
+        Operation::new("get", "/api/v1/auth/keys", "列出 API 密钥", Some(Scope::Admin)).response(array_of(schema::<ApiKeyInfo>(&mut gen))),
+        Operation::new("post", "/api/v1/auth/keys", "创建 API 密钥，明文密钥只返回一次", Some(Scope::Admin))
+            .request(schema::<ApiKeyRequest>(&mut gen))
+            .status(201)
+            .response(schema::<CreatedApiKey>(&mut gen)),
+        Operation::new("delete", "/api/v1/auth/keys/{name}", "吊销 API 密钥", Some(Scope::Admin))
+            .path_param("name", "密钥名称")
+            .response(schema::<ApiKeyInfo>(&mut gen)),
+        Operation::new("get", "/health", "健康检查，任一检查项失败时返回 503 (响应结构相同)", None).response(schema::<HealthReport>(&mut gen)),
+    ];
+
+    let mut paths = Map::new();
+    for operation in &operations {
+        let item = paths.entry(operation.path.to_string()).or_insert_with(|| json!({}));
+        item[operation.method] = operation.to_json();
+    }
+    let schemas = serde_json::to_value(gen.take_definitions()).unwrap_or_else(|_| json!({}));
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Virus Scanner API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+            },
+        },
+    })
+}
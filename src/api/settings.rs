@@ -0,0 +1,218 @@
+use crate::config::ScannerConfig;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+// GET 返回的配置中密钥替换为该值，PATCH 时原样传回表示不修改
+pub const REDACTED: &str = "********";
+
+// 以 JSON Pointer 表示的密钥字段
+const SECRET_FIELDS: [&str; 3] = ["/api/api_key", "/logging/remote_logging/api_key", "/update/misp/api_key"];
+// webhook 的地址和请求头中可能包含令牌，按数组下标对应
+const WEBHOOKS: &str = "/alerts/webhooks";
+
+pub type ReloadHook = Arc<dyn Fn(&ScannerConfig) -> Result<(), anyhow::Error> + Send + Sync>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ConfigUpdate {
+    // 发生变化的顶层配置项，如 scan_modes、performance
+    pub changed: Vec<String>,
+    // 已在运行中生效的配置项
+    pub reloaded: Vec<String>,
+    // 需要重启后才能生效的配置项
+    pub restart_required: Vec<String>,
+    // 重新加载失败的配置项及原因，新配置已保存
+    pub errors: Vec<String>,
+}
+
+// 运行中的配置，与 VirusScanner 共享。修改后按配置项调用重新加载函数
+pub struct ConfigManager {
+    config: Arc<RwLock<ScannerConfig>>,
+    config_path: Option<PathBuf>,
+    hooks: Vec<(&'static str, ReloadHook)>,
+    // 同一时间只处理一个修改
+    update_lock: Mutex<()>,
+}
+
+impl ConfigManager {
+    pub fn new(config: Arc<RwLock<ScannerConfig>>) -> Self {
+        Self {
+            config,
+            config_path: None,
+            hooks: Vec::new(),
+            update_lock: Mutex::new(()),
+        }
+    }
+
+    // 设置后修改写入配置文件，重启后仍然有效
+    pub fn persist_to(mut self, config_path: PathBuf) -> Self {
+        self.config_path = Some(config_path);
+        self
+    }
+
+    // section 为顶层配置项的名称，该项变化时调用 hook。同一配置项可以注册多个函数
+    pub fn on_reload(&mut self, section: &'static str, hook: ReloadHook) {
+        self.hooks.push((section, hook));
+    }
+
+    // 密钥已隐藏
+    pub async fn current(&self) -> Result<Value, anyhow::Error> {
+        let mut value = serde_json::to_value(&*self.config.read().await)?;
+        for pointer in SECRET_FIELDS {
+            if let Some(secret) = value.pointer_mut(pointer) {
+                if secret.as_str().map_or(false, |s| !s.is_empty()) {
+                    *secret = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        if let Some(Value::Array(webhooks)) = value.pointer_mut(WEBHOOKS) {
+            for webhook in webhooks {
+                for secret in webhook_secrets(webhook) {
+                    *secret = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    // 按 RFC 7386 (JSON Merge Patch) 合并修改，null 删除该项并使用默认值。
+    // 新配置通过校验后才会保存和生效
+    pub async fn apply_patch(&self, patch: &Value) -> Result<ConfigUpdate, anyhow::Error> {
+        if !patch.is_object() {
+            return Err(anyhow::anyhow!("配置修改必须是 JSON 对象"));
+        }
+        let _guard = self.update_lock.lock().await;
+
+        let current = serde_json::to_value(&*self.config.read().await)?;
+        let mut merged = current.clone();
+        merge_patch(&mut merged, patch);
+        for pointer in SECRET_FIELDS {
+            if merged.pointer(pointer).and_then(Value::as_str) == Some(REDACTED) {
+                if let (Some(secret), Some(original)) = (merged.pointer_mut(pointer), current.pointer(pointer)) {
+                    *secret = original.clone();
+                }
+            }
+        }
+        if let Some(Value::Array(webhooks)) = merged.pointer_mut(WEBHOOKS) {
+            for (index, webhook) in webhooks.iter_mut().enumerate() {
+                let original = current.pointer(&format!("{}/{}", WEBHOOKS, index));
+                restore_webhook_secrets(webhook, original);
+            }
+        }
+        let config: ScannerConfig = serde_json::from_value(merged).context("配置无效")?;
+        config.validate()?;
+        self.commit(&current, config, true).await
+    }
+
+    // 使用重新读取的配置文件替换运行中的配置 (SIGHUP)，不写回配置文件
+    pub async fn replace(&self, config: ScannerConfig) -> Result<ConfigUpdate, anyhow::Error> {
+        config.validate()?;
+        let _guard = self.update_lock.lock().await;
+        let current = serde_json::to_value(&*self.config.read().await)?;
+        self.commit(&current, config, false).await
+    }
+
+    // 调用方持有 update_lock
+    async fn commit(&self, current: &Value, config: ScannerConfig, persist: bool) -> Result<ConfigUpdate, anyhow::Error> {
+        // 与序列化后的配置比较，忽略补丁中取值未变的项
+        let updated = serde_json::to_value(&config)?;
+        let changed: Vec<String> = match (current, &updated) {
+            (Value::Object(before), Value::Object(after)) => after
+                .iter()
+                .filter(|(key, value)| before.get(*key) != Some(*value))
+                .map(|(key, _)| key.clone())
+                .collect(),
+            _ => Vec::new(),
+        };
+        if changed.is_empty() {
+            return Ok(ConfigUpdate::default());
+        }
+
+        if let Some(path) = self.config_path.as_ref().filter(|_| persist) {
+            config.save(path).with_context(|| format!("无法保存配置文件: {:?}", path))?;
+        }
+        *self.config.write().await = config.clone();
+
+        let mut update = ConfigUpdate {
+            changed: changed.clone(),
+            ..Default::default()
+        };
+        for section in changed {
+            let hooks: Vec<&ReloadHook> = self.hooks.iter().filter(|(name, _)| *name == section).map(|(_, hook)| hook).collect();
+            if hooks.is_empty() {
+                update.restart_required.push(section);
+                continue;
+            }
+            let mut failed = false;
+            for hook in hooks {
+                if let Err(e) = hook(&config) {
+                    log::error!("重新加载配置项 {} 失败: {:#}", section, e);
+                    update.errors.push(format!("{}: {:#}", section, e));
+                    failed = true;
+                }
+            }
+            if !failed {
+                update.reloaded.push(section);
+            }
+        }
+        Ok(update)
+    }
+}
+
+fn webhook_secrets(webhook: &mut Value) -> Vec<&mut Value> {
+    let Value::Object(webhook) = webhook else {
+        return Vec::new();
+    };
+    let mut secrets = Vec::new();
+    for (key, value) in webhook.iter_mut() {
+        match (key.as_str(), value) {
+            ("url", url @ Value::String(_)) => secrets.push(url),
+            ("headers", Value::Object(headers)) => secrets.extend(headers.values_mut()),
+            _ => {}
+        }
+    }
+    secrets.retain(|secret| secret.as_str().map_or(false, |s| !s.is_empty()));
+    secrets
+}
+
+// 仍为 REDACTED 的地址和请求头使用同一下标原有的值
+fn restore_webhook_secrets(webhook: &mut Value, original: Option<&Value>) {
+    if webhook.get("url").and_then(Value::as_str) == Some(REDACTED) {
+        if let Some(url) = original.and_then(|original| original.get("url")) {
+            webhook["url"] = url.clone();
+        }
+    }
+    if let Some(Value::Object(headers)) = webhook.get_mut("headers") {
+        for (name, value) in headers.iter_mut() {
+            if value.as_str() == Some(REDACTED) {
+                if let Some(header) = original.and_then(|original| original.pointer(&format!("/headers/{}", name.replace('~', "~0").replace('/', "~1")))) {
+                    *value = header.clone();
+                }
+            }
+        }
+    }
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
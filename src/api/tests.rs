@@ -0,0 +1,533 @@
+use crate::api::auth::{Scope, TokenAuthority};
+use crate::api::events::{EventHub, LiveEvent};
+use crate::api::health::{CheckStatus, HealthChecker};
+use crate::api::{ApiError, ApiServer};
+use crate::api::jobs::{JobRequest, JobStatus, ScanJobManager};
+use crate::api::openapi;
+use crate::api::settings::{ConfigManager, REDACTED};
+use crate::api::updates::UpdateJobManager;
+use crate::config::{ApiConfig, ApiKeyConfig, ApiRateLimitConfig, ApiRole, HealthCheckConfig, ScannerConfig};
+use crate::core::security::SecurityManager;
+use crate::report::DetectionLogger;
+use warp::Filter;
+use crate::scanner::{eicar_test_string, ScanMode, ScanOptions, SignatureDatabase, EICAR_SIGNATURE_ID};
+use crate::update::DatabaseUpdater;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn job_manager() -> ScanJobManager {
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let mut options = ScanOptions::from_config(&ScannerConfig::default(), ScanMode::Custom, Vec::new());
+        options.exclude_paths.clear();
+        options.exclude_extensions.clear();
+        options.thread_count = 1;
+        ScanJobManager::new(signature_db, options, 1)
+    }
+
+    #[tokio::test]
+    async fn test_scan_job_runs_in_background_and_reports_results() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("eicar.com"), eicar_test_string()).unwrap();
+        std::fs::write(dir.path().join("clean.bin"), b"hello").unwrap();
+
+        let log_dir = tempfile::tempdir().unwrap();
+        let mut logging = ScannerConfig::default().logging;
+        logging.log_dir = log_dir.path().to_path_buf();
+        let mut manager = job_manager().await;
+        manager.set_detection_logger(Arc::new(DetectionLogger::new(&logging).unwrap()));
+        let request = JobRequest {
+            scan_mode: Some(ScanMode::Custom),
+            paths: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let job = manager.submit(&request).unwrap();
+        assert!(manager.get(job.id()).is_some());
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while !job.info().status.is_finished() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let info = job.info();
+        assert_eq!(info.status, JobStatus::Completed);
+        assert_eq!(info.percent, 100.0);
+        assert_eq!(info.threats_found, 1);
+        assert!(info.finished_at.is_some());
+        let results = job.results().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].signature_id, EICAR_SIGNATURE_ID);
+        assert!(!job.cancel());
+        assert_eq!(manager.active_count(), 0);
+
+        // API 任务的检测结果同样写入检测日志
+        let detections = std::fs::read_to_string(log_dir.path().join(&logging.detection_log.file_name)).unwrap();
+        assert_eq!(detections.lines().count(), 1);
+        assert!(detections.contains(job.id()));
+        assert!(detections.contains(EICAR_SIGNATURE_ID));
+    }
+
+    #[tokio::test]
+    async fn test_scan_job_validation_and_queued_cancel() {
+        let manager = job_manager().await;
+        assert!(manager.submit(&JobRequest::default()).is_err());
+        let relative = JobRequest {
+            paths: vec![PathBuf::from("relative/dir")],
+            ..Default::default()
+        };
+        assert!(manager.submit(&relative).is_err());
+
+        // 只有一个执行许可，第二个任务排队时被取消
+        let dir = tempfile::tempdir().unwrap();
+        let request = JobRequest {
+            paths: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let first = manager.submit(&request).unwrap();
+        let second = manager.submit(&request).unwrap();
+        assert!(second.results().is_none());
+        assert!(second.cancel());
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while !(first.info().status.is_finished() && second.info().status.is_finished())
+            && std::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(first.info().status, JobStatus::Completed);
+        assert_eq!(second.info().status, JobStatus::Cancelled);
+        assert_eq!(second.results().unwrap().len(), 0);
+        assert_eq!(manager.list().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_event_hub_streams_scan_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("eicar.com"), eicar_test_string()).unwrap();
+
+        let hub = Arc::new(EventHub::new());
+        let mut events = hub.subscribe();
+        let mut manager = job_manager().await;
+        manager.set_event_hub(Arc::clone(&hub));
+        let job = manager
+            .submit(&JobRequest {
+                paths: vec![dir.path().to_path_buf()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut statuses = Vec::new();
+        let mut threats = Vec::new();
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(10), events.recv()).await.unwrap().unwrap();
+            let info = match event {
+                LiveEvent::ScanProgress(info) => info,
+                // 发现的威胁在任务结束前发布
+                LiveEvent::Threat(threat) => {
+                    threats.push(threat);
+                    continue;
+                }
+                other => panic!("unexpected event: {}", other.kind()),
+            };
+            assert_eq!(info.scan_id, job.id());
+            statuses.push(info.status);
+            if info.status.is_finished() {
+                assert_eq!(info.threats_found, 1);
+                break;
+            }
+        }
+        assert_eq!(statuses.first(), Some(&JobStatus::Queued));
+        assert!(statuses.contains(&JobStatus::Running));
+        assert_eq!(statuses.last(), Some(&JobStatus::Completed));
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].scan_id, job.id());
+
+        let json = serde_json::to_value(LiveEvent::ScanProgress(job.info())).unwrap();
+        assert_eq!(json["type"], "scan_progress");
+        assert_eq!(json["data"]["status"], "completed");
+    }
+
+    #[tokio::test]
+    async fn test_scoped_tokens_are_enforced() {
+        let mut config = ApiConfig::default();
+        config.api_key = "bootstrap-key".to_string();
+        let auth = Arc::new(TokenAuthority::ephemeral(&config));
+
+        let scanner = auth.issue("ci-runner", vec![Scope::Scan], None).unwrap();
+        let claims = auth.verify(&scanner.token).unwrap();
+        assert_eq!(claims.sub, "ci-runner");
+        assert!(claims.allows(Scope::Scan) && claims.allows(Scope::ReadOnly));
+        assert!(!claims.allows(Scope::Update) && !claims.allows(Scope::Admin));
+        assert!(auth.issue("nobody", Vec::new(), None).is_err());
+
+        // 篡改权限后签名不再匹配；其他密钥签发的令牌无效
+        let mut parts: Vec<String> = scanner.token.split('.').map(String::from).collect();
+        let forged = serde_json::to_vec(&serde_json::json!({
+            "sub": "ci-runner", "iss": "virus-scanner", "scopes": ["admin"],
+            "iat": claims.iat, "exp": claims.exp, "jti": claims.jti,
+        }))
+        .unwrap();
+        use base64::Engine;
+        parts[1] = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(forged);
+        assert!(auth.verify(&parts.join(".")).is_err());
+        assert!(TokenAuthority::ephemeral(&config).verify(&scanner.token).is_err());
+
+        let scan_filter = ApiServer::require(&auth, Scope::Scan);
+        let update_filter = ApiServer::require(&auth, Scope::Update);
+        let bearer = format!("Bearer {}", scanner.token);
+        assert!(warp::test::request().header("Authorization", &bearer).filter(&scan_filter).await.is_ok());
+        assert!(warp::test::request().header("Authorization", &bearer).filter(&update_filter).await.is_err());
+        assert!(warp::test::request().filter(&scan_filter).await.is_err());
+        assert!(warp::test::request().header("X-API-Key", "wrong").filter(&scan_filter).await.is_err());
+        let admin = warp::test::request().header("X-API-Key", "bootstrap-key").filter(&update_filter).await.unwrap();
+        assert!(admin.is_api_key());
+
+        // 刷新后旧令牌失效；轮换密钥后已签发的令牌仍然有效
+        let refreshed = auth.refresh(&claims).unwrap();
+        assert!(auth.verify(&scanner.token).is_err());
+        auth.rotate_key().unwrap();
+        assert_eq!(auth.verify(&refreshed.token).unwrap().scopes, vec![Scope::Scan]);
+        let fresh = auth.issue("dashboard", vec![Scope::ReadOnly], Some(Duration::from_secs(60))).unwrap();
+        assert!(auth.verify(&fresh.token).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_and_auth_lockout() {
+        let limited = ApiServer::new("127.0.0.1:0".parse().unwrap(), Arc::new(TokenAuthority::ephemeral(&ApiConfig::default())))
+            .with_rate_limit(&ApiRateLimitConfig {
+                requests_per_sec: 1,
+                burst: 2,
+                ..Default::default()
+            });
+        let route = ApiServer::rate_limit(limited.rate_limiter.clone())
+            .map(warp::reply)
+            .recover(ApiServer::handle_rejection);
+        let client: std::net::SocketAddr = "192.0.2.10:40000".parse().unwrap();
+        let other: std::net::SocketAddr = "192.0.2.11:40000".parse().unwrap();
+        for _ in 0..2 {
+            assert_eq!(warp::test::request().remote_addr(client).reply(&route).await.status(), 200);
+        }
+        assert_eq!(warp::test::request().remote_addr(client).reply(&route).await.status(), 429);
+        assert_eq!(warp::test::request().remote_addr(other).reply(&route).await.status(), 200);
+
+        // 错误凭据达到阈值后，即使凭据正确也被锁定；未携带凭据的请求不计数
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ApiConfig::default();
+        config.api_key = "bootstrap-key".to_string();
+        let security = Arc::new(SecurityManager::new(dir.path().to_path_buf(), 3, 60));
+        let auth = TokenAuthority::ephemeral(&config).with_security_manager(security);
+        for _ in 0..5 {
+            assert!(matches!(auth.authenticate(None, None, "192.0.2.10"), Err(ApiError::Unauthorized)));
+        }
+        assert!(auth.authenticate(None, Some("bootstrap-key"), "192.0.2.10").is_ok());
+        for _ in 0..3 {
+            assert!(matches!(auth.authenticate(Some("bogus.token.value"), None, "192.0.2.10"), Err(ApiError::Unauthorized)));
+        }
+        assert!(matches!(auth.authenticate(None, Some("bootstrap-key"), "192.0.2.10"), Err(ApiError::Locked)));
+        assert!(auth.authenticate(None, Some("bootstrap-key"), "192.0.2.11").is_ok());
+
+        let audit = std::fs::read_to_string(dir.path().join("audit.log")).unwrap();
+        assert_eq!(audit.matches("ACTION=LOGIN_FAILED").count(), 3);
+        assert_eq!(audit.matches("ACTION=API_LOCKOUT").count(), 1);
+
+        let auth = Arc::new(auth);
+        let filter = ApiServer::require(&auth, Scope::ReadOnly).map(|_| warp::reply()).recover(ApiServer::handle_rejection);
+        let locked = warp::test::request()
+            .remote_addr(client)
+            .header("X-API-Key", "bootstrap-key")
+            .reply(&filter)
+            .await;
+        assert_eq!(locked.status(), 423);
+    }
+
+    #[tokio::test]
+    async fn test_update_jobs_run_one_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        // 无法连接的镜像，检查更新会失败
+        let updater = Arc::new(DatabaseUpdater::new(
+            "http://127.0.0.1:1".to_string(),
+            dir.path().join("db"),
+            dir.path().join("backup"),
+        ));
+        let manager = UpdateJobManager::new(updater);
+
+        let job = manager.submit(true, false).unwrap();
+        assert!(job.job_id.starts_with("UPD"));
+        assert!(manager.submit(false, true).is_err());
+
+        let mut finished = None;
+        for _ in 0..100 {
+            let current = manager.get(&job.job_id).unwrap();
+            if current.status.is_finished() {
+                finished = Some(current);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let finished = finished.expect("更新任务未结束");
+        assert_eq!(finished.status, JobStatus::Failed);
+        assert!(finished.error.is_some());
+        assert!(finished.update.is_none());
+        assert_eq!(manager.status().last_job.unwrap().job_id, job.job_id);
+
+        // 上一个任务结束后可以再次提交
+        let next = manager.submit(true, false).unwrap();
+        assert_eq!(manager.list()[0].job_id, next.job_id);
+    }
+
+    #[tokio::test]
+    async fn test_config_patch_validates_and_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let mut initial = ScannerConfig::default();
+        initial.api.api_key = "secret-key".to_string();
+        let shared = Arc::new(tokio::sync::RwLock::new(initial));
+        let reloads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&reloads);
+        let mut manager = ConfigManager::new(Arc::clone(&shared)).persist_to(config_path.clone());
+        manager.on_reload(
+            "performance",
+            Arc::new(move |config| {
+                assert_eq!(config.performance.thread_pool_size, 3);
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }),
+        );
+
+        let current = manager.current().await.unwrap();
+        assert_eq!(current["api"]["api_key"], REDACTED);
+
+        // 传回隐藏的密钥不会覆盖原值
+        let mut patch = serde_json::json!({
+            "performance": { "thread_pool_size": 3 },
+            "logging": { "level": "debug" },
+        });
+        patch["api"] = current["api"].clone();
+        let update = manager.apply_patch(&patch).await.unwrap();
+        let mut changed = update.changed.clone();
+        changed.sort();
+        assert_eq!(changed, vec!["logging".to_string(), "performance".to_string()]);
+        assert_eq!(update.reloaded, vec!["performance".to_string()]);
+        assert_eq!(update.restart_required, vec!["logging".to_string()]);
+        assert_eq!(reloads.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(shared.read().await.api.api_key, "secret-key");
+        assert_eq!(ScannerConfig::load(&config_path).unwrap().performance.thread_pool_size, 3);
+
+        for invalid in [
+            serde_json::json!({ "performance": { "thread_pool_size": 0 } }),
+            serde_json::json!({ "logging": { "level": "verbose" } }),
+            serde_json::json!({ "scan_modes": { "max_file_size": "big" } }),
+            serde_json::json!(["not", "an", "object"]),
+        ] {
+            assert!(manager.apply_patch(&invalid).await.is_err());
+        }
+        assert_eq!(shared.read().await.logging.level, "debug");
+        assert!(manager.apply_patch(&serde_json::json!({})).await.unwrap().changed.is_empty());
+
+        // 重新加载配置文件 (SIGHUP) 时不写回配置文件
+        let mut reloaded = shared.read().await.clone();
+        reloaded.logging.level = "warn".to_string();
+        let update = manager.replace(reloaded).await.unwrap();
+        assert_eq!(update.restart_required, vec!["logging".to_string()]);
+        assert_eq!(shared.read().await.logging.level, "warn");
+        assert_eq!(ScannerConfig::load(&config_path).unwrap().logging.level, "debug");
+    }
+
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value.as_str()) {
+                        ("$ref", Some(reference)) => refs.push(reference.to_string()),
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|item| collect_refs(item, refs)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec_is_complete() {
+        let spec = openapi::spec();
+        assert_eq!(spec["openapi"], "3.0.3");
+        let scan = &spec["paths"]["/api/v1/scan"]["post"];
+        assert_eq!(scan["x-required-scope"], "scan");
+        assert!(scan["responses"]["202"].is_object());
+        assert!(spec["paths"]["/api/v1/threats"]["get"]["parameters"].as_array().unwrap().len() >= 7);
+        assert!(spec["paths"]["/health"]["get"]["security"].as_array().unwrap().is_empty());
+
+        // 所有引用的结构都已定义
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        assert!(refs.iter().any(|r| r.ends_with("/ScanResult")));
+        for reference in refs {
+            let name = reference.strip_prefix("#/components/schemas/").expect(&reference);
+            assert!(spec["components"]["schemas"][name].is_object(), "未定义的结构: {}", name);
+        }
+
+        let docs = warp::test::request().path("/api/docs").reply(&ApiServer::docs_routes()).await;
+        assert_eq!(docs.status(), 200);
+        assert!(String::from_utf8_lossy(docs.body()).contains("/api/docs/openapi.json"));
+        let json = warp::test::request().path("/api/docs/openapi.json").reply(&ApiServer::docs_routes()).await;
+        let served: serde_json::Value = serde_json::from_slice(json.body()).unwrap();
+        assert_eq!(served, spec);
+    }
+
+    #[tokio::test]
+    async fn test_rejections_are_json_responses() {
+        let auth = Arc::new(TokenAuthority::ephemeral(&ApiConfig::default()));
+        let guarded = warp::path!("guarded")
+            .and(warp::get())
+            .and(ApiServer::require(&auth, Scope::Admin))
+            .map(|_| warp::reply());
+        let body = warp::path!("body")
+            .and(warp::post())
+            .and(warp::body::json::<crate::api::TokenRequest>())
+            .map(|_| warp::reply());
+        let missing = warp::path!("missing").and_then(|| async { Err::<warp::reply::Json, _>(warp::reject::custom(ApiError::NotFound)) });
+        let invalid = warp::path!("invalid").and_then(|| async {
+            Err::<warp::reply::Json, _>(warp::reject::custom(ApiError::ValidationError("路径必须是绝对路径".to_string())))
+        });
+        let route = guarded.or(body).or(missing).or(invalid).recover(ApiServer::handle_rejection);
+
+        let cases = [
+            (warp::test::request().path("/nowhere"), 404, "接口不存在"),
+            (warp::test::request().path("/guarded"), 401, "未授权"),
+            (warp::test::request().method("DELETE").path("/body"), 405, "请求方法"),
+            (warp::test::request().method("POST").path("/body").body("{\"subject\": 1}"), 400, "请求体格式错误"),
+            (warp::test::request().path("/missing"), 404, "资源不存在"),
+            (warp::test::request().path("/invalid"), 400, "绝对路径"),
+        ];
+        for (request, status, message) in cases {
+            let response = request.reply(&route).await;
+            assert_eq!(response.status(), status);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["success"], false);
+            assert!(body["error"].as_str().unwrap().contains(message), "{}", body["error"]);
+            if status == 401 {
+                assert_eq!(response.headers()["www-authenticate"], "Bearer");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_named_api_keys_roles_and_audit() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys_file = dir.path().join("api-keys.yaml");
+        let mut config = ApiConfig::default();
+        config.keys.push(ApiKeyConfig {
+            name: "grafana".to_string(),
+            key_sha256: hex::encode(openssl::sha::sha256(b"grafana-secret")),
+            role: ApiRole::Viewer,
+            revoked: false,
+        });
+        let security = Arc::new(SecurityManager::new(dir.path().to_path_buf(), 100, 60));
+        let mut authority = TokenAuthority::ephemeral(&config).with_security_manager(security);
+        authority.load_keys_file(&keys_file).unwrap();
+        let auth = Arc::new(authority);
+
+        let ci = auth.create_key("ci-pipeline", ApiRole::Operator).unwrap();
+        assert!(auth.create_key("ci-pipeline", ApiRole::Admin).is_err());
+        assert!(auth.create_key("api-key", ApiRole::Admin).is_err());
+        let saved = std::fs::read_to_string(&keys_file).unwrap();
+        assert!(saved.contains("ci-pipeline") && !saved.contains(&ci.key) && !saved.contains("grafana"));
+
+        // 重新加载密钥文件后仍然可用
+        let mut reloaded = TokenAuthority::ephemeral(&config);
+        reloaded.load_keys_file(&keys_file).unwrap();
+        assert_eq!(reloaded.list_keys().len(), 2);
+        assert_eq!(reloaded.authenticate(None, Some(&ci.key), "192.0.2.20").unwrap().sub, "ci-pipeline");
+
+        let scan_filter = ApiServer::require(&auth, Scope::Scan).map(|claims: crate::api::auth::Claims| claims.sub);
+        let read_filter = ApiServer::require(&auth, Scope::ReadOnly).map(|claims: crate::api::auth::Claims| claims.sub);
+        let admin_filter = ApiServer::require(&auth, Scope::Admin).map(|claims: crate::api::auth::Claims| claims.sub);
+        let viewer = warp::test::request().path("/api/v1/status").header("X-API-Key", "grafana-secret");
+        assert_eq!(viewer.filter(&read_filter).await.unwrap(), "grafana");
+        let viewer = warp::test::request().path("/api/v1/scan").method("POST").header("X-API-Key", "grafana-secret");
+        assert!(viewer.filter(&scan_filter).await.is_err());
+        let operator = warp::test::request().path("/api/v1/scan").method("POST").header("X-API-Key", &ci.key);
+        assert_eq!(operator.filter(&scan_filter).await.unwrap(), "ci-pipeline");
+        let operator = warp::test::request().path("/api/v1/config").method("PATCH").header("X-API-Key", &ci.key);
+        assert!(operator.filter(&admin_filter).await.is_err());
+
+        // 吊销后立即失效，其他密钥不受影响
+        let revoked = auth.revoke_key("ci-pipeline").unwrap().unwrap();
+        assert!(revoked.revoked && revoked.persistent);
+        assert!(auth.revoke_key("missing").unwrap().is_none());
+        assert!(matches!(auth.authenticate(None, Some(&ci.key), "192.0.2.20"), Err(ApiError::Unauthorized)));
+        assert!(auth.authenticate(None, Some("grafana-secret"), "192.0.2.20").is_ok());
+        assert!(!auth.revoke_key("grafana").unwrap().unwrap().persistent);
+        let mut reloaded = TokenAuthority::ephemeral(&ApiConfig::default());
+        reloaded.load_keys_file(&keys_file).unwrap();
+        assert!(reloaded.authenticate(None, Some(&ci.key), "192.0.2.20").is_err());
+
+        let audit = std::fs::read_to_string(dir.path().join("audit.log")).unwrap();
+        let lines: Vec<&str> = audit.lines().collect();
+        assert!(lines.iter().any(|line| line.contains("ACTION=API_REQUEST") && line.contains("grafana") && line.contains("GET /api/v1/status")));
+        assert!(lines.iter().any(|line| line.contains("ACTION=API_FORBIDDEN") && line.contains("grafana") && line.contains("POST /api/v1/scan")));
+        assert!(lines.iter().any(|line| line.contains("ACTION=API_REQUEST") && line.contains("ci-pipeline") && line.contains("POST /api/v1/scan")));
+        assert!(lines.iter().any(|line| line.contains("ACTION=API_FORBIDDEN") && line.contains("ci-pipeline") && line.contains("PATCH /api/v1/config")));
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_each_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("database");
+        let quarantine = dir.path().join("quarantine");
+        std::fs::create_dir_all(&database).unwrap();
+        std::fs::create_dir_all(&quarantine).unwrap();
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+
+        let mut checker = HealthChecker::new(HealthCheckConfig {
+            max_database_age_hours: 24,
+            min_free_space_mb: 1,
+            monitor_stall_secs: 30,
+        });
+        checker.set_signature_database(Arc::clone(&signature_db), database.clone());
+        checker.add_directory("quarantine", quarantine.clone());
+        checker.add_directory("reports", dir.path().join("missing"));
+        let checker = Arc::new(checker);
+        let route = ApiServer::health_routes(Some(Arc::clone(&checker)));
+
+        // 只有内置特征码，报告目录不存在
+        let response = warp::test::request().path("/health").reply(&route).await;
+        assert_eq!(response.status(), 503);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["success"], false);
+        let status_of = |body: &serde_json::Value, name: &str| {
+            body["data"]["checks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|check| check["name"] == name)
+                .map(|check| check["status"].as_str().unwrap().to_string())
+        };
+        assert_eq!(status_of(&body, "signature_database").as_deref(), Some("fail"));
+        assert_eq!(status_of(&body, "directory:quarantine").as_deref(), Some("pass"));
+        assert_eq!(status_of(&body, "directory:reports").as_deref(), Some("fail"));
+        assert!(status_of(&body, "disk_space").is_some());
+        assert!(status_of(&body, "monitor").is_none());
+
+        std::fs::write(database.join("daily.cvd"), b"signatures").unwrap();
+        let mut checker = HealthChecker::new(HealthCheckConfig {
+            min_free_space_mb: 1,
+            ..Default::default()
+        });
+        checker.set_signature_database(signature_db, database);
+        checker.add_directory("quarantine", quarantine);
+        let report = checker.run().await;
+        assert!(report.is_healthy(), "{:?}", report);
+        assert_eq!(report.checks[0].status, CheckStatus::Pass);
+
+        let response = warp::test::request().path("/health").reply(&ApiServer::health_routes(None)).await;
+        assert_eq!(response.status(), 200);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::api::jobs::JobStatus;
+use crate::update::{DatabaseUpdater, UpdateInfo};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// 保留的已结束更新任务数
+const MAX_FINISHED_JOBS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct UpdateJobInfo {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub check_only: bool,
+    pub force: bool,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // 检查到的新版本，已是最新时为 None
+    pub version_available: Option<String>,
+    // 实际安装的更新，只检查或没有新版本时为 None
+    pub update: Option<UpdateInfo>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct UpdateStatusReport {
+    pub in_progress: bool,
+    pub current_version: String,
+    pub latest_version: String,
+    pub last_update: Option<DateTime<Utc>>,
+    pub next_update: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub last_job: Option<UpdateJobInfo>,
+}
+
+// 通过 API 触发的病毒库更新。同一时间只运行一个更新，更新器正在更新时 (如定时更新) 拒绝新任务
+pub struct UpdateJobManager {
+    updater: Arc<DatabaseUpdater>,
+    // 按创建时间从旧到新排列
+    jobs: Arc<Mutex<Vec<UpdateJobInfo>>>,
+}
+
+impl UpdateJobManager {
+    pub fn new(updater: Arc<DatabaseUpdater>) -> Self {
+        Self {
+            updater,
+            jobs: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // 需要在 tokio 运行时中调用。force 为 false 时只在检查到新版本后下载
+    pub fn submit(&self, check_only: bool, force: bool) -> Result<UpdateJobInfo, anyhow::Error> {
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.iter().any(|job| !job.status.is_finished()) || self.updater.get_status().in_progress {
+                return Err(anyhow::anyhow!("更新已在进行中"));
+            }
+            // 此时所有任务都已结束
+            if jobs.len() >= MAX_FINISHED_JOBS {
+                let excess = jobs.len() + 1 - MAX_FINISHED_JOBS;
+                jobs.drain(..excess);
+            }
+            let job = UpdateJobInfo {
+                job_id: format!("UPD{:08}", rand::thread_rng().gen::<u32>()),
+                status: JobStatus::Queued,
+                check_only,
+                force,
+                created_at: Utc::now(),
+                started_at: None,
+                finished_at: None,
+                version_available: None,
+                update: None,
+                error: None,
+            };
+            jobs.push(job.clone());
+            job
+        };
+
+        let updater = Arc::clone(&self.updater);
+        let jobs = Arc::clone(&self.jobs);
+        let job_id = job.job_id.clone();
+        tokio::spawn(async move {
+            Self::modify(&jobs, &job_id, |job| {
+                job.status = JobStatus::Running;
+                job.started_at = Some(Utc::now());
+            });
+            log::info!("更新任务 {} 开始", job_id);
+
+            let outcome = if check_only {
+                updater.check_for_updates().await.map(|version| (version, None))
+            } else if force {
+                updater
+                    .perform_update()
+                    .await
+                    .map(|info| (Some(info.version.clone()), Some(info)))
+            } else {
+                match updater.check_for_updates().await {
+                    Ok(Some(version)) => updater.perform_update().await.map(|info| (Some(version), Some(info))),
+                    Ok(None) => Ok((None, None)),
+                    Err(e) => Err(e),
+                }
+            };
+
+            Self::modify(&jobs, &job_id, |job| {
+                job.finished_at = Some(Utc::now());
+                match outcome {
+                    Ok((version, update)) => {
+                        job.status = JobStatus::Completed;
+                        job.version_available = version;
+                        job.update = update;
+                    }
+                    Err(e) => {
+                        log::warn!("更新任务 {} 失败: {:#}", job.job_id, e);
+                        job.status = JobStatus::Failed;
+                        job.error = Some(format!("{:#}", e));
+                    }
+                }
+            });
+            log::info!("更新任务 {} 结束", job_id);
+        });
+
+        Ok(job)
+    }
+
+    fn modify(jobs: &Mutex<Vec<UpdateJobInfo>>, job_id: &str, update: impl FnOnce(&mut UpdateJobInfo)) {
+        if let Some(job) = jobs.lock().unwrap().iter_mut().find(|job| job.job_id == job_id) {
+            update(job);
+        }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<UpdateJobInfo> {
+        self.jobs.lock().unwrap().iter().find(|job| job.job_id == job_id).cloned()
+    }
+
+    // 按创建时间从新到旧排列
+    pub fn list(&self) -> Vec<UpdateJobInfo> {
+        self.jobs.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    pub fn status(&self) -> UpdateStatusReport {
+        let status = self.updater.get_status();
+        UpdateStatusReport {
+            in_progress: status.in_progress,
+            current_version: status.current_version,
+            latest_version: status.latest_version,
+            last_update: status.last_update.map(to_utc),
+            next_update: status.next_update.map(to_utc),
+            error: status.error,
+            last_job: self.jobs.lock().unwrap().last().cloned(),
+        }
+    }
+
+    pub fn history(&self) -> Vec<UpdateInfo> {
+        self.updater.get_update_history()
+    }
+}
+
+// Instant 不能序列化，换算为墙上时间
+fn to_utc(instant: Instant) -> DateTime<Utc> {
+    let now = Instant::now();
+    let offset = match now.checked_duration_since(instant) {
+        Some(elapsed) => -chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero()),
+        None => chrono::Duration::from_std(instant - now).unwrap_or_else(|_| chrono::Duration::zero()),
+    };
+    Utc::now() + offset
+}
@@ -1,13 +1,31 @@
-use crate::config::ScannerConfig;
-use crate::scanner::{ScannerEngine, ScanOptions, ScanMode, SignatureDatabase};
+pub mod output;
+
+use crate::config::{DetectionAction, ScannerConfig, ScheduledScanConfig};
+use crate::core::alerts::{self, AlertDispatcher};
+use crate::core::schedule::{self, CronSchedule};
+use crate::core::security::QuarantineManager;
+use crate::core::VirusScanner;
+use crate::scanner::custom;
+use crate::scanner::selftest::run_selftest;
+use crate::scanner::{Allowlist, CvdHeader, ImageScanner, persistence_locations, RootkitChecker, ScanCheckpoint, ScannerEngine, ScanOptions, ScanMode, ScanResult, SignatureDatabase, UrlScanner, VerdictCache};
 use crate::update::{DatabaseUpdater, UpdateScheduler};
-use crate::report::{ReportGenerator, ReportFormat};
-use crate::monitor::FileMonitor;
+use crate::report::{load_report, DetectionLogger, ReportGenerator, ReportFormat, ReportIndex, StoredThreat, ThreatReport, ThreatStore};
+use crate::milter::MilterServer;
+use crate::monitor::{control, on_access_scan_options, ControlRequest, ControlServer, EventFilter, EventJournal, EventQuery, FileMonitor, MonitorEvent, MonitorHandle, MonitorStatus, OnAccessScanner};
+use crate::utils::format_duration;
+use crate::utils::i18n;
+use crate::t;
+use crate::utils::logging::{AuditLogger, Logger};
+use crate::utils::service::PidFile;
 use anyhow::{Context, Result};
 use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use log::LevelFilter;
+use output::{print_json, OutputFormat};
+use serde_json::json;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "virus-scanner")]
@@ -19,8 +37,20 @@ pub struct Command {
     pub subcommand: SubCommands,
     #[arg(short, long, global = true, help = "指定配置文件路径")]
     pub config: Option<PathBuf>,
-    #[arg(short, long, global = true, help = "显示详细输出")]
+    #[arg(short, long, global = true, help = "显示详细输出 (控制台输出 debug 及以上级别的日志)")]
     pub verbose: bool,
+    #[arg(short, long, global = true, conflicts_with = "verbose", help = "控制台只输出错误日志")]
+    pub quiet: bool,
+    #[arg(
+        long,
+        global = true,
+        value_parser = ["trace", "debug", "info", "warn", "error", "off"],
+        help = "日志级别，覆盖配置文件中的 logging.level"
+    )]
+    pub log_level: Option<String>,
+    // report 等子命令有自己的 --output 文件参数，所以该参数只能放在子命令之前
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, help = "输出格式: text, json (放在子命令之前，如 virus-scanner --output json scan)")]
+    pub output: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -35,15 +65,33 @@ pub enum SubCommands {
     Report(ReportArgs),
     #[command(name = "status", about = "查看系统状态")]
     Status(StatusArgs),
+    #[command(name = "milter", about = "启动邮件网关milter服务")]
+    Milter(MilterArgs),
+    #[command(name = "selftest", about = "使用 EICAR 测试文件验证检测与隔离功能")]
+    Selftest,
+    #[command(name = "serve", about = "启动 REST API 服务")]
+    Serve(ServeArgs),
+    #[command(name = "quarantine", about = "管理隔离区中的文件")]
+    Quarantine(QuarantineArgs),
+    #[command(name = "daemon", about = "以服务方式运行文件监控、定时更新和 API")]
+    Daemon(DaemonArgs),
+    #[command(name = "database", about = "查看和管理病毒库特征码")]
+    Database(DatabaseArgs),
+    #[command(name = "config", about = "创建、校验、查看和修改配置文件")]
+    Config(ConfigArgs),
+    #[command(name = "schedule", about = "管理由 daemon 运行的定时扫描")]
+    Schedule(ScheduleArgs),
+    #[command(name = "benchmark", about = "测量病毒库加载时间、不同线程数的扫描吞吐量和缓存命中率")]
+    Benchmark(BenchmarkArgs),
 }
 
 #[derive(Args)]
 pub struct ScanArgs {
-    #[arg(long, short = 't', help = "扫描类型: quick(快速), full(全盘), custom(自定义)")]
+    #[arg(long, short = 't', help = "扫描类型: quick(快速), full(全盘), custom(自定义), persistence(持久化位置审计)")]
     pub scan_type: Option<String>,
     #[arg(long, short = 'p', help = "指定扫描路径")]
     pub paths: Vec<PathBuf>,
-    #[arg(long, short = 'e', help = "排除路径")]
+    #[arg(long, short = 'e', help = "排除路径 (支持通配符和 regex: 正则规则)")]
     pub exclude: Vec<PathBuf>,
     #[arg(long, help = "线程数")]
     pub threads: Option<usize>,
@@ -51,6 +99,22 @@ pub struct ScanArgs {
     pub report: bool,
     #[arg(long, short = 'f', help = "报告格式: json, yaml, html, text")]
     pub format: Option<String>,
+    #[arg(long, help = "扫描容器镜像 (镜像引用或 docker save/OCI 镜像包)")]
+    pub image: Option<String>,
+    #[arg(long, help = "下载并扫描 http(s) URL 指向的对象", conflicts_with_all = ["paths", "image", "scan_type"])]
+    pub url: Option<String>,
+    #[arg(long, help = "定期写入扫描检查点的文件路径")]
+    pub checkpoint: Option<PathBuf>,
+    #[arg(long, short = 'a', help = "发现威胁后的处理: report, quarantine, delete, clean")]
+    pub action: Option<String>,
+    #[arg(long, help = "扫描最长时间 (秒)，到时停止并保存检查点")]
+    pub max_duration: Option<u64>,
+    #[arg(long, help = "从检查点文件恢复中断的扫描", conflicts_with_all = ["scan_type", "paths", "image", "url"])]
+    pub resume: Option<PathBuf>,
+    #[arg(long, help = "自定义扫描后也进行 Rootkit 检查")]
+    pub rootkit: bool,
+    #[arg(long, help = "只列出将被隔离或删除的文件，不做任何处理")]
+    pub dry_run: bool,
 }
 
 #[derive(Args)]
@@ -61,34 +125,317 @@ pub struct UpdateArgs {
     pub schedule: bool,
     #[arg(long, help = "仅检查更新")]
     pub check_only: bool,
+    #[command(subcommand)]
+    pub action: Option<UpdateAction>,
+}
+
+#[derive(Subcommand)]
+pub enum UpdateAction {
+    #[command(name = "list-backups", about = "列出病毒库备份")]
+    ListBackups,
+    #[command(name = "rollback", about = "从备份恢复病毒库")]
+    Rollback {
+        #[arg(long, help = "备份 ID 或 latest")]
+        to: String,
+        #[arg(long, help = "只列出将恢复和删除的病毒库文件，不做任何修改")]
+        dry_run: bool,
+    },
 }
 
 #[derive(Args)]
 pub struct MonitorArgs {
-    #[arg(long, short = 's', help = "启动监控")]
+    #[arg(long, short = 's', help = "在后台启动监控")]
     pub start: bool,
-    #[arg(long, short = 'p', help = "停止监控")]
+    #[arg(long, short = 'p', help = "停止运行中的监控")]
     pub stop: bool,
+    #[arg(long, help = "查看运行中的监控状态")]
+    pub status: bool,
+    #[arg(long, help = "与 --start 一起使用，在前台运行 (供 systemd 等进程管理器使用)")]
+    pub foreground: bool,
+    #[arg(long, help = "查询监控事件日志")]
+    pub events: bool,
+    #[arg(long, help = "与 --events 一起使用，只显示最近一段时间的事件 (如 30m, 1h, 2d)")]
+    pub since: Option<String>,
+    #[arg(long, help = "与 --events 一起使用，只显示该路径下的事件")]
+    pub path: Option<PathBuf>,
+    #[arg(long, help = "与 --events 一起使用，最多显示的事件数", default_value_t = 100)]
+    pub limit: usize,
     #[arg(long, help = "监控路径")]
     pub watch: Vec<PathBuf>,
+    #[command(subcommand)]
+    pub action: Option<MonitorAction>,
+}
+
+#[derive(Subcommand)]
+pub enum MonitorAction {
+    #[command(name = "add-path", about = "添加监控路径")]
+    AddPath {
+        #[arg(help = "要监控的目录")]
+        path: PathBuf,
+    },
+    #[command(name = "remove-path", about = "移除监控路径")]
+    RemovePath {
+        #[arg(help = "要移除的监控目录")]
+        path: PathBuf,
+    },
 }
 
 #[derive(Args)]
 pub struct ReportArgs {
-    #[arg(long, short = 'i', help = "输入报告文件")]
-    pub input: PathBuf,
-    #[arg(long, short = 'f', help = "报告格式: json, yaml, html, text")]
-    pub format: String,
-    #[arg(long, short = 'o', help = "输出报告文件")]
-    pub output: PathBuf,
+    #[command(subcommand)]
+    pub action: ReportAction,
+}
+
+#[derive(Subcommand)]
+pub enum ReportAction {
+    #[command(name = "list", about = "列出报告目录中的报告")]
+    List {
+        #[arg(long, help = "最多显示的条数", default_value_t = 20)]
+        limit: usize,
+    },
+    #[command(name = "convert", about = "将 JSON 或 YAML 报告转换为其他格式")]
+    Convert {
+        #[arg(long, short = 'i', help = "输入报告文件 (JSON 或 YAML，按扩展名或内容自动识别)")]
+        input: PathBuf,
+        #[arg(long, short = 't', help = "目标格式: json, yaml, html, text")]
+        to: String,
+        #[arg(long, short = 'o', help = "输出文件，- 为标准输出 (默认保存到报告目录)")]
+        output: Option<PathBuf>,
+    },
+    #[command(name = "show", about = "显示报告内容")]
+    Show {
+        #[arg(help = "报告 ID (或唯一的前缀)")]
+        id: String,
+        #[arg(long, short = 'f', help = "显示格式: text, json, yaml, html (默认 text)")]
+        format: Option<String>,
+    },
 }
 
 #[derive(Args)]
 pub struct StatusArgs {
-    #[arg(long, short = 'd', help = "显示病毒库信息")]
+    #[arg(long, short = 'd', help = "显示各病毒库文件的版本和内存占用")]
     pub database: bool,
     #[arg(long, short = 's', help = "显示系统信息")]
     pub system: bool,
+    #[arg(long, help = "以 JSON 格式输出，与 --output json 相同")]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct MilterArgs {
+    #[arg(long, short = 'l', help = "监听地址: inet:主机:端口 或 unix:/套接字路径")]
+    pub listen: Option<String>,
+    #[arg(long, help = "发现病毒时的处理: reject, quarantine, discard, tempfail, accept")]
+    pub on_infected: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(long, short = 'l', help = "监听地址，如 0.0.0.0:8080 (默认使用配置文件中的 api.listen)")]
+    pub addr: Option<String>,
+    #[arg(long, help = "在同一进程中启动文件监控")]
+    pub monitor: bool,
+    #[arg(long, help = "按 update.schedule 定时更新病毒库")]
+    pub schedule_updates: bool,
+}
+
+#[derive(Args)]
+pub struct QuarantineArgs {
+    #[command(subcommand)]
+    pub action: QuarantineAction,
+}
+
+#[derive(Subcommand)]
+pub enum QuarantineAction {
+    #[command(name = "list", about = "列出隔离文件")]
+    List,
+    #[command(name = "restore", about = "恢复隔离文件并从隔离区删除")]
+    Restore {
+        #[arg(help = "隔离文件 ID")]
+        id: String,
+        #[arg(long, help = "恢复到指定路径或目录 (默认恢复到原位置)")]
+        to: Option<PathBuf>,
+        #[arg(long, help = "覆盖已存在的文件")]
+        force: bool,
+    },
+    #[command(name = "delete", about = "永久删除隔离文件")]
+    Delete {
+        #[arg(help = "隔离文件 ID")]
+        id: String,
+    },
+    #[command(name = "purge", about = "删除隔离时间超过指定时长的文件")]
+    Purge {
+        #[arg(long, help = "时长，如 30d, 12h")]
+        older_than: String,
+        #[arg(long, help = "只列出将被删除的隔离文件，不做任何修改")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    #[arg(long, short = 'l', help = "API 监听地址 (默认使用配置文件中的 api.listen)")]
+    pub addr: Option<String>,
+    #[arg(long, help = "不启动 API 服务")]
+    pub no_api: bool,
+    #[arg(long, help = "PID 文件路径 (默认使用配置文件中的 daemon.pid_file)")]
+    pub pid_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DatabaseArgs {
+    #[command(subcommand)]
+    pub action: DatabaseAction,
+}
+
+#[derive(Subcommand)]
+pub enum DatabaseAction {
+    #[command(name = "info", about = "显示病毒库版本和各类特征码数量")]
+    Info,
+    #[command(name = "search", about = "按名称查找特征码")]
+    Search {
+        #[arg(help = "特征码名称或 ID 的一部分 (不区分大小写)")]
+        name: String,
+        #[arg(long, help = "最多显示的条数", default_value_t = 50)]
+        limit: usize,
+    },
+    #[command(name = "add-custom", about = "添加本地自定义特征码 (特征码文件或样本文件)")]
+    AddCustom {
+        #[arg(help = ".ndb/.ldb/.hdb/.hsb/.imp 特征码文件，其他文件按 SHA256 生成哈希特征码")]
+        file: PathBuf,
+        #[arg(long, help = "为样本文件生成的特征码名称")]
+        name: Option<String>,
+    },
+    #[command(name = "remove", about = "删除本地自定义特征码")]
+    Remove {
+        #[arg(help = "特征码 ID")]
+        id: String,
+    },
+    #[command(name = "verify", about = "校验病毒库文件的完整性")]
+    Verify,
+}
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    #[command(name = "init", about = "写入默认配置文件")]
+    Init {
+        #[arg(help = "配置文件路径 (默认使用 -c 指定的路径)")]
+        path: Option<PathBuf>,
+        #[arg(long, help = "覆盖已存在的配置文件")]
+        force: bool,
+    },
+    #[command(name = "validate", about = "检查配置文件的语法、类型和取值，报告出错的行")]
+    Validate {
+        #[arg(help = "配置文件路径 (默认使用 -c 指定的路径)")]
+        path: Option<PathBuf>,
+    },
+    #[command(name = "show", about = "显示配置")]
+    Show {
+        #[arg(long, help = "显示应用 VIRUS_SCANNER__* 环境变量覆盖后实际使用的配置")]
+        effective: bool,
+    },
+    #[command(name = "set", about = "修改配置文件中的一项 (文件按当前配置重新生成，不保留注释)")]
+    Set {
+        #[arg(help = "点分隔的配置项，如 performance.thread_pool_size、alerts.webhooks.0.url")]
+        key: String,
+        #[arg(help = "取值，按 YAML 解析，如 8、true、[log, tmp]")]
+        value: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ScheduleArgs {
+    #[command(subcommand)]
+    pub action: ScheduleAction,
+}
+
+#[derive(Subcommand)]
+pub enum ScheduleAction {
+    #[command(name = "list", about = "列出定时扫描及下次、上次运行时间")]
+    List,
+    #[command(name = "add", about = "添加定时扫描")]
+    Add {
+        #[arg(help = "定时扫描名称")]
+        name: String,
+        #[arg(long, help = "cron 表达式 (分 时 日 月 周)，如 \"0 3 * * 0\" 或 @daily")]
+        cron: String,
+        #[arg(long = "type", short = 't', default_value = "quick", help = "扫描类型: quick, full, custom, persistence")]
+        scan_type: String,
+        #[arg(long, short = 'p', help = "custom 扫描的路径")]
+        paths: Vec<PathBuf>,
+        #[arg(long, help = "添加但暂不启用")]
+        disabled: bool,
+    },
+    #[command(name = "remove", about = "删除定时扫描")]
+    Remove {
+        #[arg(help = "定时扫描名称")]
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct BenchmarkArgs {
+    #[arg(long, short = 'p', help = "用于测试的目录，建议包含有代表性的文件")]
+    pub path: PathBuf,
+    #[arg(long, short = 't', value_delimiter = ',', help = "要比较的线程数，如 1,2,4,8 (默认 1 和 performance.thread_pool_size)")]
+    pub threads: Vec<usize>,
+}
+
+// 与 clamscan 一致的退出码，便于脚本和 CI 根据扫描结果分支；执行出错时退出码为 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Clean,
+    Infected,
+    // 扫描过程中有路径或文件无法扫描且没有发现威胁，或命令已经输出了错误详情 (如配置校验失败)，
+    // main 不再输出错误
+    Error,
+}
+
+impl ExitStatus {
+    pub const ERROR_CODE: i32 = 2;
+
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Clean => 0,
+            ExitStatus::Infected => 1,
+            ExitStatus::Error => Self::ERROR_CODE,
+        }
+    }
+
+    // 与 clamscan 相同，发现威胁时优先返回 Infected，否则扫描中有错误 (如路径不存在、文件无法读取) 时返回 Error
+    pub fn for_scan(infected: bool, errors: usize) -> Self {
+        if infected {
+            ExitStatus::Infected
+        } else if errors > 0 {
+            ExitStatus::Error
+        } else {
+            ExitStatus::Clean
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitStatus::Clean => "clean",
+            ExitStatus::Infected => "infected",
+            ExitStatus::Error => "error",
+        }
+    }
+}
+
+// status 命令显示的病毒库、daemon、监控和定时扫描状态
+struct StatusInfo {
+    load_error: Option<String>,
+    updated_at: Option<chrono::DateTime<chrono::Local>>,
+    headers: Vec<(String, CvdHeader)>,
+    daemon_pid: Option<u32>,
+    monitor: Option<MonitorStatus>,
+    schedules: Vec<schedule::ScheduleSummary>,
 }
 
 impl Command {
@@ -96,21 +443,93 @@ impl Command {
         Command::parse()
     }
 
-    pub async fn execute(matches: &Command) -> Result<()> {
+    // --output 或子命令自己的 --json
+    pub fn output_format(&self) -> OutputFormat {
+        match self.subcommand {
+            SubCommands::Status(ref args) if args.json => OutputFormat::Json,
+            _ => self.output,
+        }
+    }
+
+    pub async fn execute(matches: &Command) -> Result<ExitStatus> {
         let config_path = matches.config.clone()
             .unwrap_or_else(|| PathBuf::from("/etc/virus-scanner/config.yaml"));
+        let output = matches.output_format();
+
+        // 配置文件可能不存在或有错误，不能先加载
+        if let SubCommands::Config(ref args) = matches.subcommand {
+            return Self::handle_config(args, &config_path, output);
+        }
 
-        let config = ScannerConfig::load(&config_path)
+        let mut config = ScannerConfig::load(&config_path)
             .with_context(|| format!("无法加载配置文件: {:?}", config_path))?;
+        let overrides = config.apply_env_overrides()?;
+        if let Some(language) = config.language {
+            i18n::set_language(language);
+        }
+        Self::init_logging(matches, &mut config)?;
+        for item in overrides {
+            log::info!("配置项 {} 由环境变量 {} 覆盖", item.key, item.variable);
+        }
 
         let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.set_regex_time_budget(Duration::from_millis(config.performance.regex_time_budget_ms));
+        signature_db.set_scan_buffer_size(config.performance.scan_buffer_size);
+        signature_db.set_use_mmap(config.performance.use_mmap);
+        signature_db.load_builtin_signatures().await?;
 
         match &matches.subcommand {
-            SubCommands::Scan(args) => Self::handle_scan(args, &config, &signature_db).await,
-            SubCommands::Update(args) => Self::handle_update(args, &config).await,
-            SubCommands::Monitor(args) => Self::handle_monitor(args, &config).await,
-            SubCommands::Report(args) => Self::handle_report(args, &config).await,
-            SubCommands::Status(args) => Self::handle_status(args, &config, &signature_db).await,
+            SubCommands::Scan(args) => Self::handle_scan(args, &config, &signature_db, output).await,
+            SubCommands::Update(args) => Self::handle_update(args, &config, output).await.map(|_| ExitStatus::Clean),
+            SubCommands::Monitor(args) => {
+                Self::handle_monitor(args, &config, &config_path, &signature_db, output).await.map(|_| ExitStatus::Clean)
+            }
+            SubCommands::Report(args) => Self::handle_report(args, &config, output).await.map(|_| ExitStatus::Clean),
+            SubCommands::Status(args) => {
+                Self::handle_status(args, &config, &signature_db, output).await.map(|_| ExitStatus::Clean)
+            }
+            SubCommands::Milter(args) => {
+                Self::handle_milter(args, &config, &signature_db).await.map(|_| ExitStatus::Clean)
+            }
+            SubCommands::Selftest => Self::handle_selftest(&config, &signature_db).await.map(|_| ExitStatus::Clean),
+            SubCommands::Serve(args) => Self::handle_serve(args, &config, &config_path).await.map(|_| ExitStatus::Clean),
+            SubCommands::Quarantine(args) => Self::handle_quarantine(args, &config).map(|_| ExitStatus::Clean),
+            SubCommands::Daemon(args) => Self::handle_daemon(args, &config, &config_path).await.map(|_| ExitStatus::Clean),
+            SubCommands::Database(args) => {
+                Self::handle_database(args, &config, &signature_db).await.map(|_| ExitStatus::Clean)
+            }
+            SubCommands::Schedule(args) => Self::handle_schedule(args, &config, &config_path, output).map(|_| ExitStatus::Clean),
+            SubCommands::Benchmark(args) => Self::handle_benchmark(args, &config, output).await.map(|_| ExitStatus::Clean),
+            SubCommands::Config(_) => unreachable!("config 子命令在加载配置前处理"),
+        }
+    }
+
+    fn init_logging(matches: &Command, config: &mut ScannerConfig) -> Result<()> {
+        let console = Self::apply_log_flags(matches, config);
+        Logger::init_cli(&config.logging, console).context("无法初始化日志系统")
+    }
+
+    // --log-level 覆盖 logging.level，--verbose 至少记录 debug 级别。控制台默认只输出警告和错误，
+    // 前台运行的服务命令输出与日志文件相同级别的日志。返回控制台的日志级别
+    fn apply_log_flags(matches: &Command, config: &mut ScannerConfig) -> LevelFilter {
+        if let Some(ref level) = matches.log_level {
+            config.logging.level = level.clone();
+        }
+        let mut level = Logger::get_level_filter(&config.logging.level);
+        if matches.verbose {
+            level = level.max(LevelFilter::Debug);
+            config.logging.level = level.to_string().to_lowercase();
+        }
+        let service = matches!(
+            matches.subcommand,
+            SubCommands::Daemon(_) | SubCommands::Serve(_) | SubCommands::Milter(_)
+        );
+        if matches.quiet {
+            LevelFilter::Error
+        } else if matches.verbose || service {
+            level
+        } else {
+            level.min(LevelFilter::Warn)
         }
     }
 
@@ -118,16 +537,25 @@ impl Command {
         args: &ScanArgs,
         config: &ScannerConfig,
         signature_db: &Arc<SignatureDatabase>,
-    ) -> Result<()> {
-        println!("开始病毒扫描...");
+        output: OutputFormat,
+    ) -> Result<ExitStatus> {
+        let text = !output.is_json();
+        if text {
+            println!("{}", t!("scan.started"));
+        }
 
         let database_path = config.update.database_path.clone();
         let backup_path = config.update.backup_path.clone();
-        let updater = Arc::new(DatabaseUpdater::new(
+        let mut updater = DatabaseUpdater::new(
             config.update.mirror_url.clone(),
             database_path.clone(),
             backup_path,
-        ));
+        );
+        updater.set_cdiff_config(config.update.cdiff.clone());
+        updater.set_verify_signatures(config.update.verify_signatures);
+        updater.set_backup_retention(config.update.backup_retention.clone());
+        updater.set_validate_after_update(config.update.validate_after_update);
+        let updater = Arc::new(updater);
 
         updater.check_and_auto_download(&config.update).await?;
 
@@ -135,6 +563,7 @@ impl Command {
             Some("quick") | Some("fast") => ScanMode::Quick,
             Some("full") => ScanMode::Full,
             Some("custom") | None => ScanMode::Custom,
+            Some("persistence") => ScanMode::Persistence,
             _ => return Err(anyhow::anyhow!("无效的扫描类型")),
         };
 
@@ -145,6 +574,7 @@ impl Command {
                     .collect(),
                 ScanMode::Full => vec![PathBuf::from("/")],
                 ScanMode::Custom => vec![PathBuf::from(".")],
+                ScanMode::Persistence => persistence_locations(),
             }
         } else {
             args.paths.clone()
@@ -163,36 +593,204 @@ impl Command {
             exclude_extensions: config.scan_modes.exclude_extensions.clone(),
             max_file_size: config.scan_modes.max_file_size,
             thread_count: args.threads.unwrap_or(config.performance.thread_pool_size),
+            walk_threads: config.performance.walk_threads,
             quick_scan_paths: config.scan_modes.quick_scan_paths.iter()
                 .map(|p| PathBuf::from(p))
                 .collect(),
+            priority_paths: config.scan_modes.priority_paths.iter()
+                .map(|p| PathBuf::from(p))
+                .collect(),
+            use_xattr_markers: config.scan_modes.use_xattr_markers,
+            xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: config.performance.max_read_mb_per_s,
+            idle_io_priority: config.performance.idle_io_priority,
+            max_duration: Some(args.max_duration.unwrap_or(config.scan_modes.max_duration_secs))
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            action: match args.action {
+                Some(ref action) => action.parse()?,
+                None => config.scan_modes.action,
+            },
+            auto_quarantine_min_risk: config.security.auto_quarantine.min_risk(),
+            dry_run: args.dry_run,
         };
 
-        let engine = ScannerEngine::new(Arc::clone(signature_db), scan_options);
+        if let Some(ref image) = args.image {
+            return Self::handle_image_scan(image, scan_options, signature_db, output).await;
+        }
+        if let Some(ref url) = args.url {
+            return Self::handle_url_scan(url, scan_options, config, signature_db, output).await;
+        }
+
+        let (mut engine, scan_mode, paths) = match args.resume {
+            Some(ref checkpoint_path) => {
+                let checkpoint = ScanCheckpoint::load(checkpoint_path)?;
+                if text {
+                    println!("{}", t!("scan.resuming", done = checkpoint.completed.len()));
+                }
+                let engine = ScannerEngine::from_checkpoint(Arc::clone(signature_db), checkpoint);
+                let options = engine.get_options();
+                let (scan_mode, paths) = (options.scan_mode, options.custom_paths.clone());
+                (engine, scan_mode, paths)
+            }
+            None => (ScannerEngine::new(Arc::clone(signature_db), scan_options), scan_mode, paths),
+        };
+
+        engine.set_allowlist(Arc::new(Allowlist::from_config(&config.allowlist)?));
+        match VerdictCache::from_config(&config.scan_modes.verdict_cache) {
+            Ok(Some(cache)) => engine.set_verdict_cache(Arc::new(cache)),
+            Ok(None) => {}
+            Err(e) => log::warn!("扫描结论缓存不可用，本次扫描不使用缓存: {}", e),
+        }
+
+        let options = engine.get_options();
+        if options.auto_quarantine_min_risk.is_some()
+            || matches!(options.action, DetectionAction::Quarantine | DetectionAction::Clean)
+        {
+            engine.set_quarantine_manager(Arc::new(QuarantineManager::from_config(&config.security)?));
+        }
+
+        // 有时间限制的扫描总是写检查点，以便在下一个窗口继续
+        let checkpoint_config = &config.scan_modes.checkpoint;
+        let checkpoint_path = args.resume.clone()
+            .or_else(|| args.checkpoint.clone())
+            .or_else(|| {
+                let time_limited = engine.get_options().max_duration.is_some();
+                (time_limited || (scan_mode == ScanMode::Full && checkpoint_config.enabled_for_full_scan))
+                    .then(|| checkpoint_config.path.clone())
+            });
+        if let Some(ref path) = checkpoint_path {
+            engine.enable_checkpoints(path.clone(), Duration::from_secs(checkpoint_config.interval_secs));
+        }
+
+        if std::io::stderr().is_terminal() {
+            engine.set_progress_interval(Duration::from_millis(config.scan_modes.progress_interval_ms));
+            engine.set_progress_callback(|progress| {
+                let total = if progress.total_is_estimate {
+                    format!("{}+", progress.files_total)
+                } else {
+                    progress.files_total.to_string()
+                };
+                let eta = progress.eta.map(format_duration).unwrap_or_else(|| "--".to_string());
+                let current = progress.current_path.as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                eprint!(
+                    "\r\x1b[2K{}",
+                    t!(
+                        "scan.progress",
+                        percent = format!("{:.1}", progress.percent),
+                        done = progress.files_done,
+                        total = total,
+                        eta = eta,
+                        current = current
+                    )
+                );
+            });
+        }
+
+        // Ctrl+C 时取消扫描而不是直接退出，以便保存检查点
+        let control = engine.scan_control();
+        let interrupt = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("\n{}", t!("scan.interrupt_received"));
+                control.cancel();
+            }
+        });
+
         let start_time = Instant::now();
 
-        let results = engine.start_scan().await?;
+        let results = engine.start_scan().await;
+        interrupt.abort();
+        let results = results?;
+
+        let deadline_reached = engine.deadline_reached();
+        let interrupted = engine.is_cancelled();
+        if text {
+            if deadline_reached {
+                println!("{}", t!("scan.deadline_reached"));
+            }
+            if interrupted {
+                if let Some(ref path) = checkpoint_path {
+                    println!("{}", t!("scan.interrupted", checkpoint = path.display()));
+                }
+            }
+        }
 
         let duration = start_time.elapsed();
         let stats = engine.get_stats();
 
-        println!("\n扫描完成!");
-        println!("扫描文件数: {}", stats.get_files_scanned());
-        println!("发现威胁数: {}", stats.get_threats_found());
-        println!("扫描耗时: {:.2}秒", duration.as_secs_f64());
-        println!("扫描速度: {:.2} MB/s", stats.get_speed_mb_per_s());
-
-        if args.report {
-            let report_generator = ReportGenerator::new(config.report.output_dir.clone());
-            let report = report_generator.generate(
-                &results,
-                &format!("{:?}", scan_mode),
-                &paths,
-                start_time,
-                0.0,
-                signature_db.get_version(),
-            )?;
+        let dry_run = engine.get_options().dry_run;
+        if dry_run {
+            Self::audit_simulated_actions(config, &results, text);
+        }
+
+        if text {
+            println!("\n{}", t!("scan.completed"));
+            println!("{}", t!("scan.files_scanned", count = stats.get_files_scanned()));
+            if stats.get_files_skipped() > 0 {
+                println!("{}", t!("scan.files_skipped", count = stats.get_files_skipped()));
+            }
+            println!("{}", t!("scan.threats_found", count = stats.get_threats_found()));
+            println!("{}", t!("scan.duration", duration = format_duration(duration)));
+            println!("{}", t!("scan.speed", speed = format!("{:.2}", stats.get_speed_mb_per_s())));
+        }
+
+        let rootkit_findings = if args.rootkit || (config.scan_modes.rootkit_check && scan_mode != ScanMode::Custom) {
+            if text {
+                println!("{}", t!("scan.rootkit_checking"));
+            }
+            let findings = tokio::task::spawn_blocking(|| RootkitChecker::default().run()).await?;
+            if text {
+                for finding in &findings {
+                    println!("[{:?}] {}: {}", finding.risk_level, finding.check.as_str(), finding.description);
+                }
+                println!("{}", t!("scan.rootkit_done", count = findings.len()));
+            }
+            findings
+        } else {
+            Vec::new()
+        };
+        let rootkit_detected = !rootkit_findings.is_empty();
+
+        let report_generator = ReportGenerator::new(config.report.output_dir.clone());
+        let mut report = report_generator.generate(
+            &results,
+            &format!("{:?}", scan_mode),
+            &paths,
+            start_time,
+            0.0,
+            signature_db.get_version(),
+        )?;
+        report.add_rootkit_findings(rootkit_findings.clone());
+
+        if let Some(detection_logger) = DetectionLogger::open_or_warn(&config.logging) {
+            detection_logger.log_threats(&report.id, &report.threats);
+        }
+        let stored = if config.report.threat_store.enabled {
+            ThreatStore::from_config(&config.report.threat_store).record_or_warn(&report.id, "scan", &report.threats)
+        } else {
+            report.threats.iter().map(|threat| StoredThreat::new(&report.id, "scan", threat)).collect()
+        };
+        if !stored.is_empty() {
+            match AlertDispatcher::start(&config.alerts) {
+                Ok(Some(dispatcher)) => {
+                    dispatcher.notify(&stored);
+                    dispatcher.shutdown(alerts::SHUTDOWN_TIMEOUT).await;
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("威胁告警不可用: {:#}", e),
+            }
+        }
 
+        let report_path = if args.report {
             let format = match args.format.as_ref().map(|s| s.as_str()) {
                 Some("json") => ReportFormat::Json,
                 Some("yaml") => ReportFormat::Yaml,
@@ -202,77 +800,618 @@ impl Command {
             };
 
             let report_path = report_generator.save(&report, format)?;
-            println!("报告已保存: {:?}", report_path);
+            if text {
+                println!("{}", t!("report.saved", path = format!("{:?}", report_path)));
+            }
+            Some(report_path)
+        } else {
+            None
+        };
+
+        let status = ExitStatus::for_scan(!results.is_empty() || rootkit_detected, stats.get_errors());
+        if output.is_json() {
+            print_json("scan", &json!({
+                "status": status.as_str(),
+                "scan_type": format!("{:?}", scan_mode).to_lowercase(),
+                "paths": paths,
+                "stats": {
+                    "files_scanned": stats.get_files_scanned(),
+                    "files_skipped": stats.get_files_skipped(),
+                    "threats_found": stats.get_threats_found(),
+                    "errors": stats.get_errors(),
+                    "bytes_scanned": stats.get_bytes_scanned(),
+                    "duration_secs": duration.as_secs_f64(),
+                    "speed_mb_per_s": stats.get_speed_mb_per_s(),
+                },
+                "dry_run": dry_run,
+                "interrupted": interrupted,
+                "deadline_reached": deadline_reached,
+                "checkpoint": checkpoint_path.filter(|_| interrupted || deadline_reached),
+                "threats": results,
+                "rootkit_findings": rootkit_findings,
+                "report_id": report.id,
+                "report_path": report_path,
+            }))?;
+        }
+        Ok(status)
+    }
+
+    // 模拟运行时 action_taken 为 would_delete 或 would_quarantine，每个文件记录一条审计日志
+    fn audit_simulated_actions(config: &ScannerConfig, results: &[ScanResult], text: bool) {
+        let mut simulated: Vec<(&Path, &str)> = results
+            .iter()
+            .filter_map(|result| Some((result.file_path.as_path(), result.action_taken.as_deref()?)))
+            .filter(|(_, action)| action.starts_with("would_"))
+            .collect();
+        simulated.sort();
+        simulated.dedup();
+
+        let audit = AuditLogger::new(config.logging.log_dir.clone(), config.security.audit_log_enabled);
+        let user = crate::utils::get_current_user().unwrap_or_else(|_| "unknown".to_string());
+        for (path, action) in &simulated {
+            audit.log("SCAN_DRY_RUN", &user, &format!("action={} path={:?}", action.trim_start_matches("would_"), path));
+        }
+
+        if text {
+            println!("\n{}", t!("scan.dry_run_summary", count = simulated.len()));
+            for (path, action) in &simulated {
+                let action = if *action == "would_delete" { t!("action.delete") } else { t!("action.quarantine") };
+                println!("  [{}] {}", action, path.display());
+            }
+        }
+    }
+
+    async fn handle_selftest(
+        config: &ScannerConfig,
+        signature_db: &Arc<SignatureDatabase>,
+    ) -> Result<()> {
+        println!("{}", t!("selftest.running"));
+
+        let options = ScanOptions {
+            scan_mode: ScanMode::Custom,
+            custom_paths: vec![],
+            exclude_paths: vec![],
+            exclude_extensions: vec![],
+            max_file_size: config.scan_modes.max_file_size,
+            thread_count: 1,
+            walk_threads: 1,
+            quick_scan_paths: vec![],
+            priority_paths: vec![],
+            use_xattr_markers: false,
+            xattr_marker_key_file: None,
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: 0,
+            idle_io_priority: false,
+            max_duration: None,
+            action: DetectionAction::Report,
+            auto_quarantine_min_risk: None,
+            dry_run: false,
+        };
+        let quarantine = QuarantineManager::from_config(&config.security)?;
+        let report = run_selftest(Arc::clone(signature_db), options, &quarantine).await?;
+
+        for check in &report.checks {
+            let status = if check.passed { t!("common.passed") } else { t!("common.failed") };
+            println!("  [{}] {}: {}", status, check.name, check.detail);
+        }
+
+        if !report.passed() {
+            return Err(anyhow::anyhow!("自检失败"));
+        }
+        println!("{}", t!("selftest.passed"));
+        Ok(())
+    }
+
+    async fn handle_benchmark(args: &BenchmarkArgs, config: &ScannerConfig, output: OutputFormat) -> Result<()> {
+        let threads = Self::benchmark_threads(args, config);
+        if !output.is_json() {
+            eprintln!("{}", t!("benchmark.running", path = format!("{:?}", args.path), rounds = threads.len() + 2));
+        }
+        let report = crate::scanner::benchmark::run_benchmark(config, &args.path, &threads).await?;
+        if output.is_json() {
+            return print_json("benchmark", &report);
         }
 
+        println!(
+            "{}",
+            t!(
+                "benchmark.signature_load",
+                secs = format!("{:.2}", report.signature_load_secs),
+                count = report.signature_count,
+                version = report.database_version
+            )
+        );
+        println!("{}", t!("benchmark.data", files = report.files, size = crate::utils::format_bytes(report.bytes as u64)));
+        println!();
+        println!(
+            "{:<16} {:>6} {:>10} {:>10} {:>10} {:>8} {:>10}",
+            t!("benchmark.col_scenario"),
+            t!("benchmark.col_threads"),
+            t!("benchmark.col_seconds"),
+            t!("benchmark.col_files_per_s"),
+            t!("benchmark.col_mb_per_s"),
+            t!("benchmark.col_speedup"),
+            t!("benchmark.col_cache_hits")
+        );
+        for run in &report.runs {
+            println!(
+                "{:<16} {:>6} {:>10.2} {:>10.0} {:>10.1} {:>7.2}x {:>10}",
+                Self::benchmark_run_name(&run.name),
+                run.threads,
+                run.duration_secs,
+                run.files_per_s,
+                run.mb_per_s,
+                run.speedup,
+                run.cache_hit_rate.map_or_else(|| "-".to_string(), |rate| format!("{:.1}%", rate * 100.0))
+            );
+        }
+        if let Some(best) = report.runs.iter().filter(|run| run.cache_hit_rate.is_none()).max_by(|a, b| a.files_per_s.total_cmp(&b.files_per_s)) {
+            println!();
+            println!("{}", t!("benchmark.best_threads", threads = best.threads, current = config.performance.thread_pool_size));
+        }
         Ok(())
     }
 
-    async fn handle_update(args: &UpdateArgs, config: &ScannerConfig) -> Result<()> {
+    // 未指定 --threads 时比较单线程和 performance.thread_pool_size，线程池大小未设置时使用 CPU 数
+    fn benchmark_threads(args: &BenchmarkArgs, config: &ScannerConfig) -> Vec<usize> {
+        let mut threads = args.threads.clone();
+        if threads.is_empty() {
+            let pool = match config.performance.thread_pool_size {
+                0 | 1 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+                pool => pool,
+            };
+            threads = vec![1, pool];
+        }
+        threads.dedup();
+        threads
+    }
+
+    fn benchmark_run_name(name: &str) -> &'static str {
+        match name {
+            "verdict_cache_cold" => t!("benchmark.run_cache_cold"),
+            "verdict_cache_warm" => t!("benchmark.run_cache_warm"),
+            _ => t!("benchmark.run_scan"),
+        }
+    }
+
+    async fn handle_image_scan(
+        image: &str,
+        scan_options: ScanOptions,
+        signature_db: &Arc<SignatureDatabase>,
+        output: OutputFormat,
+    ) -> Result<ExitStatus> {
+        let text = !output.is_json();
+        if text {
+            println!("{}", t!("image.scanning", image = image));
+        }
+
+        let scanner = ImageScanner::new(Arc::clone(signature_db), scan_options);
+        let start_time = Instant::now();
+        let report = scanner.scan(image).await?;
+
+        if text {
+            println!("\n{}", t!("image.completed"));
+            println!("{}", t!("image.layers", count = report.layers.len()));
+            println!("{}", t!("scan.files_scanned", count = report.files_scanned));
+            println!("{}", t!("scan.threats_found", count = report.detections.len()));
+            println!("{}", t!("scan.duration", duration = format_duration(start_time.elapsed())));
+        }
+
+        for detection in &report.detections {
+            log::warn!(
+                image = image,
+                layer = detection.layer_digest.as_str(),
+                path:% = detection.image_path.display(),
+                signature = detection.result.signature_id.as_str();
+                "镜像中发现威胁: {:?}", detection.image_path
+            );
+            if text {
+                println!(
+                    "  [{} {}] {} {:?} ({})",
+                    t!("image.layer"),
+                    detection.layer_index,
+                    detection.layer_digest,
+                    detection.image_path,
+                    detection.result.signature_id
+                );
+            }
+        }
+
+        let status = if report.detections.is_empty() { ExitStatus::Clean } else { ExitStatus::Infected };
+        if output.is_json() {
+            print_json("scan", &json!({
+                "status": status.as_str(),
+                "scan_type": "image",
+                "duration_secs": start_time.elapsed().as_secs_f64(),
+                "image": report,
+            }))?;
+        }
+        Ok(status)
+    }
+
+    async fn handle_url_scan(
+        url: &str,
+        scan_options: ScanOptions,
+        config: &ScannerConfig,
+        signature_db: &Arc<SignatureDatabase>,
+        output: OutputFormat,
+    ) -> Result<ExitStatus> {
+        let text = !output.is_json();
+        if text {
+            println!("{}", t!("url.scanning", url = url));
+        }
+
+        let scanner = UrlScanner::new(Arc::clone(signature_db), scan_options, &config.scan_modes.url)?;
+        let start_time = Instant::now();
+        let report = scanner.scan(url).await?;
+
+        if text {
+            println!("\n{}", t!("url.completed"));
+            if report.final_url != report.url {
+                println!("{}", t!("url.final_url", url = report.final_url));
+            }
+            println!("{}", t!("url.size", size = report.size));
+            println!("{}", t!("url.content_type", content_type = report.content_type.as_deref().unwrap_or(t!("common.unknown"))));
+            println!("SHA256: {}", report.sha256);
+            println!("{}", t!("scan.duration", duration = format_duration(start_time.elapsed())));
+        }
+
+        for result in &report.results {
+            log::warn!(
+                url = url,
+                signature = result.signature_id.as_str();
+                "URL 对象中发现威胁: {}", url
+            );
+            if text {
+                let member = result.archive_member.as_ref().map(|m| format!(" [{}]", m)).unwrap_or_default();
+                println!("  {}{} ({:?}, {:?})", result.signature_id, member, result.threat_type, result.risk_level);
+            }
+        }
+
+        let status = if report.is_infected() { ExitStatus::Infected } else { ExitStatus::Clean };
+        if text {
+            println!("{}", if report.is_infected() { t!("url.verdict_infected") } else { t!("url.verdict_clean") });
+        } else {
+            print_json("scan", &json!({
+                "status": status.as_str(),
+                "scan_type": "url",
+                "duration_secs": start_time.elapsed().as_secs_f64(),
+                "url": report,
+            }))?;
+        }
+        Ok(status)
+    }
+
+    async fn handle_milter(
+        args: &MilterArgs,
+        config: &ScannerConfig,
+        signature_db: &Arc<SignatureDatabase>,
+    ) -> Result<()> {
+        let mut milter_config = config.milter.clone();
+        if let Some(ref listen) = args.listen {
+            milter_config.listen = listen.clone();
+        }
+        if let Some(ref action) = args.on_infected {
+            milter_config.on_infected = action.clone();
+        }
+
+        println!("{}", t!("milter.title"));
+        println!("{}", t!("milter.listen", addr = milter_config.listen));
+        println!("{}", t!("milter.on_infected", action = milter_config.on_infected));
+
+        let server = MilterServer::new(Arc::clone(signature_db), milter_config);
+
+        tokio::select! {
+            result = server.run() => result?,
+            _ = tokio::signal::ctrl_c() => {
+                server.stop();
+                println!("{}", t!("milter.stopped"));
+            }
+        }
+
+        Ok(())
+    }
+
+    // 病毒库、扫描任务、更新任务等与 API 共享同一个 VirusScanner，收到 SIGTERM 或 Ctrl-C 后退出
+    async fn handle_serve(args: &ServeArgs, config: &ScannerConfig, config_path: &PathBuf) -> Result<()> {
+        let addr = args.addr.clone().unwrap_or_else(|| config.api.listen.clone());
+        addr.parse::<std::net::SocketAddr>()
+            .with_context(|| format!("无效的监听地址: {}", addr))?;
+
+        let mut scanner = VirusScanner::new(config.clone());
+        scanner.set_config_path(config_path.clone());
+        scanner.initialize().await?;
+
+        // 先启动监控，/health 才会检查本进程中的监控线程
+        if args.monitor {
+            if let Err(e) = scanner.start_file_monitor().await {
+                scanner.shutdown().await?;
+                return Err(e.context("无法启动文件监控"));
+            }
+        }
+        if args.schedule_updates {
+            scanner.start_update_scheduler().await?;
+        }
+        scanner.start_api_server(&addr, "").await?;
+
+        println!("{}", t!("serve.listen", addr = addr));
+        println!("{}", t!("serve.monitor", state = if args.monitor { t!("common.started") } else { t!("common.not_started") }));
+        println!("{}", t!("serve.update_schedule", state = if args.schedule_updates { t!("common.enabled") } else { t!("common.disabled") }));
+        scanner.run().await?;
+        println!("{}", t!("serve.stopped"));
+        Ok(())
+    }
+
+    // 服务模式：按 monitor.enabled、update.enabled 启动文件监控和定时更新，并运行 API。
+    // 供 systemd (Type=notify) 使用，SIGHUP 重新加载配置文件，SIGTERM 退出
+    async fn handle_daemon(args: &DaemonArgs, config: &ScannerConfig, config_path: &PathBuf) -> Result<()> {
+        let pid_file = args.pid_file.clone().unwrap_or_else(|| config.daemon.pid_file.clone());
+        let _pid_file = PidFile::create(&pid_file)?;
+
+        let mut scanner = VirusScanner::new(config.clone());
+        scanner.set_config_path(config_path.clone());
+        scanner.initialize().await?;
+
+        let started: Result<()> = async {
+            if config.monitor.enabled {
+                // 已有单独的监控进程 (monitor --start) 时不重复监控，/health 改为检查该进程
+                match control::running_pid(&config.monitor.daemon.pid_file) {
+                    Some(pid) => log::warn!("监控进程已在运行 (PID {})，服务中不再启动文件监控", pid),
+                    None => scanner.start_file_monitor().await.context("无法启动文件监控")?,
+                }
+            }
+            if config.update.enabled {
+                scanner.start_update_scheduler().await?;
+            }
+            scanner.start_scan_scheduler().await?;
+            if !args.no_api {
+                let addr = args.addr.clone().unwrap_or_else(|| config.api.listen.clone());
+                scanner.start_api_server(&addr, "").await?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = started {
+            scanner.shutdown().await?;
+            return Err(e);
+        }
+
+        log::info!("服务已启动 (PID {})", std::process::id());
+        scanner.run().await
+    }
+
+    fn handle_quarantine(args: &QuarantineArgs, config: &ScannerConfig) -> Result<()> {
+        // 密钥不可用时仍可列出和删除，恢复加密文件时报错
+        let quarantine = QuarantineManager::from_config(&config.security).unwrap_or_else(|e| {
+            log::warn!("隔离区加密密钥不可用: {}", e);
+            QuarantineManager::new(config.security.quarantine_dir.clone(), None)
+        });
+        let audit = AuditLogger::new(config.logging.log_dir.clone(), config.security.audit_log_enabled);
+        let user = crate::utils::get_current_user().unwrap_or_else(|_| "unknown".to_string());
+
+        match &args.action {
+            QuarantineAction::List => {
+                let entries = quarantine.list()?;
+                if entries.is_empty() {
+                    println!("{}", t!("quarantine.empty"));
+                    return Ok(());
+                }
+                println!("{:<26} {:<20} {:>10}  {}", "ID", t!("quarantine.col_time"), t!("common.size"), t!("quarantine.col_original_path"));
+                for entry in entries.iter().rev() {
+                    println!(
+                        "{:<26} {:<20} {:>10}  {}",
+                        entry.id,
+                        entry.quarantined_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+                        crate::utils::format_bytes(entry.size),
+                        entry.original_path.as_ref().map_or_else(|| format!("({}) {}", t!("common.unknown"), entry.original_name()), |path| path.display().to_string())
+                    );
+                }
+                println!();
+                println!("{}", t!("quarantine.total", count = entries.len(), dir = format!("{:?}", config.security.quarantine_dir)));
+            }
+            QuarantineAction::Restore { id, to, force } => {
+                let (entry, restored) = quarantine.restore(id, to.as_deref(), *force)?;
+                audit.log("QUARANTINE_RESTORE", &user, &format!("id={} path={:?}", entry.id, restored));
+                println!("{}", t!("quarantine.restored", path = format!("{:?}", restored)));
+            }
+            QuarantineAction::Delete { id } => {
+                let entry = quarantine.delete(id)?;
+                audit.log("QUARANTINE_DELETE", &user, &format!("id={} file={}", entry.id, entry.original_name()));
+                println!("{}", t!("quarantine.deleted", id = entry.id));
+            }
+            QuarantineAction::Purge { older_than, dry_run: true } => {
+                let age = humantime::parse_duration(older_than).with_context(|| format!("无效的时长: {}", older_than))?;
+                let expired = quarantine.expired(chrono::Duration::from_std(age)?)?;
+                let size: u64 = expired.iter().map(|entry| entry.size).sum();
+                audit.log("QUARANTINE_PURGE_DRY_RUN", &user, &format!("older_than={} count={}", older_than, expired.len()));
+                for entry in &expired {
+                    println!(
+                        "{:<26} {:<20} {:>10}  {}",
+                        entry.id,
+                        entry.quarantined_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+                        crate::utils::format_bytes(entry.size),
+                        entry.original_name()
+                    );
+                }
+                println!("{}", t!("quarantine.purge_dry_run", count = expired.len(), size = crate::utils::format_bytes(size)));
+            }
+            QuarantineAction::Purge { older_than, dry_run: false } => {
+                let age = humantime::parse_duration(older_than).with_context(|| format!("无效的时长: {}", older_than))?;
+                let purged = quarantine.purge(chrono::Duration::from_std(age)?)?;
+                let freed: u64 = purged.iter().map(|entry| entry.size).sum();
+                audit.log("QUARANTINE_PURGE", &user, &format!("older_than={} count={}", older_than, purged.len()));
+                println!("{}", t!("quarantine.purged", count = purged.len(), size = crate::utils::format_bytes(freed)));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_update(args: &UpdateArgs, config: &ScannerConfig, output: OutputFormat) -> Result<()> {
         let database_path = PathBuf::from("/var/lib/virus-scanner/database");
         let backup_path = PathBuf::from("/var/lib/virus-scanner/backups");
+        let text = !output.is_json();
 
         std::fs::create_dir_all(&database_path)?;
         std::fs::create_dir_all(&backup_path)?;
 
-        let updater = Arc::new(DatabaseUpdater::new(
+        let mut updater = DatabaseUpdater::new(
             config.update.mirror_url.clone(),
             database_path.clone(),
             backup_path,
-        ));
+        );
+        updater.set_cdiff_config(config.update.cdiff.clone());
+        updater.set_verify_signatures(config.update.verify_signatures);
+        updater.set_backup_retention(config.update.backup_retention.clone());
+        updater.set_validate_after_update(config.update.validate_after_update);
+        let updater = Arc::new(updater);
 
-        println!("病毒库更新工具");
-        println!("镜像服务器: {}", config.update.mirror_url);
-        println!("本地数据库路径: {:?}", database_path);
-        println!();
+        match &args.action {
+            Some(UpdateAction::ListBackups) => {
+                let backups = updater.list_backups()?;
+                if output.is_json() {
+                    return print_json("update", &json!({ "backups": backups }));
+                }
+                if backups.is_empty() {
+                    println!("{}", t!("update.no_backups"));
+                    return Ok(());
+                }
+                println!(
+                    "{:<20} {:<20} {:>10} {:>6}  {}",
+                    t!("update.col_backup_id"),
+                    t!("update.col_created_at"),
+                    t!("common.size"),
+                    t!("update.col_files"),
+                    t!("common.version")
+                );
+                for entry in backups.iter().rev() {
+                    let versions: Vec<String> = entry
+                        .versions
+                        .iter()
+                        .map(|(database, version)| format!("{} {}", database, version))
+                        .collect();
+                    println!(
+                        "{:<20} {:<20} {:>9.2}M {:>6}  {}",
+                        entry.id,
+                        entry.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+                        entry.size as f64 / 1024.0 / 1024.0,
+                        entry.files,
+                        if versions.is_empty() { "-".to_string() } else { versions.join(", ") }
+                    );
+                }
+                return Ok(());
+            }
+            Some(UpdateAction::Rollback { to, dry_run: true }) => {
+                let (entry, plan) = updater.rollback_plan(to)?;
+                let audit = AuditLogger::new(config.logging.log_dir.clone(), config.security.audit_log_enabled);
+                let user = crate::utils::get_current_user().unwrap_or_else(|_| "unknown".to_string());
+                audit.log(
+                    "UPDATE_ROLLBACK_DRY_RUN",
+                    &user,
+                    &format!("backup={} restore={} remove={}", entry.id, plan.restore.len(), plan.remove.len()),
+                );
+                if output.is_json() {
+                    return print_json("update", &json!({ "dry_run": true, "rollback_to": entry, "database_path": database_path, "plan": plan }));
+                }
+                println!("{}", t!("update.rollback_dry_run", id = entry.id));
+                for path in &plan.restore {
+                    println!("  [{}] {}", t!("action.restore"), path.display());
+                }
+                for path in &plan.remove {
+                    println!("  [{}] {}", t!("action.delete"), path.display());
+                }
+                return Ok(());
+            }
+            Some(UpdateAction::Rollback { to, dry_run: false }) => {
+                let entry = updater.rollback(to).await?;
+                if output.is_json() {
+                    return print_json("update", &json!({ "rolled_back_to": entry, "database_path": database_path }));
+                }
+                println!("{}", t!("update.rolled_back", id = entry.id));
+                println!("{}", t!("update.restored_to", path = format!("{:?}", database_path)));
+                return Ok(());
+            }
+            None => {}
+        }
+
+        if text {
+            println!("{}", t!("update.title"));
+            println!("{}", t!("update.mirror", url = config.update.mirror_url));
+            println!("{}", t!("update.database_path", path = format!("{:?}", database_path)));
+            println!();
+        }
 
         if args.check_only {
-            println!("正在检查病毒库更新...");
-            if let Some(version) = updater.check_for_updates().await? {
-                println!("发现新版本: {}", version);
-                println!("请运行 'virus-scanner update --force' 进行更新");
+            if text {
+                println!("{}", t!("update.checking"));
+            }
+            let latest = updater.check_for_updates().await?;
+            if output.is_json() {
+                return print_json("update", &json!({
+                    "mirror_url": config.update.mirror_url,
+                    "update_available": latest.is_some(),
+                    "latest_version": latest,
+                }));
+            }
+            if let Some(version) = latest {
+                println!("{}", t!("update.new_version", version = version));
+                println!("{}", t!("update.run_force"));
             } else {
-                println!("当前已是最新版本");
+                println!("{}", t!("update.up_to_date"));
             }
             return Ok(());
         }
 
+        let mut update = None;
         if args.force || args.schedule {
-            println!("开始更新病毒库...");
-            println!("正在下载 ClamAV 病毒库文件:");
-            println!("  - main.cvd (主病毒库)");
-            println!("  - daily.cvd (每日更新)");
-            println!("  - bytecode.cvd (字节码库)");
-            println!();
+            if text {
+                println!("{}", t!("update.starting"));
+                println!("{}", t!("update.downloading"));
+                println!("  - main.cvd ({})", t!("update.main_cvd"));
+                println!("  - daily.cvd ({})", t!("update.daily_cvd"));
+                println!("  - bytecode.cvd ({})", t!("update.bytecode_cvd"));
+                println!();
+            }
 
             match updater.perform_update().await {
+                Ok(update_info) if output.is_json() => update = Some(update_info),
                 Ok(update_info) => {
-                    println!("病毒库更新完成!");
+                    println!("{}", t!("update.completed"));
                     println!();
-                    println!("更新详情:");
-                    println!("  版本: {}", update_info.version);
-                    println!("  更新时间: {}", update_info.timestamp.format("%Y-%m-%d %H:%M:%S UTC"));
-                    println!("  下载大小: {:.2} MB", update_info.download_size as f64 / 1024.0 / 1024.0);
-                    println!("  新增签名: {}", update_info.signatures_added);
-                    println!("  删除签名: {}", update_info.signatures_removed);
-                    println!("  总签名数: {}", update_info.total_signatures);
+                    println!("{}", t!("update.details"));
+                    println!("  {}", t!("update.version", version = update_info.version));
+                    println!("  {}", t!("update.time", time = update_info.timestamp.format("%Y-%m-%d %H:%M:%S UTC")));
+                    println!(
+                        "  {}",
+                        t!("update.download_size", size = format!("{:.2}", update_info.download_size as f64 / 1024.0 / 1024.0))
+                    );
+                    println!("  {}", t!("update.signatures_added", count = update_info.signatures_added));
+                    println!("  {}", t!("update.signatures_removed", count = update_info.signatures_removed));
+                    println!("  {}", t!("update.signatures_total", count = update_info.total_signatures));
                     println!();
-                    println!("病毒库文件已更新到: {:?}", database_path);
+                    println!("{}", t!("update.updated_to", path = format!("{:?}", database_path)));
                 }
+                // JSON 输出时错误由 main 以 JSON 对象输出
+                Err(e) if output.is_json() => return Err(e),
                 Err(e) => {
-                    println!("病毒库更新失败: {}", e);
+                    println!("{}", t!("update.failed", error = e));
                     println!();
-                    println!("可能的原因:");
-                    println!("  1. 网络连接问题");
-                    println!("  2. 镜像服务器不可用");
-                    println!("  3. 磁盘空间不足");
-                    println!("  4. 权限不足");
+                    println!("{}", t!("update.failure_causes"));
+                    println!("  1. {}", t!("update.cause_network"));
+                    println!("  2. {}", t!("update.cause_mirror"));
+                    println!("  3. {}", t!("update.cause_disk"));
+                    println!("  4. {}", t!("update.cause_permission"));
                     println!();
-                    println!("建议:");
-                    println!("  - 检查网络连接");
-                    println!("  - 尝试使用其他镜像服务器");
-                    println!("  - 检查磁盘空间");
-                    println!("  - 确保有足够的权限");
+                    println!("{}", t!("update.suggestions"));
+                    println!("  - {}", t!("update.suggest_network"));
+                    println!("  - {}", t!("update.suggest_mirror"));
+                    println!("  - {}", t!("update.suggest_disk"));
+                    println!("  - {}", t!("update.suggest_permission"));
                     return Err(e);
                 }
             }
@@ -287,84 +1426,966 @@ impl Command {
             };
             let scheduler = UpdateScheduler::new(Arc::clone(&updater), schedule);
             scheduler.start().await;
-            println!("定时更新已启用");
-            println!("更新频率: {}", config.update.schedule.frequency);
-            println!("更新时间: {}", config.update.schedule.time);
+            if text {
+                println!("{}", t!("update.schedule_enabled"));
+                println!("{}", t!("update.schedule_frequency", frequency = config.update.schedule.frequency));
+                println!("{}", t!("update.schedule_time", time = config.update.schedule.time));
+            }
         }
 
+        if output.is_json() {
+            print_json("update", &json!({
+                "mirror_url": config.update.mirror_url,
+                "database_path": database_path,
+                "update": update,
+                "schedule": args.schedule.then(|| json!({
+                    "frequency": config.update.schedule.frequency,
+                    "time": config.update.schedule.time,
+                })),
+            }))?;
+        }
         Ok(())
     }
 
-    async fn handle_monitor(args: &MonitorArgs, config: &ScannerConfig) -> Result<()> {
-        let mut monitor = FileMonitor::new();
+    async fn handle_monitor(
+        args: &MonitorArgs,
+        config: &ScannerConfig,
+        config_path: &PathBuf,
+        signature_db: &Arc<SignatureDatabase>,
+        output: OutputFormat,
+    ) -> Result<()> {
+        let daemon = &config.monitor.daemon;
+
+        if let Some(ref action) = args.action {
+            return Self::handle_watch_list(action, config, config_path, output).await;
+        }
 
-        if args.start {
-            monitor.add_default_watches()?;
-            monitor.start()?;
-            println!("文件监控已启动");
-            println!("监控路径: {:?}", config.monitor.watch_paths);
-
-            tokio::signal::ctrl_c().await?;
-            monitor.stop();
-            println!("监控已停止");
-        } else if args.stop {
-            monitor.stop();
-            println!("文件监控已停止");
+        if output.is_json() {
+            return Self::handle_monitor_json(args, config, config_path, signature_db).await;
+        }
+
+        if args.stop {
+            match control::stop_daemon(daemon).await? {
+                Some(pid) => println!("{}", t!("monitor.stopped_pid", pid = pid)),
+                None => println!("{}", t!("monitor.not_running")),
+            }
+        } else if args.status {
+            match control::query_status(daemon).await? {
+                Some(status) => {
+                    println!("{}", t!("monitor.running"));
+                    println!("  PID: {}", status.pid);
+                    println!(
+                        "  {}",
+                        t!("monitor.started_at", time = status.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"))
+                    );
+                    println!("  {}", t!("monitor.watch_paths", paths = format!("{:?}", status.watch_paths)));
+                    println!(
+                        "  {}",
+                        t!("monitor.access_control", state = if status.access_control { t!("common.enabled") } else { t!("common.disabled") })
+                    );
+                    let stats = &status.stats;
+                    println!("  {}", t!("monitor.events_received", count = stats.events_received));
+                    println!(
+                        "  {}",
+                        t!(
+                            "monitor.events_filtered",
+                            count = stats.events_filtered + stats.events_rate_limited,
+                            rules = stats.events_filtered,
+                            rate_limited = stats.events_rate_limited
+                        )
+                    );
+                    println!("  {}", t!("monitor.events_dropped", count = stats.events_dropped));
+                    println!("  {}", t!("monitor.scans_triggered", count = stats.scans_triggered));
+                    println!("  {}", t!("monitor.threats_found", count = stats.threats_found));
+                    match stats.max_user_watches {
+                        Some(limit) => println!("  {}", t!("monitor.inotify_watches", count = stats.watches, limit = limit)),
+                        None => println!("  {}", t!("monitor.watches", count = stats.watches)),
+                    }
+                }
+                None => println!("{}", t!("monitor.not_running")),
+            }
+        } else if args.events {
+            Self::show_monitor_events(args, config)?;
+        } else if args.start && !args.foreground {
+            let pid = control::spawn_daemon(daemon, &config.logging.log_dir.join("monitor.out"))?;
+            println!("{}", t!("monitor.started_background", pid = pid));
+        } else if args.start {
+            Self::run_monitor(config, config_path, signature_db, OutputFormat::Text).await?;
         } else {
-            println!("用法: virus-scanner monitor --start|--stop");
+            println!("{}", t!("monitor.usage"));
         }
 
         Ok(())
     }
 
-    async fn handle_report(args: &ReportArgs, config: &ScannerConfig) -> Result<()> {
-        let report_generator = ReportGenerator::new(config.report.output_dir.clone());
+    async fn handle_monitor_json(
+        args: &MonitorArgs,
+        config: &ScannerConfig,
+        config_path: &PathBuf,
+        signature_db: &Arc<SignatureDatabase>,
+    ) -> Result<()> {
+        let daemon = &config.monitor.daemon;
+        let body = if args.stop {
+            let pid = control::stop_daemon(daemon).await?;
+            json!({ "action": "stop", "was_running": pid.is_some(), "pid": pid })
+        } else if args.status {
+            let status = control::query_status(daemon).await?;
+            json!({ "action": "status", "running": status.is_some(), "status": status })
+        } else if args.events {
+            let events = Self::query_monitor_events(args, config)?;
+            json!({ "action": "events", "count": events.len(), "events": events })
+        } else if args.start && !args.foreground {
+            let pid = control::spawn_daemon(daemon, &config.logging.log_dir.join("monitor.out"))?;
+            json!({ "action": "start", "pid": pid })
+        } else if args.start {
+            // 前台运行时在监控停止后输出
+            Self::run_monitor(config, config_path, signature_db, OutputFormat::Json).await?;
+            json!({ "action": "run", "watch_paths": config.monitor.watch_paths })
+        } else {
+            return Err(anyhow::anyhow!("用法: virus-scanner monitor --start [--foreground]|--stop|--status|--events"));
+        };
+        print_json("monitor", &body)
+    }
 
-        match std::fs::read_to_string(&args.input) {
-            Ok(content) => {
-                let report: crate::report::ScanReport = match args.format.as_str() {
-                    "json" => serde_json::from_str(&content)?,
-                    "yaml" => serde_yaml::from_str(&content)?,
-                    _ => return Err(anyhow::anyhow!("不支持的格式")),
-                };
+    // 监控运行中时由监控进程修改并保存配置；未运行时直接修改配置文件，下次启动生效
+    async fn handle_watch_list(
+        action: &MonitorAction,
+        config: &ScannerConfig,
+        config_path: &PathBuf,
+        output: OutputFormat,
+    ) -> Result<()> {
+        let daemon = &config.monitor.daemon;
+        let running = control::running_pid(&daemon.pid_file).is_some();
 
-                let output_path = if args.output.as_os_str().is_empty() {
-                    report_generator.save(&report, ReportFormat::Text)?
+        let paths = match action {
+            MonitorAction::AddPath { path } => {
+                let path = std::fs::canonicalize(path).with_context(|| format!("无法解析路径: {:?}", path))?;
+                if running {
+                    control::request_watches(daemon, &ControlRequest::AddWatch { path: path.clone() }).await?
                 } else {
-                    args.output.clone()
+                    control::update_watch_paths(config_path, |watch_paths| {
+                        if !watch_paths.iter().any(|p| control::same_path(p, &path)) {
+                            watch_paths.push(path.display().to_string());
+                        }
+                    })?;
+                    ScannerConfig::load(config_path)?.monitor.watch_paths.iter().map(PathBuf::from).collect()
+                }
+            }
+            MonitorAction::RemovePath { path } => {
+                let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                if running {
+                    control::request_watches(daemon, &ControlRequest::RemoveWatch { path: path.clone() }).await?
+                } else {
+                    control::update_watch_paths(config_path, |watch_paths| {
+                        watch_paths.retain(|p| !control::same_path(p, &path));
+                    })?;
+                    ScannerConfig::load(config_path)?.monitor.watch_paths.iter().map(PathBuf::from).collect()
+                }
+            }
+        };
+
+        if output.is_json() {
+            return print_json("monitor", &json!({
+                "action": match action {
+                    MonitorAction::AddPath { .. } => "add-path",
+                    MonitorAction::RemovePath { .. } => "remove-path",
+                },
+                "running": running,
+                "config_path": config_path,
+                "watch_paths": paths,
+            }));
+        }
+        if !running {
+            println!("{}", t!("monitor.saved_for_next_start", path = format!("{:?}", config_path)));
+        }
+        println!("{}", t!("monitor.watch_paths_header"));
+        for path in paths {
+            println!("  {}", path.display());
+        }
+        Ok(())
+    }
+
+    fn query_monitor_events(args: &MonitorArgs, config: &ScannerConfig) -> Result<Vec<MonitorEvent>> {
+        let since = match args.since {
+            Some(ref since) => {
+                let window = humantime::parse_duration(since).with_context(|| format!("无效的时间范围: {}", since))?;
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Some(now.saturating_sub(window).as_secs())
+            }
+            None => None,
+        };
+        let query = EventQuery {
+            since,
+            path: args.path.clone(),
+            limit: Some(args.limit),
+        };
+        EventJournal::query(&config.logging.log_dir, &config.monitor.journal, &query)
+    }
+
+    fn show_monitor_events(args: &MonitorArgs, config: &ScannerConfig) -> Result<()> {
+        let events = Self::query_monitor_events(args, config)?;
+        if events.is_empty() {
+            println!("{}", t!("monitor.no_events"));
+            return Ok(());
+        }
+
+        for event in &events {
+            let time = chrono::DateTime::from_timestamp(event.timestamp as i64, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| event.timestamp.to_string());
+            let process = event.process_info.as_ref()
+                .map(|p| format!("  [{} PID {} {}]", p.user_name, p.pid, p.command))
+                .unwrap_or_default();
+            println!("{}  {:<10} {}{}", time, format!("{:?}", event.event_type), event.file_path.display(), process);
+        }
+        println!("{}", t!("monitor.events_total", count = events.len()));
+        Ok(())
+    }
+
+    // 在当前进程中运行监控，直到收到 Ctrl-C、SIGTERM 或控制套接字上的停止请求
+    async fn run_monitor(
+        config: &ScannerConfig,
+        config_path: &PathBuf,
+        signature_db: &Arc<SignatureDatabase>,
+        output: OutputFormat,
+    ) -> Result<()> {
+        let text = !output.is_json();
+        let control_server = ControlServer::bind(&config.monitor.daemon)?;
+        let mut monitor = FileMonitor::new();
+
+        let mut on_access = OnAccessScanner::new(
+            Arc::clone(signature_db),
+            on_access_scan_options(config),
+            config.monitor.actions.clone(),
+            &config.monitor.on_access,
+        );
+        on_access.set_allowlist(Arc::new(Allowlist::from_config(&config.allowlist)?));
+        if config.monitor.actions.auto_quarantine {
+            on_access.set_quarantine_manager(Arc::new(QuarantineManager::from_config(&config.security)?));
+        }
+        on_access.set_audit_logger(Arc::new(AuditLogger::new(
+            config.logging.log_dir.clone(),
+            config.security.audit_log_enabled,
+        )));
+        let dispatcher = AlertDispatcher::start(&config.alerts).unwrap_or_else(|e| {
+            log::error!("威胁告警不可用: {:#}", e);
+            None
+        });
+        let detection_logger = DetectionLogger::open_or_warn(&config.logging);
+        if config.report.threat_store.enabled || dispatcher.is_some() || detection_logger.is_some() {
+            let store = config.report.threat_store.enabled.then(|| ThreatStore::from_config(&config.report.threat_store));
+            let alerts = dispatcher.clone();
+            on_access.set_result_callback(Arc::new(move |result| {
+                let threat = ThreatReport::from_result(String::new(), result, true);
+                if let Some(ref detection_logger) = detection_logger {
+                    detection_logger.log_threats("monitor", std::slice::from_ref(&threat));
+                }
+                let stored = match store {
+                    Some(ref store) => store.record_or_warn("monitor", "monitor", &[threat]),
+                    None => vec![StoredThreat::new("monitor", "monitor", &threat)],
                 };
+                if let Some(ref alerts) = alerts {
+                    alerts.notify(&stored);
+                }
+            }));
+        }
+        let stats = on_access.stats();
+        let on_access = Arc::new(on_access);
+        let task = on_access.start()?;
 
-                println!("报告已保存: {:?}", output_path);
+        let journal = if config.monitor.journal.enabled {
+            match EventJournal::new(config.logging.log_dir.clone(), &config.monitor.journal) {
+                Ok(journal) => Some(journal),
+                Err(e) => {
+                    log::error!("无法打开监控事件日志: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let filter = Arc::new(EventFilter::new(&config.monitor.filter)?);
+        monitor.add_default_watches(&config.monitor)?;
+        let (events, _) = tokio::sync::broadcast::channel(control::EVENT_STREAM_CAPACITY);
+        let (event_filter, event_stats, event_tx) = (Arc::clone(&filter), Arc::clone(&stats), events.clone());
+        monitor.set_event_callback(Arc::new(move |event| {
+            event_stats.record_event();
+            if !event_filter.allow(&event) {
+                return;
+            }
+            on_access.submit(&event);
+            // 没有订阅者时发送失败，忽略即可
+            let _ = event_tx.send(event.clone());
+            if let Some(ref journal) = journal {
+                if !event_filter.is_duplicate(&event) {
+                    if let Err(e) = journal.record(&event) {
+                        log::error!("无法写入监控事件日志: {}", e);
+                    }
+                }
             }
-            Err(e) => return Err(anyhow::anyhow!("无法读取报告文件: {}", e)),
+        }));
+        monitor.start()?;
+        if text {
+            println!("{}", t!("monitor.started"));
+            println!("{}", t!("monitor.watch_paths", paths = format!("{:?}", config.monitor.watch_paths)));
+        }
+        #[cfg(target_os = "linux")]
+        let mut access_guard = crate::monitor::start_access_guard(Arc::clone(signature_db), config)?;
+        #[cfg(target_os = "linux")]
+        let access_control = access_guard.is_some();
+        #[cfg(not(target_os = "linux"))]
+        let access_control = false;
+        if access_control && text {
+            let paths: Vec<&str> = config.monitor.access_control.paths.iter().map(|p| p.path.as_str()).collect();
+            println!("{}", t!("monitor.access_control_paths", paths = format!("{:?}", paths)));
+        }
+
+        let handle = Arc::new(
+            MonitorHandle::new(monitor, config.monitor.clone(), access_control)
+                .persist_to(config_path.clone())
+                .with_stats(stats, filter)
+                .with_event_stream(events),
+        );
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => log::info!("收到 SIGTERM"),
+            _ = control_server.serve(Arc::clone(&handle)) => {}
         }
 
+        handle.stop();
+        task.abort();
+        #[cfg(target_os = "linux")]
+        if let Some(ref mut guard) = access_guard {
+            guard.stop();
+        }
+        if let Some(ref dispatcher) = dispatcher {
+            dispatcher.shutdown(alerts::SHUTDOWN_TIMEOUT).await;
+        }
+        if text {
+            println!("{}", t!("monitor.stopped"));
+        }
+        Ok(())
+    }
+
+    async fn handle_report(args: &ReportArgs, config: &ScannerConfig, output: OutputFormat) -> Result<()> {
+        match args.action {
+            ReportAction::List { limit } => Self::list_reports(config, limit, output),
+            ReportAction::Show { ref id, ref format } => Self::show_report(config, id, format.as_deref(), output),
+            ReportAction::Convert { ref input, ref to, output: ref target } => {
+                Self::convert_report(config, input, to, target.as_ref(), output)
+            }
+        }
+    }
+
+    // 未指定输出文件时与扫描生成的报告一样保存到报告目录并加入索引
+    fn convert_report(
+        config: &ScannerConfig,
+        input: &Path,
+        to: &str,
+        target: Option<&PathBuf>,
+        output: OutputFormat,
+    ) -> Result<()> {
+        let to: ReportFormat = to.parse()?;
+        let (report, from) = crate::report::read_report(input)?;
+        let generator = ReportGenerator::new(config.report.output_dir.clone());
+
+        let path = match target {
+            Some(target) if target.as_os_str() == "-" => {
+                print!("{}", generator.render(&report, to)?);
+                return Ok(());
+            }
+            Some(target) => {
+                let content = generator.render(&report, to)?;
+                std::fs::write(target, content).with_context(|| format!("无法写入报告文件: {:?}", target))?;
+                target.clone()
+            }
+            None => generator.save(&report, to)?,
+        };
+
+        if output.is_json() {
+            return print_json("report", &json!({
+                "action": "convert",
+                "id": report.id,
+                "input": input,
+                "from": from,
+                "to": to,
+                "path": path,
+            }));
+        }
+        println!(
+            "{}",
+            t!("report.converted", id = report.id, from = from.extension(), to = to.extension(), path = format!("{:?}", path))
+        );
         Ok(())
     }
 
+    fn list_reports(config: &ScannerConfig, limit: usize, output: OutputFormat) -> Result<()> {
+        let mut reports = ReportIndex::new(config.report.output_dir.clone()).list()?;
+        let total = reports.len();
+        reports.truncate(limit);
+        if output.is_json() {
+            return print_json("report", &json!({ "action": "list", "total": total, "reports": reports }));
+        }
+        if reports.is_empty() {
+            println!("{}", t!("report.none", dir = format!("{:?}", config.report.output_dir)));
+            return Ok(());
+        }
+        println!(
+            "{:<14} {:<20} {:<24} {:>10} {:>6}  {}",
+            "ID",
+            t!("report.col_time"),
+            t!("report.col_scan_type"),
+            t!("report.col_files"),
+            t!("report.col_threats"),
+            t!("report.col_format")
+        );
+        for report in &reports {
+            println!(
+                "{:<14} {:<20} {:<24} {:>10} {:>6}  {}",
+                report.id,
+                report.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                report.scan_type,
+                report.files_scanned,
+                report.threats,
+                report.format.extension()
+            );
+        }
+        if total > reports.len() {
+            println!();
+            println!("{}", t!("report.truncated", total = total, shown = reports.len()));
+        }
+        Ok(())
+    }
+
+    // 优先读取 JSON 或 YAML 文件并按 format 重新生成；只有 HTML 或文本文件时只能按原格式显示
+    fn show_report(config: &ScannerConfig, id: &str, format: Option<&str>, output: OutputFormat) -> Result<()> {
+        let files = ReportIndex::new(config.report.output_dir.clone()).find(id)?;
+        let format = match format {
+            Some(format) => Some(format.parse::<ReportFormat>()?),
+            None => None,
+        };
+        let source = files.iter().rev().find(|file| matches!(file.format, ReportFormat::Json | ReportFormat::Yaml));
+
+        let Some(source) = source else {
+            let wanted = format.unwrap_or(ReportFormat::Text);
+            let file = files
+                .iter()
+                .rev()
+                .find(|file| file.format == wanted)
+                .filter(|_| !output.is_json())
+                .ok_or_else(|| {
+                    let saved: Vec<&str> = files.iter().map(|file| file.format.extension()).collect();
+                    anyhow::anyhow!("报告 {} 只保存了 {} 格式，无法以其他格式显示", files[0].id, saved.join(", "))
+                })?;
+            let content = std::fs::read_to_string(&file.path).with_context(|| format!("无法读取报告文件: {:?}", file.path))?;
+            print!("{}", content);
+            return Ok(());
+        };
+
+        let report = load_report(&source.path, source.format)?;
+        if output.is_json() {
+            return print_json("report", &json!({ "action": "show", "path": source.path, "report": report }));
+        }
+        let generator = ReportGenerator::new(config.report.output_dir.clone());
+        let content = generator.render(&report, format.unwrap_or(ReportFormat::Text))?;
+        print!("{}", content);
+        if !content.ends_with('\n') {
+            println!();
+        }
+        Ok(())
+    }
+
+    async fn handle_database(
+        args: &DatabaseArgs,
+        config: &ScannerConfig,
+        signature_db: &Arc<SignatureDatabase>,
+    ) -> Result<()> {
+        let database_path = &config.update.database_path;
+
+        match &args.action {
+            DatabaseAction::Info => {
+                signature_db.load_from_directory(database_path).await?;
+                println!("{}", t!("database.dir", path = format!("{:?}", database_path)));
+                let mut headers: Vec<_> = signature_db.get_database_headers().into_iter().collect();
+                headers.sort_by(|a, b| a.0.cmp(&b.0));
+                if headers.is_empty() {
+                    println!("{}", t!("database.no_cvd"));
+                } else {
+                    println!();
+                    println!(
+                        "{:<12} {:>8} {:<26} {:>10}",
+                        t!("database.col_database"),
+                        t!("common.version"),
+                        t!("database.col_build_time"),
+                        t!("database.col_signatures")
+                    );
+                    for (name, header) in &headers {
+                        println!("{:<12} {:>8} {:<26} {:>10}", name, header.version, header.build_time, header.signature_count);
+                    }
+                }
+
+                println!();
+                println!("{}", t!("database.loaded_by_type"));
+                for (kind, count) in signature_db.signature_type_counts() {
+                    println!("  {:<14} {:>10}", kind, count);
+                }
+                println!("  {:<14} {:>10}", t!("database.total"), signature_db.get_signature_count().await);
+
+                let custom_files = custom::custom_signature_files(database_path);
+                if !custom_files.is_empty() {
+                    println!();
+                    println!("{}", t!("database.custom_files"));
+                    for path in custom_files {
+                        println!("  {:?}", path);
+                    }
+                }
+                println!();
+                println!("{}", t!("database.memory", size = crate::utils::format_bytes(signature_db.get_memory_usage())));
+            }
+            DatabaseAction::Search { name, limit } => {
+                signature_db.load_from_directory(database_path).await?;
+                let found = signature_db.search(name);
+                if found.is_empty() {
+                    println!("{}", t!("database.no_match", name = format!("{:?}", name)));
+                    return Ok(());
+                }
+                println!(
+                    "{:<48} {:<14} {:<12} {:<8}",
+                    "ID",
+                    t!("database.col_kind"),
+                    t!("database.col_threat_type"),
+                    t!("database.col_risk")
+                );
+                for sig in found.iter().take(*limit) {
+                    println!("{:<48} {:<14} {:<12} {:<8}", sig.id, sig.kind, sig.threat_type, sig.risk_level);
+                }
+                if found.len() > *limit {
+                    println!("{}", t!("database.truncated", total = found.len(), shown = limit));
+                }
+            }
+            DatabaseAction::AddCustom { file, name } => {
+                let result = custom::add_custom_signatures(database_path, file, name.as_deref())?;
+                for id in &result.existing {
+                    println!("{}", t!("database.custom_exists", id = id));
+                }
+                for id in &result.added {
+                    println!("{}", t!("database.custom_added", id = id));
+                }
+                if !result.added.is_empty() {
+                    println!("{}", t!("database.custom_written", path = format!("{:?}", result.file)));
+                    println!("{}", t!("database.reload_hint"));
+                }
+            }
+            DatabaseAction::Remove { id } => {
+                let removed = custom::remove_custom_signature(database_path, id)?;
+                if removed == 0 {
+                    signature_db.load_from_directory(database_path).await?;
+                    if signature_db.search(id).iter().any(|sig| sig.id == *id) {
+                        return Err(anyhow::anyhow!("{} 不是本地自定义特征码，只能删除本地特征码；误报的文件请加入白名单", id));
+                    }
+                    return Err(anyhow::anyhow!("未找到特征码: {}", id));
+                }
+                println!("{}", t!("database.custom_removed", id = id, count = removed));
+                println!("{}", t!("database.reload_hint"));
+            }
+            DatabaseAction::Verify => {
+                let checks = SignatureDatabase::verify_directory(database_path);
+                if checks.is_empty() {
+                    return Err(anyhow::anyhow!("病毒库目录中没有病毒库文件: {:?}", database_path));
+                }
+                let mut failed = 0;
+                for check in &checks {
+                    let version = check.version.map_or_else(String::new, |version| format!(" {} {}", t!("common.version"), version));
+                    match check.error {
+                        Some(ref error) => println!("[{}] {:?}: {}", t!("common.failed"), check.path, error),
+                        None if !check.invalid_lines.is_empty() => println!(
+                            "[{}] {:?}: {}",
+                            t!("common.failed"),
+                            check.path,
+                            t!("database.verify_invalid", count = check.signatures, lines = format!("{:?}", check.invalid_lines))
+                        ),
+                        None => println!(
+                            "[{}] {:?}{}: {}",
+                            t!("common.passed"),
+                            check.path,
+                            version,
+                            t!("database.verify_ok", count = check.signatures, skipped = check.skipped)
+                        ),
+                    }
+                    if !check.passed() {
+                        failed += 1;
+                    }
+                }
+                if failed > 0 {
+                    return Err(anyhow::anyhow!("{} 个病毒库文件校验失败", failed));
+                }
+                println!("{}", t!("database.verify_passed", count = checks.len()));
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_config(args: &ConfigArgs, config_path: &PathBuf, output: OutputFormat) -> Result<ExitStatus> {
+        match &args.action {
+            ConfigAction::Init { path, force } => {
+                let path = path.as_ref().unwrap_or(config_path);
+                if path.exists() && !force {
+                    return Err(anyhow::anyhow!("配置文件已存在: {:?}，使用 --force 覆盖", path));
+                }
+                ScannerConfig::default().save(path).with_context(|| format!("无法写入配置文件: {:?}", path))?;
+                if output.is_json() {
+                    print_json("config", &json!({ "action": "init", "path": path }))?;
+                } else {
+                    println!("{}", t!("config.initialized", path = format!("{:?}", path)));
+                }
+            }
+            ConfigAction::Validate { path } => {
+                let path = path.as_ref().unwrap_or(config_path);
+                let check = crate::config::check::check_file(path)?;
+                if output.is_json() {
+                    print_json("config", &json!({
+                        "success": check.is_valid(),
+                        "action": "validate",
+                        "path": path,
+                        "valid": check.is_valid(),
+                        "errors": check.errors,
+                        "warnings": check.warnings,
+                    }))?;
+                } else {
+                    // 与编译器相同的 文件:行: 消息 格式，便于编辑器跳转
+                    for (level, issue) in check.errors.iter().map(|issue| (t!("config.level_error"), issue))
+                        .chain(check.warnings.iter().map(|issue| (t!("config.level_warning"), issue)))
+                    {
+                        match issue.line {
+                            Some(line) => println!("{}:{}: {}: {}", path.display(), line, level, issue.message),
+                            None => println!("{}: {}: {}", path.display(), level, issue.message),
+                        }
+                    }
+                    if check.is_valid() {
+                        println!("{}", t!("config.valid", path = format!("{:?}", path)));
+                    } else {
+                        println!("{}", t!("config.invalid", count = check.errors.len()));
+                    }
+                }
+                if !check.is_valid() {
+                    return Ok(ExitStatus::Error);
+                }
+            }
+            ConfigAction::Show { effective } => {
+                let mut config = Self::load_config_file(config_path)?;
+                let overrides = if *effective { config.apply_env_overrides()? } else { Vec::new() };
+                if output.is_json() {
+                    print_json("config", &json!({
+                        "action": "show",
+                        "path": config_path,
+                        "effective": effective,
+                        "overrides": overrides,
+                        "config": config,
+                    }))?;
+                } else {
+                    for item in &overrides {
+                        println!("# {}", t!("config.overridden", key = item.key, variable = item.variable));
+                    }
+                    print!("{}", serde_yaml::to_string(&config)?);
+                }
+            }
+            ConfigAction::Set { key, value } => {
+                let mut config = Self::load_config_file(config_path)?;
+                config.set_value(key, value)?;
+                config.validate()?;
+                config.save(config_path).with_context(|| format!("无法保存配置文件: {:?}", config_path))?;
+                if output.is_json() {
+                    print_json("config", &json!({ "action": "set", "path": config_path, "key": key, "value": value }))?;
+                } else {
+                    println!("{}", t!("config.set", key = key, value = value));
+                }
+            }
+        }
+        Ok(ExitStatus::Clean)
+    }
+
+    // add 和 remove 修改配置文件 (不含环境变量覆盖)，daemon 正在运行时发送 SIGHUP 使其重新加载
+    fn handle_schedule(args: &ScheduleArgs, config: &ScannerConfig, config_path: &PathBuf, output: OutputFormat) -> Result<()> {
+        let text = !output.is_json();
+        let (action, name) = match &args.action {
+            ScheduleAction::List => {
+                let schedules = schedule::summarize(config);
+                if !text {
+                    return print_json("schedule", &json!({ "action": "list", "schedules": schedules }));
+                }
+                if schedules.is_empty() {
+                    println!("{}", t!("schedule.none"));
+                    return Ok(());
+                }
+                println!(
+                    "{:<20} {:<16} {:<12} {:<20} {:<20} {}",
+                    t!("schedule.col_name"),
+                    "cron",
+                    t!("schedule.col_type"),
+                    t!("schedule.col_next_run"),
+                    t!("schedule.col_last_run"),
+                    t!("schedule.col_last_result")
+                );
+                for summary in &schedules {
+                    let next_run = match (summary.schedule.enabled, summary.next_run) {
+                        (false, _) => format!("({})", t!("schedule.disabled")),
+                        (true, Some(at)) => at.format("%Y-%m-%d %H:%M").to_string(),
+                        (true, None) => "-".to_string(),
+                    };
+                    let (last_run, result) = match summary.last_run {
+                        Some(ref run) => (
+                            run.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string(),
+                            match run.error {
+                                Some(ref error) => t!("schedule.run_failed", error = error),
+                                None => t!(
+                                    "schedule.run_result",
+                                    status = run.status,
+                                    files = run.files_scanned,
+                                    threats = run.threats_found
+                                ),
+                            },
+                        ),
+                        None => ("-".to_string(), String::new()),
+                    };
+                    println!(
+                        "{:<20} {:<16} {:<12} {:<20} {:<20} {}",
+                        summary.schedule.name, summary.schedule.cron, summary.schedule.scan_type, next_run, last_run, result
+                    );
+                    if !summary.schedule.paths.is_empty() {
+                        println!("{:<20} {}", "", t!("schedule.paths", paths = summary.schedule.paths.join(", ")));
+                    }
+                }
+                return Ok(());
+            }
+            ScheduleAction::Add { name, cron, scan_type, paths, disabled } => {
+                let mut file_config = Self::load_config_file(config_path)?;
+                if file_config.scheduled_scans.iter().any(|schedule| schedule.name == *name) {
+                    return Err(anyhow::anyhow!("定时扫描已存在: {}", name));
+                }
+                file_config.scheduled_scans.push(ScheduledScanConfig {
+                    name: name.clone(),
+                    cron: cron.clone(),
+                    scan_type: scan_type.clone(),
+                    paths: paths.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+                    enabled: !disabled,
+                });
+                file_config.validate()?;
+                file_config.save(config_path).with_context(|| format!("无法保存配置文件: {:?}", config_path))?;
+                if text {
+                    println!("{}", t!("schedule.added", name = name));
+                    let next_run = cron.parse::<CronSchedule>().ok().and_then(|cron| cron.next_after(chrono::Local::now()));
+                    if let (false, Some(at)) = (disabled, next_run) {
+                        println!("{}", t!("schedule.next_run", time = at.format("%Y-%m-%d %H:%M")));
+                    }
+                }
+                ("add", name)
+            }
+            ScheduleAction::Remove { name } => {
+                let mut file_config = Self::load_config_file(config_path)?;
+                let count = file_config.scheduled_scans.len();
+                file_config.scheduled_scans.retain(|schedule| schedule.name != *name);
+                if file_config.scheduled_scans.len() == count {
+                    return Err(anyhow::anyhow!("未找到定时扫描: {}", name));
+                }
+                file_config.save(config_path).with_context(|| format!("无法保存配置文件: {:?}", config_path))?;
+                if text {
+                    println!("{}", t!("schedule.removed", name = name));
+                }
+                ("remove", name)
+            }
+        };
+
+        let daemon_pid = control::running_pid(&config.daemon.pid_file);
+        let reloaded = match daemon_pid {
+            Some(pid) => (unsafe { libc::kill(pid as i32, libc::SIGHUP) }) == 0,
+            None => false,
+        };
+        if !text {
+            return print_json("schedule", &json!({
+                "action": action,
+                "name": name,
+                "path": config_path,
+                "daemon_pid": daemon_pid,
+                "daemon_reloaded": reloaded,
+            }));
+        }
+        match daemon_pid {
+            Some(pid) if reloaded => println!("{}", t!("schedule.daemon_reloaded", pid = pid)),
+            Some(pid) => println!("{}", t!("schedule.daemon_reload_failed", pid = pid, error = std::io::Error::last_os_error())),
+            None => println!("{}", t!("schedule.daemon_not_running")),
+        }
+        Ok(())
+    }
+
+    // 与其他命令一致，配置文件不存在时使用默认配置，但不写入文件。不应用环境变量覆盖
+    fn load_config_file(config_path: &PathBuf) -> Result<ScannerConfig> {
+        if config_path.exists() {
+            ScannerConfig::load(config_path).with_context(|| format!("无法加载配置文件: {:?}", config_path))
+        } else {
+            Ok(ScannerConfig::default())
+        }
+    }
+
+    // 从病毒库目录加载病毒库 (与 scan 使用的相同)，并查询服务进程和监控进程是否在运行
     async fn handle_status(
         args: &StatusArgs,
         config: &ScannerConfig,
         signature_db: &Arc<SignatureDatabase>,
+        output: OutputFormat,
     ) -> Result<()> {
-        println!("病毒查杀工具状态");
-        println!("================");
+        let database_path = &config.update.database_path;
+        let status = Self::collect_status(config, signature_db).await;
+        if output.is_json() {
+            return print_json("status", &Self::status_json(args, config, signature_db, &status).await);
+        }
+        let StatusInfo { load_error, updated_at, headers, daemon_pid, monitor, schedules } = status;
+
+        let title = t!("status.title");
+        println!("{}", title);
+        // 中文字符在终端中占两列
+        println!("{}", "=".repeat(title.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()));
+
+        match daemon_pid {
+            Some(pid) => println!("{}", t!("status.daemon_running", pid = pid)),
+            None => println!("{}", t!("status.daemon_stopped")),
+        }
+        match monitor {
+            Some(ref status) => println!("{}", t!("status.monitor_running", pid = status.pid, paths = status.watch_paths.len())),
+            None => println!("{}", t!("status.monitor_stopped")),
+        }
 
+        println!("\n{}", t!("status.database"));
+        println!("  {}", t!("status.database_dir", path = format!("{:?}", database_path)));
+        if let Some(ref error) = load_error {
+            println!("  {}", t!("status.database_load_failed", error = error));
+        }
+        println!("  {}", t!("status.signatures", count = signature_db.get_signature_count().await));
+        println!("  {}", t!("status.database_version", version = signature_db.get_version()));
+        match updated_at {
+            Some(at) => println!("  {}", t!("status.updated_at", time = at.format("%Y-%m-%d %H:%M:%S"))),
+            None => println!("  {}", t!("status.never_updated")),
+        }
         if args.database || args.system {
-            println!("\n病毒库信息:");
-            println!("  签名数量: {}", signature_db.get_signature_count().await);
-            println!("  内存占用: {:.2} MB", signature_db.get_memory_usage() as f64 / 1024.0 / 1024.0);
-            println!("  最后更新: {:?}", signature_db.get_last_update());
-            println!("  病毒库版本: {}", signature_db.get_version());
+            println!("  {}", t!("status.memory", size = format!("{:.2}", signature_db.get_memory_usage() as f64 / 1024.0 / 1024.0)));
+            for (name, header) in &headers {
+                println!(
+                    "  {}: {}",
+                    name,
+                    t!("status.cvd_header", version = header.version, build_time = header.build_time, count = header.signature_count)
+                );
+            }
         }
 
         if args.system {
-            println!("\n系统信息:");
-            println!("  线程数: {}", config.performance.thread_pool_size);
-            println!("  CPU限制: {}%", config.performance.cpu_usage_limit);
-            println!("  内存限制: {} MB", config.performance.memory_limit_mb);
+            println!("\n{}", t!("status.system"));
+            println!("  {}", t!("status.threads", count = config.performance.thread_pool_size));
+            println!("  {}", t!("status.cpu_limit", percent = config.performance.cpu_usage_limit));
+            println!("  {}", t!("status.memory_limit", size = config.performance.memory_limit_mb));
+        }
+
+        if !schedules.is_empty() {
+            println!("\n{}", t!("status.scheduled_scans"));
+            for summary in &schedules {
+                let next_run = match summary.next_run {
+                    Some(at) if summary.schedule.enabled => at.format("%Y-%m-%d %H:%M").to_string(),
+                    _ => "-".to_string(),
+                };
+                let last_run = match summary.last_run {
+                    Some(ref run) => {
+                        let result = match run.report_id {
+                            Some(ref id) => t!("status.schedule_result_report", status = run.status, threats = run.threats_found, report = id),
+                            None => t!("status.schedule_result", status = run.status, threats = run.threats_found),
+                        };
+                        format!("{} ({})", run.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"), result)
+                    }
+                    None => "-".to_string(),
+                };
+                println!("  {}: {}", summary.schedule.name, t!("status.schedule_runs", next = next_run, last = last_run));
+            }
         }
 
         Ok(())
     }
+
+    // 加载磁盘上的病毒库，查询 daemon 和监控进程
+    async fn collect_status(config: &ScannerConfig, signature_db: &Arc<SignatureDatabase>) -> StatusInfo {
+        let database_path = &config.update.database_path;
+        let load_error = signature_db.load_from_directory(database_path).await.err().map(|e| format!("{:#}", e));
+        let mut headers: Vec<_> = signature_db.get_database_headers().into_iter().collect();
+        headers.sort_by(|a, b| a.0.cmp(&b.0));
+        let monitor = match control::query_status(&config.monitor.daemon).await {
+            Ok(status) => status,
+            Err(e) => {
+                log::warn!("无法查询监控状态: {:#}", e);
+                None
+            }
+        };
+        StatusInfo {
+            load_error,
+            updated_at: Self::database_updated_at(database_path),
+            headers,
+            daemon_pid: control::running_pid(&config.daemon.pid_file),
+            monitor,
+            schedules: schedule::summarize(config),
+        }
+    }
+
+    async fn status_json(
+        args: &StatusArgs,
+        config: &ScannerConfig,
+        signature_db: &Arc<SignatureDatabase>,
+        status: &StatusInfo,
+    ) -> serde_json::Value {
+        let mut database = json!({
+            "path": config.update.database_path,
+            "loaded": status.load_error.is_none(),
+            "error": status.load_error,
+            "signatures": signature_db.get_signature_count().await,
+            "version": signature_db.get_version(),
+            "updated_at": status.updated_at,
+        });
+        if args.database || args.system {
+            database["memory_bytes"] = json!(signature_db.get_memory_usage());
+            database["files"] = status
+                .headers
+                .iter()
+                .map(|(name, header)| json!({
+                    "name": name,
+                    "version": header.version,
+                    "build_time": header.build_time,
+                    "signatures": header.signature_count,
+                }))
+                .collect();
+        }
+        let system = json!({
+            "thread_pool_size": config.performance.thread_pool_size,
+            "cpu_usage_limit": config.performance.cpu_usage_limit,
+            "memory_limit_mb": config.performance.memory_limit_mb,
+        });
+        json!({
+            "daemon": { "running": status.daemon_pid.is_some(), "pid": status.daemon_pid },
+            "monitor": { "running": status.monitor.is_some(), "status": status.monitor },
+            "database": database,
+            "system": args.system.then_some(system),
+            "scheduled_scans": status.schedules,
+        })
+    }
+
+    // 病毒库目录中最近修改的文件的修改时间，即最近一次更新或添加本地特征码的时间
+    fn database_updated_at(database_path: &Path) -> Option<chrono::DateTime<chrono::Local>> {
+        std::fs::read_dir(database_path)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.file_type().map_or(false, |file_type| file_type.is_file()))
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max()
+            .map(chrono::DateTime::from)
+    }
 }
+
+#[cfg(test)]
+mod tests;
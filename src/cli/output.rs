@@ -0,0 +1,46 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    // 中文文本
+    #[default]
+    Text,
+    // 标准输出只有一个 JSON 对象，供脚本和自动化工具解析。进度仍输出到标准错误
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(&self) -> bool {
+        *self == OutputFormat::Json
+    }
+}
+
+// 所有命令的 JSON 输出都带有 success 和 command 字段，其余字段由 body 提供。
+// body 中没有 success 时为 true
+pub fn json_output<T: Serialize>(command: &str, body: &T) -> anyhow::Result<Value> {
+    let mut value = serde_json::to_value(body)?;
+    let fields = match value {
+        Value::Object(ref mut fields) => fields,
+        _ => return Err(anyhow::anyhow!("JSON 输出必须是对象")),
+    };
+    fields.entry("success").or_insert(Value::Bool(true));
+    fields.insert("command".to_string(), Value::String(command.to_string()));
+    Ok(value)
+}
+
+pub fn print_json<T: Serialize>(command: &str, body: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&json_output(command, body)?)?);
+    Ok(())
+}
+
+// 命令执行失败时的输出，causes 为 anyhow 错误链中的各层原因
+pub fn error_json(error: &anyhow::Error) -> String {
+    let value = serde_json::json!({
+        "success": false,
+        "error": error.to_string(),
+        "causes": error.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&value).unwrap_or_else(|_| error.to_string())
+}
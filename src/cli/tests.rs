@@ -0,0 +1,347 @@
+use crate::cli::output::{error_json, json_output, OutputFormat};
+use crate::cli::{BenchmarkArgs, Command, ExitStatus, ServeArgs, StatusArgs, SubCommands};
+use crate::config::ScannerConfig;
+use crate::scanner::benchmark::run_benchmark;
+use crate::scanner::cvd::CVD_HEADER_SIZE;
+use crate::scanner::{eicar_test_string, ScanMode, ScanOptions, ScannerEngine, SignatureDatabase};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use clap::Parser;
+use log::LevelFilter;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 与 scan 命令相同，按扫描结果和错误数得出退出码
+    async fn scan_exit_code(paths: Vec<PathBuf>) -> i32 {
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let mut options = ScanOptions::from_config(&ScannerConfig::default(), ScanMode::Custom, paths);
+        options.exclude_paths.clear();
+        options.use_xattr_markers = false;
+        let engine = ScannerEngine::new(signature_db, options);
+        let results = engine.start_scan().await.unwrap();
+        ExitStatus::for_scan(!results.is_empty(), engine.get_stats().get_errors()).code()
+    }
+
+    #[tokio::test]
+    async fn test_scan_exit_codes() {
+        let clean = tempfile::tempdir().unwrap();
+        std::fs::write(clean.path().join("readme.txt"), b"harmless").unwrap();
+        let infected = tempfile::tempdir().unwrap();
+        std::fs::write(infected.path().join("eicar.com"), eicar_test_string()).unwrap();
+        let missing = clean.path().join("missing");
+
+        assert_eq!(scan_exit_code(vec![clean.path().to_path_buf()]).await, 0);
+        assert_eq!(scan_exit_code(vec![infected.path().to_path_buf()]).await, 1);
+        assert_eq!(scan_exit_code(vec![clean.path().to_path_buf(), missing.clone()]).await, 2);
+        // 同时发现威胁和出错时按发现威胁处理
+        assert_eq!(scan_exit_code(vec![infected.path().to_path_buf(), missing]).await, 1);
+    }
+
+    // root 不受文件权限限制，以 root 运行时跳过
+    #[tokio::test]
+    async fn test_unreadable_file_exits_with_error() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("readme.txt"), b"harmless").unwrap();
+        let locked = dir.path().join("locked.bin");
+        std::fs::write(&locked, b"harmless").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        if std::fs::read(&locked).is_ok() {
+            return;
+        }
+
+        // 无法读取的文件计入错误，不当作干净文件
+        assert_eq!(scan_exit_code(vec![dir.path().to_path_buf()]).await, 2);
+    }
+
+    #[test]
+    fn test_exit_status_codes() {
+        assert_eq!(ExitStatus::for_scan(false, 0), ExitStatus::Clean);
+        assert_eq!(ExitStatus::for_scan(true, 0), ExitStatus::Infected);
+        assert_eq!(ExitStatus::for_scan(false, 3), ExitStatus::Error);
+        assert_eq!(ExitStatus::for_scan(true, 3), ExitStatus::Infected);
+        assert_eq!(
+            [ExitStatus::Clean, ExitStatus::Infected, ExitStatus::Error].map(|status| status.code()),
+            [0, 1, ExitStatus::ERROR_CODE]
+        );
+        assert_eq!(ExitStatus::Error.as_str(), "error");
+    }
+
+    // 所有文件和目录都放在 dir 中的配置
+    fn sandbox_config(dir: &Path) -> ScannerConfig {
+        let mut config = ScannerConfig::default();
+        config.scan_modes.xattr_marker_key_file = dir.join("marker.key");
+        config.scan_modes.checkpoint.path = dir.join("scan.checkpoint");
+        config.scan_modes.verdict_cache.path = dir.join("verdicts.db");
+        config.security.quarantine_dir = dir.join("quarantine");
+        config.logging.log_dir = dir.join("log");
+        config.update.database_path = dir.join("database");
+        config.update.backup_path = dir.join("backup");
+        config.monitor.daemon.pid_file = dir.join("monitor.pid");
+        config.monitor.daemon.control_socket = dir.join("monitor.sock");
+        config.report.output_dir = dir.join("reports");
+        config.report.threat_store.path = dir.join("threats.jsonl");
+        config.daemon.pid_file = dir.join("daemon.pid");
+        config.api.keys_file = Some(dir.join("api-keys.yaml"));
+        config.api.auth.jwt_secret_file = dir.join("api-jwt.key");
+        config
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    #[tokio::test]
+    async fn test_serve_runs_api_until_sigterm() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = sandbox_config(dir.path());
+        let config_path = dir.path().join("config.yaml");
+        let port = free_port();
+        let args = ServeArgs {
+            addr: Some(format!("127.0.0.1:{}", port)),
+            monitor: false,
+            schedule_updates: false,
+        };
+        // 先注册 SIGTERM 处理，服务开始等待信号之前收到的 SIGTERM 不会结束测试进程
+        let _terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
+        let mut server = tokio::spawn(async move { Command::handle_serve(&args, &config, &config_path).await });
+
+        let url = format!("http://127.0.0.1:{}/health", port);
+        let mut report = None;
+        for _ in 0..100 {
+            if let Ok(response) = reqwest::get(&url).await {
+                report = Some(response.json::<serde_json::Value>().await.unwrap());
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let report = report.expect("API 服务未启动");
+        assert!(report["data"]["status"].is_string());
+        assert!(!server.is_finished());
+
+        // 收到 SIGTERM 后关闭 API 并正常返回
+        let result = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+                tokio::select! {
+                    result = &mut server => break result.unwrap(),
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                }
+            }
+        })
+        .await
+        .expect("serve 收到 SIGTERM 后没有退出");
+        assert!(result.is_ok());
+        assert!(reqwest::get(&url).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_rejects_invalid_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = sandbox_config(dir.path());
+        config.api.listen = "localhost".to_string();
+        let args = ServeArgs {
+            addr: None,
+            monitor: false,
+            schedule_updates: false,
+        };
+
+        // 未指定 --addr 时使用 api.listen，地址无效时在初始化前报错
+        let error = Command::handle_serve(&args, &config, &dir.path().join("config.yaml")).await.unwrap_err();
+        assert!(error.to_string().contains("无效的监听地址"));
+        assert!(!config.update.database_path.exists());
+    }
+
+    #[test]
+    fn test_output_flag_selects_json() {
+        let command = Command::try_parse_from(["virus-scanner", "--output", "json", "scan", "-p", "/tmp"]).unwrap();
+        assert_eq!(command.output_format(), OutputFormat::Json);
+        let command = Command::try_parse_from(["virus-scanner", "scan", "-p", "/tmp"]).unwrap();
+        assert_eq!(command.output_format(), OutputFormat::Text);
+        // status 的 --json 与 --output json 相同
+        let command = Command::try_parse_from(["virus-scanner", "status", "--json"]).unwrap();
+        assert_eq!(command.output_format(), OutputFormat::Json);
+
+        assert!(Command::try_parse_from(["virus-scanner", "--output", "xml", "status"]).is_err());
+        // --output 只能放在子命令之前，report convert 有自己的 --output 文件参数
+        assert!(Command::try_parse_from(["virus-scanner", "scan", "--output", "json"]).is_err());
+        let command =
+            Command::try_parse_from(["virus-scanner", "report", "convert", "-i", "a.json", "-t", "html", "--output", "json"]).unwrap();
+        assert_eq!(command.output_format(), OutputFormat::Text);
+    }
+
+    #[test]
+    fn test_json_output_envelope() {
+        let value = json_output("scan", &serde_json::json!({ "status": "clean", "threats": [] })).unwrap();
+        assert_eq!(value["success"], true);
+        assert_eq!(value["command"], "scan");
+        assert_eq!(value["status"], "clean");
+
+        // body 中的 success 不被覆盖
+        let value = json_output("update", &serde_json::json!({ "success": false })).unwrap();
+        assert_eq!(value["success"], false);
+        assert_eq!(value["command"], "update");
+        assert!(json_output("status", &vec![1, 2]).is_err());
+
+        let error = anyhow::anyhow!("权限不足").context("无法加载配置文件");
+        let value: serde_json::Value = serde_json::from_str(&error_json(&error)).unwrap();
+        assert_eq!(value["success"], false);
+        assert_eq!(value["error"], "无法加载配置文件");
+        assert_eq!(value["causes"], serde_json::json!(["权限不足"]));
+    }
+
+    // 返回控制台日志级别和写入日志文件的级别
+    fn log_levels(args: &[&str]) -> (LevelFilter, String) {
+        let command = Command::try_parse_from(["virus-scanner"].iter().chain(args)).unwrap();
+        let mut config = ScannerConfig::default();
+        config.logging.level = "info".to_string();
+        let console = Command::apply_log_flags(&command, &mut config);
+        (console, config.logging.level)
+    }
+
+    #[test]
+    fn test_log_level_flags() {
+        // 控制台默认只输出警告和错误，日志文件按配置记录
+        assert_eq!(log_levels(&["status"]), (LevelFilter::Warn, "info".to_string()));
+        assert_eq!(log_levels(&["serve"]), (LevelFilter::Info, "info".to_string()));
+        assert_eq!(log_levels(&["--log-level", "error", "status"]), (LevelFilter::Error, "error".to_string()));
+        assert_eq!(log_levels(&["--log-level", "trace", "status"]), (LevelFilter::Warn, "trace".to_string()));
+        assert_eq!(log_levels(&["--log-level", "trace", "daemon"]), (LevelFilter::Trace, "trace".to_string()));
+
+        // --verbose 至少记录 debug，不降低 --log-level 指定的级别
+        assert_eq!(log_levels(&["status", "-v"]), (LevelFilter::Debug, "debug".to_string()));
+        assert_eq!(log_levels(&["-v", "--log-level", "trace", "status"]), (LevelFilter::Trace, "trace".to_string()));
+
+        // --quiet 只影响控制台
+        assert_eq!(log_levels(&["-q", "status"]), (LevelFilter::Error, "info".to_string()));
+        assert_eq!(log_levels(&["--quiet", "--log-level", "debug", "serve"]), (LevelFilter::Error, "debug".to_string()));
+
+        assert!(Command::try_parse_from(["virus-scanner", "-v", "-q", "status"]).is_err());
+        assert!(Command::try_parse_from(["virus-scanner", "--log-level", "loud", "status"]).is_err());
+    }
+
+    // 只包含 daily.ndb 的 .cvd 病毒库，没有有效的数字签名
+    fn build_cvd(version: u32, ndb: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(ndb.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "daily.ndb", ndb.as_bytes()).unwrap();
+        let body = builder.into_inner().unwrap().finish().unwrap();
+
+        let md5 = hex::encode(openssl::hash::hash(openssl::hash::MessageDigest::md5(), &body).unwrap());
+        let mut cvd = format!("ClamAV-VDB:16 Oct 2026 08-00 +0000:{}:2:90:{}:dsig:tester:1792137600", version, md5).into_bytes();
+        cvd.resize(CVD_HEADER_SIZE, b' ');
+        cvd.extend_from_slice(&body);
+        cvd
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_on_disk_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = sandbox_config(dir.path());
+        std::fs::create_dir_all(&config.update.database_path).unwrap();
+        let ndb = "Test.Status-1:0:*:7374617475732d6f6e65\nTest.Status-2:0:*:7374617475732d74776f\n";
+        std::fs::write(config.update.database_path.join("daily.cvd"), build_cvd(27, ndb)).unwrap();
+        std::fs::write(&config.daemon.pid_file, std::process::id().to_string()).unwrap();
+        let args = StatusArgs {
+            database: true,
+            system: false,
+            json: true,
+        };
+
+        // 读取磁盘上的病毒库，而不是新建的空病毒库
+        let signature_db = Arc::new(SignatureDatabase::new());
+        let status = Command::collect_status(&config, &signature_db).await;
+        let value = Command::status_json(&args, &config, &signature_db, &status).await;
+        assert_eq!(value["database"]["loaded"], true);
+        assert_eq!(value["database"]["signatures"], 2);
+        assert_eq!(value["database"]["version"], "27");
+        assert!(value["database"]["updated_at"].is_string());
+        assert_eq!(value["database"]["files"][0]["name"], "daily");
+        assert_eq!(value["database"]["files"][0]["version"], 27);
+        assert_eq!(value["daemon"], serde_json::json!({ "running": true, "pid": std::process::id() }));
+        assert_eq!(value["monitor"]["running"], false);
+        assert!(value["system"].is_null());
+
+        // 还没有下载病毒库，daemon 未运行
+        let empty = tempfile::tempdir().unwrap();
+        let config = sandbox_config(empty.path());
+        let args = StatusArgs {
+            database: false,
+            system: true,
+            json: true,
+        };
+        let signature_db = Arc::new(SignatureDatabase::new());
+        let status = Command::collect_status(&config, &signature_db).await;
+        let value = Command::status_json(&args, &config, &signature_db, &status).await;
+        assert_eq!(value["database"]["signatures"], 0);
+        assert!(value["database"]["updated_at"].is_null());
+        assert_eq!(value["database"]["files"], serde_json::json!([]));
+        assert_eq!(value["daemon"]["running"], false);
+        assert_eq!(value["system"]["thread_pool_size"], config.performance.thread_pool_size);
+    }
+
+    fn benchmark_args(args: &[&str]) -> BenchmarkArgs {
+        let command = Command::try_parse_from(["virus-scanner", "benchmark"].iter().chain(args)).unwrap();
+        match command.subcommand {
+            SubCommands::Benchmark(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_benchmark_thread_counts() {
+        let mut config = ScannerConfig::default();
+        config.performance.thread_pool_size = 6;
+        assert_eq!(Command::benchmark_threads(&benchmark_args(&["-p", "/data"]), &config), vec![1, 6]);
+        assert_eq!(Command::benchmark_threads(&benchmark_args(&["-p", "/data", "-t", "2,2,4"]), &config), vec![2, 4]);
+
+        // 线程池大小未设置时与 CPU 数比较
+        config.performance.thread_pool_size = 0;
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let mut expected = vec![1, cpus];
+        expected.dedup();
+        assert_eq!(Command::benchmark_threads(&benchmark_args(&["-p", "/data"]), &config), expected);
+
+        assert!(Command::try_parse_from(["virus-scanner", "benchmark"]).is_err());
+        assert!(Command::try_parse_from(["virus-scanner", "benchmark", "-p", "/data", "-t", "two"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_measures_scan_and_cache_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = sandbox_config(dir.path());
+        let data = dir.path().join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        for i in 0..20 {
+            std::fs::write(data.join(format!("sample-{}.bin", i)), format!("benchmark sample {}", i).repeat(100)).unwrap();
+        }
+
+        let report = run_benchmark(&config, &data, &[1, 2]).await.unwrap();
+        assert_eq!(report.files, 20);
+        assert!(report.signature_count > 0);
+        let runs: Vec<(&str, usize)> = report.runs.iter().map(|run| (run.name.as_str(), run.threads)).collect();
+        assert_eq!(
+            runs,
+            [("scan", 1), ("scan", 2), ("verdict_cache_cold", 2), ("verdict_cache_warm", 2)]
+        );
+        assert_eq!(report.runs[0].speedup, 1.0);
+        assert!(report.runs.iter().all(|run| run.files_per_s > 0.0));
+        // 只有缓存的两轮有命中率，第二轮全部命中
+        assert!(report.runs[..2].iter().all(|run| run.cache_hit_rate.is_none()));
+        assert_eq!(report.runs[2].cache_hit_rate, Some(0.0));
+        assert_eq!(report.runs[3].cache_hit_rate, Some(1.0));
+
+        let empty = dir.path().join("empty");
+        std::fs::create_dir_all(&empty).unwrap();
+        assert!(run_benchmark(&config, &empty, &[1]).await.is_err());
+        assert!(run_benchmark(&config, &dir.path().join("missing"), &[1]).await.is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::config::{ConfigIssue, ScannerConfig};
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+// config validate 的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConfigCheck {
+    // YAML 语法错误、类型错误和取值错误，有错误时无法加载或加载后无法运行
+    pub errors: Vec<ConfigIssue>,
+    // 未知的配置项会被忽略，通常是拼写错误或旧版本的配置项
+    pub warnings: Vec<ConfigIssue>,
+}
+
+impl ConfigCheck {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+pub fn check_file(path: &Path) -> Result<ConfigCheck, anyhow::Error> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("无法读取配置文件: {:?}", path))?;
+    Ok(check_source(&source))
+}
+
+// 类型错误时 serde 在第一个错误处停止，此时只报告这一个错误
+pub fn check_source(source: &str) -> ConfigCheck {
+    let mut check = ConfigCheck::default();
+    let document: Value = match serde_yaml::from_str(source) {
+        Ok(document) => document,
+        Err(e) => {
+            check.errors.push(yaml_issue(e));
+            return check;
+        }
+    };
+    let config: ScannerConfig = match serde_yaml::from_str(source) {
+        Ok(config) => config,
+        Err(e) => {
+            check.errors.push(yaml_issue(e));
+            return check;
+        }
+    };
+
+    let mut unknown = Vec::new();
+    let known = serde_json::to_value(&config).unwrap_or_default();
+    unknown_keys(&document, &known, "", &mut unknown);
+    for key in unknown {
+        check.warnings.push(ConfigIssue {
+            line: locate_key(source, &key),
+            message: format!("未知的配置项: {}", key),
+            key,
+        });
+    }
+    for mut issue in config.check() {
+        issue.line = locate_key(source, &issue.key);
+        check.errors.push(issue);
+    }
+    check
+}
+
+fn yaml_issue(error: serde_yaml::Error) -> ConfigIssue {
+    ConfigIssue {
+        key: String::new(),
+        line: error.location().map(|location| location.line()),
+        message: error.to_string(),
+    }
+}
+
+// 配置文件中有、重新序列化后没有的键
+fn unknown_keys(document: &Value, known: &Value, prefix: &str, unknown: &mut Vec<String>) {
+    let join = |segment: &str| if prefix.is_empty() { segment.to_string() } else { format!("{}.{}", prefix, segment) };
+    match (document, known) {
+        (Value::Object(document), Value::Object(known)) => {
+            for (name, value) in document {
+                match known.get(name) {
+                    Some(known) => unknown_keys(value, known, &join(name), unknown),
+                    None => unknown.push(join(name)),
+                }
+            }
+        }
+        (Value::Array(document), Value::Array(known)) => {
+            for (index, (value, known)) in document.iter().zip(known).enumerate() {
+                unknown_keys(value, known, &join(&index.to_string()), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Frame {
+    indent: usize,
+    segment: String,
+    // 列表项的下标是否由该帧分配
+    list_item: bool,
+    next_index: usize,
+}
+
+// 查找点分隔的配置项在 YAML 文本中的行号 (从 1 开始)。只识别配置文件使用的块格式，
+// 写在 {...}、[...] 中的项无法定位
+pub fn locate_key(source: &str, key: &str) -> Option<usize> {
+    if key.is_empty() {
+        return None;
+    }
+    let mut stack: Vec<Frame> = Vec::new();
+    // 多行字符串 (| 或 >) 所属键的缩进，其内容不按键解析
+    let mut block_scalar: Option<usize> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        if let Some(block_indent) = block_scalar {
+            if content.is_empty() || indent > block_indent {
+                continue;
+            }
+            block_scalar = None;
+        }
+        if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+            continue;
+        }
+
+        let (indent, content) = if content == "-" || content.starts_with("- ") {
+            // 上一个列表项及其中的键结束
+            while stack.last().map_or(false, |top| top.indent > indent || (top.indent == indent && top.list_item)) {
+                stack.pop();
+            }
+            let index_segment = match stack.last_mut() {
+                Some(parent) => {
+                    parent.next_index += 1;
+                    (parent.next_index - 1).to_string()
+                }
+                None => continue,
+            };
+            stack.push(Frame {
+                indent,
+                segment: index_segment,
+                list_item: true,
+                next_index: 0,
+            });
+            if path_of(&stack) == key {
+                return Some(index + 1);
+            }
+            let item = content[1..].trim_start();
+            (indent + (content.len() - item.len()), item)
+        } else {
+            (indent, content)
+        };
+
+        let Some((name, value)) = split_key(content) else {
+            continue;
+        };
+        while stack.last().map_or(false, |top| top.indent >= indent) {
+            stack.pop();
+        }
+        stack.push(Frame {
+            indent,
+            segment: name,
+            list_item: false,
+            next_index: 0,
+        });
+        if path_of(&stack) == key {
+            return Some(index + 1);
+        }
+        if value.starts_with('|') || value.starts_with('>') {
+            block_scalar = Some(indent);
+        }
+    }
+    None
+}
+
+fn split_key(content: &str) -> Option<(String, &str)> {
+    let (name, value) = match content.split_once(": ") {
+        Some((name, value)) => (name, value.trim()),
+        None => (content.strip_suffix(':')?, ""),
+    };
+    let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+    (!name.is_empty()).then(|| (name.to_string(), value))
+}
+
+fn path_of(stack: &[Frame]) -> String {
+    stack.iter().map(|frame| frame.segment.as_str()).collect::<Vec<_>>().join(".")
+}
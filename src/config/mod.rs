@@ -1,4 +1,10 @@
+pub mod check;
+
+use crate::scanner::RiskLevel;
+use crate::utils::i18n::Language;
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 use num_cpus;
@@ -13,22 +19,312 @@ pub struct ScannerConfig {
     pub update: UpdateConfig,
     pub monitor: MonitorConfig,
     pub report: ReportConfig,
+    #[serde(default)]
+    pub milter: MilterConfig,
+    #[serde(default)]
+    pub allowlist: AllowlistConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub scheduled_scans: Vec<ScheduledScanConfig>,
+    // 命令行输出和报告使用的语言 (zh 或 en)，未设置时按 LC_ALL、LC_MESSAGES、LANG 选择
+    #[serde(default)]
+    pub language: Option<Language>,
+}
+
+// 白名单中的文件不会被报告为威胁
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AllowlistConfig {
+    pub sha256: Vec<String>,
+    // 通配符规则，不含通配符的路径按目录前缀匹配
+    pub paths: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanModesConfig {
     pub quick_scan_paths: Vec<String>,
+    // 扫描时优先遍历的高风险位置，尽早发现威胁
+    #[serde(default = "default_priority_paths")]
+    pub priority_paths: Vec<String>,
     pub exclude_paths: Vec<String>,
     pub exclude_extensions: Vec<String>,
     pub max_file_size: u64,
+    #[serde(default)]
+    pub use_xattr_markers: bool,
+    // 扫描标记的 HMAC 密钥，不存在时自动生成；只能由运行扫描的用户读取
+    #[serde(default = "default_xattr_marker_key_file")]
+    pub xattr_marker_key_file: PathBuf,
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    #[serde(default)]
+    pub heuristics: HeuristicsConfig,
+    #[serde(default)]
+    pub pdf: PdfConfig,
+    #[serde(default)]
+    pub mail: MailConfig,
+    // 图片/音视频只做特征码和哈希匹配，不做启发式检测
+    #[serde(default = "default_skip_benign_types")]
+    pub skip_benign_types: bool,
+    // 快速扫描和全盘扫描结束后检查隐藏进程、隐藏内核模块和预加载库
+    #[serde(default = "default_rootkit_check")]
+    pub rootkit_check: bool,
+    // 遵循扫描目录中的 .scanignore 文件，严格环境下应关闭以免被用来隐藏文件
+    #[serde(default = "default_honor_scanignore")]
+    pub honor_scanignore: bool,
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+    #[serde(default)]
+    pub verdict_cache: VerdictCacheConfig,
+    #[serde(default)]
+    pub url: UrlScanConfig,
+    // 单次扫描的最长时间 (秒)，0 表示不限制
+    #[serde(default)]
+    pub max_duration_secs: u64,
+    #[serde(default = "default_progress_interval_ms")]
+    pub progress_interval_ms: u64,
+    #[serde(default)]
+    pub action: DetectionAction,
+}
+
+// 发现威胁后对文件的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionAction {
+    #[default]
+    Report,
+    Quarantine,
+    Delete,
+    // 基于签名无法安全地修复文件，清除操作按隔离处理，保留恢复的可能
+    Clean,
+}
+
+impl DetectionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectionAction::Report => "report",
+            DetectionAction::Quarantine => "quarantine",
+            DetectionAction::Delete => "delete",
+            DetectionAction::Clean => "clean",
+        }
+    }
+}
+
+impl std::str::FromStr for DetectionAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "report" => Ok(DetectionAction::Report),
+            "quarantine" => Ok(DetectionAction::Quarantine),
+            "delete" => Ok(DetectionAction::Delete),
+            "clean" => Ok(DetectionAction::Clean),
+            _ => Err(anyhow::anyhow!("无效的处理方式: {} (可选 report, quarantine, delete, clean)", s)),
+        }
+    }
+}
+
+fn default_skip_benign_types() -> bool {
+    true
+}
+
+fn default_xattr_marker_key_file() -> PathBuf {
+    PathBuf::from("/var/lib/virus-scanner/marker.key")
+}
+
+fn default_priority_paths() -> Vec<String> {
+    ["/tmp", "/var/tmp", "/var/www", "/home/*/Downloads", "/root/Downloads"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn default_rootkit_check() -> bool {
+    true
+}
+
+fn default_honor_scanignore() -> bool {
+    true
+}
+
+fn default_progress_interval_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    pub enabled: bool,
+    pub max_depth: usize,
+    pub max_decompressed_size: u64,
+    // 单个压缩包中解出的成员总数上限，包括嵌套压缩包中的成员
+    pub max_members: usize,
+    // 单个成员解压后与压缩后大小之比的上限，0 表示不限制
+    pub max_expansion_ratio: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_depth: 5,
+            max_decompressed_size: 256 * 1024 * 1024,
+            max_members: 10000,
+            max_expansion_ratio: 250,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfConfig {
+    pub enabled: bool,
+    // 单个 PDF 中所有流的最大解压总量
+    pub max_decompressed_size: u64,
+}
+
+impl Default for PdfConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_decompressed_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailConfig {
+    pub enabled: bool,
+    // 单封邮件的最大读取大小，超出部分不扫描
+    pub max_message_size: u64,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_message_size: 50 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    // 全盘扫描默认写入检查点，其他模式需在命令行指定 --checkpoint
+    pub enabled_for_full_scan: bool,
+    pub path: PathBuf,
+    pub interval_secs: u64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            enabled_for_full_scan: true,
+            path: PathBuf::from("/var/lib/virus-scanner/scan.checkpoint"),
+            interval_secs: 60,
+        }
+    }
+}
+
+// scan --url 下载远程对象的限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UrlScanConfig {
+    pub max_size_mb: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for UrlScanConfig {
+    fn default() -> Self {
+        Self {
+            max_size_mb: 100,
+            timeout_secs: 60,
+        }
+    }
+}
+
+// 按文件内容和病毒库版本缓存扫描结论，重复扫描时跳过未变化的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerdictCacheConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+}
+
+impl Default for VerdictCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("/var/lib/virus-scanner/verdicts.db"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeuristicsConfig {
+    pub enabled: bool,
+    pub threshold: u8,
+    pub script_sensitivity: ScriptSensitivity,
+}
+
+impl Default for HeuristicsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 60,
+            script_sensitivity: ScriptSensitivity::default(),
+        }
+    }
+}
+
+// 脚本启发式的灵敏度，灵敏度越高判定阈值越低，误报也越多
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptSensitivity {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ScriptSensitivity {
+    pub fn threshold(&self) -> u8 {
+        match self {
+            ScriptSensitivity::Low => 80,
+            ScriptSensitivity::Medium => 60,
+            ScriptSensitivity::High => 40,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceConfig {
     pub thread_pool_size: usize,
+    // 目录遍历线程数，0 表示自动
+    #[serde(default)]
+    pub walk_threads: usize,
     pub cpu_usage_limit: f64,
     pub memory_limit_mb: u64,
     pub scan_buffer_size: usize,
+    #[serde(default = "default_regex_time_budget_ms")]
+    pub regex_time_budget_ms: u64,
+    #[serde(default)]
+    pub use_mmap: bool,
+    #[serde(default)]
+    pub max_read_mb_per_s: u64,
+    #[serde(default)]
+    pub idle_io_priority: bool,
+}
+
+fn default_regex_time_budget_ms() -> u64 {
+    500
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +333,47 @@ pub struct SecurityConfig {
     pub database_encryption: bool,
     pub audit_log_enabled: bool,
     pub quarantine_dir: PathBuf,
+    // 32 字节原始密钥或 64 位十六进制字符串，设置后隔离文件使用 AES-256-GCM 加密
+    #[serde(default)]
+    pub quarantine_key_file: Option<PathBuf>,
+    #[serde(default)]
+    pub auto_quarantine: AutoQuarantineConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoQuarantineConfig {
+    pub enabled: bool,
+    // 达到该风险等级 (low, medium, high, critical) 的检测结果立即隔离
+    pub min_risk_level: String,
+}
+
+impl AutoQuarantineConfig {
+    // 无法识别的风险等级按未启用处理，避免拼写错误导致隔离所有检测结果
+    pub fn min_risk(&self) -> Option<RiskLevel> {
+        if !self.enabled {
+            return None;
+        }
+        match self.min_risk_level.to_lowercase().as_str() {
+            "low" => Some(RiskLevel::Low),
+            "medium" => Some(RiskLevel::Medium),
+            "high" => Some(RiskLevel::High),
+            "critical" => Some(RiskLevel::Critical),
+            other => {
+                log::warn!("无效的自动隔离风险等级: {}，自动隔离未启用", other);
+                None
+            }
+        }
+    }
+}
+
+impl Default for AutoQuarantineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_risk_level: "high".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,9 +382,44 @@ pub struct LoggingConfig {
     pub log_dir: PathBuf,
     pub max_size_mb: u64,
     pub max_files: usize,
+    #[serde(default)]
+    pub rotate_daily: bool,
+    #[serde(default)]
+    pub compress_rotated: bool,
+    #[serde(default = "default_log_format")]
+    pub format: String,
+    #[serde(default)]
+    pub journald: bool,
+    #[serde(default)]
+    pub module_levels: HashMap<String, String>,
+    #[serde(default)]
+    pub detection_log: DetectionLogConfig,
     pub remote_logging: Option<RemoteLoggingConfig>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionLogConfig {
+    pub file_name: String,
+    pub max_size_mb: u64,
+    pub max_files: usize,
+    pub compress_rotated: bool,
+}
+
+impl Default for DetectionLogConfig {
+    fn default() -> Self {
+        Self {
+            file_name: "detections.log".to_string(),
+            max_size_mb: 50,
+            max_files: 10,
+            compress_rotated: true,
+        }
+    }
+}
+
+fn default_log_format() -> String {
+    "text".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteLoggingConfig {
     pub endpoint: String,
@@ -61,9 +433,89 @@ pub struct UpdateConfig {
     pub auto_download: bool,
     pub schedule: UpdateSchedule,
     pub mirror_url: String,
+    // 校验 .cvd 和 .cdiff 的 ClamAV 数字签名，关闭后接受未签名的病毒库 (只用于自建镜像)
     pub verify_signatures: bool,
     pub database_path: PathBuf,
     pub backup_path: PathBuf,
+    #[serde(default)]
+    pub misp: MispConfig,
+    #[serde(default)]
+    pub cdiff: CdiffConfig,
+    #[serde(default)]
+    pub backup_retention: BackupRetentionConfig,
+    // 安装后加载新病毒库并运行 EICAR 自检，失败时自动回滚
+    #[serde(default = "default_validate_after_update")]
+    pub validate_after_update: bool,
+}
+
+fn default_validate_after_update() -> bool {
+    true
+}
+
+// 每次更新前的备份按数量和天数清理，0 表示不限制，最新的备份始终保留
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRetentionConfig {
+    pub keep: usize,
+    pub max_age_days: u64,
+}
+
+impl Default for BackupRetentionConfig {
+    fn default() -> Self {
+        Self {
+            keep: 5,
+            max_age_days: 30,
+        }
+    }
+}
+
+// 本地版本落后不超过 max_version_gap 时逐个下载 .cdiff 增量更新，否则下载完整病毒库
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CdiffConfig {
+    pub enabled: bool,
+    pub max_version_gap: u32,
+}
+
+impl Default for CdiffConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_version_gap: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MispConfig {
+    pub enabled: bool,
+    pub url: Option<String>,
+    pub api_key: String,
+    pub feed_path: Option<PathBuf>,
+    pub verify_tls: bool,
+    pub only_to_ids: bool,
+    pub tags: Vec<String>,
+    pub threat_type: String,
+    pub risk_level: String,
+    pub refresh_interval_hours: u64,
+}
+
+impl Default for MispConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: None,
+            api_key: String::new(),
+            feed_path: None,
+            verify_tls: true,
+            only_to_ids: true,
+            tags: Vec::new(),
+            threat_type: "trojan".to_string(),
+            risk_level: "high".to_string(),
+            refresh_interval_hours: 6,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,6 +532,164 @@ pub struct MonitorConfig {
     pub watch_paths: Vec<String>,
     pub events: Vec<String>,
     pub actions: MonitorActions,
+    // 非 Linux 系统轮询监控目录的间隔
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    #[serde(default)]
+    pub on_access: OnAccessConfig,
+    #[serde(default)]
+    pub access_control: AccessControlConfig,
+    #[serde(default)]
+    pub daemon: MonitorDaemonConfig,
+    #[serde(default)]
+    pub journal: MonitorJournalConfig,
+    #[serde(default)]
+    pub filter: MonitorFilterConfig,
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+// 监控事件触发的实时扫描
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnAccessConfig {
+    // 同一文件在该时间内没有新事件后才扫描，避免写入过程中重复扫描
+    pub debounce_ms: u64,
+    // 待扫描队列长度，队列满时丢弃新事件
+    pub queue_size: usize,
+}
+
+// 基于 fanotify 权限事件的访问拦截，文件扫描完成前打开或执行会被挂起，发现威胁时拒绝访问。
+// 需要 root 权限 (CAP_SYS_ADMIN)，仅 Linux 可用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessControlConfig {
+    pub enabled: bool,
+    pub paths: Vec<AccessControlPath>,
+    // 并行扫描线程数
+    pub workers: usize,
+    // 无法读取或超过大小限制的文件是否拒绝访问
+    pub deny_on_error: bool,
+}
+
+impl Default for AccessControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paths: Vec::new(),
+            workers: 2,
+            deny_on_error: false,
+        }
+    }
+}
+
+// 只拦截目录下的直接子文件，与 inotify 监控相同
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessControlPath {
+    pub path: String,
+    #[serde(default = "default_block_exec")]
+    pub block_exec: bool,
+    #[serde(default)]
+    pub block_open: bool,
+}
+
+fn default_block_exec() -> bool {
+    true
+}
+
+// 后台运行的监控进程，monitor --stop/--status 通过控制套接字与其通信
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorDaemonConfig {
+    pub pid_file: PathBuf,
+    pub control_socket: PathBuf,
+}
+
+impl Default for MonitorDaemonConfig {
+    fn default() -> Self {
+        Self {
+            pid_file: PathBuf::from("/run/virus-scanner/monitor.pid"),
+            control_socket: PathBuf::from("/run/virus-scanner/monitor.sock"),
+        }
+    }
+}
+
+// 监控事件日志，每个事件一行JSON，写入 logging.log_dir 并独立轮转，供 monitor --events 查询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorJournalConfig {
+    pub enabled: bool,
+    pub file_name: String,
+    pub max_size_mb: u64,
+    pub max_files: usize,
+    pub compress_rotated: bool,
+}
+
+impl Default for MonitorJournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file_name: "monitor-events.log".to_string(),
+            max_size_mb: 50,
+            max_files: 10,
+            compress_rotated: true,
+        }
+    }
+}
+
+// 进入实时扫描和事件日志之前的事件过滤，避免构建目录或日志文件的大量写入淹没扫描队列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorFilterConfig {
+    // 忽略的扩展名 (不含点，不区分大小写)
+    pub exclude_extensions: Vec<String>,
+    // 忽略的路径通配符，匹配完整文件路径
+    pub exclude_patterns: Vec<String>,
+    // 按监控目录细化的规则，按最长路径前缀匹配
+    pub rules: Vec<WatchFilterRule>,
+    // 同一文件的相同事件在该时间内只写入一次事件日志 (毫秒)，0 表示不去重
+    pub dedup_window_ms: u64,
+    // 每个监控目录每秒最多放行的事件数，超出的事件被丢弃；0 表示不限制
+    pub max_events_per_sec: u64,
+    // 令牌桶容量，允许短时间内的突发事件
+    pub burst: u64,
+}
+
+impl Default for MonitorFilterConfig {
+    fn default() -> Self {
+        Self {
+            exclude_extensions: vec!["swp".to_string(), "swx".to_string(), "part".to_string()],
+            exclude_patterns: Vec::new(),
+            rules: Vec::new(),
+            dedup_window_ms: 1000,
+            max_events_per_sec: 200,
+            burst: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchFilterRule {
+    pub path: String,
+    // 只保留这些扩展名的文件，为空时不限制
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    // 只保留这些事件 (create, modify, delete, move, access)，为空时不限制
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl Default for OnAccessConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 500,
+            queue_size: 1024,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,6 +706,267 @@ pub struct ReportConfig {
     pub format: String,
     pub output_dir: PathBuf,
     pub include_details: bool,
+    #[serde(default)]
+    pub threat_store: ThreatStoreConfig,
+}
+
+// 扫描、API 扫描任务和实时监控发现的威胁都记录到该文件，供 /api/v1/threats 查询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatStoreConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+    // 超出后删除最早的记录
+    pub max_records: usize,
+}
+
+impl Default for ThreatStoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("/var/lib/virus-scanner/threats.jsonl"),
+            max_records: 10000,
+        }
+    }
+}
+
+// daemon 子命令在同一进程中运行文件监控 (monitor.enabled)、定时更新 (update.enabled) 和 API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    pub pid_file: PathBuf,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            pid_file: PathBuf::from("/run/virus-scanner/daemon.pid"),
+        }
+    }
+}
+
+// 定时扫描由 daemon 子命令按 cron 表达式运行，结果按 report.format 保存为报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledScanConfig {
+    pub name: String,
+    // 分 时 日 月 周 (本地时间)，如 "0 3 * * 0" 为每周日 03:00；也可以使用 @hourly、@daily、@weekly、@monthly
+    pub cron: String,
+    // quick、full、custom 或 persistence
+    #[serde(default = "default_scheduled_scan_type")]
+    pub scan_type: String,
+    // custom 扫描的路径
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default = "default_scheduled_scan_enabled")]
+    pub enabled: bool,
+}
+
+fn default_scheduled_scan_type() -> String {
+    "quick".to_string()
+}
+
+fn default_scheduled_scan_enabled() -> bool {
+    true
+}
+
+// 扫描、API 扫描任务和实时监控发现威胁时，以 JSON POST 到每个 webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    pub enabled: bool,
+    pub webhooks: Vec<WebhookConfig>,
+    // 连接失败、超时、429 和 5xx 时重试，每次重试的间隔加倍
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    pub timeout_secs: u64,
+    // 待发送的告警超过该数量时丢弃新告警
+    pub queue_size: usize,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            webhooks: Vec::new(),
+            max_retries: 3,
+            retry_backoff_ms: 1000,
+            timeout_secs: 10,
+            queue_size: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    // 用于日志，URL 中可能包含令牌，不写入日志
+    #[serde(default)]
+    pub name: String,
+    pub url: String,
+    // 附加的请求头，如 Authorization
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    // 低于该风险等级 (low, medium, high, critical) 的威胁不发送
+    #[serde(default = "default_alert_min_risk_level")]
+    pub min_risk_level: String,
+}
+
+fn default_alert_min_risk_level() -> String {
+    "low".to_string()
+}
+
+impl WebhookConfig {
+    pub fn min_risk(&self) -> Option<RiskLevel> {
+        match self.min_risk_level.to_lowercase().as_str() {
+            "low" => Some(RiskLevel::Low),
+            "medium" => Some(RiskLevel::Medium),
+            "high" => Some(RiskLevel::High),
+            "critical" => Some(RiskLevel::Critical),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MilterConfig {
+    pub enabled: bool,
+    pub listen: String,
+    pub on_infected: String,
+    pub reject_message: String,
+    pub add_header: bool,
+    pub max_message_size_mb: u64,
+}
+
+impl Default for MilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "inet:127.0.0.1:8891".to_string(),
+            on_infected: "reject".to_string(),
+            reject_message: "Message rejected: virus detected".to_string(),
+            add_header: true,
+            max_message_size_mb: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub listen: String,
+    // 静态 API 密钥，通过 X-API-Key 请求头使用，拥有 admin 权限，用于签发第一个令牌。为空时禁用
+    pub api_key: String,
+    // 命名的 API 密钥，只保存 SHA256 摘要，通过 X-API-Key 请求头使用
+    pub keys: Vec<ApiKeyConfig>,
+    // 通过 API 创建的密钥保存在该文件中，格式与 keys 相同
+    pub keys_file: Option<PathBuf>,
+    pub auth: ApiAuthConfig,
+    pub rate_limit: ApiRateLimitConfig,
+    pub health: HealthCheckConfig,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8080".to_string(),
+            api_key: String::new(),
+            keys: Vec::new(),
+            keys_file: Some(PathBuf::from("/var/lib/virus-scanner/api-keys.yaml")),
+            auth: ApiAuthConfig::default(),
+            rate_limit: ApiRateLimitConfig::default(),
+            health: HealthCheckConfig::default(),
+        }
+    }
+}
+
+// viewer 只能访问只读接口；operator 可以扫描、更新病毒库和管理隔离区；admin 拥有全部权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ApiRole {
+    #[default]
+    Viewer,
+    Operator,
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    // 审计日志中记录的名称，不能重复
+    pub name: String,
+    // 密钥的 SHA256 摘要 (十六进制)，可以用 `echo -n <密钥> | sha256sum` 计算
+    pub key_sha256: String,
+    #[serde(default)]
+    pub role: ApiRole,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiAuthConfig {
+    // JWT 签名密钥 (HMAC-SHA256)，文件不存在时自动生成
+    pub jwt_secret_file: PathBuf,
+    pub issuer: String,
+    // 签发令牌时未指定有效期使用的默认值
+    pub token_ttl_secs: u64,
+    pub max_token_ttl_secs: u64,
+}
+
+impl Default for ApiAuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret_file: PathBuf::from("/var/lib/virus-scanner/api-jwt.key"),
+            issuer: "virus-scanner".to_string(),
+            token_ttl_secs: 3600,
+            max_token_ttl_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+// 按客户端 IP 限制请求速率，/health 和 /metrics 不受限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiRateLimitConfig {
+    // 为 0 时不限制
+    pub requests_per_sec: u64,
+    pub burst: u64,
+    // 同一 IP 在 lockout_duration_secs 内认证失败达到该次数后锁定，锁定期间的请求返回 423
+    pub lockout_threshold: usize,
+    pub lockout_duration_secs: u64,
+}
+
+impl Default for ApiRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 20,
+            burst: 60,
+            lockout_threshold: 5,
+            lockout_duration_secs: 900,
+        }
+    }
+}
+
+// /health 的检查项阈值，任一检查失败时返回 503
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    // 本地病毒库的构建时间超过该时长视为过期，为 0 时不检查
+    pub max_database_age_hours: u64,
+    // 隔离区、报告和病毒库所在文件系统的最小可用空间，低于两倍时返回警告
+    pub min_free_space_mb: u64,
+    // 监控线程超过该时长没有心跳视为停止，需要大于轮询监控的间隔
+    pub monitor_stall_secs: u64,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            max_database_age_hours: 168,
+            min_free_space_mb: 512,
+            monitor_stall_secs: 30,
+        }
+    }
 }
 
 impl Default for ScannerConfig {
@@ -107,6 +978,7 @@ impl Default for ScannerConfig {
                     "/usr/bin".to_string(),
                     "/etc".to_string(),
                 ],
+                priority_paths: default_priority_paths(),
                 exclude_paths: vec![
                     "/proc".to_string(),
                     "/sys".to_string(),
@@ -122,24 +994,52 @@ impl Default for ScannerConfig {
                     "pid".to_string(),
                 ],
                 max_file_size: 50 * 1024 * 1024,
+                use_xattr_markers: false,
+                xattr_marker_key_file: default_xattr_marker_key_file(),
+                archive: ArchiveConfig::default(),
+                heuristics: HeuristicsConfig::default(),
+                pdf: PdfConfig::default(),
+                mail: MailConfig::default(),
+                skip_benign_types: default_skip_benign_types(),
+                rootkit_check: default_rootkit_check(),
+                honor_scanignore: default_honor_scanignore(),
+                checkpoint: CheckpointConfig::default(),
+                verdict_cache: VerdictCacheConfig::default(),
+                url: UrlScanConfig::default(),
+                max_duration_secs: 0,
+                progress_interval_ms: default_progress_interval_ms(),
+                action: DetectionAction::Report,
             },
             performance: PerformanceConfig {
                 thread_pool_size: 1,
+                walk_threads: 0,
                 cpu_usage_limit: 50.0,
                 memory_limit_mb: 64,
                 scan_buffer_size: 4096,
+                regex_time_budget_ms: default_regex_time_budget_ms(),
+                use_mmap: false,
+                max_read_mb_per_s: 0,
+                idle_io_priority: false,
             },
             security: SecurityConfig {
                 run_as_user: None,
                 database_encryption: false,
                 audit_log_enabled: false,
                 quarantine_dir: PathBuf::from("/var/lib/virus-scanner/quarantine"),
+                quarantine_key_file: None,
+                auto_quarantine: AutoQuarantineConfig::default(),
             },
             logging: LoggingConfig {
                 level: "WARN".to_string(),
                 log_dir: PathBuf::from("/var/log/virus-scanner"),
                 max_size_mb: 10,
                 max_files: 3,
+                rotate_daily: false,
+                compress_rotated: false,
+                format: default_log_format(),
+                journald: false,
+                module_levels: HashMap::new(),
+                detection_log: DetectionLogConfig::default(),
                 remote_logging: None,
             },
             update: UpdateConfig {
@@ -152,9 +1052,13 @@ impl Default for ScannerConfig {
                     check_interval_hours: 24,
                 },
                 mirror_url: "https://database.clamav.net".to_string(),
-                verify_signatures: false,
+                verify_signatures: true,
                 database_path: PathBuf::from("/var/lib/virus-scanner/database"),
                 backup_path: PathBuf::from("/var/lib/virus-scanner/backup"),
+                misp: MispConfig::default(),
+                cdiff: CdiffConfig::default(),
+                backup_retention: BackupRetentionConfig::default(),
+                validate_after_update: true,
             },
             monitor: MonitorConfig {
                 enabled: false,
@@ -166,17 +1070,51 @@ impl Default for ScannerConfig {
                     on_delete: "log".to_string(),
                     auto_quarantine: false,
                 },
+                poll_interval_ms: default_poll_interval_ms(),
+                on_access: OnAccessConfig::default(),
+                access_control: AccessControlConfig::default(),
+                daemon: MonitorDaemonConfig::default(),
+                journal: MonitorJournalConfig::default(),
+                filter: MonitorFilterConfig::default(),
             },
             report: ReportConfig {
                 enabled: true,
                 format: "text".to_string(),
                 output_dir: PathBuf::from("/var/lib/virus-scanner/reports"),
                 include_details: false,
+                threat_store: ThreatStoreConfig::default(),
             },
+            milter: MilterConfig::default(),
+            allowlist: AllowlistConfig::default(),
+            api: ApiConfig::default(),
+            alerts: AlertConfig::default(),
+            daemon: DaemonConfig::default(),
+            scheduled_scans: Vec::new(),
+            language: None,
         }
     }
 }
 
+// 环境变量覆盖配置文件中的取值，名称为前缀加上以 __ 分隔的配置项，
+// 如 VIRUS_SCANNER__PERFORMANCE__THREAD_POOL_SIZE=8 对应 performance.thread_pool_size
+pub const ENV_PREFIX: &str = "VIRUS_SCANNER__";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvOverride {
+    pub variable: String,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigIssue {
+    // 点分隔的配置项，如 update.schedule.time；无法定位时为空
+    pub key: String,
+    // 配置文件中的行号 (从 1 开始)
+    pub line: Option<usize>,
+    pub message: String,
+}
+
 impl ScannerConfig {
     pub fn load(path: &PathBuf) -> Result<Self, anyhow::Error> {
         if path.exists() {
@@ -198,6 +1136,148 @@ impl ScannerConfig {
         Ok(())
     }
 
+    // 检查反序列化无法发现的取值错误，返回第一个错误
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        match self.check().into_iter().next() {
+            Some(issue) => Err(anyhow::anyhow!(issue.message)),
+            None => Ok(()),
+        }
+    }
+
+    // 全部取值错误，key 为出错的配置项，供 config validate 定位到配置文件中的行
+    pub fn check(&self) -> Vec<ConfigIssue> {
+        const LOG_LEVELS: [&str; 6] = ["trace", "debug", "info", "warn", "error", "off"];
+        const REPORT_FORMATS: [&str; 4] = ["json", "yaml", "html", "text"];
+
+        let mut issues = Vec::new();
+        let mut issue = |key: String, message: String| issues.push(ConfigIssue { key, line: None, message });
+
+        if !LOG_LEVELS.contains(&self.logging.level.to_lowercase().as_str()) {
+            issue("logging.level".into(), format!("无效的日志级别: {}", self.logging.level));
+        }
+        if !["text", "json"].contains(&self.logging.format.as_str()) {
+            issue("logging.format".into(), format!("无效的日志格式: {} (可选 text, json)", self.logging.format));
+        }
+        if !REPORT_FORMATS.contains(&self.report.format.as_str()) {
+            issue("report.format".into(), format!("无效的报告格式: {} (可选 json, yaml, html, text)", self.report.format));
+        }
+        if self.performance.thread_pool_size == 0 {
+            issue("performance.thread_pool_size".into(), "thread_pool_size 必须大于 0".into());
+        }
+        if self.performance.scan_buffer_size == 0 {
+            issue("performance.scan_buffer_size".into(), "scan_buffer_size 必须大于 0".into());
+        }
+        if !(self.performance.cpu_usage_limit > 0.0 && self.performance.cpu_usage_limit <= 100.0) {
+            issue("performance.cpu_usage_limit".into(), "cpu_usage_limit 必须在 0 到 100 之间".into());
+        }
+        if self.scan_modes.max_file_size == 0 {
+            issue("scan_modes.max_file_size".into(), "max_file_size 必须大于 0".into());
+        }
+        let time: Vec<&str> = self.update.schedule.time.split(':').collect();
+        let valid_time = time.len() == 2
+            && time[0].parse::<u32>().map_or(false, |hour| hour < 24)
+            && time[1].parse::<u32>().map_or(false, |minute| minute < 60);
+        if !valid_time {
+            issue("update.schedule.time".into(), format!("无效的更新时间: {} (格式为 HH:MM)", self.update.schedule.time));
+        }
+        if self.update.schedule.day_of_week.map_or(false, |day| day > 6) {
+            issue("update.schedule.day_of_week".into(), "day_of_week 必须在 0 到 6 之间".into());
+        }
+        if !self.update.mirror_url.starts_with("http://") && !self.update.mirror_url.starts_with("https://") {
+            issue("update.mirror_url".into(), format!("无效的镜像地址: {}", self.update.mirror_url));
+        }
+        if self.api.listen.parse::<std::net::SocketAddr>().is_err() {
+            issue("api.listen".into(), format!("无效的 API 监听地址: {}", self.api.listen));
+        }
+        if let Err(e) = crate::scanner::Allowlist::from_config(&self.allowlist) {
+            issue("allowlist".into(), format!("{:#}", e));
+        }
+        let mut key_names = std::collections::HashSet::new();
+        for (index, key) in self.api.keys.iter().enumerate() {
+            if key.name.is_empty() || !key_names.insert(key.name.as_str()) {
+                issue(format!("api.keys.{}.name", index), format!("API 密钥名称为空或重复: {:?}", key.name));
+            }
+            if key.key_sha256.len() != 64 || !key.key_sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                issue(format!("api.keys.{}.key_sha256", index), format!("API 密钥 {} 的 key_sha256 不是有效的 SHA256 摘要", key.name));
+            }
+        }
+        let mut schedule_names = std::collections::HashSet::new();
+        for (index, schedule) in self.scheduled_scans.iter().enumerate() {
+            if schedule.name.is_empty() || !schedule_names.insert(schedule.name.as_str()) {
+                issue(format!("scheduled_scans.{}.name", index), format!("定时扫描名称为空或重复: {:?}", schedule.name));
+            }
+            if let Err(e) = schedule.cron.parse::<crate::core::schedule::CronSchedule>() {
+                issue(format!("scheduled_scans.{}.cron", index), format!("定时扫描 {} 的 cron 表达式无效: {:#}", schedule.name, e));
+            }
+            match schedule.scan_type.parse::<crate::scanner::ScanMode>() {
+                Ok(crate::scanner::ScanMode::Custom) if schedule.paths.is_empty() => {
+                    issue(format!("scheduled_scans.{}.paths", index), format!("定时扫描 {} 为 custom 扫描，需要指定 paths", schedule.name));
+                }
+                Ok(_) => {}
+                Err(e) => issue(format!("scheduled_scans.{}.scan_type", index), format!("定时扫描 {}: {}", schedule.name, e)),
+            }
+        }
+        for (index, webhook) in self.alerts.webhooks.iter().enumerate() {
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                issue(format!("alerts.webhooks.{}.url", index), format!("webhook {} 的地址无效，必须以 http:// 或 https:// 开头", index + 1));
+            }
+            if webhook.min_risk().is_none() {
+                issue(format!("alerts.webhooks.{}.min_risk_level", index), format!("webhook {} 的风险等级无效: {}", index + 1, webhook.min_risk_level));
+            }
+        }
+        issues
+    }
+
+    // 按点分隔的配置项名称修改一项配置，如 performance.thread_pool_size、alerts.webhooks.0.url。
+    // value 按 YAML 解析 (可以写 true、8、[a, b])，与配置项类型不符时按字符串处理
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<(), anyhow::Error> {
+        let current = serde_json::to_value(&*self)?;
+        let mut candidates = Vec::new();
+        if let Ok(parsed) = serde_yaml::from_str::<serde_json::Value>(value) {
+            if !parsed.is_string() {
+                candidates.push(parsed);
+            }
+        }
+        candidates.push(serde_json::Value::String(value.to_string()));
+
+        let mut error = None;
+        for candidate in candidates {
+            let mut updated = current.clone();
+            *value_at(&mut updated, key)? = candidate;
+            match serde_json::from_value::<ScannerConfig>(updated) {
+                Ok(config) => {
+                    *self = config;
+                    return Ok(());
+                }
+                // 保留按 YAML 解析时的错误，更能说明问题
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        Err(anyhow::anyhow!("配置项 {} 的取值无效: {}", key, error.map(|e| e.to_string()).unwrap_or_default()))
+    }
+
+    // 使用 VIRUS_SCANNER__ 开头的环境变量覆盖配置，返回生效的覆盖项
+    pub fn apply_env_overrides(&mut self) -> Result<Vec<EnvOverride>, anyhow::Error> {
+        self.apply_overrides(std::env::vars())
+    }
+
+    pub fn apply_overrides(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Vec<EnvOverride>, anyhow::Error> {
+        let mut overrides: Vec<EnvOverride> = vars
+            .into_iter()
+            .filter_map(|(variable, value)| {
+                let key = variable.strip_prefix(ENV_PREFIX)?.split("__").collect::<Vec<_>>().join(".").to_lowercase();
+                Some(EnvOverride { variable, key, value })
+            })
+            .collect();
+        overrides.sort_by(|a, b| a.variable.cmp(&b.variable));
+        for item in &overrides {
+            self.set_value(&item.key, &item.value).with_context(|| format!("环境变量 {} 无效", item.variable))?;
+        }
+        Ok(overrides)
+    }
+
     pub fn create_default_config_file() -> Result<PathBuf, anyhow::Error> {
         let config_path = dirs::config_dir()
             .unwrap_or(PathBuf::from("/etc"))
@@ -210,3 +1290,20 @@ impl ScannerConfig {
         Ok(config_file)
     }
 }
+
+// 配置项必须已经存在，serde 会忽略未知字段，写入不存在的配置项不会生效
+fn value_at<'a>(value: &'a mut serde_json::Value, key: &str) -> Result<&'a mut serde_json::Value, anyhow::Error> {
+    let mut current = value;
+    for segment in key.split('.') {
+        current = match current {
+            serde_json::Value::Object(fields) => fields.get_mut(segment),
+            serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow::anyhow!("未知的配置项: {}", key))?;
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests;
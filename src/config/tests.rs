@@ -0,0 +1,75 @@
+use crate::config::check::{check_source, locate_key};
+use crate::config::ScannerConfig;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_set_value_and_env_overrides() {
+        let mut config = ScannerConfig::default();
+        config.set_value("performance.thread_pool_size", "3").unwrap();
+        config.set_value("update.schedule.time", "04:30").unwrap();
+        config.set_value("scan_modes.exclude_extensions", "[log, tmp]").unwrap();
+        // 字符串配置项写成数字时按字符串处理
+        config.set_value("api.api_key", "12345").unwrap();
+        assert_eq!(config.performance.thread_pool_size, 3);
+        assert_eq!(config.update.schedule.time, "04:30");
+        assert_eq!(config.scan_modes.exclude_extensions, vec!["log", "tmp"]);
+        assert_eq!(config.api.api_key, "12345");
+
+        assert!(config.set_value("performance.thread_pool_sise", "3").is_err());
+        assert!(config.set_value("performance.thread_pool_size", "many").is_err());
+        assert_eq!(config.performance.thread_pool_size, 3);
+
+        let overrides = config
+            .apply_overrides(vec![
+                ("VIRUS_SCANNER__LOGGING__LEVEL".to_string(), "debug".to_string()),
+                ("VIRUS_SCANNER__SCAN_MODES__MAX_FILE_SIZE".to_string(), "1024".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].key, "logging.level");
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.scan_modes.max_file_size, 1024);
+
+        let error = config
+            .apply_overrides(vec![("VIRUS_SCANNER__LOGGING__NOPE".to_string(), "1".to_string())])
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("VIRUS_SCANNER__LOGGING__NOPE"));
+    }
+
+    #[test]
+    fn test_config_check_reports_lines() {
+        let mut config = ScannerConfig::default();
+        config.alerts.webhooks.push(serde_yaml::from_str("url: https://hooks.example.com/a").unwrap());
+        config.alerts.webhooks.push(serde_yaml::from_str("url: https://hooks.example.com/b").unwrap());
+        let source = serde_yaml::to_string(&config).unwrap();
+        assert!(check_source(&source).is_valid());
+
+        let line_of = |needle: &str| source.lines().position(|line| line.contains(needle)).unwrap() + 1;
+        assert_eq!(locate_key(&source, "performance.thread_pool_size"), Some(line_of("thread_pool_size:")));
+        assert_eq!(locate_key(&source, "alerts.webhooks.1.url"), Some(line_of("hooks.example.com/b")));
+        assert_eq!(locate_key(&source, "alerts.webhooks.2.url"), None);
+
+        let broken = source
+            .replace("time: 03:00", "time: '25:00'")
+            .replace("hooks.example.com/b", "hooks.example.com/b\n    timeuot_secs: 5")
+            .replace("https://hooks.example.com/a", "ftp://hooks.example.com/a");
+        let check = check_source(&broken);
+        let line_of = |needle: &str| broken.lines().position(|line| line.contains(needle)).unwrap() + 1;
+        assert_eq!(check.errors.len(), 2);
+        assert_eq!(check.errors[0].key, "update.schedule.time");
+        assert_eq!(check.errors[0].line, Some(line_of("25:00")));
+        assert_eq!(check.errors[1].key, "alerts.webhooks.0.url");
+        assert_eq!(check.errors[1].line, Some(line_of("ftp://")));
+        assert_eq!(check.warnings.len(), 1);
+        assert_eq!(check.warnings[0].key, "alerts.webhooks.1.timeuot_secs");
+        assert_eq!(check.warnings[0].line, Some(line_of("timeuot_secs")));
+
+        let check = check_source(&source.replace("thread_pool_size: ", "thread_pool_size: lots # "));
+        assert!(!check.is_valid());
+        assert_eq!(check.errors[0].line, Some(line_of("thread_pool_size:")));
+    }
+}
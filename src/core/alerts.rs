@@ -0,0 +1,162 @@
+use crate::config::{AlertConfig, WebhookConfig};
+use crate::report::StoredThreat;
+use crate::scanner::RiskLevel;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// 重试间隔的上限，也是 Retry-After 的上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// 退出前等待队列中的告警发送完毕的最长时间
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreatAlert {
+    // 固定为 threat_detected
+    pub event: String,
+    // 一行摘要，Slack、Teams 的 incoming webhook 直接显示该字段
+    pub text: String,
+    pub hostname: String,
+    pub threat: StoredThreat,
+}
+
+impl ThreatAlert {
+    pub fn new(threat: StoredThreat) -> Self {
+        let hostname = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+        let text = format!(
+            "[{}] 发现威胁 {} ({}, 风险等级 {}): {}",
+            hostname,
+            threat.detection_name,
+            threat.threat_type,
+            threat.risk_level,
+            threat.file_path.display()
+        );
+        Self {
+            event: "threat_detected".to_string(),
+            text,
+            hostname,
+            threat,
+        }
+    }
+}
+
+struct WebhookTarget {
+    label: String,
+    min_risk: RiskLevel,
+    sender: mpsc::Sender<Arc<ThreatAlert>>,
+}
+
+// 每个 webhook 有各自的队列和发送任务，某个 webhook 不可用时不影响其他 webhook
+pub struct AlertDispatcher {
+    targets: Mutex<Vec<WebhookTarget>>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl AlertDispatcher {
+    // 未启用或没有配置 webhook 时返回 None。需要在 tokio 运行时中调用
+    pub fn start(config: &AlertConfig) -> Result<Option<Arc<Self>>, anyhow::Error> {
+        if !config.enabled || config.webhooks.is_empty() {
+            return Ok(None);
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .build()
+            .context("无法创建 HTTP 客户端")?;
+        let backoff = Duration::from_millis(config.retry_backoff_ms);
+
+        let mut targets = Vec::new();
+        let mut workers = Vec::new();
+        for (index, webhook) in config.webhooks.iter().enumerate() {
+            let label = if webhook.name.is_empty() { format!("webhook-{}", index + 1) } else { webhook.name.clone() };
+            let min_risk = webhook
+                .min_risk()
+                .ok_or_else(|| anyhow::anyhow!("webhook {} 的风险等级无效: {}", label, webhook.min_risk_level))?;
+            let (sender, mut receiver) = mpsc::channel::<Arc<ThreatAlert>>(config.queue_size.max(1));
+            let (client, webhook, worker_label, max_retries) = (client.clone(), webhook.clone(), label.clone(), config.max_retries);
+            workers.push(tokio::spawn(async move {
+                while let Some(alert) = receiver.recv().await {
+                    if let Err(e) = send_with_retry(&client, &webhook, &alert, max_retries, backoff).await {
+                        log::error!("告警发送到 {} 失败，已放弃: {:#}", worker_label, e);
+                    }
+                }
+            }));
+            targets.push(WebhookTarget { label, min_risk, sender });
+        }
+        log::info!("威胁告警已启用，webhook 数量: {}", targets.len());
+
+        Ok(Some(Arc::new(Self {
+            targets: Mutex::new(targets),
+            workers: Mutex::new(workers),
+        })))
+    }
+
+    // 不等待发送完成，可以在任意线程中调用。队列已满时丢弃告警
+    pub fn notify(&self, threats: &[StoredThreat]) {
+        let targets = self.targets.lock().unwrap();
+        for threat in threats {
+            let level = RiskLevel::from(threat.risk_level.as_str());
+            let alert = Arc::new(ThreatAlert::new(threat.clone()));
+            for target in targets.iter().filter(|target| level >= target.min_risk) {
+                if let Err(mpsc::error::TrySendError::Full(_)) = target.sender.try_send(Arc::clone(&alert)) {
+                    log::warn!("{} 的告警队列已满，丢弃威胁 {} 的告警", target.label, threat.id);
+                }
+            }
+        }
+    }
+
+    // 停止接受新告警，等待队列中的告警发送完毕 (包括重试)，超时后放弃剩余告警
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.targets.lock().unwrap().clear();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        let deadline = tokio::time::Instant::now() + timeout;
+        for mut worker in workers {
+            if tokio::time::timeout_at(deadline, &mut worker).await.is_err() {
+                log::warn!("等待告警发送超时，剩余告警已丢弃");
+                worker.abort();
+            }
+        }
+    }
+}
+
+// 连接失败、超时、429 和 5xx 时重试，其他 4xx 视为配置错误，不再重试
+async fn send_with_retry(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    alert: &ThreatAlert,
+    max_retries: u32,
+    backoff: Duration,
+) -> Result<(), anyhow::Error> {
+    let mut delay = backoff;
+    let mut attempt = 0;
+    loop {
+        let mut request = client.post(&webhook.url).json(alert);
+        for (name, value) in &webhook.headers {
+            request = request.header(name, value);
+        }
+        let (error, retry_after) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS || response.status().is_server_error() => {
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                (anyhow::anyhow!("服务器返回 {}", response.status()), retry_after)
+            }
+            Ok(response) => return Err(anyhow::anyhow!("服务器返回 {}", response.status())),
+            Err(e) => (anyhow::Error::new(e.without_url()).context("请求失败"), None),
+        };
+        if attempt >= max_retries {
+            return Err(error.context(format!("已重试 {} 次", max_retries)));
+        }
+        attempt += 1;
+        let wait = retry_after.map_or(delay, |retry_after| retry_after.max(delay)).min(MAX_BACKOFF);
+        log::debug!("告警发送失败，{:?} 后第 {} 次重试: {:#}", wait, attempt, error);
+        tokio::time::sleep(wait).await;
+        delay = (delay * 2).min(MAX_BACKOFF);
+    }
+}
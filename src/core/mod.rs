@@ -1,37 +1,117 @@
+pub mod alerts;
+pub mod schedule;
+pub mod security;
+
+use crate::api::auth::TokenAuthority;
+use crate::api::events::{EventHub, LiveEvent};
+use crate::api::health::HealthChecker;
+use crate::api::jobs::ScanJobManager;
+use crate::api::settings::{ConfigManager, ConfigUpdate};
+use crate::api::updates::UpdateJobManager;
 use crate::api::ApiServer;
+use crate::core::alerts::AlertDispatcher;
+use crate::core::schedule::ScanScheduler;
+use crate::core::security::{QuarantineManager, SecurityManager};
 use crate::config::ScannerConfig;
-use crate::monitor::FileMonitor;
-use crate::report::ReportGenerator;
-use crate::scanner::{ScannerEngine, ScanOptions, ScanMode, SignatureDatabase};
-use crate::update::{DatabaseUpdater, UpdateScheduler};
+use crate::monitor::{on_access_scan_options, FileMonitor, OnAccessScanner};
+use crate::report::{DetectionLogger, ReportGenerator, StoredThreat, ThreatReport, ThreatStore};
+use crate::scanner::{Allowlist, ScanControl, ScannerEngine, ScanOptions, ScanMode, SignatureDatabase, VerdictCache};
+use crate::utils::logging::AuditLogger;
+use crate::utils::service;
+use crate::update::{spawn_signature_reloader, DatabaseUpdater, MispScheduler, UpdateSchedule, UpdateScheduler};
 use anyhow::{Context, Result};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::signal;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
+
+// 通过 API 提交的扫描任务同时运行的数量，其余任务排队
+const MAX_CONCURRENT_API_SCANS: usize = 2;
 
 pub struct VirusScanner {
     config: Arc<RwLock<ScannerConfig>>,
     signature_db: Arc<SignatureDatabase>,
     scanner_engine: Option<ScannerEngine>,
     monitor: Option<FileMonitor>,
+    on_access_task: Option<tokio::task::JoinHandle<()>>,
+    #[cfg(target_os = "linux")]
+    access_guard: Option<crate::monitor::AccessGuard>,
     updater: Option<Arc<DatabaseUpdater>>,
+    misp_scheduler: Option<MispScheduler>,
+    update_scheduler: Option<UpdateScheduler>,
+    scan_scheduler: Option<(Arc<ScanScheduler>, tokio::task::JoinHandle<()>)>,
     api_server: Option<ApiServer>,
+    scan_control: ScanControl,
+    quarantine: Arc<QuarantineManager>,
+    allowlist: Arc<Allowlist>,
+    verdict_cache: Option<Arc<VerdictCache>>,
+    database_path: PathBuf,
+    event_hub: Arc<EventHub>,
+    threat_store: Option<Arc<ThreatStore>>,
+    // 所有扫描入口发现的威胁都写入检测日志
+    detection_logger: Option<Arc<DetectionLogger>>,
+    // initialize 中启动，未启用告警时为 None
+    alerts: Option<Arc<AlertDispatcher>>,
+    // 设置后通过 API 修改的配置写回该文件
+    config_path: Option<PathBuf>,
+    // 与 API 共用，SIGHUP 重新加载配置时使用
+    config_manager: Option<Arc<ConfigManager>>,
 }
 
 impl VirusScanner {
     pub fn new(config: ScannerConfig) -> Self {
-        let config = Arc::new(RwLock::new(config));
         let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.set_regex_time_budget(Duration::from_millis(config.performance.regex_time_budget_ms));
+        signature_db.set_scan_buffer_size(config.performance.scan_buffer_size);
+        signature_db.set_use_mmap(config.performance.use_mmap);
+        let quarantine = QuarantineManager::from_config(&config.security).unwrap_or_else(|e| {
+            log::error!("隔离区加密密钥不可用，隔离文件将不加密: {}", e);
+            QuarantineManager::new(config.security.quarantine_dir.clone(), None)
+        });
+        let quarantine = Arc::new(quarantine);
+        let allowlist = Allowlist::from_config(&config.allowlist).unwrap_or_else(|e| {
+            log::error!("白名单配置无效，已忽略: {}", e);
+            Allowlist::new()
+        });
+        let verdict_cache = VerdictCache::from_config(&config.scan_modes.verdict_cache)
+            .unwrap_or_else(|e| {
+                log::error!("扫描结论缓存不可用，已禁用: {}", e);
+                None
+            })
+            .map(Arc::new);
+        let threat_store = config
+            .report
+            .threat_store
+            .enabled
+            .then(|| Arc::new(ThreatStore::from_config(&config.report.threat_store)));
+        let detection_logger = DetectionLogger::open_or_warn(&config.logging).map(Arc::new);
+        let config = Arc::new(RwLock::new(config));
 
         Self {
             config,
             signature_db,
             scanner_engine: None,
             monitor: None,
+            on_access_task: None,
+            #[cfg(target_os = "linux")]
+            access_guard: None,
             updater: None,
+            misp_scheduler: None,
+            update_scheduler: None,
+            scan_scheduler: None,
             api_server: None,
+            scan_control: ScanControl::new(),
+            quarantine,
+            allowlist: Arc::new(allowlist),
+            verdict_cache,
+            database_path: PathBuf::new(),
+            event_hub: Arc::new(EventHub::new()),
+            threat_store,
+            detection_logger,
+            alerts: None,
+            config_path: None,
+            config_manager: None,
         }
     }
 
@@ -44,8 +124,8 @@ impl VirusScanner {
         std::fs::create_dir_all(&config.logging.log_dir)?;
         std::fs::create_dir_all(&config.report.output_dir)?;
 
-        let database_path = PathBuf::from("/var/lib/virus-scanner/database");
-        let backup_path = PathBuf::from("/var/lib/virus-scanner/backups");
+        let database_path = config.update.database_path.clone();
+        let backup_path = config.update.backup_path.clone();
 
         std::fs::create_dir_all(&database_path)?;
         std::fs::create_dir_all(&backup_path)?;
@@ -53,6 +133,7 @@ impl VirusScanner {
         if let Err(e) = self.signature_db.load_from_directory(&database_path).await {
             log::warn!("无法加载本地病毒库: {}，将使用空数据库", e);
         }
+        self.signature_db.load_builtin_signatures().await?;
 
         log::info!(
             "病毒库已加载，签名数量: {}",
@@ -61,12 +142,34 @@ impl VirusScanner {
 
         drop(config);
 
-        let updater = Arc::new(DatabaseUpdater::new(
+        let mut updater = DatabaseUpdater::new(
             self.config.read().await.update.mirror_url.clone(),
-            database_path,
+            database_path.clone(),
             backup_path,
-        ));
-        self.updater = Some(updater);
+        );
+        let (event_tx, event_rx) = mpsc::channel(16);
+        updater.set_event_tx(event_tx);
+        updater.set_cdiff_config(self.config.read().await.update.cdiff.clone());
+        updater.set_verify_signatures(self.config.read().await.update.verify_signatures);
+        updater.set_backup_retention(self.config.read().await.update.backup_retention.clone());
+        updater.set_validate_after_update(self.config.read().await.update.validate_after_update);
+        let event_rx = self.event_hub.tee_updates(event_rx);
+        spawn_signature_reloader(event_rx, Arc::clone(&self.signature_db), database_path.clone());
+        self.updater = Some(Arc::new(updater));
+        self.database_path = database_path;
+
+        let alert_config = self.config.read().await.alerts.clone();
+        self.alerts = AlertDispatcher::start(&alert_config).unwrap_or_else(|e| {
+            log::error!("威胁告警不可用: {:#}", e);
+            None
+        });
+
+        let misp_config = self.config.read().await.update.misp.clone();
+        if misp_config.enabled {
+            let scheduler = MispScheduler::new(misp_config, Arc::clone(&self.signature_db));
+            scheduler.start();
+            self.misp_scheduler = Some(scheduler);
+        }
 
         Ok(())
     }
@@ -85,20 +188,34 @@ impl VirusScanner {
             exclude_extensions: config.scan_modes.exclude_extensions.clone(),
             max_file_size: config.scan_modes.max_file_size,
             thread_count: config.performance.thread_pool_size,
+            walk_threads: config.performance.walk_threads,
             quick_scan_paths: config.scan_modes.quick_scan_paths.iter()
                 .map(|p| PathBuf::from(p))
                 .collect(),
+            priority_paths: config.scan_modes.priority_paths.iter()
+                .map(|p| PathBuf::from(p))
+                .collect(),
+            use_xattr_markers: config.scan_modes.use_xattr_markers,
+            xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: config.performance.max_read_mb_per_s,
+            idle_io_priority: config.performance.idle_io_priority,
+            max_duration: (config.scan_modes.max_duration_secs > 0)
+                .then(|| Duration::from_secs(config.scan_modes.max_duration_secs)),
+            action: config.scan_modes.action,
+            auto_quarantine_min_risk: config.security.auto_quarantine.min_risk(),
+            dry_run: false,
         };
 
         drop(config);
 
-        self.scanner_engine = Some(ScannerEngine::new(Arc::clone(&self.signature_db), scan_options));
-
-        if let Some(engine) = &self.scanner_engine {
-            engine.start_scan().await
-        } else {
-            Err(anyhow::anyhow!("扫描引擎未初始化"))
-        }
+        self.run_engine(scan_options).await
     }
 
     pub async fn run_full_scan(&mut self) -> Result<Vec<crate::scanner::ScanResult>, anyhow::Error> {
@@ -113,18 +230,32 @@ impl VirusScanner {
             exclude_extensions: config.scan_modes.exclude_extensions.clone(),
             max_file_size: config.scan_modes.max_file_size,
             thread_count: config.performance.thread_pool_size,
+            walk_threads: config.performance.walk_threads,
             quick_scan_paths: vec![],
+            priority_paths: config.scan_modes.priority_paths.iter()
+                .map(|p| PathBuf::from(p))
+                .collect(),
+            use_xattr_markers: config.scan_modes.use_xattr_markers,
+            xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: config.performance.max_read_mb_per_s,
+            idle_io_priority: config.performance.idle_io_priority,
+            max_duration: (config.scan_modes.max_duration_secs > 0)
+                .then(|| Duration::from_secs(config.scan_modes.max_duration_secs)),
+            action: config.scan_modes.action,
+            auto_quarantine_min_risk: config.security.auto_quarantine.min_risk(),
+            dry_run: false,
         };
 
         drop(config);
 
-        self.scanner_engine = Some(ScannerEngine::new(Arc::clone(&self.signature_db), scan_options));
-
-        if let Some(engine) = &self.scanner_engine {
-            engine.start_scan().await
-        } else {
-            Err(anyhow::anyhow!("扫描引擎未初始化"))
-        }
+        self.run_engine(scan_options).await
     }
 
     pub async fn run_custom_scan(
@@ -142,18 +273,32 @@ impl VirusScanner {
             exclude_extensions: config.scan_modes.exclude_extensions.clone(),
             max_file_size: config.scan_modes.max_file_size,
             thread_count: config.performance.thread_pool_size,
+            walk_threads: config.performance.walk_threads,
             quick_scan_paths: vec![],
+            priority_paths: config.scan_modes.priority_paths.iter()
+                .map(|p| PathBuf::from(p))
+                .collect(),
+            use_xattr_markers: config.scan_modes.use_xattr_markers,
+            xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: config.performance.max_read_mb_per_s,
+            idle_io_priority: config.performance.idle_io_priority,
+            max_duration: (config.scan_modes.max_duration_secs > 0)
+                .then(|| Duration::from_secs(config.scan_modes.max_duration_secs)),
+            action: config.scan_modes.action,
+            auto_quarantine_min_risk: config.security.auto_quarantine.min_risk(),
+            dry_run: false,
         };
 
         drop(config);
 
-        self.scanner_engine = Some(ScannerEngine::new(Arc::clone(&self.signature_db), scan_options));
-
-        if let Some(engine) = &self.scanner_engine {
-            engine.start_scan().await
-        } else {
-            Err(anyhow::anyhow!("扫描引擎未初始化"))
-        }
+        self.run_engine(scan_options).await
     }
 
     pub async fn update_database(&self, force: bool) -> Result<(), anyhow::Error> {
@@ -170,39 +315,419 @@ impl VirusScanner {
         Ok(())
     }
 
-    pub fn start_file_monitor(&mut self) -> Result<(), anyhow::Error> {
+    // 不重启进程切换到磁盘上的新病毒库，返回特征码数量
+    pub async fn reload_signatures(&self) -> Result<usize, anyhow::Error> {
+        self.signature_db.reload_from_directory(&self.database_path).await
+    }
+
+    pub async fn start_file_monitor(&mut self) -> Result<(), anyhow::Error> {
+        let config = self.config.read().await;
+        let mut on_access = OnAccessScanner::new(
+            Arc::clone(&self.signature_db),
+            on_access_scan_options(&config),
+            config.monitor.actions.clone(),
+            &config.monitor.on_access,
+        );
+        on_access.set_allowlist(Arc::clone(&self.allowlist));
+        on_access.set_quarantine_manager(Arc::clone(&self.quarantine));
+        on_access.set_audit_logger(Arc::new(AuditLogger::new(
+            config.logging.log_dir.clone(),
+            config.security.audit_log_enabled,
+        )));
+        let (store, alerts, hub) = (self.threat_store.clone(), self.alerts.clone(), Arc::clone(&self.event_hub));
+        let detection_logger = self.detection_logger.clone();
+        on_access.set_result_callback(Arc::new(move |result| {
+            let threat = ThreatReport::from_result(String::new(), result, true);
+            if let Some(ref detection_logger) = detection_logger {
+                detection_logger.log_threats("monitor", std::slice::from_ref(&threat));
+            }
+            let stored = match store {
+                Some(ref store) => store.record_or_warn("monitor", "monitor", &[threat]),
+                None => vec![StoredThreat::new("monitor", "monitor", &threat)],
+            };
+            if let Some(ref alerts) = alerts {
+                alerts.notify(&stored);
+            }
+            for threat in stored {
+                hub.publish(LiveEvent::Threat(threat));
+            }
+        }));
+        let on_access = Arc::new(on_access);
+
         let mut monitor = FileMonitor::new();
-        monitor.add_default_watches()?;
+        monitor.add_default_watches(&config.monitor)?;
+        #[cfg(target_os = "linux")]
+        {
+            self.access_guard = crate::monitor::start_access_guard(Arc::clone(&self.signature_db), &config)?;
+        }
+        drop(config);
+        self.on_access_task = Some(on_access.start()?);
+        let event_hub = Arc::clone(&self.event_hub);
+        monitor.set_event_callback(Arc::new(move |event| {
+            on_access.submit(&event);
+            event_hub.publish(LiveEvent::Monitor(event));
+        }));
         monitor.start()?;
         self.monitor = Some(monitor);
         log::info!("文件监控已启动");
         Ok(())
     }
 
+    // 发现的威胁以扫描类型作为扫描 ID 写入检测日志
+    async fn run_engine(&mut self, scan_options: ScanOptions) -> Result<Vec<crate::scanner::ScanResult>, anyhow::Error> {
+        let scan_id = format!("{:?}", scan_options.scan_mode).to_lowercase();
+        self.scanner_engine = Some(self.create_engine(scan_options));
+
+        let results = match &self.scanner_engine {
+            Some(engine) => engine.start_scan().await?,
+            None => return Err(anyhow::anyhow!("扫描引擎未初始化")),
+        };
+        if let Some(detection_logger) = self.detection_logger.clone().filter(|_| !results.is_empty()) {
+            let detections = results.clone();
+            // 计算文件摘要需要读取文件
+            let _ = tokio::task::spawn_blocking(move || {
+                let threats: Vec<ThreatReport> = detections
+                    .iter()
+                    .map(|result| ThreatReport::from_result(String::new(), result, true))
+                    .collect();
+                detection_logger.log_threats(&scan_id, &threats);
+            })
+            .await;
+        }
+        Ok(results)
+    }
+
+    // 按 update.schedule 定时更新病毒库，需要先调用 initialize
+    pub async fn start_update_scheduler(&mut self) -> Result<(), anyhow::Error> {
+        let updater = self.updater.clone().context("病毒库更新器未初始化")?;
+        let config = self.config.read().await;
+        let schedule = UpdateSchedule {
+            enabled: true,
+            frequency: config.update.schedule.frequency.clone(),
+            time: config.update.schedule.time.clone(),
+            day_of_week: config.update.schedule.day_of_week,
+        };
+        drop(config);
+        let scheduler = UpdateScheduler::new(updater, schedule);
+        scheduler.start().await;
+        self.update_scheduler = Some(scheduler);
+        Ok(())
+    }
+
+    // 按 scheduled_scans 运行定时扫描，需要先调用 initialize。计划从共享配置中读取，重新加载配置后立即生效
+    pub async fn start_scan_scheduler(&mut self) -> Result<(), anyhow::Error> {
+        let mut scheduler = ScanScheduler::new(Arc::clone(&self.config), Arc::clone(&self.signature_db));
+        scheduler.set_allowlist(Arc::clone(&self.allowlist));
+        scheduler.set_quarantine_manager(Arc::clone(&self.quarantine));
+        if let Some(cache) = &self.verdict_cache {
+            scheduler.set_verdict_cache(Arc::clone(cache));
+        }
+        if let Some(store) = &self.threat_store {
+            scheduler.set_threat_store(Arc::clone(store));
+        }
+        if let Some(detection_logger) = &self.detection_logger {
+            scheduler.set_detection_logger(Arc::clone(detection_logger));
+        }
+        if let Some(alerts) = &self.alerts {
+            scheduler.set_alert_dispatcher(Arc::clone(alerts));
+        }
+        scheduler.set_event_hub(Arc::clone(&self.event_hub));
+        let scheduler = Arc::new(scheduler);
+        let task = scheduler.start();
+        log::info!("已配置 {} 个定时扫描", self.config.read().await.scheduled_scans.len());
+        self.scan_scheduler = Some((scheduler, task));
+        Ok(())
+    }
+
+    // 每次扫描共用同一个控制句柄，外部组件只需在启动时获取一次
+    fn create_engine(&self, scan_options: ScanOptions) -> ScannerEngine {
+        self.scan_control.reset();
+        let mut engine = ScannerEngine::new(Arc::clone(&self.signature_db), scan_options);
+        engine.set_scan_control(self.scan_control.clone());
+        engine.set_quarantine_manager(Arc::clone(&self.quarantine));
+        engine.set_allowlist(Arc::clone(&self.allowlist));
+        if let Some(cache) = &self.verdict_cache {
+            engine.set_verdict_cache(Arc::clone(cache));
+        }
+        engine
+    }
+
+    pub fn scan_control(&self) -> ScanControl {
+        self.scan_control.clone()
+    }
+
+    pub fn set_config_path(&mut self, config_path: PathBuf) {
+        self.config_path = Some(config_path);
+    }
+
+    pub fn event_hub(&self) -> Arc<EventHub> {
+        Arc::clone(&self.event_hub)
+    }
+
+    pub fn allowlist(&self) -> Arc<Allowlist> {
+        Arc::clone(&self.allowlist)
+    }
+
+    pub fn cancel_scan(&self) -> bool {
+        log::info!("请求取消当前扫描");
+        self.scan_control.cancel()
+    }
+
+    pub fn pause_scan(&self) -> bool {
+        log::info!("请求暂停当前扫描");
+        self.scan_control.pause()
+    }
+
+    pub fn resume_scan(&self) -> bool {
+        log::info!("请求恢复当前扫描");
+        self.scan_control.resume()
+    }
+
     pub fn stop_file_monitor(&mut self) {
         if let Some(ref mut monitor) = self.monitor {
             monitor.stop();
             log::info!("文件监控已停止");
         }
+        if let Some(task) = self.on_access_task.take() {
+            task.abort();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(mut guard) = self.access_guard.take() {
+            guard.stop();
+        }
     }
 
-    pub fn start_api_server(&mut self, addr: &str, api_key: &str) -> Result<(), anyhow::Error> {
+    pub async fn start_api_server(&mut self, addr: &str, api_key: &str) -> Result<(), anyhow::Error> {
         let addr: std::net::SocketAddr = addr.parse()?;
-        self.api_server = Some(ApiServer::new(addr, api_key.to_string()));
-        log::info!("API服务器将在后台启动...");
+        let config = self.config.read().await;
+        let mut scan_jobs = ScanJobManager::new(
+            Arc::clone(&self.signature_db),
+            ScanOptions::from_config(&config, ScanMode::Custom, Vec::new()),
+            MAX_CONCURRENT_API_SCANS,
+        );
+        scan_jobs.set_allowlist(Arc::clone(&self.allowlist));
+        scan_jobs.set_quarantine_manager(Arc::clone(&self.quarantine));
+        if let Some(cache) = &self.verdict_cache {
+            scan_jobs.set_verdict_cache(Arc::clone(cache));
+        }
+        scan_jobs.set_event_hub(Arc::clone(&self.event_hub));
+        if let Some(store) = &self.threat_store {
+            scan_jobs.set_threat_store(Arc::clone(store));
+        }
+        if let Some(detection_logger) = &self.detection_logger {
+            scan_jobs.set_detection_logger(Arc::clone(detection_logger));
+        }
+        if let Some(alerts) = &self.alerts {
+            scan_jobs.set_alert_dispatcher(Arc::clone(alerts));
+        }
+        let monitor_daemon = config.monitor.daemon.clone();
+        let mut health = HealthChecker::new(config.api.health.clone());
+        health.set_signature_database(Arc::clone(&self.signature_db), self.database_path.clone());
+        health.add_directory("quarantine", config.security.quarantine_dir.clone());
+        health.add_directory("reports", config.report.output_dir.clone());
+        // 监控在本进程中运行时检查监控线程，否则在启用监控时检查监控进程
+        match self.monitor {
+            Some(ref monitor) => health.set_monitor_liveness(monitor.liveness()),
+            None if config.monitor.enabled => health.set_monitor_daemon(monitor_daemon.clone()),
+            None => {}
+        }
+        let security = SecurityManager::new(
+            config.logging.log_dir.clone(),
+            config.api.rate_limit.lockout_threshold.max(1),
+            config.api.rate_limit.lockout_duration_secs,
+        );
+        // 参数中的 API 密钥优先于配置文件
+        let mut api_config = config.api.clone();
+        if !api_key.is_empty() {
+            api_config.api_key = api_key.to_string();
+        }
+        drop(config);
+        let auth = TokenAuthority::from_config(&api_config).unwrap_or_else(|e| {
+            log::error!("无法加载令牌签名密钥，使用临时密钥，重启后令牌失效: {:#}", e);
+            TokenAuthority::ephemeral(&api_config)
+        });
+        let auth = auth.with_security_manager(Arc::new(security));
+
+        let scan_jobs = Arc::new(scan_jobs);
+        let config_manager = Arc::new(self.build_config_manager(Some(&scan_jobs)).await);
+        self.config_manager = Some(Arc::clone(&config_manager));
+
+        let mut api_server = ApiServer::new(addr, Arc::new(auth))
+            .with_rate_limit(&api_config.rate_limit)
+            .with_scan_control(self.scan_control.clone())
+            .with_allowlist(Arc::clone(&self.allowlist))
+            .with_monitor_daemon(monitor_daemon)
+            .with_scan_jobs(scan_jobs)
+            .with_event_hub(Arc::clone(&self.event_hub))
+            .with_config_manager(config_manager)
+            .with_health_checker(Arc::new(health));
+        if let Some(ref updater) = self.updater {
+            api_server = api_server.with_update_jobs(Arc::new(UpdateJobManager::new(Arc::clone(updater))));
+        }
+        if let Some(store) = &self.threat_store {
+            api_server = api_server.with_threat_store(Arc::clone(store));
+        }
+        self.api_server = Some(api_server);
+        log::info!("API服务器将在 run 中启动: {}", addr);
         Ok(())
     }
 
+    // 通过 API 修改配置或重新加载配置文件后，扫描参数、性能参数和白名单立即生效，其余配置项需要重启
+    async fn build_config_manager(&self, scan_jobs: Option<&Arc<ScanJobManager>>) -> ConfigManager {
+        let mut manager = ConfigManager::new(Arc::clone(&self.config));
+        if let Some(ref path) = self.config_path {
+            manager = manager.persist_to(path.clone());
+        }
+
+        for section in ["scan_modes", "performance", "security"] {
+            let Some(scan_jobs) = scan_jobs.map(Arc::clone) else {
+                break;
+            };
+            manager.on_reload(
+                section,
+                Arc::new(move |config| {
+                    scan_jobs.set_base_options(ScanOptions::from_config(config, ScanMode::Custom, Vec::new()));
+                    Ok(())
+                }),
+            );
+        }
+
+        let signature_db = Arc::clone(&self.signature_db);
+        manager.on_reload(
+            "performance",
+            Arc::new(move |config| {
+                signature_db.set_regex_time_budget(Duration::from_millis(config.performance.regex_time_budget_ms));
+                signature_db.set_scan_buffer_size(config.performance.scan_buffer_size);
+                signature_db.set_use_mmap(config.performance.use_mmap);
+                Ok(())
+            }),
+        );
+
+        // 只同步配置文件中增删的条目，保留通过 /api/v1/allowlist 添加的条目
+        let allowlist = Arc::clone(&self.allowlist);
+        let previous = std::sync::Mutex::new(self.config.read().await.allowlist.clone());
+        manager.on_reload(
+            "allowlist",
+            Arc::new(move |config| {
+                let mut previous = previous.lock().unwrap();
+                for hash in previous.sha256.iter().filter(|hash| !config.allowlist.sha256.contains(hash)) {
+                    allowlist.remove_hash(hash);
+                }
+                for pattern in previous.paths.iter().filter(|pattern| !config.allowlist.paths.contains(pattern)) {
+                    allowlist.remove_path(pattern)?;
+                }
+                for hash in &config.allowlist.sha256 {
+                    allowlist.add_hash(hash)?;
+                }
+                for pattern in &config.allowlist.paths {
+                    allowlist.add_path(pattern)?;
+                }
+                *previous = config.allowlist.clone();
+                Ok(())
+            }),
+        );
+
+        // 定时扫描调度器每分钟读取一次 scheduled_scans，不需要额外处理
+        manager.on_reload("scheduled_scans", Arc::new(|_| Ok(())));
+        manager
+    }
+
+    // 已调用 start_api_server 时在这里运行 API 服务器。收到 Ctrl-C 或 SIGTERM 后关闭，
+    // 收到 SIGHUP 时重新读取配置文件和病毒库。由 systemd 以 Type=notify 启动时报告就绪并发送看门狗心跳
     pub async fn run(&mut self) -> Result<(), anyhow::Error> {
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = match self.api_server.take() {
+            Some(server) => Some(tokio::spawn(server.bind(Arc::new(()), async move {
+                let _ = stop_rx.await;
+            })?)),
+            None => None,
+        };
+        if self.config_manager.is_none() {
+            self.config_manager = Some(Arc::new(self.build_config_manager(None).await));
+        }
+        let watchdog = service::watchdog_timeout().map(|timeout| self.spawn_watchdog(timeout));
+
         log::info!("病毒查杀工具启动完成");
+        service::notify("READY=1");
+        let result = self.wait_for_termination().await;
+        service::notify("STOPPING=1");
 
-        tokio::select! {
-            _ = signal::ctrl_c() => {
-                log::info!("收到终止信号，正在关闭...");
+        if let Some(watchdog) = watchdog {
+            watchdog.abort();
+        }
+        let _ = stop_tx.send(());
+        if let Some(server) = server {
+            let _ = server.await;
+        }
+        self.shutdown().await?;
+        result
+    }
+
+    async fn wait_for_termination(&self) -> Result<(), anyhow::Error> {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())?;
+        loop {
+            tokio::select! {
+                _ = signal::ctrl_c() => {
+                    log::info!("收到终止信号，正在关闭...");
+                    return Ok(());
+                }
+                _ = terminate.recv() => {
+                    log::info!("收到 SIGTERM，正在关闭...");
+                    return Ok(());
+                }
+                _ = hangup.recv() => {
+                    service::notify("RELOADING=1");
+                    match self.reload_config().await {
+                        Ok(update) => log::info!(
+                            "配置已重新加载，变化: {:?}，已生效: {:?}，需要重启: {:?}",
+                            update.changed,
+                            update.reloaded,
+                            update.restart_required
+                        ),
+                        Err(e) => log::error!("重新加载配置失败，继续使用原配置: {:#}", e),
+                    }
+                    // 同时加载本地自定义特征码的修改
+                    if let Err(e) = self.signature_db.reload_from_directory(&self.database_path).await {
+                        log::error!("重新加载病毒库失败，继续使用当前病毒库: {:#}", e);
+                    }
+                    service::notify("READY=1");
+                }
             }
         }
+    }
 
-        self.shutdown().await
+    // 重新读取配置文件并应用环境变量覆盖，可以在运行中生效的配置项立即生效
+    pub async fn reload_config(&self) -> Result<ConfigUpdate, anyhow::Error> {
+        let path = self.config_path.as_ref().context("未设置配置文件路径")?;
+        let manager = self.config_manager.as_ref().context("配置管理未初始化")?;
+        let mut config = ScannerConfig::load(path).with_context(|| format!("无法加载配置文件: {:?}", path))?;
+        config.apply_env_overrides()?;
+        manager.replace(config).await
+    }
+
+    // 本进程中的文件监控线程停止响应时不再发送心跳，由 systemd 重启服务
+    fn spawn_watchdog(&self, timeout: Duration) -> tokio::task::JoinHandle<()> {
+        let liveness = self.monitor.as_ref().map(|monitor| monitor.liveness());
+        let config = Arc::clone(&self.config);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timeout / 2);
+            loop {
+                interval.tick().await;
+                if let Some(ref liveness) = liveness {
+                    let stall = chrono::Duration::seconds(config.read().await.api.health.monitor_stall_secs.max(1) as i64);
+                    let alive = liveness.is_running()
+                        && liveness
+                            .last_heartbeat()
+                            .map_or(false, |heartbeat| chrono::Utc::now().signed_duration_since(heartbeat) <= stall);
+                    if !alive {
+                        log::error!("文件监控线程已停止响应，停止发送看门狗心跳");
+                        continue;
+                    }
+                }
+                service::notify("WATCHDOG=1");
+            }
+        })
     }
 
     pub async fn shutdown(&mut self) -> Result<(), anyhow::Error> {
@@ -210,6 +735,23 @@ impl VirusScanner {
 
         self.stop_file_monitor();
 
+        if let Some(ref scheduler) = self.misp_scheduler {
+            scheduler.stop();
+        }
+
+        if let Some(scheduler) = self.update_scheduler.take() {
+            scheduler.stop();
+        }
+
+        if let Some((scheduler, task)) = self.scan_scheduler.take() {
+            scheduler.stop();
+            task.abort();
+        }
+
+        if let Some(ref alerts) = self.alerts {
+            alerts.shutdown(alerts::SHUTDOWN_TIMEOUT).await;
+        }
+
         log::info!("病毒查杀工具已关闭");
         Ok(())
     }
@@ -247,3 +789,6 @@ impl Default for VirusScanner {
         Self::new(ScannerConfig::default())
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,438 @@
+use crate::api::events::{EventHub, LiveEvent};
+use crate::core::alerts::AlertDispatcher;
+use crate::core::security::QuarantineManager;
+use crate::config::{ScheduledScanConfig, ScannerConfig};
+use crate::report::{DetectionLogger, ReportFormat, ReportGenerator, StoredThreat, ThreatStore};
+use crate::scanner::{Allowlist, ScanControl, ScanMode, ScanOptions, ScannerEngine, SignatureDatabase, VerdictCache};
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+// 各定时扫描最近一次运行的结果，保存在报告目录中，供 status 和 schedule list 读取
+pub const SCHEDULE_STATE_FILE: &str = "scheduled_scans.json";
+
+// 查找下一次运行时间时最多向后查找的天数，2 月 30 日这样的表达式永远不会运行
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 5;
+
+// 5 字段 cron 表达式：分 时 日 月 周。每个字段支持 *、数字、a-b 范围、逗号分隔的列表和 /步长，
+// 周日为 0 或 7。日和周都不是 * 时满足其一即可 (与 cron 相同)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow::anyhow!("需要 5 个字段 (分 时 日 月 周)，实际为 {} 个", fields.len()));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7).context("周字段")?;
+        // 7 和 0 都表示周日
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59).context("分钟字段")?,
+            hours: parse_field(fields[1], 0, 23).context("小时字段")?,
+            days: parse_field(fields[2], 1, 31).context("日期字段")?,
+            months: parse_field(fields[3], 1, 12).context("月份字段")?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        self.matches_date(time) && bit(self.hours, time.hour()) && bit(self.minutes, time.minute())
+    }
+
+    fn matches_date(&self, time: &DateTime<Local>) -> bool {
+        if !bit(self.months, time.month()) {
+            return false;
+        }
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    // after 之后 (不含) 的第一个运行时间。夏令时跳过的时刻不运行
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let mut date = start.date_naive();
+        let last = date + ChronoDuration::days(MAX_LOOKAHEAD_DAYS);
+        while date <= last {
+            let midnight = Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest();
+            if midnight.map_or(true, |midnight| self.matches_date(&midnight)) {
+                for hour in 0..24 {
+                    if !bit(self.hours, hour) {
+                        continue;
+                    }
+                    for minute in 0..60 {
+                        if !bit(self.minutes, minute) {
+                            continue;
+                        }
+                        let Some(time) = Local.from_local_datetime(&date.and_hms_opt(hour, minute, 0)?).earliest() else {
+                            continue;
+                        };
+                        if time >= start && self.matches_date(&time) {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, anyhow::Error> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| anyhow::anyhow!("无效的步长: {}", part))?;
+                if step == 0 {
+                    return Err(anyhow::anyhow!("步长不能为 0: {}", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => {
+                let parse = |value: &str| value.parse::<u32>().map_err(|_| anyhow::anyhow!("无效的取值: {}", part));
+                match range.split_once('-') {
+                    Some((start, end)) => (parse(start)?, parse(end)?),
+                    // 5/15 表示从 5 开始每 15 一次
+                    None if step > 1 => (parse(range)?, max),
+                    None => {
+                        let value = parse(range)?;
+                        (value, value)
+                    }
+                }
+            }
+        };
+        if start < min || end > max || start > end {
+            return Err(anyhow::anyhow!("{} 超出范围 {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    // completed、cancelled 或 failed
+    pub status: String,
+    pub files_scanned: usize,
+    pub threats_found: usize,
+    pub report_id: Option<String>,
+    pub report_path: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleState {
+    // 按定时扫描名称
+    pub last_runs: BTreeMap<String, ScheduledRun>,
+}
+
+impl ScheduleState {
+    // 文件不存在或无法解析时返回空状态
+    pub fn load(report_dir: &Path) -> Self {
+        std::fs::read_to_string(report_dir.join(SCHEDULE_STATE_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, report_dir: &Path) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(report_dir).with_context(|| format!("无法创建报告目录: {:?}", report_dir))?;
+        let path = report_dir.join(SCHEDULE_STATE_FILE);
+        let tmp = report_dir.join(format!(".{}.tmp", SCHEDULE_STATE_FILE));
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?).with_context(|| format!("无法写入 {:?}", tmp))?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("无法写入 {:?}", path))?;
+        Ok(())
+    }
+}
+
+// 定时扫描的运行时间和结果，schedule list 和 status 使用
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleSummary {
+    #[serde(flatten)]
+    pub schedule: ScheduledScanConfig,
+    pub next_run: Option<DateTime<Local>>,
+    pub last_run: Option<ScheduledRun>,
+}
+
+pub fn summarize(config: &ScannerConfig) -> Vec<ScheduleSummary> {
+    let mut state = ScheduleState::load(&config.report.output_dir);
+    let now = Local::now();
+    config
+        .scheduled_scans
+        .iter()
+        .map(|schedule| ScheduleSummary {
+            next_run: schedule
+                .enabled
+                .then(|| schedule.cron.parse::<CronSchedule>().ok()?.next_after(now))
+                .flatten(),
+            last_run: state.last_runs.remove(&schedule.name),
+            schedule: schedule.clone(),
+        })
+        .collect()
+}
+
+// 每分钟从共享配置读取 scheduled_scans，到时运行扫描。修改配置 (SIGHUP 或 API) 后下一分钟生效。
+// 同一时间只运行一个定时扫描，到时仍在运行或排队的计划本次跳过
+pub struct ScanScheduler {
+    config: Arc<RwLock<ScannerConfig>>,
+    signature_db: Arc<SignatureDatabase>,
+    allowlist: Option<Arc<Allowlist>>,
+    quarantine: Option<Arc<QuarantineManager>>,
+    verdict_cache: Option<Arc<VerdictCache>>,
+    threat_store: Option<Arc<ThreatStore>>,
+    detection_logger: Option<Arc<DetectionLogger>>,
+    alerts: Option<Arc<AlertDispatcher>>,
+    event_hub: Option<Arc<EventHub>>,
+    control: ScanControl,
+    pending: Mutex<HashSet<String>>,
+    scan_lock: tokio::sync::Mutex<()>,
+    state_lock: Mutex<()>,
+}
+
+impl ScanScheduler {
+    pub fn new(config: Arc<RwLock<ScannerConfig>>, signature_db: Arc<SignatureDatabase>) -> Self {
+        Self {
+            config,
+            signature_db,
+            allowlist: None,
+            quarantine: None,
+            verdict_cache: None,
+            threat_store: None,
+            detection_logger: None,
+            alerts: None,
+            event_hub: None,
+            control: ScanControl::new(),
+            pending: Mutex::new(HashSet::new()),
+            scan_lock: tokio::sync::Mutex::new(()),
+            state_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn set_allowlist(&mut self, allowlist: Arc<Allowlist>) {
+        self.allowlist = Some(allowlist);
+    }
+
+    pub fn set_quarantine_manager(&mut self, quarantine: Arc<QuarantineManager>) {
+        self.quarantine = Some(quarantine);
+    }
+
+    pub fn set_verdict_cache(&mut self, cache: Arc<VerdictCache>) {
+        self.verdict_cache = Some(cache);
+    }
+
+    pub fn set_threat_store(&mut self, threat_store: Arc<ThreatStore>) {
+        self.threat_store = Some(threat_store);
+    }
+
+    pub fn set_detection_logger(&mut self, detection_logger: Arc<DetectionLogger>) {
+        self.detection_logger = Some(detection_logger);
+    }
+
+    pub fn set_alert_dispatcher(&mut self, alerts: Arc<AlertDispatcher>) {
+        self.alerts = Some(alerts);
+    }
+
+    pub fn set_event_hub(&mut self, event_hub: Arc<EventHub>) {
+        self.event_hub = Some(event_hub);
+    }
+
+    // 需要在 tokio 运行时中调用。返回的任务被中止或调用 stop 后不再启动新的扫描
+    pub fn start(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let scheduler = Arc::clone(self);
+        log::info!("定时扫描调度器已启动");
+        tokio::spawn(async move {
+            loop {
+                // 在每分钟开始时检查
+                let now = Local::now();
+                let wait = 60 - now.second() as u64;
+                tokio::time::sleep(Duration::from_secs(wait)).await;
+                if scheduler.control.is_cancelled() {
+                    return;
+                }
+
+                let now = Local::now();
+                let schedules = scheduler.config.read().await.scheduled_scans.clone();
+                for schedule in schedules.into_iter().filter(|schedule| schedule.enabled) {
+                    let due = match schedule.cron.parse::<CronSchedule>() {
+                        Ok(cron) => cron.matches(&now),
+                        Err(e) => {
+                            log::warn!("定时扫描 {} 的 cron 表达式无效: {:#}", schedule.name, e);
+                            false
+                        }
+                    };
+                    if !due {
+                        continue;
+                    }
+                    if !scheduler.pending.lock().unwrap().insert(schedule.name.clone()) {
+                        log::warn!("定时扫描 {} 的上一次运行尚未结束，跳过本次运行", schedule.name);
+                        continue;
+                    }
+                    let scheduler = Arc::clone(&scheduler);
+                    tokio::spawn(async move {
+                        scheduler.run(&schedule).await;
+                        scheduler.pending.lock().unwrap().remove(&schedule.name);
+                    });
+                }
+            }
+        })
+    }
+
+    // 取消正在运行的定时扫描，调度任务在下一分钟退出
+    pub fn stop(&self) {
+        self.control.cancel();
+        log::info!("定时扫描调度器已停止");
+    }
+
+    pub async fn run(&self, schedule: &ScheduledScanConfig) -> ScheduledRun {
+        let _guard = self.scan_lock.lock().await;
+        let started_at = Utc::now();
+        log::info!("定时扫描 {} 开始", schedule.name);
+        let run = match self.scan(schedule).await {
+            Ok(run) => run,
+            Err(e) => {
+                log::error!("定时扫描 {} 失败: {:#}", schedule.name, e);
+                ScheduledRun {
+                    started_at,
+                    finished_at: Utc::now(),
+                    status: "failed".to_string(),
+                    files_scanned: 0,
+                    threats_found: 0,
+                    report_id: None,
+                    report_path: None,
+                    error: Some(format!("{:#}", e)),
+                }
+            }
+        };
+        log::info!("定时扫描 {} 结束: {}，发现 {} 个威胁", schedule.name, run.status, run.threats_found);
+
+        let report_dir = self.config.read().await.report.output_dir.clone();
+        let _state = self.state_lock.lock().unwrap();
+        let mut state = ScheduleState::load(&report_dir);
+        state.last_runs.insert(schedule.name.clone(), run.clone());
+        if let Err(e) = state.save(&report_dir) {
+            log::warn!("无法保存定时扫描状态: {:#}", e);
+        }
+        run
+    }
+
+    async fn scan(&self, schedule: &ScheduledScanConfig) -> Result<ScheduledRun, anyhow::Error> {
+        let scan_mode: ScanMode = schedule.scan_type.parse()?;
+        let paths = schedule.paths.iter().map(PathBuf::from).collect();
+        let (options, report_dir, report_format) = {
+            let config = self.config.read().await;
+            (
+                ScanOptions::from_config(&config, scan_mode, paths),
+                config.report.output_dir.clone(),
+                config.report.format.parse::<ReportFormat>()?,
+            )
+        };
+        let scan_paths = options.custom_paths.clone();
+
+        let started_at = Utc::now();
+        let start_time = Instant::now();
+        let mut engine = ScannerEngine::new(Arc::clone(&self.signature_db), options);
+        engine.set_scan_control(self.control.clone());
+        if let Some(ref allowlist) = self.allowlist {
+            engine.set_allowlist(Arc::clone(allowlist));
+        }
+        if let Some(ref quarantine) = self.quarantine {
+            engine.set_quarantine_manager(Arc::clone(quarantine));
+        }
+        if let Some(ref cache) = self.verdict_cache {
+            engine.set_verdict_cache(Arc::clone(cache));
+        }
+        let results = engine.start_scan().await?;
+        let files_scanned = engine.get_stats().get_files_scanned();
+
+        let generator = ReportGenerator::new(report_dir);
+        let report = generator.generate(
+            &results,
+            &format!("scheduled:{}", schedule.name),
+            &scan_paths,
+            start_time,
+            0.0,
+            self.signature_db.get_version(),
+        )?;
+        let report_path = generator.save(&report, report_format)?;
+
+        if !report.threats.is_empty() {
+            let source = format!("schedule:{}", schedule.name);
+            if let Some(ref detection_logger) = self.detection_logger {
+                detection_logger.log_threats(&report.id, &report.threats);
+            }
+            let stored = match self.threat_store {
+                Some(ref store) => store.record_or_warn(&report.id, &source, &report.threats),
+                None => report.threats.iter().map(|threat| StoredThreat::new(&report.id, &source, threat)).collect(),
+            };
+            if let Some(ref alerts) = self.alerts {
+                alerts.notify(&stored);
+            }
+            if let Some(ref hub) = self.event_hub {
+                for threat in stored {
+                    hub.publish(LiveEvent::Threat(threat));
+                }
+            }
+        }
+
+        Ok(ScheduledRun {
+            started_at,
+            finished_at: Utc::now(),
+            status: if self.control.is_cancelled() { "cancelled" } else { "completed" }.to_string(),
+            files_scanned,
+            threats_found: results.len(),
+            report_id: Some(report.id),
+            report_path: Some(report_path),
+            error: None,
+        })
+    }
+}
@@ -1,7 +1,24 @@
-use std::path::PathBuf;
+use std::os::unix::io::AsRawFd;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use crate::utils::AuditLogger;
+
+// 隔离文件格式: 随机数(12字节) || 密文 || GCM 认证标签(16字节)
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// 隔离目录下的元数据索引
+pub const QUARANTINE_INDEX: &str = "index.json";
+// 索引通过改名整体替换，进程间的互斥加在单独的锁文件上
+const QUARANTINE_INDEX_LOCK: &str = ".index.json.lock";
+use crate::config::SecurityConfig;
+use crate::utils::logging::AuditLogger;
+use crate::utils::{ensure_free_space, get_file_digests, safe_canonicalize};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use nix::fcntl::{flock, FlockArg};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 pub struct SecurityManager {
     audit_logger: AuditLogger,
@@ -73,9 +90,39 @@ impl SecurityManager {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    // 隔离文件名中下划线之前的部分；索引之外的文件使用完整文件名
+    pub id: String,
+    // 隔离目录中的文件名
+    pub file_name: String,
+    // 索引之外的文件 (旧版本隔离或索引丢失) 不知道原始路径
+    pub original_path: Option<PathBuf>,
+    pub quarantined_at: DateTime<Utc>,
+    // 原文件大小
+    pub size: u64,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub encrypted: bool,
+    // 原文件的权限位，恢复时还原
+    #[serde(default)]
+    pub mode: Option<u32>,
+}
+
+impl QuarantineEntry {
+    pub fn original_name(&self) -> String {
+        match self.original_path.as_ref().and_then(|path| path.file_name()) {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => self.file_name.split_once('_').map_or(self.file_name.as_str(), |(_, name)| name).to_string(),
+        }
+    }
+}
+
 pub struct QuarantineManager {
     quarantine_dir: PathBuf,
     encryption_key: Option<Vec<u8>>,
+    // 同一进程中串行修改索引，进程之间另用锁文件
+    index_lock: Mutex<()>,
 }
 
 impl QuarantineManager {
@@ -84,9 +131,22 @@ impl QuarantineManager {
         Self {
             quarantine_dir,
             encryption_key,
+            index_lock: Mutex::new(()),
         }
     }
 
+    pub fn from_config(config: &SecurityConfig) -> Result<Self, anyhow::Error> {
+        let encryption_key = match config.quarantine_key_file {
+            Some(ref key_file) => Some(load_encryption_key(key_file)?),
+            None => None,
+        };
+        Ok(Self::new(config.quarantine_dir.clone(), encryption_key))
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption_key.is_some()
+    }
+
     pub async fn quarantine_file(
         &self,
         file_path: &PathBuf,
@@ -94,21 +154,253 @@ impl QuarantineManager {
         let file_name = file_path.file_name()
             .ok_or_else(|| anyhow::anyhow!("无效的文件名"))?;
 
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
-        let quarantine_name = format!("{}_{}", timestamp, file_name.to_string_lossy());
+        // 前缀中不能含下划线，恢复时按第一个下划线拆出原文件名；随机后缀避免同名文件互相覆盖
+        let timestamp = chrono::Local::now().format("%Y%m%dT%H%M%S").to_string();
+        let quarantine_name = format!(
+            "{}-{:08x}_{}",
+            timestamp,
+            rand::thread_rng().gen::<u32>(),
+            file_name.to_string_lossy()
+        );
         let quarantine_path = self.quarantine_dir.join(&quarantine_name);
 
+        let metadata = std::fs::metadata(file_path)?;
+        ensure_free_space(&self.quarantine_dir, metadata.len())?;
+
+        let sha256 = get_file_digests(file_path).ok().map(|digests| digests.sha256);
         if let Some(ref key) = self.encryption_key {
             self.encrypt_and_copy(file_path, &quarantine_path, key).await?;
         } else {
             std::fs::copy(file_path, &quarantine_path)?;
         }
 
+        // 索引写入失败时隔离照常进行，下次读取索引时按文件名补录
+        let entry = QuarantineEntry {
+            id: quarantine_name.split_once('_').map_or(quarantine_name.as_str(), |(id, _)| id).to_string(),
+            file_name: quarantine_name.clone(),
+            original_path: Some(std::path::absolute(file_path).unwrap_or_else(|_| file_path.clone())),
+            quarantined_at: Utc::now(),
+            size: metadata.len(),
+            sha256,
+            encrypted: self.encryption_key.is_some(),
+            mode: {
+                use std::os::unix::fs::PermissionsExt;
+                Some(metadata.permissions().mode() & 0o777)
+            },
+        };
+        // 文件已经复制进隔离目录，读取索引时会被当作索引之外的文件补录，这里用完整记录替换
+        if let Err(e) = self.update_index(|entries| {
+            entries.retain(|existing| existing.file_name != entry.file_name);
+            entries.push(entry);
+            Ok(())
+        }) {
+            log::warn!("无法更新隔离区索引: {:#}", e);
+        }
+
         std::fs::remove_file(file_path)?;
 
         Ok(quarantine_path)
     }
 
+    // 按隔离时间从旧到新排列
+    pub fn list(&self) -> Result<Vec<QuarantineEntry>, anyhow::Error> {
+        let _guard = self.index_lock.lock().unwrap();
+        self.load_index()
+    }
+
+    pub fn path(&self, entry: &QuarantineEntry) -> PathBuf {
+        self.quarantine_dir.join(&entry.file_name)
+    }
+
+    // 索引可能被篡改：文件名只能是隔离目录下的单个文件名，已存在的文件解析后也必须仍在隔离目录中
+    fn stored_path(&self, entry: &QuarantineEntry) -> Result<PathBuf, anyhow::Error> {
+        let mut components = Path::new(&entry.file_name).components();
+        let single_name = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+        if !single_name || entry.file_name.contains(['/', '\\']) {
+            return Err(anyhow::anyhow!("隔离区索引中的文件名无效: {:?}", entry.file_name));
+        }
+
+        let path = self.path(entry);
+        if path.symlink_metadata().is_err() {
+            return Ok(path);
+        }
+        safe_canonicalize(&path, &[self.quarantine_dir.clone()])
+    }
+
+    // destination 为空时恢复到原始路径，为目录时恢复到该目录下的原文件名。
+    // 恢复成功后从隔离区删除
+    pub fn restore(
+        &self,
+        id: &str,
+        destination: Option<&Path>,
+        overwrite: bool,
+    ) -> Result<(QuarantineEntry, PathBuf), anyhow::Error> {
+        self.update_index(|entries| {
+            let position = entries
+                .iter()
+                .position(|entry| entry.id == id)
+                .ok_or_else(|| anyhow::anyhow!("隔离文件不存在: {}", id))?;
+            let entry = &entries[position];
+            let restore_path = match destination {
+                Some(dir) if dir.is_dir() => dir.join(entry.original_name()),
+                Some(path) => path.to_path_buf(),
+                None => entry
+                    .original_path
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("隔离文件 {} 的原始路径未知，请指定恢复位置", id))?,
+            };
+            if restore_path.exists() && !overwrite {
+                return Err(anyhow::anyhow!("目标文件已存在: {:?}", restore_path));
+            }
+            if let Some(parent) = restore_path.parent() {
+                std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+            }
+
+            let stored = self.stored_path(entry)?;
+            if entry.encrypted {
+                let key = self
+                    .encryption_key
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("隔离文件已加密，但未配置隔离区密钥"))?;
+                self.decrypt_and_copy(&stored, &restore_path, key)?;
+            } else {
+                std::fs::copy(&stored, &restore_path)?;
+            }
+            if let Some(mode) = entry.mode {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&restore_path, std::fs::Permissions::from_mode(mode))?;
+            }
+
+            std::fs::remove_file(&stored)?;
+            Ok((entries.remove(position), restore_path))
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<QuarantineEntry, anyhow::Error> {
+        self.update_index(|entries| {
+            let position = entries
+                .iter()
+                .position(|entry| entry.id == id)
+                .ok_or_else(|| anyhow::anyhow!("隔离文件不存在: {}", id))?;
+            remove_if_exists(&self.stored_path(&entries[position])?)?;
+            Ok(entries.remove(position))
+        })
+    }
+
+    // purge 将删除的记录，不修改隔离区
+    pub fn expired(&self, older_than: chrono::Duration) -> Result<Vec<QuarantineEntry>, anyhow::Error> {
+        let cutoff = Utc::now() - older_than;
+        Ok(self.list()?.into_iter().filter(|entry| entry.quarantined_at < cutoff).collect())
+    }
+
+    // 删除隔离时间早于 now - older_than 的文件，返回已删除的记录
+    pub fn purge(&self, older_than: chrono::Duration) -> Result<Vec<QuarantineEntry>, anyhow::Error> {
+        let cutoff = Utc::now() - older_than;
+        self.update_index(|entries| {
+            let mut purged = Vec::new();
+            let mut kept = Vec::new();
+            for entry in entries.drain(..) {
+                if entry.quarantined_at >= cutoff {
+                    kept.push(entry);
+                    continue;
+                }
+                let removed = self
+                    .stored_path(&entry)
+                    .and_then(|path| remove_if_exists(&path).map_err(anyhow::Error::from));
+                match removed {
+                    Ok(()) => purged.push(entry),
+                    Err(e) => {
+                        log::warn!("无法删除隔离文件 {}: {}", entry.file_name, e);
+                        kept.push(entry);
+                    }
+                }
+            }
+            *entries = kept;
+            Ok(purged)
+        })
+    }
+
+    // 读取索引并与隔离目录核对：删除文件已不存在的记录，补录索引之外的隔离文件
+    fn load_index(&self) -> Result<Vec<QuarantineEntry>, anyhow::Error> {
+        let index_path = self.quarantine_dir.join(QUARANTINE_INDEX);
+        let mut entries: Vec<QuarantineEntry> = match std::fs::read_to_string(&index_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("隔离区索引已损坏，将重新生成: {}", e);
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("无法读取隔离区索引: {:?}", index_path)),
+        };
+        entries.retain(|entry| match self.stored_path(entry) {
+            Ok(path) => path.is_file(),
+            Err(e) => {
+                log::warn!("忽略隔离区索引中的记录 {}: {:#}", entry.id, e);
+                false
+            }
+        });
+
+        if let Ok(files) = std::fs::read_dir(&self.quarantine_dir) {
+            for file in files.flatten() {
+                let name = file.file_name().to_string_lossy().to_string();
+                if name == QUARANTINE_INDEX || name.starts_with('.') || entries.iter().any(|entry| entry.file_name == name) {
+                    continue;
+                }
+                let metadata = file.metadata()?;
+                if !metadata.is_file() {
+                    continue;
+                }
+                let size = match self.encryption_key {
+                    Some(_) => metadata.len().saturating_sub((NONCE_LEN + TAG_LEN) as u64),
+                    None => metadata.len(),
+                };
+                entries.push(QuarantineEntry {
+                    id: name.clone(),
+                    file_name: name,
+                    original_path: None,
+                    quarantined_at: metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now()),
+                    size,
+                    sha256: None,
+                    encrypted: self.encryption_key.is_some(),
+                    mode: None,
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.quarantined_at.cmp(&b.quarantined_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    fn update_index<R>(
+        &self,
+        update: impl FnOnce(&mut Vec<QuarantineEntry>) -> Result<R, anyhow::Error>,
+    ) -> Result<R, anyhow::Error> {
+        let _guard = self.index_lock.lock().unwrap();
+        let _lock = self.lock_index_file()?;
+        let mut entries = self.load_index()?;
+        let result = update(&mut entries);
+
+        let index_path = self.quarantine_dir.join(QUARANTINE_INDEX);
+        let temp = self.quarantine_dir.join(format!(".{}.tmp", QUARANTINE_INDEX));
+        std::fs::write(&temp, serde_json::to_string_pretty(&entries)?)
+            .with_context(|| format!("无法写入隔离区索引: {:?}", temp))?;
+        std::fs::rename(&temp, &index_path).with_context(|| format!("无法写入隔离区索引: {:?}", index_path))?;
+        result
+    }
+
+    // daemon 与命令行的 quarantine restore/delete/purge 可能同时修改索引，
+    // 读取-修改-写回期间持有锁文件上的排他 flock，文件关闭时释放
+    fn lock_index_file(&self) -> Result<std::fs::File, anyhow::Error> {
+        let lock_path = self.quarantine_dir.join(QUARANTINE_INDEX_LOCK);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("无法打开隔离区索引锁: {:?}", lock_path))?;
+        flock(file.as_raw_fd(), FlockArg::LockExclusive).with_context(|| format!("无法锁定隔离区索引: {:?}", lock_path))?;
+        Ok(file)
+    }
+
     async fn encrypt_and_copy(
         &self,
         src: &PathBuf,
@@ -125,6 +417,7 @@ impl QuarantineManager {
 
         let mut dst_file = tokio::fs::File::create(dst).await?;
         dst_file.write_all(&encrypted).await?;
+        dst_file.flush().await?;
 
         Ok(())
     }
@@ -134,54 +427,22 @@ impl QuarantineManager {
         data: &[u8],
         key: &[u8],
     ) -> Result<Vec<u8>, anyhow::Error> {
-        use aes::Aes256;
-        use ctr::Ctr128BE;
-        use crypto_mac::Hmac;
-        use crypto_mac::NewMac;
-
-        let cipher = Aes256::new_from_slice(key)
-            .map_err(|e| anyhow::anyhow!("密钥错误: {}", e))?;
-        let mut cipher = Ctr128BE::new(cipher, &Default::default());
-
-        let mut encrypted = vec![0u8; data.len()];
-        cipher.encrypt(data, &mut encrypted);
+        if key.len() != 32 {
+            return Err(anyhow::anyhow!("密钥错误: 需要32字节，实际 {} 字节", key.len()));
+        }
 
-        let mut hmac = Hmac::<sha2::Sha256>::new_from_slice(key)
-            .map_err(|e| anyhow::anyhow!("HMAC错误: {}", e))?;
-        hmac.update(&encrypted);
-        let tag = hmac.finalize().into_bytes();
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let mut tag = [0u8; TAG_LEN];
+        let encrypted = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), &[], data, &mut tag)
+            .map_err(|e| anyhow::anyhow!("加密失败: {}", e))?;
 
-        let mut result = encrypted;
+        let mut result = nonce.to_vec();
+        result.extend_from_slice(&encrypted);
         result.extend_from_slice(&tag);
 
         Ok(result)
     }
 
-    pub fn restore_file(&self, quarantine_path: &PathBuf) -> Result<PathBuf, anyhow::Error> {
-        if !quarantine_path.exists() {
-            return Err(anyhow::anyhow!("文件不存在"));
-        }
-
-        let file_name = quarantine_path.file_name()
-            .ok_or_else(|| anyhow::anyhow!("无效的文件名"))?;
-
-        let parts: Vec<&str> = file_name.to_string_lossy().splitn(2, '_').collect();
-        if parts.len() < 2 {
-            return Err(anyhow::anyhow!("文件名格式错误"));
-        }
-
-        let original_name = parts[1];
-        let restore_path = std::env::current_dir()?.join(original_name);
-
-        if let Some(ref key) = self.encryption_key {
-            self.decrypt_and_copy(quarantine_path, &restore_path, key)?;
-        } else {
-            std::fs::copy(quarantine_path, &restore_path)?;
-        }
-
-        Ok(restore_path)
-    }
-
     fn decrypt_and_copy(
         &self,
         src: &PathBuf,
@@ -190,37 +451,49 @@ impl QuarantineManager {
     ) -> Result<(), anyhow::Error> {
         let content = std::fs::read(src)?;
 
-        if content.len() < 32 {
+        if content.len() < NONCE_LEN + TAG_LEN {
             return Err(anyhow::anyhow!("文件格式错误"));
         }
 
-        let data_len = content.len() - 32;
-        let (encrypted, tag) = content.split_at(data_len);
-
-        let mut hmac = Hmac::<sha2::Sha256>::new_from_slice(key)
-            .map_err(|e| anyhow::anyhow!("HMAC错误: {}", e))?;
-        hmac.update(encrypted);
-        hmac.verify(tag)
+        let (nonce, rest) = content.split_at(NONCE_LEN);
+        let (encrypted, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let decrypted = decrypt_aead(Cipher::aes_256_gcm(), key, Some(nonce), &[], encrypted, tag)
             .map_err(|e| anyhow::anyhow!("验证失败: {}", e))?;
 
-        let cipher = Aes256::new_from_slice(key)
-            .map_err(|e| anyhow::anyhow!("密钥错误: {}", e))?;
-        let mut cipher = Ctr128BE::new(cipher, &Default::default());
-
-        let mut decrypted = vec![0u8; data_len];
-        cipher.decrypt(encrypted, &mut decrypted);
-
         std::fs::write(dst, &decrypted)?;
 
         Ok(())
     }
 
     pub fn delete_quarantined(&self, quarantine_path: &PathBuf) -> Result<(), anyhow::Error> {
-        if quarantine_path.exists() {
-            std::fs::remove_file(quarantine_path)?;
-        }
-        Ok(())
+        remove_if_exists(quarantine_path)?;
+        // 记录随文件一起删除
+        self.update_index(|_| Ok(()))
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn load_encryption_key(path: &PathBuf) -> Result<Vec<u8>, anyhow::Error> {
+    let content = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("无法读取隔离区密钥 {:?}: {}", path, e))?;
+
+    let trimmed = String::from_utf8_lossy(&content).trim().to_string();
+    let key = if trimmed.len() == 64 && trimmed.bytes().all(|b| b.is_ascii_hexdigit()) {
+        hex::decode(&trimmed)?
+    } else {
+        content
+    };
+
+    if key.len() != 32 {
+        return Err(anyhow::anyhow!("隔离区密钥长度错误: 需要32字节，实际 {} 字节", key.len()));
     }
+    Ok(key)
 }
 
 pub struct PermissionManager {
@@ -258,8 +531,8 @@ impl PermissionManager {
             .ok_or_else(|| anyhow::anyhow!("用户不存在: {}", run_as_user))?;
 
         nix::unistd::setgroups(&[])?;
-        nix::unistd::setgid(user.primary_gid())?;
-        nix::unistd::setuid(user.uid())?;
+        nix::unistd::setgid(nix::unistd::Gid::from_raw(user.primary_group_id()))?;
+        nix::unistd::setuid(nix::unistd::Uid::from_raw(user.uid()))?;
 
         Ok(())
     }
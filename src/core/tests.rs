@@ -0,0 +1,190 @@
+use crate::config::{AlertConfig, WebhookConfig};
+use crate::core::alerts::AlertDispatcher;
+use crate::core::schedule::CronSchedule;
+use crate::core::security::{QuarantineEntry, QuarantineManager, QUARANTINE_INDEX};
+use crate::report::{FileReportInfo, StoredThreat, ThreatReport};
+use chrono::{Local, TimeZone};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use warp::Filter;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_threat() -> StoredThreat {
+        let report = ThreatReport {
+            id: "t-1".to_string(),
+            file_path: PathBuf::from("/tmp/eicar.com"),
+            threat_type: "Virus".to_string(),
+            risk_level: "High".to_string(),
+            signature_id: "sig-1".to_string(),
+            detection_name: "Eicar-Test-Signature".to_string(),
+            file_info: FileReportInfo {
+                size: 68,
+                permissions: "rw-r--r--".to_string(),
+                created: None,
+                modified: None,
+                md5: None,
+                sha256: None,
+                file_type: None,
+            },
+            action_taken: None,
+            timestamp: Local::now(),
+            archive_member: None,
+            heuristic_score: None,
+        };
+        StoredThreat::new("SCN1", "api", &report)
+    }
+
+    #[tokio::test]
+    async fn test_alerts_retry_and_filter_by_risk() {
+        // 第一次请求返回 503，之后返回 200
+        let received: Arc<Mutex<Vec<(String, Option<String>, serde_json::Value)>>> = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&received);
+        let route = warp::post()
+            .and(warp::path::param::<String>())
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::json())
+            .map(move |hook: String, authorization: Option<String>, body: serde_json::Value| {
+                let mut log = log.lock().unwrap();
+                log.push((hook, authorization, body));
+                if log.len() == 1 {
+                    warp::http::StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    warp::http::StatusCode::OK
+                }
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = AlertConfig {
+            enabled: true,
+            webhooks: vec![
+                WebhookConfig {
+                    name: "soc".to_string(),
+                    url: format!("http://{}/soc", addr),
+                    headers: HashMap::from([("Authorization".to_string(), "Bearer soc-token".to_string())]),
+                    min_risk_level: "medium".to_string(),
+                },
+                WebhookConfig {
+                    name: "pager".to_string(),
+                    url: format!("http://{}/pager", addr),
+                    headers: HashMap::new(),
+                    min_risk_level: "critical".to_string(),
+                },
+            ],
+            max_retries: 2,
+            retry_backoff_ms: 10,
+            ..Default::default()
+        };
+        let dispatcher = AlertDispatcher::start(&config).unwrap().unwrap();
+        let threat = sample_threat();
+        dispatcher.notify(std::slice::from_ref(&threat));
+        dispatcher.shutdown(Duration::from_secs(10)).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|(hook, _, _)| hook == "soc"));
+        let (_, authorization, body) = &received[1];
+        assert_eq!(authorization.as_deref(), Some("Bearer soc-token"));
+        assert_eq!(body["event"], "threat_detected");
+        assert_eq!(body["threat"]["id"], threat.id.as_str());
+        assert!(body["text"].as_str().unwrap().contains("Eicar-Test-Signature"));
+
+        // 未启用时不启动
+        assert!(AlertDispatcher::start(&AlertConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cron_schedule_next_run() {
+        let at = |day, hour, minute| Local.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap();
+
+        // 2026-03-04 为周三
+        let weekly: CronSchedule = "30 3 * * 0".parse().unwrap();
+        assert_eq!(weekly.next_after(at(4, 12, 0)), Some(at(8, 3, 30)));
+        assert!(weekly.matches(&at(8, 3, 30)));
+        assert!(!weekly.matches(&at(8, 3, 31)));
+        // 不含起始时刻
+        assert_eq!(weekly.next_after(at(8, 3, 30)), Some(at(15, 3, 30)));
+        assert_eq!("30 3 * * 7".parse::<CronSchedule>().unwrap(), weekly);
+
+        let steps: CronSchedule = "*/20 9-17 * * 1-5".parse().unwrap();
+        assert_eq!(steps.next_after(at(4, 17, 45)), Some(at(5, 9, 0)));
+        assert_eq!(steps.next_after(at(6, 17, 40)), Some(at(9, 9, 0)));
+
+        // 日和周都指定时满足其一即可
+        let either: CronSchedule = "0 0 10 * 1".parse().unwrap();
+        assert_eq!(either.next_after(at(4, 0, 0)), Some(at(9, 0, 0)));
+        assert_eq!(either.next_after(at(9, 0, 0)), Some(at(10, 0, 0)));
+
+        assert_eq!("@daily".parse::<CronSchedule>().unwrap().next_after(at(4, 0, 0)), Some(at(5, 0, 0)));
+        assert!("0 0 30 2 *".parse::<CronSchedule>().unwrap().next_after(at(4, 0, 0)).is_none());
+        for invalid in ["", "* * * *", "60 * * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(invalid.parse::<CronSchedule>().is_err(), "{}", invalid);
+        }
+    }
+
+    fn tampered_entry(id: &str, file_name: &str) -> QuarantineEntry {
+        QuarantineEntry {
+            id: id.to_string(),
+            file_name: file_name.to_string(),
+            original_path: None,
+            quarantined_at: chrono::Utc::now() - chrono::Duration::days(30),
+            size: 4,
+            sha256: None,
+            encrypted: false,
+            mode: None,
+        }
+    }
+
+    #[test]
+    fn test_quarantine_rejects_paths_outside_quarantine_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine_dir = dir.path().join("quarantine");
+        let victim = dir.path().join("victim.txt");
+        std::fs::write(&victim, b"keep").unwrap();
+        let quarantine = QuarantineManager::new(quarantine_dir.clone(), None);
+
+        let entries = vec![
+            tampered_entry("relative", "../victim.txt"),
+            tampered_entry("absolute", &victim.to_string_lossy()),
+        ];
+        std::fs::write(quarantine_dir.join(QUARANTINE_INDEX), serde_json::to_string(&entries).unwrap()).unwrap();
+
+        // 被篡改的索引记录不出现在列表中，也不能删除隔离目录之外的文件
+        assert!(quarantine.list().unwrap().is_empty());
+        assert!(quarantine.delete("relative").is_err());
+        assert!(quarantine.delete("absolute").is_err());
+        assert!(quarantine.purge(chrono::Duration::zero()).unwrap().is_empty());
+        assert!(victim.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_quarantine_index_shared_between_managers() {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine_dir = dir.path().join("quarantine");
+
+        // 两个管理器模拟 daemon 和命令行进程，各自的进程内锁互不可见
+        let mut tasks = Vec::new();
+        for manager in 0..2 {
+            let quarantine = Arc::new(QuarantineManager::new(quarantine_dir.clone(), None));
+            for i in 0..10 {
+                let quarantine = Arc::clone(&quarantine);
+                let file = dir.path().join(format!("sample-{}-{}.bin", manager, i));
+                std::fs::write(&file, b"data").unwrap();
+                tasks.push(tokio::spawn(async move { quarantine.quarantine_file(&file).await.unwrap() }));
+            }
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // 修改没有互相覆盖：每条记录都保留了原始路径，而不是读取索引时补录的孤立文件
+        let entries = QuarantineManager::new(quarantine_dir, None).list().unwrap();
+        assert_eq!(entries.len(), 20);
+        assert!(entries.iter().all(|entry| entry.original_path.is_some()));
+    }
+}
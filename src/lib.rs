@@ -1,6 +1,7 @@
 pub mod core;
 pub mod scanner;
 pub mod monitor;
+pub mod milter;
 pub mod update;
 pub mod report;
 pub mod api;
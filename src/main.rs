@@ -1,4 +1,6 @@
-use virus_scanner::cli::Command;
+use virus_scanner::cli::{output, Command, ExitStatus};
+use virus_scanner::t;
+use virus_scanner::utils::logging::FILE_ONLY_TARGET;
 use anyhow::Result;
 use std::process;
 
@@ -7,14 +9,19 @@ async fn main() -> Result<()> {
     let command = Command::build();
 
     match Command::execute(&command).await {
-        Ok(_) => {
+        Ok(status) => {
             log::info!("程序执行完成");
-            Ok(())
+            process::exit(status.code());
         }
         Err(e) => {
-            log::error!("执行错误: {}", e);
-            eprintln!("错误: {}", e);
-            process::exit(1);
+            // 下面会向终端输出错误，控制台日志不再重复
+            log::error!(target: FILE_ONLY_TARGET, "执行错误: {:#}", e);
+            if command.output_format().is_json() {
+                println!("{}", output::error_json(&e));
+            } else {
+                eprintln!("{}", t!("cli.error", error = e));
+            }
+            process::exit(ExitStatus::ERROR_CODE);
         }
     }
 }
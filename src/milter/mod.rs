@@ -0,0 +1,363 @@
+use crate::config::MilterConfig;
+use crate::scanner::mail::{scan_message, MailDetection};
+use crate::scanner::SignatureDatabase;
+use anyhow::Context;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const MILTER_VERSION: u32 = 6;
+// 单个数据包上限，MTA发送的正文块通常不超过64KB
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+// 接受连接失败后重试前的等待时间
+const ACCEPT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+const SMFIC_ABORT: u8 = b'A';
+const SMFIC_BODY: u8 = b'B';
+const SMFIC_MACRO: u8 = b'D';
+const SMFIC_BODYEOB: u8 = b'E';
+const SMFIC_HEADER: u8 = b'L';
+const SMFIC_MAIL: u8 = b'M';
+const SMFIC_EOH: u8 = b'N';
+const SMFIC_OPTNEG: u8 = b'O';
+const SMFIC_QUIT: u8 = b'Q';
+const SMFIC_QUIT_NC: u8 = b'K';
+
+const SMFIR_ADDHEADER: u8 = b'h';
+const SMFIR_ACCEPT: u8 = b'a';
+const SMFIR_CONTINUE: u8 = b'c';
+const SMFIR_DISCARD: u8 = b'd';
+const SMFIR_QUARANTINE: u8 = b'q';
+const SMFIR_REPLYCODE: u8 = b'y';
+const SMFIR_TEMPFAIL: u8 = b't';
+
+const SMFIF_ADDHDRS: u32 = 0x01;
+const SMFIF_QUARANTINE: u32 = 0x20;
+
+const SMFIP_NOCONNECT: u32 = 0x01;
+const SMFIP_NOHELO: u32 = 0x02;
+const SMFIP_NORCPT: u32 = 0x08;
+const SMFIP_NOUNKNOWN: u32 = 0x100;
+const SMFIP_NODATA: u32 = 0x200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MilterVerdict {
+    Accept,
+    Reject,
+    Quarantine,
+    Discard,
+    Tempfail,
+}
+
+impl MilterVerdict {
+    pub fn parse(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "accept" => MilterVerdict::Accept,
+            "quarantine" => MilterVerdict::Quarantine,
+            "discard" => MilterVerdict::Discard,
+            "tempfail" => MilterVerdict::Tempfail,
+            _ => MilterVerdict::Reject,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MessageState {
+    sender: String,
+    raw: Vec<u8>,
+    oversized: bool,
+}
+
+pub struct MilterServer {
+    signature_db: Arc<SignatureDatabase>,
+    config: MilterConfig,
+    running: Arc<AtomicBool>,
+}
+
+impl MilterServer {
+    pub fn new(signature_db: Arc<SignatureDatabase>, config: MilterConfig) -> Self {
+        Self {
+            signature_db,
+            config,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // 监听地址格式: "unix:/path/to/socket"、"inet:host:port" 或 "host:port"
+    pub async fn run(&self) -> Result<(), anyhow::Error> {
+        self.running.store(true, Ordering::SeqCst);
+        let listen = self.config.listen.as_str();
+
+        #[cfg(unix)]
+        if let Some(path) = listen.strip_prefix("unix:") {
+            let _ = std::fs::remove_file(path);
+            let listener = tokio::net::UnixListener::bind(path)
+                .with_context(|| format!("无法监听milter套接字: {}", path))?;
+            log::info!("milter服务已启动: {}", listen);
+
+            while self.running.load(Ordering::Relaxed) {
+                match listener.accept().await {
+                    Ok((stream, _)) => self.spawn_session(stream),
+                    Err(e) => Self::accept_failed(e).await,
+                }
+            }
+            return Ok(());
+        }
+
+        let addr = listen.strip_prefix("inet:").unwrap_or(listen);
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("无法监听milter地址: {}", addr))?;
+        log::info!("milter服务已启动: {}", listen);
+
+        while self.running.load(Ordering::Relaxed) {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    log::debug!("milter连接: {}", peer);
+                    self.spawn_session(stream);
+                }
+                Err(e) => Self::accept_failed(e).await,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        log::info!("milter服务已停止");
+    }
+
+    // 文件描述符耗尽、连接在握手前被重置等错误是暂时的，不结束服务；稍等后再接受连接，避免空转
+    async fn accept_failed(e: std::io::Error) {
+        log::warn!("接受milter连接失败: {}", e);
+        tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+    }
+
+    fn spawn_session<S>(&self, stream: S)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let session = MilterSession::new(Arc::clone(&self.signature_db), self.config.clone());
+        tokio::spawn(async move {
+            if let Err(e) = session.handle(stream).await {
+                log::warn!("milter会话异常结束: {}", e);
+            }
+        });
+    }
+}
+
+pub struct MilterSession {
+    signature_db: Arc<SignatureDatabase>,
+    config: MilterConfig,
+    message: MessageState,
+    // 协商时MTA允许的修改操作，协商前不允许任何修改
+    actions: u32,
+}
+
+impl MilterSession {
+    pub fn new(signature_db: Arc<SignatureDatabase>, config: MilterConfig) -> Self {
+        Self {
+            signature_db,
+            config,
+            message: MessageState::default(),
+            actions: 0,
+        }
+    }
+
+    pub async fn handle<S>(mut self, mut stream: S) -> Result<(), anyhow::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        loop {
+            let (command, data) = match read_packet(&mut stream).await? {
+                Some(packet) => packet,
+                None => return Ok(()),
+            };
+
+            match command {
+                SMFIC_OPTNEG => {
+                    let reply = self.negotiate(&data)?;
+                    write_packet(&mut stream, SMFIC_OPTNEG, &reply).await?;
+                }
+                SMFIC_MACRO => {}
+                SMFIC_MAIL => {
+                    self.message = MessageState::default();
+                    self.message.sender = data
+                        .split(|&b| b == 0)
+                        .next()
+                        .map(|s| String::from_utf8_lossy(s).to_string())
+                        .unwrap_or_default();
+                    write_packet(&mut stream, SMFIR_CONTINUE, &[]).await?;
+                }
+                SMFIC_HEADER => {
+                    let mut fields = data.split(|&b| b == 0);
+                    let name = fields.next().unwrap_or_default();
+                    let value = fields.next().unwrap_or_default();
+                    self.append(&[name, b": ", value, b"\r\n"].concat());
+                    write_packet(&mut stream, SMFIR_CONTINUE, &[]).await?;
+                }
+                SMFIC_EOH => {
+                    self.append(b"\r\n");
+                    write_packet(&mut stream, SMFIR_CONTINUE, &[]).await?;
+                }
+                SMFIC_BODY => {
+                    self.append(&data);
+                    write_packet(&mut stream, SMFIR_CONTINUE, &[]).await?;
+                }
+                SMFIC_BODYEOB => {
+                    self.append(&data);
+                    self.finish_message(&mut stream).await?;
+                    self.message = MessageState::default();
+                }
+                SMFIC_ABORT | SMFIC_QUIT_NC => {
+                    self.message = MessageState::default();
+                }
+                SMFIC_QUIT => return Ok(()),
+                _ => {
+                    write_packet(&mut stream, SMFIR_CONTINUE, &[]).await?;
+                }
+            }
+        }
+    }
+
+    fn negotiate(&mut self, data: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        if data.len() < 12 {
+            return Err(anyhow::anyhow!("milter协商数据包无效"));
+        }
+        let version = u32::from_be_bytes(data[0..4].try_into()?);
+        let mta_actions = u32::from_be_bytes(data[4..8].try_into()?);
+        let mta_protocol = u32::from_be_bytes(data[8..12].try_into()?);
+
+        let actions = (SMFIF_ADDHDRS | SMFIF_QUARANTINE) & mta_actions;
+        self.actions = actions;
+        // 连接、HELO、收件人等阶段与病毒扫描无关，请求MTA跳过
+        let protocol = (SMFIP_NOCONNECT | SMFIP_NOHELO | SMFIP_NORCPT | SMFIP_NOUNKNOWN | SMFIP_NODATA) & mta_protocol;
+
+        let mut reply = Vec::with_capacity(12);
+        reply.extend_from_slice(&version.min(MILTER_VERSION).to_be_bytes());
+        reply.extend_from_slice(&actions.to_be_bytes());
+        reply.extend_from_slice(&protocol.to_be_bytes());
+        Ok(reply)
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        if self.message.oversized {
+            return;
+        }
+        let limit = self.config.max_message_size_mb.saturating_mul(1024 * 1024) as usize;
+        if self.message.raw.len() + data.len() > limit {
+            self.message.oversized = true;
+            self.message.raw.clear();
+            return;
+        }
+        self.message.raw.extend_from_slice(data);
+    }
+
+    async fn finish_message<S>(&mut self, stream: &mut S) -> Result<(), anyhow::Error>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        if self.message.oversized {
+            log::warn!("邮件超过大小限制，跳过扫描 (发件人: {})", self.message.sender);
+            return write_packet(stream, SMFIR_ACCEPT, &[]).await;
+        }
+
+        let detections = scan_message(&self.signature_db, &self.message.raw).await;
+        let add_header = self.config.add_header && self.actions & SMFIF_ADDHDRS != 0;
+
+        if detections.is_empty() {
+            if add_header {
+                write_packet(stream, SMFIR_ADDHEADER, &header_packet("X-Virus-Scanned", "clean")).await?;
+            }
+            return write_packet(stream, SMFIR_CONTINUE, &[]).await;
+        }
+
+        let names = Self::detection_names(&detections);
+        for detection in &detections {
+            log::warn!(
+                sender = self.message.sender.as_str(),
+                attachment = detection.filename.as_deref().unwrap_or(""),
+                signature = detection.threat.id.as_str();
+                "邮件中发现威胁: {}", detection.threat.name
+            );
+        }
+
+        let mut verdict = MilterVerdict::parse(&self.config.on_infected);
+        if verdict == MilterVerdict::Quarantine && self.actions & SMFIF_QUARANTINE == 0 {
+            log::warn!("MTA不允许隔离邮件，改为拒绝");
+            verdict = MilterVerdict::Reject;
+        }
+        match verdict {
+            MilterVerdict::Accept => {
+                if add_header {
+                    write_packet(stream, SMFIR_ADDHEADER, &header_packet("X-Virus-Found", &names)).await?;
+                }
+                write_packet(stream, SMFIR_CONTINUE, &[]).await
+            }
+            MilterVerdict::Quarantine => {
+                if add_header {
+                    write_packet(stream, SMFIR_ADDHEADER, &header_packet("X-Virus-Found", &names)).await?;
+                }
+                let reason = format!("virus detected: {}\0", names);
+                write_packet(stream, SMFIR_QUARANTINE, reason.as_bytes()).await?;
+                write_packet(stream, SMFIR_CONTINUE, &[]).await
+            }
+            MilterVerdict::Discard => write_packet(stream, SMFIR_DISCARD, &[]).await,
+            MilterVerdict::Tempfail => write_packet(stream, SMFIR_TEMPFAIL, &[]).await,
+            MilterVerdict::Reject => {
+                let reply = format!("554 5.7.1 {}: {}\0", self.config.reject_message, names);
+                write_packet(stream, SMFIR_REPLYCODE, reply.as_bytes()).await
+            }
+        }
+    }
+
+    fn detection_names(detections: &[MailDetection]) -> String {
+        let mut names: Vec<&str> = detections.iter().map(|d| d.threat.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        names.join(", ")
+    }
+}
+
+fn header_packet(name: &str, value: &str) -> Vec<u8> {
+    [name.as_bytes(), b"\0", value.as_bytes(), b"\0"].concat()
+}
+
+async fn read_packet<S>(stream: &mut S) -> Result<Option<(u8, Vec<u8>)>, anyhow::Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut len_buf = [0u8; 4];
+    match stream.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len == 0 || len > MAX_PACKET_SIZE {
+        return Err(anyhow::anyhow!("milter数据包长度无效: {}", len));
+    }
+
+    let mut packet = vec![0u8; len];
+    stream.read_exact(&mut packet).await?;
+    let command = packet[0];
+    packet.remove(0);
+    Ok(Some((command, packet)))
+}
+
+async fn write_packet<S>(stream: &mut S, command: u8, data: &[u8]) -> Result<(), anyhow::Error>
+where
+    S: AsyncWrite + Unpin,
+{
+    let len = (data.len() + 1) as u32;
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(&[command]).await?;
+    stream.write_all(data).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
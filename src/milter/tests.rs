@@ -0,0 +1,159 @@
+use crate::config::MilterConfig;
+use crate::milter::MilterSession;
+use crate::scanner::{PatternType, Signature, SignatureDatabase};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn send(stream: &mut DuplexStream, command: u8, data: &[u8]) {
+        stream.write_all(&((data.len() + 1) as u32).to_be_bytes()).await.unwrap();
+        stream.write_all(&[command]).await.unwrap();
+        stream.write_all(data).await.unwrap();
+    }
+
+    async fn recv(stream: &mut DuplexStream) -> (u8, Vec<u8>) {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).await.unwrap();
+        let mut packet = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut packet).await.unwrap();
+        (packet[0], packet[1..].to_vec())
+    }
+
+    // 与 SignatureDatabase 中的内容哈希保持一致
+    fn content_hash(data: &[u8]) -> String {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+
+    // 所有修改操作都允许时的MTA协商参数
+    const ALL_ACTIONS: u32 = 0x1ff;
+
+    async fn deliver(
+        db: Arc<SignatureDatabase>,
+        config: MilterConfig,
+        mta_actions: u32,
+        attachment: &[u8],
+    ) -> Vec<(u8, Vec<u8>)> {
+        let (mut mta, milter) = tokio::io::duplex(64 * 1024);
+        let session = tokio::spawn(MilterSession::new(db, config).handle(milter));
+
+        let mut optneg = Vec::new();
+        optneg.extend_from_slice(&6u32.to_be_bytes());
+        optneg.extend_from_slice(&mta_actions.to_be_bytes());
+        optneg.extend_from_slice(&0x1fffffu32.to_be_bytes());
+        send(&mut mta, b'O', &optneg).await;
+        let (command, reply) = recv(&mut mta).await;
+        assert_eq!(command, b'O');
+        assert_eq!(u32::from_be_bytes(reply[0..4].try_into().unwrap()), 6);
+
+        send(&mut mta, b'M', b"<sender@example.org>\0").await;
+        assert_eq!(recv(&mut mta).await.0, b'c');
+        send(&mut mta, b'L', b"Content-Type\0multipart/mixed; boundary=b1\0").await;
+        assert_eq!(recv(&mut mta).await.0, b'c');
+        send(&mut mta, b'N', b"").await;
+        assert_eq!(recv(&mut mta).await.0, b'c');
+
+        let body = [
+            b"--b1\r\nContent-Type: application/octet-stream\r\n\r\n".as_slice(),
+            attachment,
+            b"\r\n--b1--\r\n",
+        ]
+        .concat();
+        send(&mut mta, b'B', &body).await;
+        assert_eq!(recv(&mut mta).await.0, b'c');
+
+        send(&mut mta, b'E', b"").await;
+        let mut replies = Vec::new();
+        loop {
+            let reply = recv(&mut mta).await;
+            let done = reply.0 != b'h' && reply.0 != b'q';
+            replies.push(reply);
+            if done {
+                break;
+            }
+        }
+
+        send(&mut mta, b'Q', b"").await;
+        session.await.unwrap().unwrap();
+        replies
+    }
+
+    #[tokio::test]
+    async fn test_milter_accepts_clean_message() {
+        let db = Arc::new(SignatureDatabase::new());
+        let replies = deliver(db, MilterConfig::default(), ALL_ACTIONS, b"harmless").await;
+
+        assert_eq!(replies[0].0, b'h');
+        assert!(replies[0].1.starts_with(b"X-Virus-Scanned\0"));
+        assert_eq!(replies.last().unwrap().0, b'c');
+    }
+
+    #[tokio::test]
+    async fn test_milter_rejects_infected_attachment() {
+        let payload = b"infected-payload";
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![Signature {
+            id: content_hash(payload),
+            name: "Test.Mail.Virus".to_string(),
+            threat_type: "Virus".to_string(),
+            risk_level: "High".to_string(),
+            pattern: payload.to_vec(),
+            pattern_type: PatternType::Hash,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+        .unwrap();
+
+        let replies = deliver(Arc::clone(&db), MilterConfig::default(), ALL_ACTIONS, payload).await;
+        let (command, data) = replies.last().unwrap();
+        assert_eq!(*command, b'y');
+        assert!(String::from_utf8_lossy(data).starts_with("554 5.7.1"));
+        assert!(String::from_utf8_lossy(data).contains("Test.Mail.Virus"));
+
+        let config = MilterConfig {
+            on_infected: "quarantine".to_string(),
+            ..MilterConfig::default()
+        };
+        let replies = deliver(db, config, ALL_ACTIONS, payload).await;
+        assert!(replies.iter().any(|(command, _)| *command == b'q'));
+        assert_eq!(replies.last().unwrap().0, b'c');
+    }
+
+    #[tokio::test]
+    async fn test_milter_respects_negotiated_actions() {
+        let payload = b"infected-payload";
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![Signature {
+            id: content_hash(payload),
+            name: "Test.Mail.Virus".to_string(),
+            threat_type: "Virus".to_string(),
+            risk_level: "High".to_string(),
+            pattern: payload.to_vec(),
+            pattern_type: PatternType::Hash,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+        .unwrap();
+
+        // MTA未允许添加邮件头时不发送 SMFIR_ADDHEADER
+        let replies = deliver(Arc::clone(&db), MilterConfig::default(), 0, b"harmless").await;
+        assert_eq!(replies, vec![(b'c', Vec::new())]);
+
+        // 只允许添加邮件头时不隔离，改为拒绝
+        let config = MilterConfig {
+            on_infected: "quarantine".to_string(),
+            ..MilterConfig::default()
+        };
+        let replies = deliver(db, config, 0x01, payload).await;
+        assert!(!replies.iter().any(|(command, _)| *command == b'q'));
+        assert_eq!(replies.last().unwrap().0, b'y');
+    }
+}
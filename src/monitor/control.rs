@@ -0,0 +1,488 @@
+use crate::config::{MonitorConfig, MonitorDaemonConfig, ScannerConfig};
+use crate::monitor::stats::max_user_watches;
+use crate::monitor::{EventFilter, FileMonitor, MonitorEvent, MonitorStats, MonitorStatsSnapshot};
+use crate::utils::safe_canonicalize;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// 启动和停止后台进程时等待其就绪或退出的最长时间
+const DAEMON_WAIT: Duration = Duration::from_secs(10);
+// 事件流订阅者落后超过该数量的事件时丢弃最早的事件
+pub const EVENT_STREAM_CAPACITY: usize = 1024;
+
+// 控制套接字上每个连接发送一行 JSON 请求，收到一行 JSON 响应。
+// subscribe 例外：响应之后连接保持打开，每个通过过滤的监控事件发送一行 JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    Status,
+    Stop,
+    ListWatches,
+    AddWatch { path: PathBuf },
+    RemoveWatch { path: PathBuf },
+    Subscribe,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ControlResponse {
+    Status(MonitorStatus),
+    Stopping,
+    Watches { paths: Vec<PathBuf> },
+    Subscribed,
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct MonitorStatus {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub watch_paths: Vec<PathBuf>,
+    pub access_control: bool,
+    #[serde(default)]
+    pub stats: MonitorStatsSnapshot,
+    // 监控线程最近一次心跳，旧版本的监控进程不报告
+    #[serde(default)]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+}
+
+// 运行中的监控进程的状态，控制请求通过它查询和修改监控目录
+pub struct MonitorHandle {
+    monitor: Mutex<FileMonitor>,
+    config: MonitorConfig,
+    // 设置后运行时的监控目录修改会写回配置文件，重启后仍然生效
+    config_path: Option<PathBuf>,
+    started_at: DateTime<Utc>,
+    access_control: bool,
+    stats: Option<Arc<MonitorStats>>,
+    filter: Option<Arc<EventFilter>>,
+    events: Option<broadcast::Sender<MonitorEvent>>,
+}
+
+impl MonitorHandle {
+    pub fn new(monitor: FileMonitor, config: MonitorConfig, access_control: bool) -> Self {
+        Self {
+            monitor: Mutex::new(monitor),
+            config,
+            config_path: None,
+            started_at: Utc::now(),
+            access_control,
+            stats: None,
+            filter: None,
+            events: None,
+        }
+    }
+
+    pub fn persist_to(mut self, config_path: PathBuf) -> Self {
+        self.config_path = Some(config_path);
+        self
+    }
+
+    // 状态中的统计来自这两个对象
+    pub fn with_stats(mut self, stats: Arc<MonitorStats>, filter: Arc<EventFilter>) -> Self {
+        self.stats = Some(stats);
+        self.filter = Some(filter);
+        self
+    }
+
+    // 事件回调把通过过滤的事件发送到该通道，subscribe 请求从中订阅
+    pub fn with_event_stream(mut self, events: broadcast::Sender<MonitorEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<MonitorEvent>> {
+        self.events.as_ref().map(|events| events.subscribe())
+    }
+
+    pub fn status(&self) -> MonitorStatus {
+        let watch_paths = self.watched_paths();
+        let last_heartbeat = self.monitor.lock().unwrap().liveness().last_heartbeat();
+        let mut stats = self.stats.as_ref().map(|stats| stats.snapshot()).unwrap_or_default();
+        if let Some(ref filter) = self.filter {
+            let filter_stats = filter.stats();
+            stats.events_filtered = filter_stats.filtered;
+            stats.events_rate_limited = filter_stats.rate_limited;
+        }
+        stats.watches = watch_paths.len();
+        stats.max_user_watches = max_user_watches();
+
+        MonitorStatus {
+            pid: std::process::id(),
+            started_at: self.started_at,
+            watch_paths,
+            access_control: self.access_control,
+            stats,
+            last_heartbeat,
+        }
+    }
+
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.monitor.lock().unwrap().get_watched_paths();
+        paths.sort();
+        paths
+    }
+
+    pub fn add_watch(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = safe_canonicalize(path, &[])?;
+        if self.watched_paths().contains(&path) {
+            return Err(anyhow::anyhow!("已在监控: {:?}", path));
+        }
+        self.monitor.lock().unwrap().add_configured_watch(&path, &self.config)?;
+        self.persist(|watch_paths| {
+            if !watch_paths.iter().any(|p| same_path(p, &path)) {
+                watch_paths.push(path.display().to_string());
+            }
+        });
+        Ok(self.watched_paths())
+    }
+
+    pub fn remove_watch(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if !self.watched_paths().contains(&path) {
+            return Err(anyhow::anyhow!("未监控该路径: {:?}", path));
+        }
+        self.monitor.lock().unwrap().remove_watch(&path)?;
+        self.persist(|watch_paths| watch_paths.retain(|p| !same_path(p, &path)));
+        Ok(self.watched_paths())
+    }
+
+    pub fn stop(&self) {
+        self.monitor.lock().unwrap().stop();
+    }
+
+    // 监控已经生效，保存失败只记录警告
+    fn persist(&self, update: impl FnOnce(&mut Vec<String>)) {
+        let Some(ref config_path) = self.config_path else {
+            return;
+        };
+        if let Err(e) = update_watch_paths(config_path, update) {
+            log::warn!("监控目录已修改，但无法写入配置文件 {:?}: {:#}", config_path, e);
+        }
+    }
+}
+
+// 修改配置文件中的 monitor.watch_paths，监控未运行时 CLI 也直接使用
+pub fn update_watch_paths(config_path: &Path, update: impl FnOnce(&mut Vec<String>)) -> Result<()> {
+    let config_path = config_path.to_path_buf();
+    let mut config = ScannerConfig::load(&config_path)?;
+    update(&mut config.monitor.watch_paths);
+    config.save(&config_path)
+}
+
+// 配置中的路径可能未规范化，按规范化后的路径比较
+pub fn same_path(configured: &str, path: &Path) -> bool {
+    let configured = PathBuf::from(configured);
+    configured == path || std::fs::canonicalize(&configured).map(|c| c == path).unwrap_or(false)
+}
+
+// 运行中的监控进程持有 PID 文件和控制套接字，退出时删除
+pub struct ControlServer {
+    listener: UnixListener,
+    pid_file: PathBuf,
+    socket: PathBuf,
+}
+
+impl ControlServer {
+    // 已有监控进程在运行时返回错误；上次异常退出残留的 PID 文件和套接字会被清理
+    pub fn bind(config: &MonitorDaemonConfig) -> Result<Self> {
+        if let Some(pid) = running_pid(&config.pid_file) {
+            return Err(anyhow::anyhow!("监控已在运行 (PID {})", pid));
+        }
+        for path in [&config.pid_file, &config.control_socket] {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+            }
+        }
+
+        let _ = std::fs::remove_file(&config.control_socket);
+        let listener = UnixListener::bind(&config.control_socket)
+            .with_context(|| format!("无法监听控制套接字: {:?}", config.control_socket))?;
+        // 控制套接字可以停止监控，只允许属主访问
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&config.control_socket, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::write(&config.pid_file, format!("{}\n", std::process::id()))
+            .with_context(|| format!("无法写入PID文件: {:?}", config.pid_file))?;
+
+        Ok(Self {
+            listener,
+            pid_file: config.pid_file.clone(),
+            socket: config.control_socket.clone(),
+        })
+    }
+
+    // 处理请求直到收到 stop
+    pub async fn serve(&self, handle: Arc<MonitorHandle>) {
+        loop {
+            let stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    log::warn!("控制连接失败: {}", e);
+                    continue;
+                }
+            };
+            match handle_connection(stream, &handle).await {
+                Ok(true) => {
+                    log::info!("收到停止请求");
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => log::warn!("控制请求处理失败: {:#}", e),
+            }
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+        let _ = std::fs::remove_file(&self.pid_file);
+    }
+}
+
+// 返回是否为停止请求
+async fn handle_connection(stream: UnixStream, handle: &MonitorHandle) -> Result<bool> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    tokio::time::timeout(REQUEST_TIMEOUT, BufReader::new(reader).read_line(&mut line))
+        .await
+        .context("读取控制请求超时")??;
+
+    let watches = |result: Result<Vec<PathBuf>>| match result {
+        Ok(paths) => ControlResponse::Watches { paths },
+        Err(e) => ControlResponse::Error {
+            message: format!("{:#}", e),
+        },
+    };
+    let mut subscription = None;
+    let (response, stop) = match serde_json::from_str::<ControlRequest>(line.trim()) {
+        Ok(ControlRequest::Status) => (ControlResponse::Status(handle.status()), false),
+        Ok(ControlRequest::Stop) => (ControlResponse::Stopping, true),
+        Ok(ControlRequest::ListWatches) => (watches(Ok(handle.watched_paths())), false),
+        Ok(ControlRequest::AddWatch { path }) => (watches(handle.add_watch(&path)), false),
+        Ok(ControlRequest::RemoveWatch { path }) => (watches(handle.remove_watch(&path)), false),
+        Ok(ControlRequest::Subscribe) => match handle.subscribe() {
+            Some(events) => {
+                subscription = Some(events);
+                (ControlResponse::Subscribed, false)
+            }
+            None => (
+                ControlResponse::Error {
+                    message: "监控进程未提供事件流".to_string(),
+                },
+                false,
+            ),
+        },
+        Err(e) => (
+            ControlResponse::Error {
+                message: format!("无效的控制请求: {}", e),
+            },
+            false,
+        ),
+    };
+    let mut body = serde_json::to_vec(&response)?;
+    body.push(b'\n');
+    writer.write_all(&body).await?;
+    // 订阅连接交给单独的任务，不阻塞后续的控制请求
+    if let Some(events) = subscription {
+        tokio::spawn(stream_events(writer, events));
+        return Ok(false);
+    }
+    writer.shutdown().await?;
+    Ok(stop)
+}
+
+// 订阅者断开或监控停止时结束；订阅者处理过慢时跳过丢失的事件继续发送
+async fn stream_events(mut writer: OwnedWriteHalf, mut events: broadcast::Receiver<MonitorEvent>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("事件订阅者处理过慢，已跳过 {} 个事件", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Ok(mut line) = serde_json::to_vec(&event) else {
+            continue;
+        };
+        line.push(b'\n');
+        if writer.write_all(&line).await.is_err() {
+            return;
+        }
+    }
+}
+
+// 监控进程的事件流，由 subscribe 建立
+pub struct EventSubscription {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    // 写端关闭会让监控进程认为连接已结束，订阅期间保持打开
+    _writer: OwnedWriteHalf,
+}
+
+impl EventSubscription {
+    // 监控进程退出时返回 None
+    pub async fn next(&mut self) -> Result<Option<MonitorEvent>> {
+        while let Some(line) = self.lines.next_line().await.context("无法读取监控事件")? {
+            match serde_json::from_str(&line) {
+                Ok(event) => return Ok(Some(event)),
+                Err(e) => log::warn!("无法解析监控事件: {}", e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub async fn subscribe(socket: &Path) -> Result<EventSubscription> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("无法连接控制套接字: {:?}", socket))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut body = serde_json::to_vec(&ControlRequest::Subscribe)?;
+    body.push(b'\n');
+    writer.write_all(&body).await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let line = tokio::time::timeout(REQUEST_TIMEOUT, lines.next_line())
+        .await
+        .context("等待监控进程响应超时")??
+        .ok_or_else(|| anyhow::anyhow!("监控进程关闭了连接"))?;
+    match serde_json::from_str(line.trim()).context("无法解析监控进程的响应")? {
+        ControlResponse::Subscribed => Ok(EventSubscription { lines, _writer: writer }),
+        ControlResponse::Error { message } => Err(anyhow::anyhow!(message)),
+        other => Err(anyhow::anyhow!("监控进程返回了意外的响应: {:?}", other)),
+    }
+}
+
+pub async fn send_request(socket: &Path, request: &ControlRequest) -> Result<ControlResponse> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("无法连接控制套接字: {:?}", socket))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut body = serde_json::to_vec(request)?;
+    body.push(b'\n');
+    writer.write_all(&body).await?;
+
+    let mut line = String::new();
+    tokio::time::timeout(REQUEST_TIMEOUT, BufReader::new(reader).read_line(&mut line))
+        .await
+        .context("等待监控进程响应超时")??;
+    serde_json::from_str(line.trim()).context("无法解析监控进程的响应")
+}
+
+// PID 文件中的进程仍然存在时返回其 PID
+pub fn running_pid(pid_file: &Path) -> Option<u32> {
+    let pid: i32 = std::fs::read_to_string(pid_file).ok()?.trim().parse().ok()?;
+    if pid <= 0 {
+        return None;
+    }
+    let alive = unsafe { libc::kill(pid, 0) } == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    alive.then_some(pid as u32)
+}
+
+// 在 tokio 运行时中 fork 不安全，因此以 --foreground 重新执行当前命令，并用 setsid 脱离终端。
+// 等到子进程写入 PID 文件后返回其 PID；子进程提前退出时错误信息指向输出日志
+pub fn spawn_daemon(config: &MonitorDaemonConfig, output_log: &Path) -> Result<u32> {
+    if let Some(pid) = running_pid(&config.pid_file) {
+        return Err(anyhow::anyhow!("监控已在运行 (PID {})", pid));
+    }
+    if let Some(parent) = output_log.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+    let output = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output_log)
+        .with_context(|| format!("无法打开监控输出日志: {:?}", output_log))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe().context("无法确定程序路径")?);
+    command
+        .args(std::env::args_os().skip(1))
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(output.try_clone()?)
+        .stderr(output);
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().context("无法启动后台监控进程")?;
+
+    let deadline = Instant::now() + DAEMON_WAIT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!("后台监控进程启动失败 ({})，详见 {:?}", status, output_log));
+        }
+        if running_pid(&config.pid_file) == Some(child.id()) && config.control_socket.exists() {
+            return Ok(child.id());
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("等待后台监控进程就绪超时，详见 {:?}", output_log));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// 请求运行中的监控进程停止并等待其退出，返回其 PID；没有运行中的监控时返回 None
+pub async fn stop_daemon(config: &MonitorDaemonConfig) -> Result<Option<u32>> {
+    let Some(pid) = running_pid(&config.pid_file) else {
+        return Ok(None);
+    };
+    match send_request(&config.control_socket, &ControlRequest::Stop).await? {
+        ControlResponse::Stopping => {}
+        ControlResponse::Error { message } => return Err(anyhow::anyhow!("监控进程拒绝停止: {}", message)),
+        other => return Err(anyhow::anyhow!("监控进程返回了意外的响应: {:?}", other)),
+    }
+
+    let deadline = Instant::now() + DAEMON_WAIT;
+    while running_pid(&config.pid_file) == Some(pid) {
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("监控进程 (PID {}) 未在 {:?} 内退出", pid, DAEMON_WAIT));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(Some(pid))
+}
+
+// 查询运行中的监控进程状态；没有运行中的监控时返回 None
+pub async fn query_status(config: &MonitorDaemonConfig) -> Result<Option<MonitorStatus>> {
+    if running_pid(&config.pid_file).is_none() {
+        return Ok(None);
+    }
+    match send_request(&config.control_socket, &ControlRequest::Status).await? {
+        ControlResponse::Status(status) => Ok(Some(status)),
+        ControlResponse::Error { message } => Err(anyhow::anyhow!("查询监控状态失败: {}", message)),
+        other => Err(anyhow::anyhow!("监控进程返回了意外的响应: {:?}", other)),
+    }
+}
+
+// 向运行中的监控进程发送监控目录请求，返回修改后的监控目录
+pub async fn request_watches(config: &MonitorDaemonConfig, request: &ControlRequest) -> Result<Vec<PathBuf>> {
+    if running_pid(&config.pid_file).is_none() {
+        return Err(anyhow::anyhow!("文件监控未在运行"));
+    }
+    match send_request(&config.control_socket, request).await? {
+        ControlResponse::Watches { paths } => Ok(paths),
+        ControlResponse::Error { message } => Err(anyhow::anyhow!(message)),
+        other => Err(anyhow::anyhow!("监控进程返回了意外的响应: {:?}", other)),
+    }
+}
@@ -0,0 +1,472 @@
+use crate::config::{AccessControlConfig, AccessControlPath, ScannerConfig};
+use crate::monitor::process::process_info;
+use crate::scanner::{SignatureDatabase, SignatureSnapshot};
+use crate::utils::logging::AuditLogger;
+use crate::utils::{detect_file_type_from_bytes, MappedFile};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+const EVENT_BUFFER_SIZE: usize = 8192;
+const POLL_TIMEOUT_MS: i32 = 500;
+// 超过后整体清空，文件变化后 (ctime 或大小不同) 缓存自然失效
+const MAX_CACHED_VERDICTS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessDecision {
+    Allow,
+    Deny(String),
+}
+
+// 同一文件未变化时复用上次的扫描结论
+type VerdictKey = (u64, u64);
+type Verdict = (i64, u64, AccessDecision);
+
+struct GuardState {
+    signature_db: Arc<SignatureDatabase>,
+    max_file_size: u64,
+    deny_on_error: bool,
+    // 结论只对得出它的病毒库快照有效，病毒库更新后清空
+    verdicts: Mutex<(Option<Arc<SignatureSnapshot>>, HashMap<VerdictKey, Verdict>)>,
+    audit_logger: Option<Arc<AuditLogger>>,
+}
+
+struct PermissionEvent {
+    fd: OwnedFd,
+    pid: i32,
+}
+
+// fanotify 权限事件：内核在收到允许/拒绝响应之前挂起打开或执行文件的进程。
+// 事件自带一个不会再次触发事件的文件描述符，扫描直接读取该描述符
+pub struct AccessGuard {
+    config: AccessControlConfig,
+    state: Arc<GuardState>,
+    running: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+    fanotify: Option<Arc<OwnedFd>>,
+}
+
+impl AccessGuard {
+    pub fn new(signature_db: Arc<SignatureDatabase>, config: AccessControlConfig, max_file_size: u64) -> Self {
+        let deny_on_error = config.deny_on_error;
+        Self {
+            config,
+            state: Arc::new(GuardState {
+                signature_db,
+                max_file_size,
+                deny_on_error,
+                verdicts: Mutex::new((None, HashMap::new())),
+                audit_logger: None,
+            }),
+            running: Arc::new(AtomicBool::new(false)),
+            threads: Vec::new(),
+            fanotify: None,
+        }
+    }
+
+    // 拒绝访问时写入审计日志，需在 start 之前调用
+    pub fn set_audit_logger(&mut self, audit_logger: Arc<AuditLogger>) {
+        if let Some(state) = Arc::get_mut(&mut self.state) {
+            state.audit_logger = Some(audit_logger);
+        }
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        if self.running.load(Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("访问拦截已在运行中"));
+        }
+
+        let fanotify = init_fanotify(libc::FAN_CLASS_CONTENT)?;
+
+        let mut marked = 0;
+        for rule in &self.config.paths {
+            match mark_path(fanotify.as_raw_fd(), rule) {
+                Ok(true) => {
+                    log::info!("已启用访问拦截: {} (执行: {}, 打开: {})", rule.path, rule.block_exec, rule.block_open);
+                    marked += 1;
+                }
+                Ok(false) => {}
+                Err(e) => log::warn!("{:#}", e),
+            }
+        }
+        if marked == 0 {
+            return Err(anyhow::anyhow!("没有可拦截的路径"));
+        }
+
+        self.running.store(true, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel::<PermissionEvent>();
+        let rx = Arc::new(Mutex::new(rx));
+        let fanotify = Arc::new(fanotify);
+
+        for _ in 0..self.config.workers.max(1) {
+            let rx = Arc::clone(&rx);
+            let state = Arc::clone(&self.state);
+            let fanotify = Arc::clone(&fanotify);
+            let running = Arc::clone(&self.running);
+            self.threads.push(thread::spawn(move || loop {
+                let event = match rx.lock().unwrap().recv() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                };
+                // 停止后队列中剩余的事件直接放行，不再扫描
+                let decision = if running.load(Ordering::Relaxed) {
+                    state.check_event(&event)
+                } else {
+                    AccessDecision::Allow
+                };
+                respond(fanotify.as_raw_fd(), event.fd.as_raw_fd(), &decision);
+            }));
+        }
+
+        self.fanotify = Some(Arc::clone(&fanotify));
+        let running = Arc::clone(&self.running);
+        // 事件路径是内核解析后的绝对路径，规则路径也需要解析符号链接
+        let rules: Vec<AccessControlPath> = self
+            .config
+            .paths
+            .iter()
+            .map(|rule| AccessControlPath {
+                path: std::fs::canonicalize(&rule.path)
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| rule.path.clone()),
+                ..rule.clone()
+            })
+            .collect();
+        self.threads.push(thread::spawn(move || {
+            log::info!("访问拦截线程已启动");
+            read_events(fanotify.as_raw_fd(), &running, &rules, &tx);
+            log::info!("访问拦截线程已停止");
+        }));
+
+        log::info!("访问拦截已启动");
+        Ok(())
+    }
+
+    // 读取线程退出后工作线程对已读取的事件回复 FAN_ALLOW，线程结束后关闭 fanotify 描述符，
+    // 内核放行尚未读取的事件
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        for handle in self.threads.drain(..) {
+            let _ = handle.join();
+        }
+        // 线程持有的引用已释放，这里是最后一个引用
+        self.fanotify = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    // 扫描已打开的文件并给出结论，不经过 fanotify 也可直接调用
+    pub fn check_file(&self, file: &File, path: &Path) -> AccessDecision {
+        self.state.check_file(file, path)
+    }
+}
+
+// 按配置启动访问拦截，未启用时返回 None
+pub fn start_access_guard(signature_db: Arc<SignatureDatabase>, config: &ScannerConfig) -> Result<Option<AccessGuard>> {
+    if !config.monitor.access_control.enabled {
+        return Ok(None);
+    }
+    let mut guard = AccessGuard::new(
+        signature_db,
+        config.monitor.access_control.clone(),
+        config.scan_modes.max_file_size,
+    );
+    guard.set_audit_logger(Arc::new(AuditLogger::new(
+        config.logging.log_dir.clone(),
+        config.security.audit_log_enabled,
+    )));
+    guard.start()?;
+    Ok(Some(guard))
+}
+
+impl Drop for AccessGuard {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl GuardState {
+    fn check_event(&self, event: &PermissionEvent) -> AccessDecision {
+        let path = fd_path(event.fd.as_raw_fd());
+        let file = match event.fd.try_clone() {
+            Ok(fd) => File::from(fd),
+            Err(e) => return self.on_error(&path, &e.into()),
+        };
+        let decision = self.check_file(&file, &path);
+        if let AccessDecision::Deny(ref reason) = decision {
+            let process = match process_info(event.pid as u32) {
+                Some(info) => format!("pid={} uid={} user={} command={:?}", info.pid, info.user_id, info.user_name, info.command),
+                None => format!("pid={}", event.pid),
+            };
+            log::warn!("已拒绝访问 {:?} ({}): {}", path, process, reason);
+            if let Some(audit_logger) = &self.audit_logger {
+                audit_logger.log(
+                    "ACCESS_DENIED",
+                    "monitor",
+                    &format!("file={:?} {} reason={}", path, process, reason),
+                );
+            }
+        }
+        decision
+    }
+
+    fn check_file(&self, file: &File, path: &Path) -> AccessDecision {
+        let metadata = match file.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => return self.on_error(path, &e.into()),
+        };
+        if !metadata.is_file() {
+            return AccessDecision::Allow;
+        }
+
+        let snapshot = self.signature_db.snapshot();
+        let key = (metadata.dev(), metadata.ino());
+        // mtime 可以被 utimensat 改回原值，ctime 只能由内核更新
+        let stamp = (metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec(), metadata.size());
+        {
+            let mut verdicts = self.verdicts.lock().unwrap();
+            if !verdicts.0.as_ref().is_some_and(|cached| Arc::ptr_eq(cached, &snapshot)) {
+                *verdicts = (Some(Arc::clone(&snapshot)), HashMap::new());
+            }
+            if let Some((ctime, size, decision)) = verdicts.1.get(&key) {
+                if (*ctime, *size) == stamp {
+                    return decision.clone();
+                }
+            }
+        }
+
+        if self.max_file_size > 0 && metadata.size() > self.max_file_size {
+            log::debug!("文件超过扫描大小限制，跳过访问检查: {:?}", path);
+            return self.on_error(path, &anyhow::anyhow!("文件超过扫描大小限制"));
+        }
+
+        // 事件描述符由内核单独为本进程打开，读取不影响被拦截进程的文件偏移
+        let threat = match MappedFile::map(file) {
+            Ok(mapped) => snapshot.scan_bytes(&mapped, detect_file_type_from_bytes(&mapped)),
+            Err(_) => {
+                let mut data = Vec::new();
+                if let Err(e) = (&*file).read_to_end(&mut data) {
+                    return self.on_error(path, &e.into());
+                }
+                snapshot.scan_bytes(&data, detect_file_type_from_bytes(&data))
+            }
+        };
+        let decision = match threat {
+            Some(threat) => AccessDecision::Deny(threat.id),
+            None => AccessDecision::Allow,
+        };
+
+        let mut verdicts = self.verdicts.lock().unwrap();
+        if verdicts.0.as_ref().is_some_and(|cached| Arc::ptr_eq(cached, &snapshot)) {
+            if verdicts.1.len() >= MAX_CACHED_VERDICTS {
+                verdicts.1.clear();
+            }
+            verdicts.1.insert(key, (stamp.0, stamp.1, decision.clone()));
+        }
+        decision
+    }
+
+    fn on_error(&self, path: &Path, error: &anyhow::Error) -> AccessDecision {
+        log::warn!("访问检查失败 {:?}: {:#}", path, error);
+        if self.deny_on_error {
+            AccessDecision::Deny(format!("{:#}", error))
+        } else {
+            AccessDecision::Allow
+        }
+    }
+}
+
+// 目录标记只覆盖直接子文件，因此标记目录所在的挂载点以及目录下的其他挂载点，
+// 事件到达后再按规则路径过滤，之后新建的子目录同样受拦截。目录本身不存在时跳过
+fn mark_path(fanotify: RawFd, rule: &AccessControlPath) -> Result<bool> {
+    let mask = permission_mask(rule);
+    if mask == 0 {
+        return Ok(false);
+    }
+    let path = PathBuf::from(&rule.path);
+    if !path.exists() {
+        log::warn!("访问拦截路径不存在，已跳过: {:?}", path);
+        return Ok(false);
+    }
+    mark_mount(fanotify, &path, mask).with_context(|| format!("无法拦截路径: {:?}", path))?;
+    for mount_point in mount_points_under(&path) {
+        if let Err(e) = mark_mount(fanotify, &mount_point, mask) {
+            log::warn!("无法拦截挂载点 {:?}: {}", mount_point, e);
+        }
+    }
+    Ok(true)
+}
+
+// /proc/self/mounts 中位于 path 之下的挂载点，路径里的空白字符按八进制转义
+fn mount_points_under(path: &Path) -> Vec<PathBuf> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .map(|field| PathBuf::from(field.replace("\\040", " ").replace("\\011", "\t")))
+        .filter(|mount_point| mount_point != path && mount_point.starts_with(path))
+        .collect()
+}
+
+// 事件文件路径命中的规则所拦截的事件类型
+pub(crate) fn rules_mask(rules: &[AccessControlPath], path: &Path) -> u64 {
+    rules
+        .iter()
+        .filter(|rule| path.starts_with(&rule.path))
+        .fold(0, |mask, rule| mask | permission_mask(rule))
+}
+
+fn mark_mount(fanotify: RawFd, path: &Path, mask: u64) -> std::io::Result<()> {
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe {
+        libc::fanotify_mark(
+            fanotify,
+            libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT,
+            mask,
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn init_fanotify(class: libc::c_uint) -> Result<OwnedFd> {
+    let flags = libc::FAN_CLOEXEC | class | libc::FAN_UNLIMITED_QUEUE;
+    let fd = unsafe { libc::fanotify_init(flags, (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as u32) };
+    if fd < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EPERM) {
+            return Err(anyhow::anyhow!("无法初始化fanotify: 需要 root 权限 (CAP_SYS_ADMIN)"));
+        }
+        return Err(anyhow::anyhow!("无法初始化fanotify: {}", err));
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+// 标记目录及其直接子文件
+pub(crate) fn mark_directory(fanotify: RawFd, path: &Path, mask: u64) -> std::io::Result<()> {
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let ret = unsafe {
+        libc::fanotify_mark(
+            fanotify,
+            libc::FAN_MARK_ADD,
+            mask | libc::FAN_EVENT_ON_CHILD,
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub fn permission_mask(rule: &AccessControlPath) -> u64 {
+    let mut mask = 0;
+    if rule.block_exec {
+        mask |= libc::FAN_OPEN_EXEC_PERM;
+    }
+    if rule.block_open {
+        mask |= libc::FAN_OPEN_PERM;
+    }
+    mask
+}
+
+fn read_events(fanotify: RawFd, running: &AtomicBool, rules: &[AccessControlPath], tx: &mpsc::Sender<PermissionEvent>) {
+    let own_pid = std::process::id() as i32;
+    read_fanotify_events(fanotify, running, |fd, mask, pid| {
+        let is_permission = mask & (libc::FAN_OPEN_PERM | libc::FAN_OPEN_EXEC_PERM | libc::FAN_ACCESS_PERM) != 0;
+        if !is_permission {
+            return;
+        }
+        // 本进程的扫描也会打开被拦截的文件，直接放行以免互相等待
+        if pid == own_pid {
+            respond(fanotify, fd.as_raw_fd(), &AccessDecision::Allow);
+            return;
+        }
+        // 挂载点标记会收到规则路径以外的事件
+        if rules_mask(rules, &fd_path(fd.as_raw_fd())) & mask == 0 {
+            respond(fanotify, fd.as_raw_fd(), &AccessDecision::Allow);
+            return;
+        }
+        let event = PermissionEvent { fd, pid };
+        if let Err(mpsc::SendError(event)) = tx.send(event) {
+            respond(fanotify, event.fd.as_raw_fd(), &AccessDecision::Allow);
+        }
+    });
+}
+
+// 逐个处理事件直到 running 被清除，handler 收到事件描述符、事件掩码和进程号
+pub(crate) fn read_fanotify_events(fanotify: RawFd, running: &AtomicBool, mut handler: impl FnMut(OwnedFd, u64, i32)) {
+    let mut buffer = vec![0u8; EVENT_BUFFER_SIZE];
+    let metadata_size = std::mem::size_of::<libc::fanotify_event_metadata>();
+
+    while running.load(Ordering::Relaxed) {
+        let mut poll_fd = libc::pollfd {
+            fd: fanotify,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, POLL_TIMEOUT_MS) };
+        if ready <= 0 {
+            continue;
+        }
+
+        let len = unsafe { libc::read(fanotify, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+        if len < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted && err.kind() != std::io::ErrorKind::WouldBlock {
+                log::error!("读取fanotify事件失败: {}", err);
+            }
+            continue;
+        }
+
+        let len = len as usize;
+        let mut offset = 0;
+        while offset + metadata_size <= len {
+            let metadata: libc::fanotify_event_metadata =
+                unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const _) };
+            let event_len = metadata.event_len as usize;
+            if metadata.vers != libc::FANOTIFY_METADATA_VERSION || event_len < metadata_size {
+                log::error!("不支持的fanotify事件格式");
+                break;
+            }
+            offset += event_len;
+            if metadata.fd >= 0 {
+                handler(unsafe { OwnedFd::from_raw_fd(metadata.fd) }, metadata.mask, metadata.pid);
+            }
+        }
+    }
+}
+
+fn respond(fanotify: RawFd, fd: RawFd, decision: &AccessDecision) {
+    let response = libc::fanotify_response {
+        fd,
+        response: match decision {
+            AccessDecision::Allow => libc::FAN_ALLOW,
+            AccessDecision::Deny(_) => libc::FAN_DENY,
+        },
+    };
+    let size = std::mem::size_of::<libc::fanotify_response>();
+    let written = unsafe { libc::write(fanotify, &response as *const _ as *const libc::c_void, size) };
+    if written != size as isize {
+        log::error!("无法响应fanotify事件: {}", std::io::Error::last_os_error());
+    }
+}
+
+pub(crate) fn fd_path(fd: RawFd) -> PathBuf {
+    std::fs::read_link(format!("/proc/self/fd/{}", fd)).unwrap_or_else(|_| PathBuf::from(format!("fd:{}", fd)))
+}
@@ -0,0 +1,184 @@
+use crate::config::{MonitorFilterConfig, WatchFilterRule};
+use crate::monitor::{EventType, MonitorEvent};
+use crate::utils::{GlobMatcher, GlobOptions, KeyedRateLimiter};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 去重表超过该大小时清理过期条目
+const DEDUP_CLEANUP_THRESHOLD: usize = 4096;
+
+struct CompiledRule {
+    path: PathBuf,
+    extensions: HashSet<String>,
+    exclude: GlobMatcher,
+    events: Vec<EventType>,
+}
+
+// 按扩展名和路径规则、监控目录规则和每个目录的速率限制过滤监控事件。
+// 重复事件由 is_duplicate 单独判断：实时扫描依赖最后一次写入事件来推迟扫描，不能丢弃重复事件，
+// 扫描队列中的重复由 OnAccessScanner 合并
+pub struct EventFilter {
+    exclude_extensions: HashSet<String>,
+    exclude: GlobMatcher,
+    rules: Vec<CompiledRule>,
+    dedup_window: Duration,
+    recent: Mutex<HashMap<(PathBuf, EventType), Instant>>,
+    limiter: Option<KeyedRateLimiter<PathBuf>>,
+    filtered: AtomicU64,
+    deduplicated: AtomicU64,
+    rate_limited: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FilterStats {
+    pub filtered: u64,
+    pub deduplicated: u64,
+    pub rate_limited: u64,
+}
+
+impl EventFilter {
+    pub fn new(config: &MonitorFilterConfig) -> Result<Self, anyhow::Error> {
+        let mut rules = config
+            .rules
+            .iter()
+            .map(Self::compile_rule)
+            .collect::<Result<Vec<_>, _>>()?;
+        // 最长的路径优先匹配
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.path.as_os_str().len()));
+
+        let limiter = (config.max_events_per_sec > 0).then(|| {
+            KeyedRateLimiter::new(config.burst.max(config.max_events_per_sec), config.max_events_per_sec as f64)
+        });
+
+        Ok(Self {
+            exclude_extensions: normalize_extensions(&config.exclude_extensions),
+            exclude: GlobMatcher::new(&config.exclude_patterns, GlobOptions::default())?,
+            rules,
+            dedup_window: Duration::from_millis(config.dedup_window_ms),
+            recent: Mutex::new(HashMap::new()),
+            limiter,
+            filtered: AtomicU64::new(0),
+            deduplicated: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+        })
+    }
+
+    fn compile_rule(rule: &WatchFilterRule) -> Result<CompiledRule, anyhow::Error> {
+        // 事件中的监控目录已规范化，规则路径也需要规范化才能匹配
+        let path = PathBuf::from(&rule.path);
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        Ok(CompiledRule {
+            path,
+            extensions: normalize_extensions(&rule.extensions),
+            exclude: GlobMatcher::new(&rule.exclude_patterns, GlobOptions::default())?,
+            events: event_types(&rule.events),
+        })
+    }
+
+    // 返回事件是否应继续处理
+    pub fn allow(&self, event: &MonitorEvent) -> bool {
+        if !self.matches(event) {
+            self.filtered.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if let Some(ref limiter) = self.limiter {
+            if !limiter.try_acquire(&event.watch_path, 1) {
+                // 每丢弃 1000 个事件记录一次，避免日志本身被刷屏
+                if self.rate_limited.fetch_add(1, Ordering::Relaxed) % 1000 == 0 {
+                    log::warn!("监控目录 {:?} 事件过多，超出速率限制的事件已丢弃", event.watch_path);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn matches(&self, event: &MonitorEvent) -> bool {
+        let extension = extension_of(&event.file_path);
+        if let Some(ref extension) = extension {
+            if self.exclude_extensions.contains(extension) {
+                return false;
+            }
+        }
+        if self.exclude.is_match(&event.file_path) {
+            return false;
+        }
+
+        let Some(rule) = self.rules.iter().find(|rule| event.file_path.starts_with(&rule.path)) else {
+            return true;
+        };
+        if !rule.events.is_empty() && !rule.events.contains(&event.event_type) {
+            return false;
+        }
+        if !rule.extensions.is_empty() && !extension.map(|e| rule.extensions.contains(&e)).unwrap_or(false) {
+            return false;
+        }
+        !rule.exclude.is_match(&event.file_path)
+    }
+
+    // 同一文件的相同事件在去重窗口内再次出现时返回 true，用于事件日志等只需记录一次的场合
+    pub fn is_duplicate(&self, event: &MonitorEvent) -> bool {
+        if self.dedup_window.is_zero() {
+            return false;
+        }
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() >= DEDUP_CLEANUP_THRESHOLD {
+            recent.retain(|_, seen| now.duration_since(*seen) < self.dedup_window);
+            if let Some(ref limiter) = self.limiter {
+                limiter.cleanup();
+            }
+        }
+
+        let key = (event.file_path.clone(), event.event_type.clone());
+        match recent.get(&key) {
+            Some(seen) if now.duration_since(*seen) < self.dedup_window => {
+                self.deduplicated.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            _ => {
+                recent.insert(key, now);
+                false
+            }
+        }
+    }
+
+    pub fn stats(&self) -> FilterStats {
+        FilterStats {
+            filtered: self.filtered.load(Ordering::Relaxed),
+            deduplicated: self.deduplicated.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn normalize_extensions(extensions: &[String]) -> HashSet<String> {
+    extensions
+        .iter()
+        .map(|e| e.trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+fn extension_of(path: &Path) -> Option<String> {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+}
+
+// 配置中的事件名转换为事件类型，move 同时包括移入和移出
+fn event_types(events: &[String]) -> Vec<EventType> {
+    let mut types = Vec::new();
+    for event in events {
+        match event.to_lowercase().as_str() {
+            "create" => types.push(EventType::Created),
+            "modify" => types.push(EventType::Modified),
+            "delete" => types.push(EventType::Deleted),
+            "move" => types.extend([EventType::MovedFrom, EventType::MovedTo]),
+            "access" => types.push(EventType::Accessed),
+            other => log::warn!("未知的监控事件: {}", other),
+        }
+    }
+    types
+}
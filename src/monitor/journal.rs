@@ -0,0 +1,122 @@
+use crate::config::MonitorJournalConfig;
+use crate::monitor::MonitorEvent;
+use crate::utils::logging::{RotatingFileWriter, RotationPolicy};
+use anyhow::Context;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// 追加写入的监控事件日志，每个事件一行JSON，按大小轮转
+pub struct EventJournal {
+    writer: Mutex<RotatingFileWriter>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    // 只返回该时间戳 (Unix 秒) 之后的事件
+    pub since: Option<u64>,
+    // 只返回该路径或其下的文件的事件
+    pub path: Option<PathBuf>,
+    // 最多返回最近的多少条
+    pub limit: Option<usize>,
+}
+
+impl EventQuery {
+    fn matches(&self, event: &MonitorEvent) -> bool {
+        if let Some(since) = self.since {
+            if event.timestamp < since {
+                return false;
+            }
+        }
+        match self.path {
+            Some(ref path) => event.file_path.starts_with(path),
+            None => true,
+        }
+    }
+}
+
+impl EventJournal {
+    pub fn new(log_dir: PathBuf, config: &MonitorJournalConfig) -> Result<Self, anyhow::Error> {
+        let policy = RotationPolicy {
+            max_size_bytes: config.max_size_mb.max(1) * 1024 * 1024,
+            max_files: config.max_files,
+            daily: false,
+            compress: config.compress_rotated,
+        };
+        let writer = RotatingFileWriter::new(log_dir, &config.file_name, policy)?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub fn record(&self, event: &MonitorEvent) -> Result<(), anyhow::Error> {
+        let mut line = serde_json::to_string(event)?;
+        line.push('\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(line.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // 按时间顺序读取已轮转的归档和当前日志，返回匹配的事件。无法解析的行 (如写入中断) 被跳过
+    pub fn query(log_dir: &Path, config: &MonitorJournalConfig, query: &EventQuery) -> Result<Vec<MonitorEvent>, anyhow::Error> {
+        let mut files = Self::archives(log_dir, &config.file_name);
+        let active = log_dir.join(&config.file_name);
+        if active.exists() {
+            files.push(active);
+        }
+
+        let mut events = Vec::new();
+        for file in files {
+            let reader = Self::open(&file)?;
+            for line in reader.lines() {
+                let line = line.with_context(|| format!("无法读取事件日志: {:?}", file))?;
+                let Ok(event) = serde_json::from_str::<MonitorEvent>(&line) else {
+                    continue;
+                };
+                if query.matches(&event) {
+                    events.push(event);
+                }
+            }
+        }
+
+        if let Some(limit) = query.limit {
+            if events.len() > limit {
+                events.drain(..events.len() - limit);
+            }
+        }
+        Ok(events)
+    }
+
+    // 归档文件名为 <file_name>.<时间戳>[.gz]，按文件名排序即为时间顺序
+    fn archives(log_dir: &Path, file_name: &str) -> Vec<PathBuf> {
+        let prefix = format!("{}.", file_name);
+        let mut archives: Vec<PathBuf> = match std::fs::read_dir(log_dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|n| n.starts_with(&prefix))
+                        .unwrap_or(false)
+                })
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        archives.sort();
+        archives
+    }
+
+    fn open(path: &Path) -> Result<Box<dyn BufRead>, anyhow::Error> {
+        let file = std::fs::File::open(path).with_context(|| format!("无法打开事件日志: {:?}", path))?;
+        let reader: Box<dyn Read> = if path.extension().map(|e| e == "gz").unwrap_or(false) {
+            Box::new(flate2::read::GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Ok(Box::new(BufReader::new(reader)))
+    }
+}
@@ -1,9 +1,22 @@
+#[cfg(target_os = "linux")]
+pub mod fanotify;
+pub mod control;
+pub mod filter;
+pub mod journal;
+pub mod on_access;
+pub mod poll;
+pub mod process;
+pub mod stats;
+
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use crate::config::MonitorConfig;
+use crate::utils::safe_canonicalize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MonitorEvent {
     pub watch_path: PathBuf,
     pub event_type: EventType,
@@ -13,7 +26,8 @@ pub struct MonitorEvent {
     pub process_info: Option<ProcessInfo>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum EventType {
     Created,
     Modified,
@@ -23,7 +37,7 @@ pub enum EventType {
     Accessed,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessInfo {
     pub pid: u32,
     pub command: String,
@@ -31,80 +45,149 @@ pub struct ProcessInfo {
     pub user_name: String,
 }
 
+// 监控线程每轮循环更新心跳，线程退出或卡住后心跳停止更新
+#[derive(Clone)]
+pub struct MonitorLiveness {
+    running: Arc<AtomicBool>,
+    // Unix 时间戳 (秒)，0 表示还没有心跳
+    heartbeat: Arc<AtomicU64>,
+}
+
+impl MonitorLiveness {
+    fn new(running: Arc<AtomicBool>) -> Self {
+        Self {
+            running,
+            heartbeat: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn beat(&self) {
+        self.heartbeat.store(chrono::Utc::now().timestamp().max(0) as u64, Ordering::Relaxed);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    // 监控未运行时为 None
+    pub fn last_heartbeat(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        if !self.is_running() {
+            return None;
+        }
+        match self.heartbeat.load(Ordering::Relaxed) {
+            0 => None,
+            secs => chrono::DateTime::from_timestamp(secs as i64, 0),
+        }
+    }
+}
+
 #[cfg(target_os = "linux")]
 mod linux_monitor {
     use super::*;
-    use inotify::{Inotify, WatchMask};
+    use inotify::{Inotify, WatchDescriptor, WatchMask};
+    use std::collections::HashMap;
     use std::thread;
     use std::time::Duration;
     use tokio::sync::mpsc;
+    use crate::monitor::process::ProcessTracker;
 
     pub struct FileMonitor {
         inotify: Arc<Mutex<Option<Inotify>>>,
         running: Arc<AtomicBool>,
-        watches: Arc<Mutex<HashMap<PathBuf, WatchMask>>>,
+        liveness: MonitorLiveness,
+        watches: Arc<Mutex<HashMap<PathBuf, WatchDescriptor>>>,
+        // 事件只携带监控描述符，按描述符找回对应的监控目录
+        watch_paths: Arc<Mutex<HashMap<WatchDescriptor, PathBuf>>>,
         event_callback: Arc<Mutex<Option<Arc<dyn Fn(MonitorEvent) + Send + Sync>>>>,
+        // 没有 root 权限时为 None，事件不带进程信息
+        process_tracker: Arc<Mutex<Option<Arc<ProcessTracker>>>>,
     }
 
     impl FileMonitor {
         pub fn new() -> Self {
+            let running = Arc::new(AtomicBool::new(false));
             Self {
                 inotify: Arc::new(Mutex::new(None)),
-                running: Arc::new(AtomicBool::new(false)),
+                liveness: MonitorLiveness::new(Arc::clone(&running)),
+                running,
                 watches: Arc::new(Mutex::new(HashMap::new())),
+                watch_paths: Arc::new(Mutex::new(HashMap::new())),
                 event_callback: Arc::new(Mutex::new(None)),
+                process_tracker: Arc::new(Mutex::new(None)),
             }
         }
 
         pub fn add_watch(&self, path: &PathBuf, mask: WatchMask) -> Result<(), anyhow::Error> {
+            let path = &safe_canonicalize(path, &[])?;
             let mut inotify_guard = self.inotify.lock().unwrap();
-            let inotify = inotify_guard
-                .as_mut()
-                .expect("监控器未初始化，请先调用start()");
+            if inotify_guard.is_none() {
+                *inotify_guard = Some(Inotify::init().context("无法初始化inotify")?);
+            }
+            let inotify = inotify_guard.as_mut().unwrap();
 
-            inotify
+            let wd = inotify
                 .watches()
                 .add(path.clone(), mask)
                 .with_context(|| format!("无法监控路径: {:?}", path))?;
 
             let mut watches = self.watches.lock().unwrap();
-            watches.insert(path.clone(), mask);
+            if let Some(previous) = watches.insert(path.clone(), wd.clone()) {
+                self.watch_paths.lock().unwrap().remove(&previous);
+            }
+            self.watch_paths.lock().unwrap().insert(wd, path.clone());
+            if let Some(ref tracker) = *self.process_tracker.lock().unwrap() {
+                if let Err(e) = tracker.add_path(path) {
+                    log::warn!("{:#}", e);
+                }
+            }
 
             log::info!("已添加监控: {:?}", path);
             Ok(())
         }
 
         pub fn remove_watch(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
+            let path = &safe_canonicalize(path, &[]).unwrap_or_else(|_| path.clone());
             let mut inotify_guard = self.inotify.lock().unwrap();
             let inotify = inotify_guard
                 .as_mut()
                 .expect("监控器未初始化，请先调用start()");
 
-            if let Some(wd) = inotify.watches().find(path) {
+            let mut watches = self.watches.lock().unwrap();
+            if let Some(wd) = watches.remove(path) {
+                self.watch_paths.lock().unwrap().remove(&wd);
                 inotify.watches().remove(wd)?;
             }
 
-            let mut watches = self.watches.lock().unwrap();
-            watches.remove(path);
-
             log::info!("已移除监控: {:?}", path);
             Ok(())
         }
 
-        pub fn add_default_watches(&self) -> Result<(), anyhow::Error> {
-            let mask = WatchMask::CREATE | WatchMask::MODIFY;
+        // 监控配置中的 watch_paths，不存在或无法监控的路径只记录警告。返回成功添加的数量
+        pub fn add_default_watches(&self, config: &MonitorConfig) -> Result<usize, anyhow::Error> {
+            let mask = watch_mask(&config.events);
 
-            let default_paths = vec![
-                PathBuf::from("/tmp"),
-            ];
-
-            for path in default_paths {
-                if path.exists() {
-                    self.add_watch(&path, mask)?;
+            let mut added = 0;
+            for path in &config.watch_paths {
+                let path = PathBuf::from(path);
+                if !path.exists() {
+                    log::warn!("监控路径不存在，已跳过: {:?}", path);
+                    continue;
+                }
+                match self.add_watch(&path, mask) {
+                    Ok(()) => added += 1,
+                    Err(e) => log::warn!("{:#}", e),
                 }
             }
 
-            Ok(())
+            if added == 0 && !config.watch_paths.is_empty() {
+                return Err(anyhow::anyhow!("没有可监控的路径: {:?}", config.watch_paths));
+            }
+            Ok(added)
+        }
+
+        // 运行时添加的监控目录使用与 watch_paths 相同的事件类型
+        pub fn add_configured_watch(&self, path: &PathBuf, config: &MonitorConfig) -> Result<(), anyhow::Error> {
+            self.add_watch(path, watch_mask(&config.events))
         }
 
         pub fn start(&mut self) -> Result<(), anyhow::Error> {
@@ -112,33 +195,49 @@ mod linux_monitor {
                 return Err(anyhow::anyhow!("监控器已在运行中"));
             }
 
-            let inotify = Inotify::init()
-                .context("无法初始化inotify")?;
-
             {
                 let mut guard = self.inotify.lock().unwrap();
-                *guard = Some(inotify);
+                if guard.is_none() {
+                    *guard = Some(Inotify::init().context("无法初始化inotify")?);
+                }
+            }
+
+            match ProcessTracker::start() {
+                Ok(tracker) => {
+                    for path in self.watches.lock().unwrap().keys() {
+                        if let Err(e) = tracker.add_path(path) {
+                            log::warn!("{:#}", e);
+                        }
+                    }
+                    *self.process_tracker.lock().unwrap() = Some(Arc::new(tracker));
+                }
+                Err(e) => log::info!("监控事件将不包含进程信息: {:#}", e),
             }
 
             self.running.store(true, Ordering::Relaxed);
 
             let inotify = Arc::clone(&self.inotify);
             let running = Arc::clone(&self.running);
-            let watches = Arc::clone(&self.watches);
+            let liveness = self.liveness.clone();
+            let watch_paths = Arc::clone(&self.watch_paths);
+            let process_tracker = self.process_tracker.lock().unwrap().clone();
             let event_callback = Arc::clone(&self.event_callback);
 
             thread::spawn(move || {
                 log::info!("文件监控线程已启动");
 
                 while running.load(Ordering::Relaxed) {
+                    liveness.beat();
                     let mut buffer = [0u8; 1024];
                     let mut inotify_guard = inotify.lock().unwrap();
 
-                    if let Some(ref inotify) = *inotify_guard {
+                    if let Some(ref mut inotify) = *inotify_guard {
                         match inotify.read_events(&mut buffer) {
                             Ok(events) => {
                                 for event in events {
-                                    let watch_path = PathBuf::from("/tmp");
+                                    let Some(watch_path) = watch_paths.lock().unwrap().get(&event.wd).cloned() else {
+                                        continue;
+                                    };
                                     let (event_type, file_name) = Self::parse_event(
                                         event.mask,
                                         event.name,
@@ -150,6 +249,13 @@ mod linux_monitor {
                                             .duration_since(std::time::UNIX_EPOCH)
                                             .unwrap_or_default()
                                             .as_secs();
+                                        // 删除和移出的文件无法通过写入事件关联进程
+                                        let process_info = match event_type {
+                                            EventType::Created | EventType::Modified | EventType::MovedTo => process_tracker
+                                                .as_ref()
+                                                .and_then(|tracker| tracker.lookup(&file_path)),
+                                            _ => None,
+                                        };
 
                                         let monitor_event = MonitorEvent {
                                             watch_path,
@@ -157,7 +263,7 @@ mod linux_monitor {
                                             file_path,
                                             cookie: event.cookie,
                                             timestamp,
-                                            process_info: None,
+                                            process_info,
                                         };
 
                                         if let Some(ref callback) = *event_callback.lock().unwrap() {
@@ -166,6 +272,7 @@ mod linux_monitor {
                                     }
                                 }
                             }
+                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
                             Err(e) => {
                                 log::error!("读取inotify事件失败: {}", e);
                             }
@@ -212,13 +319,15 @@ mod linux_monitor {
 
             let mut guard = self.inotify.lock().unwrap();
             if let Some(ref mut inotify) = *guard {
-                let watch_paths: Vec<PathBuf> = self.watches.lock().unwrap().keys().cloned().collect();
-                for path in &watch_paths {
-                    if let Ok(wd) = inotify.watches().add(path.clone(), WatchMask::empty()) {
-                        let _ = inotify.watches().remove(wd);
-                    }
+                let mut watches = self.watches.lock().unwrap();
+                for (_, wd) in watches.drain() {
+                    let _ = inotify.watches().remove(wd);
                 }
-                self.watches.lock().unwrap().clear();
+            }
+            self.watch_paths.lock().unwrap().clear();
+            drop(guard);
+            if let Some(tracker) = self.process_tracker.lock().unwrap().take() {
+                tracker.stop();
             }
 
             log::info!("文件监控服务已停止");
@@ -233,60 +342,50 @@ mod linux_monitor {
             self.running.load(Ordering::Relaxed)
         }
 
+        pub fn liveness(&self) -> MonitorLiveness {
+            self.liveness.clone()
+        }
+
         pub fn get_watched_paths(&self) -> Vec<PathBuf> {
             self.watches.lock().unwrap().keys().cloned().collect()
         }
     }
 
-    pub use FileMonitor;
-}
-
-#[cfg(not(target_os = "linux"))]
-mod stub_monitor {
-    use super::*;
-
-    pub struct FileMonitor;
-
-    impl FileMonitor {
-        pub fn new() -> Self {
-            Self
-        }
-
-        pub fn add_watch(&self, _path: &PathBuf, _mask: u32) -> Result<(), anyhow::Error> {
-            Err(anyhow::anyhow!("文件监控仅在Linux系统上可用"))
-        }
-
-        pub fn remove_watch(&self, _path: &PathBuf) -> Result<(), anyhow::Error> {
-            Err(anyhow::anyhow!("文件监控仅在Linux系统上可用"))
-        }
-
-        pub fn add_default_watches(&self) -> Result<(), anyhow::Error> {
-            Err(anyhow::anyhow!("文件监控仅在Linux系统上可用"))
-        }
-
-        pub fn start(&mut self) -> Result<(), anyhow::Error> {
-            Err(anyhow::anyhow!("文件监控仅在Linux系统上可用"))
-        }
-
-        pub fn stop(&mut self) {
-            log::warn!("文件监控仅在Linux系统上可用");
-        }
-
-        pub fn set_event_callback(&mut self, _callback: Arc<dyn Fn(MonitorEvent) + Send + Sync>) {
+    // 配置中的事件名转换为 inotify 掩码，未配置时监控创建和修改
+    pub fn watch_mask(events: &[String]) -> WatchMask {
+        let mut mask = WatchMask::empty();
+        for event in events {
+            match event.to_lowercase().as_str() {
+                "create" => mask |= WatchMask::CREATE,
+                "modify" => mask |= WatchMask::MODIFY,
+                "delete" => mask |= WatchMask::DELETE,
+                "move" => mask |= WatchMask::MOVED_FROM | WatchMask::MOVED_TO,
+                "access" => mask |= WatchMask::ACCESS,
+                other => log::warn!("未知的监控事件: {}", other),
+            }
         }
-
-        pub fn is_running(&self) -> bool {
-            false
-        }
-
-        pub fn get_watched_paths(&self) -> Vec<PathBuf> {
-            Vec::new()
+        if mask.is_empty() {
+            mask = WatchMask::CREATE | WatchMask::MODIFY;
         }
+        mask
     }
 }
 
 #[cfg(target_os = "linux")]
 pub use linux_monitor::FileMonitor;
 
+// 其他系统使用轮询实现，API 与 Linux 版本相同
 #[cfg(not(target_os = "linux"))]
-pub use stub_monitor::FileMonitor;
+pub use poll::PollingMonitor as FileMonitor;
+
+#[cfg(target_os = "linux")]
+pub use fanotify::{start_access_guard, AccessDecision, AccessGuard};
+pub use control::{ControlRequest, ControlServer, MonitorHandle, MonitorStatus};
+pub use filter::{EventFilter, FilterStats};
+pub use journal::{EventJournal, EventQuery};
+pub use on_access::{on_access_scan_options, OnAccessScanner};
+pub use stats::{MonitorStats, MonitorStatsSnapshot};
+pub use poll::PollingMonitor;
+
+#[cfg(test)]
+mod tests;
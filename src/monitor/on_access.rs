@@ -0,0 +1,250 @@
+use crate::config::{DetectionAction, MonitorActions, OnAccessConfig, ScannerConfig};
+use crate::core::security::QuarantineManager;
+use crate::monitor::{EventType, MonitorEvent, MonitorStats, ProcessInfo};
+use crate::scanner::{Allowlist, ScanMode, ScanOptions, ScanResult, ScannerEngine, SignatureDatabase};
+use crate::utils::logging::AuditLogger;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// 监控事件触发的扫描。同一文件在防抖时间内的多次事件只扫描一次，
+// 队列满时丢弃新事件，避免事件风暴拖垮监控线程
+pub struct OnAccessScanner {
+    signature_db: Arc<SignatureDatabase>,
+    options: ScanOptions,
+    actions: MonitorActions,
+    debounce: Duration,
+    allowlist: Option<Arc<Allowlist>>,
+    quarantine: Option<Arc<QuarantineManager>>,
+    audit_logger: Option<Arc<AuditLogger>>,
+    tx: mpsc::Sender<PathBuf>,
+    rx: Mutex<Option<mpsc::Receiver<PathBuf>>>,
+    // 已入队但尚未扫描的文件及其最近一次事件的时间和写入进程
+    pending: Arc<Mutex<HashMap<PathBuf, (Instant, Option<ProcessInfo>)>>>,
+    result_callback: Option<Arc<dyn Fn(&ScanResult) + Send + Sync>>,
+    stats: Arc<MonitorStats>,
+}
+
+impl OnAccessScanner {
+    pub fn new(
+        signature_db: Arc<SignatureDatabase>,
+        options: ScanOptions,
+        actions: MonitorActions,
+        config: &OnAccessConfig,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(config.queue_size.max(1));
+        let mut options = options;
+        if actions.auto_quarantine {
+            options.action = DetectionAction::Quarantine;
+        }
+        Self {
+            signature_db,
+            options,
+            actions,
+            debounce: Duration::from_millis(config.debounce_ms),
+            allowlist: None,
+            quarantine: None,
+            audit_logger: None,
+            tx,
+            rx: Mutex::new(Some(rx)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            result_callback: None,
+            stats: Arc::new(MonitorStats::new()),
+        }
+    }
+
+    pub fn set_allowlist(&mut self, allowlist: Arc<Allowlist>) {
+        self.allowlist = Some(allowlist);
+    }
+
+    // 启用 auto_quarantine 时必须设置
+    pub fn set_quarantine_manager(&mut self, quarantine: Arc<QuarantineManager>) {
+        self.quarantine = Some(quarantine);
+    }
+
+    // 自动隔离的结果写入审计日志
+    pub fn set_audit_logger(&mut self, audit_logger: Arc<AuditLogger>) {
+        self.audit_logger = Some(audit_logger);
+    }
+
+    // 每个检测结果调用一次
+    pub fn set_result_callback(&mut self, callback: Arc<dyn Fn(&ScanResult) + Send + Sync>) {
+        self.result_callback = Some(callback);
+    }
+
+    // 队列溢出、扫描次数和发现的威胁计入该统计，可与监控进程的其他统计共用
+    pub fn set_stats(&mut self, stats: Arc<MonitorStats>) {
+        self.stats = stats;
+    }
+
+    pub fn stats(&self) -> Arc<MonitorStats> {
+        Arc::clone(&self.stats)
+    }
+
+    // 事件对应的配置动作为 scan 或 quarantine 时需要扫描
+    pub fn should_scan(&self, event_type: &EventType) -> bool {
+        let action = match event_type {
+            EventType::Created | EventType::MovedTo => &self.actions.on_create,
+            EventType::Modified => &self.actions.on_modify,
+            EventType::Deleted | EventType::MovedFrom | EventType::Accessed => return false,
+        };
+        matches!(action.to_lowercase().as_str(), "scan" | "quarantine")
+    }
+
+    // 可在监控线程中直接调用，不会阻塞。返回是否新加入扫描队列
+    pub fn submit(&self, event: &MonitorEvent) -> bool {
+        if !self.should_scan(&event.event_type) {
+            log::info!("监控事件: {:?} {:?}", event.event_type, event.file_path);
+            return false;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        if let Some((last, process)) = pending.get_mut(&event.file_path) {
+            *last = Instant::now();
+            if event.process_info.is_some() {
+                *process = event.process_info.clone();
+            }
+            return false;
+        }
+        match self.tx.try_send(event.file_path.clone()) {
+            Ok(()) => {
+                pending.insert(event.file_path.clone(), (Instant::now(), event.process_info.clone()));
+                true
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.stats.record_dropped();
+                log::warn!("实时扫描队列已满，丢弃事件: {:?}", event.file_path);
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+
+    // 需要在 tokio 运行时中调用，只能启动一次
+    pub fn start(&self) -> Result<tokio::task::JoinHandle<()>, anyhow::Error> {
+        if self.actions.auto_quarantine && self.quarantine.is_none() {
+            return Err(anyhow::anyhow!("已启用自动隔离但未配置隔离区"));
+        }
+        let mut rx = self
+            .rx
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("实时扫描已启动"))?;
+        let signature_db = Arc::clone(&self.signature_db);
+        let options = self.options.clone();
+        let allowlist = self.allowlist.clone();
+        let quarantine = self.quarantine.clone();
+        let audit_logger = self.audit_logger.clone();
+        let pending = Arc::clone(&self.pending);
+        let debounce = self.debounce;
+        let result_callback = self.result_callback.clone();
+        let stats = Arc::clone(&self.stats);
+
+        Ok(tokio::spawn(async move {
+            while let Some(path) = rx.recv().await {
+                // 等到文件在防抖时间内不再变化后再扫描
+                loop {
+                    let Some(last) = pending.lock().unwrap().get(&path).map(|(last, _)| *last) else {
+                        break;
+                    };
+                    let due = last + debounce;
+                    if Instant::now() >= due {
+                        break;
+                    }
+                    tokio::time::sleep_until(due.into()).await;
+                }
+                // 扫描期间的新事件会重新入队
+                let process = pending.lock().unwrap().remove(&path).and_then(|(_, process)| process);
+
+                if !path.is_file() {
+                    continue;
+                }
+                let mut options = options.clone();
+                options.custom_paths = vec![path.clone()];
+                let mut engine = ScannerEngine::new(Arc::clone(&signature_db), options);
+                if let Some(allowlist) = &allowlist {
+                    engine.set_allowlist(Arc::clone(allowlist));
+                }
+                if let Some(quarantine) = &quarantine {
+                    engine.set_quarantine_manager(Arc::clone(quarantine));
+                }
+                match engine.start_scan().await {
+                    Ok(results) => {
+                        stats.record_scan(results.len());
+                        for result in &results {
+                            log::warn!(
+                                "实时监控发现威胁: {:?} ({}, {:?}){}",
+                                result.file_path,
+                                result.signature_id,
+                                result.risk_level,
+                                process.as_ref().map(|p| format!(" 写入进程: {} ({})", p.pid, p.command)).unwrap_or_default()
+                            );
+                            if let (Some(audit_logger), Some(action_taken)) = (&audit_logger, &result.action_taken) {
+                                audit_action(audit_logger, result, action_taken, process.as_ref());
+                            }
+                            if let Some(callback) = &result_callback {
+                                callback(result);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        stats.record_scan(0);
+                        log::warn!("实时扫描失败 {:?}: {:#}", path, e);
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn audit_action(audit_logger: &AuditLogger, result: &ScanResult, action_taken: &str, process: Option<&ProcessInfo>) {
+    let action = match action_taken {
+        "report" => return,
+        "quarantined" => "MONITOR_QUARANTINE",
+        _ => "MONITOR_QUARANTINE_FAILED",
+    };
+    let mut details = format!(
+        "file={:?} signature={} risk={:?} result={}",
+        result.file_path, result.signature_id, result.risk_level, action_taken
+    );
+    if let Some(process) = process {
+        details.push_str(&format!(
+            " pid={} uid={} user={} command={:?}",
+            process.pid, process.user_id, process.user_name, process.command
+        ));
+    }
+    audit_logger.log(action, "monitor", &details);
+}
+
+// 每次只扫描单个文件，使用单线程且不调整进程 I/O 优先级
+pub fn on_access_scan_options(config: &ScannerConfig) -> ScanOptions {
+    ScanOptions {
+        scan_mode: ScanMode::Custom,
+        custom_paths: vec![],
+        exclude_paths: config.scan_modes.exclude_paths.iter().map(PathBuf::from).collect(),
+        exclude_extensions: config.scan_modes.exclude_extensions.clone(),
+        max_file_size: config.scan_modes.max_file_size,
+        thread_count: 1,
+        walk_threads: 1,
+        quick_scan_paths: vec![],
+        priority_paths: vec![],
+        use_xattr_markers: config.scan_modes.use_xattr_markers,
+        xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+        archive: config.scan_modes.archive.clone(),
+        heuristics: config.scan_modes.heuristics.clone(),
+        pdf: config.scan_modes.pdf.clone(),
+        mail: config.scan_modes.mail.clone(),
+        skip_benign_types: config.scan_modes.skip_benign_types,
+        honor_scanignore: config.scan_modes.honor_scanignore,
+        memory_limit_mb: config.performance.memory_limit_mb,
+        max_read_mb_per_s: config.performance.max_read_mb_per_s,
+        idle_io_priority: false,
+        max_duration: None,
+        action: DetectionAction::Report,
+        auto_quarantine_min_risk: None,
+        dry_run: false,
+    }
+}
@@ -0,0 +1,232 @@
+use crate::config::MonitorConfig;
+use crate::monitor::{EventType, MonitorEvent, MonitorLiveness};
+use crate::utils::safe_canonicalize;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// 目录中每个文件的 (修改时间, 大小)
+type DirectorySnapshot = HashMap<PathBuf, (SystemTime, u64)>;
+
+struct PollWatch {
+    events: Vec<EventType>,
+    files: DirectorySnapshot,
+}
+
+// 不依赖平台通知机制的监控：定期列出监控目录的直接子文件并与上次结果比较。
+// 非 Linux 系统使用该实现；重命名表现为删除加创建，也无法得知写入进程
+pub struct PollingMonitor {
+    interval_ms: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    liveness: MonitorLiveness,
+    watches: Arc<Mutex<HashMap<PathBuf, PollWatch>>>,
+    event_callback: Arc<Mutex<Option<Arc<dyn Fn(MonitorEvent) + Send + Sync>>>>,
+}
+
+impl PollingMonitor {
+    pub fn new() -> Self {
+        let running = Arc::new(AtomicBool::new(false));
+        Self {
+            interval_ms: Arc::new(AtomicU64::new(1000)),
+            liveness: MonitorLiveness::new(Arc::clone(&running)),
+            running,
+            watches: Arc::new(Mutex::new(HashMap::new())),
+            event_callback: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn set_poll_interval(&self, interval: Duration) {
+        self.interval_ms.store(interval.as_millis().max(1) as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_watch(&self, path: &PathBuf, events: Vec<EventType>) -> Result<(), anyhow::Error> {
+        let path = safe_canonicalize(path, &[])?;
+        let files = snapshot(&path).with_context(|| format!("无法监控路径: {:?}", path))?;
+        self.watches.lock().unwrap().insert(path.clone(), PollWatch { events, files });
+        log::info!("已添加监控: {:?}", path);
+        Ok(())
+    }
+
+    pub fn remove_watch(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
+        let path = safe_canonicalize(path, &[]).unwrap_or_else(|_| path.clone());
+        self.watches.lock().unwrap().remove(&path);
+        log::info!("已移除监控: {:?}", path);
+        Ok(())
+    }
+
+    // 与 Linux 实现相同：不存在或无法监控的路径只记录警告，返回成功添加的数量
+    pub fn add_default_watches(&self, config: &MonitorConfig) -> Result<usize, anyhow::Error> {
+        self.set_poll_interval(Duration::from_millis(config.poll_interval_ms));
+        let events = watch_events(&config.events);
+
+        let mut added = 0;
+        for path in &config.watch_paths {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                log::warn!("监控路径不存在，已跳过: {:?}", path);
+                continue;
+            }
+            match self.add_watch(&path, events.clone()) {
+                Ok(()) => added += 1,
+                Err(e) => log::warn!("{:#}", e),
+            }
+        }
+
+        if added == 0 && !config.watch_paths.is_empty() {
+            return Err(anyhow::anyhow!("没有可监控的路径: {:?}", config.watch_paths));
+        }
+        Ok(added)
+    }
+
+    pub fn add_configured_watch(&self, path: &PathBuf, config: &MonitorConfig) -> Result<(), anyhow::Error> {
+        self.add_watch(path, watch_events(&config.events))
+    }
+
+    pub fn start(&mut self) -> Result<(), anyhow::Error> {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("监控器已在运行中"));
+        }
+
+        let running = Arc::clone(&self.running);
+        let liveness = self.liveness.clone();
+        let interval_ms = Arc::clone(&self.interval_ms);
+        let watches = Arc::clone(&self.watches);
+        let event_callback = Arc::clone(&self.event_callback);
+
+        thread::spawn(move || {
+            log::info!("文件监控线程已启动 (轮询)");
+
+            while running.load(Ordering::Relaxed) {
+                liveness.beat();
+                thread::sleep(Duration::from_millis(interval_ms.load(Ordering::Relaxed)));
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let mut events = Vec::new();
+                for (watch_path, watch) in watches.lock().unwrap().iter_mut() {
+                    let current = match snapshot(watch_path) {
+                        Ok(current) => current,
+                        Err(e) => {
+                            log::warn!("无法读取监控目录 {:?}: {}", watch_path, e);
+                            continue;
+                        }
+                    };
+                    for (file_path, event_type) in diff(&watch.files, &current) {
+                        if watch.events.contains(&event_type) {
+                            events.push(MonitorEvent {
+                                watch_path: watch_path.clone(),
+                                event_type,
+                                file_path,
+                                cookie: 0,
+                                timestamp: SystemTime::now()
+                                    .duration_since(SystemTime::UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_secs(),
+                                process_info: None,
+                            });
+                        }
+                    }
+                    watch.files = current;
+                }
+
+                // 回调中可能再次调用 add_watch，先释放锁
+                let callback = event_callback.lock().unwrap().clone();
+                if let Some(callback) = callback {
+                    for event in events {
+                        callback(event);
+                    }
+                }
+            }
+
+            log::info!("文件监控线程已停止");
+        });
+
+        log::info!("文件监控服务已启动");
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        self.watches.lock().unwrap().clear();
+        log::info!("文件监控服务已停止");
+    }
+
+    pub fn set_event_callback(&mut self, callback: Arc<dyn Fn(MonitorEvent) + Send + Sync>) {
+        *self.event_callback.lock().unwrap() = Some(callback);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn liveness(&self) -> MonitorLiveness {
+        self.liveness.clone()
+    }
+
+    pub fn get_watched_paths(&self) -> Vec<PathBuf> {
+        self.watches.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+impl Default for PollingMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 配置中的事件名转换为可轮询的事件类型，未配置时监控创建和修改
+pub fn watch_events(events: &[String]) -> Vec<EventType> {
+    let mut types = Vec::new();
+    for event in events {
+        match event.to_lowercase().as_str() {
+            "create" => types.push(EventType::Created),
+            "modify" => types.push(EventType::Modified),
+            "delete" => types.push(EventType::Deleted),
+            other => log::warn!("轮询监控不支持的事件: {}", other),
+        }
+    }
+    if types.is_empty() {
+        types = vec![EventType::Created, EventType::Modified];
+    }
+    types
+}
+
+fn snapshot(dir: &PathBuf) -> std::io::Result<DirectorySnapshot> {
+    let mut files = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.insert(entry.path(), (modified, metadata.len()));
+        }
+    }
+    Ok(files)
+}
+
+fn diff(previous: &DirectorySnapshot, current: &DirectorySnapshot) -> Vec<(PathBuf, EventType)> {
+    let mut changes = Vec::new();
+    for (path, state) in current {
+        match previous.get(path) {
+            None => changes.push((path.clone(), EventType::Created)),
+            Some(old) if old != state => changes.push((path.clone(), EventType::Modified)),
+            Some(_) => {}
+        }
+    }
+    for path in previous.keys() {
+        if !current.contains_key(path) {
+            changes.push((path.clone(), EventType::Deleted));
+        }
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
@@ -0,0 +1,154 @@
+use crate::monitor::ProcessInfo;
+use std::path::PathBuf;
+
+// 从 /proc 读取进程信息，进程已退出时返回 None
+pub fn process_info(pid: u32) -> Option<ProcessInfo> {
+    let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+    let cmdline = std::fs::read(proc_dir.join("cmdline")).ok()?;
+    let mut command = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+    // 内核线程和僵尸进程没有命令行
+    if command.is_empty() {
+        command = format!("[{}]", std::fs::read_to_string(proc_dir.join("comm")).ok()?.trim());
+    }
+
+    let status = std::fs::read_to_string(proc_dir.join("status")).ok()?;
+    let user_id = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())?;
+
+    Some(ProcessInfo {
+        pid,
+        command,
+        user_id,
+        user_name: user_name(user_id),
+    })
+}
+
+fn user_name(uid: u32) -> String {
+    #[cfg(unix)]
+    if let Ok(Some(user)) = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid)) {
+        return user.name;
+    }
+    uid.to_string()
+}
+
+#[cfg(target_os = "linux")]
+pub use tracker::ProcessTracker;
+
+// inotify 事件不含进程信息。用一个 fanotify 通知实例监听同样的目录，记录最近写入各文件的进程，
+// inotify 事件到达时按文件路径查询。需要 root 权限
+#[cfg(target_os = "linux")]
+mod tracker {
+    use super::process_info;
+    use crate::monitor::fanotify::{fd_path, init_fanotify, mark_directory, read_fanotify_events};
+    use crate::monitor::ProcessInfo;
+    use anyhow::{Context, Result};
+    use std::collections::HashMap;
+    use std::os::unix::io::{AsRawFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const WRITE_EVENTS: u64 = libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE;
+    // 超过该时间的记录不再与 inotify 事件关联
+    const RECORD_TTL: Duration = Duration::from_secs(30);
+    const MAX_RECORDS: usize = 10_000;
+
+    type Records = HashMap<PathBuf, (Instant, ProcessInfo)>;
+
+    pub struct ProcessTracker {
+        fanotify: Arc<OwnedFd>,
+        running: Arc<AtomicBool>,
+        records: Arc<Mutex<Records>>,
+        thread: Mutex<Option<thread::JoinHandle<()>>>,
+    }
+
+    impl ProcessTracker {
+        pub fn start() -> Result<Self> {
+            let fanotify = Arc::new(init_fanotify(libc::FAN_CLASS_NOTIF)?);
+            let running = Arc::new(AtomicBool::new(true));
+            let records: Arc<Mutex<Records>> = Arc::new(Mutex::new(HashMap::new()));
+
+            let thread = {
+                let fanotify = Arc::clone(&fanotify);
+                let running = Arc::clone(&running);
+                let records = Arc::clone(&records);
+                thread::spawn(move || {
+                    read_fanotify_events(fanotify.as_raw_fd(), &running, |fd, _mask, pid| {
+                        let path = fd_path(fd.as_raw_fd());
+                        drop(fd);
+                        record(&records, path, pid as u32);
+                    });
+                })
+            };
+
+            Ok(Self {
+                fanotify,
+                running,
+                records,
+                thread: Mutex::new(Some(thread)),
+            })
+        }
+
+        pub fn add_path(&self, path: &Path) -> Result<()> {
+            mark_directory(self.fanotify.as_raw_fd(), path, WRITE_EVENTS)
+                .with_context(|| format!("无法跟踪写入进程: {:?}", path))
+        }
+
+        // 最近写入该文件的进程
+        pub fn lookup(&self, path: &Path) -> Option<ProcessInfo> {
+            let records = self.records.lock().unwrap();
+            records
+                .get(path)
+                .filter(|(seen, _)| seen.elapsed() < RECORD_TTL)
+                .map(|(_, info)| info.clone())
+        }
+
+        pub fn stop(&self) {
+            self.running.store(false, Ordering::Relaxed);
+            if let Some(handle) = self.thread.lock().unwrap().take() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    impl Drop for ProcessTracker {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    // 同一进程连续写入同一文件时只在首次读取 /proc
+    fn record(records: &Mutex<Records>, path: PathBuf, pid: u32) {
+        {
+            let mut records = records.lock().unwrap();
+            if let Some((seen, info)) = records.get_mut(&path) {
+                if info.pid == pid {
+                    *seen = Instant::now();
+                    return;
+                }
+            }
+        }
+
+        let Some(info) = process_info(pid) else {
+            return;
+        };
+        let mut records = records.lock().unwrap();
+        if records.len() >= MAX_RECORDS {
+            records.retain(|_, (seen, _)| seen.elapsed() < RECORD_TTL);
+            if records.len() >= MAX_RECORDS {
+                records.clear();
+            }
+        }
+        records.insert(path, (Instant::now(), info));
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 监控运行期间的累计计数，由事件回调和实时扫描任务共同更新
+#[derive(Debug, Default)]
+pub struct MonitorStats {
+    events_received: AtomicU64,
+    events_dropped: AtomicU64,
+    scans_triggered: AtomicU64,
+    threats_found: AtomicU64,
+}
+
+impl MonitorStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_event(&self) {
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 实时扫描队列已满而丢弃的事件
+    pub fn record_dropped(&self) {
+        self.events_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_scan(&self, threats: usize) {
+        self.scans_triggered.fetch_add(1, Ordering::Relaxed);
+        self.threats_found.fetch_add(threats as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MonitorStatsSnapshot {
+        MonitorStatsSnapshot {
+            events_received: self.events_received.load(Ordering::Relaxed),
+            events_dropped: self.events_dropped.load(Ordering::Relaxed),
+            scans_triggered: self.scans_triggered.load(Ordering::Relaxed),
+            threats_found: self.threats_found.load(Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct MonitorStatsSnapshot {
+    pub events_received: u64,
+    pub events_filtered: u64,
+    pub events_rate_limited: u64,
+    pub events_dropped: u64,
+    pub scans_triggered: u64,
+    pub threats_found: u64,
+    pub watches: usize,
+    // 系统允许每个用户创建的 inotify 监控数，非 Linux 系统为 None
+    pub max_user_watches: Option<u64>,
+}
+
+pub fn max_user_watches() -> Option<u64> {
+    std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
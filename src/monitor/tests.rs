@@ -0,0 +1,550 @@
+use crate::config::{AccessControlConfig, AccessControlPath, MonitorConfig, OnAccessConfig, ScannerConfig};
+use crate::monitor::{on_access_scan_options, EventType, FileMonitor, MonitorEvent, OnAccessScanner};
+use crate::core::security::QuarantineManager;
+use crate::utils::logging::AuditLogger;
+use crate::scanner::{eicar_test_string, ScanResult, SignatureDatabase, EICAR_SIGNATURE_ID};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor_config(paths: &[&PathBuf]) -> MonitorConfig {
+        let mut config = ScannerConfig::default().monitor;
+        config.watch_paths = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        config.events = vec!["create".to_string()];
+        config
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_events_report_their_own_watch_path() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let first_path = first.path().canonicalize().unwrap();
+        let second_path = second.path().canonicalize().unwrap();
+
+        let events: Arc<Mutex<Vec<MonitorEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut monitor = FileMonitor::new();
+        let sink = Arc::clone(&events);
+        monitor.set_event_callback(Arc::new(move |event| sink.lock().unwrap().push(event)));
+
+        let missing = first_path.join("missing");
+        let config = monitor_config(&[&first_path, &missing, &second_path]);
+        assert_eq!(monitor.add_default_watches(&config).unwrap(), 2);
+        monitor.start().unwrap();
+
+        std::fs::write(second_path.join("b.txt"), b"b").unwrap();
+        std::fs::write(first_path.join("a.txt"), b"a").unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while events.lock().unwrap().len() < 2 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        monitor.stop();
+
+        let events = events.lock().unwrap();
+        let created: Vec<_> = events.iter().filter(|e| e.event_type == EventType::Created).collect();
+        assert_eq!(created.len(), 2);
+        for event in created {
+            assert_eq!(event.file_path.parent().unwrap(), event.watch_path);
+        }
+        assert!(events.iter().any(|e| e.file_path == second_path.join("b.txt") && e.watch_path == second_path));
+        assert!(events.iter().any(|e| e.file_path == first_path.join("a.txt") && e.watch_path == first_path));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_no_usable_watch_paths_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let monitor = FileMonitor::new();
+        assert!(monitor.add_default_watches(&monitor_config(&[&missing])).is_err());
+        assert!(monitor.get_watched_paths().is_empty());
+    }
+
+    fn event(event_type: EventType, path: &std::path::Path) -> MonitorEvent {
+        MonitorEvent {
+            watch_path: path.parent().unwrap().to_path_buf(),
+            event_type,
+            file_path: path.to_path_buf(),
+            cookie: 0,
+            timestamp: 0,
+            process_info: None,
+        }
+    }
+
+    async fn on_access_scanner(on_modify: &str, queue_size: usize) -> OnAccessScanner {
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let config = ScannerConfig::default();
+        let mut actions = config.monitor.actions.clone();
+        actions.on_create = "scan".to_string();
+        actions.on_modify = on_modify.to_string();
+        let on_access = OnAccessConfig {
+            debounce_ms: 200,
+            queue_size,
+        };
+        OnAccessScanner::new(signature_db, on_access_scan_options(&config), actions, &on_access)
+    }
+
+    #[tokio::test]
+    async fn test_on_access_scans_debounced_events_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dropped.com");
+
+        let results: Arc<Mutex<Vec<ScanResult>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut scanner = on_access_scanner("scan", 16).await;
+        let sink = Arc::clone(&results);
+        scanner.set_result_callback(Arc::new(move |result| sink.lock().unwrap().push(result.clone())));
+        let task = scanner.start().unwrap();
+
+        // 写入过程中的多次事件合并为一次扫描
+        std::fs::write(&path, b"partial").unwrap();
+        assert!(scanner.submit(&event(EventType::Created, &path)));
+        std::fs::write(&path, eicar_test_string()).unwrap();
+        assert!(!scanner.submit(&event(EventType::Modified, &path)));
+        assert!(!scanner.submit(&event(EventType::Modified, &path)));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while results.lock().unwrap().is_empty() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        task.abort();
+
+        let results = results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].signature_id, EICAR_SIGNATURE_ID);
+        let stats = scanner.stats().snapshot();
+        assert_eq!((stats.scans_triggered, stats.threats_found), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_on_access_queue_is_bounded_and_honours_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let scanner = on_access_scanner("log", 1).await;
+
+        assert!(!scanner.submit(&event(EventType::Modified, &dir.path().join("a"))));
+        assert!(!scanner.submit(&event(EventType::Deleted, &dir.path().join("a"))));
+        assert!(scanner.submit(&event(EventType::Created, &dir.path().join("a"))));
+        // 未启动扫描时队列不会被消费，超出容量的事件被丢弃
+        assert!(!scanner.submit(&event(EventType::Created, &dir.path().join("b"))));
+        assert_eq!(scanner.stats().snapshot().events_dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_on_access_auto_quarantine() {
+        let dir = tempfile::tempdir().unwrap();
+        let watched = dir.path().join("watched");
+        let quarantine_dir = dir.path().join("quarantine");
+        let log_dir = dir.path().join("log");
+        std::fs::create_dir_all(&watched).unwrap();
+        let path = watched.join("dropped.com");
+        std::fs::write(&path, eicar_test_string()).unwrap();
+
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let config = ScannerConfig::default();
+        let mut actions = config.monitor.actions.clone();
+        actions.on_create = "scan".to_string();
+        actions.auto_quarantine = true;
+        let on_access = OnAccessConfig {
+            debounce_ms: 0,
+            queue_size: 16,
+        };
+        let mut scanner = OnAccessScanner::new(signature_db, on_access_scan_options(&config), actions, &on_access);
+        // 未配置隔离区时拒绝启动
+        assert!(scanner.start().is_err());
+
+        let results: Arc<Mutex<Vec<ScanResult>>> = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&results);
+        scanner.set_result_callback(Arc::new(move |result| sink.lock().unwrap().push(result.clone())));
+        scanner.set_quarantine_manager(Arc::new(QuarantineManager::new(quarantine_dir.clone(), None)));
+        scanner.set_audit_logger(Arc::new(AuditLogger::new(log_dir.clone(), true)));
+        let task = scanner.start().unwrap();
+        assert!(scanner.submit(&event(EventType::Created, &path)));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while results.lock().unwrap().is_empty() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        task.abort();
+
+        let results = results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].action_taken.as_deref(), Some("quarantined"));
+        assert!(!path.exists());
+        assert_eq!(QuarantineManager::new(quarantine_dir.clone(), None).list().unwrap().len(), 1);
+        let audit = std::fs::read_to_string(log_dir.join("audit.log")).unwrap();
+        assert!(audit.contains("ACTION=MONITOR_QUARANTINE "));
+        assert!(audit.contains(EICAR_SIGNATURE_ID));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_access_guard_verdicts_follow_file_changes() {
+        use crate::monitor::{AccessDecision, AccessGuard};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.com");
+        std::fs::write(&path, b"harmless").unwrap();
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let guard = AccessGuard::new(signature_db, AccessControlConfig::default(), 1024);
+
+        let check = |guard: &AccessGuard| guard.check_file(&std::fs::File::open(&path).unwrap(), &path);
+        assert_eq!(check(&guard), AccessDecision::Allow);
+        std::fs::write(&path, eicar_test_string()).unwrap();
+        assert_eq!(check(&guard), AccessDecision::Deny(EICAR_SIGNATURE_ID.to_string()));
+
+        // 同样大小的内容替换后把 mtime 改回原值，ctime 仍然变化，缓存的结论失效
+        let eicar = eicar_test_string();
+        std::fs::write(&path, vec![b'A'; eicar.len()]).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(check(&guard), AccessDecision::Allow);
+        std::fs::write(&path, &eicar).unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        assert_eq!(check(&guard), AccessDecision::Deny(EICAR_SIGNATURE_ID.to_string()));
+
+        // 超过大小限制的文件默认放行
+        std::fs::write(&path, vec![b'A'; 2048]).unwrap();
+        assert_eq!(check(&guard), AccessDecision::Allow);
+        let strict = AccessControlConfig {
+            deny_on_error: true,
+            ..AccessControlConfig::default()
+        };
+        let guard = AccessGuard::new(Arc::new(SignatureDatabase::new()), strict, 1024);
+        assert!(matches!(check(&guard), AccessDecision::Deny(_)));
+    }
+
+    // 需要 root 权限，权限不足时跳过
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_access_guard_denies_infected_files() {
+        use crate::monitor::AccessGuard;
+
+        let dir = tempfile::tempdir().unwrap();
+        let clean = dir.path().join("clean.txt");
+        let infected = dir.path().join("infected.com");
+        std::fs::write(&clean, b"harmless").unwrap();
+        std::fs::write(&infected, eicar_test_string()).unwrap();
+
+        let signature_db = Arc::new(SignatureDatabase::new());
+        signature_db.load_builtin_signatures().await.unwrap();
+        let config = AccessControlConfig {
+            enabled: true,
+            paths: vec![AccessControlPath {
+                path: dir.path().to_string_lossy().to_string(),
+                block_exec: true,
+                block_open: true,
+            }],
+            ..AccessControlConfig::default()
+        };
+        let mut guard = AccessGuard::new(signature_db, config, 1024 * 1024);
+        if let Err(e) = guard.start() {
+            eprintln!("跳过: {:#}", e);
+            return;
+        }
+
+        // 本进程的访问直接放行，需要由子进程打开文件
+        let cat = |path: &PathBuf| std::process::Command::new("cat").arg(path).output().unwrap();
+        assert!(cat(&clean).status.success());
+        let denied = cat(&infected);
+        assert!(!denied.status.success());
+        assert!(std::fs::read(&infected).is_ok());
+
+        // 启动后新建的多级子目录同样受拦截
+        let nested = dir.path().join("a").join("b").join("infected.com");
+        std::fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        std::fs::write(&nested, eicar_test_string()).unwrap();
+        assert!(!cat(&nested).status.success());
+
+        guard.stop();
+        assert!(cat(&infected).status.success());
+        assert!(cat(&nested).status.success());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_access_rules_cover_nested_paths() {
+        use crate::monitor::fanotify::rules_mask;
+        use std::path::Path;
+
+        let rules = vec![
+            AccessControlPath { path: "/srv/upload".to_string(), block_exec: true, block_open: false },
+            AccessControlPath { path: "/srv/upload/private".to_string(), block_exec: false, block_open: true },
+        ];
+        assert_eq!(rules_mask(&rules, Path::new("/srv/upload/a.sh")), libc::FAN_OPEN_EXEC_PERM);
+        assert_eq!(rules_mask(&rules, Path::new("/srv/upload/x/y/z/a.sh")), libc::FAN_OPEN_EXEC_PERM);
+        assert_eq!(
+            rules_mask(&rules, Path::new("/srv/upload/private/deep/er/a.sh")),
+            libc::FAN_OPEN_EXEC_PERM | libc::FAN_OPEN_PERM
+        );
+        assert_eq!(rules_mask(&rules, Path::new("/srv/uploads/a.sh")), 0);
+        assert_eq!(rules_mask(&rules, Path::new("/etc/passwd")), 0);
+    }
+
+    #[test]
+    fn test_process_info_reads_proc() {
+        let info = crate::monitor::process::process_info(std::process::id()).unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert!(!info.command.is_empty());
+        let uid = nix::unistd::getuid().as_raw();
+        assert_eq!(info.user_id, uid);
+        if uid == 0 {
+            assert_eq!(info.user_name, "root");
+        }
+        assert!(crate::monitor::process::process_info(u32::MAX).is_none());
+    }
+
+    // 需要 root 权限，权限不足时跳过
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_monitor_events_carry_writer_process() {
+        if let Err(e) = crate::monitor::process::ProcessTracker::start() {
+            eprintln!("跳过: {:#}", e);
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let watched = dir.path().canonicalize().unwrap();
+
+        let events: Arc<Mutex<Vec<MonitorEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut monitor = FileMonitor::new();
+        let sink = Arc::clone(&events);
+        monitor.set_event_callback(Arc::new(move |event| sink.lock().unwrap().push(event)));
+        let mut config = monitor_config(&[&watched]);
+        config.events = vec!["create".to_string(), "modify".to_string()];
+        monitor.add_default_watches(&config).unwrap();
+        monitor.start().unwrap();
+
+        // 写入后保持运行，确保读取 /proc 时进程仍然存在
+        let script = format!("echo dropped > {:?}; sleep 1", watched.join("payload.sh"));
+        let mut child = std::process::Command::new("sh").arg("-c").arg(&script).spawn().unwrap();
+        let child_pid = child.id();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let find = || {
+            events
+                .lock()
+                .unwrap()
+                .iter()
+                .find_map(|e| e.process_info.clone().filter(|_| e.event_type == EventType::Modified))
+        };
+        while find().is_none() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        child.wait().unwrap();
+        monitor.stop();
+
+        let info = find().expect("修改事件应包含写入进程");
+        assert_eq!(info.pid, child_pid);
+        assert!(info.command.starts_with("sh -c"));
+        assert_eq!(info.user_id, nix::unistd::getuid().as_raw());
+    }
+
+    #[test]
+    fn test_polling_monitor_detects_changes() {
+        use crate::monitor::PollingMonitor;
+
+        let dir = tempfile::tempdir().unwrap();
+        let watched = dir.path().canonicalize().unwrap();
+        let existing = watched.join("existing.txt");
+        std::fs::write(&existing, b"v1").unwrap();
+
+        let events: Arc<Mutex<Vec<MonitorEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut monitor = PollingMonitor::new();
+        let sink = Arc::clone(&events);
+        monitor.set_event_callback(Arc::new(move |event| sink.lock().unwrap().push(event)));
+        let mut config = monitor_config(&[&watched]);
+        config.events = vec!["create".to_string(), "modify".to_string(), "delete".to_string()];
+        config.poll_interval_ms = 50;
+        assert_eq!(monitor.add_default_watches(&config).unwrap(), 1);
+        assert_eq!(monitor.get_watched_paths(), vec![watched.clone()]);
+        monitor.start().unwrap();
+
+        let created = watched.join("created.txt");
+        std::fs::write(&created, b"new").unwrap();
+        std::fs::write(&existing, b"version 2").unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while events.lock().unwrap().len() < 2 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::fs::remove_file(&created).unwrap();
+        while events.lock().unwrap().len() < 3 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        monitor.stop();
+
+        let events = events.lock().unwrap();
+        let has = |path: &PathBuf, event_type: EventType| {
+            events.iter().any(|e| &e.file_path == path && e.event_type == event_type && e.watch_path == watched)
+        };
+        assert!(has(&created, EventType::Created));
+        assert!(has(&existing, EventType::Modified));
+        assert!(has(&created, EventType::Deleted));
+    }
+
+    #[tokio::test]
+    async fn test_control_socket_status_and_stop() {
+        use crate::config::MonitorDaemonConfig;
+        use crate::monitor::{control, ControlRequest, ControlServer, MonitorHandle};
+
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = MonitorDaemonConfig {
+            pid_file: dir.path().join("run/monitor.pid"),
+            control_socket: dir.path().join("run/monitor.sock"),
+        };
+        assert!(control::query_status(&daemon).await.unwrap().is_none());
+
+        let server = ControlServer::bind(&daemon).unwrap();
+        assert_eq!(control::running_pid(&daemon.pid_file), Some(std::process::id()));
+        assert!(ControlServer::bind(&daemon).is_err());
+
+        let watched = dir.path().canonicalize().unwrap().join("watched");
+        let added = dir.path().canonicalize().unwrap().join("added");
+        std::fs::create_dir_all(&watched).unwrap();
+        std::fs::create_dir_all(&added).unwrap();
+        let config_path = dir.path().join("config.yaml");
+        let mut config = ScannerConfig::default();
+        config.monitor = monitor_config(&[&watched]);
+        config.save(&config_path).unwrap();
+
+        let monitor = FileMonitor::new();
+        monitor.add_default_watches(&config.monitor).unwrap();
+        let (events, _) = tokio::sync::broadcast::channel(control::EVENT_STREAM_CAPACITY);
+        let handle = Arc::new(
+            MonitorHandle::new(monitor, config.monitor.clone(), false)
+                .persist_to(config_path.clone())
+                .with_event_stream(events.clone()),
+        );
+        let served = tokio::spawn(async move {
+            server.serve(handle).await;
+        });
+
+        let reported = control::query_status(&daemon).await.unwrap().unwrap();
+        assert_eq!(reported.pid, std::process::id());
+        assert_eq!(reported.watch_paths, vec![watched.clone()]);
+        assert_eq!(reported.stats.watches, 1);
+        assert!(crate::api::render_metrics(Some(&reported)).contains("virus_scanner_monitor_watches 1\n"));
+        assert_eq!(crate::api::render_metrics(None), "# HELP virus_scanner_monitor_up Whether the file monitor is running\n# TYPE virus_scanner_monitor_up gauge\nvirus_scanner_monitor_up 0\n");
+
+        // 订阅连接保持打开，不影响后续的控制请求
+        let mut subscription = control::subscribe(&daemon.control_socket).await.unwrap();
+        events.send(event(EventType::Created, &watched.join("new.php"))).unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), subscription.next()).await.unwrap().unwrap().unwrap();
+        assert_eq!(received.event_type, EventType::Created);
+        assert_eq!(received.file_path, watched.join("new.php"));
+
+        let paths = control::request_watches(&daemon, &ControlRequest::AddWatch { path: added.clone() }).await.unwrap();
+        assert_eq!(paths, vec![added.clone(), watched.clone()]);
+        assert!(control::request_watches(&daemon, &ControlRequest::AddWatch { path: added.clone() }).await.is_err());
+        let paths = control::request_watches(&daemon, &ControlRequest::RemoveWatch { path: watched.clone() }).await.unwrap();
+        assert_eq!(paths, vec![added.clone()]);
+        let saved = ScannerConfig::load(&config_path).unwrap();
+        assert_eq!(saved.monitor.watch_paths, vec![added.display().to_string()]);
+
+        assert_eq!(control::stop_daemon(&daemon).await.unwrap(), Some(std::process::id()));
+        served.await.unwrap();
+        assert!(!daemon.pid_file.exists());
+        assert!(!daemon.control_socket.exists());
+    }
+
+    #[test]
+    fn test_event_journal_query_filters() {
+        use crate::config::MonitorJournalConfig;
+        use crate::monitor::{EventJournal, EventQuery};
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = MonitorJournalConfig::default();
+        let journal = EventJournal::new(dir.path().to_path_buf(), &config).unwrap();
+
+        let mut old = event(EventType::Created, std::path::Path::new("/var/www/old.php"));
+        old.timestamp = 100;
+        journal.record(&old).unwrap();
+        let mut recent = event(EventType::Modified, std::path::Path::new("/var/www/index.php"));
+        recent.timestamp = 200;
+        journal.record(&recent).unwrap();
+        let mut other = event(EventType::Deleted, std::path::Path::new("/tmp/x"));
+        other.timestamp = 300;
+        journal.record(&other).unwrap();
+        // 写入中断留下的半行不影响查询
+        let mut file = std::fs::OpenOptions::new().append(true).open(dir.path().join(&config.file_name)).unwrap();
+        file.write_all(b"{\"watch_path\":").unwrap();
+        drop(file);
+
+        let all = EventJournal::query(dir.path(), &config, &EventQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let query = EventQuery {
+            since: Some(150),
+            path: Some(PathBuf::from("/var/www")),
+            limit: None,
+        };
+        let matched = EventJournal::query(dir.path(), &config, &query).unwrap();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].file_path, PathBuf::from("/var/www/index.php"));
+        assert_eq!(matched[0].event_type, EventType::Modified);
+
+        let query = EventQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let latest = EventJournal::query(dir.path(), &config, &query).unwrap();
+        assert_eq!(latest.iter().map(|e| e.timestamp).collect::<Vec<_>>(), vec![200, 300]);
+    }
+
+    #[test]
+    fn test_event_filter_rules_and_rate_limit() {
+        use crate::config::{MonitorFilterConfig, WatchFilterRule};
+        use crate::monitor::EventFilter;
+
+        let config = MonitorFilterConfig {
+            exclude_extensions: vec![".SWP".to_string()],
+            exclude_patterns: vec!["**/.git/**".to_string()],
+            rules: vec![WatchFilterRule {
+                path: "/srv/www".to_string(),
+                extensions: vec!["php".to_string()],
+                exclude_patterns: vec!["/srv/www/cache/**".to_string()],
+                events: vec!["create".to_string()],
+            }],
+            dedup_window_ms: 60_000,
+            max_events_per_sec: 0,
+            burst: 0,
+        };
+        let filter = EventFilter::new(&config).unwrap();
+        let allow = |event_type: EventType, path: &str| filter.allow(&event(event_type, std::path::Path::new(path)));
+
+        assert!(allow(EventType::Modified, "/tmp/build/main.o"));
+        assert!(!allow(EventType::Modified, "/tmp/notes.txt.swp"));
+        assert!(!allow(EventType::Created, "/tmp/repo/.git/index"));
+        assert!(allow(EventType::Created, "/srv/www/shell.php"));
+        assert!(!allow(EventType::Modified, "/srv/www/shell.php"));
+        assert!(!allow(EventType::Created, "/srv/www/style.css"));
+        assert!(!allow(EventType::Created, "/srv/www/cache/page.php"));
+        assert_eq!(filter.stats().filtered, 5);
+
+        let modified = event(EventType::Modified, std::path::Path::new("/tmp/app.log"));
+        assert!(!filter.is_duplicate(&modified));
+        assert!(filter.is_duplicate(&modified));
+        assert!(!filter.is_duplicate(&event(EventType::Deleted, std::path::Path::new("/tmp/app.log"))));
+        assert_eq!(filter.stats().deduplicated, 1);
+
+        let limited = EventFilter::new(&MonitorFilterConfig {
+            max_events_per_sec: 1,
+            burst: 3,
+            ..MonitorFilterConfig::default()
+        })
+        .unwrap();
+        let allowed = (0..10)
+            .filter(|i| limited.allow(&event(EventType::Created, &PathBuf::from(format!("/tmp/build/{}.o", i)))))
+            .count();
+        assert_eq!(allowed, 3);
+        assert_eq!(limited.stats().rate_limited, 7);
+        // 其他监控目录有独立的配额
+        assert!(limited.allow(&event(EventType::Created, std::path::Path::new("/var/tmp/a.o"))));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::config::LoggingConfig;
+use crate::report::ThreatReport;
+use crate::utils::logging::{RotatingFileWriter, RotationPolicy};
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Mutex;
+
+#[derive(Serialize)]
+struct DetectionRecord<'a> {
+    scan_id: &'a str,
+    #[serde(flatten)]
+    threat: &'a ThreatReport,
+}
+
+pub struct DetectionLogger {
+    writer: Mutex<RotatingFileWriter>,
+}
+
+impl DetectionLogger {
+    pub fn new(config: &LoggingConfig) -> Result<Self, anyhow::Error> {
+        let detection = &config.detection_log;
+        let policy = RotationPolicy {
+            max_size_bytes: detection.max_size_mb.max(1) * 1024 * 1024,
+            max_files: detection.max_files,
+            daily: false,
+            compress: detection.compress_rotated,
+        };
+        let writer = RotatingFileWriter::new(config.log_dir.clone(), &detection.file_name, policy)?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    // 无法打开日志文件时只记录错误，检测记录不影响扫描
+    pub fn open_or_warn(config: &LoggingConfig) -> Option<Self> {
+        Self::new(config).map_err(|e| log::error!("无法打开检测日志: {}", e)).ok()
+    }
+
+    // 检测记录独立于常规日志级别，始终写入
+    pub fn log_threat(&self, scan_id: &str, threat: &ThreatReport) -> Result<(), anyhow::Error> {
+        let mut line = serde_json::to_string(&DetectionRecord { scan_id, threat })?;
+        line.push('\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(line.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn log_threats(&self, scan_id: &str, threats: &[ThreatReport]) {
+        for threat in threats {
+            if let Err(e) = self.log_threat(scan_id, threat) {
+                log::error!("无法写入检测日志: {}", e);
+            }
+        }
+    }
+}
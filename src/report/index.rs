@@ -0,0 +1,161 @@
+use crate::report::{ReportFormat, ScanReport};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// 报告目录中的索引文件，每保存一个报告文件追加一行 JSON
+pub const REPORT_INDEX_FILE: &str = "index.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportIndexEntry {
+    pub id: String,
+    pub timestamp: DateTime<Local>,
+    pub scan_type: String,
+    pub files_scanned: u64,
+    pub threats: u64,
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl ReportIndexEntry {
+    pub fn new(report: &ScanReport, format: ReportFormat, path: PathBuf) -> Self {
+        Self {
+            id: report.id.clone(),
+            timestamp: report.timestamp,
+            scan_type: report.scan_type.clone(),
+            files_scanned: report.summary.total_files_scanned,
+            threats: report.summary.total_threats,
+            format,
+            path,
+        }
+    }
+}
+
+// 同一报告可能以多种格式保存，每种格式一条记录
+pub struct ReportIndex {
+    dir: PathBuf,
+}
+
+impl ReportIndex {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(REPORT_INDEX_FILE)
+    }
+
+    // 整行一次写入，多个进程同时保存报告时记录不会交错
+    pub fn append(&self, entry: &ReportIndexEntry) -> Result<(), anyhow::Error> {
+        let path = self.path();
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("无法打开报告索引: {:?}", path))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    // 按保存顺序返回报告文件仍然存在的记录，跳过无法解析的行。索引不存在时 (本版本之前生成的报告)
+    // 从目录中的 JSON 和 YAML 报告重建
+    pub fn load(&self) -> Result<Vec<ReportIndexEntry>, anyhow::Error> {
+        let path = self.path();
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return self.rebuild(),
+            Err(e) => return Err(e).with_context(|| format!("无法读取报告索引: {:?}", path)),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<ReportIndexEntry>(line).ok())
+            .filter(|entry| entry.path.exists())
+            .collect())
+    }
+
+    // 每个报告一条记录，按时间从新到旧排列。同一报告有多个文件时使用最后保存的文件
+    pub fn list(&self) -> Result<Vec<ReportIndexEntry>, anyhow::Error> {
+        let mut latest: HashMap<String, ReportIndexEntry> = HashMap::new();
+        for entry in self.load()? {
+            latest.insert(entry.id.clone(), entry);
+        }
+        let mut entries: Vec<ReportIndexEntry> = latest.into_values().collect();
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+        Ok(entries)
+    }
+
+    // 报告 ID 不区分大小写，也可以只写唯一的前缀。返回该报告的全部文件
+    pub fn find(&self, id: &str) -> Result<Vec<ReportIndexEntry>, anyhow::Error> {
+        let entries = self.load()?;
+        let id = id.to_uppercase();
+        let exact: Vec<ReportIndexEntry> = entries.iter().filter(|entry| entry.id.to_uppercase() == id).cloned().collect();
+        if !exact.is_empty() {
+            return Ok(exact);
+        }
+        let matched: Vec<ReportIndexEntry> = entries.into_iter().filter(|entry| entry.id.to_uppercase().starts_with(&id)).collect();
+        let mut ids: Vec<&str> = matched.iter().map(|entry| entry.id.as_str()).collect();
+        ids.sort();
+        ids.dedup();
+        match ids.len() {
+            0 => Err(anyhow::anyhow!("未找到报告: {}", id)),
+            1 => Ok(matched),
+            _ => Err(anyhow::anyhow!("报告 ID 前缀 {} 不唯一: {}", id, ids.join(", "))),
+        }
+    }
+
+    fn rebuild(&self) -> Result<Vec<ReportIndexEntry>, anyhow::Error> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Ok(Vec::new());
+        };
+        let mut entries: Vec<ReportIndexEntry> = dir
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().map_or(false, |name| name.to_string_lossy().starts_with("report_")))
+            .filter_map(|path| {
+                let format = path.extension()?.to_str()?.parse::<ReportFormat>().ok()?;
+                let report = load_report(&path, format).ok()?;
+                Some(ReportIndexEntry::new(&report, format, path))
+            })
+            .collect();
+        entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(entries)
+    }
+}
+
+// 只能读取 JSON 和 YAML 报告，HTML 和文本报告无法还原
+pub fn load_report(path: &Path, format: ReportFormat) -> Result<ScanReport, anyhow::Error> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("无法读取报告文件: {:?}", path))?;
+    let report = match format {
+        ReportFormat::Json => serde_json::from_str(&content).with_context(|| format!("无法解析 JSON 报告: {:?}", path))?,
+        ReportFormat::Yaml => serde_yaml::from_str(&content).with_context(|| format!("无法解析 YAML 报告: {:?}", path))?,
+        ReportFormat::Html | ReportFormat::Text => {
+            return Err(anyhow::anyhow!("{:?} 为 {} 格式，无法读取报告内容", path, format.extension()))
+        }
+    };
+    Ok(report)
+}
+
+// 按扩展名判断格式，扩展名无法判断时按内容判断 (以 { 开头为 JSON，否则为 YAML)
+pub fn read_report(path: &Path) -> Result<(ScanReport, ReportFormat), anyhow::Error> {
+    let by_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| extension.parse::<ReportFormat>().ok());
+    let format = match by_extension {
+        Some(format) => format,
+        None => {
+            let content = std::fs::read_to_string(path).with_context(|| format!("无法读取报告文件: {:?}", path))?;
+            if content.trim_start().starts_with('{') {
+                ReportFormat::Json
+            } else {
+                ReportFormat::Yaml
+            }
+        }
+    };
+    Ok((load_report(path, format)?, format))
+}
@@ -1,4 +1,10 @@
-use crate::scanner::{ScanResult, ThreatType, RiskLevel};
+pub mod detection_log;
+pub mod index;
+pub mod threat_store;
+
+use crate::scanner::{RootkitFinding, ScanResult, ThreatType, RiskLevel};
+use crate::t;
+use crate::utils::{ensure_free_space, format_duration_secs, get_file_digests};
 use anyhow::Context;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
@@ -6,6 +12,10 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Instant;
 
+pub use detection_log::DetectionLogger;
+pub use index::{load_report, read_report, ReportIndex, ReportIndexEntry};
+pub use threat_store::{StoredThreat, ThreatPage, ThreatQuery, ThreatStore};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanReport {
     pub id: String,
@@ -16,6 +26,17 @@ pub struct ScanReport {
     pub threats: Vec<ThreatReport>,
     pub recommendations: Vec<String>,
     pub system_info: SystemInfo,
+    #[serde(default)]
+    pub rootkit_findings: Vec<RootkitFinding>,
+}
+
+impl ScanReport {
+    pub fn add_rootkit_findings(&mut self, findings: Vec<RootkitFinding>) {
+        if !findings.is_empty() {
+            self.recommendations.insert(0, t!("recommendation.rootkit", count = findings.len()));
+        }
+        self.rootkit_findings.extend(findings);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +48,18 @@ pub struct ReportSummary {
     pub scan_duration: u64,
     pub scan_speed_mb_s: f64,
     pub memory_peak_mb: f64,
+    #[serde(default)]
+    pub detection_groups: Vec<DetectionGroup>,
+}
+
+// 同一签名命中相同内容的文件出现在多个目录时归为一组，只记录出现多次的检测
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionGroup {
+    pub signature_id: String,
+    pub detection_name: String,
+    pub sha256: Option<String>,
+    pub count: u64,
+    pub file_paths: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,9 +73,47 @@ pub struct ThreatReport {
     pub file_info: FileReportInfo,
     pub action_taken: Option<String>,
     pub timestamp: DateTime<Local>,
+    #[serde(default)]
+    pub archive_member: Option<String>,
+    #[serde(default)]
+    pub heuristic_score: Option<u8>,
+}
+
+impl ThreatReport {
+    // 计算文件摘要时读取整个文件，文件已被隔离或删除时摘要为 None
+    pub fn from_result(id: String, result: &ScanResult, include_file_hashes: bool) -> Self {
+        let digests = if include_file_hashes {
+            get_file_digests(&result.file_path).ok()
+        } else {
+            None
+        };
+
+        ThreatReport {
+            id,
+            file_path: result.file_path.clone(),
+            threat_type: format!("{:?}", result.threat_type),
+            risk_level: format!("{:?}", result.risk_level),
+            signature_id: result.signature_id.clone(),
+            detection_name: format!("Malware.{}", result.signature_id),
+            file_info: FileReportInfo {
+                size: result.file_info.size,
+                permissions: result.file_info.permissions.clone(),
+                created: result.file_info.created,
+                modified: result.file_info.modified,
+                md5: digests.as_ref().map(|d| d.md5.clone()),
+                sha256: digests.as_ref().map(|d| d.sha256.clone()),
+                file_type: Some(result.file_info.file_kind.to_string()),
+            },
+            action_taken: result.action_taken.clone(),
+            timestamp: Local::now(),
+            archive_member: result.archive_member.clone(),
+            heuristic_score: result.heuristic_score,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct FileReportInfo {
     pub size: u64,
     pub permissions: String,
@@ -50,6 +121,8 @@ pub struct FileReportInfo {
     pub modified: Option<u64>,
     pub md5: Option<String>,
     pub sha256: Option<String>,
+    #[serde(default)]
+    pub file_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,10 +156,14 @@ impl ReportGenerator {
         Self {
             output_dir,
             include_system_info: true,
-            include_file_hashes: false,
+            include_file_hashes: true,
         }
     }
 
+    pub fn set_include_file_hashes(&mut self, include: bool) {
+        self.include_file_hashes = include;
+    }
+
     pub fn generate(
         &self,
         results: &[ScanResult],
@@ -105,26 +182,11 @@ impl ReportGenerator {
         let threat_reports: Vec<ThreatReport> = results
             .iter()
             .enumerate()
-            .map(|(i, result)| ThreatReport {
-                id: format!("THR{:08}", i + 1),
-                file_path: result.file_path.clone(),
-                threat_type: format!("{:?}", result.threat_type),
-                risk_level: format!("{:?}", result.risk_level),
-                signature_id: result.signature_id.clone(),
-                detection_name: self.get_detection_name(&result.signature_id),
-                file_info: FileReportInfo {
-                    size: result.file_info.size,
-                    permissions: result.file_info.permissions.clone(),
-                    created: result.file_info.created,
-                    modified: result.file_info.modified,
-                    md5: None,
-                    sha256: None,
-                },
-                action_taken: None,
-                timestamp: Local::now(),
-            })
+            .map(|(i, result)| ThreatReport::from_result(format!("THR{:08}", i + 1), result, self.include_file_hashes))
             .collect();
 
+        let detection_groups = Self::group_detections(&threat_reports);
+
         let system_info = if self.include_system_info {
             self.get_system_info(database_version)
         } else {
@@ -151,10 +213,12 @@ impl ReportGenerator {
                 scan_duration: duration.elapsed().as_secs(),
                 scan_speed_mb_s: scan_speed,
                 memory_peak_mb: memory_peak,
+                detection_groups,
             },
             threats: threat_reports,
             recommendations,
             system_info,
+            rootkit_findings: Vec::new(),
         };
 
         Ok(report)
@@ -164,38 +228,41 @@ impl ReportGenerator {
         std::fs::create_dir_all(&self.output_dir)
             .context(format!("无法创建报告目录: {:?}", self.output_dir))?;
         
-        let filename = format!("report_{}.{}", report.timestamp.format("%Y%m%d_%H%M%S"), format.extension());
+        // 文件名带报告 ID，同一秒内生成的报告不会互相覆盖
+        let filename = format!("report_{}_{}.{}", report.timestamp.format("%Y%m%d_%H%M%S"), report.id, format.extension());
         let filepath = self.output_dir.join(&filename);
 
-        match format {
-            ReportFormat::Json => {
-                let json = serde_json::to_string_pretty(report)?;
-                std::fs::write(&filepath, json)?;
-            }
-            ReportFormat::Yaml => {
-                let yaml = serde_yaml::to_string(report)?;
-                std::fs::write(&filepath, yaml)?;
-            }
-            ReportFormat::Html => {
-                let html = self.render_html(report);
-                std::fs::write(&filepath, html)?;
-            }
-            ReportFormat::Text => {
-                let text = self.render_text(report);
-                std::fs::write(&filepath, text)?;
-            }
+        let content = self.render(report, format)?;
+
+        ensure_free_space(&self.output_dir, content.len() as u64)?;
+        std::fs::write(&filepath, content)?;
+
+        // 索引只用于 report list 和 report show，写入失败不影响报告
+        let entry = ReportIndexEntry::new(report, format, filepath.clone());
+        if let Err(e) = ReportIndex::new(self.output_dir.clone()).append(&entry) {
+            log::warn!("无法更新报告索引: {:#}", e);
         }
 
         log::info!("报告已保存: {:?}", filepath);
         Ok(filepath)
     }
 
+    pub fn render(&self, report: &ScanReport, format: ReportFormat) -> Result<String, anyhow::Error> {
+        Ok(match format {
+            ReportFormat::Json => serde_json::to_string_pretty(report)?,
+            ReportFormat::Yaml => serde_yaml::to_string(report)?,
+            ReportFormat::Html => self.render_html(report),
+            ReportFormat::Text => self.render_text(report),
+        })
+    }
+
     fn render_html(&self, report: &ScanReport) -> String {
         format!(
             r#"<!DOCTYPE html>
-<html>
+<html lang="{}">
 <head>
-    <title>病毒扫描报告 - {}</title>
+    <meta charset="utf-8">
+    <title>{} - {}</title>
     <style>
         body {{ font-family: Arial, sans-serif; margin: 20px; }}
         .header {{ background: #2c3e50; color: white; padding: 20px; }}
@@ -207,65 +274,97 @@ impl ReportGenerator {
 </head>
 <body>
     <div class="header">
-        <h1>病毒扫描报告</h1>
-        <p>扫描时间: {}</p>
-        <p>扫描类型: {}</p>
+        <h1>{}</h1>
+        <p>{}</p>
+        <p>{}</p>
     </div>
     <div class="summary">
-        <h2>扫描摘要</h2>
-        <p>扫描文件数: {}</p>
-        <p>发现威胁: {}</p>
-        <p>扫描时长: {}秒</p>
+        <h2>{}</h2>
+        <p>{}</p>
+        <p>{}</p>
+        <p>{}</p>
     </div>
 </body>
 </html>"#,
+            crate::utils::i18n::language().as_str(),
+            t!("report.title"),
             report.id,
-            report.timestamp,
-            report.scan_type,
-            report.summary.total_files_scanned,
-            report.summary.total_threats,
-            report.summary.scan_duration
+            t!("report.title"),
+            t!("report.scan_time", time = report.timestamp),
+            t!("report.scan_type", scan_type = report.scan_type),
+            t!("report.summary"),
+            t!("report.files_scanned", count = report.summary.total_files_scanned),
+            t!("report.threats_found", count = report.summary.total_threats),
+            t!("report.duration", duration = format_duration_secs(report.summary.scan_duration))
         )
     }
 
     fn render_text(&self, report: &ScanReport) -> String {
         let mut text = format!(
-            r#"病毒扫描报告
-===============
-扫描ID: {}
-扫描时间: {}
-扫描类型: {}
-
-扫描摘要
---------
-扫描文件数: {}
-发现威胁: {}
-扫描时长: {}秒
-扫描速度: {:.2} MB/s
-
-威胁列表
---------
-"#,
-            report.id,
-            report.timestamp,
-            report.scan_type,
-            report.summary.total_files_scanned,
-            report.summary.total_threats,
-            report.summary.scan_duration,
-            report.summary.scan_speed_mb_s
+            "{}\n===============\n{}\n{}\n{}\n\n{}\n--------\n{}\n{}\n{}\n{}\n\n{}\n--------\n",
+            t!("report.title"),
+            t!("report.id", id = report.id),
+            t!("report.scan_time", time = report.timestamp),
+            t!("report.scan_type", scan_type = report.scan_type),
+            t!("report.summary"),
+            t!("report.files_scanned", count = report.summary.total_files_scanned),
+            t!("report.threats_found", count = report.summary.total_threats),
+            t!("report.duration", duration = format_duration_secs(report.summary.scan_duration)),
+            t!("report.speed", speed = format!("{:.2}", report.summary.scan_speed_mb_s)),
+            t!("report.threat_list")
         );
 
         for threat in &report.threats {
+            let member = threat
+                .archive_member
+                .as_ref()
+                .map(|m| format!("  {}\n", t!("report.archive_member", member = m)))
+                .unwrap_or_default();
+            let score = threat
+                .heuristic_score
+                .map(|s| format!("  {}\n", t!("report.heuristic_score", score = s)))
+                .unwrap_or_default();
             text.push_str(&format!(
-                "- 文件: {:?}\n  类型: {}\n  风险等级: {}\n  签名ID: {}\n\n",
-                threat.file_path,
-                threat.threat_type,
-                threat.risk_level,
-                threat.signature_id
+                "- {}\n{}  {}\n  {}\n  {}\n{}  {}\n\n",
+                t!("report.file", path = format!("{:?}", threat.file_path)),
+                member,
+                t!("report.threat_type", threat_type = threat.threat_type),
+                t!("report.risk_level", risk_level = threat.risk_level),
+                t!("report.signature_id", id = threat.signature_id),
+                score,
+                t!("report.file_type", file_type = threat.file_info.file_type.as_deref().unwrap_or("Unknown"))
             ));
         }
 
-        text.push_str("\n处理建议\n--------\n");
+        if !report.summary.detection_groups.is_empty() {
+            text.push_str(&format!("\n{}\n------------\n", t!("report.detection_groups")));
+            for group in &report.summary.detection_groups {
+                text.push_str(&format!("- {}\n", t!("report.detection_group", name = group.detection_name, count = group.count)));
+                for path in &group.file_paths {
+                    text.push_str(&format!("  {:?}\n", path));
+                }
+            }
+        }
+
+        if !report.rootkit_findings.is_empty() {
+            text.push_str(&format!("\n{}\n------------\n", t!("report.rootkit_checks")));
+            for finding in &report.rootkit_findings {
+                let path = finding
+                    .path
+                    .as_ref()
+                    .map(|p| format!("  {}\n", t!("report.path", path = format!("{:?}", p))))
+                    .unwrap_or_default();
+                text.push_str(&format!(
+                    "- [{}] {}\n  {}\n{}",
+                    finding.check.as_str(),
+                    finding.description,
+                    t!("report.risk_level", risk_level = format!("{:?}", finding.risk_level)),
+                    path
+                ));
+            }
+        }
+
+        text.push_str(&format!("\n{}\n--------\n", t!("report.recommendations")));
         for rec in &report.recommendations {
             text.push_str(&format!("- {}\n", rec));
         }
@@ -273,6 +372,44 @@ impl ReportGenerator {
         text
     }
 
+    // 没有文件哈希时按签名和文件大小判断内容是否相同
+    fn group_detections(threats: &[ThreatReport]) -> Vec<DetectionGroup> {
+        let mut groups: Vec<DetectionGroup> = Vec::new();
+        let mut index: HashMap<(&str, Option<&str>, u64, Option<&str>), usize> = HashMap::new();
+
+        for threat in threats {
+            let key = (
+                threat.signature_id.as_str(),
+                threat.file_info.sha256.as_deref(),
+                threat.file_info.size,
+                threat.archive_member.as_deref(),
+            );
+            match index.get(&key) {
+                Some(&i) => {
+                    let group = &mut groups[i];
+                    group.count += 1;
+                    if !group.file_paths.contains(&threat.file_path) {
+                        group.file_paths.push(threat.file_path.clone());
+                    }
+                }
+                None => {
+                    index.insert(key, groups.len());
+                    groups.push(DetectionGroup {
+                        signature_id: threat.signature_id.clone(),
+                        detection_name: threat.detection_name.clone(),
+                        sha256: threat.file_info.sha256.clone(),
+                        count: 1,
+                        file_paths: vec![threat.file_path.clone()],
+                    });
+                }
+            }
+        }
+
+        groups.retain(|group| group.file_paths.len() > 1);
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.signature_id.cmp(&b.signature_id)));
+        groups
+    }
+
     fn count_threats_by_type(results: &[ScanResult]) -> HashMap<String, u64> {
         let mut counts = HashMap::new();
         for result in results {
@@ -305,10 +442,6 @@ impl ReportGenerator {
         format!("RPT{:08}", rand::random::<u32>())
     }
 
-    fn get_detection_name(&self, signature_id: &str) -> String {
-        format!("Malware.{}", signature_id)
-    }
-
     fn get_system_info(&self, database_version: String) -> SystemInfo {
         let uname = nix::sys::utsname::uname().unwrap();
         SystemInfo {
@@ -326,28 +459,23 @@ impl ReportGenerator {
 
         let critical_count = results.iter().filter(|r| r.risk_level == RiskLevel::Critical).count();
         if critical_count > 0 {
-            recommendations.push(format!(
-                "发现 {} 个高危威胁，请立即隔离并清除受影响文件",
-                critical_count
-            ));
+            recommendations.push(t!("recommendation.critical", count = critical_count));
         }
 
         let virus_count = results.iter().filter(|r| r.threat_type == ThreatType::Virus).count();
         if virus_count > 0 {
-            recommendations.push(format!(
-                "发现 {} 个病毒，请使用最新病毒库进行全盘扫描",
-                virus_count
-            ));
+            recommendations.push(t!("recommendation.virus", count = virus_count));
         }
 
-        recommendations.push("建议定期更新病毒库以确保检测能力".to_string());
-        recommendations.push("建议启用实时文件监控功能".to_string());
+        recommendations.push(t!("recommendation.update_regularly").to_string());
+        recommendations.push(t!("recommendation.enable_monitor").to_string());
 
         recommendations
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ReportFormat {
     Json,
     Yaml,
@@ -365,3 +493,20 @@ impl ReportFormat {
         }
     }
 }
+
+impl std::str::FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "yaml" | "yml" => Ok(ReportFormat::Yaml),
+            "html" => Ok(ReportFormat::Html),
+            "text" | "txt" => Ok(ReportFormat::Text),
+            _ => Err(anyhow::anyhow!("无效的报告格式: {} (可选 json, yaml, html, text)", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,211 @@
+use crate::config::ScannerConfig;
+use crate::report::{DetectionLogger, FileReportInfo, ReportFormat, ReportGenerator, ReportIndex, ThreatQuery, ThreatReport, ThreatStore};
+use crate::scanner::{FileInfo, RiskLevel, ScanResult, ThreatType};
+use crate::t;
+use crate::utils::FileKind;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_threat() -> ThreatReport {
+        ThreatReport {
+            id: "t-1".to_string(),
+            file_path: PathBuf::from("/tmp/eicar.com"),
+            threat_type: "Virus".to_string(),
+            risk_level: "High".to_string(),
+            signature_id: "sig-1".to_string(),
+            detection_name: "Eicar-Test-Signature".to_string(),
+            file_info: FileReportInfo {
+                size: 68,
+                permissions: "rw-r--r--".to_string(),
+                created: None,
+                modified: None,
+                md5: Some("44d88612fea8a8f36de82e1278abb02f".to_string()),
+                sha256: None,
+                file_type: Some("text".to_string()),
+            },
+            action_taken: Some("quarantined".to_string()),
+            timestamp: Local::now(),
+            archive_member: None,
+            heuristic_score: None,
+        }
+    }
+
+    #[test]
+    fn test_detection_log_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ScannerConfig::default().logging;
+        config.log_dir = dir.path().to_path_buf();
+
+        let logger = DetectionLogger::new(&config).unwrap();
+        logger.log_threats("scan-1", &[sample_threat(), sample_threat()]);
+
+        let content = std::fs::read_to_string(dir.path().join(&config.detection_log.file_name)).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["scan_id"], "scan-1");
+        assert_eq!(record["detection_name"], "Eicar-Test-Signature");
+        assert_eq!(record["action_taken"], "quarantined");
+        assert_eq!(record["file_info"]["md5"], "44d88612fea8a8f36de82e1278abb02f");
+    }
+
+    fn detection(path: &Path, signature_id: &str) -> ScanResult {
+        ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: ThreatType::Virus,
+            risk_level: RiskLevel::High,
+            signature_id: signature_id.to_string(),
+            file_info: FileInfo {
+                size: std::fs::metadata(path).unwrap().len(),
+                permissions: "644".to_string(),
+                created: None,
+                modified: None,
+                accessed: None,
+                file_kind: FileKind::Unknown,
+            },
+            archive_member: None,
+            heuristic_score: None,
+            action_taken: None,
+        }
+    }
+
+    #[test]
+    fn test_report_groups_identical_detections() {
+        let dir = tempfile::tempdir().unwrap();
+        for (file, content) in [("a/x.bin", "payload"), ("b/x.bin", "payload"), ("c/y.bin", "payloae")] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let results = vec![
+            detection(&dir.path().join("a/x.bin"), "sig-1"),
+            detection(&dir.path().join("b/x.bin"), "sig-1"),
+            detection(&dir.path().join("c/y.bin"), "sig-1"),
+            detection(&dir.path().join("c/y.bin"), "sig-2"),
+        ];
+
+        let generator = ReportGenerator::new(dir.path().join("reports"));
+        let report = generator
+            .generate(&results, "custom", &[], Instant::now(), 0.0, "1".to_string())
+            .unwrap();
+        let groups = &report.summary.detection_groups;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].signature_id, "sig-1");
+        assert_eq!(groups[0].count, 2);
+        assert_eq!(groups[0].file_paths, vec![dir.path().join("a/x.bin"), dir.path().join("b/x.bin")]);
+        assert!(generator.render_text(&report).contains(t!("report.detection_groups")));
+    }
+
+    #[test]
+    fn test_threat_store_queries_and_compacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ThreatStore::new(dir.path().join("store/threats.jsonl"), 10);
+
+        let mut older = sample_threat();
+        older.timestamp = Local::now() - chrono::Duration::hours(2);
+        older.file_path = PathBuf::from("/home/user/old.exe");
+        older.risk_level = "Low".to_string();
+        let mut recent = sample_threat();
+        recent.timestamp = Local::now() - chrono::Duration::minutes(10);
+        let mut trojan = sample_threat();
+        trojan.threat_type = "Trojan".to_string();
+        store.record("RPT1", "scan", &[older, recent]).unwrap();
+        let stored = store.record("SCN1", "api", &[trojan]).unwrap();
+
+        let all = store.query(&ThreatQuery::default()).unwrap();
+        assert_eq!(all.total, 3);
+        assert_eq!(all.threats[0].scan_id, "SCN1");
+        assert_eq!(all.threats[2].file_path, PathBuf::from("/home/user/old.exe"));
+
+        let high = store
+            .query(&ThreatQuery {
+                risk_level: Some("high".to_string()),
+                since: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(high.total, 2);
+        let trojans = store
+            .query(&ThreatQuery {
+                threat_type: Some("trojan".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(trojans.threats[0].id, stored[0].id);
+        let home = store
+            .query(&ThreatQuery {
+                path_prefix: Some(PathBuf::from("/home")),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(home.total, 1);
+        let page = store
+            .query(&ThreatQuery {
+                limit: Some(1),
+                offset: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((page.total, page.threats.len()), (3, 1));
+        assert_eq!(page.threats[0].scan_id, "RPT1");
+
+        assert_eq!(store.get(&stored[0].id).unwrap().unwrap().threat_type, "Trojan");
+        assert!(store.get("THR-missing").unwrap().is_none());
+
+        // 超出上限后只保留最新的记录
+        for _ in 0..9 {
+            store.record("RPT2", "scan", &[sample_threat()]).unwrap();
+        }
+        let remaining = store.load().unwrap();
+        assert_eq!(remaining.len(), 10);
+        assert!(remaining.iter().all(|threat| threat.scan_id != "RPT1"));
+    }
+
+    #[test]
+    fn test_report_index_lists_and_finds_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let generator = ReportGenerator::new(dir.path().to_path_buf());
+        let mut first = generator.generate(&[], "Quick", &[], Instant::now(), 0.0, "1".to_string()).unwrap();
+        first.id = "RPT00000001".to_string();
+        first.timestamp = Local::now() - chrono::Duration::hours(1);
+        let mut second = generator.generate(&[], "scheduled:nightly", &[], Instant::now(), 0.0, "1".to_string()).unwrap();
+        second.id = "RPT00000002".to_string();
+        generator.save(&first, ReportFormat::Json).unwrap();
+        generator.save(&first, ReportFormat::Html).unwrap();
+        generator.save(&second, ReportFormat::Text).unwrap();
+
+        let index = ReportIndex::new(dir.path().to_path_buf());
+        let reports = index.list().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].id, "RPT00000002");
+        assert_eq!(reports[0].scan_type, "scheduled:nightly");
+        assert_eq!(reports[1].format, ReportFormat::Html);
+
+        let files = index.find("rpt00000001").unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(crate::report::load_report(&files[0].path, files[0].format).unwrap().id, "RPT00000001");
+        assert!(index.find("RPT0000000").is_err());
+        assert!(index.find("RPT9").is_err());
+
+        // 报告文件删除后不再列出；没有索引时从 JSON 报告重建
+        std::fs::remove_file(&files[1].path).unwrap();
+        assert_eq!(index.find("RPT00000001").unwrap().len(), 1);
+        std::fs::remove_file(index.path()).unwrap();
+        let rebuilt = index.list().unwrap();
+        assert_eq!(rebuilt.len(), 1);
+        assert_eq!(rebuilt[0].id, "RPT00000001");
+
+        // 扩展名无法识别时按内容判断格式
+        let yaml = dir.path().join("exported.report");
+        std::fs::write(&yaml, generator.render(&second, ReportFormat::Yaml).unwrap()).unwrap();
+        let (report, format) = crate::report::read_report(&yaml).unwrap();
+        assert_eq!((report.id.as_str(), format), ("RPT00000002", ReportFormat::Yaml));
+        assert!(crate::report::read_report(&rebuilt[0].path.with_extension("html")).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::ThreatStoreConfig;
+use crate::report::{FileReportInfo, ThreatReport};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct StoredThreat {
+    pub id: String,
+    // 发现该威胁的扫描报告或扫描任务，实时监控为 "monitor"
+    pub scan_id: String,
+    // scan、api 或 monitor
+    pub source: String,
+    pub detected_at: DateTime<Utc>,
+    pub file_path: PathBuf,
+    pub threat_type: String,
+    pub risk_level: String,
+    pub signature_id: String,
+    pub detection_name: String,
+    pub file_info: FileReportInfo,
+    pub action_taken: Option<String>,
+    #[serde(default)]
+    pub archive_member: Option<String>,
+    #[serde(default)]
+    pub heuristic_score: Option<u8>,
+}
+
+impl StoredThreat {
+    pub fn new(scan_id: &str, source: &str, threat: &ThreatReport) -> Self {
+        Self {
+            id: format!("THR{:016x}", rand::random::<u64>()),
+            scan_id: scan_id.to_string(),
+            source: source.to_string(),
+            detected_at: threat.timestamp.with_timezone(&Utc),
+            file_path: threat.file_path.clone(),
+            threat_type: threat.threat_type.clone(),
+            risk_level: threat.risk_level.clone(),
+            signature_id: threat.signature_id.clone(),
+            detection_name: threat.detection_name.clone(),
+            file_info: threat.file_info.clone(),
+            action_taken: threat.action_taken.clone(),
+            archive_member: threat.archive_member.clone(),
+            heuristic_score: threat.heuristic_score,
+        }
+    }
+}
+
+// 查询条件，未指定的项不过滤。风险等级和威胁类型不区分大小写
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub risk_level: Option<String>,
+    pub threat_type: Option<String>,
+    pub path_prefix: Option<PathBuf>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl ThreatQuery {
+    fn matches(&self, threat: &StoredThreat) -> bool {
+        self.since.map_or(true, |since| threat.detected_at >= since)
+            && self.until.map_or(true, |until| threat.detected_at < until)
+            && self
+                .risk_level
+                .as_ref()
+                .map_or(true, |level| threat.risk_level.eq_ignore_ascii_case(level))
+            && self
+                .threat_type
+                .as_ref()
+                .map_or(true, |kind| threat.threat_type.eq_ignore_ascii_case(kind))
+            && self
+                .path_prefix
+                .as_ref()
+                .map_or(true, |prefix| threat.file_path.starts_with(prefix))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub struct ThreatPage {
+    // 符合条件的记录总数
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    // 按发现时间从新到旧排列
+    pub threats: Vec<StoredThreat>,
+}
+
+// 每行一条 JSON 记录，以追加方式写入，扫描命令、API 和监控进程可以同时写入。
+// 记录数超出上限一定比例后整理文件，只保留最新的记录
+pub struct ThreatStore {
+    path: PathBuf,
+    max_records: usize,
+    // 本进程已知的记录数，首次写入时统计
+    records: Mutex<Option<usize>>,
+}
+
+impl ThreatStore {
+    pub fn new(path: PathBuf, max_records: usize) -> Self {
+        Self {
+            path,
+            max_records: max_records.max(1),
+            records: Mutex::new(None),
+        }
+    }
+
+    pub fn from_config(config: &ThreatStoreConfig) -> Self {
+        Self::new(config.path.clone(), config.max_records)
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn record(&self, scan_id: &str, source: &str, threats: &[ThreatReport]) -> Result<Vec<StoredThreat>, anyhow::Error> {
+        if threats.is_empty() {
+            return Ok(Vec::new());
+        }
+        let stored: Vec<StoredThreat> = threats.iter().map(|threat| StoredThreat::new(scan_id, source, threat)).collect();
+        let mut lines = String::new();
+        for threat in &stored {
+            lines.push_str(&serde_json::to_string(threat)?);
+            lines.push('\n');
+        }
+
+        let mut records = self.records.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+        }
+        let known = match *records {
+            Some(count) => count,
+            None => self.load()?.len(),
+        };
+        // 一次写入整批记录，避免与其他进程的记录交错
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("无法打开威胁记录: {:?}", self.path))?;
+        file.write_all(lines.as_bytes())?;
+        file.flush()?;
+
+        let count = known + stored.len();
+        *records = Some(if count > self.max_records + self.max_records / 10 {
+            self.compact()?
+        } else {
+            count
+        });
+        Ok(stored)
+    }
+
+    // 出错时只记录日志，不影响扫描，返回未保存的记录用于告警
+    pub fn record_or_warn(&self, scan_id: &str, source: &str, threats: &[ThreatReport]) -> Vec<StoredThreat> {
+        self.record(scan_id, source, threats).unwrap_or_else(|e| {
+            log::error!("无法写入威胁记录: {:#}", e);
+            threats.iter().map(|threat| StoredThreat::new(scan_id, source, threat)).collect()
+        })
+    }
+
+    // 按写入顺序返回全部记录，跳过无法解析的行
+    pub fn load(&self) -> Result<Vec<StoredThreat>, anyhow::Error> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("无法读取威胁记录: {:?}", self.path)),
+        };
+        let mut threats = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(threat) => threats.push(threat),
+                Err(e) => log::debug!("跳过无法解析的威胁记录: {}", e),
+            }
+        }
+        Ok(threats)
+    }
+
+    pub fn query(&self, query: &ThreatQuery) -> Result<ThreatPage, anyhow::Error> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0);
+        let mut threats: Vec<StoredThreat> = self.load()?.into_iter().filter(|threat| query.matches(threat)).collect();
+        // 多个进程写入时记录不一定按时间排列
+        threats.sort_by(|a, b| b.detected_at.cmp(&a.detected_at));
+        let total = threats.len();
+        let threats = threats.into_iter().skip(offset).take(limit).collect();
+        Ok(ThreatPage {
+            total,
+            offset,
+            limit,
+            threats,
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<StoredThreat>, anyhow::Error> {
+        Ok(self.load()?.into_iter().find(|threat| threat.id == id))
+    }
+
+    // 只保留最新的 max_records 条记录，返回保留的记录数
+    fn compact(&self) -> Result<usize, anyhow::Error> {
+        let threats = self.load()?;
+        let keep = &threats[threats.len().saturating_sub(self.max_records)..];
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp).with_context(|| format!("无法写入威胁记录: {:?}", tmp))?;
+            for threat in keep {
+                file.write_all(serde_json::to_string(threat)?.as_bytes())?;
+                file.write_all(b"\n")?;
+            }
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp, &self.path).with_context(|| format!("无法写入威胁记录: {:?}", self.path))?;
+        log::info!("威胁记录已整理，删除了 {} 条最早的记录", threats.len() - keep.len());
+        Ok(keep.len())
+    }
+}
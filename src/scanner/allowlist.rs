@@ -0,0 +1,120 @@
+use crate::config::AllowlistConfig;
+use crate::utils::{get_file_digests, GlobMatcher, GlobOptions};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowReason {
+    Path(String),
+    Sha256(String),
+}
+
+impl std::fmt::Display for AllowReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllowReason::Path(pattern) => write!(f, "路径规则 {}", pattern),
+            AllowReason::Sha256(hash) => write!(f, "SHA256 {}", hash),
+        }
+    }
+}
+
+// 白名单中的文件即使命中签名或启发式规则也不会被报告。
+// 运行期间可通过 API 增删条目，修改立即对之后扫描的文件生效
+pub struct Allowlist {
+    hashes: RwLock<HashSet<String>>,
+    paths: RwLock<GlobMatcher>,
+}
+
+impl Allowlist {
+    pub fn new() -> Self {
+        Self {
+            hashes: RwLock::new(HashSet::new()),
+            paths: RwLock::new(GlobMatcher::empty()),
+        }
+    }
+
+    pub fn from_config(config: &AllowlistConfig) -> Result<Self, anyhow::Error> {
+        let allowlist = Self::new();
+        for hash in &config.sha256 {
+            allowlist.add_hash(hash)?;
+        }
+        *allowlist.paths.write().unwrap() = GlobMatcher::new(&config.paths, GlobOptions::default())?;
+        Ok(allowlist)
+    }
+
+    pub fn add_hash(&self, hash: &str) -> Result<bool, anyhow::Error> {
+        let hash = normalize_sha256(hash)?;
+        Ok(self.hashes.write().unwrap().insert(hash))
+    }
+
+    pub fn remove_hash(&self, hash: &str) -> bool {
+        self.hashes.write().unwrap().remove(&hash.trim().to_lowercase())
+    }
+
+    pub fn add_path(&self, pattern: &str) -> Result<bool, anyhow::Error> {
+        let mut paths = self.paths.write().unwrap();
+        if paths.patterns().iter().any(|p| p == pattern.trim()) {
+            return Ok(false);
+        }
+        let mut patterns = paths.patterns().to_vec();
+        patterns.push(pattern.to_string());
+        *paths = GlobMatcher::new(&patterns, GlobOptions::default())?;
+        Ok(true)
+    }
+
+    pub fn remove_path(&self, pattern: &str) -> Result<bool, anyhow::Error> {
+        let mut paths = self.paths.write().unwrap();
+        let patterns: Vec<String> = paths.patterns().iter().filter(|p| *p != pattern.trim()).cloned().collect();
+        if patterns.len() == paths.len() {
+            return Ok(false);
+        }
+        *paths = GlobMatcher::new(&patterns, GlobOptions::default())?;
+        Ok(true)
+    }
+
+    pub fn hashes(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = self.hashes.read().unwrap().iter().cloned().collect();
+        hashes.sort();
+        hashes
+    }
+
+    pub fn path_patterns(&self) -> Vec<String> {
+        self.paths.read().unwrap().patterns().to_vec()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.read().unwrap().is_empty() && self.paths.read().unwrap().is_empty()
+    }
+
+    // 先匹配路径，只有存在哈希条目时才计算文件的 SHA256
+    pub fn check(&self, path: &Path) -> Option<AllowReason> {
+        if let Some(pattern) = self.paths.read().unwrap().matching_patterns(path).first() {
+            return Some(AllowReason::Path(pattern.to_string()));
+        }
+
+        if self.hashes.read().unwrap().is_empty() {
+            return None;
+        }
+        let sha256 = get_file_digests(path).ok()?.sha256;
+        self.hashes
+            .read()
+            .unwrap()
+            .contains(&sha256)
+            .then_some(AllowReason::Sha256(sha256))
+    }
+}
+
+impl Default for Allowlist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn normalize_sha256(hash: &str) -> Result<String, anyhow::Error> {
+    let hash = hash.trim().to_lowercase();
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("无效的SHA256: {}", hash));
+    }
+    Ok(hash)
+}
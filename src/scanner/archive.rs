@@ -0,0 +1,271 @@
+use crate::config::ArchiveConfig;
+use crate::scanner::{SignatureDatabase, ThreatSignature};
+use crate::utils::{detect_file_type_from_bytes, FileKind};
+use std::io::{Cursor, Read};
+
+// 解压结果小于该大小时不检查压缩率，避免小文件的高压缩率误报
+const MIN_RATIO_CHECK_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug)]
+pub struct ArchiveDetection {
+    pub member: String,
+    pub threat: ThreatSignature,
+}
+
+// 触发的资源限制，说明压缩包可能是压缩炸弹
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveViolation {
+    Depth,
+    Ratio,
+    DecompressedSize,
+    Members,
+}
+
+impl ArchiveViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveViolation::Depth => "Depth",
+            ArchiveViolation::Ratio => "Ratio",
+            ArchiveViolation::DecompressedSize => "DecompressedSize",
+            ArchiveViolation::Members => "Members",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ArchiveViolation::Depth => "嵌套层数超过限制",
+            ArchiveViolation::Ratio => "压缩率超过限制",
+            ArchiveViolation::DecompressedSize => "解压总量超过限制",
+            ArchiveViolation::Members => "成员数量超过限制",
+        }
+    }
+}
+
+struct PendingMember {
+    name: String,
+    data: Vec<u8>,
+    depth: usize,
+}
+
+pub struct ArchiveScanner<'a> {
+    signature_db: &'a SignatureDatabase,
+    config: &'a ArchiveConfig,
+    remaining: u64,
+    members: usize,
+    truncated: bool,
+    violation: Option<ArchiveViolation>,
+}
+
+impl<'a> ArchiveScanner<'a> {
+    pub fn new(signature_db: &'a SignatureDatabase, config: &'a ArchiveConfig) -> Self {
+        Self {
+            signature_db,
+            config,
+            remaining: config.max_decompressed_size,
+            members: 0,
+            truncated: false,
+            violation: None,
+        }
+    }
+
+    // 超过总解压大小或成员数量上限后停止继续展开，已解出的成员仍会被扫描
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    // 第一个触发的资源限制。嵌套过深和压缩率过高的成员只是不展开，其余成员照常扫描
+    pub fn violation(&self) -> Option<ArchiveViolation> {
+        self.violation
+    }
+
+    pub async fn scan(&mut self, file_name: &str, data: &[u8]) -> Vec<ArchiveDetection> {
+        let mut detections = Vec::new();
+        let mut stack = self.expand(file_name, data, 0);
+
+        while let Some(member) = stack.pop() {
+            if let Some(threat) = self.signature_db.scan_bytes(&member.data).await {
+                detections.push(ArchiveDetection {
+                    member: member.name.clone(),
+                    threat,
+                });
+            }
+
+            let nested = self.expand(&member.name, &member.data, member.depth);
+            stack.extend(nested);
+        }
+
+        detections
+    }
+
+    fn expand(&mut self, parent: &str, data: &[u8], depth: usize) -> Vec<PendingMember> {
+        let kind = detect_file_type_from_bytes(data);
+        if !kind.is_archive() || self.truncated {
+            return Vec::new();
+        }
+        if depth >= self.config.max_depth {
+            log::debug!("压缩包嵌套超过 {} 层，不再展开: {}", self.config.max_depth, parent);
+            self.record(ArchiveViolation::Depth);
+            return Vec::new();
+        }
+
+        let members = match kind {
+            FileKind::Zip | FileKind::Ooxml => self.extract_zip(data),
+            FileKind::Tar => self.extract_tar(data),
+            FileKind::Gzip => self.decompress(flate2::read::GzDecoder::new(data), parent, ".gz", data.len() as u64),
+            FileKind::Bzip2 => self.decompress(bzip2::read::BzDecoder::new(data), parent, ".bz2", data.len() as u64),
+            FileKind::Xz => self.decompress(xz2::read::XzDecoder::new(data), parent, ".xz", data.len() as u64),
+            _ => return Vec::new(),
+        };
+
+        members
+            .into_iter()
+            .map(|(name, data)| PendingMember {
+                name: if depth == 0 { name } else { format!("{}/{}", parent, name) },
+                data,
+                depth: depth + 1,
+            })
+            .collect()
+    }
+
+    fn record(&mut self, violation: ArchiveViolation) {
+        self.violation.get_or_insert(violation);
+    }
+
+    // 每解出一个成员计数一次，超过上限后停止展开
+    fn take_member(&mut self) -> bool {
+        if self.members >= self.config.max_members {
+            log::warn!("压缩包成员数量超过限制 {}，停止展开", self.config.max_members);
+            self.record(ArchiveViolation::Members);
+            self.truncated = true;
+            return false;
+        }
+        self.members += 1;
+        true
+    }
+
+    // 按压缩后大小限制单个成员的解压量，声明的大小不可信，以实际解出的数据为准
+    fn ratio_limit(&self, compressed_size: Option<u64>) -> u64 {
+        match compressed_size {
+            Some(size) if self.config.max_expansion_ratio > 0 => {
+                size.saturating_mul(self.config.max_expansion_ratio).max(MIN_RATIO_CHECK_SIZE)
+            }
+            _ => u64::MAX,
+        }
+    }
+
+    fn read_limited<R: Read>(&mut self, reader: R, compressed_size: Option<u64>) -> Option<Vec<u8>> {
+        let ratio_limit = self.ratio_limit(compressed_size);
+        let mut data = Vec::new();
+        match reader.take(self.remaining.min(ratio_limit).saturating_add(1)).read_to_end(&mut data) {
+            Ok(_) => {}
+            Err(e) => {
+                log::debug!("无法解压压缩包成员: {}", e);
+                return None;
+            }
+        }
+
+        if data.len() as u64 > self.remaining {
+            log::warn!("压缩包解压大小超过限制 {} 字节，停止展开", self.config.max_decompressed_size);
+            self.record(ArchiveViolation::DecompressedSize);
+            self.truncated = true;
+            self.remaining = 0;
+            return None;
+        }
+        if data.len() as u64 > ratio_limit {
+            log::warn!("压缩包成员压缩率超过 {} 倍，跳过该成员", self.config.max_expansion_ratio);
+            self.record(ArchiveViolation::Ratio);
+            return None;
+        }
+
+        self.remaining -= data.len() as u64;
+        Some(data)
+    }
+
+    fn extract_zip(&mut self, data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = match zip::ZipArchive::new(Cursor::new(data)) {
+            Ok(archive) => archive,
+            Err(e) => {
+                log::debug!("无法解析ZIP压缩包: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut members = Vec::new();
+        for i in 0..archive.len() {
+            let file = match archive.by_index(i) {
+                Ok(file) => file,
+                // 加密或不支持的压缩方式，跳过该成员
+                Err(_) => continue,
+            };
+            if file.is_dir() {
+                continue;
+            }
+            if !self.take_member() {
+                break;
+            }
+            let name = file.name().to_string();
+            let compressed_size = file.compressed_size();
+            match self.read_limited(file, Some(compressed_size)) {
+                Some(content) => members.push((name, content)),
+                None if self.truncated => break,
+                None => continue,
+            }
+        }
+        members
+    }
+
+    fn extract_tar(&mut self, data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = tar::Archive::new(Cursor::new(data));
+        let entries = match archive.entries() {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut members = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => break,
+            };
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            if !self.take_member() {
+                break;
+            }
+            let name = entry
+                .path()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match self.read_limited(entry, None) {
+                Some(content) => members.push((name, content)),
+                None if self.truncated => break,
+                None => continue,
+            }
+        }
+        members
+    }
+
+    fn decompress<R: Read>(
+        &mut self,
+        reader: R,
+        parent: &str,
+        extension: &str,
+        compressed_size: u64,
+    ) -> Vec<(String, Vec<u8>)> {
+        let name = parent
+            .rsplit('/')
+            .next()
+            .and_then(|n| n.strip_suffix(extension))
+            .filter(|n| !n.is_empty())
+            .unwrap_or("data")
+            .to_string();
+
+        if !self.take_member() {
+            return Vec::new();
+        }
+        self.read_limited(reader, Some(compressed_size))
+            .map(|content| vec![(name, content)])
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,123 @@
+use crate::config::{DetectionAction, ScannerConfig};
+use crate::scanner::{ScanMode, ScanOptions, ScannerEngine, SignatureDatabase, VerdictCache};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRun {
+    // scan、verdict_cache_cold 或 verdict_cache_warm
+    pub name: String,
+    pub threads: usize,
+    pub duration_secs: f64,
+    pub files_per_s: f64,
+    pub mb_per_s: f64,
+    // 相对第一轮的加速比
+    pub speedup: f64,
+    // 未使用扫描结论缓存时为 None
+    pub cache_hit_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub path: PathBuf,
+    pub signature_count: usize,
+    pub database_version: String,
+    pub signature_load_secs: f64,
+    // 每一轮扫描的文件数和字节数，取自预热轮
+    pub files: usize,
+    pub bytes: usize,
+    pub runs: Vec<BenchmarkRun>,
+}
+
+// 依次测量病毒库加载时间、各线程数的扫描吞吐量和扫描结论缓存冷、热两轮的命中率。
+// 先完整扫描一遍作为预热，之后各轮读取的文件都在页缓存中，结果反映扫描引擎本身的性能。
+// 不限制读取速率，检测结果只报告不处理
+pub async fn run_benchmark(
+    config: &ScannerConfig,
+    path: &Path,
+    thread_counts: &[usize],
+) -> Result<BenchmarkReport, anyhow::Error> {
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("基准测试路径不是目录: {:?}", path));
+    }
+    let thread_counts: Vec<usize> = thread_counts.iter().map(|&threads| threads.max(1)).collect();
+    let max_threads = thread_counts.iter().copied().max().unwrap_or(1);
+
+    let signature_db = Arc::new(SignatureDatabase::new());
+    signature_db.set_regex_time_budget(Duration::from_millis(config.performance.regex_time_budget_ms));
+    signature_db.set_scan_buffer_size(config.performance.scan_buffer_size);
+    signature_db.set_use_mmap(config.performance.use_mmap);
+    let start = Instant::now();
+    signature_db.load_builtin_signatures().await?;
+    if let Err(e) = signature_db.load_from_directory(&config.update.database_path).await {
+        log::warn!("无法加载本地病毒库，只使用内置特征码: {:#}", e);
+    }
+    let signature_load_secs = start.elapsed().as_secs_f64();
+
+    let options = ScanOptions {
+        custom_paths: vec![path.to_path_buf()],
+        use_xattr_markers: false,
+        max_read_mb_per_s: 0,
+        idle_io_priority: false,
+        max_duration: None,
+        action: DetectionAction::Report,
+        auto_quarantine_min_risk: None,
+        ..ScanOptions::from_config(config, ScanMode::Custom, Vec::new())
+    };
+
+    let mut engine = ScannerEngine::new(Arc::clone(&signature_db), ScanOptions { thread_count: max_threads, ..options.clone() });
+    engine.start_scan().await?;
+    let files = engine.get_stats().get_files_scanned();
+    let bytes = engine.get_stats().get_bytes_scanned();
+    if files == 0 {
+        return Err(anyhow::anyhow!("{:?} 中没有可扫描的文件", path));
+    }
+
+    let mut runs = Vec::new();
+    for &threads in &thread_counts {
+        engine = ScannerEngine::new(Arc::clone(&signature_db), ScanOptions { thread_count: threads, ..options.clone() });
+        runs.push(measure(&engine, "scan", threads, files, bytes).await?);
+    }
+
+    let workspace = tempfile::Builder::new().prefix("virus-scanner-benchmark").tempdir()?;
+    let cache = Arc::new(VerdictCache::open(&workspace.path().join("verdicts.db"))?);
+    for name in ["verdict_cache_cold", "verdict_cache_warm"] {
+        engine = ScannerEngine::new(Arc::clone(&signature_db), ScanOptions { thread_count: max_threads, ..options.clone() });
+        engine.set_verdict_cache(Arc::clone(&cache));
+        runs.push(measure(&engine, name, max_threads, files, bytes).await?);
+    }
+
+    let baseline = runs[0].duration_secs;
+    for run in &mut runs {
+        run.speedup = baseline / run.duration_secs;
+    }
+
+    Ok(BenchmarkReport {
+        path: path.to_path_buf(),
+        signature_count: signature_db.get_signature_count().await,
+        database_version: signature_db.get_version(),
+        signature_load_secs,
+        files,
+        bytes,
+        runs,
+    })
+}
+
+async fn measure(engine: &ScannerEngine, name: &str, threads: usize, files: usize, bytes: usize) -> Result<BenchmarkRun, anyhow::Error> {
+    let start = Instant::now();
+    engine.start_scan().await?;
+    let duration_secs = start.elapsed().as_secs_f64().max(f64::EPSILON);
+    let stats = engine.get_stats();
+    let lookups = stats.get_cache_hits() + stats.get_cache_misses();
+    Ok(BenchmarkRun {
+        name: name.to_string(),
+        threads,
+        duration_secs,
+        files_per_s: files as f64 / duration_secs,
+        mb_per_s: bytes as f64 / duration_secs / (1024.0 * 1024.0),
+        speedup: 1.0,
+        cache_hit_rate: (lookups > 0).then(|| stats.get_cache_hits() as f64 / lookups as f64),
+    })
+}
@@ -0,0 +1,90 @@
+use crate::scanner::{ScanOptions, ScanResult, ScanStats};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+// 扫描过程中不断累积的进度，由工作线程写入、定时任务读取
+#[derive(Debug, Default)]
+pub(crate) struct CheckpointProgress {
+    pub completed: HashSet<PathBuf>,
+    pub results: Vec<ScanResult>,
+}
+
+// 目录遍历顺序不固定，因此不保存队列本身，而是记录已完成的文件；
+// 恢复时重新遍历扫描路径并跳过这些文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    pub version: u32,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub db_version: String,
+    pub options: ScanOptions,
+    pub completed: HashSet<PathBuf>,
+    pub files_scanned: usize,
+    pub threats_found: usize,
+    pub bytes_scanned: usize,
+    pub errors: usize,
+    pub files_skipped: usize,
+    pub results: Vec<ScanResult>,
+}
+
+impl ScanCheckpoint {
+    pub(crate) fn capture(
+        options: &ScanOptions,
+        db_version: &str,
+        stats: &ScanStats,
+        progress: &CheckpointProgress,
+    ) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            updated_at: chrono::Utc::now(),
+            db_version: db_version.to_string(),
+            options: options.clone(),
+            completed: progress.completed.clone(),
+            files_scanned: stats.files_scanned.load(Ordering::Relaxed),
+            threats_found: stats.threats_found.load(Ordering::Relaxed),
+            bytes_scanned: stats.bytes_scanned.load(Ordering::Relaxed),
+            errors: stats.errors.load(Ordering::Relaxed),
+            files_skipped: stats.files_skipped.load(Ordering::Relaxed),
+            results: progress.results.clone(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read(path)
+            .with_context(|| format!("无法读取扫描检查点: {:?}", path))?;
+        let checkpoint: Self = serde_json::from_slice(&content)
+            .with_context(|| format!("扫描检查点格式错误: {:?}", path))?;
+
+        if checkpoint.version != CHECKPOINT_VERSION {
+            anyhow::bail!("扫描检查点版本不兼容: {} (当前支持 {})", checkpoint.version, CHECKPOINT_VERSION);
+        }
+        Ok(checkpoint)
+    }
+
+    // 先写临时文件再改名，进程中途崩溃也不会留下半截的检查点
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建检查点目录: {:?}", parent))?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .with_context(|| format!("无法写入扫描检查点: {:?}", tmp_path))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("无法保存扫描检查点: {:?}", path))?;
+        Ok(())
+    }
+
+    pub fn restore_stats(&self, stats: &ScanStats) {
+        stats.files_scanned.store(self.files_scanned, Ordering::Relaxed);
+        stats.threats_found.store(self.threats_found, Ordering::Relaxed);
+        stats.bytes_scanned.store(self.bytes_scanned, Ordering::Relaxed);
+        stats.errors.store(self.errors, Ordering::Relaxed);
+        stats.files_skipped.store(self.files_skipped, Ordering::Relaxed);
+    }
+}
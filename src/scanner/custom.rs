@@ -0,0 +1,178 @@
+use crate::scanner::cvd::{ParsedSignature, SignatureFormat};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// 本地自定义特征码保存在病毒库目录下的 local.<扩展名> 文件中，格式与 ClamAV 相同，
+// 随病毒库一起加载和备份，病毒库更新不会覆盖
+pub const LOCAL_DATABASE: &str = "local";
+
+#[derive(Debug, Clone)]
+pub struct AddedSignatures {
+    pub file: PathBuf,
+    pub added: Vec<String>,
+    // 本地特征码中已有同名特征码，未重复添加
+    pub existing: Vec<String>,
+}
+
+// source 为特征码文件 (.ndb、.ldb、.hdb、.hsb、.imp) 时导入其中的全部特征码，有无法解析的行时不写入；
+// 其他文件视为样本，按 SHA256 生成哈希特征码，name 为空时使用 Local.Custom.<摘要前缀>
+pub fn add_custom_signatures(database_dir: &Path, source: &Path, name: Option<&str>) -> Result<AddedSignatures> {
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow::anyhow!("无效的文件名: {:?}", source))?;
+
+    let (extension, lines) = match SignatureFormat::from_file_name(&file_name) {
+        Some(format) => {
+            let content = std::fs::read_to_string(source).with_context(|| format!("无法读取特征码文件: {:?}", source))?;
+            let mut lines = Vec::new();
+            let mut invalid = Vec::new();
+            for (index, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if line.starts_with('#') {
+                    lines.push((None, line.to_string()));
+                    continue;
+                }
+                match format.parse_line(line).map(|parsed| signature_id(&parsed)) {
+                    Some(id) => lines.push((Some(id), line.to_string())),
+                    None => invalid.push(index + 1),
+                }
+            }
+            if !invalid.is_empty() {
+                return Err(anyhow::anyhow!("{:?} 中有无法解析的特征码 (行号 {:?})，未添加任何特征码", source, invalid));
+            }
+            if lines.iter().all(|(id, _)| id.is_none()) {
+                return Err(anyhow::anyhow!("{:?} 中没有特征码", source));
+            }
+            let extension = file_name.rsplit_once('.').map_or("", |(_, extension)| extension).to_string();
+            (extension, lines)
+        }
+        None => {
+            let content = std::fs::read(source).with_context(|| format!("无法读取样本文件: {:?}", source))?;
+            if content.is_empty() {
+                return Err(anyhow::anyhow!("样本文件为空: {:?}", source));
+            }
+            let digest = hex::encode(openssl::sha::sha256(&content));
+            let name = match name {
+                Some(name) => name.to_string(),
+                None => format!("Local.Custom.{}", &digest[..12]),
+            };
+            if name.is_empty() || name.contains(':') || name.contains(char::is_whitespace) {
+                return Err(anyhow::anyhow!("无效的特征码名称: {:?}", name));
+            }
+            ("hsb".to_string(), vec![(Some(name.clone()), format!("{}:{}:{}", digest, content.len(), name))])
+        }
+    };
+
+    let target = database_dir.join(format!("{}.{}", LOCAL_DATABASE, extension));
+    let mut content = match std::fs::read_to_string(&target) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("无法读取 {:?}", target)),
+    };
+    let format = SignatureFormat::from_file_name(&target.to_string_lossy());
+    let mut known: HashSet<String> = content
+        .lines()
+        .filter_map(|line| format?.parse_line(line.trim()))
+        .map(|parsed| signature_id(&parsed))
+        .collect();
+
+    // 注释行随其后的特征码一起写入，该特征码已存在时一并跳过
+    let mut added = Vec::new();
+    let mut existing = Vec::new();
+    let mut comments = Vec::new();
+    for (id, line) in lines {
+        let Some(id) = id else {
+            comments.push(line);
+            continue;
+        };
+        if !known.insert(id.clone()) {
+            existing.push(id);
+            comments.clear();
+            continue;
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        for line in comments.drain(..).chain(std::iter::once(line)) {
+            content.push_str(&line);
+            content.push('\n');
+        }
+        added.push(id);
+    }
+    if !added.is_empty() {
+        std::fs::create_dir_all(database_dir).with_context(|| format!("无法创建病毒库目录: {:?}", database_dir))?;
+        write_file(&target, &content)?;
+    }
+
+    Ok(AddedSignatures {
+        file: target,
+        added,
+        existing,
+    })
+}
+
+// 从所有本地特征码文件中删除该 ID 的特征码，返回删除的条数
+pub fn remove_custom_signature(database_dir: &Path, id: &str) -> Result<usize> {
+    let mut removed = 0;
+    for path in custom_signature_files(database_dir) {
+        let Some(format) = SignatureFormat::from_file_name(&path.to_string_lossy()) else {
+            continue;
+        };
+        let content = std::fs::read_to_string(&path).with_context(|| format!("无法读取 {:?}", path))?;
+        let mut kept = String::new();
+        let mut changed = false;
+        for line in content.lines() {
+            let matches = format.parse_line(line.trim()).map_or(false, |parsed| signature_id(&parsed) == id);
+            if matches {
+                removed += 1;
+                changed = true;
+            } else {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+        if changed {
+            write_file(&path, &kept)?;
+        }
+    }
+    Ok(removed)
+}
+
+// 病毒库目录下的 local.* 特征码文件
+pub fn custom_signature_files(database_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(database_dir) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", LOCAL_DATABASE);
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            name.starts_with(&prefix) && SignatureFormat::from_file_name(&name).is_some() && path.is_file()
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn signature_id(parsed: &ParsedSignature) -> String {
+    match parsed {
+        ParsedSignature::Pattern(sig) => sig.id.clone(),
+        ParsedSignature::Hash(sig) => sig.id.clone(),
+    }
+}
+
+// 先写入临时文件再替换，扫描进程重新加载时不会读到写了一半的文件
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name));
+    std::fs::write(&tmp, content).with_context(|| format!("无法写入 {:?}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("无法写入 {:?}", path))?;
+    Ok(())
+}
@@ -0,0 +1,387 @@
+use crate::scanner::logical::LogicalSignature;
+use crate::scanner::{HashAlgorithm, HashSignature, PatternType, Signature};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+pub const CVD_HEADER_SIZE: usize = 512;
+const CVD_MAGIC: &str = "ClamAV-VDB";
+// 归档中的这些文件不是特征码
+const METADATA_EXTENSIONS: [&str; 3] = ["info", "cfg", "ign2"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvdHeader {
+    pub build_time: String,
+    pub version: u32,
+    pub signature_count: u64,
+    pub functionality_level: u32,
+    pub md5: String,
+    pub digital_signature: String,
+    pub builder: String,
+    pub build_timestamp: Option<u64>,
+}
+
+impl CvdHeader {
+    // 格式: ClamAV-VDB:构建时间:版本:特征码数:功能级别:MD5:数字签名:构建者:时间戳，右侧以空格补齐到512字节
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        if raw.len() < CVD_HEADER_SIZE {
+            return Err(anyhow::anyhow!("病毒库文件头不完整"));
+        }
+
+        let text = String::from_utf8_lossy(&raw[..CVD_HEADER_SIZE]);
+        let fields: Vec<&str> = text.trim_end_matches(|c: char| c == ' ' || c == '\0').split(':').collect();
+
+        if fields.first() != Some(&CVD_MAGIC) {
+            return Err(anyhow::anyhow!("不是有效的CVD文件"));
+        }
+        if fields.len() < 8 {
+            return Err(anyhow::anyhow!("CVD文件头字段不足: {}", fields.len()));
+        }
+
+        Ok(Self {
+            build_time: fields[1].to_string(),
+            version: fields[2].trim().parse().context("无法解析病毒库版本")?,
+            signature_count: fields[3].trim().parse().context("无法解析特征码数量")?,
+            functionality_level: fields[4].trim().parse().context("无法解析功能级别")?,
+            md5: fields[5].trim().to_ascii_lowercase(),
+            digital_signature: fields[6].trim().to_string(),
+            builder: fields[7].trim().to_string(),
+            build_timestamp: fields.get(8).and_then(|t| t.trim().parse().ok()),
+        })
+    }
+}
+
+// 病毒库中支持的特征码文件，按扩展名区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFormat {
+    // .ndb 十六进制特征码
+    Ndb,
+    // .ldb 逻辑签名
+    Ldb,
+    // .hdb (MD5)、.hsb (SHA1/SHA256) 文件哈希
+    Hash,
+    // .imp PE 导入表哈希
+    Imphash,
+}
+
+pub enum ParsedSignature {
+    Pattern(Signature),
+    Hash(HashSignature),
+}
+
+impl SignatureFormat {
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        match name.rsplit_once('.').map(|(_, extension)| extension)? {
+            "ndb" => Some(SignatureFormat::Ndb),
+            "ldb" => Some(SignatureFormat::Ldb),
+            "hdb" | "hsb" => Some(SignatureFormat::Hash),
+            "imp" => Some(SignatureFormat::Imphash),
+            _ => None,
+        }
+    }
+
+    // 无法解析或暂不支持的特征码返回 None
+    pub fn parse_line(&self, line: &str) -> Option<ParsedSignature> {
+        match self {
+            SignatureFormat::Ndb => parse_ndb_line(line).map(ParsedSignature::Pattern),
+            SignatureFormat::Ldb => parse_ldb_line(line).map(ParsedSignature::Pattern),
+            SignatureFormat::Hash => parse_hash_line(line).map(ParsedSignature::Hash),
+            SignatureFormat::Imphash => parse_imphash_line(line).map(ParsedSignature::Hash),
+        }
+    }
+}
+
+pub struct CvdFile {
+    pub header: CvdHeader,
+    pub signatures: Vec<Signature>,
+    pub hash_signatures: Vec<HashSignature>,
+    pub skipped: usize,
+}
+
+pub fn read_cvd<P: AsRef<Path>>(path: P) -> Result<CvdFile> {
+    let data = std::fs::read(path.as_ref()).context("无法读取病毒库文件")?;
+    parse_cvd(&data)
+}
+
+// 校验文件头并返回正文 tar 的读取器
+pub fn open_cvd(data: &[u8]) -> Result<(CvdHeader, Box<dyn Read + '_>)> {
+    let header = CvdHeader::parse(data)?;
+    let body = &data[CVD_HEADER_SIZE..];
+
+    // .cld 为未压缩的 tar，其文件头中的 MD5 不对应正文
+    let is_gzip = body.starts_with(&[0x1f, 0x8b]);
+    if is_gzip && header.md5.len() == 32 {
+        let digest = openssl::hash::hash(openssl::hash::MessageDigest::md5(), body)?;
+        if hex::encode(digest) != header.md5 {
+            return Err(anyhow::anyhow!("病毒库MD5校验失败"));
+        }
+    }
+
+    let reader: Box<dyn Read> = if is_gzip {
+        Box::new(flate2::read::GzDecoder::new(body))
+    } else {
+        Box::new(body)
+    };
+    Ok((header, reader))
+}
+
+pub fn parse_cvd(data: &[u8]) -> Result<CvdFile> {
+    let (header, reader) = open_cvd(data)?;
+
+    let mut signatures = Vec::new();
+    let mut hash_signatures = Vec::new();
+    let mut skipped = 0;
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries().context("无法解析病毒库归档")? {
+        let entry = entry.context("无法读取病毒库归档条目")?;
+        let name = entry.path()?.to_string_lossy().to_string();
+
+        let Some(format) = SignatureFormat::from_file_name(&name) else {
+            log::debug!("跳过暂不支持的病毒库文件: {}", name);
+            continue;
+        };
+
+        for line in BufReader::new(entry).split(b'\n') {
+            let line = line?;
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match format.parse_line(line) {
+                Some(ParsedSignature::Pattern(sig)) => signatures.push(sig),
+                Some(ParsedSignature::Hash(sig)) => hash_signatures.push(sig),
+                None => skipped += 1,
+            }
+        }
+    }
+
+    Ok(CvdFile {
+        header,
+        signatures,
+        hash_signatures,
+        skipped,
+    })
+}
+
+pub fn is_signature_file(name: &str) -> bool {
+    let extension = name.rsplit_once('.').map(|(_, e)| e).unwrap_or_default();
+    !METADATA_EXTENSIONS.contains(&extension)
+}
+
+// 病毒库中每条特征码 (文件名和内容) 的摘要，用于统计两个版本之间新增和删除的特征码，
+// 包括本程序暂不支持的特征码类型
+pub fn signature_digests(data: &[u8]) -> Result<HashSet<u64>> {
+    let (_, reader) = open_cvd(data)?;
+    let mut digests = HashSet::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().context("无法解析病毒库归档")? {
+        let entry = entry.context("无法读取病毒库归档条目")?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        if !is_signature_file(&name) {
+            continue;
+        }
+        for line in BufReader::new(entry).split(b'\n') {
+            let line = line?;
+            let line = line.trim_ascii();
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            (&name, line).hash(&mut hasher);
+            digests.insert(hasher.finish());
+        }
+    }
+    Ok(digests)
+}
+
+// 病毒名形如 Win.Trojan.Agent-12345，取第一段为平台、第二段为威胁类型
+fn classify_name(name: &str) -> (String, Option<String>) {
+    let mut parts = name.split('.');
+    match (parts.next(), parts.next()) {
+        (Some(platform), Some(category)) => (category.to_string(), Some(platform.to_string())),
+        _ => ("Unknown".to_string(), None),
+    }
+}
+
+// 格式: 病毒名:目标类型:偏移:十六进制特征码[:最低功能级别[:最高功能级别]]
+pub fn parse_ndb_line(line: &str) -> Option<Signature> {
+    let fields: Vec<&str> = line.split(':').collect();
+    if fields.len() < 4 {
+        return None;
+    }
+
+    let name = fields[0];
+    let (threat_type, subplatform) = classify_name(name);
+    let anchor = offset_anchor(fields[2]);
+    let hex_pattern = fields[3];
+
+    let (pattern, pattern_type) = match hex::decode(hex_pattern) {
+        Ok(bytes) if anchor.is_empty() && !bytes.is_empty() => (bytes, PatternType::ByteSequence),
+        _ => {
+            let body = hex_signature_to_regex(hex_pattern)?;
+            (format!("(?s){}{}", anchor, body).into_bytes(), PatternType::Regex)
+        }
+    };
+
+    Some(Signature {
+        id: name.to_string(),
+        name: name.to_string(),
+        threat_type,
+        risk_level: "High".to_string(),
+        pattern,
+        pattern_type,
+        target: fields[1].to_string(),
+        subplatform,
+    })
+}
+
+// 逻辑签名原样保存，加载时先校验能否解析，匹配时由数据库编译并缓存
+pub fn parse_ldb_line(line: &str) -> Option<Signature> {
+    let logical = LogicalSignature::parse(line)?;
+    let (threat_type, subplatform) = classify_name(&logical.name);
+
+    Some(Signature {
+        id: logical.name.clone(),
+        name: logical.name.clone(),
+        threat_type,
+        risk_level: "High".to_string(),
+        pattern: line.as_bytes().to_vec(),
+        pattern_type: PatternType::LogicalExpression,
+        target: logical.target.unwrap_or_else(|| "0".to_string()),
+        subplatform,
+    })
+}
+
+// 格式: 摘要:文件大小:病毒名[:最低功能级别]，.hdb 为 MD5，.hsb 可为 SHA1/SHA256；大小为 * 表示不限
+pub fn parse_hash_line(line: &str) -> Option<HashSignature> {
+    let mut fields = line.split(':');
+    let digest = fields.next()?.trim().to_ascii_lowercase();
+    let size = fields.next()?.trim();
+    let name = fields.next()?.trim();
+
+    if name.is_empty() || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let file_size = match size {
+        "*" => None,
+        size => Some(size.parse().ok()?),
+    };
+    let (threat_type, _) = classify_name(name);
+
+    Some(HashSignature {
+        id: name.to_string(),
+        name: name.to_string(),
+        threat_type,
+        risk_level: "High".to_string(),
+        algorithm: HashAlgorithm::from_hex_len(digest.len())?,
+        digest,
+        file_size,
+    })
+}
+
+// .imp 与 .hdb 格式相同，摘要为 PE 导入表的 imphash
+pub fn parse_imphash_line(line: &str) -> Option<HashSignature> {
+    let sig = parse_hash_line(line)?;
+    (sig.algorithm == HashAlgorithm::Md5).then(|| HashSignature {
+        algorithm: HashAlgorithm::Imphash,
+        ..sig
+    })
+}
+
+// 仅绝对偏移可以直接锚定；EP/节/EOF 相对偏移需要解析文件结构，暂按浮动匹配处理
+pub(crate) fn offset_anchor(offset: &str) -> String {
+    let (start, shift) = match offset.split_once(',') {
+        Some((start, shift)) => (start, shift.parse::<usize>().ok()),
+        None => (offset, Some(0)),
+    };
+
+    match (start.parse::<usize>(), shift) {
+        (Ok(start), Some(shift)) => format!(r"\A.{{{},{}}}", start, start + shift),
+        _ => String::new(),
+    }
+}
+
+fn nibble(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn byte_class(bytes: impl Iterator<Item = u8>) -> String {
+    let mut class = String::from("[");
+    for b in bytes {
+        class.push_str(&format!(r"\x{:02x}", b));
+    }
+    class.push(']');
+    class
+}
+
+// 将 ClamAV 十六进制特征码 (含 ?? * {n-m} [n-m] (aa|bb) 等通配) 转换为字节正则
+pub fn hex_signature_to_regex(pattern: &str) -> Option<String> {
+    if !pattern.is_ascii() {
+        return None;
+    }
+
+    let bytes = pattern.as_bytes();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'*' => {
+                regex.push_str(".*?");
+                i += 1;
+            }
+            b'{' | b'[' => {
+                let close = if bytes[i] == b'{' { '}' } else { ']' };
+                let end = pattern[i..].find(close)? + i;
+                let range = &pattern[i + 1..end];
+                let quantifier = match range.split_once('-') {
+                    Some(("", max)) => format!("{{0,{}}}", max.parse::<usize>().ok()?),
+                    Some((min, "")) => format!("{{{},}}", min.parse::<usize>().ok()?),
+                    Some((min, max)) => format!("{{{},{}}}", min.parse::<usize>().ok()?, max.parse::<usize>().ok()?),
+                    None => format!("{{{}}}", range.parse::<usize>().ok()?),
+                };
+                regex.push('.');
+                regex.push_str(&quantifier);
+                i = end + 1;
+            }
+            b'(' => {
+                let end = pattern[i..].find(')')? + i;
+                let alternatives = pattern[i + 1..end]
+                    .split('|')
+                    .map(hex_signature_to_regex)
+                    .collect::<Option<Vec<_>>>()?;
+                if alternatives.iter().any(|a| a.is_empty()) {
+                    return None;
+                }
+                regex.push_str(&format!("(?:{})", alternatives.join("|")));
+                i = end + 1;
+            }
+            high if i + 1 < bytes.len() => {
+                let low = bytes[i + 1];
+                match (high, low) {
+                    (b'?', b'?') => regex.push('.'),
+                    (b'?', low) => {
+                        let low = nibble(low)?;
+                        regex.push_str(&byte_class((0..16).map(|h| h << 4 | low)));
+                    }
+                    (high, b'?') => {
+                        let high = nibble(high)?;
+                        regex.push_str(&format!(r"[\x{:02x}-\x{:02x}]", high << 4, high << 4 | 0x0f));
+                    }
+                    (high, low) => regex.push_str(&format!(r"\x{:02x}", nibble(high)? << 4 | nibble(low)?)),
+                }
+                i += 2;
+            }
+            // 否定分组、(B)/(L) 边界等语法暂不支持
+            _ => return None,
+        }
+    }
+
+    Some(regex)
+}
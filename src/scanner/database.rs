@@ -1,13 +1,42 @@
+use aho_corasick::AhoCorasick;
 use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use lru::LruCache;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
 use walkdir::WalkDir;
+use regex::bytes::{Regex, RegexBuilder};
+use crate::scanner::cvd::{read_cvd, CvdHeader, ParsedSignature, SignatureFormat};
+use crate::scanner::logical::LogicalSignature;
+use crate::scanner::pe::imphash;
+use crate::utils::{detect_file_type, detect_file_type_from_bytes, FileKind, MappedFile};
+
+const DEFAULT_REGEX_TIME_BUDGET_MS: u64 = 500;
+const DEFAULT_SCAN_BUFFER_SIZE: usize = 8192;
+// 流式扫描每次读取 scan_buffer_size 的若干倍，相邻块之间重叠一个 scan_buffer_size
+const STREAM_CHUNK_FACTOR: usize = 64;
+// 限制单条正则编译后的大小，防止恶意或错误的签名占用过多内存
+const REGEX_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+// 编译后的正则和逻辑签名无法直接测量大小，按每条固定开销估算
+const COMPILED_PATTERN_ESTIMATE: u64 = 16 * 1024;
+const HASH_ENTRY_OVERHEAD: u64 = 64;
+
+// 标准 EICAR 测试字符串，用于验证部署是否能正常检测和隔离。后半段倒序保存，
+// 避免扫描器自身的可执行文件中出现完整的测试字符串而被检测
+const EICAR_HEAD: &[u8] = b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-";
+const EICAR_TAIL_REVERSED: &[u8] = b"*H+H$!ELIF-TSET-SURIVITNA";
+
+pub fn eicar_test_string() -> Vec<u8> {
+    let mut data = EICAR_HEAD.to_vec();
+    data.extend(EICAR_TAIL_REVERSED.iter().rev());
+    data
+}
+
+pub const EICAR_SIGNATURE_ID: &str = "Eicar-Test-Signature";
 
 #[derive(Debug, Clone)]
 pub struct Signature {
@@ -29,6 +58,130 @@ pub enum PatternType {
     Regex,
     PEHeader,
     Hash,
+    Imphash,
+}
+
+impl PatternType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PatternType::ByteSequence => "字节序列",
+            PatternType::ExtendedByteSequence => "扩展字节序列",
+            PatternType::LogicalExpression => "逻辑签名",
+            PatternType::Regex => "正则",
+            PatternType::PEHeader => "PE 头",
+            PatternType::Hash => "哈希",
+            PatternType::Imphash => "imphash",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    // PE 导入表哈希，只对 PE 文件计算
+    Imphash,
+}
+
+impl HashAlgorithm {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Sha1 => "SHA1",
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Imphash => "imphash",
+        }
+    }
+
+    // 十六进制摘要长度唯一对应一种算法
+    pub fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(HashAlgorithm::Md5),
+            40 => Some(HashAlgorithm::Sha1),
+            64 => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn message_digest(&self) -> Option<openssl::hash::MessageDigest> {
+        match self {
+            HashAlgorithm::Md5 => Some(openssl::hash::MessageDigest::md5()),
+            HashAlgorithm::Sha1 => Some(openssl::hash::MessageDigest::sha1()),
+            HashAlgorithm::Sha256 => Some(openssl::hash::MessageDigest::sha256()),
+            HashAlgorithm::Imphash => None,
+        }
+    }
+
+    fn digest(&self, data: &[u8]) -> Option<String> {
+        match self.message_digest() {
+            Some(md) => openssl::hash::hash(md, data).map(hex::encode).ok(),
+            None => imphash(data),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HashSignature {
+    pub id: String,
+    pub name: String,
+    pub threat_type: String,
+    pub risk_level: String,
+    pub algorithm: HashAlgorithm,
+    pub digest: String,
+    pub file_size: Option<u64>,
+}
+
+impl HashSignature {
+    // 特征码模式为原始摘要字节的 Hash/Imphash 类型签名 (如 MISP 导入) 同样进入哈希索引
+    fn from_signature(sig: &Signature) -> Option<Self> {
+        let digest = hex::encode(&sig.pattern);
+        let algorithm = match sig.pattern_type {
+            PatternType::Imphash if digest.len() == 32 => HashAlgorithm::Imphash,
+            PatternType::Imphash => return None,
+            _ => HashAlgorithm::from_hex_len(digest.len())?,
+        };
+        Some(Self {
+            id: sig.id.clone(),
+            name: sig.name.clone(),
+            threat_type: sig.threat_type.clone(),
+            risk_level: sig.risk_level.clone(),
+            algorithm,
+            digest,
+            file_size: None,
+        })
+    }
+}
+
+// 查询病毒库时返回的签名信息，不含特征码内容
+#[derive(Debug, Clone)]
+pub struct SignatureSummary {
+    pub id: String,
+    pub name: String,
+    pub threat_type: String,
+    pub risk_level: String,
+    // PatternType 或 HashAlgorithm 的名称
+    pub kind: &'static str,
+}
+
+// 病毒库目录中一个文件的校验结果
+#[derive(Debug, Clone)]
+pub struct DatabaseFileCheck {
+    pub path: PathBuf,
+    // CVD/CLD 的版本，特征码文本文件为 None
+    pub version: Option<u32>,
+    pub signatures: usize,
+    // 无法解析或暂不支持的特征码数量
+    pub skipped: usize,
+    // 文本特征码文件中无法解析的行号
+    pub invalid_lines: Vec<usize>,
+    pub error: Option<String>,
+}
+
+impl DatabaseFileCheck {
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.invalid_lines.is_empty()
+    }
 }
 
 #[derive(Debug)]
@@ -44,30 +197,715 @@ pub struct ThreatSignature {
     pub target: String,
 }
 
+struct PatternScan {
+    budget: Duration,
+    start: Instant,
+    budget_exceeded: bool,
+    first_window: bool,
+    overlap: usize,
+    logical_counts: HashMap<String, Vec<usize>>,
+}
+
+impl PatternScan {
+    fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            start: Instant::now(),
+            budget_exceeded: false,
+            first_window: true,
+            overlap: 0,
+            logical_counts: HashMap::new(),
+        }
+    }
+
+    fn within_budget(&mut self) -> bool {
+        if !self.budget_exceeded && self.start.elapsed() >= self.budget {
+            log::debug!("正则签名匹配超出时间预算 {:?}，跳过剩余正则和逻辑签名", self.budget);
+            self.budget_exceeded = true;
+        }
+        !self.budget_exceeded
+    }
+}
+
+fn read_chunk<R: std::io::Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+// 从一个病毒库文件解析出的内容
+struct DatabaseFile {
+    // (库名, CVD 文件头)，早期自定义格式没有文件头
+    header: Option<(String, CvdHeader)>,
+    signatures: Vec<Signature>,
+    hash_signatures: Vec<HashSignature>,
+}
+
+// 扫描用的病毒库快照，创建后不再修改，扫描时不获取任何锁。
+// 病毒库更新时复制一份修改后整体替换，已取得的快照保持不变；未修改的部分在新旧快照间共享
+#[derive(Clone)]
+pub struct SignatureSnapshot {
+    signatures: Arc<HashMap<String, Signature>>,
+    signatures_by_type: Arc<HashMap<String, Vec<String>>>,
+    hash_index: Arc<HashMap<String, Vec<HashSignature>>>,
+    hash_algorithms: HashSet<HashAlgorithm>,
+    regexes: Arc<HashMap<String, OnceLock<Option<Arc<Regex>>>>>,
+    logicals: Arc<HashMap<String, OnceLock<Option<Arc<LogicalSignature>>>>>,
+    // 签名变化后换成新的空单元，第一次扫描时按当前签名构建
+    matcher: Arc<OnceLock<ContentMatcher>>,
+    // 从病毒库文件加载的签名 ID，重新加载时整体替换
+    file_signatures: Arc<HashSet<String>>,
+    regex_time_budget: Duration,
+    scan_buffer_size: usize,
+    use_mmap: bool,
+}
+
+impl Default for SignatureSnapshot {
+    fn default() -> Self {
+        Self {
+            signatures: Arc::new(HashMap::new()),
+            signatures_by_type: Arc::new(HashMap::new()),
+            hash_index: Arc::new(HashMap::new()),
+            hash_algorithms: HashSet::new(),
+            regexes: Arc::new(HashMap::new()),
+            logicals: Arc::new(HashMap::new()),
+            matcher: Arc::default(),
+            file_signatures: Arc::new(HashSet::new()),
+            regex_time_budget: Duration::from_millis(DEFAULT_REGEX_TIME_BUDGET_MS),
+            scan_buffer_size: DEFAULT_SCAN_BUFFER_SIZE,
+            use_mmap: false,
+        }
+    }
+}
+
+// 签名的目标类型 (PE、ELF 等) 与文件类型不符时不参与匹配，顶层文件和压缩包等的成员按同样规则处理
+fn applies_to(sig: &Signature, kind: FileKind) -> bool {
+    kind.matches_target(&sig.target)
+}
+
+impl SignatureSnapshot {
+    // 文件无法读取时返回错误，不当作干净文件
+    pub fn scan_path(&self, path: &Path, kind: FileKind) -> Result<Option<ThreatSignature>> {
+        if let Some(mmap) = self.map_file(path) {
+            return Ok(self.scan_bytes(&mmap, kind));
+        }
+
+        let size = std::fs::metadata(path).with_context(|| format!("无法读取文件信息 {:?}", path))?.len();
+        if size > self.stream_threshold() {
+            return self.scan_stream(path, size, kind);
+        }
+
+        let data = std::fs::read(path).with_context(|| format!("无法读取文件 {:?}", path))?;
+        Ok(self.scan_bytes(&data, kind))
+    }
+
+    pub fn scan_bytes(&self, data: &[u8], kind: FileKind) -> Option<ThreatSignature> {
+        if let Some(sig) = self.whole_file_signature(&SignatureDatabase::calculate_hash(data), kind) {
+            return Some(SignatureDatabase::to_threat(sig));
+        }
+        self.match_content(data, kind)
+    }
+
+    fn whole_file_signature(&self, file_hash: &str, kind: FileKind) -> Option<&Signature> {
+        self.signatures.get(file_hash).filter(|sig| applies_to(sig, kind))
+    }
+
+    // 哈希未命中时逐条匹配特征码内容；正则签名共享同一个单文件时间预算
+    fn match_content(&self, data: &[u8], kind: FileKind) -> Option<ThreatSignature> {
+        if let Some(threat) = self.match_hash(data) {
+            return Some(threat);
+        }
+
+        let mut scan = PatternScan::new(self.regex_time_budget);
+        if let Some(sig) = self.scan_window(data, kind, &mut scan) {
+            return Some(SignatureDatabase::to_threat(sig));
+        }
+        self.finish_logical(&scan, kind)
+    }
+
+    // 字节序列特征码由多模式自动机一遍匹配，其余特征码逐条匹配。
+    // 自动机报告所有 (包括相互重叠的) 命中，目标类型不符的命中不会挡住后面的签名
+    fn scan_window(&self, data: &[u8], kind: FileKind, scan: &mut PatternScan) -> Option<&Signature> {
+        let matcher = self.matcher();
+        if let Some(automaton) = &matcher.automaton {
+            let hit = automaton
+                .find_overlapping_iter(data)
+                .filter_map(|found| self.signatures.get(&matcher.literal_ids[found.pattern().as_usize()]))
+                .find(|sig| applies_to(sig, kind));
+            if hit.is_some() {
+                return hit;
+            }
+        }
+
+        let candidates = matcher.other_ids.iter().filter_map(|id| self.signatures.get(id));
+        for sig in candidates.filter(|sig| applies_to(sig, kind)) {
+            let matched = match sig.pattern_type {
+                PatternType::Regex | PatternType::LogicalExpression => {
+                    if !scan.within_budget() {
+                        continue;
+                    }
+                    if sig.pattern_type == PatternType::Regex {
+                        // 偏移锚定的正则只对文件开头有效
+                        if !scan.first_window && sig.pattern.windows(2).any(|w| w == br"\A") {
+                            continue;
+                        }
+                        self.regex(sig).map_or(false, |regex| regex.is_match(data))
+                    } else {
+                        if let Some(logical) = self.logical(sig) {
+                            let min_end = if scan.first_window { 0 } else { scan.overlap };
+                            let counts = logical.count_matches(data, min_end);
+                            let total = scan
+                                .logical_counts
+                                .entry(sig.id.clone())
+                                .or_insert_with(|| vec![0; counts.len()]);
+                            for (total, count) in total.iter_mut().zip(counts) {
+                                *total += count;
+                            }
+                        }
+                        false
+                    }
+                }
+                pattern_type => SignatureDatabase::match_pattern(data, &sig.pattern, pattern_type),
+            };
+
+            if matched {
+                return Some(sig);
+            }
+        }
+
+        None
+    }
+
+    // 逻辑签名的子特征码命中次数在所有窗口累计后统一求值
+    fn finish_logical(&self, scan: &PatternScan, kind: FileKind) -> Option<ThreatSignature> {
+        for (id, counts) in &scan.logical_counts {
+            let Some(sig) = self.signatures.get(id).filter(|sig| applies_to(sig, kind)) else {
+                continue;
+            };
+            if self.logical(sig).map_or(false, |logical| logical.evaluate(counts)) {
+                return Some(SignatureDatabase::to_threat(sig));
+            }
+        }
+        None
+    }
+
+    // 大文件分两遍流式读取：先增量计算整文件摘要做哈希匹配，再按重叠窗口做特征码匹配，内存占用与文件大小无关
+    fn scan_stream(&self, path: &Path, size: u64, kind: FileKind) -> Result<Option<ThreatSignature>> {
+        let overlap = self.scan_buffer_size.max(1);
+        let chunk_size = overlap * STREAM_CHUNK_FACTOR;
+        let mut buffer = vec![0u8; chunk_size];
+
+        let mut hashers: Vec<(HashAlgorithm, openssl::hash::Hasher)> = self
+            .hash_algorithms
+            .iter()
+            .filter_map(|&algorithm| openssl::hash::Hasher::new(algorithm.message_digest()?).ok().map(|h| (algorithm, h)))
+            .collect();
+        let mut id_hasher = std::collections::hash_map::DefaultHasher::new();
+        std::hash::Hasher::write_usize(&mut id_hasher, size as usize);
+
+        let mut file = std::fs::File::open(path).with_context(|| format!("无法打开文件 {:?}", path))?;
+        loop {
+            let n = read_chunk(&mut file, &mut buffer).with_context(|| format!("无法读取文件 {:?}", path))?;
+            if n == 0 {
+                break;
+            }
+            std::hash::Hasher::write(&mut id_hasher, &buffer[..n]);
+            for (_, hasher) in hashers.iter_mut() {
+                hasher.update(&buffer[..n])?;
+            }
+        }
+
+        let file_hash = format!("{:x}", std::hash::Hasher::finish(&id_hasher));
+        if let Some(sig) = self.whole_file_signature(&file_hash, kind) {
+            return Ok(Some(SignatureDatabase::to_threat(sig)));
+        }
+        let mut digests: Vec<(HashAlgorithm, String)> = hashers
+            .into_iter()
+            .filter_map(|(algorithm, mut hasher)| hasher.finish().ok().map(|d| (algorithm, hex::encode(d))))
+            .collect();
+        // 导入表只需按偏移读取文件头和导入段，不必载入整个文件
+        if self.hash_algorithms.contains(&HashAlgorithm::Imphash) {
+            if let Some(digest) = std::fs::File::open(path).ok().and_then(|file| imphash(&file)) {
+                digests.push((HashAlgorithm::Imphash, digest));
+            }
+        }
+        if let Some(threat) = self.match_digests(&digests, size) {
+            return Ok(Some(threat));
+        }
+
+        let mut scan = PatternScan::new(self.regex_time_budget);
+        scan.overlap = overlap;
+        let mut window: Vec<u8> = Vec::with_capacity(chunk_size + overlap);
+        let mut file = std::fs::File::open(path).with_context(|| format!("无法打开文件 {:?}", path))?;
+
+        loop {
+            let n = read_chunk(&mut file, &mut buffer).with_context(|| format!("无法读取文件 {:?}", path))?;
+            if n == 0 {
+                break;
+            }
+            window.extend_from_slice(&buffer[..n]);
+            if let Some(sig) = self.scan_window(&window, kind, &mut scan) {
+                return Ok(Some(SignatureDatabase::to_threat(sig)));
+            }
+
+            // 保留窗口尾部作为下一块的前缀，跨块边界的特征码仍能完整匹配
+            let keep = overlap.min(window.len());
+            window.drain(..window.len() - keep);
+            scan.first_window = false;
+        }
+
+        Ok(self.finish_logical(&scan, kind))
+    }
+
+    // 只计算索引中实际存在的摘要算法，命中后还需满足文件大小约束
+    fn match_hash(&self, data: &[u8]) -> Option<ThreatSignature> {
+        if self.hash_index.is_empty() {
+            return None;
+        }
+
+        let digests: Vec<(HashAlgorithm, String)> = self
+            .hash_algorithms
+            .iter()
+            .filter_map(|algorithm| algorithm.digest(data).map(|digest| (*algorithm, digest)))
+            .collect();
+        self.match_digests(&digests, data.len() as u64)
+    }
+
+    fn match_digests(&self, digests: &[(HashAlgorithm, String)], size: u64) -> Option<ThreatSignature> {
+        for (algorithm, digest) in digests {
+            let hit = self.hash_index.get(digest).and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|h| h.algorithm == *algorithm && h.file_size.map_or(true, |s| s == size))
+            });
+
+            if let Some(hash_sig) = hit {
+                let (pattern_type, target) = match hash_sig.algorithm {
+                    HashAlgorithm::Imphash => (PatternType::Imphash, "1"),
+                    _ => (PatternType::Hash, "0"),
+                };
+                return Some(ThreatSignature {
+                    id: hash_sig.id.clone(),
+                    name: hash_sig.name.clone(),
+                    threat_type: hash_sig.threat_type.clone(),
+                    risk_level: hash_sig.risk_level.clone(),
+                    encrypted_pattern: hash_sig.digest.as_bytes().to_vec(),
+                    pattern_type,
+                    decompressed_size: size,
+                    offset: 0,
+                    target: target.to_string(),
+                });
+            }
+        }
+
+        None
+    }
+
+    // 映射失败 (空文件、不支持mmap的文件系统等) 时返回 None，由调用方回退到缓冲读取
+    fn map_file(&self, path: &Path) -> Option<MappedFile> {
+        if !self.use_mmap {
+            return None;
+        }
+
+        let file = std::fs::File::open(path).ok()?;
+        match MappedFile::map(&file) {
+            Ok(mmap) => Some(mmap),
+            Err(e) => {
+                log::debug!("无法映射文件 {:?}，改用缓冲读取: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn stream_threshold(&self) -> u64 {
+        (self.scan_buffer_size * STREAM_CHUNK_FACTOR) as u64
+    }
+
+    fn matcher(&self) -> &ContentMatcher {
+        self.matcher.get_or_init(|| ContentMatcher::build(&self.signatures))
+    }
+
+    // 正则和逻辑签名在第一次用到时编译，之后的扫描无锁读取
+    fn regex(&self, sig: &Signature) -> Option<&Regex> {
+        self.regexes.get(&sig.id)?.get_or_init(|| compile_regex(sig)).as_deref()
+    }
+
+    fn logical(&self, sig: &Signature) -> Option<&LogicalSignature> {
+        self.logicals.get(&sig.id)?.get_or_init(|| compile_logical(sig)).as_deref()
+    }
+
+    // 替换同 ID 的旧签名，旧签名的编译结果一并丢弃
+    fn insert_signatures(&mut self, new_signatures: Vec<Signature>) {
+        let signatures = Arc::make_mut(&mut self.signatures);
+        let by_type = Arc::make_mut(&mut self.signatures_by_type);
+        let regexes = Arc::make_mut(&mut self.regexes);
+        let logicals = Arc::make_mut(&mut self.logicals);
+        self.matcher = Arc::default();
+
+        for sig in new_signatures {
+            regexes.remove(&sig.id);
+            logicals.remove(&sig.id);
+            match sig.pattern_type {
+                PatternType::Regex => {
+                    regexes.insert(sig.id.clone(), OnceLock::new());
+                }
+                PatternType::LogicalExpression => {
+                    logicals.insert(sig.id.clone(), OnceLock::new());
+                }
+                _ => {}
+            }
+            by_type
+                .entry(sig.threat_type.clone())
+                .or_insert_with(Vec::new)
+                .push(sig.id.clone());
+            signatures.insert(sig.id.clone(), sig);
+        }
+    }
+
+    fn insert_hash_signatures(&mut self, hash_signatures: Vec<HashSignature>) {
+        let index = Arc::make_mut(&mut self.hash_index);
+        for hash_sig in hash_signatures {
+            self.hash_algorithms.insert(hash_sig.algorithm);
+            let entries = index.entry(hash_sig.digest.clone()).or_insert_with(Vec::new);
+            entries.retain(|h| h.id != hash_sig.id);
+            entries.push(hash_sig);
+        }
+    }
+
+    fn insert_file(&mut self, file: DatabaseFile) {
+        let ids = Arc::make_mut(&mut self.file_signatures);
+        ids.extend(file.signatures.iter().map(|sig| sig.id.clone()));
+        ids.extend(file.hash_signatures.iter().map(|h| h.id.clone()));
+        self.insert_hash_signatures(file.hash_signatures);
+        self.insert_signatures(file.signatures);
+    }
+
+    // 返回删除的签名数，不含哈希索引中的条目
+    fn remove_where(&mut self, remove: impl Fn(&str) -> bool) -> usize {
+        let signatures = Arc::make_mut(&mut self.signatures);
+        let before = signatures.len();
+        signatures.retain(|id, _| !remove(id));
+        let removed = before - signatures.len();
+        if removed > 0 {
+            self.matcher = Arc::default();
+        }
+
+        let by_type = Arc::make_mut(&mut self.signatures_by_type);
+        for ids in by_type.values_mut() {
+            ids.retain(|id| !remove(id));
+        }
+        by_type.retain(|_, ids| !ids.is_empty());
+        Arc::make_mut(&mut self.regexes).retain(|id, _| !remove(id));
+        Arc::make_mut(&mut self.logicals).retain(|id, _| !remove(id));
+        Arc::make_mut(&mut self.file_signatures).retain(|id| !remove(id));
+
+        let index = Arc::make_mut(&mut self.hash_index);
+        for entries in index.values_mut() {
+            entries.retain(|h| !remove(&h.id));
+        }
+        index.retain(|_, entries| !entries.is_empty());
+        removed
+    }
+
+    // 丢弃所有编译结果，之后用到的签名重新编译
+    fn reset_compiled(&mut self) {
+        self.regexes = Arc::new(self.regexes.keys().map(|id| (id.clone(), OnceLock::new())).collect());
+        self.logicals = Arc::new(self.logicals.keys().map(|id| (id.clone(), OnceLock::new())).collect());
+        self.matcher = Arc::default();
+    }
+
+    fn compiled_count(&self) -> usize {
+        let regexes = self.regexes.values().filter(|cell| cell.get().is_some()).count();
+        let logicals = self.logicals.values().filter(|cell| cell.get().is_some()).count();
+        regexes + logicals
+    }
+
+    fn memory_usage(&self) -> u64 {
+        let patterns: u64 = self.signatures.values().map(|s| s.pattern.len() as u64).sum();
+        let hashes: u64 = self
+            .hash_index
+            .values()
+            .flatten()
+            .map(|h| (h.id.len() + h.name.len() + h.digest.len()) as u64 + HASH_ENTRY_OVERHEAD)
+            .sum();
+        patterns + hashes
+    }
+}
+
+// 快照中所有字节序列特征码编译成的 Aho-Corasick 自动机，每个窗口只需扫描一遍。
+// 快照不可变，自动机构建后在所有扫描线程间共享
+#[derive(Default)]
+struct ContentMatcher {
+    automaton: Option<AhoCorasick>,
+    // 自动机中第 i 个模式对应的签名 ID
+    literal_ids: Vec<String>,
+    // 扩展字节序列、正则和逻辑签名，仍需逐条匹配
+    other_ids: Vec<String>,
+}
+
+impl ContentMatcher {
+    fn build(signatures: &HashMap<String, Signature>) -> Self {
+        let mut literal_ids = Vec::new();
+        let mut patterns: Vec<&[u8]> = Vec::new();
+        let mut other_ids = Vec::new();
+        for sig in signatures.values() {
+            match sig.pattern_type {
+                PatternType::ByteSequence if !sig.pattern.is_empty() => {
+                    literal_ids.push(sig.id.clone());
+                    patterns.push(&sig.pattern);
+                }
+                PatternType::ExtendedByteSequence | PatternType::Regex | PatternType::LogicalExpression
+                    if !sig.pattern.is_empty() =>
+                {
+                    other_ids.push(sig.id.clone())
+                }
+                _ => {}
+            }
+        }
+
+        let automaton = match AhoCorasick::new(&patterns) {
+            Ok(automaton) if !patterns.is_empty() => Some(automaton),
+            Ok(_) => None,
+            Err(e) => {
+                log::error!("无法构建特征码自动机，字节序列特征码改为逐条匹配: {}", e);
+                other_ids.append(&mut literal_ids);
+                None
+            }
+        };
+        Self {
+            automaton,
+            literal_ids,
+            other_ids,
+        }
+    }
+}
+
+fn compile_regex(sig: &Signature) -> Option<Arc<Regex>> {
+    let source = String::from_utf8_lossy(&sig.pattern);
+    match RegexBuilder::new(&source).unicode(false).size_limit(REGEX_SIZE_LIMIT).build() {
+        Ok(regex) => Some(Arc::new(regex)),
+        Err(e) => {
+            log::warn!("无效的正则签名 {}: {}", sig.id, e);
+            None
+        }
+    }
+}
+
+fn compile_logical(sig: &Signature) -> Option<Arc<LogicalSignature>> {
+    let logical = LogicalSignature::parse(&String::from_utf8_lossy(&sig.pattern));
+    if logical.is_none() {
+        log::warn!("无效的逻辑签名 {}", sig.id);
+    }
+    logical.map(Arc::new)
+}
+
 pub struct SignatureDatabase {
-    signatures: Arc<RwLock<HashMap<String, Signature>>>,
-    signatures_by_type: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    current: ArcSwap<SignatureSnapshot>,
+    // 串行化更新，避免并发的复制-修改-替换互相覆盖；扫描不需要这个锁
+    update_lock: Mutex<()>,
     hash_cache: Arc<Mutex<LruCache<String, String>>>,
     memory_usage: Arc<Mutex<u64>>,
     last_update: Arc<Mutex<Option<Instant>>>,
     version: Arc<Mutex<String>>,
+    database_headers: Arc<Mutex<HashMap<String, CvdHeader>>>,
 }
 
 impl SignatureDatabase {
     pub fn new() -> Self {
         Self {
-            signatures: Arc::new(RwLock::new(HashMap::new())),
-            signatures_by_type: Arc::new(RwLock::new(HashMap::new())),
+            current: ArcSwap::from_pointee(SignatureSnapshot::default()),
+            update_lock: Mutex::new(()),
             hash_cache: Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(1000).unwrap()))),
             memory_usage: Arc::new(Mutex::new(0)),
             last_update: Arc::new(Mutex::new(None)),
             version: Arc::new(Mutex::new(String::from("0.0.0"))),
+            database_headers: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
     pub async fn load_from_cvd<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
-        log::info!("正在加载病毒库: {:?}", path.as_ref());
+        let mut file = Self::read_database_file(path.as_ref())?;
+
+        if let Some((db_name, header)) = file.header.take() {
+            let mut headers = self.database_headers.lock().unwrap();
+            headers.insert(db_name, header);
+            let latest = headers.values().map(|h| h.version).max().unwrap_or_default();
+            drop(headers);
+            self.set_version(latest.to_string());
+        }
+
+        // 特征码和哈希签名在同一个快照中生效
+        let total = self.publish(|snapshot| {
+            snapshot.insert_file(file);
+            snapshot.signatures.len()
+        });
+
+        log::info!("已加载 {} 条病毒特征码", total);
+
+        Ok(())
+    }
+
+    // 重新读取目录中的全部病毒库文件，在一个新快照中整体替换此前从文件加载的签名；
+    // 任一文件无法解析时保留当前病毒库。内置签名和 MISP 导入的签名不受影响
+    pub async fn reload_from_directory<P: AsRef<Path>>(&self, dir: P) -> Result<usize, anyhow::Error> {
+        log::info!("正在重新加载病毒库: {:?}", dir.as_ref());
+
+        let paths = Self::database_files(dir.as_ref());
+        if paths.is_empty() {
+            return Err(anyhow::anyhow!("病毒库目录中没有病毒库文件: {:?}", dir.as_ref()));
+        }
+        let mut files = Vec::new();
+        for path in &paths {
+            files.push(Self::read_database_file(path).with_context(|| format!("无法加载病毒库 {:?}", path))?);
+        }
+
+        let headers: HashMap<String, CvdHeader> = files.iter_mut().filter_map(|f| f.header.take()).collect();
+        let latest = headers.values().map(|h| h.version).max();
+        let total = self.publish(|snapshot| {
+            let previous = std::mem::take(Arc::make_mut(&mut snapshot.file_signatures));
+            snapshot.remove_where(|id| previous.contains(id));
+            for file in files {
+                snapshot.insert_file(file);
+            }
+            snapshot.signatures.len()
+        });
+        *self.database_headers.lock().unwrap() = headers;
+        if let Some(latest) = latest {
+            self.set_version(latest.to_string());
+        }
+
+        log::info!("病毒库已重新加载 ({} 个文件)，特征码数量: {}", paths.len(), total);
+        Ok(total)
+    }
+
+    fn database_files(dir: &Path) -> Vec<PathBuf> {
+        WalkDir::new(dir)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy();
+                name.ends_with(".cvd") || name.ends_with(".cld") || SignatureFormat::from_file_name(&name).is_some()
+            })
+            .map(|e| e.into_path())
+            .collect()
+    }
+
+    fn read_database_file(path: &Path) -> Result<DatabaseFile, anyhow::Error> {
+        log::info!("正在加载病毒库: {:?}", path);
+
+        let mut magic = [0u8; 10];
+        let is_cvd = std::fs::File::open(path)
+            .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut magic))
+            .map(|_| &magic == b"ClamAV-VDB")
+            .unwrap_or(false);
+
+        if is_cvd {
+            let cvd = read_cvd(path)?;
+            log::info!(
+                "病毒库版本 {} (构建于 {}，构建者 {})，声明 {} 条特征码，解析 {} 条，其中哈希 {} 条，跳过 {} 条",
+                cvd.header.version,
+                cvd.header.build_time,
+                cvd.header.builder,
+                cvd.header.signature_count,
+                cvd.signatures.len() + cvd.hash_signatures.len(),
+                cvd.hash_signatures.len(),
+                cvd.skipped
+            );
+
+            let db_name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            Ok(DatabaseFile {
+                header: Some((db_name, cvd.header)),
+                signatures: cvd.signatures,
+                hash_signatures: cvd.hash_signatures,
+            })
+        } else if let Some(format) = path.file_name().and_then(|name| SignatureFormat::from_file_name(&name.to_string_lossy())) {
+            let (signatures, hash_signatures, invalid_lines) = Self::read_signature_file(path, format)?;
+            if !invalid_lines.is_empty() {
+                log::warn!("{:?} 中有 {} 行无法解析，已跳过 (行号 {:?})", path, invalid_lines.len(), invalid_lines);
+            }
+            Ok(DatabaseFile {
+                header: None,
+                signatures,
+                hash_signatures,
+            })
+        } else {
+            Ok(DatabaseFile {
+                header: None,
+                signatures: Self::read_legacy_database(path)?,
+                hash_signatures: Vec::new(),
+            })
+        }
+    }
+
+    // 病毒库目录中的 .ndb、.ldb、.hdb、.hsb、.imp 文本文件，每行一条特征码，用于本地自定义特征码。
+    // 返回无法解析的行号
+    fn read_signature_file(
+        path: &Path,
+        format: SignatureFormat,
+    ) -> Result<(Vec<Signature>, Vec<HashSignature>, Vec<usize>), anyhow::Error> {
+        let content = std::fs::read_to_string(path).context("无法读取特征码文件")?;
+        let mut signatures = Vec::new();
+        let mut hash_signatures = Vec::new();
+        let mut invalid_lines = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match format.parse_line(line) {
+                Some(ParsedSignature::Pattern(sig)) => signatures.push(sig),
+                Some(ParsedSignature::Hash(sig)) => hash_signatures.push(sig),
+                None => invalid_lines.push(index + 1),
+            }
+        }
+        Ok((signatures, hash_signatures, invalid_lines))
+    }
+
+    // 逐个解析目录中的病毒库文件 (包括 CVD 的 MD5 校验)，不影响当前加载的病毒库
+    pub fn verify_directory(dir: &Path) -> Vec<DatabaseFileCheck> {
+        let mut paths = Self::database_files(dir);
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                let mut check = DatabaseFileCheck {
+                    path: path.clone(),
+                    version: None,
+                    signatures: 0,
+                    skipped: 0,
+                    invalid_lines: Vec::new(),
+                    error: None,
+                };
+                let format = path.file_name().and_then(|name| SignatureFormat::from_file_name(&name.to_string_lossy()));
+                let result = match format {
+                    Some(format) => Self::read_signature_file(&path, format).map(|(signatures, hashes, invalid_lines)| {
+                        check.signatures = signatures.len() + hashes.len();
+                        check.invalid_lines = invalid_lines;
+                    }),
+                    None => read_cvd(&path).map(|cvd| {
+                        check.version = Some(cvd.header.version);
+                        check.signatures = cvd.signatures.len() + cvd.hash_signatures.len();
+                        check.skipped = cvd.skipped;
+                    }),
+                };
+                if let Err(e) = result {
+                    check.error = Some(format!("{:#}", e));
+                }
+                check
+            })
+            .collect()
+    }
 
+    // 早期自定义格式: ZIP 包内 main.cvd 为 CSV 记录
+    fn read_legacy_database(path: &Path) -> Result<Vec<Signature>, anyhow::Error> {
         let file = std::fs::File::open(path).context("无法打开病毒库文件")?;
         let reader = std::io::BufReader::new(file);
 
@@ -95,22 +933,11 @@ impl SignatureDatabase {
             signatures.push(signature);
         }
 
-        let mut sig_map = self.signatures.write().await;
-        let mut type_map = self.signatures_by_type.write().await;
-
-        for sig in signatures {
-            sig_map.insert(sig.id.clone(), sig.clone());
-            type_map
-                .entry(sig.threat_type.clone())
-                .or_insert_with(Vec::new)
-                .push(sig.id.clone());
-        }
-
-        *self.memory_usage.lock().unwrap() = self.calculate_memory_usage().await;
-
-        log::info!("已加载 {} 条病毒特征码", sig_map.len());
+        Ok(signatures)
+    }
 
-        Ok(())
+    pub fn get_database_headers(&self) -> HashMap<String, CvdHeader> {
+        self.database_headers.lock().unwrap().clone()
     }
 
     pub async fn load_from_directory<P: AsRef<Path>>(
@@ -121,14 +948,10 @@ impl SignatureDatabase {
 
         let mut loaded_count = 0;
 
-        for entry in WalkDir::new(dir)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_name().to_string_lossy().ends_with(".cvd"))
-        {
-            if self.load_from_cvd(entry.path()).await.is_ok() {
-                loaded_count += 1;
+        for path in Self::database_files(dir.as_ref()) {
+            match self.load_from_cvd(&path).await {
+                Ok(()) => loaded_count += 1,
+                Err(e) => log::warn!("无法加载病毒库 {:?}: {}", path, e),
             }
         }
 
@@ -141,106 +964,103 @@ impl SignatureDatabase {
         &self,
         path: P,
     ) -> Result<Option<ThreatSignature>, anyhow::Error> {
-        let path_str = path.as_ref().to_string_lossy().to_string();
-
-        let mut cache = self.hash_cache.lock().unwrap();
-        if let Some(cached) = cache.get(&path_str) {
-            if let Some(sig_id) = self.signatures.read().await.get(cached) {
-                return Ok(Some(ThreatSignature {
-                    id: sig_id.id.clone(),
-                    name: sig_id.id.clone(),
-                    threat_type: sig_id.threat_type.clone(),
-                    risk_level: sig_id.risk_level.clone(),
-                    encrypted_pattern: sig_id.pattern.clone(),
-                    pattern_type: sig_id.pattern_type,
-                    decompressed_size: sig_id.pattern.len() as u64,
-                    offset: 0,
-                    target: sig_id.target.clone(),
-                }));
-            }
+        let kind = detect_file_type(path.as_ref()).unwrap_or(FileKind::Unknown);
+        self.scan_file_sync(path, kind).await
+    }
+
+    // 文件无法读取时返回错误，由调用方计入扫描错误
+    pub async fn scan_file_sync<P: AsRef<Path>>(
+        &self,
+        path: P,
+        kind: FileKind,
+    ) -> Result<Option<ThreatSignature>, anyhow::Error> {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy().to_string();
+        let snapshot = self.snapshot();
+
+        let cached = self.hash_cache.lock().unwrap().get(&path_str).cloned();
+        if let Some(sig) = cached.and_then(|id| snapshot.whole_file_signature(&id, kind)) {
+            return Ok(Some(Self::to_threat(sig)));
         }
-        drop(cache);
 
-        let file_data = match std::fs::read(path) {
-            Ok(data) => data,
-            Err(_) => return Ok(None),
-        };
+        if let Some(mmap) = snapshot.map_file(path) {
+            return Ok(snapshot.scan_bytes(&mmap, kind));
+        }
 
-        let file_hash = Self::calculate_hash(&file_data);
+        let size = std::fs::metadata(path).with_context(|| format!("无法读取文件信息 {:?}", path))?.len();
+        if size > snapshot.stream_threshold() {
+            return snapshot.scan_stream(path, size, kind);
+        }
 
-        let mut signatures = self.signatures.write().await;
-        if let Some(sig_id) = signatures.get(&file_hash) {
-            let mut cache = self.hash_cache.lock().unwrap();
-            cache.put(path_str, sig_id.id.clone());
-            return Ok(Some(ThreatSignature {
-                id: sig_id.id.clone(),
-                name: sig_id.id.clone(),
-                threat_type: sig_id.threat_type.clone(),
-                risk_level: sig_id.risk_level.clone(),
-                encrypted_pattern: sig_id.pattern.clone(),
-                pattern_type: sig_id.pattern_type,
-                decompressed_size: sig_id.pattern.len() as u64,
-                offset: 0,
-                target: sig_id.target.clone(),
-            }));
+        let file_data = std::fs::read(path).with_context(|| format!("无法读取文件 {:?}", path))?;
+        if let Some(sig) = snapshot.whole_file_signature(&Self::calculate_hash(&file_data), kind) {
+            self.hash_cache.lock().unwrap().put(path_str, sig.id.clone());
+            return Ok(Some(Self::to_threat(sig)));
         }
+        Ok(snapshot.match_content(&file_data, kind))
+    }
 
-        drop(signatures);
-        drop(file_data);
+    // 整批文件共用一个快照，扫描期间更新病毒库不影响本批结果
+    pub async fn scan_batch(&self, paths: &[PathBuf]) -> Vec<Result<Option<ThreatSignature>>> {
+        let snapshot = self.snapshot();
+        paths
+            .iter()
+            .map(|path| snapshot.scan_path(path, detect_file_type(path).unwrap_or(FileKind::Unknown)))
+            .collect()
+    }
 
-        Ok(None)
+    // 内存中的内容 (压缩包、邮件和 PDF 的成员等) 按文件头判断类型
+    pub async fn scan_bytes(&self, data: &[u8]) -> Option<ThreatSignature> {
+        self.snapshot().scan_bytes(data, detect_file_type_from_bytes(data))
     }
 
-    pub async fn scan_file_sync<P: AsRef<Path>>(
-        &self,
-        path: P,
-    ) -> Option<ThreatSignature> {
-        let path_str = path.as_ref().to_string_lossy().to_string();
+    // 当前病毒库的快照，不会被正在进行的更新阻塞
+    pub fn snapshot(&self) -> Arc<SignatureSnapshot> {
+        self.current.load_full()
+    }
 
-        let mut cache = self.hash_cache.lock().unwrap();
-        if let Some(cached) = cache.get(&path_str) {
-            let signatures = self.signatures.read().await;
-            if let Some(sig_id) = signatures.get(cached) {
-                return Some(ThreatSignature {
-                    id: sig_id.id.clone(),
-                    name: sig_id.name.clone(),
-                    threat_type: sig_id.threat_type.clone(),
-                    risk_level: sig_id.risk_level.clone(),
-                    encrypted_pattern: sig_id.pattern.clone(),
-                    pattern_type: sig_id.pattern_type,
-                    decompressed_size: sig_id.pattern.len() as u64,
-                    offset: 0,
-                    target: sig_id.target.clone(),
-                });
-            }
-        }
-        drop(cache);
+    // 复制当前快照、修改后整体替换，扫描线程始终看到完整的新旧版本之一
+    fn publish<T>(&self, update: impl FnOnce(&mut SignatureSnapshot) -> T) -> T {
+        let _guard = self.update_lock.lock().unwrap();
+        let mut next = SignatureSnapshot::clone(&self.current.load());
+        let result = update(&mut next);
+        *self.memory_usage.lock().unwrap() = next.memory_usage();
+        self.current.store(Arc::new(next));
+        result
+    }
 
-        let file_data = match std::fs::read(path.as_ref()) {
-            Ok(data) => data,
-            Err(_) => return None,
-        };
+    pub async fn add_hash_signatures(&self, hash_signatures: Vec<HashSignature>) {
+        self.publish(|snapshot| snapshot.insert_hash_signatures(hash_signatures));
+    }
 
-        let file_hash = Self::calculate_hash(&file_data);
-
-        let mut signatures = self.signatures.write().await;
-        if let Some(sig_id) = signatures.get(&file_hash) {
-            let mut cache = self.hash_cache.lock().unwrap();
-            cache.put(path_str, sig_id.id.clone());
-            return Some(ThreatSignature {
-                id: sig_id.id.clone(),
-                name: sig_id.name.clone(),
-                threat_type: sig_id.threat_type.clone(),
-                risk_level: sig_id.risk_level.clone(),
-                encrypted_pattern: sig_id.pattern.clone(),
-                pattern_type: sig_id.pattern_type,
-                decompressed_size: sig_id.pattern.len() as u64,
-                offset: 0,
-                target: sig_id.target.clone(),
-            });
-        }
+    pub async fn get_hash_signature_count(&self) -> usize {
+        self.snapshot().hash_index.values().map(|entries| entries.len()).sum()
+    }
 
-        None
+    pub fn set_regex_time_budget(&self, budget: Duration) {
+        self.publish(|snapshot| snapshot.regex_time_budget = budget);
+    }
+
+    pub fn set_scan_buffer_size(&self, size: usize) {
+        self.publish(|snapshot| snapshot.scan_buffer_size = size.max(1));
+    }
+
+    pub fn set_use_mmap(&self, enabled: bool) {
+        self.publish(|snapshot| snapshot.use_mmap = enabled);
+    }
+
+    fn to_threat(sig: &Signature) -> ThreatSignature {
+        ThreatSignature {
+            id: sig.id.clone(),
+            name: sig.name.clone(),
+            threat_type: sig.threat_type.clone(),
+            risk_level: sig.risk_level.clone(),
+            encrypted_pattern: sig.pattern.clone(),
+            pattern_type: sig.pattern_type,
+            decompressed_size: sig.pattern.len() as u64,
+            offset: 0,
+            target: sig.target.clone(),
+        }
     }
 
     fn match_pattern(
@@ -248,6 +1068,10 @@ impl SignatureDatabase {
         pattern: &[u8],
         pattern_type: PatternType,
     ) -> bool {
+        if pattern.is_empty() {
+            return false;
+        }
+
         match pattern_type {
             PatternType::ByteSequence => data.windows(pattern.len()).any(|w| w == pattern),
             PatternType::ExtendedByteSequence => {
@@ -301,20 +1125,103 @@ impl SignatureDatabase {
             "regex" => PatternType::Regex,
             "pe" => PatternType::PEHeader,
             "hash" => PatternType::Hash,
+            "imphash" => PatternType::Imphash,
             _ => PatternType::ByteSequence,
         }
     }
 
-    async fn calculate_memory_usage(&self) -> u64 {
-        self.signatures.read().await.values().map(|s| s.pattern.len() as u64).sum()
+    // 编译缓存随扫描增长，单独统计以便在内存紧张时回收
+    pub fn get_cache_memory_usage(&self) -> u64 {
+        self.snapshot().compiled_count() as u64 * COMPILED_PATTERN_ESTIMATE
+    }
+
+    // 清空可重建的缓存，之后用到的签名会重新编译；正在使用旧快照的扫描不受影响
+    pub fn evict_caches(&self) {
+        self.publish(SignatureSnapshot::reset_compiled);
+        self.hash_cache.lock().unwrap().clear();
+    }
+
+    // 扫描一个文件时需要的堆内存：内存映射不占堆，超过阈值的文件按块流式读取
+    pub fn read_footprint(&self, size: u64) -> u64 {
+        let snapshot = self.snapshot();
+        if snapshot.use_mmap {
+            return 0;
+        }
+        let threshold = snapshot.stream_threshold();
+        if size > threshold {
+            threshold + snapshot.scan_buffer_size as u64
+        } else {
+            size
+        }
     }
 
     pub fn get_memory_usage(&self) -> u64 {
         *self.memory_usage.lock().unwrap()
     }
 
+    // 由 Signature 派生的哈希索引项已计入 signatures，不重复统计
     pub async fn get_signature_count(&self) -> usize {
-        self.signatures.read().await.len()
+        let snapshot = self.snapshot();
+        let hash_only = snapshot
+            .hash_index
+            .values()
+            .flatten()
+            .filter(|h| !snapshot.signatures.contains_key(&h.id))
+            .count();
+        snapshot.signatures.len() + hash_only
+    }
+
+    // 按特征码类型统计，只在哈希索引中的签名按哈希算法统计
+    pub fn signature_type_counts(&self) -> BTreeMap<&'static str, usize> {
+        let snapshot = self.snapshot();
+        let mut counts = BTreeMap::new();
+        for sig in snapshot.signatures.values() {
+            *counts.entry(sig.pattern_type.label()).or_insert(0) += 1;
+        }
+        for hash_sig in snapshot.hash_index.values().flatten() {
+            if !snapshot.signatures.contains_key(&hash_sig.id) {
+                *counts.entry(hash_sig.algorithm.label()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    // 按名称或 ID 查找 (不区分大小写的子串匹配)，按 ID 排序
+    pub fn search(&self, query: &str) -> Vec<SignatureSummary> {
+        let snapshot = self.snapshot();
+        let query = query.to_lowercase();
+        let matches = |id: &str, name: &str| id.to_lowercase().contains(&query) || name.to_lowercase().contains(&query);
+
+        let mut found: Vec<SignatureSummary> = snapshot
+            .signatures
+            .values()
+            .filter(|sig| matches(&sig.id, &sig.name))
+            .map(|sig| SignatureSummary {
+                id: sig.id.clone(),
+                name: sig.name.clone(),
+                threat_type: sig.threat_type.clone(),
+                risk_level: sig.risk_level.clone(),
+                kind: sig.pattern_type.label(),
+            })
+            .collect();
+        let mut seen: HashSet<&str> = HashSet::new();
+        for hash_sig in snapshot.hash_index.values().flatten() {
+            if snapshot.signatures.contains_key(&hash_sig.id) || !matches(&hash_sig.id, &hash_sig.name) {
+                continue;
+            }
+            // 同名的多个摘要只列出一次
+            if seen.insert(hash_sig.id.as_str()) {
+                found.push(SignatureSummary {
+                    id: hash_sig.id.clone(),
+                    name: hash_sig.name.clone(),
+                    threat_type: hash_sig.threat_type.clone(),
+                    risk_level: hash_sig.risk_level.clone(),
+                    kind: hash_sig.algorithm.label(),
+                });
+            }
+        }
+        found.sort_by(|a, b| a.id.cmp(&b.id));
+        found
     }
 
     pub fn get_last_update(&self) -> Option<Instant> {
@@ -337,21 +1244,38 @@ impl SignatureDatabase {
         &self,
         new_signatures: Vec<Signature>,
     ) -> Result<(), anyhow::Error> {
-        let mut sig_map = self.signatures.write().await;
-        let mut type_map = self.signatures_by_type.write().await;
+        let hash_signatures: Vec<HashSignature> = new_signatures
+            .iter()
+            .filter(|sig| matches!(sig.pattern_type, PatternType::Hash | PatternType::Imphash))
+            .filter_map(HashSignature::from_signature)
+            .collect();
 
-        for sig in new_signatures {
-            sig_map.insert(sig.id.clone(), sig.clone());
-            type_map
-                .entry(sig.threat_type.clone())
-                .or_insert_with(Vec::new)
-                .push(sig.id.clone());
-        }
-
-        *self.memory_usage.lock().unwrap() = self.calculate_memory_usage().await;
+        self.publish(|snapshot| {
+            snapshot.insert_hash_signatures(hash_signatures);
+            snapshot.insert_signatures(new_signatures);
+        });
 
         Ok(())
     }
+
+    // 内置签名不依赖病毒库文件，即使病毒库为空也能完成自检
+    pub async fn load_builtin_signatures(&self) -> Result<(), anyhow::Error> {
+        self.update_signatures(vec![Signature {
+            id: EICAR_SIGNATURE_ID.to_string(),
+            name: EICAR_SIGNATURE_ID.to_string(),
+            threat_type: "Test".to_string(),
+            risk_level: "Medium".to_string(),
+            pattern: eicar_test_string(),
+            pattern_type: PatternType::ByteSequence,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+    }
+
+    pub async fn remove_signatures_by_prefix(&self, prefix: &str) -> usize {
+        self.publish(|snapshot| snapshot.remove_where(|id| id.starts_with(prefix)))
+    }
 }
 
 impl Default for SignatureDatabase {
@@ -0,0 +1,412 @@
+use anyhow::Result;
+
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHF_EXECINSTR: u64 = 0x4;
+const DT_NEEDED: u64 = 1;
+
+// 防止畸形文件声明超大表项数量导致长时间解析
+const MAX_TABLE_ENTRIES: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfSection {
+    pub name: String,
+    pub section_type: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    link: u32,
+    entry_size: u64,
+}
+
+impl ElfSection {
+    pub fn is_executable(&self) -> bool {
+        self.flags & SHF_EXECINSTR != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfSegment {
+    pub segment_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub virtual_address: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+}
+
+impl ElfSegment {
+    pub fn is_executable(&self) -> bool {
+        self.flags & PF_X != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.flags & PF_W != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElfFlag {
+    WritableExecutableSegment,
+    UpxPacked,
+    SectionHeadersStripped,
+    EntryPointOutsideCode,
+    Stripped,
+}
+
+impl ElfFlag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElfFlag::WritableExecutableSegment => "writable-executable-segment",
+            ElfFlag::UpxPacked => "upx-packed",
+            ElfFlag::SectionHeadersStripped => "section-headers-stripped",
+            ElfFlag::EntryPointOutsideCode => "entry-point-outside-code",
+            ElfFlag::Stripped => "stripped",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfInfo {
+    pub class: ElfClass,
+    pub little_endian: bool,
+    pub file_type: u16,
+    pub machine: u16,
+    pub entry_point: u64,
+    pub interpreter: Option<String>,
+    pub sections: Vec<ElfSection>,
+    pub segments: Vec<ElfSegment>,
+    pub needed_libraries: Vec<String>,
+    pub imported_symbols: Vec<String>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+    class: ElfClass,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: u64, len: usize) -> Result<&'a [u8]> {
+        let start = usize::try_from(offset)?;
+        self.data
+            .get(start..start.checked_add(len).ok_or_else(|| anyhow::anyhow!("ELF偏移溢出"))?)
+            .ok_or_else(|| anyhow::anyhow!("ELF数据越界: 偏移 {}", offset))
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16> {
+        let b: [u8; 2] = self.bytes(offset, 2)?.try_into()?;
+        Ok(if self.little_endian { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32> {
+        let b: [u8; 4] = self.bytes(offset, 4)?.try_into()?;
+        Ok(if self.little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        let b: [u8; 8] = self.bytes(offset, 8)?.try_into()?;
+        Ok(if self.little_endian { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) })
+    }
+
+    // 按 ELF 类别读取地址/偏移宽度的字段
+    fn word(&self, offset: u64) -> Result<u64> {
+        match self.class {
+            ElfClass::Elf32 => self.u32(offset).map(u64::from),
+            ElfClass::Elf64 => self.u64(offset),
+        }
+    }
+
+    // 表项偏移 start + index * entry_size。这些数值都来自文件内容，溢出或超出文件长度时按文件损坏处理；
+    // 返回的偏移不超过文件长度，再加上表项内字段的固定偏移不会溢出
+    fn entry_offset(&self, start: u64, index: u64, entry_size: u64) -> Result<u64> {
+        index
+            .checked_mul(entry_size)
+            .and_then(|offset| start.checked_add(offset))
+            .filter(|&offset| offset <= self.data.len() as u64)
+            .ok_or_else(|| anyhow::anyhow!("ELF表项偏移无效"))
+    }
+
+    // 字符串表中的字符串，表偏移加索引溢出时视为不存在
+    fn table_string(&self, table: u64, index: u64) -> Option<String> {
+        self.c_string(table.checked_add(index)?)
+    }
+
+    fn c_string(&self, offset: u64) -> Option<String> {
+        let start = usize::try_from(offset).ok()?;
+        let rest = self.data.get(start..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).to_string())
+    }
+}
+
+impl ElfInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"\x7fELF") || data.len() < 0x34 {
+            return Err(anyhow::anyhow!("不是有效的ELF文件"));
+        }
+
+        let class = match data[4] {
+            1 => ElfClass::Elf32,
+            2 => ElfClass::Elf64,
+            other => return Err(anyhow::anyhow!("未知的ELF类别: {}", other)),
+        };
+        let little_endian = match data[5] {
+            1 => true,
+            2 => false,
+            other => return Err(anyhow::anyhow!("未知的ELF字节序: {}", other)),
+        };
+        let r = Reader {
+            data,
+            little_endian,
+            class,
+        };
+
+        let (entry_at, phoff_at, shoff_at, sizes_at) = match class {
+            ElfClass::Elf32 => (0x18, 0x1c, 0x20, 0x2a),
+            ElfClass::Elf64 => (0x18, 0x20, 0x28, 0x36),
+        };
+
+        let file_type = r.u16(0x10)?;
+        let machine = r.u16(0x12)?;
+        let entry_point = r.word(entry_at)?;
+        let phoff = r.word(phoff_at)?;
+        let shoff = r.word(shoff_at)?;
+        let phentsize = r.u16(sizes_at)? as u64;
+        let phnum = (r.u16(sizes_at + 2)? as usize).min(MAX_TABLE_ENTRIES);
+        let shentsize = r.u16(sizes_at + 4)? as u64;
+        let shnum = (r.u16(sizes_at + 6)? as usize).min(MAX_TABLE_ENTRIES);
+        let shstrndx = r.u16(sizes_at + 8)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..phnum as u64 {
+            let base = r.entry_offset(phoff, i, phentsize)?;
+            let segment = match class {
+                ElfClass::Elf32 => ElfSegment {
+                    segment_type: r.u32(base)?,
+                    offset: r.u32(base + 4)? as u64,
+                    virtual_address: r.u32(base + 8)? as u64,
+                    file_size: r.u32(base + 16)? as u64,
+                    memory_size: r.u32(base + 20)? as u64,
+                    flags: r.u32(base + 24)?,
+                },
+                ElfClass::Elf64 => ElfSegment {
+                    segment_type: r.u32(base)?,
+                    flags: r.u32(base + 4)?,
+                    offset: r.u64(base + 8)?,
+                    virtual_address: r.u64(base + 16)?,
+                    file_size: r.u64(base + 32)?,
+                    memory_size: r.u64(base + 40)?,
+                },
+            };
+            segments.push(segment);
+        }
+
+        let interpreter = segments
+            .iter()
+            .find(|s| s.segment_type == PT_INTERP)
+            .and_then(|s| r.c_string(s.offset));
+
+        // 节头表缺失或损坏时仅保留程序头信息
+        let mut sections = Vec::new();
+        if shoff != 0 {
+            for i in 0..shnum as u64 {
+                let Ok(base) = r.entry_offset(shoff, i, shentsize) else {
+                    break;
+                };
+                let section = match class {
+                    ElfClass::Elf32 => (
+                        r.u32(base),
+                        r.u32(base + 4),
+                        r.u32(base + 8).map(u64::from),
+                        r.u32(base + 12).map(u64::from),
+                        r.u32(base + 16).map(u64::from),
+                        r.u32(base + 20).map(u64::from),
+                        r.u32(base + 24),
+                        r.u32(base + 36).map(u64::from),
+                    ),
+                    ElfClass::Elf64 => (
+                        r.u32(base),
+                        r.u32(base + 4),
+                        r.u64(base + 8),
+                        r.u64(base + 16),
+                        r.u64(base + 24),
+                        r.u64(base + 32),
+                        r.u32(base + 40),
+                        r.u64(base + 56),
+                    ),
+                };
+                let (Ok(name_offset), Ok(section_type), Ok(flags), Ok(address), Ok(offset), Ok(size), Ok(link), Ok(entry_size)) = section else {
+                    break;
+                };
+                sections.push((name_offset, ElfSection {
+                    name: String::new(),
+                    section_type,
+                    flags,
+                    address,
+                    offset,
+                    size,
+                    link,
+                    entry_size,
+                }));
+            }
+        }
+
+        let names_offset = sections.get(shstrndx).map(|(_, s)| s.offset);
+        let sections: Vec<ElfSection> = sections
+            .into_iter()
+            .map(|(name_offset, mut section)| {
+                if let Some(base) = names_offset {
+                    section.name = r.table_string(base, name_offset as u64).unwrap_or_default();
+                }
+                section
+            })
+            .collect();
+
+        let mut info = Self {
+            class,
+            little_endian,
+            file_type,
+            machine,
+            entry_point,
+            interpreter,
+            sections,
+            segments,
+            needed_libraries: Vec::new(),
+            imported_symbols: Vec::new(),
+        };
+        info.read_dynamic(&r);
+
+        Ok(info)
+    }
+
+    fn read_dynamic(&mut self, r: &Reader) {
+        for section in &self.sections {
+            let Some(strtab) = self.sections.get(section.link as usize) else {
+                continue;
+            };
+
+            match section.section_type {
+                SHT_DYNAMIC => {
+                    let entry_size = match self.class {
+                        ElfClass::Elf32 => 8,
+                        ElfClass::Elf64 => 16,
+                    };
+                    let width = entry_size / 2;
+                    let count = (section.size / entry_size).min(MAX_TABLE_ENTRIES as u64);
+                    for i in 0..count {
+                        let Ok(base) = r.entry_offset(section.offset, i, entry_size) else {
+                            break;
+                        };
+                        let (Ok(tag), Ok(value)) = (r.word(base), r.word(base + width)) else {
+                            break;
+                        };
+                        if tag == 0 {
+                            break;
+                        }
+                        if tag == DT_NEEDED {
+                            if let Some(name) = r.table_string(strtab.offset, value) {
+                                self.needed_libraries.push(name);
+                            }
+                        }
+                    }
+                }
+                SHT_DYNSYM => {
+                    let entry_size = match (section.entry_size, self.class) {
+                        (0, ElfClass::Elf32) => 16,
+                        (0, ElfClass::Elf64) => 24,
+                        (size, _) => size,
+                    };
+                    let shndx_at = match self.class {
+                        ElfClass::Elf32 => 14,
+                        ElfClass::Elf64 => 6,
+                    };
+                    let count = (section.size / entry_size).min(MAX_TABLE_ENTRIES as u64);
+                    // 第0项为保留的空符号
+                    for i in 1..count {
+                        let Ok(base) = r.entry_offset(section.offset, i, entry_size) else {
+                            break;
+                        };
+                        let (Ok(name), Ok(shndx)) = (r.u32(base), r.u16(base + shndx_at)) else {
+                            break;
+                        };
+                        // 节索引为0表示未定义，即从共享库导入的符号
+                        if shndx == 0 && name != 0 {
+                            if let Some(symbol) = r.table_string(strtab.offset, name as u64) {
+                                self.imported_symbols.push(symbol);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_stripped(&self) -> bool {
+        !self.sections.iter().any(|s| s.section_type == SHT_SYMTAB)
+    }
+
+    pub fn entry_offset(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .filter(|s| s.segment_type == PT_LOAD)
+            .find(|s| within(self.entry_point, s.virtual_address, s.file_size))
+            .and_then(|s| (self.entry_point - s.virtual_address).checked_add(s.offset))
+    }
+
+    pub fn heuristic_flags(&self, data: &[u8]) -> Vec<ElfFlag> {
+        let mut flags = Vec::new();
+
+        if self
+            .segments
+            .iter()
+            .any(|s| s.segment_type == PT_LOAD && s.is_writable() && s.is_executable())
+        {
+            flags.push(ElfFlag::WritableExecutableSegment);
+        }
+
+        let upx_sections = self.sections.iter().any(|s| s.name.starts_with("UPX"));
+        let upx_marker = data.windows(4).take(4096).any(|w| w == b"UPX!");
+        if upx_sections || upx_marker {
+            flags.push(ElfFlag::UpxPacked);
+        }
+
+        if self.sections.is_empty() {
+            flags.push(ElfFlag::SectionHeadersStripped);
+        } else if self.is_stripped() {
+            flags.push(ElfFlag::Stripped);
+        }
+
+        // 仅对可执行文件检查入口点，共享库的入口可以为0
+        if self.entry_point != 0 {
+            let in_code = self.segments.iter().any(|s| {
+                s.segment_type == PT_LOAD
+                    && s.is_executable()
+                    && within(self.entry_point, s.virtual_address, s.memory_size)
+            });
+            if !in_code {
+                flags.push(ElfFlag::EntryPointOutsideCode);
+            }
+        }
+
+        flags
+    }
+}
+
+// address 是否落在 [start, start + len) 内；段地址和大小来自文件内容，不计算 start + len 以免溢出
+fn within(address: u64, start: u64, len: u64) -> bool {
+    address >= start && address - start < len
+}
@@ -1,11 +1,36 @@
-use crate::scanner::SignatureDatabase;
+use crate::config::{ArchiveConfig, DetectionAction, HeuristicsConfig, MailConfig, PdfConfig, ScannerConfig};
+use crate::core::security::QuarantineManager;
+use crate::scanner::allowlist::Allowlist;
+use crate::scanner::archive::{ArchiveScanner, ArchiveViolation};
+use crate::scanner::checkpoint::{CheckpointProgress, ScanCheckpoint};
+use crate::scanner::mail::{parse_message, MailboxReader};
+use crate::scanner::memory::MemoryBudget;
+use crate::scanner::pdf::PdfParser;
+use crate::scanner::persistence::{audit_persistence, persistence_locations, PersistenceKind};
+use crate::scanner::verdict_cache::{settings_fingerprint, CachedDetection, VerdictCache, VerdictKey};
+use crate::scanner::exclusion::SCANIGNORE_FILE;
+use crate::scanner::{ExclusionRules, HeuristicEngine, HeuristicVerdict, SignatureDatabase, ThreatSignature};
+use crate::utils::{detect_file_type, detect_file_type_from_bytes, format_duration, EtaEstimator, GlobMatcher, is_pseudo_filesystem, safe_canonicalize, set_idle_io_priority, stat_file, FileKind, RateLimiter};
+use crate::utils::xattr::{has_valid_clean_marker, load_marker_key, write_clean_marker};
 use anyhow::{Context, Result};
+use dashmap::DashSet;
+use ignore::WalkState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+const QUEUE_DEPTH_PER_WORKER: usize = 64;
+const STATS_FLUSH_INTERVAL: usize = 64;
+const MIN_MEMORY_BUDGET: u64 = 1024 * 1024;
+// 启发式检测只分析文件开头的这部分内容
+const HEURISTIC_READ_LIMIT: u64 = 16 * 1024 * 1024;
+const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanOptions {
     pub scan_mode: ScanMode,
     pub custom_paths: Vec<PathBuf>,
@@ -13,26 +38,119 @@ pub struct ScanOptions {
     pub exclude_extensions: Vec<String>,
     pub max_file_size: u64,
     pub thread_count: usize,
+    // 目录遍历线程数，0 表示按CPU核心数自动选择
+    #[serde(default)]
+    pub walk_threads: usize,
     pub quick_scan_paths: Vec<PathBuf>,
+    // 先于其余路径扫描的高风险位置，支持通配符；只有位于扫描范围内的路径生效
+    #[serde(default)]
+    pub priority_paths: Vec<PathBuf>,
+    pub use_xattr_markers: bool,
+    // 扫描标记的签名密钥，未设置时不使用扫描标记
+    pub xattr_marker_key_file: Option<PathBuf>,
+    pub archive: ArchiveConfig,
+    pub heuristics: HeuristicsConfig,
+    #[serde(default)]
+    pub pdf: PdfConfig,
+    #[serde(default)]
+    pub mail: MailConfig,
+    pub skip_benign_types: bool,
+    // 遵循扫描目录中的 .scanignore 文件
+    #[serde(default)]
+    pub honor_scanignore: bool,
+    // 0 表示不限制
+    #[serde(default)]
+    pub memory_limit_mb: u64,
+    // 0 表示不限制
+    #[serde(default)]
+    pub max_read_mb_per_s: u64,
+    #[serde(default)]
+    pub idle_io_priority: bool,
+    // 超过该时长后停止扫描并返回部分结果
+    #[serde(default)]
+    pub max_duration: Option<Duration>,
+    #[serde(default)]
+    pub action: DetectionAction,
+    // 达到该风险等级的检测结果不论 action 如何都立即隔离
+    #[serde(default)]
+    pub auto_quarantine_min_risk: Option<RiskLevel>,
+    // 只记录将对检测到的文件执行的处理，不删除或隔离，也不写入扫描标记
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl ScanOptions {
+    // 按配置文件构建扫描选项，排除规则、线程数和动作等均取配置中的值
+    pub fn from_config(config: &ScannerConfig, scan_mode: ScanMode, custom_paths: Vec<PathBuf>) -> Self {
+        Self {
+            scan_mode,
+            custom_paths,
+            exclude_paths: config.scan_modes.exclude_paths.iter().map(PathBuf::from).collect(),
+            exclude_extensions: config.scan_modes.exclude_extensions.clone(),
+            max_file_size: config.scan_modes.max_file_size,
+            thread_count: config.performance.thread_pool_size,
+            walk_threads: config.performance.walk_threads,
+            quick_scan_paths: config.scan_modes.quick_scan_paths.iter().map(PathBuf::from).collect(),
+            priority_paths: config.scan_modes.priority_paths.iter().map(PathBuf::from).collect(),
+            use_xattr_markers: config.scan_modes.use_xattr_markers,
+            xattr_marker_key_file: Some(config.scan_modes.xattr_marker_key_file.clone()),
+            archive: config.scan_modes.archive.clone(),
+            heuristics: config.scan_modes.heuristics.clone(),
+            pdf: config.scan_modes.pdf.clone(),
+            mail: config.scan_modes.mail.clone(),
+            skip_benign_types: config.scan_modes.skip_benign_types,
+            honor_scanignore: config.scan_modes.honor_scanignore,
+            memory_limit_mb: config.performance.memory_limit_mb,
+            max_read_mb_per_s: config.performance.max_read_mb_per_s,
+            idle_io_priority: config.performance.idle_io_priority,
+            max_duration: (config.scan_modes.max_duration_secs > 0)
+                .then(|| Duration::from_secs(config.scan_modes.max_duration_secs)),
+            action: config.scan_modes.action,
+            auto_quarantine_min_risk: config.security.auto_quarantine.min_risk(),
+            dry_run: false,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ScanMode {
     Quick,
     Full,
     Custom,
+    // 只审计 cron、systemd、rc.local、shell 配置和 authorized_keys 等持久化位置
+    Persistence,
 }
 
-#[derive(Debug)]
+impl std::str::FromStr for ScanMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "quick" | "fast" => Ok(ScanMode::Quick),
+            "full" => Ok(ScanMode::Full),
+            "custom" => Ok(ScanMode::Custom),
+            "persistence" => Ok(ScanMode::Persistence),
+            _ => Err(anyhow::anyhow!("无效的扫描类型: {} (可选 quick, full, custom, persistence)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct ScanResult {
     pub file_path: PathBuf,
     pub threat_type: ThreatType,
     pub risk_level: RiskLevel,
     pub signature_id: String,
     pub file_info: FileInfo,
+    pub archive_member: Option<String>,
+    pub heuristic_score: Option<u8>,
+    #[serde(default)]
+    pub action_taken: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub enum ThreatType {
     Virus,
     Trojan,
@@ -63,7 +181,8 @@ impl From<&str> for ThreatType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -82,13 +201,15 @@ impl From<&str> for RiskLevel {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct FileInfo {
     pub size: u64,
     pub permissions: String,
     pub created: Option<u64>,
     pub modified: Option<u64>,
     pub accessed: Option<u64>,
+    pub file_kind: FileKind,
 }
 
 pub struct ScanStats {
@@ -96,7 +217,13 @@ pub struct ScanStats {
     pub files_scanned: AtomicUsize,
     pub threats_found: AtomicUsize,
     pub bytes_scanned: AtomicUsize,
+    // 包括目录遍历错误在内的全部错误
     pub errors: AtomicUsize,
+    pub walk_errors: AtomicUsize,
+    pub files_skipped: AtomicUsize,
+    // 启用扫描结论缓存时，使用缓存结论 (计入 files_skipped) 和需要重新扫描的文件数
+    pub cache_hits: AtomicUsize,
+    pub cache_misses: AtomicUsize,
 }
 
 impl ScanStats {
@@ -107,6 +234,10 @@ impl ScanStats {
             threats_found: AtomicUsize::new(0),
             bytes_scanned: AtomicUsize::new(0),
             errors: AtomicUsize::new(0),
+            walk_errors: AtomicUsize::new(0),
+            files_skipped: AtomicUsize::new(0),
+            cache_hits: AtomicUsize::new(0),
+            cache_misses: AtomicUsize::new(0),
         }
     }
 
@@ -122,6 +253,26 @@ impl ScanStats {
         self.bytes_scanned.load(Ordering::Relaxed)
     }
 
+    pub fn get_errors(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn get_walk_errors(&self) -> usize {
+        self.walk_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn get_files_skipped(&self) -> usize {
+        self.files_skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn get_cache_hits(&self) -> usize {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn get_cache_misses(&self) -> usize {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+
     pub fn get_speed_mb_per_s(&self) -> f64 {
         let elapsed = self.start_time.elapsed();
         if elapsed.as_secs() == 0 {
@@ -132,11 +283,138 @@ impl ScanStats {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub enum ScanState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanProgress {
+    pub percent: f64,
+    pub files_done: usize,
+    pub files_total: usize,
+    // 目录遍历尚未结束时总数只是目前发现的文件数
+    pub total_is_estimate: bool,
+    pub bytes_scanned: usize,
+    pub current_path: Option<PathBuf>,
+    pub eta: Option<Duration>,
+}
+
+// 遍历线程和工作线程共同维护的进度，由定时任务汇总后交给回调
+#[derive(Default)]
+struct ProgressTracker {
+    discovered: AtomicUsize,
+    walk_complete: AtomicBool,
+    processed: AtomicUsize,
+    current_path: Mutex<Option<PathBuf>>,
+}
+
+impl ProgressTracker {
+    fn snapshot(&self, completed_before: usize, stats: &ScanStats, eta: &mut EtaEstimator) -> ScanProgress {
+        let walk_complete = self.walk_complete.load(Ordering::Relaxed);
+        let files_done = completed_before + self.processed.load(Ordering::Relaxed);
+        let files_total = (completed_before + self.discovered.load(Ordering::Relaxed)).max(files_done);
+
+        eta.set_total(files_total as u64);
+        eta.update(files_done as u64);
+
+        ScanProgress {
+            percent: if files_total == 0 { 0.0 } else { eta.percent() },
+            files_done,
+            files_total,
+            total_is_estimate: !walk_complete,
+            bytes_scanned: stats.get_bytes_scanned(),
+            current_path: self.current_path.lock().unwrap().clone(),
+            eta: if walk_complete { eta.eta() } else { None },
+        }
+    }
+}
+
+// 可克隆的扫描控制句柄，其他任务可通过它暂停、恢复或取消正在进行的扫描。
+// 状态只在文件之间检查，正在扫描的文件总会完成
+#[derive(Clone)]
+pub struct ScanControl {
+    sender: Arc<tokio::sync::watch::Sender<ScanState>>,
+}
+
+impl ScanControl {
+    pub fn new() -> Self {
+        let (sender, _) = tokio::sync::watch::channel(ScanState::Running);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    // 返回 false 表示此前已经取消过
+    pub fn cancel(&self) -> bool {
+        self.sender.send_replace(ScanState::Cancelled) != ScanState::Cancelled
+    }
+
+    // 只有运行中的扫描可以暂停，已取消的扫描不能再恢复
+    pub fn pause(&self) -> bool {
+        self.transition(ScanState::Running, ScanState::Paused)
+    }
+
+    pub fn resume(&self) -> bool {
+        self.transition(ScanState::Paused, ScanState::Running)
+    }
+
+    pub fn state(&self) -> ScanState {
+        *self.sender.borrow()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state() == ScanState::Cancelled
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state() == ScanState::Paused
+    }
+
+    pub fn reset(&self) {
+        self.sender.send_replace(ScanState::Running);
+    }
+
+    fn transition(&self, from: ScanState, to: ScanState) -> bool {
+        self.sender.send_if_modified(|state| {
+            if *state == from {
+                *state = to;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    fn subscribe(&self) -> tokio::sync::watch::Receiver<ScanState> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ScanControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct ScannerEngine {
     signature_db: Arc<SignatureDatabase>,
     options: ScanOptions,
     stats: Arc<ScanStats>,
-    progress_callback: Option<Arc<dyn Fn(f64) + Send + Sync>>,
+    progress_callback: Option<Arc<dyn Fn(&ScanProgress) + Send + Sync>>,
+    progress_interval: Duration,
+    scan_control: ScanControl,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    completed_before: Arc<HashSet<PathBuf>>,
+    previous_results: Vec<ScanResult>,
+    deadline_reached: Arc<AtomicBool>,
+    quarantine: Option<Arc<QuarantineManager>>,
+    allowlist: Option<Arc<Allowlist>>,
+    verdict_cache: Option<Arc<VerdictCache>>,
 }
 
 impl ScannerEngine {
@@ -146,82 +424,403 @@ impl ScannerEngine {
             options,
             stats: Arc::new(ScanStats::new()),
             progress_callback: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            scan_control: ScanControl::new(),
+            checkpoint_path: None,
+            checkpoint_interval: Duration::from_secs(60),
+            completed_before: Arc::new(HashSet::new()),
+            previous_results: Vec::new(),
+            deadline_reached: Arc::new(AtomicBool::new(false)),
+            quarantine: None,
+            allowlist: None,
+            verdict_cache: None,
         }
     }
 
+    pub fn set_allowlist(&mut self, allowlist: Arc<Allowlist>) {
+        self.allowlist = Some(allowlist);
+    }
+
+    // 持久化审计的结论与路径有关，该模式下不使用缓存
+    pub fn set_verdict_cache(&mut self, cache: Arc<VerdictCache>) {
+        self.verdict_cache = Some(cache);
+    }
+
+    // quarantine/clean 处理方式需要隔离区，未设置时这些文件只会被报告
+    pub fn set_quarantine_manager(&mut self, quarantine: Arc<QuarantineManager>) {
+        self.quarantine = Some(quarantine);
+    }
+
+    // 从检查点恢复：沿用原扫描选项和统计，已完成的文件不再扫描
+    pub fn from_checkpoint(signature_db: Arc<SignatureDatabase>, checkpoint: ScanCheckpoint) -> Self {
+        if checkpoint.db_version != signature_db.get_version() {
+            log::warn!(
+                "检查点创建时的病毒库版本 {} 与当前版本 {} 不同，已完成部分不会重新扫描",
+                checkpoint.db_version,
+                signature_db.get_version()
+            );
+        }
+
+        let mut engine = Self::new(signature_db, checkpoint.options.clone());
+        checkpoint.restore_stats(&engine.stats);
+        engine.completed_before = Arc::new(checkpoint.completed);
+        engine.previous_results = checkpoint.results;
+        engine
+    }
+
+    // 扫描期间按间隔写入检查点；扫描正常结束后删除，被取消时保留以便恢复
+    pub fn enable_checkpoints(&mut self, path: PathBuf, interval: Duration) {
+        self.checkpoint_path = Some(path);
+        self.checkpoint_interval = interval.max(Duration::from_secs(1));
+    }
+
+    pub fn get_options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    // 由调用方提供句柄，便于在扫描开始前就把它交给 API 等外部组件
+    pub fn set_scan_control(&mut self, control: ScanControl) {
+        self.scan_control = control;
+    }
+
+    pub fn scan_control(&self) -> ScanControl {
+        self.scan_control.clone()
+    }
+
+    pub fn cancel(&self) -> bool {
+        self.scan_control.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.scan_control.is_cancelled()
+    }
+
+    pub fn pause(&self) -> bool {
+        let paused = self.scan_control.pause();
+        if paused {
+            log::info!(files_scanned = self.stats.get_files_scanned(); "扫描已暂停");
+        }
+        paused
+    }
+
+    pub fn resume(&self) -> bool {
+        let resumed = self.scan_control.resume();
+        if resumed {
+            log::info!(files_scanned = self.stats.get_files_scanned(); "扫描已恢复");
+        }
+        resumed
+    }
+
+    pub fn state(&self) -> ScanState {
+        self.scan_control.state()
+    }
+
+    // 扫描因达到 max_duration 而停止时为 true，区别于手动取消
+    pub fn deadline_reached(&self) -> bool {
+        self.deadline_reached.load(Ordering::Relaxed)
+    }
+
+    // 回调按 progress_interval 定期调用，扫描结束时再调用一次
     pub fn set_progress_callback<F>(&mut self, callback: F)
     where
-        F: Fn(f64) + Send + Sync + 'static,
+        F: Fn(&ScanProgress) + Send + Sync + 'static,
     {
         self.progress_callback = Some(Arc::new(callback));
     }
 
+    pub fn set_progress_interval(&mut self, interval: Duration) {
+        self.progress_interval = interval.max(Duration::from_millis(10));
+    }
+
+    // 密钥无法读取或生成时不使用扫描标记，所有文件照常扫描
+    fn marker_key(&self) -> Option<Vec<u8>> {
+        if !self.options.use_xattr_markers {
+            return None;
+        }
+        let Some(path) = &self.options.xattr_marker_key_file else {
+            log::warn!("未配置扫描标记密钥，不使用扫描标记");
+            return None;
+        };
+        load_marker_key(path)
+            .map_err(|e| log::warn!("无法加载扫描标记密钥，不使用扫描标记: {:#}", e))
+            .ok()
+    }
+
     pub async fn start_scan(&self) -> Result<Vec<ScanResult>, anyhow::Error> {
-        log::info!("开始扫描，模式: {:?}", self.options.scan_mode);
+        log::info!(scan_mode:? = self.options.scan_mode; "开始扫描，模式: {:?}", self.options.scan_mode);
 
-        let paths = self.get_scan_paths()?;
-        let stats = Arc::clone(&self.stats);
-        let signature_db = Arc::clone(&self.signature_db);
-        let options = self.options.clone();
-        let max_file_size = options.max_file_size;
+        let exclusions = ExclusionRules::from_options(&self.options)?;
+        let paths = self.get_scan_paths(&exclusions)?;
+        let worker_count = self.options.thread_count.max(1);
+        let db_version = self.signature_db.get_version();
+        let verdict_cache = self
+            .verdict_cache
+            .clone()
+            .filter(|_| self.options.scan_mode != ScanMode::Persistence);
+        if let Some(cache) = &verdict_cache {
+            match cache.prune(&db_version) {
+                Ok(0) => {}
+                Ok(removed) => log::info!("病毒库版本已变化，清除 {} 条旧的扫描结论", removed),
+                Err(e) => log::warn!("无法清理扫描结论缓存: {}", e),
+            }
+        }
+        let context = Arc::new(ScanContext {
+            signature_db: Arc::clone(&self.signature_db),
+            db_version,
+            options: self.options.clone(),
+            memory: self.memory_budget(worker_count),
+            read_limiter: (self.options.max_read_mb_per_s > 0)
+                .then(|| RateLimiter::per_second(self.options.max_read_mb_per_s * 1024 * 1024)),
+            quarantine: self.quarantine.clone(),
+            allowlist: self.allowlist.clone(),
+            verdict_cache,
+            cache_settings: settings_fingerprint(&self.options),
+            marker_key: self.marker_key(),
+        });
 
-        let mut results = Vec::new();
+        // 对整个进程生效，守护进程中扫描结束后也保持 idle 优先级
+        if self.options.idle_io_priority {
+            if let Err(e) = set_idle_io_priority() {
+                log::warn!("无法降低扫描I/O优先级: {}", e);
+            }
+        }
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<PathBuf>(worker_count * QUEUE_DEPTH_PER_WORKER);
+        let rx = Arc::new(tokio::sync::Mutex::new(rx));
+
+        if !self.completed_before.is_empty() {
+            log::info!("从检查点恢复扫描，跳过已完成的 {} 个文件", self.completed_before.len());
+        }
+        let progress = self.checkpoint_path.as_ref().map(|_| {
+            Arc::new(std::sync::Mutex::new(CheckpointProgress {
+                completed: (*self.completed_before).clone(),
+                results: self.previous_results.clone(),
+            }))
+        });
+
+        let tracker = Arc::new(ProgressTracker::default());
+        let walker = {
+            let walker = ScanWalker {
+                exclusions,
+                stats: Arc::clone(&self.stats),
+                cancel: self.scan_control.clone(),
+                completed: Arc::clone(&self.completed_before),
+                tracker: Arc::clone(&tracker),
+                threads: self.options.walk_threads,
+            };
+            let priority = self.options.priority_paths.clone();
+            tokio::task::spawn_blocking(move || {
+                let paths = ScanPaths {
+                    priority: expand_glob_paths(&priority),
+                    roots: paths,
+                };
+                walker.run(&paths, tx);
+                if !walker.cancel.is_cancelled() {
+                    walker.tracker.walk_complete.store(true, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let mut eta = EtaEstimator::new(0);
+        let reporter = self.progress_callback.clone().map(|callback| {
+            let tracker = Arc::clone(&tracker);
+            let stats = Arc::clone(&self.stats);
+            let completed_before = self.completed_before.len();
+            let interval = self.progress_interval;
+            tokio::spawn(async move {
+                let mut eta = EtaEstimator::new(0);
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    callback(&tracker.snapshot(completed_before, &stats, &mut eta));
+                }
+            })
+        });
+
+        let saver = match (&progress, &self.checkpoint_path) {
+            (Some(progress), Some(path)) => {
+                let progress = Arc::clone(progress);
+                let stats = Arc::clone(&self.stats);
+                let options = self.options.clone();
+                let db_version = context.db_version.clone();
+                let path = path.clone();
+                let interval = self.checkpoint_interval;
 
-        for root_path in &paths {
-            let iter = walkdir::WalkDir::new(root_path)
-                .follow_links(false)
-                .same_file_system(true)
-                .into_iter();
-
-            for entry in iter {
-                match entry {
-                    Ok(entry) => {
-                        let path = entry.path().to_path_buf();
-                        if !self.should_exclude(&path) && entry.file_type().is_file() {
-                            if let Ok(metadata) = std::fs::metadata(&path) {
-                                if metadata.len() <= max_file_size {
-                                    stats.files_scanned.fetch_add(1, Ordering::Relaxed);
-                                    stats.bytes_scanned.fetch_add(metadata.len() as usize, Ordering::Relaxed);
-
-                                    if let Some(threat) = signature_db.scan_file_sync(&path).await {
-                                        stats.threats_found.fetch_add(1, Ordering::Relaxed);
-                                        results.push(ScanResult {
-                                            file_path: path.clone(),
-                                            threat_type: threat.threat_type.as_str().into(),
-                                            risk_level: threat.risk_level.as_str().into(),
-                                            signature_id: threat.id,
-                                            file_info: FileInfo {
-                                                size: metadata.len(),
-                                                permissions: String::new(),
-                                                created: None,
-                                                modified: None,
-                                                accessed: None,
-                                            },
-                                        });
-                                    }
-                                }
-                            }
+                Some(tokio::spawn(async move {
+                    let mut ticker = tokio::time::interval(interval);
+                    ticker.tick().await;
+                    loop {
+                        ticker.tick().await;
+                        let checkpoint = {
+                            let progress = progress.lock().unwrap();
+                            ScanCheckpoint::capture(&options, &db_version, &stats, &progress)
+                        };
+                        if let Err(e) = checkpoint.save(&path) {
+                            log::warn!("写入扫描检查点失败: {}", e);
                         }
                     }
-                    Err(e) => {
-                        log::warn!("访问路径错误: {}", e);
-                        stats.errors.fetch_add(1, Ordering::Relaxed);
+                }))
+            }
+            _ => None,
+        };
+
+        let mut workers = Vec::with_capacity(worker_count);
+        for _ in 0..worker_count {
+            let context = Arc::clone(&context);
+            let stats = Arc::clone(&self.stats);
+            let rx = Arc::clone(&rx);
+            let progress = progress.clone();
+            let tracker = Arc::clone(&tracker);
+            let mut state = self.scan_control.subscribe();
+
+            workers.push(tokio::spawn(async move {
+                let mut local = WorkerStats::default();
+                let mut results = Vec::new();
+
+                loop {
+                    // 暂停期间停在文件之间等待，队列中尚未扫描的路径保持不动
+                    let cancelled = match state.wait_for(|s| *s != ScanState::Paused).await {
+                        Ok(current) => *current == ScanState::Cancelled,
+                        Err(_) => true,
+                    };
+                    if cancelled {
+                        break;
+                    }
+                    let next = tokio::select! {
+                        next = async { rx.lock().await.recv().await } => next,
+                        _ = state.wait_for(|s| *s != ScanState::Running) => continue,
+                    };
+                    let path = match next {
+                        Some(path) => path,
+                        None => break,
+                    };
+
+                    *tracker.current_path.lock().unwrap() = Some(path.clone());
+                    let found = context.scan_path(&path, &mut local).await;
+                    tracker.processed.fetch_add(1, Ordering::Relaxed);
+
+                    // 检查点中的统计必须与已完成文件一致，因此每个文件都立即合并
+                    if let Some(progress) = &progress {
+                        local.merge_into(&stats);
+                        let mut progress = progress.lock().unwrap();
+                        progress.completed.insert(path);
+                        progress.results.extend(found.iter().cloned());
+                    }
+                    results.extend(found);
+
+                    if local.pending >= STATS_FLUSH_INTERVAL {
+                        local.merge_into(&stats);
                     }
                 }
+
+                local.merge_into(&stats);
+                results
+            }));
+        }
+        // 到达期限时按取消处理，复用取消流程保留部分结果和检查点
+        let deadline = self.options.max_duration.map(|limit| {
+            let control = self.scan_control.clone();
+            let reached = Arc::clone(&self.deadline_reached);
+            tokio::spawn(async move {
+                tokio::time::sleep(limit).await;
+                log::warn!("扫描达到最长时间 {}，正在停止", format_duration(limit));
+                reached.store(true, Ordering::Relaxed);
+                control.cancel();
+            })
+        });
+
+        // 工作线程全部退出后接收端随之释放，遍历线程的 blocking_send 才会返回错误而结束
+        drop(rx);
+
+        let mut results = self.previous_results.clone();
+        for worker in workers {
+            results.extend(worker.await.context("扫描工作线程异常退出")?);
+        }
+        walker.await.context("目录遍历线程异常退出")?;
+        if let Some(saver) = saver {
+            saver.abort();
+        }
+        if let Some(deadline) = deadline {
+            deadline.abort();
+        }
+        if let Some(reporter) = reporter {
+            reporter.abort();
+        }
+        if let Some(callback) = &self.progress_callback {
+            *tracker.current_path.lock().unwrap() = None;
+            callback(&tracker.snapshot(self.completed_before.len(), &self.stats, &mut eta));
+        }
+
+        if self.is_cancelled() {
+            log::warn!(
+                files_scanned = self.stats.get_files_scanned();
+                "扫描已取消，返回部分结果: 已扫描 {} 个文件",
+                self.stats.get_files_scanned()
+            );
+        }
+
+        if let (Some(progress), Some(path)) = (&progress, &self.checkpoint_path) {
+            if self.is_cancelled() {
+                let checkpoint = ScanCheckpoint::capture(
+                    &self.options,
+                    &context.db_version,
+                    &self.stats,
+                    &progress.lock().unwrap(),
+                );
+                checkpoint.save(path)?;
+                log::info!("扫描检查点已保存: {:?}", path);
+            } else if let Err(e) = std::fs::remove_file(path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("无法删除扫描检查点 {:?}: {}", path, e);
+                }
             }
         }
 
+        results.sort_by(|a, b| {
+            (&a.file_path, &a.archive_member, &a.signature_id).cmp(&(&b.file_path, &b.archive_member, &b.signature_id))
+        });
+        results.dedup_by(|a, b| {
+            a.file_path == b.file_path && a.archive_member == b.archive_member && a.signature_id == b.signature_id
+        });
         Ok(results)
     }
 
-    fn get_scan_paths(&self) -> Result<Vec<PathBuf>, anyhow::Error> {
+    // 病毒库和编译缓存常驻内存，剩余部分作为文件缓冲区的预算；
+    // 连每个工作线程一个流式窗口都放不下时先回收缓存
+    fn memory_budget(&self, worker_count: usize) -> MemoryBudget {
+        if self.options.memory_limit_mb == 0 {
+            return MemoryBudget::unlimited();
+        }
+
+        let limit = self.options.memory_limit_mb * 1024 * 1024;
+        let window = self.signature_db.read_footprint(u64::MAX).max(MIN_MEMORY_BUDGET);
+        let resident = |db: &SignatureDatabase| db.get_memory_usage() + db.get_cache_memory_usage();
+
+        if resident(&self.signature_db) + window * worker_count as u64 > limit {
+            log::info!("内存接近限制，清理签名编译缓存");
+            self.signature_db.evict_caches();
+        }
+
+        let resident = resident(&self.signature_db);
+        if resident >= limit {
+            log::warn!(
+                "病毒库占用内存 {} 字节已超过限制 {} MB，扫描将逐个文件进行",
+                resident,
+                self.options.memory_limit_mb
+            );
+        }
+        MemoryBudget::new(limit.saturating_sub(resident).max(window))
+    }
+
+    fn get_scan_paths(&self, exclusions: &ExclusionRules) -> Result<Vec<PathBuf>, anyhow::Error> {
         match self.options.scan_mode {
             ScanMode::Quick => Ok(self.options.quick_scan_paths.clone()),
             ScanMode::Full => {
                 let mut paths = Vec::new();
                 for entry in std::fs::read_dir("/")? {
                     let path = entry?.path();
-                    if self.should_exclude(&path) {
+                    if is_excluded(exclusions, &path) {
                         continue;
                     }
                     paths.push(path);
@@ -229,47 +828,806 @@ impl ScannerEngine {
                 Ok(paths)
             }
             ScanMode::Custom => Ok(self.options.custom_paths.clone()),
+            ScanMode::Persistence if self.options.custom_paths.is_empty() => Ok(persistence_locations()),
+            ScanMode::Persistence => Ok(self.options.custom_paths.clone()),
+        }
+    }
+
+    pub fn get_stats(&self) -> &Arc<ScanStats> {
+        &self.stats
+    }
+}
+
+fn is_excluded(exclusions: &ExclusionRules, path: &Path) -> bool {
+    if exclusions.excludes_path(path) {
+        return true;
+    }
+
+    // 扩展名可以伪造，文件头显示为可执行文件或压缩包时仍然扫描
+    exclusions.excludes_extension(path)
+        && !detect_file_type(path)
+            .map(|kind| kind.is_executable() || kind.is_archive())
+            .unwrap_or(false)
+}
+
+// 优先路径先于其余扫描路径遍历，之后遍历其余路径时跳过这些目录以免重复扫描
+pub(crate) struct ScanPaths {
+    pub(crate) priority: Vec<PathBuf>,
+    pub(crate) roots: Vec<PathBuf>,
+}
+
+impl ScanPaths {
+    // 规范化后合并重叠的扫描路径，只保留位于扫描路径内、未被排除且互不重叠的优先路径
+    pub(crate) fn resolve(&self, exclusions: &ExclusionRules, stats: &ScanStats) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let canonical: Vec<PathBuf> = self
+            .roots
+            .iter()
+            .filter_map(|path| match safe_canonicalize(path, &[]) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    log::warn!("跳过扫描路径: {}", e);
+                    stats.errors.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+            })
+            .collect();
+        let roots = outermost_paths(&canonical);
+
+        let candidates: Vec<PathBuf> = self
+            .priority
+            .iter()
+            .filter_map(|path| safe_canonicalize(path, &[]).ok())
+            .filter(|path| roots.iter().any(|root| path.starts_with(root)))
+            .filter(|path| !path.ancestors().any(|a| exclusions.excludes_path(a)))
+            .collect();
+        let priority = outermost_paths(&candidates);
+
+        if !priority.is_empty() {
+            log::info!("优先扫描: {:?}", priority);
         }
+        (priority, roots)
     }
+}
 
-    fn should_exclude(&self, path: &PathBuf) -> bool {
-        self.options.exclude_paths.iter().any(|p| path.starts_with(p))
-            || path.extension().and_then(|e| e.to_str()).map(|e| {
-                self.options.exclude_extensions.contains(&e.to_string())
-            }).unwrap_or(false)
+// 去掉重复的路径和位于其他路径之下的路径，保持原有顺序
+fn outermost_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut outermost: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let nested = paths.iter().any(|other| other != path && path.starts_with(other));
+        if !nested && !outermost.contains(path) {
+            outermost.push(path.clone());
+        }
     }
+    outermost
+}
 
-    fn get_permissions(path: &PathBuf) -> String {
-        if let Ok(metadata) = std::fs::metadata(path) {
-            let mut perms = String::new();
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mode = metadata.permissions().mode();
-                perms.push(if mode & 0o100 != 0 { 'x' } else { '-' });
-                perms.push(if mode & 0o200 != 0 { 'w' } else { '-' });
-                perms.push(if mode & 0o400 != 0 { 'r' } else { '-' });
+// 展开路径中的通配符，如 /home/*/Downloads，只返回实际存在的路径
+pub(crate) fn expand_glob_paths(patterns: &[PathBuf]) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
+    for pattern in patterns {
+        let mut matches = vec![PathBuf::new()];
+        for component in pattern.components() {
+            let component = component.as_os_str();
+            let text = component.to_string_lossy();
+            if !GlobMatcher::is_glob(&text) {
+                matches.iter_mut().for_each(|m| m.push(component));
+                continue;
             }
-            perms
+            let Ok(glob) = globset::Glob::new(&text).map(|g| g.compile_matcher()) else {
+                log::warn!("无效的优先扫描路径: {:?}", pattern);
+                matches.clear();
+                break;
+            };
+            matches = matches
+                .iter()
+                .filter_map(|dir| std::fs::read_dir(dir).ok())
+                .flat_map(|entries| entries.flatten().map(|e| e.path()))
+                .filter(|path| path.file_name().is_some_and(|name| glob.is_match(name)))
+                .collect();
+            matches.sort();
+        }
+        expanded.extend(matches.into_iter().filter(|path| path.exists()));
+    }
+    expanded
+}
+
+// 目录遍历在阻塞线程中进行，多个线程以工作窃取方式并行遍历，把待扫描文件送入有界队列，
+// 队列满时自然形成背压
+struct ScanWalker {
+    exclusions: ExclusionRules,
+    stats: Arc<ScanStats>,
+    cancel: ScanControl,
+    completed: Arc<HashSet<PathBuf>>,
+    tracker: Arc<ProgressTracker>,
+    threads: usize,
+}
+
+impl ScanWalker {
+    fn run(&self, paths: &ScanPaths, tx: tokio::sync::mpsc::Sender<PathBuf>) {
+        let (priority, roots) = paths.resolve(&self.exclusions, &self.stats);
+        // 同一文件可能经由重叠的扫描路径或绑定挂载多次出现，按 (设备, inode) 只扫描一次
+        let seen: DashSet<(u64, u64)> = DashSet::new();
+        // 优先路径全部遍历完后才开始遍历其余路径
+        self.walk(&priority, &[], &seen, &tx);
+        if !self.cancel.is_cancelled() {
+            self.walk(&roots, &priority, &seen, &tx);
+        }
+    }
+
+    fn walk(&self, roots: &[PathBuf], skip: &[PathBuf], seen: &DashSet<(u64, u64)>, tx: &tokio::sync::mpsc::Sender<PathBuf>) {
+        let Some((first, rest)) = roots.split_first() else {
+            return;
+        };
+        let mut builder = ignore::WalkBuilder::new(first);
+        for root in rest {
+            builder.add(root);
+        }
+        builder
+            .standard_filters(false)
+            .follow_links(false)
+            .same_file_system(true)
+            .threads(self.threads);
+        if self.exclusions.honor_scanignore() {
+            builder.add_custom_ignore_filename(SCANIGNORE_FILE);
+        }
+
+        builder.build_parallel().run(|| {
+            let mut local = WalkerStats::new(&self.stats, &self.tracker);
+            Box::new(move |entry| {
+                if self.cancel.is_cancelled() {
+                    return WalkState::Quit;
+                }
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        log::warn!("访问路径错误: {}", e);
+                        local.errors += 1;
+                        return WalkState::Continue;
+                    }
+                };
+
+                // 目录中的 .scanignore 有无法解析的规则时，其余规则仍然生效
+                if let Some(e) = entry.error() {
+                    log::warn!("扫描忽略规则错误: {}", e);
+                    local.errors += 1;
+                }
+
+                let path = entry.path();
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                // 命中排除规则的目录整棵跳过，如 **/node_modules
+                if skip.iter().any(|p| p == path) || is_pseudo_filesystem(path) || (is_dir && self.exclusions.excludes_path(path)) {
+                    return WalkState::Skip;
+                }
+
+                if entry.file_type().is_some_and(|t| t.is_file())
+                    && !self.completed.contains(path)
+                    && !is_excluded(&self.exclusions, path)
+                    && first_visit(seen, &entry)
+                {
+                    local.add_discovered();
+                    if tx.blocking_send(entry.into_path()).is_err() {
+                        return WalkState::Quit;
+                    }
+                }
+                WalkState::Continue
+            })
+        });
+    }
+}
+
+// 每个遍历线程单独计数并定期合并，线程结束时合并剩余部分，避免争用共享计数器
+struct WalkerStats<'a> {
+    stats: &'a ScanStats,
+    tracker: &'a ProgressTracker,
+    discovered: usize,
+    errors: usize,
+}
+
+impl<'a> WalkerStats<'a> {
+    fn new(stats: &'a ScanStats, tracker: &'a ProgressTracker) -> Self {
+        Self {
+            stats,
+            tracker,
+            discovered: 0,
+            errors: 0,
+        }
+    }
+
+    fn add_discovered(&mut self) {
+        self.discovered += 1;
+        if self.discovered >= STATS_FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.tracker.discovered.fetch_add(self.discovered, Ordering::Relaxed);
+        self.stats.errors.fetch_add(self.errors, Ordering::Relaxed);
+        self.stats.walk_errors.fetch_add(self.errors, Ordering::Relaxed);
+        self.discovered = 0;
+        self.errors = 0;
+    }
+}
+
+impl Drop for WalkerStats<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// 多个硬链接是不同的目录项，隔离时需要分别处理，因此只对链接数为 1 的文件去重
+fn first_visit(seen: &DashSet<(u64, u64)>, entry: &ignore::DirEntry) -> bool {
+    let Ok(metadata) = entry.metadata() else {
+        return true;
+    };
+    if metadata.nlink() > 1 || seen.insert((metadata.dev(), metadata.ino())) {
+        return true;
+    }
+    log::debug!("跳过重复出现的文件: {:?}", entry.path());
+    false
+}
+
+struct ScanContext {
+    signature_db: Arc<SignatureDatabase>,
+    db_version: String,
+    options: ScanOptions,
+    memory: MemoryBudget,
+    read_limiter: Option<RateLimiter>,
+    quarantine: Option<Arc<QuarantineManager>>,
+    allowlist: Option<Arc<Allowlist>>,
+    verdict_cache: Option<Arc<VerdictCache>>,
+    cache_settings: String,
+    // 未启用扫描标记或密钥不可用时为 None
+    marker_key: Option<Vec<u8>>,
+}
+
+impl ScanContext {
+    // 按实际读取的字节数扣减令牌，所有工作线程共享同一个速率上限
+    async fn throttle_read(&self, bytes: u64) {
+        if let Some(limiter) = &self.read_limiter {
+            limiter.acquire(bytes).await;
+        }
+    }
+
+    async fn scan_path(&self, path: &Path, local: &mut WorkerStats) -> Vec<ScanResult> {
+        let metadata = match stat_file(path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::warn!("无法读取文件信息 {:?}: {}", path, e);
+                local.errors += 1;
+                local.pending += 1;
+                return Vec::new();
+            }
+        };
+        if metadata.size > self.options.max_file_size {
+            return Vec::new();
+        }
+
+        let marked_clean = self
+            .marker_key
+            .as_deref()
+            .map_or(false, |key| has_valid_clean_marker(path, &self.db_version, key));
+        if marked_clean {
+            local.files_skipped += 1;
+            local.pending += 1;
+            return Vec::new();
+        }
+
+        let file_kind = detect_file_type(path).unwrap_or(FileKind::Unknown);
+
+        let file_info = FileInfo {
+            size: metadata.size,
+            permissions: metadata.permissions_string(),
+            created: metadata.created,
+            modified: metadata.modified,
+            accessed: metadata.accessed,
+            file_kind,
+        };
+
+        let (cache_key, cached) = self.lookup_verdict(path);
+        let mut results = match cached {
+            Some(detections) => {
+                local.files_skipped += 1;
+                local.cache_hits += 1;
+                local.pending += 1;
+                detections.into_iter().map(|d| d.into_result(path, &file_info)).collect()
+            }
+            None => {
+                if self.verdict_cache.is_some() {
+                    local.cache_misses += 1;
+                }
+                let (footprint, expand_limit) = self.memory_footprint(metadata.size, file_kind);
+                if expand_limit.is_none() {
+                    log::warn!("文件内容超过内存预算，只做特征码和启发式检测，不展开压缩包、PDF 和邮件: {:?}", path);
+                }
+                let _reservation = self.memory.reserve(footprint).await;
+
+                local.pending += 1;
+                // 读取失败的文件计入错误，不写入缓存和扫描标记
+                let results = match self.detect(path, &file_info, expand_limit).await {
+                    Ok(results) => results,
+                    Err(e) => {
+                        log::warn!("无法扫描文件 {:?}: {:#}", path, e);
+                        local.errors += 1;
+                        return Vec::new();
+                    }
+                };
+                local.files_scanned += 1;
+                local.bytes_scanned += metadata.size as usize;
+                if let (Some(cache), Some(key)) = (&self.verdict_cache, &cache_key) {
+                    if let Err(e) = cache.store(path, key, &self.db_version, &self.verdict_settings(path), &results) {
+                        log::debug!("无法写入扫描结论缓存 {:?}: {}", path, e);
+                    }
+                }
+                results
+            }
+        };
+
+        if !results.is_empty() {
+            if let Some(reason) = self.allowlist.as_ref().and_then(|allowlist| allowlist.check(path)) {
+                log::info!(path:% = path.display(); "文件在白名单中 ({})，忽略 {} 个检测结果", reason, results.len());
+                results.clear();
+            }
+        }
+
+        local.threats_found += results.len();
+
+        let action = self.effective_action(&results);
+        if !results.is_empty() && action != DetectionAction::Report {
+            let action_taken = self.apply_action(path, action).await;
+            for result in &mut results {
+                result.action_taken = Some(action_taken.clone());
+            }
+        }
+
+        if let Some(key) = self.marker_key.as_deref() {
+            if results.is_empty() && !self.options.dry_run {
+                if let Err(e) = write_clean_marker(path, &self.db_version, key, &metadata) {
+                    log::debug!("无法写入扫描标记 {:?}: {}", path, e);
+                }
+            }
+        }
+
+        results
+    }
+
+    // 启发式检测与文件名和所在目录有关，启用时路径相关的输入也作为缓存键的一部分
+    fn verdict_settings(&self, path: &Path) -> String {
+        if self.options.heuristics.enabled {
+            format!("{}|{}", self.cache_settings, HeuristicEngine::path_inputs(path))
+        } else {
+            self.cache_settings.clone()
+        }
+    }
+
+    fn lookup_verdict(&self, path: &Path) -> (Option<VerdictKey>, Option<Vec<CachedDetection>>) {
+        let Some(cache) = &self.verdict_cache else {
+            return (None, None);
+        };
+        match cache.lookup(path, &self.db_version, &self.verdict_settings(path)) {
+            Ok((key, cached)) => {
+                if cached.is_some() {
+                    log::debug!("使用缓存的扫描结论: {:?}", path);
+                }
+                (Some(key), cached)
+            }
+            Err(e) => {
+                log::debug!("无法查询扫描结论缓存 {:?}: {}", path, e);
+                (None, None)
+            }
+        }
+    }
+
+    // 检测结果在白名单和处理方式生效之前写入缓存
+    // expand_limit 为 None 时内存预算放不下压缩包、PDF 和邮件的解析，只做特征码和启发式检测
+    async fn detect(&self, path: &Path, file_info: &FileInfo, expand_limit: Option<u64>) -> Result<Vec<ScanResult>> {
+        let mut results = Vec::new();
+        let file_kind = file_info.file_kind;
+
+        self.throttle_read(file_info.size).await;
+        if let Some(threat) = self.signature_db.scan_file_sync(path, file_kind).await? {
+            log::warn!(
+                path:% = path.display(),
+                signature = threat.id.as_str(),
+                threat_type = threat.threat_type.as_str(),
+                risk_level = threat.risk_level.as_str();
+                "发现威胁: {:?}", path
+            );
+            results.push(ScanResult {
+                file_path: path.to_path_buf(),
+                threat_type: threat.threat_type.as_str().into(),
+                risk_level: threat.risk_level.as_str().into(),
+                signature_id: threat.id,
+                file_info: file_info.clone(),
+                archive_member: None,
+                heuristic_score: None,
+                action_taken: None,
+            });
+        }
+
+        if let Some(expand_limit) = expand_limit {
+            if self.options.archive.enabled && file_kind.is_archive() {
+                results.extend(self.scan_archive(path, file_info, expand_limit).await);
+            }
+
+            if self.options.pdf.enabled && file_kind == FileKind::Pdf {
+                results.extend(self.scan_pdf(path, file_info, expand_limit).await);
+            }
+
+            if self.options.mail.enabled && file_kind == FileKind::Mail {
+                results.extend(self.scan_mail(path, file_info, expand_limit).await);
+            }
+        }
+
+        if self.options.scan_mode == ScanMode::Persistence {
+            if let Some(kind) = PersistenceKind::classify(path) {
+                results.extend(self.scan_persistence(path, kind, file_info).await);
+            }
+        }
+
+        // 只看文件头无法排除伪装成图片的脚本 (如 GIF89a<?php)，图片/音视频仍做特征码和哈希匹配，只跳过启发式检测
+        let skip_heuristics = self.options.skip_benign_types && file_kind.is_benign();
+        if results.is_empty() && self.options.heuristics.enabled && !skip_heuristics {
+            results.extend(self.scan_heuristics(path, file_kind, file_info).await);
+        }
+
+        Ok(results)
+    }
+
+    // 仅报告时，高风险检测结果仍按自动隔离策略处理；删除比隔离更严格，保持不变
+    fn effective_action(&self, results: &[ScanResult]) -> DetectionAction {
+        let auto_quarantine = self
+            .options
+            .auto_quarantine_min_risk
+            .map_or(false, |min| results.iter().any(|r| r.risk_level >= min));
+
+        if auto_quarantine && self.options.action == DetectionAction::Report {
+            DetectionAction::Quarantine
+        } else {
+            self.options.action
+        }
+    }
+
+    // 压缩包成员命中时处理的是整个压缩包文件
+    async fn apply_action(&self, path: &Path, action: DetectionAction) -> String {
+        if self.options.dry_run && action != DetectionAction::Report {
+            let action_taken = if action == DetectionAction::Delete { "would_delete" } else { "would_quarantine" };
+            log::warn!(path:% = path.display(); "模拟运行，未处理威胁文件 ({}): {:?}", action_taken, path);
+            return action_taken.to_string();
+        }
+        let outcome = match action {
+            DetectionAction::Report => return action.as_str().to_string(),
+            DetectionAction::Delete => std::fs::remove_file(path)
+                .map(|_| {
+                    log::warn!(path:% = path.display(); "已删除威胁文件: {:?}", path);
+                    "deleted"
+                })
+                .map_err(anyhow::Error::from),
+            DetectionAction::Quarantine | DetectionAction::Clean => match &self.quarantine {
+                Some(quarantine) => quarantine.quarantine_file(&path.to_path_buf()).await.map(|dest| {
+                    log::warn!(path:% = path.display(); "已隔离威胁文件: {:?} -> {:?}", path, dest);
+                    "quarantined"
+                }),
+                None => Err(anyhow::anyhow!("未配置隔离区")),
+            },
+        };
+
+        match outcome {
+            Ok(action_taken) => action_taken.to_string(),
+            Err(e) => {
+                log::error!(path:% = path.display(); "处理威胁文件失败 {:?}: {}", path, e);
+                format!("{}_failed", action.as_str())
+            }
+        }
+    }
+
+    // 返回 (预留内存, 解压上限)。流式特征码匹配和启发式检测读取的内容有上限，预留内存不会超过预算；
+    // 压缩包和 PDF 流的解压上限按预算收紧。压缩包、PDF 或邮件本身放不进预算时解压上限为 None，
+    // 只跳过这些解析，文件仍做特征码和启发式检测
+    fn memory_footprint(&self, size: u64, file_kind: FileKind) -> (u64, Option<u64>) {
+        let capacity = self.memory.capacity();
+        let mut footprint = self.signature_db.read_footprint(size);
+        if self.options.heuristics.enabled {
+            footprint = footprint.max(self.heuristic_read_limit(size));
+        }
+        let footprint = footprint.min(capacity);
+
+        // 邮件逐封读取，同时持有原文和解码后的附件
+        let mut deep = 0;
+        if self.options.mail.enabled && file_kind == FileKind::Mail {
+            deep = size.min(self.options.mail.max_message_size) * 2;
+        }
+
+        let expand_limit = if self.options.archive.enabled && (file_kind.is_archive() || file_kind == FileKind::Mail) {
+            self.options.archive.max_decompressed_size
+        } else if self.options.pdf.enabled && file_kind == FileKind::Pdf {
+            self.options.pdf.max_decompressed_size
         } else {
-            String::from("???")
+            0
+        };
+        let parsed = (self.options.archive.enabled && file_kind.is_archive())
+            || (self.options.pdf.enabled && file_kind == FileKind::Pdf)
+            || (self.options.mail.enabled && file_kind == FileKind::Mail);
+        if parsed && (size > capacity || deep > capacity) {
+            return (footprint, None);
+        }
+        let expand_limit = expand_limit.min(capacity.saturating_sub(size));
+        if parsed {
+            deep = deep.max(size + expand_limit);
         }
+        (footprint.max(deep), Some(expand_limit))
+    }
+
+    fn heuristic_read_limit(&self, size: u64) -> u64 {
+        size.min(HEURISTIC_READ_LIMIT).min(self.memory.capacity())
     }
 
-    fn get_created_time(path: &PathBuf) -> Option<u64> {
-        std::fs::metadata(path).ok()?.created().ok()?.elapsed().ok().map(|d| d.as_secs())
+    // 只读取文件开头 heuristic_read_limit 字节，大文件的内存占用有上限
+    async fn scan_heuristics(&self, path: &Path, file_kind: FileKind, file_info: &FileInfo) -> Option<ScanResult> {
+        use tokio::io::AsyncReadExt;
+
+        let limit = self.heuristic_read_limit(file_info.size);
+        self.throttle_read(limit).await;
+        let file = tokio::fs::File::open(path).await.ok()?;
+        let mut data = Vec::with_capacity(limit as usize);
+        file.take(limit).read_to_end(&mut data).await.ok()?;
+        let engine = HeuristicEngine::new(&self.options.heuristics);
+        let verdict = engine
+            .analyze_script(path, file_kind, &data)
+            .or_else(|| engine.analyze(path, file_kind, &data))?;
+        Some(self.heuristic_result(path, file_info, verdict))
     }
 
-    fn get_modified_time(path: &PathBuf) -> Option<u64> {
-        std::fs::metadata(path).ok()?.modified().ok()?.elapsed().ok().map(|d| d.as_secs())
+    fn heuristic_result(&self, path: &Path, file_info: &FileInfo, verdict: HeuristicVerdict) -> ScanResult {
+        let rules: Vec<&str> = verdict.matches.iter().map(|m| m.rule).collect();
+
+        log::warn!(
+            path:% = path.display(),
+            heuristic_score = verdict.score,
+            rules = rules.join(",").as_str();
+            "启发式检测发现可疑文件: {:?} (得分 {})", path, verdict.score
+        );
+
+        ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: ThreatType::Unknown,
+            risk_level: if verdict.score >= 80 { RiskLevel::High } else { RiskLevel::Medium },
+            signature_id: verdict.detection_name(),
+            file_info: file_info.clone(),
+            archive_member: None,
+            heuristic_score: Some(verdict.score),
+            action_taken: None,
+        }
     }
 
-    fn get_accessed_time(path: &PathBuf) -> Option<u64> {
-        std::fs::metadata(path).ok()?.accessed().ok()?.elapsed().ok().map(|d| d.as_secs())
+    async fn scan_persistence(&self, path: &Path, kind: PersistenceKind, file_info: &FileInfo) -> Option<ScanResult> {
+        self.throttle_read(file_info.size).await;
+        let data = tokio::fs::read(path).await.ok()?;
+        let finding = audit_persistence(kind, &data)?;
+
+        for (line, m) in &finding.matches {
+            log::warn!(
+                path:% = path.display(),
+                rule = m.rule;
+                "持久化位置存在可疑条目: {:?} 第 {} 行: {}", path, line, m.description
+            );
+        }
+
+        Some(ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: finding.threat_type(),
+            risk_level: finding.risk_level(),
+            signature_id: finding.detection_name(),
+            file_info: file_info.clone(),
+            archive_member: None,
+            heuristic_score: Some(finding.score),
+            action_taken: None,
+        })
     }
 
-    pub fn get_stats(&self) -> &Arc<ScanStats> {
-        &self.stats
+    // 提取出的脚本和嵌入文件按压缩包成员的方式报告；签名均未命中时再对 PDF 结构评分
+    async fn scan_pdf(&self, path: &Path, file_info: &FileInfo, expand_limit: u64) -> Vec<ScanResult> {
+        self.throttle_read(file_info.size).await;
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) => {
+                log::debug!("无法读取 PDF 文件 {:?}: {}", path, e);
+                return Vec::new();
+            }
+        };
+
+        let contents = PdfParser::new(expand_limit).parse(&data);
+        if contents.truncated {
+            log::warn!("PDF 流超过解压大小限制，仅分析了部分内容: {:?}", path);
+        }
+
+        let parts = contents
+            .scripts
+            .iter()
+            .map(|script| (format!("javascript:obj{}", script.object), &script.code))
+            .chain(contents.embedded_files.iter().map(|file| (format!("embedded:{}", file.name), &file.data)));
+
+        let mut results = Vec::new();
+        for (member, data) in parts {
+            if let Some(threat) = self.signature_db.scan_bytes(data).await {
+                results.push(self.member_result(path, file_info, member, threat));
+            }
+        }
+
+        if results.is_empty() && self.options.heuristics.enabled {
+            if let Some(verdict) = HeuristicEngine::new(&self.options.heuristics).analyze_pdf(&contents) {
+                results.push(self.heuristic_result(path, file_info, verdict));
+            }
+        }
+        results
+    }
+
+    async fn scan_archive(&self, path: &Path, file_info: &FileInfo, archive_limit: u64) -> Vec<ScanResult> {
+        if archive_limit == 0 {
+            log::debug!("内存预算不足，跳过压缩包内容: {:?}", path);
+            return Vec::new();
+        }
+        self.throttle_read(file_info.size).await;
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) => {
+                log::debug!("无法读取压缩包 {:?}: {}", path, e);
+                return Vec::new();
+            }
+        };
+
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let config = ArchiveConfig {
+            max_decompressed_size: archive_limit,
+            ..self.options.archive.clone()
+        };
+        let mut scanner = ArchiveScanner::new(&self.signature_db, &config);
+        let detections = scanner.scan(&file_name, &data).await;
+        if scanner.is_truncated() {
+            log::warn!("压缩包超过解压限制，仅扫描了部分内容: {:?}", path);
+        }
+
+        let mut results: Vec<ScanResult> = detections
+            .into_iter()
+            .map(|detection| self.member_result(path, file_info, detection.member, detection.threat))
+            .collect();
+        if let Some(violation) = self.archive_violation(&scanner, archive_limit) {
+            results.push(self.archive_bomb_result(path, file_info, None, violation));
+        }
+        results
+    }
+
+    // 内存预算收紧解压上限导致的截断不算压缩炸弹
+    fn archive_violation(&self, scanner: &ArchiveScanner, archive_limit: u64) -> Option<ArchiveViolation> {
+        scanner.violation().filter(|&violation| {
+            violation != ArchiveViolation::DecompressedSize || archive_limit >= self.options.archive.max_decompressed_size
+        })
+    }
+
+    fn archive_bomb_result(
+        &self,
+        path: &Path,
+        file_info: &FileInfo,
+        member: Option<String>,
+        violation: ArchiveViolation,
+    ) -> ScanResult {
+        log::warn!(
+            path:% = path.display(),
+            violation = violation.as_str();
+            "疑似压缩炸弹: {:?} ({})", path, violation.description()
+        );
+        ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: ThreatType::Unknown,
+            risk_level: RiskLevel::Medium,
+            signature_id: format!("Heuristic.ArchiveBomb.{}", violation.as_str()),
+            file_info: file_info.clone(),
+            archive_member: member,
+            heuristic_score: None,
+            action_taken: None,
+        }
+    }
+
+    // mbox 中的成员名带上邮件序号，附件中的压缩包继续展开
+    async fn scan_mail(&self, path: &Path, file_info: &FileInfo, archive_limit: u64) -> Vec<ScanResult> {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                log::debug!("无法读取邮件文件 {:?}: {}", path, e);
+                return Vec::new();
+            }
+        };
+        let max_message_size = usize::try_from(self.options.mail.max_message_size).unwrap_or(usize::MAX);
+        let mut reader = MailboxReader::new(tokio::io::BufReader::new(file), max_message_size);
+        let config = ArchiveConfig {
+            max_decompressed_size: archive_limit,
+            ..self.options.archive.clone()
+        };
+
+        let mut results = Vec::new();
+        let mut message_index = 0;
+        loop {
+            let message = match reader.next_message().await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    log::debug!("读取邮件文件出错 {:?}: {}", path, e);
+                    break;
+                }
+            };
+            message_index += 1;
+            self.throttle_read(message.len() as u64).await;
+
+            for (part_index, part) in parse_message(&message).into_iter().enumerate() {
+                if part.data.is_empty() {
+                    continue;
+                }
+                let name = part.filename.unwrap_or_else(|| format!("part{}", part_index + 1));
+                let member = if reader.is_mbox() { format!("message{}/{}", message_index, name) } else { name };
+
+                if let Some(threat) = self.signature_db.scan_bytes(&part.data).await {
+                    results.push(self.member_result(path, file_info, member, threat));
+                } else if archive_limit > 0 && detect_file_type_from_bytes(&part.data).is_archive() {
+                    let mut scanner = ArchiveScanner::new(&self.signature_db, &config);
+                    for detection in scanner.scan(&member, &part.data).await {
+                        let nested = format!("{}/{}", member, detection.member);
+                        results.push(self.member_result(path, file_info, nested, detection.threat));
+                    }
+                    if let Some(violation) = self.archive_violation(&scanner, archive_limit) {
+                        results.push(self.archive_bomb_result(path, file_info, Some(member), violation));
+                    }
+                }
+            }
+        }
+        results
+    }
+
+    // 压缩包成员、PDF 内嵌内容和邮件附件命中签名时的检测结果
+    fn member_result(&self, path: &Path, file_info: &FileInfo, member: String, threat: ThreatSignature) -> ScanResult {
+        log::warn!(
+            path:% = path.display(),
+            member = member.as_str(),
+            signature = threat.id.as_str(),
+            threat_type = threat.threat_type.as_str(),
+            risk_level = threat.risk_level.as_str();
+            "发现威胁: {:?} -> {}", path, member
+        );
+        ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: threat.threat_type.as_str().into(),
+            risk_level: threat.risk_level.as_str().into(),
+            signature_id: threat.id,
+            file_info: file_info.clone(),
+            archive_member: Some(member),
+            heuristic_score: None,
+            action_taken: None,
+        }
+    }
+}
+
+// 每个工作线程先在本地累计，定期合并到共享的 ScanStats，减少原子操作争用
+#[derive(Default)]
+struct WorkerStats {
+    files_scanned: usize,
+    threats_found: usize,
+    bytes_scanned: usize,
+    files_skipped: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+    errors: usize,
+    pending: usize,
+}
+
+impl WorkerStats {
+    fn merge_into(&mut self, stats: &ScanStats) {
+        stats.files_scanned.fetch_add(self.files_scanned, Ordering::Relaxed);
+        stats.threats_found.fetch_add(self.threats_found, Ordering::Relaxed);
+        stats.bytes_scanned.fetch_add(self.bytes_scanned, Ordering::Relaxed);
+        stats.files_skipped.fetch_add(self.files_skipped, Ordering::Relaxed);
+        stats.cache_hits.fetch_add(self.cache_hits, Ordering::Relaxed);
+        stats.cache_misses.fetch_add(self.cache_misses, Ordering::Relaxed);
+        stats.errors.fetch_add(self.errors, Ordering::Relaxed);
+        *self = WorkerStats::default();
     }
 }
 
@@ -0,0 +1,95 @@
+use crate::scanner::ScanOptions;
+use crate::utils::{GlobMatcher, GlobOptions};
+use anyhow::Context;
+use regex::RegexSet;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// 以该前缀开头的规则按正则表达式处理，其余规则按通配符处理
+pub const REGEX_PREFIX: &str = "regex:";
+// 扫描目录中的忽略文件，语法与 .gitignore 相同
+pub const SCANIGNORE_FILE: &str = ".scanignore";
+
+// 排除规则在每次扫描开始时编译一次。不含通配符的路径仍按目录前缀匹配，
+// 不含通配符的扩展名仍按精确匹配
+#[derive(Debug, Clone)]
+pub struct ExclusionRules {
+    paths: GlobMatcher,
+    path_regexes: RegexSet,
+    extensions: HashSet<String>,
+    extension_globs: GlobMatcher,
+    extension_regexes: RegexSet,
+    honor_scanignore: bool,
+}
+
+impl Default for ExclusionRules {
+    fn default() -> Self {
+        Self {
+            paths: GlobMatcher::empty(),
+            path_regexes: RegexSet::empty(),
+            extensions: HashSet::new(),
+            extension_globs: GlobMatcher::empty(),
+            extension_regexes: RegexSet::empty(),
+            honor_scanignore: false,
+        }
+    }
+}
+
+impl ExclusionRules {
+    pub fn new(exclude_paths: &[PathBuf], exclude_extensions: &[String]) -> Result<Self, anyhow::Error> {
+        let (path_regexes, path_globs) = split_rules(exclude_paths.iter().map(|p| p.to_string_lossy().to_string()));
+        let (extension_regexes, extension_rules) = split_rules(exclude_extensions.iter().cloned());
+        let (extension_globs, extensions): (Vec<String>, Vec<String>) =
+            extension_rules.into_iter().partition(|rule| GlobMatcher::is_glob(rule));
+
+        Ok(Self {
+            paths: GlobMatcher::new(&path_globs, GlobOptions::default())?,
+            path_regexes: RegexSet::new(&path_regexes).context("无效的排除路径正则表达式")?,
+            extensions: extensions.into_iter().collect(),
+            extension_globs: GlobMatcher::new(&extension_globs, GlobOptions::default())?,
+            extension_regexes: RegexSet::new(&extension_regexes).context("无效的排除扩展名正则表达式")?,
+            honor_scanignore: false,
+        })
+    }
+
+    pub fn from_options(options: &ScanOptions) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            honor_scanignore: options.honor_scanignore,
+            ..Self::new(&options.exclude_paths, &options.exclude_extensions)?
+        })
+    }
+
+    pub fn honor_scanignore(&self) -> bool {
+        self.honor_scanignore
+    }
+
+    pub fn excludes_path(&self, path: &Path) -> bool {
+        self.paths.is_match(path) || self.path_regexes.is_match(&path.to_string_lossy())
+    }
+
+    pub fn excludes_extension(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+            return false;
+        };
+        self.extensions.contains(extension)
+            || self.extension_globs.is_match(extension)
+            || self.extension_regexes.is_match(extension)
+    }
+}
+
+// 返回 (正则规则, 其他规则)
+fn split_rules(rules: impl Iterator<Item = String>) -> (Vec<String>, Vec<String>) {
+    let mut regexes = Vec::new();
+    let mut others = Vec::new();
+    for rule in rules {
+        let rule = rule.trim();
+        if rule.is_empty() {
+            continue;
+        }
+        match rule.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => regexes.push(pattern.to_string()),
+            None => others.push(rule.to_string()),
+        }
+    }
+    (regexes, others)
+}
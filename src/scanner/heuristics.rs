@@ -0,0 +1,450 @@
+use crate::config::HeuristicsConfig;
+use crate::scanner::pdf::PdfContents;
+use crate::scanner::{ElfFlag, ElfInfo};
+use crate::utils::{detect_file_type_from_bytes, FileKind};
+use regex::bytes::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const MAX_SCORE: u8 = 100;
+
+const DOCUMENT_EXTENSIONS: [&str; 12] = [
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "jpg", "jpeg", "png", "zip",
+];
+const EXECUTABLE_EXTENSIONS: [&str; 10] = ["exe", "scr", "com", "bat", "cmd", "js", "vbs", "ps1", "sh", "elf"];
+const TEMP_DIRECTORIES: [&str; 3] = ["/tmp/", "/var/tmp/", "/dev/shm/"];
+
+// (规则名, 特征串, 分值)
+const SUSPICIOUS_STRINGS: [(&str, &[u8], u8); 8] = [
+    ("reverse-shell", b"/dev/tcp/", 30),
+    ("reverse-shell", b"nc -e /bin/sh", 40),
+    ("miner", b"stratum+tcp://", 40),
+    ("miner", b"xmrig", 25),
+    ("preload-hijack", b"/etc/ld.so.preload", 30),
+    ("history-wipe", b"history -c", 15),
+    ("destructive", b"--no-preserve-root", 40),
+    ("decode-exec", b"base64 -d | sh", 35),
+];
+
+// 历史上被 PDF 漏洞利用过的 Acrobat JavaScript 接口
+const PDF_EXPLOIT_APIS: [&str; 6] = [
+    "util.printf",
+    "Collab.collectEmailInfo",
+    "Collab.getIcon",
+    "media.newPlayer",
+    "spell.customDictionaryOpen",
+    "getAnnots",
+];
+// 混淆脚本通常把载荷压成一整行，超过该长度的无空白片段视为打包代码
+const PACKED_TOKEN_LENGTH: usize = 4096;
+const SNIPPET_LENGTH: usize = 80;
+
+// (语言, 规则名, 正则, 分值)。同一规则的多个写法只计一次分
+const SCRIPT_RULES: [(ScriptLanguage, &str, &str, u8); 27] = [
+    (ScriptLanguage::Shell, "base64-exec", r"(?i)\bbase64\s+(-d|--decode)\b[^\n]*\|\s*(sudo\s+)?(ba|da|z|k)?sh\b", 60),
+    (ScriptLanguage::Shell, "base64-exec", r#"(?i)\beval\s+["']?\$\((echo|printf)\b[^)]*\|\s*base64\s+(-d|--decode)"#, 60),
+    (ScriptLanguage::Shell, "download-exec", r"(?i)\b(curl|wget)\b[^\n;|]*\|\s*(sudo\s+)?(ba|da|z|k)?sh\b", 60),
+    (ScriptLanguage::Shell, "download-exec", r#"(?i)\b(ba|da|z|k)?sh\s+(-c\s+)?["']?(<\(|\$\()\s*(curl|wget)\b"#, 60),
+    (ScriptLanguage::Shell, "download-chmod", r"(?i)\b(curl|wget)\b[^\n]*(-o|-O|>)\s*\S+[^\n]*(&&|;|\n)\s*chmod\s+(\+x|[0-7]?7[0-7]{2})\b", 40),
+    (ScriptLanguage::Shell, "reverse-shell", r"(?i)\b(ba|z)?sh\s+-i\s*[>&]+\s*/dev/(tcp|udp)/", 70),
+    (ScriptLanguage::Shell, "reverse-shell", r"(?i)\b(nc|ncat|netcat)\b[^\n]*\s-[ec]\s+/bin/(ba)?sh\b", 70),
+    (ScriptLanguage::Shell, "reverse-shell", r"(?i)\bmkfifo\b[^\n]*\b(nc|ncat|netcat|openssl\s+s_client)\b", 60),
+    (ScriptLanguage::Shell, "reverse-shell", r"(?i)\bsocat\b[^\n]*\bexec:[^\n]*\b(ba)?sh\b", 60),
+    (ScriptLanguage::Shell, "crontab-install", r"(?i)\bcrontab\s+-l\b[^\n]*\|\s*crontab\s+-", 50),
+    (ScriptLanguage::Shell, "crontab-install", r"(?i)>>?\s*(/etc/cron\.(d|hourly|daily|weekly)/|/etc/crontab\b|/var/spool/cron/)", 50),
+    (ScriptLanguage::Shell, "profile-persistence", r"(?i)>>\s*(~|\$HOME|/root|/home/[^/\s]+)/\.(bashrc|bash_profile|profile|zshrc)\b", 30),
+    (ScriptLanguage::Shell, "defense-evasion", r"(?i)\b(setenforce\s+0|ufw\s+disable|systemctl\s+(stop|disable)\s+(firewalld|auditd|apparmor)|chattr\s+\+i)\b", 25),
+    (ScriptLanguage::Shell, "history-wipe", r"(?i)(\bhistory\s+-c\b|\bunset\s+HISTFILE\b|HISTFILE=/dev/null)", 15),
+    (ScriptLanguage::Python, "base64-exec", r#"(?i)\b(exec|eval)\s*\(\s*(__import__\(\s*['"])?(base64|zlib|marshal|codecs)\b[^\n]*(decode|decompress|loads)"#, 60),
+    (ScriptLanguage::Python, "download-exec", r"(?i)\b(exec|eval)\s*\([^\n]*\b(urlopen|requests\.get|urllib)", 60),
+    (ScriptLanguage::Python, "reverse-shell", r#"(?i)\bpty\.spawn\s*\(\s*['"]/bin/(ba)?sh"#, 50),
+    (ScriptLanguage::Python, "reverse-shell", r"(?i)\bos\.dup2\s*\(\s*\w+\.fileno\s*\(\s*\)\s*,\s*[0-2]\s*\)", 50),
+    (ScriptLanguage::Python, "crontab-install", r"(?i)(/etc/cron\.d/|/var/spool/cron/|\bcrontab\s+-l\b)", 40),
+    (ScriptLanguage::PowerShell, "encoded-command", r"(?i)\s-(e|en|enc|enco|encodedcommand)\s+[A-Za-z0-9+/]{40,}={0,2}", 60),
+    (ScriptLanguage::PowerShell, "download-exec", r"(?i)\b(iex|invoke-expression)\b[^\n]*\b(downloadstring|invoke-webrequest|iwr|invoke-restmethod|irm|net\.webclient)\b", 70),
+    (ScriptLanguage::PowerShell, "download-exec", r"(?i)\b(downloadstring|invoke-webrequest|iwr|invoke-restmethod|irm)\b[^\n]*\|\s*(iex|invoke-expression)\b", 70),
+    (ScriptLanguage::PowerShell, "base64-exec", r"(?i)\b(iex|invoke-expression)\b[^\n]*frombase64string", 60),
+    (ScriptLanguage::PowerShell, "defense-evasion", r"(?i)(set-mppreference\b[^\n]*-disable\w*\s+\$?true|\bamsiutils\b|\bamsiinitfailed\b)", 50),
+    (ScriptLanguage::PowerShell, "hidden-window", r"(?i)\s-(w|windowstyle)\s+hidden\b", 15),
+    (ScriptLanguage::PowerShell, "execution-policy-bypass", r"(?i)\s-(ep|executionpolicy)\s+bypass\b", 15),
+    (ScriptLanguage::PowerShell, "persistence", r"(?i)(\bschtasks(\.exe)?\s+/create\b|\bregister-scheduledtask\b|currentversion\\run\b)", 40),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeuristicMatch {
+    pub rule: &'static str,
+    pub description: String,
+    pub score: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeuristicVerdict {
+    pub score: u8,
+    pub matches: Vec<HeuristicMatch>,
+    // 脚本规则命中时为脚本语言
+    pub language: Option<ScriptLanguage>,
+}
+
+impl HeuristicVerdict {
+    // 以得分最高的规则命名检测结果
+    pub fn detection_name(&self) -> String {
+        let rule = self
+            .matches
+            .iter()
+            .max_by_key(|m| m.score)
+            .map(|m| m.rule)
+            .unwrap_or("generic");
+        match self.language {
+            Some(language) => format!("ScriptHeuristic.{}.{}", language.as_str(), rule),
+            None => format!("Heuristic.{}", rule),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptLanguage {
+    Shell,
+    Python,
+    PowerShell,
+}
+
+impl ScriptLanguage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptLanguage::Shell => "Shell",
+            ScriptLanguage::Python => "Python",
+            ScriptLanguage::PowerShell => "PowerShell",
+        }
+    }
+
+    // 优先按 #! 解释器判断，PowerShell 脚本通常没有 #! 行，再按扩展名判断
+    pub fn detect(path: &Path, data: &[u8]) -> Option<Self> {
+        if let Some(shebang) = data.strip_prefix(b"#!") {
+            let line = String::from_utf8_lossy(shebang.split(|&b| b == b'\n').next().unwrap_or_default()).to_lowercase();
+            if line.contains("python") {
+                return Some(ScriptLanguage::Python);
+            }
+            if line.contains("pwsh") || line.contains("powershell") {
+                return Some(ScriptLanguage::PowerShell);
+            }
+            if ["sh", "bash", "dash", "zsh", "ksh", "ash"]
+                .iter()
+                .any(|shell| line.split(|c: char| c == '/' || c.is_whitespace()).any(|part| part == *shell))
+            {
+                return Some(ScriptLanguage::Shell);
+            }
+        }
+
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "sh" | "bash" | "zsh" | "ksh" => Some(ScriptLanguage::Shell),
+            "py" | "pyw" => Some(ScriptLanguage::Python),
+            "ps1" | "psm1" | "psd1" => Some(ScriptLanguage::PowerShell),
+            _ => None,
+        }
+    }
+}
+
+// 每条规则最多命中一次，描述中带上命中的片段
+pub fn match_script_rules(language: ScriptLanguage, data: &[u8]) -> Vec<HeuristicMatch> {
+    let mut matches: Vec<HeuristicMatch> = Vec::new();
+    for (_, rule, pattern, score) in script_rules().iter().filter(|(l, ..)| *l == language) {
+        if matches.iter().any(|m| m.rule == *rule) {
+            continue;
+        }
+        if let Some(found) = pattern.find(data) {
+            let snippet: String = String::from_utf8_lossy(found.as_bytes()).trim().chars().take(SNIPPET_LENGTH).collect();
+            matches.push(HeuristicMatch {
+                rule,
+                description: format!("{} 脚本规则 {}: {}", language.as_str(), rule, snippet),
+                score: *score,
+            });
+        }
+    }
+    matches
+}
+
+fn script_rules() -> &'static [(ScriptLanguage, &'static str, Regex, u8)] {
+    static RULES: OnceLock<Vec<(ScriptLanguage, &'static str, Regex, u8)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        SCRIPT_RULES
+            .iter()
+            .map(|(language, rule, pattern, score)| (*language, *rule, Regex::new(pattern).unwrap(), *score))
+            .collect()
+    })
+}
+
+fn download_exec_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)\b(curl|wget)\b[^\n;|]*\|\s*(sudo\s+)?(ba|da|z)?sh\b").unwrap()
+    })
+}
+
+fn js_eval_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\beval\s*\(|\bnew\s+Function\s*\(|\bapp\.setTimeOut\s*\(").unwrap())
+}
+
+fn js_decode_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\b(unescape|String\.fromCharCode|atob)\s*\(").unwrap())
+}
+
+fn js_shellcode_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)(%u[0-9a-f]{4}){8,}|(\\x[0-9a-f]{2}){16,}").unwrap())
+}
+
+pub struct HeuristicEngine {
+    threshold: u8,
+    script_threshold: u8,
+}
+
+impl HeuristicEngine {
+    pub fn new(config: &HeuristicsConfig) -> Self {
+        Self {
+            threshold: config.threshold,
+            script_threshold: config.script_sensitivity.threshold(),
+        }
+    }
+
+    // 启发式规则用到的路径信息：最后两级扩展名 (双扩展名和脚本语言) 和所在的临时目录。
+    // 内容相同而这些信息不同的文件可能得出不同结论
+    pub fn path_inputs(path: &Path) -> String {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let mut parts = name.rsplit('.');
+        let last = parts.next().filter(|_| name.contains('.')).unwrap_or_default();
+        let inner = parts.next().filter(|_| parts.next().is_some()).unwrap_or_default();
+        let path_str = path.to_string_lossy();
+        let temp_dir = TEMP_DIRECTORIES.iter().find(|dir| path_str.starts_with(*dir)).copied().unwrap_or_default();
+        format!("{}|{}|{}", inner, last, temp_dir)
+    }
+
+    // 各规则分值累加，达到阈值才判定为可疑，与签名库无关
+    pub fn analyze(&self, path: &Path, file_kind: FileKind, data: &[u8]) -> Option<HeuristicVerdict> {
+        let mut matches = Vec::new();
+
+        if let Some(m) = check_double_extension(path) {
+            matches.push(m);
+        }
+        if let Some(m) = check_temp_executable(path, file_kind) {
+            matches.push(m);
+        }
+        if matches!(file_kind, FileKind::Script | FileKind::Text) {
+            if let Some(found) = download_exec_pattern().find(data) {
+                matches.push(HeuristicMatch {
+                    rule: "download-exec",
+                    description: format!("下载后直接执行: {}", String::from_utf8_lossy(found.as_bytes())),
+                    score: 60,
+                });
+            }
+        }
+        matches.extend(check_suspicious_strings(data));
+        if file_kind == FileKind::Elf {
+            matches.extend(check_elf(data));
+        }
+
+        self.verdict(matches)
+    }
+
+    // 仅分析能识别出语言的 Shell/Python/PowerShell 脚本，阈值由脚本灵敏度决定
+    pub fn analyze_script(&self, path: &Path, file_kind: FileKind, data: &[u8]) -> Option<HeuristicVerdict> {
+        if !matches!(file_kind, FileKind::Script | FileKind::Text) {
+            return None;
+        }
+        let language = ScriptLanguage::detect(path, data)?;
+
+        let mut matches = match_script_rules(language, data);
+        for m in check_suspicious_strings(data) {
+            if !matches.iter().any(|existing| existing.rule == m.rule) {
+                matches.push(m);
+            }
+        }
+
+        let score = total_score(&matches);
+        if score >= self.script_threshold && !matches.is_empty() {
+            Some(HeuristicVerdict { score, matches, language: Some(language) })
+        } else {
+            None
+        }
+    }
+
+    // PDF 中的脚本、启动动作和嵌入文件由 PdfParser 提取，这里只对提取结果评分
+    pub fn analyze_pdf(&self, contents: &PdfContents) -> Option<HeuristicVerdict> {
+        let mut matches = Vec::new();
+
+        if !contents.launch_actions.is_empty() {
+            let targets: Vec<&str> = contents.launch_actions.iter().map(|a| a.target.as_str()).collect();
+            matches.push(HeuristicMatch {
+                rule: "pdf-launch",
+                description: format!("PDF 启动外部程序: {}", targets.join(", ")),
+                score: 60,
+            });
+        }
+
+        let executables: Vec<&str> = contents
+            .embedded_files
+            .iter()
+            .filter(|f| detect_file_type_from_bytes(&f.data).is_executable())
+            .map(|f| f.name.as_str())
+            .collect();
+        if !executables.is_empty() {
+            matches.push(HeuristicMatch {
+                rule: "pdf-embedded-executable",
+                description: format!("PDF 内嵌可执行文件: {}", executables.join(", ")),
+                score: 50,
+            });
+        }
+
+        if !contents.scripts.is_empty() {
+            matches.push(HeuristicMatch {
+                rule: "pdf-javascript",
+                description: format!("PDF 包含 {} 段 JavaScript", contents.scripts.len()),
+                score: 10,
+            });
+            if contents.auto_action {
+                matches.push(HeuristicMatch {
+                    rule: "pdf-auto-action",
+                    description: "打开文档时自动执行动作".to_string(),
+                    score: 20,
+                });
+            }
+        }
+        for script in &contents.scripts {
+            for m in check_javascript(&script.code) {
+                if !matches.iter().any(|existing| existing.rule == m.rule) {
+                    matches.push(m);
+                }
+            }
+        }
+
+        self.verdict(matches)
+    }
+
+    fn verdict(&self, matches: Vec<HeuristicMatch>) -> Option<HeuristicVerdict> {
+        let score = total_score(&matches);
+        if score >= self.threshold && !matches.is_empty() {
+            Some(HeuristicVerdict { score, matches, language: None })
+        } else {
+            None
+        }
+    }
+}
+
+fn total_score(matches: &[HeuristicMatch]) -> u8 {
+    matches
+        .iter()
+        .fold(0u8, |total, m| total.saturating_add(m.score))
+        .min(MAX_SCORE)
+}
+
+fn check_javascript(code: &[u8]) -> Vec<HeuristicMatch> {
+    let mut matches = Vec::new();
+
+    if let (Some(eval), Some(decode)) = (js_eval_pattern().find(code), js_decode_pattern().captures(code)) {
+        matches.push(HeuristicMatch {
+            rule: "js-obfuscation",
+            description: format!(
+                "动态执行解码后的代码: {} + {}",
+                String::from_utf8_lossy(eval.as_bytes()).trim_end_matches('('),
+                String::from_utf8_lossy(&decode[1])
+            ),
+            score: 40,
+        });
+    }
+    if js_shellcode_pattern().is_match(code) {
+        matches.push(HeuristicMatch {
+            rule: "js-shellcode",
+            description: "脚本中包含编码的 shellcode".to_string(),
+            score: 50,
+        });
+    }
+    if let Some(api) = PDF_EXPLOIT_APIS.iter().find(|api| code.windows(api.len()).any(|w| w == api.as_bytes())) {
+        matches.push(HeuristicMatch {
+            rule: "js-exploit-api",
+            description: format!("调用曾被漏洞利用的接口: {}", api),
+            score: 40,
+        });
+    }
+    let longest_token = code.split(|b| b.is_ascii_whitespace()).map(|t| t.len()).max().unwrap_or(0);
+    if longest_token > PACKED_TOKEN_LENGTH {
+        matches.push(HeuristicMatch {
+            rule: "js-packed",
+            description: format!("单行代码长度 {} 字节", longest_token),
+            score: 20,
+        });
+    }
+
+    matches
+}
+
+fn check_double_extension(path: &Path) -> Option<HeuristicMatch> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let mut parts = name.rsplit('.');
+    let last = parts.next()?;
+    let inner = parts.next()?;
+    parts.next()?;
+
+    if EXECUTABLE_EXTENSIONS.contains(&last) && DOCUMENT_EXTENSIONS.contains(&inner) {
+        Some(HeuristicMatch {
+            rule: "double-extension",
+            description: format!("伪装的双扩展名: .{}.{}", inner, last),
+            score: 45,
+        })
+    } else {
+        None
+    }
+}
+
+fn check_temp_executable(path: &Path, file_kind: FileKind) -> Option<HeuristicMatch> {
+    let path_str = path.to_string_lossy();
+    let dir = TEMP_DIRECTORIES.iter().find(|dir| path_str.starts_with(*dir))?;
+
+    if matches!(file_kind, FileKind::Elf | FileKind::Pe | FileKind::MachO) {
+        Some(HeuristicMatch {
+            rule: "temp-executable",
+            description: format!("临时目录 {} 中的可执行文件", dir),
+            score: 30,
+        })
+    } else {
+        None
+    }
+}
+
+fn check_suspicious_strings(data: &[u8]) -> Vec<HeuristicMatch> {
+    SUSPICIOUS_STRINGS
+        .iter()
+        .filter(|(_, needle, _)| data.windows(needle.len()).any(|w| w == *needle))
+        .map(|(rule, needle, score)| HeuristicMatch {
+            rule,
+            description: format!("可疑字符串: {}", String::from_utf8_lossy(needle)),
+            score: *score,
+        })
+        .collect()
+}
+
+fn check_elf(data: &[u8]) -> Vec<HeuristicMatch> {
+    let info = match ElfInfo::parse(data) {
+        Ok(info) => info,
+        Err(_) => return Vec::new(),
+    };
+
+    info.heuristic_flags(data)
+        .into_iter()
+        .filter_map(|flag| {
+            let score = match flag {
+                ElfFlag::WritableExecutableSegment => 25,
+                ElfFlag::UpxPacked => 30,
+                ElfFlag::EntryPointOutsideCode => 30,
+                ElfFlag::SectionHeadersStripped => 10,
+                ElfFlag::Stripped => return None,
+            };
+            Some(HeuristicMatch {
+                rule: flag.as_str(),
+                description: format!("ELF特征: {}", flag.as_str()),
+                score,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,668 @@
+use crate::config::ArchiveConfig;
+use crate::scanner::{ScanOptions, ScanResult, ScannerEngine, SignatureDatabase};
+use crate::utils::{ensure_free_space, format_bytes};
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const DEFAULT_REGISTRY: &str = "registry-1.docker.io";
+
+const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.index.v1+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.docker.distribution.manifest.v2+json";
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    pub reference: String,
+}
+
+impl ImageReference {
+    pub fn parse(image: &str) -> Result<Self, anyhow::Error> {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_string())),
+            None => (image, None),
+        };
+
+        let (registry, remainder) = match name.split_once('/') {
+            Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+                (first.to_string(), rest.to_string())
+            }
+            _ => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+        };
+
+        let (repository, tag) = match remainder.rsplit_once(':') {
+            Some((repo, tag)) if !tag.contains('/') => (repo.to_string(), tag.to_string()),
+            _ => (remainder.clone(), "latest".to_string()),
+        };
+
+        if repository.is_empty() {
+            return Err(anyhow::anyhow!("无效的镜像引用: {}", image));
+        }
+
+        let registry = if registry == "docker.io" || registry == "index.docker.io" {
+            DEFAULT_REGISTRY.to_string()
+        } else {
+            registry
+        };
+
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+
+        Ok(Self {
+            registry,
+            repository,
+            reference: digest.unwrap_or(tag),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageLayer {
+    pub digest: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageDetection {
+    pub layer_index: usize,
+    pub layer_digest: String,
+    pub image_path: PathBuf,
+    pub result: ScanResult,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageScanReport {
+    pub image: String,
+    pub layers: Vec<String>,
+    pub files_scanned: usize,
+    pub detections: Vec<ImageDetection>,
+}
+
+pub struct ImageScanner {
+    signature_db: Arc<SignatureDatabase>,
+    options: ScanOptions,
+}
+
+impl ImageScanner {
+    pub fn new(signature_db: Arc<SignatureDatabase>, options: ScanOptions) -> Self {
+        Self { signature_db, options }
+    }
+
+    pub async fn scan(&self, image: &str) -> Result<ImageScanReport, anyhow::Error> {
+        let workspace = tempfile::Builder::new()
+            .prefix("virus-scanner-image-")
+            .tempdir()
+            .context("无法创建镜像临时工作目录")?;
+
+        let layers = if Path::new(image).exists() {
+            log::info!("正在读取镜像包: {}", image);
+            Self::read_archive(Path::new(image), &workspace, &self.options.archive)?
+        } else {
+            log::info!("正在拉取镜像: {}", image);
+            Self::pull(&ImageReference::parse(image)?, &workspace).await?
+        };
+
+        let rootfs = workspace.path().join("rootfs");
+        std::fs::create_dir_all(&rootfs)?;
+
+        let mut origins = HashMap::new();
+        for (index, layer) in layers.iter().enumerate() {
+            log::debug!("正在解压镜像层 {}: {}", index, layer.digest);
+            apply_layer(&layer.path, &rootfs, index, &mut origins, &self.options.archive)
+                .with_context(|| format!("无法解压镜像层 {}", layer.digest))?;
+        }
+
+        let mut options = self.options.clone();
+        options.custom_paths = vec![rootfs.clone()];
+        options.exclude_paths.clear();
+        options.use_xattr_markers = false;
+
+        let engine = ScannerEngine::new(Arc::clone(&self.signature_db), options);
+        let results = engine.start_scan().await?;
+
+        // 引擎返回的是规范化后的路径，这里按同样方式还原出镜像内路径
+        let rootfs = std::fs::canonicalize(&rootfs)?;
+        let detections = results
+            .into_iter()
+            .map(|result| {
+                let relative = result
+                    .file_path
+                    .strip_prefix(&rootfs)
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_else(|_| result.file_path.clone());
+                let layer_index = origins.get(&relative).copied().unwrap_or(0);
+                ImageDetection {
+                    layer_index,
+                    layer_digest: layers.get(layer_index).map(|l| l.digest.clone()).unwrap_or_default(),
+                    image_path: Path::new("/").join(&relative),
+                    result,
+                }
+            })
+            .collect();
+
+        Ok(ImageScanReport {
+            image: image.to_string(),
+            layers: layers.iter().map(|l| l.digest.clone()).collect(),
+            files_scanned: engine.get_stats().get_files_scanned(),
+            detections,
+        })
+    }
+
+    // 支持 docker save 生成的包 (manifest.json) 和 OCI 镜像布局 (index.json)
+    fn read_archive(path: &Path, workspace: &TempDir, limits: &ArchiveConfig) -> Result<Vec<ImageLayer>, anyhow::Error> {
+        let root = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            let extracted = workspace.path().join("archive");
+            std::fs::create_dir_all(&extracted)?;
+            let mut archive = open_tar(path, limits).with_context(|| format!("无法打开镜像包 {:?}", path))?;
+            let mut entries = 0;
+            for entry in archive.entries().context("无法解压镜像包")? {
+                let mut entry = entry.context("无法解压镜像包")?;
+                check_entry(&mut entries, limits, &extracted, entry.header().size()?)?;
+                entry.unpack_in(&extracted).context("无法解压镜像包")?;
+            }
+            extracted
+        };
+
+        if root.join("manifest.json").exists() {
+            let manifest: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(root.join("manifest.json"))?)?;
+            let layers = manifest
+                .get(0)
+                .and_then(|m| m.get("Layers"))
+                .and_then(|l| l.as_array())
+                .ok_or_else(|| anyhow::anyhow!("manifest.json 格式无效"))?;
+
+            return layers
+                .iter()
+                .filter_map(|l| l.as_str())
+                .map(|l| {
+                    let layer_path = safe_join(&root, Path::new(l))
+                        .ok_or_else(|| anyhow::anyhow!("无效的镜像层路径: {}", l))?;
+                    Ok(ImageLayer {
+                        digest: l.to_string(),
+                        path: layer_path,
+                    })
+                })
+                .collect();
+        }
+
+        if root.join("index.json").exists() {
+            let index: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(root.join("index.json"))?)?;
+            let manifest = Self::resolve_oci_manifest(&root, &index)?;
+            return Self::manifest_layers(&manifest)
+                .into_iter()
+                .map(|digest| {
+                    let layer_path = blob_path(&root, &digest)?;
+                    Ok(ImageLayer { digest, path: layer_path })
+                })
+                .collect();
+        }
+
+        Err(anyhow::anyhow!("无法识别的镜像格式: {:?}", path))
+    }
+
+    fn resolve_oci_manifest(root: &Path, document: &serde_json::Value) -> Result<serde_json::Value, anyhow::Error> {
+        if document.get("layers").is_some() {
+            return Ok(document.clone());
+        }
+
+        let digest = select_platform_manifest(document)
+            .ok_or_else(|| anyhow::anyhow!("镜像索引中没有可用的清单"))?;
+        let content = std::fs::read_to_string(blob_path(root, &digest)?)?;
+        Self::resolve_oci_manifest(root, &serde_json::from_str(&content)?)
+    }
+
+    fn manifest_layers(manifest: &serde_json::Value) -> Vec<String> {
+        manifest
+            .get("layers")
+            .and_then(|l| l.as_array())
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| l.get("digest").and_then(|d| d.as_str()).map(|d| d.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    async fn pull(reference: &ImageReference, workspace: &TempDir) -> Result<Vec<ImageLayer>, anyhow::Error> {
+        let client = RegistryClient::new(reference.clone());
+
+        let mut manifest = client.get_manifest(&reference.reference).await?;
+        if manifest.get("layers").is_none() {
+            let digest = select_platform_manifest(&manifest)
+                .ok_or_else(|| anyhow::anyhow!("镜像索引中没有适用于当前平台的清单"))?;
+            manifest = client.get_manifest(&digest).await?;
+        }
+
+        let mut layers = Vec::new();
+        for digest in Self::manifest_layers(&manifest) {
+            let layer_path = blob_path(workspace.path(), &digest)?;
+            if let Some(dir) = layer_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            client.download_blob(&digest, &layer_path).await?;
+            layers.push(ImageLayer { digest, path: layer_path });
+        }
+
+        Ok(layers)
+    }
+}
+
+struct RegistryClient {
+    client: reqwest::Client,
+    reference: ImageReference,
+    token: tokio::sync::Mutex<Option<String>>,
+}
+
+impl RegistryClient {
+    fn new(reference: ImageReference) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            reference,
+            token: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    fn url(&self, kind: &str, reference: &str) -> String {
+        let scheme = if self.reference.registry.starts_with("localhost") { "http" } else { "https" };
+        format!(
+            "{}://{}/v2/{}/{}/{}",
+            scheme, self.reference.registry, self.reference.repository, kind, reference
+        )
+    }
+
+    async fn send(&self, url: &str, accept: Option<&str>) -> Result<reqwest::Response, anyhow::Error> {
+        for _ in 0..2 {
+            let mut request = self.client.get(url);
+            if let Some(accept) = accept {
+                request = request.header("Accept", accept);
+            }
+            if let Some(ref token) = *self.token.lock().await {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request.send().await.context("无法连接到镜像仓库")?;
+            if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                let challenge = response
+                    .headers()
+                    .get("www-authenticate")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("")
+                    .to_string();
+                *self.token.lock().await = Some(self.fetch_token(&challenge).await?);
+                continue;
+            }
+
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("镜像仓库返回错误: {} ({})", response.status(), url));
+            }
+            return Ok(response);
+        }
+
+        Err(anyhow::anyhow!("镜像仓库认证失败: {}", url))
+    }
+
+    // 仅支持匿名 Bearer 令牌，适用于公开镜像
+    async fn fetch_token(&self, challenge: &str) -> Result<String, anyhow::Error> {
+        let params = parse_auth_challenge(challenge);
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| anyhow::anyhow!("镜像仓库未提供认证地址"))?;
+
+        let mut query = Vec::new();
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.clone()));
+        }
+        query.push((
+            "scope",
+            params
+                .get("scope")
+                .cloned()
+                .unwrap_or_else(|| format!("repository:{}:pull", self.reference.repository)),
+        ));
+
+        let body: serde_json::Value = self
+            .client
+            .get(realm)
+            .query(&query)
+            .send()
+            .await
+            .context("无法获取镜像仓库令牌")?
+            .json()
+            .await?;
+
+        body.get("token")
+            .or_else(|| body.get("access_token"))
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string())
+            .ok_or_else(|| anyhow::anyhow!("镜像仓库令牌响应无效"))
+    }
+
+    async fn get_manifest(&self, reference: &str) -> Result<serde_json::Value, anyhow::Error> {
+        let response = self.send(&self.url("manifests", reference), Some(MANIFEST_ACCEPT)).await?;
+        Ok(response.json().await.context("无法解析镜像清单")?)
+    }
+
+    async fn download_blob(&self, digest: &str, dest: &Path) -> Result<(), anyhow::Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut response = self.send(&self.url("blobs", digest), None).await?;
+        let mut file = tokio::fs::File::create(dest).await?;
+        let mut hasher = openssl::sha::Sha256::new();
+
+        while let Some(chunk) = response.chunk().await? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        let actual = format!("sha256:{}", hex::encode(hasher.finish()));
+        if digest.starts_with("sha256:") && actual != digest {
+            return Err(anyhow::anyhow!("镜像层校验失败: 期望 {}，实际 {}", digest, actual));
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_auth_challenge(challenge: &str) -> HashMap<String, String> {
+    let params = challenge.trim_start_matches("Bearer").trim();
+    let mut result = HashMap::new();
+    let mut rest = params;
+
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let value = value.trim_start();
+        let (value, remainder) = if let Some(quoted) = value.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((v, r)) => (v, r),
+                None => (quoted, ""),
+            }
+        } else {
+            match value.split_once(',') {
+                Some((v, r)) => (v, r),
+                None => (value, ""),
+            }
+        };
+        result.insert(key, value.to_string());
+        rest = remainder;
+    }
+
+    result
+}
+
+fn select_platform_manifest(index: &serde_json::Value) -> Option<String> {
+    let manifests = index.get("manifests")?.as_array()?;
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => other,
+    };
+
+    let matches_platform = |m: &&serde_json::Value| {
+        m.get("platform")
+            .map(|p| {
+                p.get("os").and_then(|o| o.as_str()) == Some("linux")
+                    && p.get("architecture").and_then(|a| a.as_str()) == Some(arch)
+            })
+            .unwrap_or(false)
+    };
+
+    manifests
+        .iter()
+        .find(matches_platform)
+        .or_else(|| manifests.first())
+        .and_then(|m| m.get("digest"))
+        .and_then(|d| d.as_str())
+        .map(|d| d.to_string())
+}
+
+fn blob_path(root: &Path, digest: &str) -> Result<PathBuf, anyhow::Error> {
+    let (algorithm, hex) = digest
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("无效的摘要: {}", digest))?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) || !algorithm.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow::anyhow!("无效的摘要: {}", digest));
+    }
+    Ok(root.join("blobs").join(algorithm).join(hex))
+}
+
+fn decompress(mut file: std::fs::File) -> Result<Box<dyn Read>, anyhow::Error> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut magic = [0u8; 2];
+    let n = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if n == 2 && magic == [0x1f, 0x8b] {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+// 去掉前导的 "/" 和 "./"，拒绝包含 ".." 的路径
+fn normalize_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir | Component::RootDir => {}
+            _ => return None,
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+// 父目录中出现符号链接时拒绝写入，防止恶意镜像层借助链接写到工作目录之外
+fn safe_join(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let relative = normalize_entry_path(relative)?;
+    let mut current = root.to_path_buf();
+    let components: Vec<_> = relative.components().collect();
+
+    for (i, component) in components.iter().enumerate() {
+        current.push(component);
+        if i + 1 < components.len() {
+            if let Ok(metadata) = std::fs::symlink_metadata(&current) {
+                if metadata.file_type().is_symlink() {
+                    return None;
+                }
+            }
+        }
+    }
+
+    Some(current)
+}
+
+fn remove_path(path: &Path) {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        let _ = if metadata.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+    }
+}
+
+// 镜像包和镜像层沿用压缩包扫描的解压上限：解压出的总字节数不超过 max_decompressed_size，
+// 条目数不超过 max_members，防止很小的压缩层解压后占满磁盘
+fn open_tar(path: &Path, limits: &ArchiveConfig) -> Result<tar::Archive<LimitedReader<Box<dyn Read>>>, anyhow::Error> {
+    let file = std::fs::File::open(path)?;
+    Ok(tar::Archive::new(LimitedReader {
+        inner: decompress(file)?,
+        remaining: limits.max_decompressed_size,
+        limit: limits.max_decompressed_size,
+    }))
+}
+
+// 写入每个条目前计数并检查剩余磁盘空间
+fn check_entry(entries: &mut usize, limits: &ArchiveConfig, dir: &Path, size: u64) -> Result<(), anyhow::Error> {
+    *entries += 1;
+    if *entries > limits.max_members {
+        return Err(anyhow::anyhow!("解压条目数超过上限 {}", limits.max_members));
+    }
+    ensure_free_space(dir, size.min(limits.max_decompressed_size))?;
+    Ok(())
+}
+
+// 按解压后实际读出的字节计数，不信任条目头中声明的大小
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            // 恰好在上限处结束的流不算超限
+            return match self.inner.read(&mut [0u8; 1])? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("解压后的大小超过上限 {}", format_bytes(self.limit)),
+                )),
+            };
+        }
+        let len = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+// whiteout 文件 只作用于更低的层，因此先统一处理删除，再解压本层内容
+pub fn apply_layer(
+    layer: &Path,
+    rootfs: &Path,
+    index: usize,
+    origins: &mut HashMap<PathBuf, usize>,
+    limits: &ArchiveConfig,
+) -> Result<(), anyhow::Error> {
+    let mut archive = open_tar(layer, limits)?;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let relative = match normalize_entry_path(&entry.path()?) {
+            Some(path) => path,
+            None => continue,
+        };
+        let file_name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let parent = relative.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+
+        if file_name == OPAQUE_WHITEOUT {
+            let dir = if parent.as_os_str().is_empty() {
+                Some(rootfs.to_path_buf())
+            } else {
+                safe_join(rootfs, &parent)
+            };
+            if let Some(children) = dir.and_then(|d| std::fs::read_dir(d).ok()) {
+                for child in children.flatten() {
+                    remove_path(&child.path());
+                }
+            }
+            origins.retain(|path, _| !path.starts_with(&parent) || path == &parent);
+        } else if let Some(hidden) = file_name.strip_prefix(WHITEOUT_PREFIX) {
+            let target = parent.join(hidden);
+            if let Some(dest) = safe_join(rootfs, &target) {
+                remove_path(&dest);
+            }
+            origins.retain(|path, _| !path.starts_with(&target));
+        }
+    }
+
+    let mut archive = open_tar(layer, limits)?;
+    let mut entries = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        check_entry(&mut entries, limits, rootfs, entry.header().size()?)?;
+        let entry_path = entry.path()?.to_path_buf();
+        let relative = match normalize_entry_path(&entry_path) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let is_whiteout = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().starts_with(WHITEOUT_PREFIX))
+            .unwrap_or(false);
+        if is_whiteout {
+            continue;
+        }
+
+        let dest = match safe_join(rootfs, &relative) {
+            Some(dest) => dest,
+            None => {
+                log::debug!("跳过不安全的镜像层条目: {:?}", entry_path);
+                continue;
+            }
+        };
+
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            if std::fs::symlink_metadata(&dest).map(|m| !m.is_dir()).unwrap_or(false) {
+                remove_path(&dest);
+            }
+            std::fs::create_dir_all(&dest)?;
+            continue;
+        }
+
+        if entry_type.is_file() {
+            remove_path(&dest);
+            let mut out = std::fs::File::create(&dest)?;
+            std::io::copy(&mut entry, &mut out)?;
+        } else if entry_type.is_symlink() {
+            remove_path(&dest);
+            #[cfg(unix)]
+            if let Some(target) = entry.link_name()? {
+                std::os::unix::fs::symlink(target.as_ref(), &dest)?;
+            }
+        } else if entry_type.is_hard_link() {
+            let source = entry
+                .link_name()?
+                .and_then(|target| safe_join(rootfs, &target));
+            if let Some(source) = source.filter(|s| s.is_file()) {
+                remove_path(&dest);
+                std::fs::copy(&source, &dest)?;
+            }
+        } else {
+            // 设备文件、FIFO等不含可扫描内容
+            continue;
+        }
+
+        origins.insert(relative, index);
+    }
+
+    Ok(())
+}
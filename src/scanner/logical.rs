@@ -0,0 +1,281 @@
+use crate::scanner::cvd::{hex_signature_to_regex, offset_anchor};
+use regex::bytes::{Regex, RegexBuilder};
+
+// 单个子特征码最多统计的命中次数，足以覆盖表达式中的计数条件
+const MAX_SUBSIG_MATCHES: usize = 256;
+const SUBSIG_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    Greater,
+    Less,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Subsig(usize),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Count {
+        inner: Box<Expr>,
+        comparison: Comparison,
+        value: usize,
+        min_distinct: Option<usize>,
+    },
+}
+
+impl Expr {
+    fn max_index(&self) -> usize {
+        match self {
+            Expr::Subsig(i) => *i,
+            Expr::And(items) | Expr::Or(items) => items.iter().map(Expr::max_index).max().unwrap_or(0),
+            Expr::Count { inner, .. } => inner.max_index(),
+        }
+    }
+
+    // 返回 (命中次数, 命中的不同子特征码数)，次数为 0 表示不成立
+    fn evaluate(&self, counts: &[usize]) -> (usize, usize) {
+        match self {
+            Expr::Subsig(i) => {
+                let count = counts[*i];
+                (count, usize::from(count > 0))
+            }
+            Expr::And(items) => {
+                let mut total = (0, 0);
+                for item in items {
+                    let (count, distinct) = item.evaluate(counts);
+                    if count == 0 {
+                        return (0, 0);
+                    }
+                    total = (total.0 + count, total.1 + distinct);
+                }
+                total
+            }
+            Expr::Or(items) => items.iter().fold((0, 0), |total, item| {
+                let (count, distinct) = item.evaluate(counts);
+                (total.0 + count, total.1 + distinct)
+            }),
+            Expr::Count {
+                inner,
+                comparison,
+                value,
+                min_distinct,
+            } => {
+                let (count, distinct) = inner.evaluate(counts);
+                let satisfied = match comparison {
+                    Comparison::Equal => count == *value,
+                    Comparison::Greater => count > *value,
+                    Comparison::Less => count < *value,
+                } && min_distinct.map_or(true, |min| distinct >= min);
+
+                // =0 和 <X 可以在没有任何命中时成立
+                if satisfied {
+                    (count.max(1), distinct)
+                } else {
+                    (0, 0)
+                }
+            }
+        }
+    }
+}
+
+struct ExprParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn parse(input: &'a str) -> Option<Expr> {
+        let mut parser = Self {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+        let expr = parser.or_expr()?;
+        if parser.pos == parser.input.len() {
+            Some(expr)
+        } else {
+            None
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos]).ok()?.parse().ok()
+    }
+
+    fn or_expr(&mut self) -> Option<Expr> {
+        let mut items = vec![self.and_expr()?];
+        while self.peek() == Some(b'|') {
+            self.pos += 1;
+            items.push(self.and_expr()?);
+        }
+        Some(if items.len() == 1 { items.remove(0) } else { Expr::Or(items) })
+    }
+
+    fn and_expr(&mut self) -> Option<Expr> {
+        let mut items = vec![self.atom()?];
+        while self.peek() == Some(b'&') {
+            self.pos += 1;
+            items.push(self.atom()?);
+        }
+        Some(if items.len() == 1 { items.remove(0) } else { Expr::And(items) })
+    }
+
+    fn atom(&mut self) -> Option<Expr> {
+        let expr = if self.peek() == Some(b'(') {
+            self.pos += 1;
+            let inner = self.or_expr()?;
+            if self.peek() != Some(b')') {
+                return None;
+            }
+            self.pos += 1;
+            inner
+        } else {
+            Expr::Subsig(self.number()?)
+        };
+
+        let comparison = match self.peek() {
+            Some(b'=') => Comparison::Equal,
+            Some(b'>') => Comparison::Greater,
+            Some(b'<') => Comparison::Less,
+            _ => return Some(expr),
+        };
+        self.pos += 1;
+        let value = self.number()?;
+        let min_distinct = if self.peek() == Some(b',') {
+            self.pos += 1;
+            Some(self.number()?)
+        } else {
+            None
+        };
+
+        Some(Expr::Count {
+            inner: Box::new(expr),
+            comparison,
+            value,
+            min_distinct,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct LogicalSignature {
+    pub name: String,
+    pub target: Option<String>,
+    expression: Expr,
+    subsignatures: Vec<Regex>,
+}
+
+impl LogicalSignature {
+    // 格式: 病毒名;目标描述块;逻辑表达式;子特征码0;子特征码1;...
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim().split(';').collect();
+        if fields.len() < 4 {
+            return None;
+        }
+
+        let target = fields[1]
+            .split(',')
+            .filter_map(|attr| attr.split_once(':'))
+            .find(|(key, _)| *key == "Target")
+            .map(|(_, value)| value.to_string());
+
+        let expression = ExprParser::parse(fields[2])?;
+        let subsignatures = fields[3..]
+            .iter()
+            .map(|subsig| compile_subsignature(subsig))
+            .collect::<Option<Vec<_>>>()?;
+
+        if expression.max_index() >= subsignatures.len() {
+            return None;
+        }
+
+        Some(Self {
+            name: fields[0].to_string(),
+            target,
+            expression,
+            subsignatures,
+        })
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.evaluate(&self.count_matches(data, 0))
+    }
+
+    // 分块扫描时只统计结束位置越过重叠区的命中，避免同一命中被相邻窗口重复计数；
+    // 非首个窗口中带 \A 锚点的子特征码没有意义，直接跳过
+    pub fn count_matches(&self, data: &[u8], min_end: usize) -> Vec<usize> {
+        self.subsignatures
+            .iter()
+            .map(|regex| {
+                if min_end > 0 && regex.as_str().contains(r"\A") {
+                    return 0;
+                }
+                regex
+                    .find_iter(data)
+                    .filter(|m| m.end() > min_end)
+                    .take(MAX_SUBSIG_MATCHES)
+                    .count()
+            })
+            .collect()
+    }
+
+    pub fn evaluate(&self, counts: &[usize]) -> bool {
+        self.expression.evaluate(counts).0 > 0
+    }
+}
+
+fn wide_literal(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!(r"\x{:02x}\x00", b)).collect()
+}
+
+// 子特征码: [偏移:]十六进制特征码[::修饰符]，或 [触发条件/]PCRE/标志
+fn compile_subsignature(subsig: &str) -> Option<Regex> {
+    // PCRE 子特征码的触发表达式在此不单独求值，只要求正则本身命中
+    let source = if let Some((_trigger, rest)) = subsig.split_once('/') {
+        let (pattern, flags) = rest.rsplit_once('/')?;
+        let inline: String = flags.chars().filter(|c| matches!(c, 'i' | 's' | 'm' | 'x')).collect();
+        if inline.is_empty() {
+            pattern.to_string()
+        } else {
+            format!("(?{}){}", inline, pattern)
+        }
+    } else {
+        let (body, modifiers) = subsig.split_once("::").unwrap_or((subsig, ""));
+        let (anchor, hex_pattern) = match body.split_once(':') {
+            Some((offset, hex_pattern)) => (offset_anchor(offset), hex_pattern),
+            None => (String::new(), body),
+        };
+
+        let ascii = hex_signature_to_regex(hex_pattern)?;
+        let mut body = if modifiers.contains('w') {
+            let wide = wide_literal(&hex::decode(hex_pattern).ok()?);
+            if modifiers.contains('a') {
+                format!("(?:{}|{})", ascii, wide)
+            } else {
+                wide
+            }
+        } else {
+            ascii
+        };
+        if modifiers.contains('f') {
+            body = format!(r"\b{}\b", body);
+        }
+        let case = if modifiers.contains('i') { "(?i)" } else { "" };
+        format!("(?s){}{}(?:{})", case, anchor, body)
+    };
+
+    RegexBuilder::new(&source)
+        .unicode(false)
+        .size_limit(SUBSIG_SIZE_LIMIT)
+        .build()
+        .ok()
+}
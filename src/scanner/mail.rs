@@ -0,0 +1,341 @@
+use crate::scanner::{SignatureDatabase, ThreatSignature};
+use base64::Engine;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+const MAX_MIME_DEPTH: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct MailPart {
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub is_attachment: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct MailDetection {
+    pub part_index: usize,
+    pub filename: Option<String>,
+    pub threat: ThreatSignature,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MimeHeaders {
+    content_type: String,
+    boundary: Option<String>,
+    encoding: String,
+    disposition: String,
+    filename: Option<String>,
+}
+
+impl MimeHeaders {
+    fn parse(raw: &str) -> Self {
+        let mut headers = MimeHeaders {
+            content_type: "text/plain".to_string(),
+            ..Default::default()
+        };
+
+        for (name, value) in unfold_headers(raw) {
+            match name.to_ascii_lowercase().as_str() {
+                "content-type" => {
+                    let (value_type, params) = split_header_value(&value);
+                    headers.content_type = value_type.to_ascii_lowercase();
+                    for (key, param) in params {
+                        match key.as_str() {
+                            "boundary" => headers.boundary = Some(param),
+                            "name" if headers.filename.is_none() => headers.filename = Some(param),
+                            _ => {}
+                        }
+                    }
+                }
+                "content-transfer-encoding" => headers.encoding = value.trim().to_ascii_lowercase(),
+                "content-disposition" => {
+                    let (disposition, params) = split_header_value(&value);
+                    headers.disposition = disposition.to_ascii_lowercase();
+                    for (key, param) in params {
+                        if key == "filename" {
+                            headers.filename = Some(param);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        headers
+    }
+}
+
+fn unfold_headers(raw: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in raw.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = headers.last_mut() {
+                last.1.push(' ');
+                last.1.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    headers
+}
+
+fn split_header_value(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = value.split(';');
+    let main = parts.next().unwrap_or("").trim().to_string();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.trim().trim_end_matches('*').to_ascii_lowercase(),
+                v.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    (main, params)
+}
+
+fn split_headers_body(raw: &[u8]) -> (&[u8], &[u8]) {
+    if let Some(body) = raw.strip_prefix(b"\r\n").or_else(|| raw.strip_prefix(b"\n")) {
+        return (&[], body);
+    }
+    for (i, window) in raw.windows(2).enumerate() {
+        if window == b"\n\n" {
+            return (&raw[..i], &raw[i + 2..]);
+        }
+        if window == b"\n\r" && raw.get(i + 2) == Some(&b'\n') {
+            return (&raw[..i], &raw[i + 3..]);
+        }
+    }
+    (raw, &[])
+}
+
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        let trimmed = line.strip_suffix(b"\n").unwrap_or(line);
+        let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+
+        if trimmed.starts_with(delimiter.as_bytes()) {
+            if let Some(s) = start {
+                parts.push(strip_line_ending(&body[s..offset]));
+            }
+            if trimmed[delimiter.len()..].starts_with(b"--") {
+                return parts;
+            }
+            start = Some(offset + line.len());
+        }
+        offset += line.len();
+    }
+
+    if let Some(s) = start {
+        parts.push(&body[s..]);
+    }
+    parts
+}
+
+// 边界前的换行属于分隔符，不属于正文
+fn strip_line_ending(part: &[u8]) -> &[u8] {
+    let part = part.strip_suffix(b"\n").unwrap_or(part);
+    part.strip_suffix(b"\r").unwrap_or(part)
+}
+
+fn decode_body(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
+        "base64" => {
+            let engine = base64::engine::GeneralPurpose::new(
+                &base64::alphabet::STANDARD,
+                base64::engine::GeneralPurposeConfig::new()
+                    .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+            );
+            let cleaned: Vec<u8> = body.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+            engine.decode(&cleaned).unwrap_or_else(|_| body.to_vec())
+        }
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    }
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+
+    while i < body.len() {
+        if body[i] == b'=' {
+            match (body.get(i + 1), body.get(i + 2)) {
+                (Some(b'\r'), Some(b'\n')) => i += 3,
+                (Some(b'\n'), _) => i += 2,
+                (Some(&h), Some(&l)) if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() => {
+                    let hex = [h, l];
+                    out.push(u8::from_str_radix(std::str::from_utf8(&hex).unwrap(), 16).unwrap());
+                    i += 3;
+                }
+                _ => {
+                    out.push(b'=');
+                    i += 1;
+                }
+            }
+        } else {
+            out.push(body[i]);
+            i += 1;
+        }
+    }
+
+    out
+}
+
+fn collect_parts(raw: &[u8], depth: usize, parts: &mut Vec<MailPart>) {
+    let (header_bytes, body) = split_headers_body(raw);
+    let headers = MimeHeaders::parse(&String::from_utf8_lossy(header_bytes));
+
+    if depth < MAX_MIME_DEPTH {
+        if headers.content_type.starts_with("multipart/") {
+            if let Some(ref boundary) = headers.boundary {
+                for part in split_multipart(body, boundary) {
+                    collect_parts(part, depth + 1, parts);
+                }
+                return;
+            }
+        }
+
+        if headers.content_type == "message/rfc822" {
+            collect_parts(&decode_body(body, &headers.encoding), depth + 1, parts);
+            return;
+        }
+    }
+
+    let is_attachment = headers.disposition == "attachment"
+        || headers.filename.is_some()
+        || !headers.content_type.starts_with("text/");
+
+    parts.push(MailPart {
+        content_type: headers.content_type,
+        filename: headers.filename,
+        is_attachment,
+        data: decode_body(body, &headers.encoding),
+    });
+}
+
+// 递归展开 multipart 和内嵌邮件，返回解码后的叶子部分
+pub fn parse_message(raw: &[u8]) -> Vec<MailPart> {
+    let mut parts = Vec::new();
+    collect_parts(raw, 0, &mut parts);
+    parts
+}
+
+pub fn extract_attachments(raw: &[u8]) -> Vec<MailPart> {
+    parse_message(raw).into_iter().filter(|p| p.is_attachment).collect()
+}
+
+pub async fn scan_message(signature_db: &SignatureDatabase, raw: &[u8]) -> Vec<MailDetection> {
+    let mut detections = Vec::new();
+
+    for (part_index, part) in parse_message(raw).into_iter().enumerate() {
+        if part.data.is_empty() {
+            continue;
+        }
+        if let Some(threat) = signature_db.scan_bytes(&part.data).await {
+            detections.push(MailDetection {
+                part_index,
+                filename: part.filename,
+                threat,
+            });
+        }
+    }
+
+    detections
+}
+
+// 分隔行格式为 "From 发件人 日期"，要求带有时间，避免把未转义的正文行当成分隔行
+fn is_mbox_separator(line: &[u8]) -> bool {
+    line.starts_with(b"From ")
+        && line.windows(5).any(|w| {
+            w[0].is_ascii_digit() && w[1].is_ascii_digit() && w[2] == b':' && w[3].is_ascii_digit() && w[4].is_ascii_digit()
+        })
+}
+
+// 逐封读取邮件文件，避免把整个邮件spool读入内存。以 "From " 行开头的文件按 mbox
+// 拆分，其余按单封邮件 (.eml) 处理；超过大小上限的邮件只保留开头部分
+pub struct MailboxReader<R> {
+    reader: R,
+    max_message_size: usize,
+    is_mbox: Option<bool>,
+    seen_separator: bool,
+    finished: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> MailboxReader<R> {
+    pub fn new(reader: R, max_message_size: usize) -> Self {
+        Self {
+            reader,
+            max_message_size,
+            is_mbox: None,
+            seen_separator: false,
+            finished: false,
+        }
+    }
+
+    pub fn is_mbox(&self) -> bool {
+        self.is_mbox.unwrap_or(false)
+    }
+
+    pub async fn next_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let mut message = Vec::new();
+        let mut line = Vec::new();
+        let mut previous_blank = true;
+        let mut truncated = false;
+
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line).await? == 0 {
+                self.finished = true;
+                break;
+            }
+
+            let is_separator = is_mbox_separator(&line);
+            let is_mbox = *self.is_mbox.get_or_insert(is_separator);
+            if is_mbox && is_separator && previous_blank {
+                if self.seen_separator {
+                    break;
+                }
+                // 第一封邮件的分隔行
+                self.seen_separator = true;
+                continue;
+            }
+            previous_blank = line == b"\n" || line == b"\r\n";
+
+            // mboxrd 格式中正文里的 "From " 行被转义为 ">From "
+            let quoted = line.iter().take_while(|&&b| b == b'>').count();
+            let content = if is_mbox && quoted > 0 && line[quoted..].starts_with(b"From ") {
+                &line[1..]
+            } else {
+                &line[..]
+            };
+            if message.len() + content.len() > self.max_message_size {
+                truncated = true;
+                continue;
+            }
+            message.extend_from_slice(content);
+        }
+
+        if truncated {
+            log::debug!("邮件超过大小限制 {} 字节，仅扫描开头部分", self.max_message_size);
+        }
+        if message.is_empty() && self.finished {
+            return Ok(None);
+        }
+        Ok(Some(message))
+    }
+}
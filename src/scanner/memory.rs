@@ -0,0 +1,62 @@
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+// 信号量以 KB 为单位计数，避免大文件的字节数超出许可数量上限
+const UNIT: u64 = 1024;
+
+// 正在扫描的文件缓冲区的内存预算。工作线程在读取文件前预留其所需内存，
+// 预算不足时等待其他文件扫描完成，从而自动降低并发度
+pub struct MemoryBudget {
+    semaphore: Option<Semaphore>,
+    capacity: u64,
+}
+
+pub struct MemoryReservation<'a> {
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl MemoryBudget {
+    pub fn new(capacity: u64) -> Self {
+        let units = capacity.div_ceil(UNIT).min(Semaphore::MAX_PERMITS as u64) as usize;
+        Self {
+            semaphore: Some(Semaphore::new(units)),
+            capacity: units as u64 * UNIT,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            semaphore: None,
+            capacity: u64::MAX,
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.semaphore.is_some()
+    }
+
+    pub fn in_use(&self) -> u64 {
+        match &self.semaphore {
+            Some(semaphore) => self.capacity - semaphore.available_permits() as u64 * UNIT,
+            None => 0,
+        }
+    }
+
+    // 单个文件所需内存超过全部预算时返回 None，由调用方跳过该文件
+    pub async fn reserve(&self, bytes: u64) -> Option<MemoryReservation<'_>> {
+        let semaphore = match &self.semaphore {
+            Some(semaphore) => semaphore,
+            None => return Some(MemoryReservation { _permit: None }),
+        };
+        if bytes > self.capacity {
+            return None;
+        }
+
+        let units = u32::try_from(bytes.div_ceil(UNIT)).ok()?;
+        let permit = semaphore.acquire_many(units).await.ok()?;
+        Some(MemoryReservation { _permit: Some(permit) })
+    }
+}
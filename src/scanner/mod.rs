@@ -1,5 +1,43 @@
 pub mod engine;
+pub mod allowlist;
+pub mod archive;
+pub mod benchmark;
+pub mod checkpoint;
+pub mod custom;
+pub mod cvd;
+pub mod elf;
+pub mod exclusion;
+pub mod heuristics;
 mod database;
+pub mod image;
+pub mod logical;
+pub mod mail;
+pub mod memory;
+pub mod pdf;
+pub mod pe;
+pub mod persistence;
+pub mod rootkit;
+pub mod selftest;
+pub mod url;
+pub mod verdict_cache;
 
-pub use engine::{ScannerEngine, ScanOptions, ScanMode, ScanResult, ScanStats, ThreatType, RiskLevel, FileInfo};
-pub use database::{SignatureDatabase, Signature, PatternType, ThreatSignature};
+pub use engine::{ScannerEngine, ScanControl, ScanState, ScanOptions, ScanMode, ScanResult, ScanStats, ThreatType, RiskLevel, FileInfo};
+pub use database::{eicar_test_string, DatabaseFileCheck, EICAR_SIGNATURE_ID, HashAlgorithm, HashSignature, SignatureDatabase, SignatureSnapshot, Signature, SignatureSummary, PatternType, ThreatSignature};
+pub use allowlist::{AllowReason, Allowlist};
+pub use benchmark::{BenchmarkReport, BenchmarkRun};
+pub use checkpoint::ScanCheckpoint;
+pub use cvd::CvdHeader;
+pub use elf::{ElfFlag, ElfInfo};
+pub use exclusion::ExclusionRules;
+pub use heuristics::{HeuristicEngine, HeuristicVerdict, ScriptLanguage};
+pub use memory::MemoryBudget;
+pub use pdf::{PdfContents, PdfParser};
+pub use persistence::{audit_persistence, persistence_locations, PersistenceFinding, PersistenceKind};
+pub use rootkit::{RootkitCheck, RootkitChecker, RootkitFinding};
+pub use selftest::{SelftestCheck, SelftestReport};
+pub use image::{ImageDetection, ImageReference, ImageScanReport, ImageScanner};
+pub use url::{UrlScanReport, UrlScanner};
+pub use verdict_cache::VerdictCache;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,516 @@
+use regex::bytes::Regex;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::OnceLock;
+
+// 恶意 PDF 常常结构损坏，解析时不依赖交叉引用表，而是直接查找 "N G obj ... endobj"
+const MAX_OBJECTS: usize = 100_000;
+const MAX_FILTERS: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct PdfScript {
+    pub object: u32,
+    pub code: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PdfEmbeddedFile {
+    pub object: u32,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PdfLaunchAction {
+    pub object: u32,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PdfContents {
+    pub scripts: Vec<PdfScript>,
+    pub launch_actions: Vec<PdfLaunchAction>,
+    pub embedded_files: Vec<PdfEmbeddedFile>,
+    // 文档打开或页面事件时自动触发动作 (/OpenAction, /AA)
+    pub auto_action: bool,
+    // 解压总量达到上限，之后的流未解码
+    pub truncated: bool,
+}
+
+impl PdfContents {
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty() && self.launch_actions.is_empty() && self.embedded_files.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PdfObject {
+    dict: Vec<u8>,
+    stream: Option<Vec<u8>>,
+}
+
+fn object_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(\d{1,10})\s+\d{1,5}\s+obj\b").unwrap())
+}
+
+fn name_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"/[^\s/<>\[\]()%]+").unwrap())
+}
+
+fn js_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"/JS\s*(?:(\d+)\s+\d+\s+R|([(<]))").unwrap())
+}
+
+fn embedded_ref_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"/EF\s*<<[^>]*?/(?:UF|F)\s*(\d+)\s+\d+\s+R").unwrap())
+}
+
+fn file_name_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"/(?:UF|F)\s*\(").unwrap())
+}
+
+pub struct PdfParser {
+    remaining: u64,
+    truncated: bool,
+}
+
+impl PdfParser {
+    pub fn new(max_decompressed_size: u64) -> Self {
+        Self {
+            remaining: max_decompressed_size,
+            truncated: false,
+        }
+    }
+
+    pub fn parse(mut self, data: &[u8]) -> PdfContents {
+        let objects = self.read_objects(data);
+        let mut contents = PdfContents::default();
+
+        // 嵌入文件的文件名记录在引用它的文件规范 (/Filespec) 字典中
+        let mut embedded_names = HashMap::new();
+        for object in objects.values() {
+            for caps in embedded_ref_pattern().captures_iter(&object.dict) {
+                if let Some(id) = parse_u32(&caps[1]) {
+                    embedded_names.insert(id, file_spec_name(&object.dict).unwrap_or_default());
+                }
+            }
+        }
+
+        let mut ids: Vec<u32> = objects.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let object = &objects[&id];
+            let dict = &object.dict;
+
+            if has_key(dict, b"/OpenAction") || has_key(dict, b"/AA") {
+                contents.auto_action = true;
+            }
+
+            for caps in js_pattern().captures_iter(dict) {
+                let code = match (caps.get(1), caps.get(2)) {
+                    (Some(reference), _) => parse_u32(reference.as_bytes())
+                        .and_then(|target| objects.get(&target))
+                        .and_then(|target| target.stream.clone().or_else(|| first_string(&target.dict))),
+                    (_, Some(start)) => parse_string(&dict[start.start()..]).map(|(s, _)| s),
+                    _ => None,
+                };
+                if let Some(code) = code.filter(|c| !c.is_empty()) {
+                    contents.scripts.push(PdfScript { object: id, code: decode_text_string(code) });
+                }
+            }
+
+            if is_launch_action(dict) {
+                contents.launch_actions.push(PdfLaunchAction {
+                    object: id,
+                    target: launch_target(dict),
+                });
+            }
+
+            let is_embedded = embedded_names.contains_key(&id) || has_name_value(dict, b"/Type", b"/EmbeddedFile");
+            if let (true, Some(stream)) = (is_embedded, &object.stream) {
+                let name = embedded_names.get(&id).filter(|n| !n.is_empty()).cloned()
+                    .unwrap_or_else(|| format!("embedded-{}", id));
+                contents.embedded_files.push(PdfEmbeddedFile {
+                    object: id,
+                    name,
+                    data: stream.clone(),
+                });
+            }
+        }
+
+        contents.truncated = self.truncated;
+        contents
+    }
+
+    fn read_objects(&mut self, data: &[u8]) -> HashMap<u32, PdfObject> {
+        let mut objects = HashMap::new();
+        let headers: Vec<(u32, usize, usize)> = object_pattern()
+            .captures_iter(data)
+            .take(MAX_OBJECTS)
+            .filter_map(|caps| {
+                let header = caps.get(0)?;
+                Some((parse_u32(&caps[1])?, header.start(), header.end()))
+            })
+            .collect();
+
+        for (i, &(id, _, start)) in headers.iter().enumerate() {
+            let limit = headers.get(i + 1).map_or(data.len(), |&(_, next, _)| next.max(start));
+            let body = &data[start..limit];
+            let body = &body[..find(body, b"endobj").unwrap_or(body.len())];
+
+            let (dict, raw_stream) = split_stream(body);
+            let dict = normalize_names(dict);
+            let stream = raw_stream.map(|raw| self.decode_stream(&dict, raw));
+
+            // 对象流 (/ObjStm) 中压缩存放了其他对象，解开后按普通对象处理
+            if let (true, Some(decoded)) = (has_name_value(&dict, b"/Type", b"/ObjStm"), &stream) {
+                for (inner_id, inner_dict) in split_object_stream(&dict, decoded) {
+                    objects.insert(inner_id, PdfObject {
+                        dict: normalize_names(&inner_dict),
+                        stream: None,
+                    });
+                }
+            }
+
+            // 增量更新中同号对象以后出现的为准
+            objects.insert(id, PdfObject { dict, stream });
+        }
+
+        objects
+    }
+
+    // 不支持的过滤器保留原始数据，签名仍可匹配未压缩的内容
+    fn decode_stream(&mut self, dict: &[u8], raw: &[u8]) -> Vec<u8> {
+        let mut data = raw.to_vec();
+        for filter in stream_filters(dict).into_iter().take(MAX_FILTERS) {
+            let decoded = match filter.as_str() {
+                "FlateDecode" | "Fl" => self.inflate(&data),
+                "ASCIIHexDecode" | "AHx" => Some(decode_hex_string(&data)),
+                _ => {
+                    log::debug!("不支持的 PDF 流过滤器: {}", filter);
+                    None
+                }
+            };
+            match decoded {
+                Some(decoded) => data = decoded,
+                None => break,
+            }
+        }
+        data
+    }
+
+    fn inflate(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        if self.truncated {
+            return None;
+        }
+
+        // 恶意文件常带有损坏的校验和，尽量保留已解出的部分
+        let mut decoded = Vec::new();
+        let mut reader = flate2::read::ZlibDecoder::new(data).take(self.remaining.saturating_add(1));
+        if let Err(e) = reader.read_to_end(&mut decoded) {
+            if decoded.is_empty() {
+                log::debug!("无法解压 PDF 流: {}", e);
+                return None;
+            }
+        }
+
+        if decoded.len() as u64 > self.remaining {
+            log::warn!("PDF 流解压大小超过限制，停止解压后续内容");
+            self.truncated = true;
+            self.remaining = 0;
+            return None;
+        }
+        self.remaining -= decoded.len() as u64;
+        Some(decoded)
+    }
+}
+
+fn split_stream(body: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let keyword = match find(body, b"stream") {
+        Some(pos) if !body[..pos].ends_with(b"end") => pos,
+        _ => return (body, None),
+    };
+
+    let mut start = keyword + b"stream".len();
+    if body[start..].starts_with(b"\r\n") {
+        start += 2;
+    } else if body[start..].starts_with(b"\n") || body[start..].starts_with(b"\r") {
+        start += 1;
+    }
+    let end = rfind(&body[start..], b"endstream").map_or(body.len(), |pos| start + pos);
+    let mut stream = &body[start..end];
+    if let Some(trimmed) = stream.strip_suffix(b"\n") {
+        stream = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+    } else if let Some(trimmed) = stream.strip_suffix(b"\r") {
+        stream = trimmed;
+    }
+    (&body[..keyword], Some(stream))
+}
+
+// 对象流开头为 "对象号 偏移" 整数对，/First 指出第一个对象的位置
+fn split_object_stream(dict: &[u8], data: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let count = dict_integer(dict, b"/N").unwrap_or(0) as usize;
+    let first = dict_integer(dict, b"/First").unwrap_or(0) as usize;
+    if first > data.len() {
+        return Vec::new();
+    }
+
+    let numbers: Vec<usize> = String::from_utf8_lossy(&data[..first])
+        .split_ascii_whitespace()
+        .filter_map(|n| n.parse().ok())
+        .take(count.min(MAX_OBJECTS) * 2)
+        .collect();
+    let entries: Vec<(usize, usize)> = numbers.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &(id, offset))| {
+            let start = first.checked_add(offset)?;
+            let end = entries.get(i + 1).map_or(data.len(), |&(_, next)| first.saturating_add(next));
+            let body = data.get(start..end.min(data.len()))?;
+            Some((u32::try_from(id).ok()?, body.to_vec()))
+        })
+        .collect()
+}
+
+// 名称中允许用 #xx 转义任意字符，恶意文件借此把 /JavaScript 写成 /J#61vaScript 躲避检测
+fn normalize_names(dict: &[u8]) -> Vec<u8> {
+    name_pattern()
+        .replace_all(dict, |caps: &regex::bytes::Captures| {
+            let name = &caps[0];
+            let mut decoded = Vec::with_capacity(name.len());
+            let mut i = 0;
+            while i < name.len() {
+                if name[i] == b'#' && i + 2 < name.len() {
+                    if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&name[i + 1..i + 3]), 16) {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                }
+                decoded.push(name[i]);
+                i += 1;
+            }
+            decoded
+        })
+        .into_owned()
+}
+
+fn stream_filters(dict: &[u8]) -> Vec<String> {
+    let pos = match find(dict, b"/Filter") {
+        Some(pos) => pos + b"/Filter".len(),
+        None => return Vec::new(),
+    };
+    let rest = trim_start(&dict[pos..]);
+    let value = if rest.starts_with(b"[") {
+        &rest[1..find(rest, b"]").unwrap_or(rest.len())]
+    } else {
+        let end = rest.iter().skip(1).position(|&b| b == b'/' || b == b'>' || b.is_ascii_whitespace())
+            .map_or(rest.len(), |p| p + 1);
+        &rest[..end]
+    };
+
+    String::from_utf8_lossy(value)
+        .split(|c: char| c == '/' || c.is_ascii_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn is_launch_action(dict: &[u8]) -> bool {
+    has_name_value(dict, b"/S", b"/Launch")
+}
+
+// /Launch 的目标可能在 /F 或 Windows 专用的 /Win << /F /P >> 中，这里收集其中的全部字符串
+fn launch_target(dict: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut rest = dict;
+    while let Some(pos) = rest.iter().position(|&b| b == b'(') {
+        match parse_string(&rest[pos..]) {
+            Some((value, consumed)) => {
+                if !value.is_empty() {
+                    parts.push(String::from_utf8_lossy(&value).to_string());
+                }
+                rest = &rest[pos + consumed..];
+            }
+            None => break,
+        }
+    }
+    parts.join(" ")
+}
+
+fn file_spec_name(dict: &[u8]) -> Option<String> {
+    let found = file_name_pattern().find(dict)?;
+    let (name, _) = parse_string(&dict[found.end() - 1..])?;
+    Some(String::from_utf8_lossy(&name).to_string())
+}
+
+fn first_string(dict: &[u8]) -> Option<Vec<u8>> {
+    let pos = dict.iter().position(|&b| b == b'(' || b == b'<')?;
+    parse_string(&dict[pos..]).map(|(s, _)| s)
+}
+
+// 解析字面量字符串 (...) 或十六进制字符串 <...>，返回内容和消耗的字节数
+fn parse_string(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+    match data.first()? {
+        b'(' => parse_literal_string(data),
+        b'<' if data.get(1) != Some(&b'<') => {
+            let end = data.iter().position(|&b| b == b'>')?;
+            Some((decode_hex_string(&data[1..end]), end + 1))
+        }
+        _ => None,
+    }
+}
+
+fn parse_literal_string(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut value = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < data.len() {
+        let byte = data[i];
+        match byte {
+            b'(' => {
+                if depth > 0 {
+                    value.push(byte);
+                }
+                depth += 1;
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((value, i + 1));
+                }
+                value.push(byte);
+            }
+            b'\\' => {
+                i += 1;
+                let escaped = *data.get(i)?;
+                match escaped {
+                    b'n' => value.push(b'\n'),
+                    b'r' => value.push(b'\r'),
+                    b't' => value.push(b'\t'),
+                    b'b' => value.push(0x08),
+                    b'f' => value.push(0x0c),
+                    b'\r' | b'\n' => {
+                        if escaped == b'\r' && data.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'0'..=b'7' => {
+                        let digits = data[i..].iter().take(3).take_while(|b| (b'0'..=b'7').contains(*b)).count();
+                        let octal = std::str::from_utf8(&data[i..i + digits]).ok()?;
+                        value.push(u16::from_str_radix(octal, 8).ok()? as u8);
+                        i += digits - 1;
+                    }
+                    other => value.push(other),
+                }
+            }
+            _ => value.push(byte),
+        }
+        i += 1;
+    }
+
+    // 未闭合的字符串按已读取的内容返回
+    Some((value, data.len()))
+}
+
+// 以 FE FF 开头的文本字符串为 UTF-16BE 编码
+fn decode_text_string(data: Vec<u8>) -> Vec<u8> {
+    match data.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units).into_bytes()
+        }
+        None => data,
+    }
+}
+
+fn decode_hex_string(data: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = data
+        .iter()
+        .take_while(|&&b| b != b'>')
+        .filter(|b| b.is_ascii_hexdigit())
+        .copied()
+        .collect();
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let high = hex_value(pair[0]);
+            let low = pair.get(1).map_or(0, |&b| hex_value(b));
+            high << 4 | low
+        })
+        .collect()
+}
+
+fn hex_value(digit: u8) -> u8 {
+    (digit as char).to_digit(16).unwrap_or(0) as u8
+}
+
+fn has_key(dict: &[u8], key: &[u8]) -> bool {
+    let mut rest = dict;
+    while let Some(pos) = find(rest, key) {
+        let after = &rest[pos + key.len()..];
+        if !after.first().map_or(false, |b| b.is_ascii_alphanumeric()) {
+            return true;
+        }
+        rest = after;
+    }
+    false
+}
+
+fn has_name_value(dict: &[u8], key: &[u8], value: &[u8]) -> bool {
+    let mut rest = dict;
+    while let Some(pos) = find(rest, key) {
+        let after = &rest[pos + key.len()..];
+        // 避免 /S 匹配到 /Subtype 之类的前缀
+        if after.first().map_or(false, |b| b.is_ascii_alphanumeric()) {
+            rest = after;
+            continue;
+        }
+        let after = trim_start(after);
+        if after.starts_with(value) && !after.get(value.len()).map_or(false, |b| b.is_ascii_alphanumeric()) {
+            return true;
+        }
+        rest = after;
+    }
+    false
+}
+
+fn dict_integer(dict: &[u8], key: &[u8]) -> Option<u64> {
+    let mut rest = dict;
+    while let Some(pos) = find(rest, key) {
+        let after = &rest[pos + key.len()..];
+        if !after.first().map_or(false, |b| b.is_ascii_alphanumeric()) {
+            let after = trim_start(after);
+            let digits = after.iter().take_while(|b| b.is_ascii_digit()).count();
+            return std::str::from_utf8(&after[..digits]).ok()?.parse().ok();
+        }
+        rest = after;
+    }
+    None
+}
+
+fn parse_u32(digits: &[u8]) -> Option<u32> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn trim_start(data: &[u8]) -> &[u8] {
+    let start = data.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(data.len());
+    &data[start..]
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
@@ -0,0 +1,299 @@
+use std::os::unix::fs::FileExt;
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const IMPORT_DIRECTORY: u32 = 1;
+const IMPORT_DESCRIPTOR_SIZE: u64 = 20;
+const SECTION_HEADER_SIZE: u64 = 40;
+const MAX_NAME_LENGTH: usize = 512;
+
+// 防止畸形文件声明超大导入表导致长时间解析
+const MAX_IMPORT_LIBRARIES: usize = 4096;
+const MAX_IMPORTS_PER_LIBRARY: usize = 65536;
+const MAX_SECTIONS: u16 = 96;
+
+// pefile 为按序号导入的 Winsock 函数补全函数名，这里只收录 Winsock 1.1 的固定序号；
+// 其他序号 (包括 oleaut32) 按 ord<N> 计算，与 pefile 的结果可能不同
+const WINSOCK_ORDINALS: [(u16, &str); 47] = [
+    (1, "accept"),
+    (2, "bind"),
+    (3, "closesocket"),
+    (4, "connect"),
+    (5, "getpeername"),
+    (6, "getsockname"),
+    (7, "getsockopt"),
+    (8, "htonl"),
+    (9, "htons"),
+    (10, "ioctlsocket"),
+    (11, "inet_addr"),
+    (12, "inet_ntoa"),
+    (13, "listen"),
+    (14, "ntohl"),
+    (15, "ntohs"),
+    (16, "recv"),
+    (17, "recvfrom"),
+    (18, "select"),
+    (19, "send"),
+    (20, "sendto"),
+    (21, "setsockopt"),
+    (22, "shutdown"),
+    (23, "socket"),
+    (51, "gethostbyaddr"),
+    (52, "gethostbyname"),
+    (53, "getprotobyname"),
+    (54, "getprotobynumber"),
+    (55, "getservbyname"),
+    (56, "getservbyport"),
+    (57, "gethostname"),
+    (101, "WSAAsyncSelect"),
+    (102, "WSAAsyncGetHostByAddr"),
+    (103, "WSAAsyncGetHostByName"),
+    (104, "WSAAsyncGetProtoByNumber"),
+    (105, "WSAAsyncGetProtoByName"),
+    (106, "WSAAsyncGetServByPort"),
+    (107, "WSAAsyncGetServByName"),
+    (108, "WSACancelAsyncRequest"),
+    (109, "WSASetBlockingHook"),
+    (110, "WSAUnhookBlockingHook"),
+    (111, "WSAGetLastError"),
+    (112, "WSASetLastError"),
+    (113, "WSACancelBlockingCall"),
+    (114, "WSAIsBlocking"),
+    (115, "WSAStartup"),
+    (116, "WSACleanup"),
+    (151, "__WSAFDIsSet"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedFunction {
+    Name(String),
+    Ordinal(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct PeImport {
+    pub library: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+// 按偏移读取，内存中的文件和磁盘上的大文件共用同一套解析逻辑
+pub trait PeSource {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize;
+}
+
+impl PeSource for [u8] {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let Some(start) = usize::try_from(offset).ok().filter(|&o| o < self.len()) else {
+            return 0;
+        };
+        let n = buf.len().min(self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+        n
+    }
+}
+
+impl PeSource for std::fs::File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match FileExt::read_at(self, &mut buf[filled..], offset + filled as u64) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => filled += n,
+            }
+        }
+        filled
+    }
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+struct PeReader<'a, S: PeSource + ?Sized> {
+    source: &'a S,
+    is_64: bool,
+    size_of_headers: u32,
+    sections: Vec<Section>,
+}
+
+impl<'a, S: PeSource + ?Sized> PeReader<'a, S> {
+    fn parse(source: &'a S) -> Option<(Self, u32)> {
+        if read_u16(source, 0)? != 0x5a4d {
+            return None;
+        }
+        let pe_offset = read_u32(source, 0x3c)? as u64;
+        if read_u32(source, pe_offset)? != 0x0000_4550 {
+            return None;
+        }
+
+        let section_count = read_u16(source, pe_offset + 6)?.min(MAX_SECTIONS);
+        let optional_size = read_u16(source, pe_offset + 20)? as u64;
+        let optional = pe_offset + 24;
+        let is_64 = match read_u16(source, optional)? {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            _ => return None,
+        };
+        let (rva_count_offset, directories) = if is_64 { (108, 112) } else { (92, 96) };
+        if read_u32(source, optional + rva_count_offset)? <= IMPORT_DIRECTORY {
+            return None;
+        }
+        let import_rva = read_u32(source, optional + directories + IMPORT_DIRECTORY as u64 * 8)?;
+
+        let section_table = optional + optional_size;
+        let sections = (0..section_count as u64)
+            .filter_map(|i| {
+                let header = section_table + i * SECTION_HEADER_SIZE;
+                Some(Section {
+                    virtual_size: read_u32(source, header + 8)?,
+                    virtual_address: read_u32(source, header + 12)?,
+                    raw_size: read_u32(source, header + 16)?,
+                    raw_offset: read_u32(source, header + 20)?,
+                })
+            })
+            .collect();
+
+        let reader = Self {
+            source,
+            is_64,
+            size_of_headers: read_u32(source, optional + 60)?,
+            sections,
+        };
+        Some((reader, import_rva))
+    }
+
+    fn rva_to_offset(&self, rva: u32) -> Option<u64> {
+        let section = self.sections.iter().find(|s| {
+            let span = s.virtual_size.max(s.raw_size);
+            rva >= s.virtual_address && rva - s.virtual_address < span
+        });
+        match section {
+            Some(s) => Some(s.raw_offset as u64 + (rva - s.virtual_address) as u64),
+            None if rva < self.size_of_headers => Some(rva as u64),
+            None => None,
+        }
+    }
+
+    fn read_string(&self, rva: u32) -> Option<String> {
+        let mut buf = vec![0u8; MAX_NAME_LENGTH];
+        let n = self.source.read_at(self.rva_to_offset(rva)?, &mut buf);
+        let end = buf[..n].iter().position(|&b| b == 0)?;
+        let name = std::str::from_utf8(&buf[..end]).ok()?;
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    fn read_thunks(&self, rva: u32) -> Vec<ImportedFunction> {
+        let (width, ordinal_flag) = if self.is_64 { (8u64, 1u64 << 63) } else { (4u64, 1u64 << 31) };
+        let Some(start) = self.rva_to_offset(rva) else {
+            return Vec::new();
+        };
+
+        let mut functions = Vec::new();
+        for i in 0..MAX_IMPORTS_PER_LIBRARY as u64 {
+            let thunk = if self.is_64 {
+                read_u64(self.source, start + i * width)
+            } else {
+                read_u32(self.source, start + i * width).map(u64::from)
+            };
+            let thunk = match thunk {
+                Some(0) | None => break,
+                Some(thunk) => thunk,
+            };
+            if thunk & ordinal_flag != 0 {
+                functions.push(ImportedFunction::Ordinal(thunk as u16));
+            } else if let Some(name) = u32::try_from(thunk).ok().and_then(|hint| self.read_string(hint.checked_add(2)?)) {
+                functions.push(ImportedFunction::Name(name));
+            }
+        }
+        functions
+    }
+}
+
+// 解析导入表；不是 PE 文件或没有导入表时返回 None
+pub fn parse_imports<S: PeSource + ?Sized>(source: &S) -> Option<Vec<PeImport>> {
+    let (reader, import_rva) = PeReader::parse(source)?;
+    if import_rva == 0 {
+        return None;
+    }
+    let table = reader.rva_to_offset(import_rva)?;
+
+    let mut imports = Vec::new();
+    for i in 0..MAX_IMPORT_LIBRARIES as u64 {
+        let descriptor = table + i * IMPORT_DESCRIPTOR_SIZE;
+        let Some(original_thunk) = read_u32(source, descriptor) else {
+            break;
+        };
+        let name_rva = read_u32(source, descriptor + 12).unwrap_or(0);
+        let first_thunk = read_u32(source, descriptor + 16).unwrap_or(0);
+        if original_thunk == 0 && name_rva == 0 && first_thunk == 0 {
+            break;
+        }
+
+        let Some(library) = reader.read_string(name_rva) else {
+            continue;
+        };
+        let thunks = if original_thunk != 0 { original_thunk } else { first_thunk };
+        imports.push(PeImport {
+            library,
+            functions: reader.read_thunks(thunks),
+        });
+    }
+    (!imports.is_empty()).then_some(imports)
+}
+
+// 与 pefile 的 get_imphash 相同：小写的 "库名.函数名" 以逗号连接后取 MD5，
+// 库名去掉 .dll/.ocx/.sys 扩展名
+pub fn imphash<S: PeSource + ?Sized>(source: &S) -> Option<String> {
+    let imports = parse_imports(source)?;
+
+    let mut entries = Vec::new();
+    for import in &imports {
+        let library = import.library.to_lowercase();
+        let library = match library.rsplit_once('.') {
+            Some((stem, "dll" | "ocx" | "sys")) => stem.to_string(),
+            _ => library,
+        };
+        for function in &import.functions {
+            let name = match function {
+                ImportedFunction::Name(name) => name.to_lowercase(),
+                ImportedFunction::Ordinal(ordinal) => ordinal_name(&library, *ordinal)
+                    .map(str::to_lowercase)
+                    .unwrap_or_else(|| format!("ord{}", ordinal)),
+            };
+            entries.push(format!("{}.{}", library, name));
+        }
+    }
+    if entries.is_empty() {
+        return None;
+    }
+
+    let digest = openssl::hash::hash(openssl::hash::MessageDigest::md5(), entries.join(",").as_bytes()).ok()?;
+    Some(hex::encode(digest))
+}
+
+fn ordinal_name(library: &str, ordinal: u16) -> Option<&'static str> {
+    if !matches!(library, "ws2_32" | "wsock32") {
+        return None;
+    }
+    WINSOCK_ORDINALS.iter().find(|(o, _)| *o == ordinal).map(|(_, name)| *name)
+}
+
+fn read_bytes<const N: usize, S: PeSource + ?Sized>(source: &S, offset: u64) -> Option<[u8; N]> {
+    let mut buf = [0u8; N];
+    (source.read_at(offset, &mut buf) == N).then_some(buf)
+}
+
+fn read_u16<S: PeSource + ?Sized>(source: &S, offset: u64) -> Option<u16> {
+    read_bytes(source, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32<S: PeSource + ?Sized>(source: &S, offset: u64) -> Option<u32> {
+    read_bytes(source, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64<S: PeSource + ?Sized>(source: &S, offset: u64) -> Option<u64> {
+    read_bytes(source, offset).map(u64::from_le_bytes)
+}
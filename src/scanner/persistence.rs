@@ -0,0 +1,305 @@
+use crate::scanner::heuristics::{match_script_rules, HeuristicMatch};
+use crate::scanner::{RiskLevel, ScriptLanguage, ThreatType};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+const MAX_SCORE: u8 = 100;
+// 临时目录中最近出现的可执行文件更可能是刚投放的载荷
+const RECENT_BINARY_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+const SYSTEM_LOCATIONS: [&str; 20] = [
+    "/etc/crontab",
+    "/etc/cron.d",
+    "/etc/cron.hourly",
+    "/etc/cron.daily",
+    "/etc/cron.weekly",
+    "/etc/cron.monthly",
+    "/var/spool/cron",
+    "/etc/systemd/system",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+    "/run/systemd/system",
+    "/etc/rc.local",
+    "/etc/rc.d/rc.local",
+    "/etc/init.d",
+    "/etc/profile",
+    "/etc/profile.d",
+    "/etc/bash.bashrc",
+    "/etc/bashrc",
+    "/etc/zsh",
+    "/etc/environment",
+];
+
+const USER_LOCATIONS: [&str; 9] = [
+    ".bashrc",
+    ".bash_profile",
+    ".bash_login",
+    ".bash_logout",
+    ".profile",
+    ".zshrc",
+    ".zprofile",
+    ".config/systemd/user",
+    ".ssh",
+];
+
+const SHELL_PROFILES: [&str; 12] = [
+    ".bashrc",
+    ".bash_profile",
+    ".bash_login",
+    ".bash_logout",
+    ".profile",
+    ".zshrc",
+    ".zprofile",
+    ".zshenv",
+    "profile",
+    "bash.bashrc",
+    "bashrc",
+    "environment",
+];
+
+const UNIT_EXTENSIONS: [&str; 5] = ["service", "timer", "socket", "path", "target"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistenceKind {
+    Cron,
+    SystemdUnit,
+    RcScript,
+    ShellProfile,
+    AuthorizedKeys,
+}
+
+impl PersistenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PersistenceKind::Cron => "Cron",
+            PersistenceKind::SystemdUnit => "SystemdUnit",
+            PersistenceKind::RcScript => "RcScript",
+            PersistenceKind::ShellProfile => "ShellProfile",
+            PersistenceKind::AuthorizedKeys => "AuthorizedKeys",
+        }
+    }
+
+    // 按路径中的目录和文件名判断，不要求位于根目录下，便于审计挂载的其他系统
+    pub fn classify(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let parent = path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()).unwrap_or_default();
+        let in_dir = |dir: &str| path.parent().is_some_and(|p| p.components().any(|c| c.as_os_str() == dir));
+
+        if matches!(name, "authorized_keys" | "authorized_keys2") && parent == ".ssh" {
+            return Some(PersistenceKind::AuthorizedKeys);
+        }
+        if name == "crontab" || parent.starts_with("cron.") || in_dir("crontabs") || (parent == "cron" && in_dir("spool")) {
+            return Some(PersistenceKind::Cron);
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        if UNIT_EXTENSIONS.contains(&extension) && in_dir("systemd") {
+            return Some(PersistenceKind::SystemdUnit);
+        }
+        if name == "rc.local" || parent == "init.d" {
+            return Some(PersistenceKind::RcScript);
+        }
+        if SHELL_PROFILES.contains(&name) || parent == "profile.d" || parent == "zsh" {
+            return Some(PersistenceKind::ShellProfile);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PersistenceFinding {
+    pub kind: PersistenceKind,
+    pub score: u8,
+    pub matches: Vec<(usize, HeuristicMatch)>,
+}
+
+impl PersistenceFinding {
+    pub fn detection_name(&self) -> String {
+        let rule = self
+            .matches
+            .iter()
+            .max_by_key(|(_, m)| m.score)
+            .map(|(_, m)| m.rule)
+            .unwrap_or("generic");
+        format!("Persistence.{}.{}", self.kind.as_str(), rule)
+    }
+
+    // 反弹 shell 和下载执行属于攻击工具，其余按未知威胁报告
+    pub fn threat_type(&self) -> ThreatType {
+        if self
+            .matches
+            .iter()
+            .any(|(_, m)| matches!(m.rule, "reverse-shell" | "download-exec"))
+        {
+            ThreatType::HackTool
+        } else {
+            ThreatType::Unknown
+        }
+    }
+
+    pub fn risk_level(&self) -> RiskLevel {
+        if self.score >= 60 {
+            RiskLevel::High
+        } else {
+            RiskLevel::Medium
+        }
+    }
+}
+
+// 持久化扫描的默认位置：系统级的 cron、systemd、rc 脚本和 shell 配置，
+// 以及 root 和 /home 下每个用户的 shell 配置、用户级 systemd 单元和 authorized_keys
+pub fn persistence_locations() -> Vec<PathBuf> {
+    let mut homes = vec![PathBuf::from("/root")];
+    if let Ok(entries) = std::fs::read_dir("/home") {
+        homes.extend(entries.flatten().map(|e| e.path()));
+    }
+
+    SYSTEM_LOCATIONS
+        .iter()
+        .map(PathBuf::from)
+        .chain(homes.iter().flat_map(|home| USER_LOCATIONS.iter().map(move |p| home.join(p))))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+// 逐条检查持久化配置中会被执行的命令，每个文件只返回一个汇总结果
+pub fn audit_persistence(kind: PersistenceKind, data: &[u8]) -> Option<PersistenceFinding> {
+    let text = String::from_utf8_lossy(data);
+    let mut matches: Vec<(usize, HeuristicMatch)> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let Some(command) = entry_command(kind, line.trim()) else {
+            continue;
+        };
+        for m in check_command(&command) {
+            if !matches.iter().any(|(_, existing)| existing.rule == m.rule) {
+                matches.push((index + 1, m));
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        return None;
+    }
+    let score = matches
+        .iter()
+        .fold(0u8, |total, (_, m)| total.saturating_add(m.score))
+        .min(MAX_SCORE);
+    Some(PersistenceFinding { kind, score, matches })
+}
+
+// 取出配置行中会被执行的部分；authorized_keys 只检查 command= 和 environment= 选项
+fn entry_command(kind: PersistenceKind, line: &str) -> Option<String> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    match kind {
+        PersistenceKind::SystemdUnit => {
+            let (key, value) = line.split_once('=')?;
+            key.trim().starts_with("Exec").then(|| value.trim().to_string())
+        }
+        // 去掉调度字段，系统 crontab 中还有一列用户名
+        PersistenceKind::Cron => match cron_schedule().captures(line) {
+            Some(entry) => {
+                let command = entry.get(1)?.as_str();
+                let stripped = command
+                    .split_once(char::is_whitespace)
+                    .filter(|(user, rest)| is_system_user(user) && rest.trim_start().starts_with(['/', '.', '(']))
+                    .map(|(_, rest)| rest.trim_start());
+                Some(stripped.unwrap_or(command).to_string())
+            }
+            None => Some(line.to_string()),
+        },
+        PersistenceKind::AuthorizedKeys => {
+            let options = authorized_key_options().captures(line)?;
+            Some(options.get(1)?.as_str().to_string())
+        }
+        _ => Some(line.to_string()),
+    }
+}
+
+fn check_command(command: &str) -> Vec<HeuristicMatch> {
+    let mut matches = match_script_rules(ScriptLanguage::Shell, command.as_bytes());
+
+    if let Some(found) = obfuscation_pattern().find(command) {
+        matches.push(HeuristicMatch {
+            rule: "obfuscated-command",
+            description: format!("命令经过混淆: {}", found.as_str()),
+            score: 40,
+        });
+    }
+    if let Some(option) = ssh_environment_option().find(command) {
+        matches.push(HeuristicMatch {
+            rule: "ssh-environment",
+            description: format!("authorized_keys 设置了环境变量: {}", option.as_str()),
+            score: 30,
+        });
+    }
+
+    for found in temp_path_pattern().captures_iter(command) {
+        let target = Path::new(&found[1]);
+        let recent = std::fs::metadata(target)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age <= RECENT_BINARY_AGE);
+        let (rule, score, description) = if recent {
+            ("temp-new-executable", 60, format!("执行临时目录中最近投放的文件: {:?}", target))
+        } else {
+            ("temp-executable", 40, format!("执行临时目录中的文件: {:?}", target))
+        };
+        if !matches.iter().any(|m| m.rule == rule) {
+            matches.push(HeuristicMatch { rule, description, score });
+        }
+    }
+
+    matches
+}
+
+fn is_system_user(name: &str) -> bool {
+    static USERS: OnceLock<Vec<String>> = OnceLock::new();
+    USERS
+        .get_or_init(|| {
+            std::fs::read_to_string("/etc/passwd")
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|line| line.split(':').next())
+                .map(str::to_string)
+                .collect()
+        })
+        .iter()
+        .any(|user| user == name)
+        || name == "root"
+}
+
+fn cron_schedule() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(?:@\w+|[\d*][\d*/,-]*(?:\s+[\w*/,-]+){4})\s+(.+)$").unwrap())
+}
+
+// 只匹配处于命令位置的临时目录路径，忽略 TMPDIR=/tmp/x 或 rm /tmp/x 这类引用
+fn temp_path_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?:^|[;|&(`]\s*|\$\(\s*|-c\s+['"]?|command="\s*|\b(?:sh|bash|nohup|exec|sudo|setsid|source|python3?|perl)\s+|(?:^|\s)\.\s+)((?:/tmp|/var/tmp|/dev/shm|/run/shm)/[^\s;|&'")`]+)"#).unwrap()
+    })
+}
+
+// 连续的 \x 转义、${IFS} 拼接和 rev/tr 还原的命令
+fn obfuscation_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)((\\x[0-9a-f]{2}){8,}|\$\{IFS\}|\|\s*rev\s*\|\s*(ba)?sh\b|\btr\s+['\x22]?[a-z]-[a-z][a-z]-[a-z]['\x22]?[^|]*\|\s*(ba)?sh\b)").unwrap()
+    })
+}
+
+fn ssh_environment_option() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"environment="[^"]*""#).unwrap())
+}
+
+fn authorized_key_options() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"^((?:[\w-]+(?:="(?:[^"\\]|\\.)*")?,?)*?(?:command|environment)="(?:[^"\\]|\\.)*"[^ ]*)\s"#).unwrap())
+}
@@ -0,0 +1,281 @@
+use crate::scanner::RiskLevel;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_PID_MAX: u32 = 32768;
+const PID_MAX_LIMIT: u32 = 4 * 1024 * 1024;
+
+// 正常的预加载库只会放在系统库目录下
+const TRUSTED_LIBRARY_DIRS: [&str; 6] = ["/lib", "/lib64", "/usr/lib", "/usr/lib64", "/usr/local/lib", "/opt"];
+const WORLD_WRITABLE_DIRS: [&str; 4] = ["/tmp", "/var/tmp", "/dev/shm", "/run/shm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RootkitCheck {
+    HiddenProcess,
+    HiddenModule,
+    LdPreload,
+    LdSoPreload,
+}
+
+impl RootkitCheck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RootkitCheck::HiddenProcess => "hidden-process",
+            RootkitCheck::HiddenModule => "hidden-module",
+            RootkitCheck::LdPreload => "ld-preload",
+            RootkitCheck::LdSoPreload => "ld-so-preload",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootkitFinding {
+    pub check: RootkitCheck,
+    pub risk_level: RiskLevel,
+    pub description: String,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+// 通过对比 /proc、/sys 与系统调用的结果发现被隐藏的进程和内核模块，
+// 并检查动态链接器的预加载配置
+pub struct RootkitChecker {
+    proc_dir: PathBuf,
+    sys_module_dir: PathBuf,
+    ld_so_preload: PathBuf,
+}
+
+impl Default for RootkitChecker {
+    fn default() -> Self {
+        Self::new(PathBuf::from("/proc"), PathBuf::from("/sys/module"), PathBuf::from("/etc/ld.so.preload"))
+    }
+}
+
+impl RootkitChecker {
+    pub fn new(proc_dir: PathBuf, sys_module_dir: PathBuf, ld_so_preload: PathBuf) -> Self {
+        Self {
+            proc_dir,
+            sys_module_dir,
+            ld_so_preload,
+        }
+    }
+
+    pub fn run(&self) -> Vec<RootkitFinding> {
+        let mut findings = Vec::new();
+        // 进程探测依赖本机系统调用，只对真实的 /proc 有意义
+        if self.proc_dir == Path::new("/proc") {
+            findings.extend(self.check_hidden_processes());
+        }
+        findings.extend(self.check_hidden_modules());
+        findings.extend(self.check_ld_preload());
+        findings.extend(self.check_ld_so_preload());
+        findings
+    }
+
+    fn check_hidden_processes(&self) -> Vec<RootkitFinding> {
+        let pid_max = std::fs::read_to_string(self.proc_dir.join("sys/kernel/pid_max"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(DEFAULT_PID_MAX)
+            .min(PID_MAX_LIMIT);
+
+        let listed = self.list_pids();
+        let alive = (1..=pid_max).filter(|&pid| process_exists(pid));
+        let candidates = find_hidden_pids(&listed, alive, |pid| self.read_tgid(pid));
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+
+        // 重新读取目录，排除探测期间新启动的进程
+        let listed = self.list_pids();
+        candidates
+            .into_iter()
+            .filter(|pid| !listed.contains(pid) && process_exists(*pid))
+            .map(|pid| {
+                let comm = std::fs::read_to_string(self.proc_dir.join(pid.to_string()).join("comm"))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_default();
+                RootkitFinding {
+                    check: RootkitCheck::HiddenProcess,
+                    risk_level: RiskLevel::Critical,
+                    description: format!("进程 {} ({}) 存在但未出现在 /proc 目录列表中", pid, comm),
+                    path: std::fs::read_link(self.proc_dir.join(pid.to_string()).join("exe")).ok(),
+                }
+            })
+            .collect()
+    }
+
+    fn list_pids(&self) -> HashSet<u32> {
+        std::fs::read_dir(&self.proc_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| e.file_name().to_str().and_then(|name| name.parse().ok()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn read_tgid(&self, pid: u32) -> Option<u32> {
+        let status = std::fs::read_to_string(self.proc_dir.join(pid.to_string()).join("status")).ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Tgid:"))
+            .and_then(|tgid| tgid.trim().parse().ok())
+    }
+
+    // 已加载的模块在 /sys/module 下有 initstate，内置模块没有
+    fn check_hidden_modules(&self) -> Vec<RootkitFinding> {
+        let Ok(proc_modules) = std::fs::read_to_string(self.proc_dir.join("modules")) else {
+            return Vec::new();
+        };
+        let listed: HashSet<String> = proc_modules
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string)
+            .collect();
+        let Ok(entries) = std::fs::read_dir(&self.sys_module_dir) else {
+            return Vec::new();
+        };
+        let loaded: HashSet<String> = entries
+            .flatten()
+            .filter(|e| e.path().join("initstate").exists())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .collect();
+
+        let mut findings = Vec::new();
+        for name in loaded.difference(&listed) {
+            findings.push(RootkitFinding {
+                check: RootkitCheck::HiddenModule,
+                risk_level: RiskLevel::Critical,
+                description: format!("内核模块 {} 已加载但未出现在 /proc/modules 中", name),
+                path: Some(self.sys_module_dir.join(name)),
+            });
+        }
+        for name in listed.difference(&loaded) {
+            findings.push(RootkitFinding {
+                check: RootkitCheck::HiddenModule,
+                risk_level: RiskLevel::High,
+                description: format!("内核模块 {} 未出现在 {:?} 中", name, self.sys_module_dir),
+                path: None,
+            });
+        }
+        findings.sort_by(|a, b| a.description.cmp(&b.description));
+        findings
+    }
+
+    // 无权读取的进程环境变量直接跳过
+    fn check_ld_preload(&self) -> Vec<RootkitFinding> {
+        let mut libraries: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        let mut pids: Vec<u32> = self.list_pids().into_iter().collect();
+        pids.sort_unstable();
+        for pid in pids {
+            let Ok(environ) = std::fs::read(self.proc_dir.join(pid.to_string()).join("environ")) else {
+                continue;
+            };
+            let Some(value) = environ
+                .split(|&b| b == 0)
+                .find_map(|var| var.strip_prefix(b"LD_PRELOAD="))
+            else {
+                continue;
+            };
+            for library in parse_preload_list(&String::from_utf8_lossy(value)) {
+                libraries.entry(library).or_default().push(pid);
+            }
+        }
+
+        libraries
+            .into_iter()
+            .filter_map(|(library, pids)| {
+                let reason = suspicious_library(Path::new(&library))?;
+                Some(RootkitFinding {
+                    check: RootkitCheck::LdPreload,
+                    risk_level: RiskLevel::High,
+                    description: format!("{} 个进程通过 LD_PRELOAD 加载了{}库 {} (PID: {:?})", pids.len(), reason, library, pids),
+                    path: Some(PathBuf::from(library)),
+                })
+            })
+            .collect()
+    }
+
+    // /etc/ld.so.preload 会注入到所有动态链接的程序中，正常系统几乎不使用
+    fn check_ld_so_preload(&self) -> Vec<RootkitFinding> {
+        let Ok(content) = std::fs::read_to_string(&self.ld_so_preload) else {
+            return Vec::new();
+        };
+        let content: String = content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        parse_preload_list(&content)
+            .into_iter()
+            .map(|library| {
+                let (risk_level, reason) = match suspicious_library(Path::new(&library)) {
+                    Some(reason) => (RiskLevel::Critical, reason),
+                    None => (RiskLevel::Medium, ""),
+                };
+                RootkitFinding {
+                    check: RootkitCheck::LdSoPreload,
+                    risk_level,
+                    description: format!("{:?} 预加载了{}库 {}", self.ld_so_preload, reason, library),
+                    path: Some(PathBuf::from(library)),
+                }
+            })
+            .collect()
+    }
+}
+
+// 在系统调用中存在、目录列表中却缺失的线程组主进程即为隐藏进程
+pub fn find_hidden_pids(
+    listed: &HashSet<u32>,
+    alive: impl Iterator<Item = u32>,
+    tgid: impl Fn(u32) -> Option<u32>,
+) -> Vec<u32> {
+    alive
+        .filter(|pid| !listed.contains(pid))
+        .filter(|&pid| tgid(pid) == Some(pid))
+        .collect()
+}
+
+fn process_exists(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // 信号 0 只检查进程是否存在，EPERM 说明进程存在但属于其他用户
+    unsafe { libc::kill(pid, 0) == 0 || *libc::__errno_location() == libc::EPERM }
+}
+
+fn parse_preload_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// 不含 / 的库名由链接器在系统库目录中查找
+fn suspicious_library(path: &Path) -> Option<&'static str> {
+    if !path.to_string_lossy().contains('/') {
+        return None;
+    }
+    if WORLD_WRITABLE_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return Some("临时目录中的");
+    }
+    if path
+        .components()
+        .any(|c| matches!(c, Component::Normal(name) if name.to_string_lossy().starts_with('.')))
+    {
+        return Some("隐藏路径下的");
+    }
+    if !path.exists() {
+        return Some("不存在的");
+    }
+    if !TRUSTED_LIBRARY_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return Some("非系统目录中的");
+    }
+    None
+}
@@ -0,0 +1,98 @@
+use crate::config::DetectionAction;
+use crate::core::security::QuarantineManager;
+use crate::scanner::{eicar_test_string, ScanMode, ScanOptions, ScannerEngine, SignatureDatabase, EICAR_SIGNATURE_ID};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelftestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelftestReport {
+    pub checks: Vec<SelftestCheck>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        !self.checks.is_empty() && self.checks.iter().all(|c| c.passed)
+    }
+
+    fn record(&mut self, name: &str, passed: bool, detail: impl Into<String>) {
+        self.checks.push(SelftestCheck {
+            name: name.to_string(),
+            passed,
+            detail: detail.into(),
+        });
+    }
+}
+
+// 在临时目录写入 EICAR 测试文件，用部署的扫描参数和隔离区依次验证检测与隔离。
+// 测试文件隔离后立即从隔离区删除，不留下痕迹
+pub async fn run_selftest(
+    signature_db: Arc<SignatureDatabase>,
+    options: ScanOptions,
+    quarantine: &QuarantineManager,
+) -> Result<SelftestReport, anyhow::Error> {
+    let mut report = SelftestReport::default();
+
+    signature_db.load_builtin_signatures().await?;
+
+    let workspace = tempfile::Builder::new().prefix("virus-scanner-selftest").tempdir()?;
+    let test_file = workspace.path().join("eicar.com");
+    let eicar = eicar_test_string();
+    std::fs::write(&test_file, &eicar)?;
+
+    // 只扫描测试目录，排除规则和检测后处理由自检自行控制
+    let options = ScanOptions {
+        scan_mode: ScanMode::Custom,
+        custom_paths: vec![workspace.path().to_path_buf()],
+        exclude_paths: vec![],
+        exclude_extensions: vec![],
+        priority_paths: vec![],
+        use_xattr_markers: false,
+        max_duration: None,
+        action: DetectionAction::Report,
+        auto_quarantine_min_risk: None,
+        ..options
+    };
+    let engine = ScannerEngine::new(signature_db, options);
+    let detected = match engine.start_scan().await {
+        Ok(results) => results.iter().any(|r| r.signature_id == EICAR_SIGNATURE_ID),
+        Err(e) => {
+            report.record("检测", false, format!("扫描失败: {}", e));
+            return Ok(report);
+        }
+    };
+    if !detected {
+        report.record("检测", false, "未检测到 EICAR 测试文件");
+        return Ok(report);
+    }
+    report.record("检测", true, format!("检测到 {}", EICAR_SIGNATURE_ID));
+
+    match quarantine.quarantine_file(&test_file).await {
+        Ok(stored) => {
+            let content = std::fs::read(&stored).unwrap_or_default();
+            let plaintext = content.windows(eicar.len()).any(|w| w == eicar.as_slice());
+            let (passed, detail) = if test_file.exists() || content.is_empty() {
+                (false, "隔离后文件状态异常".to_string())
+            } else if quarantine.is_encrypted() && plaintext {
+                (false, "隔离文件未加密".to_string())
+            } else {
+                let mode = if quarantine.is_encrypted() { "已加密" } else { "未加密" };
+                (true, format!("已隔离到 {:?} ({})", stored.parent().unwrap_or(&stored), mode))
+            };
+            report.record("隔离", passed, detail);
+
+            if let Err(e) = quarantine.delete_quarantined(&stored) {
+                log::warn!("无法删除自检隔离文件 {:?}: {}", stored, e);
+            }
+        }
+        Err(e) => report.record("隔离", false, format!("隔离失败: {}", e)),
+    }
+
+    Ok(report)
+}
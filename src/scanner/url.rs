@@ -0,0 +1,153 @@
+use crate::config::{DetectionAction, UrlScanConfig};
+use crate::scanner::{ScanOptions, ScanResult, ScannerEngine, SignatureDatabase};
+use anyhow::Context;
+use reqwest::Url;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const MAX_REDIRECTS: usize = 5;
+const DEFAULT_FILE_NAME: &str = "download";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UrlScanReport {
+    pub url: String,
+    // 跟随重定向后的实际地址
+    pub final_url: String,
+    pub content_type: Option<String>,
+    pub size: u64,
+    pub sha256: String,
+    pub results: Vec<ScanResult>,
+}
+
+impl UrlScanReport {
+    pub fn is_infected(&self) -> bool {
+        !self.results.is_empty()
+    }
+}
+
+// 把远程对象下载到临时目录后扫描，供只有 URL 的网关集成使用。
+// 始终校验 TLS 证书，超过大小上限的对象不会写入磁盘
+pub struct UrlScanner {
+    signature_db: Arc<SignatureDatabase>,
+    options: ScanOptions,
+    client: reqwest::Client,
+    max_size: u64,
+}
+
+impl UrlScanner {
+    pub fn new(
+        signature_db: Arc<SignatureDatabase>,
+        options: ScanOptions,
+        config: &UrlScanConfig,
+    ) -> Result<Self, anyhow::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+            .user_agent(concat!("virus-scanner/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("无法创建 HTTP 客户端")?;
+
+        Ok(Self {
+            signature_db,
+            options,
+            client,
+            max_size: config.max_size_mb * 1024 * 1024,
+        })
+    }
+
+    pub async fn scan(&self, url: &str) -> Result<UrlScanReport, anyhow::Error> {
+        let parsed = Url::parse(url).with_context(|| format!("无效的 URL: {}", url))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow::anyhow!("只支持 http 和 https URL: {}", url));
+        }
+
+        let workspace = tempfile::Builder::new()
+            .prefix("virus-scanner-url-")
+            .tempdir()
+            .context("无法创建下载临时目录")?;
+        // 保留原文件名，脚本启发式等检测依赖扩展名
+        let dest = workspace.path().join(file_name(&parsed));
+
+        log::info!("正在下载: {}", url);
+        let mut response = self
+            .client
+            .get(parsed)
+            .send()
+            .await
+            .with_context(|| format!("无法下载 {}", url))?
+            .error_for_status()
+            .with_context(|| format!("无法下载 {}", url))?;
+        if let Some(length) = response.content_length().filter(|&length| length > self.max_size) {
+            return Err(anyhow::anyhow!("对象大小 {} 字节超过上限 {} 字节", length, self.max_size));
+        }
+
+        let final_url = response.url().to_string();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let mut file = tokio::fs::File::create(&dest).await?;
+        let mut hasher = openssl::sha::Sha256::new();
+        let mut size = 0u64;
+        while let Some(chunk) = response.chunk().await.context("下载中断")? {
+            size += chunk.len() as u64;
+            if size > self.max_size {
+                return Err(anyhow::anyhow!("对象大小超过上限 {} 字节", self.max_size));
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        let results = self.scan_file(workspace.path(), &dest).await?;
+        Ok(UrlScanReport {
+            url: url.to_string(),
+            final_url,
+            content_type,
+            size,
+            sha256: hex::encode(hasher.finish()),
+            results,
+        })
+    }
+
+    // 临时文件只用于判定，不做隔离或删除
+    async fn scan_file(&self, workspace: &Path, dest: &Path) -> Result<Vec<ScanResult>, anyhow::Error> {
+        let mut options = self.options.clone();
+        options.custom_paths = vec![workspace.to_path_buf()];
+        options.exclude_paths.clear();
+        options.exclude_extensions.clear();
+        options.priority_paths.clear();
+        options.honor_scanignore = false;
+        options.use_xattr_markers = false;
+        options.skip_benign_types = false;
+        options.max_file_size = options.max_file_size.max(self.max_size);
+        options.action = DetectionAction::Report;
+        options.auto_quarantine_min_risk = None;
+
+        let engine = ScannerEngine::new(Arc::clone(&self.signature_db), options);
+        let mut results = engine.start_scan().await?;
+        let dest = std::fs::canonicalize(dest)?;
+        results.retain(|r| r.file_path == dest);
+        Ok(results)
+    }
+}
+
+fn file_name(url: &Url) -> String {
+    let name: String = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => DEFAULT_FILE_NAME.to_string(),
+        name => name.to_string(),
+    }
+}
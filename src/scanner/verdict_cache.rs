@@ -0,0 +1,265 @@
+use crate::config::VerdictCacheConfig;
+use crate::scanner::{FileInfo, RiskLevel, ScanOptions, ScanResult, ThreatType};
+use anyhow::Context;
+use openssl::hash::{Hasher, MessageDigest};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS verdicts (
+        sha256 TEXT NOT NULL,
+        db_version TEXT NOT NULL,
+        settings TEXT NOT NULL,
+        detections TEXT NOT NULL,
+        PRIMARY KEY (sha256, db_version, settings)
+    );
+    CREATE TABLE IF NOT EXISTS file_hashes (
+        path BLOB PRIMARY KEY,
+        dev INTEGER NOT NULL,
+        ino INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        mtime_ns INTEGER NOT NULL,
+        ctime_ns INTEGER NOT NULL,
+        sha256 TEXT NOT NULL
+    );
+";
+
+// 缓存的检测结果不含路径和文件信息，命中时按当前文件重新填充
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDetection {
+    pub threat_type: ThreatType,
+    pub risk_level: RiskLevel,
+    pub signature_id: String,
+    pub archive_member: Option<String>,
+    pub heuristic_score: Option<u8>,
+}
+
+impl CachedDetection {
+    pub fn into_result(self, path: &Path, file_info: &FileInfo) -> ScanResult {
+        ScanResult {
+            file_path: path.to_path_buf(),
+            threat_type: self.threat_type,
+            risk_level: self.risk_level,
+            signature_id: self.signature_id,
+            file_info: file_info.clone(),
+            archive_member: self.archive_member,
+            heuristic_score: self.heuristic_score,
+            action_taken: None,
+        }
+    }
+}
+
+impl From<&ScanResult> for CachedDetection {
+    fn from(result: &ScanResult) -> Self {
+        Self {
+            threat_type: result.threat_type.clone(),
+            risk_level: result.risk_level,
+            signature_id: result.signature_id.clone(),
+            archive_member: result.archive_member.clone(),
+            heuristic_score: result.heuristic_score,
+        }
+    }
+}
+
+// ctime 无法由用户伪造，与 mtime 一起判断文件自上次计算哈希后是否被修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileIdentity {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime_ns: i64,
+    ctime_ns: i64,
+}
+
+impl FileIdentity {
+    fn of(path: &Path) -> Result<Self, anyhow::Error> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.size(),
+            mtime_ns: metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec(),
+            ctime_ns: metadata.ctime() * 1_000_000_000 + metadata.ctime_nsec(),
+        })
+    }
+}
+
+// 查询缓存时得到的文件内容标识，扫描结束后用于写回结论
+#[derive(Debug, Clone)]
+pub struct VerdictKey {
+    pub sha256: String,
+    identity: FileIdentity,
+}
+
+// 以 (sha256, 病毒库版本, 扫描设置) 为键保存扫描结论，病毒库版本变化后旧结论自动失效；
+// 扫描设置中包含启发式检测用到的路径信息。
+// 文件哈希按路径缓存，大小、inode、mtime 和 ctime 都未变化时重复扫描无需读取文件内容
+pub struct VerdictCache {
+    conn: Mutex<Connection>,
+}
+
+impl VerdictCache {
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(format!("无法创建扫描结论缓存目录: {:?}", parent))?;
+        }
+        let conn = Connection::open(path).context(format!("无法打开扫描结论缓存: {:?}", path))?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        conn.execute_batch(SCHEMA).context("无法初始化扫描结论缓存")?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    // 未启用时返回 None
+    pub fn from_config(config: &VerdictCacheConfig) -> Result<Option<Self>, anyhow::Error> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Self::open(&config.path).map(Some)
+    }
+
+    // 返回文件的内容标识和缓存的结论；None 表示没有可用的结论
+    pub fn lookup(
+        &self,
+        path: &Path,
+        db_version: &str,
+        settings: &str,
+    ) -> Result<(VerdictKey, Option<Vec<CachedDetection>>), anyhow::Error> {
+        let key = self.content_key(path)?;
+        let detections: Option<String> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT detections FROM verdicts WHERE sha256 = ?1 AND db_version = ?2 AND settings = ?3",
+                params![key.sha256, db_version, settings],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let detections = detections.and_then(|json| serde_json::from_str(&json).ok());
+        Ok((key, detections))
+    }
+
+    // 扫描期间文件被修改时结论对应的内容不确定，不写入缓存
+    pub fn store(
+        &self,
+        path: &Path,
+        key: &VerdictKey,
+        db_version: &str,
+        settings: &str,
+        results: &[ScanResult],
+    ) -> Result<bool, anyhow::Error> {
+        if FileIdentity::of(path)? != key.identity {
+            return Ok(false);
+        }
+        let detections: Vec<CachedDetection> = results.iter().map(CachedDetection::from).collect();
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO verdicts (sha256, db_version, settings, detections) VALUES (?1, ?2, ?3, ?4)",
+            params![key.sha256, db_version, settings, serde_json::to_string(&detections)?],
+        )?;
+        Ok(true)
+    }
+
+    // 删除其他病毒库版本的结论，返回删除的条数
+    pub fn prune(&self, db_version: &str) -> Result<usize, anyhow::Error> {
+        let removed = self
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM verdicts WHERE db_version != ?1", params![db_version])?;
+        Ok(removed)
+    }
+
+    pub fn len(&self) -> Result<usize, anyhow::Error> {
+        let count: i64 = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM verdicts", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, anyhow::Error> {
+        Ok(self.len()? == 0)
+    }
+
+    fn content_key(&self, path: &Path) -> Result<VerdictKey, anyhow::Error> {
+        let identity = FileIdentity::of(path)?;
+        let path_key = path.as_os_str().as_bytes();
+
+        let cached: Option<(i64, i64, i64, i64, i64, String)> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT dev, ino, size, mtime_ns, ctime_ns, sha256 FROM file_hashes WHERE path = ?1",
+                params![path_key],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+            )
+            .optional()?;
+        if let Some((dev, ino, size, mtime_ns, ctime_ns, sha256)) = cached {
+            let stored = FileIdentity {
+                dev: dev as u64,
+                ino: ino as u64,
+                size: size as u64,
+                mtime_ns,
+                ctime_ns,
+            };
+            if stored == identity {
+                return Ok(VerdictKey { sha256, identity });
+            }
+        }
+
+        // 读取前后文件标识不一致说明哈希期间文件被修改，不记录该哈希
+        let sha256 = sha256_file(path)?;
+        if FileIdentity::of(path)? == identity {
+            self.conn.lock().unwrap().execute(
+                "INSERT OR REPLACE INTO file_hashes (path, dev, ino, size, mtime_ns, ctime_ns, sha256)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    path_key,
+                    identity.dev as i64,
+                    identity.ino as i64,
+                    identity.size as i64,
+                    identity.mtime_ns,
+                    identity.ctime_ns,
+                    sha256
+                ],
+            )?;
+        }
+        Ok(VerdictKey { sha256, identity })
+    }
+}
+
+// 影响检测结果的扫描设置的摘要，设置变化后不复用旧结论
+pub fn settings_fingerprint(options: &ScanOptions) -> String {
+    let settings = serde_json::json!({
+        "archive": options.archive,
+        "heuristics": options.heuristics,
+        "pdf": options.pdf,
+        "mail": options.mail,
+        "skip_benign_types": options.skip_benign_types,
+    });
+    let digest = openssl::hash::hash(MessageDigest::sha256(), settings.to_string().as_bytes())
+        .map(hex::encode)
+        .unwrap_or_default();
+    digest.chars().take(16).collect()
+}
+
+fn sha256_file(path: &Path) -> Result<String, anyhow::Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Hasher::new(MessageDigest::sha256())?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n])?;
+    }
+    Ok(hex::encode(hasher.finish()?))
+}
@@ -0,0 +1,310 @@
+use crate::config::BackupRetentionConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+pub const INDEX_FILE: &str = "index.json";
+pub const LATEST: &str = "latest";
+const BACKUP_PREFIX: &str = "backup_";
+const BACKUP_SUFFIX: &str = ".tar.gz";
+const ID_FORMAT: &str = "%Y%m%d_%H%M%S";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    // 备份时间 (本地时间)，同一秒内的多个备份追加序号
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    // 备份时各病毒库的版本，旧版本创建的备份没有记录
+    #[serde(default)]
+    pub versions: BTreeMap<String, u32>,
+    pub files: usize,
+    pub size: u64,
+}
+
+impl BackupEntry {
+    pub fn file_name(&self) -> String {
+        backup_file_name(&self.id)
+    }
+}
+
+pub fn backup_file_name(id: &str) -> String {
+    format!("{}{}{}", BACKUP_PREFIX, id, BACKUP_SUFFIX)
+}
+
+// 备份目录下的 index.json。加载时与目录中的文件核对：删除文件已不存在的记录，
+// 补充索引之外的 backup_*.tar.gz (例如旧版本创建的备份)
+pub struct BackupIndex {
+    dir: PathBuf,
+    entries: Vec<BackupEntry>,
+}
+
+impl BackupIndex {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let index_path = dir.join(INDEX_FILE);
+        let mut entries: Vec<BackupEntry> = match std::fs::read_to_string(&index_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("备份索引已损坏，将重新生成: {}", e);
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("无法读取备份索引: {:?}", index_path)),
+        };
+        entries.retain(|entry| dir.join(entry.file_name()).is_file());
+
+        if let Ok(files) = std::fs::read_dir(dir) {
+            for file in files.flatten() {
+                let name = file.file_name().to_string_lossy().to_string();
+                let Some(id) = name.strip_prefix(BACKUP_PREFIX).and_then(|n| n.strip_suffix(BACKUP_SUFFIX)) else {
+                    continue;
+                };
+                if entries.iter().any(|entry| entry.id == id) {
+                    continue;
+                }
+                let metadata = file.metadata()?;
+                let created_at = id_time(id)
+                    .or_else(|| metadata.modified().ok().map(DateTime::<Utc>::from))
+                    .unwrap_or_else(Utc::now);
+                entries.push(BackupEntry {
+                    id: id.to_string(),
+                    created_at,
+                    versions: BTreeMap::new(),
+                    files: 0,
+                    size: metadata.len(),
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let index_path = self.dir.join(INDEX_FILE);
+        let temp = self.dir.join(format!(".{}.tmp", INDEX_FILE));
+        std::fs::write(&temp, serde_json::to_string_pretty(&self.entries)?)
+            .with_context(|| format!("无法写入备份索引: {:?}", temp))?;
+        std::fs::rename(&temp, &index_path).with_context(|| format!("无法写入备份索引: {:?}", index_path))?;
+        Ok(())
+    }
+
+    // 按时间从旧到新排列
+    pub fn entries(&self) -> &[BackupEntry] {
+        &self.entries
+    }
+
+    // selector 为备份 ID 或 latest
+    pub fn find(&self, selector: &str) -> Option<&BackupEntry> {
+        if selector == LATEST {
+            return self.entries.last();
+        }
+        self.entries.iter().find(|entry| entry.id == selector)
+    }
+
+    pub fn path(&self, entry: &BackupEntry) -> PathBuf {
+        self.dir.join(entry.file_name())
+    }
+
+    // 当前时间对应的 ID，与已有备份重名时追加序号
+    pub fn next_id(&self) -> String {
+        let base = Local::now().format(ID_FORMAT).to_string();
+        let mut id = base.clone();
+        let mut sequence = 1;
+        while self.find(&id).is_some() || self.dir.join(backup_file_name(&id)).exists() {
+            id = format!("{}_{}", base, sequence);
+            sequence += 1;
+        }
+        id
+    }
+
+    pub fn push(&mut self, entry: BackupEntry) {
+        self.entries.push(entry);
+    }
+
+    // 按保留数量和最长保留天数删除旧备份，0 表示不限制；最新的备份始终保留。返回被删除的备份
+    pub fn prune(&mut self, retention: &BackupRetentionConfig, now: DateTime<Utc>) -> Result<Vec<BackupEntry>> {
+        let count = self.entries.len();
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for (index, entry) in std::mem::take(&mut self.entries).into_iter().enumerate() {
+            let newer = count - index - 1;
+            let over_count = retention.keep > 0 && newer >= retention.keep;
+            let expired = retention.max_age_days > 0
+                && now.signed_duration_since(entry.created_at).num_days() >= retention.max_age_days as i64;
+            if newer > 0 && (over_count || expired) {
+                removed.push(entry);
+            } else {
+                kept.push(entry);
+            }
+        }
+        self.entries = kept;
+
+        for entry in &removed {
+            let path = self.path(entry);
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e).with_context(|| format!("无法删除旧备份: {:?}", path));
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+// ID 形如 20261016_030000 或 20261016_030000_1
+fn id_time(id: &str) -> Option<DateTime<Utc>> {
+    let time = NaiveDateTime::parse_from_str(id.get(..15)?, ID_FORMAT).ok()?;
+    Local.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+// 与 `tar -czf backup.tar.gz -C 上级目录 病毒库目录` 的布局相同，旧备份仍可恢复。
+// 只备份病毒库目录下的普通文件，跳过更新时的临时目录和未完成的下载
+pub fn create_backup(database_dir: &Path, backup_file: &Path) -> Result<usize> {
+    let root = database_dir
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("database"));
+    let files = backup_candidates(database_dir)?;
+    if files.is_empty() {
+        return Ok(0);
+    }
+
+    let partial = backup_file.with_extension("partial");
+    let result = (|| -> Result<()> {
+        let file = File::create(&partial).with_context(|| format!("无法创建备份文件: {:?}", partial))?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, flate2::Compression::default()));
+        for path in &files {
+            let name = root.join(path.file_name().unwrap_or_default());
+            builder
+                .append_path_with_name(path, &name)
+                .with_context(|| format!("无法备份 {:?}", path))?;
+        }
+        builder.into_inner()?.finish()?.sync_all()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    std::fs::rename(&partial, backup_file).with_context(|| format!("无法保存备份文件: {:?}", backup_file))?;
+    Ok(files.len())
+}
+
+// 先解压到病毒库目录下的临时目录，全部成功后再替换当前文件，解压失败时当前病毒库不受影响
+pub fn restore_backup(backup_file: &Path, database_dir: &Path) -> Result<usize> {
+    let file = File::open(backup_file).with_context(|| format!("无法打开备份文件: {:?}", backup_file))?;
+    std::fs::create_dir_all(database_dir).with_context(|| format!("无法创建病毒库目录: {:?}", database_dir))?;
+    let staging = tempfile::tempdir_in(database_dir).context("无法创建临时目录")?;
+
+    let mut restored = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    for entry in archive.entries().context("无法解析备份文件")? {
+        let mut entry = entry.context("无法读取备份条目")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let Some(name) = backup_member_name(&path) else {
+            log::debug!("跳过备份中的条目: {:?}", path);
+            continue;
+        };
+        let target = staging.path().join(&name);
+        entry
+            .unpack(&target)
+            .with_context(|| format!("无法解压 {:?}", path))?;
+        restored.push(name);
+    }
+    if restored.is_empty() {
+        return Err(anyhow::anyhow!("备份文件中没有病毒库: {:?}", backup_file));
+    }
+
+    for path in stale_databases(database_dir, &restored)? {
+        std::fs::remove_file(&path).with_context(|| format!("无法删除 {:?}", path))?;
+    }
+    for name in &restored {
+        std::fs::rename(staging.path().join(name), database_dir.join(name))
+            .with_context(|| format!("无法恢复 {:?}", name))?;
+    }
+    Ok(restored.len())
+}
+
+// 回滚时会恢复和删除的病毒库文件，只读取备份内容而不修改病毒库目录
+#[derive(Debug, Clone, Serialize)]
+pub struct RestorePlan {
+    pub restore: Vec<PathBuf>,
+    pub remove: Vec<PathBuf>,
+}
+
+pub fn plan_restore(backup_file: &Path, database_dir: &Path) -> Result<RestorePlan> {
+    let file = File::open(backup_file).with_context(|| format!("无法打开备份文件: {:?}", backup_file))?;
+    let mut names = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    for entry in archive.entries().context("无法解析备份文件")? {
+        let entry = entry.context("无法读取备份条目")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if let Some(name) = backup_member_name(&entry.path()?) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        return Err(anyhow::anyhow!("备份文件中没有病毒库: {:?}", backup_file));
+    }
+    Ok(RestorePlan {
+        remove: stale_databases(database_dir, &names)?,
+        restore: names.iter().map(|name| database_dir.join(name)).collect(),
+    })
+}
+
+// 备份之后新增的病毒库文件，恢复时删除，避免与恢复的版本同时加载
+fn stale_databases(database_dir: &Path, restored: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    Ok(backup_candidates(database_dir)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cvd" || extension == "cld"))
+        .filter(|path| !restored.iter().any(|name| path.file_name() == Some(name.as_os_str())))
+        .collect())
+}
+
+fn backup_candidates(database_dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(database_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("无法读取病毒库目录: {:?}", database_dir)),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name.ends_with(".part") || name.ends_with(".validator") {
+            continue;
+        }
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// 只恢复 "病毒库目录/文件名" 形式的条目，防止备份中的路径写到病毒库目录之外
+fn backup_member_name(path: &Path) -> Option<PathBuf> {
+    let components: Vec<Component> = path.components().collect();
+    match components.as_slice() {
+        [Component::Normal(_), Component::Normal(name)] => {
+            let name = Path::new(name);
+            let hidden = name.to_string_lossy().starts_with('.');
+            (!hidden).then(|| name.to_path_buf())
+        }
+        _ => None,
+    }
+}
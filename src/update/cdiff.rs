@@ -0,0 +1,356 @@
+use crate::scanner::cvd::{is_signature_file, open_cvd, CvdHeader, CVD_HEADER_SIZE};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+const CDIFF_MAGIC: &str = "ClamAV-Diff";
+// 解压后的脚本大小上限，防止异常文件耗尽内存
+const MAX_SCRIPT_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CdiffCommand {
+    Open(String),
+    Add(String),
+    // 行号从 1 开始，指向 OPEN 时的原始文件；前缀用于确认删除的是预期的行
+    Del {
+        line: usize,
+        prefix: String,
+    },
+    Xchg {
+        line: usize,
+        prefix: String,
+        replacement: String,
+    },
+    // 把源文件的 start_line..=end_line 移动到目标文件末尾
+    Move {
+        source: String,
+        destination: String,
+        start_line: usize,
+        start_prefix: String,
+        end_line: usize,
+        end_prefix: String,
+    },
+    Close,
+    Unlink(String),
+}
+
+// ClamAV 的增量更新脚本 (name-N.cdiff)：gzip 压缩的命令文本，可带 "ClamAV-Diff:版本:..." 文件头，
+// 压缩数据之后附带数字签名，由 dsig::verify_cdiff 在解析前校验
+#[derive(Debug, Clone)]
+pub struct CdiffScript {
+    pub version: Option<u32>,
+    pub commands: Vec<CdiffCommand>,
+}
+
+impl CdiffScript {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (version, compressed) = if data.starts_with(CDIFF_MAGIC.as_bytes()) {
+            let start = data
+                .windows(2)
+                .position(|w| w == [0x1f, 0x8b])
+                .ok_or_else(|| anyhow::anyhow!("增量更新文件缺少压缩数据"))?;
+            let header = String::from_utf8_lossy(&data[..start]);
+            let version = header
+                .split(':')
+                .nth(1)
+                .and_then(|v| v.trim().parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("无法解析增量更新文件头: {}", header.trim()))?;
+            (Some(version), &data[start..])
+        } else {
+            (None, data)
+        };
+
+        // GzDecoder 只解码第一个成员，忽略其后的数字签名
+        let mut script = Vec::new();
+        flate2::read::GzDecoder::new(compressed)
+            .take(MAX_SCRIPT_SIZE + 1)
+            .read_to_end(&mut script)
+            .context("无法解压增量更新文件")?;
+        if script.len() as u64 > MAX_SCRIPT_SIZE {
+            return Err(anyhow::anyhow!("增量更新脚本超过 {} 字节", MAX_SCRIPT_SIZE));
+        }
+
+        let text = String::from_utf8_lossy(&script);
+        let mut commands = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command = parse_command(line).with_context(|| format!("增量更新脚本第 {} 行无效: {}", index + 1, line))?;
+            commands.push(command);
+        }
+        Ok(Self { version, commands })
+    }
+}
+
+fn parse_command(line: &str) -> Result<CdiffCommand> {
+    let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut args = rest.split_whitespace();
+    let mut next = || args.next().map(str::to_string).ok_or_else(|| anyhow::anyhow!("参数不足"));
+    let line_number = |value: String| -> Result<usize> {
+        value.parse().ok().filter(|&n| n > 0).ok_or_else(|| anyhow::anyhow!("无效的行号: {}", value))
+    };
+
+    let command = match name {
+        "OPEN" => CdiffCommand::Open(database_name(&next()?)?),
+        "ADD" if !rest.is_empty() => CdiffCommand::Add(rest.to_string()),
+        "DEL" => CdiffCommand::Del {
+            line: line_number(next()?)?,
+            prefix: next()?,
+        },
+        "XCHG" => {
+            let line = line_number(next()?)?;
+            let prefix = next()?;
+            // 新行是第三个参数之后的全部内容，可能包含空格
+            let replacement = rest
+                .splitn(3, ' ')
+                .nth(2)
+                .filter(|r| !r.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("参数不足"))?;
+            CdiffCommand::Xchg {
+                line,
+                prefix,
+                replacement: replacement.to_string(),
+            }
+        }
+        "MOVE" => CdiffCommand::Move {
+            source: database_name(&next()?)?,
+            destination: database_name(&next()?)?,
+            start_line: line_number(next()?)?,
+            start_prefix: next()?,
+            end_line: line_number(next()?)?,
+            end_prefix: next()?,
+        },
+        "CLOSE" => CdiffCommand::Close,
+        "UNLINK" => CdiffCommand::Unlink(database_name(&next()?)?),
+        _ => return Err(anyhow::anyhow!("未知命令")),
+    };
+    Ok(command)
+}
+
+// 脚本只能修改病毒库归档内的文件
+fn database_name(name: &str) -> Result<String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(anyhow::anyhow!("无效的病毒库文件名: {}", name));
+    }
+    Ok(name.to_string())
+}
+
+// OPEN 与 CLOSE 之间的修改，CLOSE 时按原始行号一次性应用
+#[derive(Default)]
+struct PendingEdit {
+    name: String,
+    added: Vec<String>,
+    deleted: HashMap<usize, String>,
+    exchanged: HashMap<usize, (String, String)>,
+}
+
+// 解包到内存的病毒库，应用增量更新后重新打包为 .cld
+pub struct UnpackedDatabase {
+    pub header: CvdHeader,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl UnpackedDatabase {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let (header, reader) = open_cvd(data)?;
+        let mut files = BTreeMap::new();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().context("无法解析病毒库归档")? {
+            let mut entry = entry.context("无法读取病毒库归档条目")?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.insert(name, content);
+        }
+        Ok(Self { header, files })
+    }
+
+    pub fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files.get(name).map(Vec::as_slice)
+    }
+
+    // 任一命令与当前内容不符时返回错误，调用方应放弃增量更新
+    pub fn apply(&mut self, script: &CdiffScript, version: u32) -> Result<()> {
+        if let Some(expected) = script.version.filter(|&v| v != version) {
+            return Err(anyhow::anyhow!("增量更新版本不符: 期望 {}，实际 {}", version, expected));
+        }
+        if version != self.header.version + 1 {
+            return Err(anyhow::anyhow!("无法将版本 {} 的病毒库更新到 {}", self.header.version, version));
+        }
+
+        let mut pending: Option<PendingEdit> = None;
+        for command in &script.commands {
+            match command {
+                CdiffCommand::Open(name) => {
+                    if let Some(edit) = &pending {
+                        return Err(anyhow::anyhow!("打开 {} 前未关闭 {}", name, edit.name));
+                    }
+                    pending = Some(PendingEdit {
+                        name: name.clone(),
+                        ..PendingEdit::default()
+                    });
+                }
+                CdiffCommand::Add(line) => open_edit(&mut pending)?.added.push(line.clone()),
+                CdiffCommand::Del { line, prefix } => {
+                    open_edit(&mut pending)?.deleted.insert(*line, prefix.clone());
+                }
+                CdiffCommand::Xchg { line, prefix, replacement } => {
+                    open_edit(&mut pending)?
+                        .exchanged
+                        .insert(*line, (prefix.clone(), replacement.clone()));
+                }
+                CdiffCommand::Close => {
+                    let edit = pending.take().ok_or_else(|| anyhow::anyhow!("CLOSE 前没有打开的文件"))?;
+                    self.close(edit)?;
+                }
+                CdiffCommand::Move {
+                    source,
+                    destination,
+                    start_line,
+                    start_prefix,
+                    end_line,
+                    end_prefix,
+                } => {
+                    if pending.is_some() {
+                        return Err(anyhow::anyhow!("MOVE 前未关闭打开的文件"));
+                    }
+                    self.move_lines(source, destination, (*start_line, start_prefix), (*end_line, end_prefix))?;
+                }
+                CdiffCommand::Unlink(name) => {
+                    if pending.is_some() {
+                        return Err(anyhow::anyhow!("UNLINK 前未关闭打开的文件"));
+                    }
+                    self.files
+                        .remove(name)
+                        .ok_or_else(|| anyhow::anyhow!("要删除的文件不存在: {}", name))?;
+                }
+            }
+        }
+        if let Some(edit) = pending {
+            return Err(anyhow::anyhow!("脚本结束时 {} 未关闭", edit.name));
+        }
+
+        self.header.version = version;
+        self.header.signature_count = self.signature_count();
+        Ok(())
+    }
+
+    fn close(&mut self, edit: PendingEdit) -> Result<()> {
+        let lines = self.files.get(&edit.name).map(|c| split_lines(c)).unwrap_or_default();
+        let exchanged = edit.exchanged.iter().map(|(line, (prefix, _))| (line, prefix));
+        for (&line, prefix) in edit.deleted.iter().chain(exchanged) {
+            check_line(&lines, &edit.name, line, prefix)?;
+        }
+
+        let mut updated = Vec::with_capacity(lines.len() + edit.added.len());
+        for (index, line) in lines.into_iter().enumerate() {
+            let number = index + 1;
+            if edit.deleted.contains_key(&number) {
+                continue;
+            }
+            match edit.exchanged.get(&number) {
+                Some((_, replacement)) => updated.push(replacement.as_bytes().to_vec()),
+                None => updated.push(line),
+            }
+        }
+        updated.extend(edit.added.into_iter().map(String::into_bytes));
+        self.files.insert(edit.name, join_lines(&updated));
+        Ok(())
+    }
+
+    fn move_lines(
+        &mut self,
+        source: &str,
+        destination: &str,
+        (start, start_prefix): (usize, &str),
+        (end, end_prefix): (usize, &str),
+    ) -> Result<()> {
+        let content = self
+            .files
+            .get(source)
+            .ok_or_else(|| anyhow::anyhow!("要移动的文件不存在: {}", source))?;
+        let mut lines = split_lines(content);
+        check_line(&lines, source, start, start_prefix)?;
+        check_line(&lines, source, end, end_prefix)?;
+        if end < start {
+            return Err(anyhow::anyhow!("{} 中的移动范围无效: {}-{}", source, start, end));
+        }
+
+        let moved: Vec<Vec<u8>> = lines.drain(start - 1..end).collect();
+        self.files.insert(source.to_string(), join_lines(&lines));
+        let mut target = self.files.get(destination).map(|c| split_lines(c)).unwrap_or_default();
+        target.extend(moved);
+        self.files.insert(destination.to_string(), join_lines(&target));
+        Ok(())
+    }
+
+    fn signature_count(&self) -> u64 {
+        self.files
+            .iter()
+            .filter(|(name, _)| is_signature_file(name))
+            .flat_map(|(_, content)| split_lines(content))
+            .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+            .count() as u64
+    }
+
+    // .cld 文件头中的 MD5 和数字签名以 X 占位，正文为未压缩的 tar
+    pub fn to_cld(&self) -> Result<Vec<u8>> {
+        let now = chrono::Utc::now();
+        let mut cld = format!(
+            "ClamAV-VDB:{}:{}:{}:{}:X:X:{}:{}",
+            now.format("%d %b %Y %H-%M %z"),
+            self.header.version,
+            self.header.signature_count,
+            self.header.functionality_level,
+            self.header.builder,
+            now.timestamp()
+        )
+        .into_bytes();
+        if cld.len() > CVD_HEADER_SIZE {
+            return Err(anyhow::anyhow!("病毒库文件头过长"));
+        }
+        cld.resize(CVD_HEADER_SIZE, b' ');
+
+        let mut builder = tar::Builder::new(cld);
+        for (name, content) in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(now.timestamp() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_slice())
+                .with_context(|| format!("无法打包 {}", name))?;
+        }
+        Ok(builder.into_inner()?)
+    }
+}
+
+fn open_edit(pending: &mut Option<PendingEdit>) -> Result<&mut PendingEdit> {
+    pending.as_mut().ok_or_else(|| anyhow::anyhow!("修改前没有打开的文件"))
+}
+
+fn check_line(lines: &[Vec<u8>], name: &str, line: usize, prefix: &str) -> Result<()> {
+    match lines.get(line - 1) {
+        Some(content) if content.starts_with(prefix.as_bytes()) => Ok(()),
+        _ => Err(anyhow::anyhow!("{} 第 {} 行与增量更新不符", name, line)),
+    }
+}
+
+fn split_lines(content: &[u8]) -> Vec<Vec<u8>> {
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    if content.is_empty() {
+        return Vec::new();
+    }
+    content.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect()
+}
+
+fn join_lines(lines: &[Vec<u8>]) -> Vec<u8> {
+    let mut content = lines.join(&b'\n');
+    if !content.is_empty() {
+        content.push(b'\n');
+    }
+    content
+}
@@ -0,0 +1,142 @@
+use crate::scanner::cvd::{CvdHeader, CVD_HEADER_SIZE};
+use anyhow::{Context, Result};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::hash::{hash, MessageDigest};
+
+// ClamAV 官方签名公钥 (libclamav/dsig.c 与 libclamav_rust/src/cdiff.rs)，十进制表示
+const CVD_MODULUS: &str = concat!(
+    "1186409955516453426030700016584531897515277744120277437465994057432431426074",
+    "6414476736106064065584474976078889002228342492276248891756555100246777110966",
+    "9598189410434699034532232228621591089508178591428456220796841621637175567590",
+    "4766669286987701433281373839528203831975320477717801965769576958226412242626",
+    "93037",
+);
+const CVD_EXPONENT: &str = "100001027";
+const CDIFF_MODULUS: &str = concat!(
+    "1478390587407746709026222851655791757025459963837620353203198921410555284726",
+    "9687489771975792123442185817287694951949800908791527542017115600501303394778",
+    "6185358648452357000415900563182301024496122174585490160893133065913885907907",
+    "9651581965410232072571230082235634872401123265483750324173617790778419870083",
+    "4440681124727060540035754699658105895050096576226753008596881698828185652424",
+    "9019216687583265784620032479064709820922981067896572119054889862810783463614",
+    "6952448482955956088622719809199549844067663963983046359321138605506536028842",
+    "2394053998134458623712540683294034953818412458362198117811990006021989844180",
+    "721010947",
+);
+const CDIFF_EXPONENT: &str = "100002053";
+
+const MD5_LEN: usize = 16;
+const SHA256_LEN: usize = 32;
+// .cdiff 使用 2048 位密钥的 PSS 填充，盐长度与摘要相同
+const PSS_LEN: usize = 2048 / 8;
+const PSS_BLOCK_LEN: usize = PSS_LEN - SHA256_LEN - 1;
+// 2048 位签名编码后为 342 个字符
+const MAX_CDIFF_SIGNATURE_LEN: usize = 350;
+
+// 校验完整病毒库 (.cvd)：正文 MD5 与文件头一致，文件头中的数字签名是该 MD5 的签名。
+// .cld 由本地增量更新生成，没有有效签名
+pub fn verify_cvd(data: &[u8]) -> Result<CvdHeader> {
+    let header = CvdHeader::parse(data)?;
+    let body = &data[CVD_HEADER_SIZE..];
+    let digest = hex::encode(hash(MessageDigest::md5(), body)?);
+    if header.md5.len() != MD5_LEN * 2 || digest != header.md5 {
+        return Err(anyhow::anyhow!("病毒库MD5校验失败"));
+    }
+
+    let plain = decode_signature(&header.digital_signature, MD5_LEN, CVD_EXPONENT, CVD_MODULUS)?;
+    if hex::encode(plain) != header.md5 {
+        return Err(anyhow::anyhow!("病毒库数字签名无效"));
+    }
+    Ok(header)
+}
+
+// 校验增量更新 (.cdiff)：文件末尾最后一个 ':' 之后是数字签名，签名覆盖其之前的全部内容
+pub fn verify_cdiff(data: &[u8]) -> Result<()> {
+    let tail_start = data.len().saturating_sub(MAX_CDIFF_SIGNATURE_LEN + 1);
+    let separator = data[tail_start..]
+        .iter()
+        .rposition(|&b| b == b':')
+        .map(|position| tail_start + position)
+        .ok_or_else(|| anyhow::anyhow!("增量更新文件缺少数字签名"))?;
+    let signature = std::str::from_utf8(&data[separator + 1..])
+        .map(str::trim)
+        .ok()
+        .filter(|signature| !signature.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("增量更新文件缺少数字签名"))?;
+
+    let digest = hash(MessageDigest::sha256(), &data[..separator])?;
+    if !verify_pss(&digest, signature)? {
+        return Err(anyhow::anyhow!("增量更新数字签名无效"));
+    }
+    Ok(())
+}
+
+// 与 libclamav 的 cli_versig2 相同的 PSS 校验 (MGF1-SHA256)
+fn verify_pss(digest: &[u8], signature: &str) -> Result<bool> {
+    let decoded = match decode_signature(signature, PSS_LEN, CDIFF_EXPONENT, CDIFF_MODULUS) {
+        Ok(decoded) => decoded,
+        Err(_) => return Ok(false),
+    };
+    if decoded[PSS_LEN - 1] != 0xbc {
+        return Ok(false);
+    }
+    let (masked, hashed) = (&decoded[..PSS_BLOCK_LEN], &decoded[PSS_BLOCK_LEN..PSS_LEN - 1]);
+
+    let mut block = Vec::with_capacity(PSS_BLOCK_LEN + SHA256_LEN);
+    for counter in 0..PSS_BLOCK_LEN.div_ceil(SHA256_LEN) as u32 {
+        let mut input = hashed.to_vec();
+        input.extend_from_slice(&counter.to_be_bytes());
+        block.extend_from_slice(&hash(MessageDigest::sha256(), &input)?);
+    }
+    block.truncate(PSS_BLOCK_LEN);
+    for (byte, mask) in block.iter_mut().zip(masked) {
+        *byte ^= mask;
+    }
+    block[0] &= 0x7f;
+
+    let Some(salt_start) = block.iter().position(|&b| b == 0x01).map(|position| position + 1) else {
+        return Ok(false);
+    };
+    if PSS_BLOCK_LEN - salt_start != SHA256_LEN {
+        return Ok(false);
+    }
+
+    let mut message = vec![0u8; 8];
+    message.extend_from_slice(digest);
+    message.extend_from_slice(&block[salt_start..]);
+    let expected = hash(MessageDigest::sha256(), &message)?;
+    Ok(openssl::memcmp::eq(&expected, hashed))
+}
+
+// ClamAV 的签名编码：每个字符 6 位，低位在前，字母表为 a-z A-Z 0-9 + /。
+// 解码后做 RSA 公钥运算，结果按大端序左侧补零到 plain_len 字节
+fn decode_signature(signature: &str, plain_len: usize, exponent: &str, modulus: &str) -> Result<Vec<u8>> {
+    let mut value = BigNum::new()?;
+    for c in signature.bytes().rev() {
+        let digit = match c {
+            b'a'..=b'z' => c - b'a',
+            b'A'..=b'Z' => c - b'A' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(anyhow::anyhow!("数字签名包含无效字符")),
+        };
+        let previous = value.to_owned()?;
+        value.lshift(&previous, 6)?;
+        value.add_word(digit as u32)?;
+    }
+
+    let exponent = BigNum::from_dec_str(exponent)?;
+    let modulus = BigNum::from_dec_str(modulus)?;
+    let mut plain = BigNum::new()?;
+    let mut context = BigNumContext::new()?;
+    plain.mod_exp(&value, &exponent, &modulus, &mut context).context("数字签名解码失败")?;
+
+    let bytes = plain.to_vec();
+    if bytes.len() > plain_len {
+        return Err(anyhow::anyhow!("数字签名长度不符"));
+    }
+    let mut padded = vec![0u8; plain_len - bytes.len()];
+    padded.extend_from_slice(&bytes);
+    Ok(padded)
+}
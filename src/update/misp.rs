@@ -0,0 +1,283 @@
+use crate::config::MispConfig;
+use crate::scanner::{PatternType, Signature, SignatureDatabase};
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const MISP_SIGNATURE_PREFIX: &str = "misp:";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MispAttribute {
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(rename = "type")]
+    pub attr_type: String,
+    pub value: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub to_ids: bool,
+    #[serde(default)]
+    pub comment: String,
+}
+
+pub struct MispImporter {
+    config: MispConfig,
+}
+
+impl MispImporter {
+    pub fn new(config: MispConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn fetch_attributes(&self) -> Result<Vec<MispAttribute>, anyhow::Error> {
+        let mut attributes = Vec::new();
+
+        if let Some(ref url) = self.config.url {
+            attributes.extend(self.fetch_from_instance(url).await?);
+        }
+
+        if let Some(ref feed_path) = self.config.feed_path {
+            attributes.extend(Self::load_feed(feed_path)?);
+        }
+
+        Ok(attributes)
+    }
+
+    async fn fetch_from_instance(&self, url: &str) -> Result<Vec<MispAttribute>, anyhow::Error> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!self.config.verify_tls)
+            .timeout(Duration::from_secs(120))
+            .build()?;
+
+        let mut query = serde_json::json!({
+            "returnFormat": "json",
+            "type": ["md5", "sha1", "sha256", "imphash", "filename|md5", "filename|sha1", "filename|sha256"],
+            "to_ids": self.config.only_to_ids,
+        });
+        if !self.config.tags.is_empty() {
+            query["tags"] = serde_json::json!(self.config.tags);
+        }
+
+        let response = client
+            .post(format!("{}/attributes/restSearch", url.trim_end_matches('/')))
+            .header("Authorization", &self.config.api_key)
+            .header("Accept", "application/json")
+            .json(&query)
+            .send()
+            .await
+            .context("无法连接到MISP服务器")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("MISP服务器返回错误: {}", response.status()));
+        }
+
+        let body: serde_json::Value = response.json().await.context("无法解析MISP响应")?;
+        Ok(Self::parse_attributes(&body))
+    }
+
+    // 支持单个导出文件或MISP feed目录（每个事件一个JSON文件）
+    pub fn load_feed(path: &Path) -> Result<Vec<MispAttribute>, anyhow::Error> {
+        let mut attributes = Vec::new();
+
+        if path.is_dir() {
+            for entry in walkdir::WalkDir::new(path)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().map(|ext| ext == "json").unwrap_or(false))
+                .filter(|e| e.file_name() != "manifest.json")
+            {
+                match Self::load_feed_file(entry.path()) {
+                    Ok(attrs) => attributes.extend(attrs),
+                    Err(e) => log::warn!("跳过无效的MISP事件文件 {:?}: {}", entry.path(), e),
+                }
+            }
+        } else {
+            attributes = Self::load_feed_file(path)?;
+        }
+
+        Ok(attributes)
+    }
+
+    fn load_feed_file(path: &Path) -> Result<Vec<MispAttribute>, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取MISP导出文件 {:?}", path))?;
+        let body: serde_json::Value = serde_json::from_str(&content).context("无法解析MISP导出文件")?;
+        Ok(Self::parse_attributes(&body))
+    }
+
+    // restSearch响应、事件导出和feed文件的结构不同，统一收集所有 "Attribute" 数组
+    pub fn parse_attributes(body: &serde_json::Value) -> Vec<MispAttribute> {
+        let mut attributes = Vec::new();
+        Self::collect_attributes(body, &mut attributes);
+        attributes
+    }
+
+    fn collect_attributes(value: &serde_json::Value, out: &mut Vec<MispAttribute>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, child) in map {
+                    if key == "Attribute" {
+                        if let Some(items) = child.as_array() {
+                            out.extend(
+                                items
+                                    .iter()
+                                    .filter_map(|item| serde_json::from_value(item.clone()).ok()),
+                            );
+                        }
+                    } else {
+                        Self::collect_attributes(child, out);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    Self::collect_attributes(item, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn to_signatures(&self, attributes: &[MispAttribute]) -> Vec<Signature> {
+        let mut seen = HashSet::new();
+        let mut signatures = Vec::new();
+
+        for attr in attributes {
+            if self.config.only_to_ids && !attr.to_ids {
+                continue;
+            }
+            if let Some(signature) = self.convert_attribute(attr) {
+                if seen.insert(signature.id.clone()) {
+                    signatures.push(signature);
+                }
+            }
+        }
+
+        signatures
+    }
+
+    fn convert_attribute(&self, attr: &MispAttribute) -> Option<Signature> {
+        let (kind, value) = match attr.attr_type.split_once('|') {
+            Some(("filename", hash_type)) => (hash_type, attr.value.split_once('|')?.1),
+            _ => (attr.attr_type.as_str(), attr.value.as_str()),
+        };
+
+        let (pattern, pattern_type) = match kind {
+            "md5" | "sha1" | "sha256" => {
+                let digest = hex::decode(value.trim()).ok()?;
+                let expected_len = match kind {
+                    "md5" => 16,
+                    "sha1" => 20,
+                    _ => 32,
+                };
+                if digest.len() != expected_len {
+                    return None;
+                }
+                (digest, PatternType::Hash)
+            }
+            "imphash" => {
+                let digest = hex::decode(value.trim()).ok()?;
+                if digest.len() != 16 {
+                    return None;
+                }
+                (digest, PatternType::Imphash)
+            }
+            // 只有文件名的属性无法转换为内容特征码，作为字节序列会误报内容中含该名称的文件，因此跳过
+            _ => return None,
+        };
+
+        let key = if attr.uuid.is_empty() {
+            format!("{}:{}", kind, value.trim().to_lowercase())
+        } else {
+            attr.uuid.clone()
+        };
+
+        let name = if attr.comment.is_empty() {
+            format!("MISP.{}.{}", attr.category.replace(' ', "_"), kind)
+        } else {
+            format!("MISP.{}", attr.comment.replace(' ', "_"))
+        };
+
+        Some(Signature {
+            id: format!("{}{}", MISP_SIGNATURE_PREFIX, key),
+            name,
+            threat_type: self.config.threat_type.clone(),
+            risk_level: self.config.risk_level.clone(),
+            pattern,
+            pattern_type,
+            target: "0".to_string(),
+            subplatform: None,
+        })
+    }
+
+    // 每次同步替换上一次导入的全部MISP特征码，已从MISP删除的指标随之失效
+    pub async fn import(&self, db: &SignatureDatabase) -> Result<usize, anyhow::Error> {
+        let attributes = self.fetch_attributes().await?;
+        let signatures = self.to_signatures(&attributes);
+        let count = signatures.len();
+
+        let removed = db.remove_signatures_by_prefix(MISP_SIGNATURE_PREFIX).await;
+        db.update_signatures(signatures).await?;
+
+        log::info!(
+            "已从MISP导入 {} 条特征码 (属性 {} 条，替换旧特征码 {} 条)",
+            count,
+            attributes.len(),
+            removed
+        );
+
+        Ok(count)
+    }
+}
+
+pub struct MispScheduler {
+    importer: Arc<MispImporter>,
+    db: Arc<SignatureDatabase>,
+    interval: Duration,
+    running: Arc<AtomicBool>,
+}
+
+impl MispScheduler {
+    pub fn new(config: MispConfig, db: Arc<SignatureDatabase>) -> Self {
+        let interval = Duration::from_secs(config.refresh_interval_hours.max(1) * 3600);
+        Self {
+            importer: Arc::new(MispImporter::new(config)),
+            db,
+            interval,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&self) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        log::info!("MISP同步调度器已启动");
+
+        let running = Arc::clone(&self.running);
+        let importer = Arc::clone(&self.importer);
+        let db = Arc::clone(&self.db);
+        let interval = self.interval;
+
+        tokio::spawn(async move {
+            while running.load(Ordering::Relaxed) {
+                if let Err(e) = importer.import(&db).await {
+                    log::error!("MISP同步失败: {}", e);
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        log::info!("MISP同步调度器已停止");
+    }
+}
@@ -1,14 +1,44 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::fs::File;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc;
-use crate::config::UpdateConfig;
-
-#[derive(Debug, Clone)]
+use crate::config::{BackupRetentionConfig, CdiffConfig, DetectionAction, UpdateConfig};
+use crate::core::security::QuarantineManager;
+use crate::scanner::cvd::{signature_digests, CVD_HEADER_SIZE};
+use crate::scanner::selftest::run_selftest;
+use crate::scanner::{CvdHeader, ScanMode, ScanOptions, SignatureDatabase};
+use crate::utils::{ensure_free_space, DiskSpaceError};
+
+pub mod backup;
+pub mod cdiff;
+pub mod dsig;
+pub mod misp;
+
+pub use backup::{BackupEntry, BackupIndex, RestorePlan};
+pub use cdiff::{CdiffScript, UnpackedDatabase};
+pub use misp::{MispImporter, MispScheduler};
+
+// 镜像上的病毒库名称，本地可能是 .cvd 或增量更新后生成的 .cld
+const DATABASE_NAMES: [&str; 3] = ["main", "daily", "bytecode"];
+// 两次进度事件之间至少下载的字节数
+const PROGRESS_INTERVAL: u64 = 1024 * 1024;
+// 每个文件最多下载的次数，重试间隔从 RETRY_BACKOFF 开始逐次加倍
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+// 更新历史保存在病毒库目录中，以点开头的文件不会被备份或当作病毒库加载
+const HISTORY_FILE: &str = ".update-history.json";
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
 pub struct UpdateInfo {
     pub version: String,
     pub timestamp: DateTime<Utc>,
@@ -36,9 +66,15 @@ pub struct DatabaseUpdater {
     update_history: Arc<Mutex<Vec<UpdateInfo>>>,
     last_check: Arc<Mutex<Option<Instant>>>,
     event_tx: Option<mpsc::Sender<UpdateEvent>>,
+    cdiff: CdiffConfig,
+    // 下载的 .cvd 和 .cdiff 必须带有效的 ClamAV 数字签名
+    verify_signatures: bool,
+    backup_retention: BackupRetentionConfig,
+    validate_after_update: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
 pub enum UpdateEvent {
     Started,
     Progress(u64, u64),
@@ -47,12 +83,47 @@ pub enum UpdateEvent {
     VersionAvailable(String),
 }
 
+fn load_history(database_path: &Path) -> Vec<UpdateInfo> {
+    let path = database_path.join(HISTORY_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("更新历史已损坏，已忽略: {}", e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+// 更新安装完成后重新加载病毒库，正在扫描的文件继续使用旧快照，之后的文件使用新病毒库。
+// 新病毒库无法加载时保留当前病毒库
+pub fn spawn_signature_reloader(
+    mut events: mpsc::Receiver<UpdateEvent>,
+    signature_db: Arc<SignatureDatabase>,
+    database_path: PathBuf,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let UpdateEvent::Completed(info) = event else {
+                continue;
+            };
+            match signature_db.reload_from_directory(&database_path).await {
+                Ok(total) => {
+                    signature_db.set_last_update(Instant::now());
+                    log::info!("已切换到新病毒库 {}，特征码数量: {}", info.version, total);
+                }
+                Err(e) => log::error!("新病毒库加载失败，继续使用当前病毒库: {}", e),
+            }
+        }
+    })
+}
+
 impl DatabaseUpdater {
     pub fn new(
         mirror_url: String,
         local_database_path: PathBuf,
         backup_path: PathBuf,
     ) -> Self {
+        let update_history = load_history(&local_database_path);
         Self {
             mirror_url,
             local_database_path,
@@ -65,9 +136,13 @@ impl DatabaseUpdater {
                 latest_version: String::from("0.0.0"),
                 error: None,
             })),
-            update_history: Arc::new(Mutex::new(Vec::new())),
+            update_history: Arc::new(Mutex::new(update_history)),
             last_check: Arc::new(Mutex::new(None)),
             event_tx: None,
+            cdiff: CdiffConfig::default(),
+            verify_signatures: true,
+            backup_retention: BackupRetentionConfig::default(),
+            validate_after_update: true,
         }
     }
 
@@ -75,6 +150,23 @@ impl DatabaseUpdater {
         self.event_tx = Some(tx);
     }
 
+    pub fn set_cdiff_config(&mut self, config: CdiffConfig) {
+        self.cdiff = config;
+    }
+
+    // 关闭后接受未签名的病毒库，只用于自建的镜像
+    pub fn set_verify_signatures(&mut self, enabled: bool) {
+        self.verify_signatures = enabled;
+    }
+
+    pub fn set_backup_retention(&mut self, retention: BackupRetentionConfig) {
+        self.backup_retention = retention;
+    }
+
+    pub fn set_validate_after_update(&mut self, enabled: bool) {
+        self.validate_after_update = enabled;
+    }
+
     pub async fn check_for_updates(&self) -> Result<Option<String>, anyhow::Error> {
         log::info!("正在检查病毒库更新...");
 
@@ -116,9 +208,10 @@ impl DatabaseUpdater {
             chrono::Utc::now().format("%Y%m%d").to_string()
         };
 
-        let mut status = self.status.lock().unwrap();
-        let old_version = status.latest_version.clone();
-        status.latest_version = version.clone();
+        let old_version = {
+            let mut status = self.status.lock().unwrap();
+            std::mem::replace(&mut status.latest_version, version.clone())
+        };
 
         if let Some(ref tx) = self.event_tx {
             let _ = tx.send(UpdateEvent::VersionAvailable(version.clone())).await;
@@ -134,6 +227,8 @@ impl DatabaseUpdater {
     }
 
     pub async fn perform_update(&self) -> Result<UpdateInfo, anyhow::Error> {
+        ensure_free_space(&self.backup_path, self.current_database_size())?;
+
         {
             let mut status = self.status.lock().unwrap();
 
@@ -149,66 +244,49 @@ impl DatabaseUpdater {
             let _ = tx.send(UpdateEvent::Started).await;
         }
 
+        // 失败后清除进行中标记，下一次更新可以续传已下载的部分
+        match self.run_update().await {
+            Ok(update_info) => Ok(update_info),
+            Err(e) => {
+                {
+                    let mut status = self.status.lock().unwrap();
+                    status.in_progress = false;
+                    status.error = Some(e.to_string());
+                }
+                if let Some(ref tx) = self.event_tx {
+                    let _ = tx.send(UpdateEvent::Failed(e.to_string())).await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn run_update(&self) -> Result<UpdateInfo, anyhow::Error> {
         log::info!("开始下载病毒库更新...");
 
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(600))
             .build()?;
 
-        let main_url = format!("{}/main.cvd", self.mirror_url);
-        let daily_url = format!("{}/daily.cvd", self.mirror_url);
-        let bytecode_url = format!("{}/bytecode.cvd", self.mirror_url);
-
         let temp_dir = tempfile::tempdir_in(&self.local_database_path)
             .context("无法创建临时目录")?;
 
-        let mut signatures_added = 0u32;
-        let mut signatures_removed = 0u32;
-        let mut total_signatures = 0u32;
+        // 各病毒库同时下载，全部完成后才安装；任一文件重试后仍失败则放弃本次更新
+        let progress = DownloadProgress::default();
+        let [main, daily, bytecode] = DATABASE_NAMES;
+        let results = tokio::join!(
+            self.fetch_database(&client, main, temp_dir.path(), &progress),
+            self.fetch_database(&client, daily, temp_dir.path(), &progress),
+            self.fetch_database(&client, bytecode, temp_dir.path(), &progress),
+        );
         let mut download_size = 0u64;
-
-        let database_files = vec![
-            ("main.cvd", &main_url),
-            ("daily.cvd", &daily_url),
-            ("bytecode.cvd", &bytecode_url),
-        ];
-
-        for (name, url) in &database_files {
-            log::info!("正在下载 {}...", name);
-
-            let response = client
-                .get(*url)
-                .send()
-                .await
-                .with_context(|| format!("无法下载 {}", name))?;
-
-            if !response.status().is_success() {
-                log::warn!("无法下载 {}，服务器返回: {}", name, response.status());
-                continue;
-            }
-
-            let size = response
-                .content_length()
-                .unwrap_or(0);
-            download_size += size;
-
-            let file_path = temp_dir.path().join(name);
-            let mut file = File::create(&file_path)
-                .await
-                .with_context(|| format!("无法创建文件: {:?}", file_path))?;
-
-            let bytes = response.bytes().await.context("下载失败")?;
-            file.write_all(&bytes)
-                .await
-                .context("写入文件失败")?;
-            let downloaded = bytes.len() as u64;
-
-            file.flush().await.context("刷新文件失败")?;
-
-            log::info!("{} 下载完成 ({:.2} MB)", name, downloaded as f64 / 1024.0 / 1024.0);
+        for result in [results.0, results.1, results.2] {
+            download_size += result?;
         }
 
         let new_version = self.get_latest_version().await?;
+        let (signatures_added, signatures_removed, total_signatures) =
+            self.count_signature_changes(temp_dir.path()).await?;
 
         let update_info = UpdateInfo {
             version: new_version.clone(),
@@ -219,8 +297,34 @@ impl DatabaseUpdater {
             download_size,
         };
 
-        self.backup_current_database()?;
-        self.install_new_database(temp_dir.path())?;
+        // 更新前的特征码数量用于判断新病毒库是否完整
+        let previous_count = if self.validate_after_update {
+            self.loaded_signature_count().await
+        } else {
+            None
+        };
+
+        let backup_id = self.backup_current_database()?;
+        let installed = self.install_new_database(temp_dir.path())?;
+        // 先删除临时目录，避免验证时重复加载下载的文件
+        drop(temp_dir);
+
+        if self.validate_after_update && !installed.is_empty() {
+            if let Err(e) = self.validate_installed_database(previous_count).await {
+                log::error!("新病毒库验证失败，正在回滚: {:#}", e);
+                match backup_id {
+                    Some(id) => {
+                        self.rollback(&id).await.context("新病毒库验证失败且回滚失败")?;
+                    }
+                    None => {
+                        for path in &installed {
+                            std::fs::remove_file(path).with_context(|| format!("无法删除 {:?}", path))?;
+                        }
+                    }
+                }
+                return Err(e.context("新病毒库验证失败，已回滚"));
+            }
+        }
 
         {
             let mut status = self.status.lock().unwrap();
@@ -230,7 +334,7 @@ impl DatabaseUpdater {
             status.error = None;
         }
 
-        self.update_history.lock().unwrap().push(update_info.clone());
+        self.record_history(&update_info);
 
         if let Some(ref tx) = self.event_tx {
             let _ = tx.send(UpdateEvent::Completed(update_info.clone())).await;
@@ -241,51 +345,400 @@ impl DatabaseUpdater {
         Ok(update_info)
     }
 
-    fn backup_current_database(&self) -> Result<(), anyhow::Error> {
-        log::info!("正在备份当前病毒库...");
+    // 优先增量更新，否则下载完整病毒库。下载中断时按递增的间隔重试，重试时从已下载的部分续传
+    async fn fetch_database(
+        &self,
+        client: &reqwest::Client,
+        database: &str,
+        temp_dir: &Path,
+        progress: &DownloadProgress,
+    ) -> Result<u64, anyhow::Error> {
+        if self.cdiff.enabled {
+            match self.try_incremental_update(client, database, temp_dir).await {
+                Ok(Some(size)) => return Ok(size),
+                Ok(None) => {}
+                Err(e) => log::warn!("{} 增量更新失败，改为完整下载: {}", database, e),
+            }
+        }
 
-        if let Err(e) = std::fs::create_dir_all(&self.backup_path) {
-            log::warn!("无法创建备份目录: {}", e);
+        let name = format!("{}.cvd", database);
+        let dest = temp_dir.join(&name);
+        let mut attempt = 1;
+        loop {
+            match self.download(client, &name, &dest, progress).await {
+                Ok(Some(size)) => {
+                    self.verify_download(&dest).await?;
+                    return Ok(size);
+                }
+                Ok(None) => return Ok(0),
+                // 空间不足时重试没有意义
+                Err(e) if attempt < DOWNLOAD_ATTEMPTS && e.downcast_ref::<DiskSpaceError>().is_none() => {
+                    let delay = RETRY_BACKOFF * 2u32.pow(attempt - 1);
+                    log::warn!("{} 下载失败，{:?} 后重试 ({}/{}): {}", name, delay, attempt, DOWNLOAD_ATTEMPTS, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
         }
+    }
 
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
-        let backup_file = self.backup_path.join(format!("backup_{}.tar.gz", timestamp));
+    // 以流的方式写入病毒库目录下的 .part 文件并发送进度事件。中断后再次更新时从已下载的长度续传，
+    // 服务器上的文件已变化 (If-Range 不匹配) 时重新下载。返回本次传输的字节数，文件不存在等客户端错误时返回 None，
+    // 服务器错误按下载失败处理以便重试
+    async fn download(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        dest: &Path,
+        progress: &DownloadProgress,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let url = format!("{}/{}", self.mirror_url, name);
+        let partial = self.local_database_path.join(format!("{}.part", name));
+        let validator_path = self.local_database_path.join(format!("{}.part.validator", name));
+
+        let mut offset = std::fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+        let validator = std::fs::read_to_string(&validator_path).ok().filter(|v| !v.is_empty());
+        let mut request = client.get(&url);
+        match validator {
+            Some(ref validator) if offset > 0 => {
+                log::info!("正在续传 {}，已下载 {} 字节...", name, offset);
+                request = request
+                    .header(RANGE, format!("bytes={}-", offset))
+                    .header(IF_RANGE, validator.as_str());
+            }
+            _ => {
+                log::info!("正在下载 {}...", name);
+                offset = 0;
+            }
+        }
 
-        let mut cmd = std::process::Command::new("tar");
-        cmd.arg("-czf")
-            .arg(&backup_file)
-            .arg("-C")
-            .arg(self.local_database_path.parent().unwrap_or(Path::new(".")))
-            .arg(self.local_database_path.file_name().unwrap_or(std::ffi::OsStr::new("cvd")));
+        let mut response = request.send().await.with_context(|| format!("无法下载 {}", name))?;
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            log::info!("{} 的已下载部分无效，重新下载", name);
+            offset = 0;
+            response = client.get(&url).send().await.with_context(|| format!("无法下载 {}", name))?;
+        }
+        if response.status().is_server_error() {
+            return Err(anyhow::anyhow!("无法下载 {}，服务器返回: {}", name, response.status()));
+        }
+        if !response.status().is_success() {
+            log::warn!("无法下载 {}，服务器返回: {}", name, response.status());
+            return Ok(None);
+        }
 
-        let output = cmd.output().context("备份失败")?;
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+        if resumed && content_range_start(response.headers()) != Some(offset) {
+            return Err(anyhow::anyhow!("{} 的续传范围与请求不符", name));
+        }
+        if !resumed {
+            offset = 0;
+        }
+        let total = response.content_length().map(|length| length + offset).unwrap_or(0);
+        // 下载的文件与随后备份的当前病毒库同时占用空间
+        if let Some(length) = response.content_length() {
+            ensure_free_space(&self.local_database_path, length.saturating_add(self.current_database_size()))?;
+        }
 
-        if !output.status.success() {
-            log::warn!("备份失败: {}", String::from_utf8_lossy(&output.stderr));
+        match download_validator(response.headers()) {
+            Some(validator) => std::fs::write(&validator_path, validator)?,
+            None => {
+                let _ = std::fs::remove_file(&validator_path);
+            }
+        }
+        // 不经过缓冲直接写入，中断时已收到的数据都保留在 .part 文件中
+        let mut file = if resumed {
+            OpenOptions::new().append(true).open(&partial).await
         } else {
-            log::info!("备份已创建: {:?}", backup_file);
+            File::create(&partial).await
         }
+        .with_context(|| format!("无法创建文件: {:?}", partial))?;
+
+        let mut downloaded = offset;
+        let mut reported = offset;
+        while let Some(chunk) = response.chunk().await.with_context(|| format!("{} 下载中断", name))? {
+            file.write_all(&chunk).await.context("写入文件失败")?;
+            downloaded += chunk.len() as u64;
+            if downloaded - reported >= PROGRESS_INTERVAL {
+                reported = downloaded;
+                self.send_progress(progress.update(name, downloaded, total)).await;
+            }
+        }
+        file.flush().await.context("刷新文件失败")?;
+        drop(file);
 
-        Ok(())
+        if total > 0 && downloaded != total {
+            return Err(anyhow::anyhow!("{} 下载不完整: {}/{} 字节", name, downloaded, total));
+        }
+        if reported != downloaded {
+            self.send_progress(progress.update(name, downloaded, total)).await;
+        }
+
+        tokio::fs::rename(&partial, dest)
+            .await
+            .with_context(|| format!("无法移动文件: {:?}", partial))?;
+        let _ = std::fs::remove_file(&validator_path);
+
+        log::info!("{} 下载完成 ({:.2} MB)", name, downloaded as f64 / 1024.0 / 1024.0);
+        Ok(Some(downloaded - offset))
     }
 
-    fn install_new_database(&self, temp_dir: &Path) -> Result<(), anyhow::Error> {
-        log::info!("正在安装新病毒库...");
+    async fn send_progress(&self, (downloaded, total): (u64, u64)) {
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(UpdateEvent::Progress(downloaded, total)).await;
+        }
+    }
 
-        for file in &["main.cvd", "daily.cvd", "bytecode.cvd"] {
-            let src = temp_dir.join(file);
-            let dst = self.local_database_path.join(file);
+    // 返回 Some(下载字节数) 表示已完成，不需要下载完整病毒库；本地已是最新时同样返回 Some(0)。
+    // 本地没有该病毒库或版本差距超过上限时返回 None
+    async fn try_incremental_update(
+        &self,
+        client: &reqwest::Client,
+        database: &str,
+        temp_dir: &Path,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let Some((local_path, local)) = self.local_database(database) else {
+            return Ok(None);
+        };
+        let remote = self.remote_header(client, database).await?;
+        if remote.version <= local.version {
+            log::info!("{} 已是最新版本: {}", database, local.version);
+            return Ok(Some(0));
+        }
+        let gap = remote.version - local.version;
+        if gap > self.cdiff.max_version_gap {
+            log::info!("{} 落后 {} 个版本，下载完整病毒库", database, gap);
+            return Ok(None);
+        }
 
-            if src.exists() {
-                std::fs::copy(&src, &dst)
-                    .with_context(|| format!("无法安装 {}", file))?;
-                log::info!("已安装: {:?}", dst);
+        let data = std::fs::read(&local_path).with_context(|| format!("无法读取 {:?}", local_path))?;
+        let mut unpacked = UnpackedDatabase::from_bytes(&data)?;
+        let mut download_size = 0u64;
+        for version in local.version + 1..=remote.version {
+            let url = format!("{}/{}-{}.cdiff", self.mirror_url, database, version);
+            log::info!("正在下载增量更新 {}-{}.cdiff...", database, version);
+            let bytes = client
+                .get(&url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .with_context(|| format!("无法下载 {}", url))?
+                .bytes()
+                .await
+                .context("下载失败")?;
+            download_size += bytes.len() as u64;
+            if self.verify_signatures {
+                dsig::verify_cdiff(&bytes).with_context(|| format!("{}-{}.cdiff 校验失败", database, version))?;
             }
+            let script = CdiffScript::parse(&bytes)?;
+            unpacked.apply(&script, version)?;
         }
 
+        let file_path = temp_dir.join(format!("{}.cld", database));
+        let cld = unpacked.to_cld()?;
+        ensure_free_space(temp_dir, cld.len() as u64)?;
+        tokio::fs::write(&file_path, cld)
+            .await
+            .with_context(|| format!("无法写入 {:?}", file_path))?;
+        log::info!(
+            "{} 已增量更新到版本 {} ({:.2} KB)",
+            database,
+            remote.version,
+            download_size as f64 / 1024.0
+        );
+        Ok(Some(download_size))
+    }
+
+    // 校验失败时删除下载的文件，不会被安装
+    async fn verify_download(&self, path: &Path) -> Result<(), anyhow::Error> {
+        if !self.verify_signatures {
+            return Ok(());
+        }
+        let data = tokio::fs::read(path).await.with_context(|| format!("无法读取 {:?}", path))?;
+        if let Err(e) = tokio::task::spawn_blocking(move || dsig::verify_cvd(&data)).await? {
+            let _ = std::fs::remove_file(path);
+            return Err(e.context(format!("{:?} 校验失败", path.file_name().unwrap_or_default())));
+        }
         Ok(())
     }
 
+    // 本地病毒库文件及其文件头，.cld 优先
+    fn local_database(&self, database: &str) -> Option<(PathBuf, CvdHeader)> {
+        ["cld", "cvd"].iter().find_map(|extension| {
+            let path = self.local_database_path.join(format!("{}.{}", database, extension));
+            let mut header = vec![0u8; CVD_HEADER_SIZE];
+            std::io::Read::read_exact(&mut std::fs::File::open(&path).ok()?, &mut header).ok()?;
+            let header = CvdHeader::parse(&header).ok()?;
+            Some((path, header))
+        })
+    }
+
+    // 只读取镜像上完整病毒库的文件头获取最新版本
+    async fn remote_header(&self, client: &reqwest::Client, database: &str) -> Result<CvdHeader, anyhow::Error> {
+        let url = format!("{}/{}.cvd", self.mirror_url, database);
+        let mut response = client
+            .get(&url)
+            .header(reqwest::header::RANGE, format!("bytes=0-{}", CVD_HEADER_SIZE - 1))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("无法获取 {} 的版本", database))?;
+
+        let mut header = Vec::with_capacity(CVD_HEADER_SIZE);
+        while header.len() < CVD_HEADER_SIZE {
+            match response.chunk().await.context("下载中断")? {
+                Some(chunk) => header.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        CvdHeader::parse(&header)
+    }
+
+    // 对比新下载的病毒库与当前病毒库中的特征码，未更新的病毒库按文件头中的特征码数量计入总数。
+    // 返回 (新增, 删除, 总数)
+    async fn count_signature_changes(&self, temp_dir: &Path) -> Result<(u32, u32, u32), anyhow::Error> {
+        let mut changed = Vec::new();
+        let mut unchanged_total = 0u64;
+        for database in DATABASE_NAMES {
+            let current = self.local_database(database);
+            let downloaded = ["cvd", "cld"]
+                .iter()
+                .map(|extension| temp_dir.join(format!("{}.{}", database, extension)))
+                .find(|path| path.exists());
+            match downloaded {
+                Some(path) => changed.push((current.map(|(path, _)| path), path)),
+                None => unchanged_total += current.map(|(_, header)| header.signature_count).unwrap_or(0),
+            }
+        }
+
+        let (added, removed, total) = tokio::task::spawn_blocking(move || {
+            changed
+                .iter()
+                .map(|(current, downloaded)| diff_signatures(current.as_deref(), downloaded))
+                .fold((0, 0, 0), |totals, counts| (totals.0 + counts.0, totals.1 + counts.1, totals.2 + counts.2))
+        })
+        .await?;
+
+        let clamp = |count: u64| u32::try_from(count).unwrap_or(u32::MAX);
+        Ok((clamp(added), clamp(removed), clamp(total + unchanged_total)))
+    }
+
+    fn current_database_size(&self) -> u64 {
+        walkdir::WalkDir::new(&self.local_database_path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .sum()
+    }
+
+    // 返回新备份的 ID，没有需要备份的文件时返回 None
+    fn backup_current_database(&self) -> Result<Option<String>, anyhow::Error> {
+        log::info!("正在备份当前病毒库...");
+
+        if let Err(e) = std::fs::create_dir_all(&self.backup_path) {
+            log::warn!("无法创建备份目录: {}", e);
+        }
+
+        let mut index = BackupIndex::load(&self.backup_path)?;
+        let id = index.next_id();
+        let backup_file = self.backup_path.join(backup::backup_file_name(&id));
+
+        let files = backup::create_backup(&self.local_database_path, &backup_file).context("备份失败")?;
+        if files == 0 {
+            log::info!("没有需要备份的病毒库文件");
+            return Ok(None);
+        }
+        log::info!("备份已创建: {:?} ({} 个文件)", backup_file, files);
+
+        let versions = DATABASE_NAMES
+            .iter()
+            .filter_map(|database| Some((database.to_string(), self.local_database(database)?.1.version)))
+            .collect();
+        index.push(BackupEntry {
+            id: id.clone(),
+            created_at: Utc::now(),
+            versions,
+            files,
+            size: std::fs::metadata(&backup_file).map(|m| m.len()).unwrap_or(0),
+        });
+        for entry in index.prune(&self.backup_retention, Utc::now())? {
+            log::info!("已删除过期备份: {}", entry.id);
+        }
+        index.save()?;
+        Ok(Some(id))
+    }
+
+    // 返回安装的文件
+    fn install_new_database(&self, temp_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+        log::info!("正在安装新病毒库...");
+
+        let mut installed = Vec::new();
+        for database in DATABASE_NAMES {
+            for (extension, stale) in [("cvd", "cld"), ("cld", "cvd")] {
+                let file = format!("{}.{}", database, extension);
+                let src = temp_dir.join(&file);
+                let dst = self.local_database_path.join(&file);
+
+                if src.exists() {
+                    std::fs::copy(&src, &dst)
+                        .with_context(|| format!("无法安装 {}", file))?;
+                    log::info!("已安装: {:?}", dst);
+                    installed.push(dst);
+
+                    // 同一病毒库只保留一个文件，避免重复加载
+                    let stale = self.local_database_path.join(format!("{}.{}", database, stale));
+                    if stale.exists() {
+                        std::fs::remove_file(&stale)
+                            .with_context(|| format!("无法删除旧病毒库 {:?}", stale))?;
+                    }
+                }
+            }
+        }
+
+        Ok(installed)
+    }
+
+    // 当前病毒库加载后的特征码数量，没有病毒库或无法加载时返回 None
+    async fn loaded_signature_count(&self) -> Option<usize> {
+        SignatureDatabase::new()
+            .reload_from_directory(&self.local_database_path)
+            .await
+            .ok()
+    }
+
+    // 在独立的 SignatureDatabase 中加载新安装的病毒库并运行 EICAR 自检，不影响正在使用的病毒库。
+    // 特征码数量少于更新前时视为下载的病毒库不完整
+    async fn validate_installed_database(&self, previous_count: Option<usize>) -> Result<usize, anyhow::Error> {
+        log::info!("正在验证新病毒库...");
+
+        let scratch = Arc::new(SignatureDatabase::new());
+        let loaded = scratch
+            .reload_from_directory(&self.local_database_path)
+            .await
+            .context("新病毒库无法加载")?;
+        if let Some(previous) = previous_count.filter(|&previous| loaded < previous) {
+            return Err(anyhow::anyhow!("新病毒库的特征码数量 {} 少于更新前的 {}", loaded, previous));
+        }
+
+        let workspace = tempfile::Builder::new().prefix("virus-scanner-validate").tempdir()?;
+        let quarantine = QuarantineManager::new(workspace.path().join("quarantine"), None);
+        let report = run_selftest(scratch, validation_scan_options(), &quarantine).await?;
+        if !report.passed() {
+            let failures: Vec<String> = report
+                .checks
+                .iter()
+                .filter(|check| !check.passed)
+                .map(|check| format!("{}: {}", check.name, check.detail))
+                .collect();
+            return Err(anyhow::anyhow!("EICAR 自检失败: {}", failures.join("; ")));
+        }
+
+        log::info!("新病毒库验证通过，特征码数量: {}", loaded);
+        Ok(loaded)
+    }
+
     async fn get_latest_version(&self) -> Result<String, anyhow::Error> {
         let status = self.status.lock().unwrap();
         Ok(status.latest_version.clone())
@@ -296,36 +749,60 @@ impl DatabaseUpdater {
         status
     }
 
+    // 按时间从旧到新排列，重启后仍然保留
     pub fn get_update_history(&self) -> Vec<UpdateInfo> {
         self.update_history.lock().unwrap().clone()
     }
 
-    pub async fn rollback(&self, version: &str) -> Result<(), anyhow::Error> {
-        log::info!("正在回滚到版本: {}", version);
+    // 更新已经完成，历史保存失败只记录警告
+    fn record_history(&self, update_info: &UpdateInfo) {
+        let mut history = self.update_history.lock().unwrap();
+        history.push(update_info.clone());
+        if history.len() > MAX_HISTORY {
+            let excess = history.len() - MAX_HISTORY;
+            history.drain(..excess);
+        }
+        let path = self.local_database_path.join(HISTORY_FILE);
+        let result = serde_json::to_vec_pretty(&*history)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| std::fs::write(&path, content).map_err(anyhow::Error::from));
+        if let Err(e) = result {
+            log::warn!("无法保存更新历史 {:?}: {}", path, e);
+        }
+    }
 
-        let backup_file = self
-            .backup_path
-            .join(format!("backup_{}.tar.gz", version));
+    // 按时间从旧到新排列
+    pub fn list_backups(&self) -> Result<Vec<BackupEntry>, anyhow::Error> {
+        Ok(BackupIndex::load(&self.backup_path)?.entries().to_vec())
+    }
 
-        if !backup_file.exists() {
-            return Err(anyhow::anyhow!("备份文件不存在: {:?}", backup_file));
-        }
+    // backup_id 为备份 ID 或 latest
+    pub async fn rollback(&self, backup_id: &str) -> Result<BackupEntry, anyhow::Error> {
+        log::info!("正在回滚到备份: {}", backup_id);
 
-        let mut cmd = std::process::Command::new("tar");
-        cmd.arg("-xzf")
-            .arg(&backup_file)
-            .arg("-C")
-            .arg(self.local_database_path.parent().unwrap_or(Path::new(".")));
+        let (entry, backup_file) = self.find_backup(backup_id)?;
+        let restored = backup::restore_backup(&backup_file, &self.local_database_path).context("回滚失败")?;
 
-        let output = cmd.output()?;
+        log::info!("已成功回滚到备份: {} ({} 个文件)", entry.id, restored);
 
-        if !output.status.success() {
-            return Err(anyhow::anyhow!("回滚失败: {}", String::from_utf8_lossy(&output.stderr)));
-        }
+        Ok(entry)
+    }
 
-        log::info!("已成功回滚到版本: {}", version);
+    // 回滚到该备份时会恢复和删除的文件，不修改病毒库
+    pub fn rollback_plan(&self, backup_id: &str) -> Result<(BackupEntry, RestorePlan), anyhow::Error> {
+        let (entry, backup_file) = self.find_backup(backup_id)?;
+        let plan = backup::plan_restore(&backup_file, &self.local_database_path)?;
+        Ok((entry, plan))
+    }
 
-        Ok(())
+    fn find_backup(&self, backup_id: &str) -> Result<(BackupEntry, PathBuf), anyhow::Error> {
+        let index = BackupIndex::load(&self.backup_path)?;
+        let entry = index
+            .find(backup_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("备份不存在: {}", backup_id))?;
+        let backup_file = index.path(&entry);
+        Ok((entry, backup_file))
     }
 
     pub async fn check_and_auto_download(&self, config: &UpdateConfig) -> Result<bool, anyhow::Error> {
@@ -342,9 +819,10 @@ impl DatabaseUpdater {
             log::warn!("无法创建备份目录: {}", e);
         }
 
-        let database_files = ["main.cvd", "daily.cvd", "bytecode.cvd"];
-        let has_database = database_files.iter().any(|file| {
-            self.local_database_path.join(file).exists()
+        let has_database = DATABASE_NAMES.iter().any(|database| {
+            ["cvd", "cld"]
+                .iter()
+                .any(|extension| self.local_database_path.join(format!("{}.{}", database, extension)).exists())
         });
 
         if !has_database {
@@ -422,6 +900,91 @@ impl DatabaseUpdater {
     }
 }
 
+// 自检只扫描自己的测试目录，扫描路径等由 run_selftest 设置
+fn validation_scan_options() -> ScanOptions {
+    ScanOptions {
+        scan_mode: ScanMode::Custom,
+        custom_paths: vec![],
+        exclude_paths: vec![],
+        exclude_extensions: vec![],
+        max_file_size: 10 * 1024 * 1024,
+        thread_count: 1,
+        walk_threads: 1,
+        quick_scan_paths: vec![],
+        priority_paths: vec![],
+        use_xattr_markers: false,
+        xattr_marker_key_file: None,
+        archive: Default::default(),
+        heuristics: Default::default(),
+        pdf: Default::default(),
+        mail: Default::default(),
+        skip_benign_types: false,
+        honor_scanignore: false,
+        memory_limit_mb: 0,
+        max_read_mb_per_s: 0,
+        idle_io_priority: false,
+        max_duration: None,
+        action: DetectionAction::Report,
+        auto_quarantine_min_risk: None,
+        dry_run: false,
+    }
+}
+
+// 并行下载时汇总各文件的进度，返回 (已下载, 总大小)；总大小未知的文件只计入已下载
+#[derive(Default)]
+struct DownloadProgress {
+    files: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl DownloadProgress {
+    fn update(&self, name: &str, downloaded: u64, total: u64) -> (u64, u64) {
+        let mut files = self.files.lock().unwrap();
+        files.insert(name.to_string(), (downloaded, total));
+        files
+            .values()
+            .fold((0, 0), |sum, (downloaded, total)| (sum.0 + downloaded, sum.1 + total))
+    }
+}
+
+// 返回 (新增, 删除, 新病毒库特征码数)。无法解析的当前病毒库 (例如早期的自定义格式) 按空病毒库处理
+fn diff_signatures(current: Option<&Path>, downloaded: &Path) -> (u64, u64, u64) {
+    let digests = |path: &Path| -> Result<HashSet<u64>, anyhow::Error> { signature_digests(&std::fs::read(path)?) };
+    let old = match current.map(|path| (path, digests(path))) {
+        Some((_, Ok(old))) => old,
+        Some((path, Err(e))) => {
+            log::warn!("无法解析当前病毒库 {:?}，按空病毒库统计: {}", path, e);
+            HashSet::new()
+        }
+        None => HashSet::new(),
+    };
+    let new = match digests(downloaded) {
+        Ok(new) => new,
+        Err(e) => {
+            log::warn!("无法统计新病毒库 {:?} 的特征码: {}", downloaded, e);
+            return (0, 0, 0);
+        }
+    };
+    let added = new.difference(&old).count() as u64;
+    let removed = old.difference(&new).count() as u64;
+    (added, removed, new.len() as u64)
+}
+
+// 续传时用于确认服务器上的文件未变化，优先使用强 ETag
+fn download_validator(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()))
+        .map(str::to_string)
+}
+
+// Content-Range: bytes 起始-结束/总长度
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(CONTENT_RANGE)?.to_str().ok()?.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
 pub struct UpdateScheduler {
     updater: Arc<DatabaseUpdater>,
     schedule: UpdateSchedule,
@@ -516,3 +1079,6 @@ impl UpdateScheduler {
         false
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,797 @@
+use crate::config::{BackupRetentionConfig, MispConfig};
+use crate::scanner::cvd::{parse_cvd, CvdHeader, CVD_HEADER_SIZE};
+use crate::scanner::{PatternType, SignatureDatabase};
+use crate::update::backup::{backup_file_name, create_backup, restore_backup, BackupIndex};
+use crate::update::cdiff::CdiffCommand;
+use crate::update::dsig::{verify_cdiff, verify_cvd};
+use crate::update::misp::{MispImporter, MISP_SIGNATURE_PREFIX};
+use crate::update::{
+    spawn_signature_reloader, CdiffScript, DatabaseUpdater, UnpackedDatabase, UpdateEvent, UpdateInfo,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> serde_json::Value {
+        serde_json::json!({
+            "Event": {
+                "info": "test event",
+                "Attribute": [
+                    {"uuid": "a1", "type": "md5", "value": "44d88612fea8a8f36de82e1278abb02f", "category": "Payload delivery", "to_ids": true},
+                    {"uuid": "a2", "type": "sha256", "value": "not-a-hash", "category": "Payload delivery", "to_ids": true},
+                    {"uuid": "a3", "type": "ip-dst", "value": "10.0.0.1", "category": "Network activity", "to_ids": true},
+                    {"uuid": "a4", "type": "filename", "value": "evil.exe", "category": "Artifacts dropped", "to_ids": false}
+                ],
+                "Object": [
+                    {"Attribute": [
+                        {"uuid": "b1", "type": "filename|sha1", "value": "dropper.bin|3395856ce81f2b7382dee72602f798b642f14140", "category": "Payload delivery", "to_ids": true, "comment": "dropper"}
+                    ]}
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_nested_attributes() {
+        let attributes = MispImporter::parse_attributes(&sample_event());
+        assert_eq!(attributes.len(), 5);
+    }
+
+    #[test]
+    fn test_convert_attributes_to_signatures() {
+        let importer = MispImporter::new(MispConfig::default());
+        let attributes = MispImporter::parse_attributes(&sample_event());
+        let signatures = importer.to_signatures(&attributes);
+
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(signatures[0].id, format!("{}a1", MISP_SIGNATURE_PREFIX));
+        assert_eq!(signatures[0].pattern_type, PatternType::Hash);
+        assert_eq!(signatures[0].pattern.len(), 16);
+        assert_eq!(signatures[1].name, "MISP.dropper");
+        assert_eq!(signatures[1].pattern.len(), 20);
+
+        let importer = MispImporter::new(MispConfig {
+            only_to_ids: false,
+            ..MispConfig::default()
+        });
+        let signatures = importer.to_signatures(&attributes);
+        assert_eq!(signatures.len(), 2);
+        assert!(signatures.iter().all(|s| s.pattern_type == PatternType::Hash));
+    }
+
+    #[tokio::test]
+    async fn test_import_replaces_previous_feed() {
+        let dir = tempfile::tempdir().unwrap();
+        let feed = dir.path().join("event.json");
+        std::fs::write(&feed, sample_event().to_string()).unwrap();
+
+        let importer = MispImporter::new(MispConfig {
+            feed_path: Some(feed.clone()),
+            ..MispConfig::default()
+        });
+        let db = SignatureDatabase::new();
+
+        assert_eq!(importer.import(&db).await.unwrap(), 2);
+        assert_eq!(importer.import(&db).await.unwrap(), 2);
+        assert_eq!(db.get_signature_count().await, 2);
+    }
+
+    // 早期自定义格式的病毒库：ZIP 包内 main.cvd 为 CSV 记录
+    fn legacy_database(rows: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut csv = String::from("id,name,threat_type,risk_level,pattern,pattern_type,target\n");
+        for (id, pattern) in rows {
+            csv.push_str(&format!("{},{},Trojan,High,{},bytecode,0\n", id, id, hex::encode(pattern)));
+        }
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip_writer.start_file("main.cvd", zip::write::FileOptions::default()).unwrap();
+        zip_writer.write_all(csv.as_bytes()).unwrap();
+        zip_writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_signature_reloader_applies_completed_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.cvd"), legacy_database(&[("Test.Old", b"old-payload")])).unwrap();
+        let db = Arc::new(SignatureDatabase::new());
+        db.load_from_directory(dir.path()).await.unwrap();
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let reloader = spawn_signature_reloader(rx, Arc::clone(&db), dir.path().to_path_buf());
+
+        std::fs::write(dir.path().join("main.cvd"), legacy_database(&[("Test.New", b"new-payload")])).unwrap();
+        tx.send(UpdateEvent::Started).await.unwrap();
+        tx.send(UpdateEvent::Completed(UpdateInfo {
+            version: "2".to_string(),
+            timestamp: chrono::Utc::now(),
+            signatures_added: 1,
+            signatures_removed: 1,
+            total_signatures: 1,
+            download_size: 0,
+        }))
+        .await
+        .unwrap();
+        drop(tx);
+        reloader.await.unwrap();
+
+        assert!(db.scan_bytes(b"old-payload").await.is_none());
+        assert_eq!(db.scan_bytes(b"new-payload").await.unwrap().id, "Test.New");
+        assert!(db.get_last_update().is_some());
+    }
+
+    fn build_cvd(version: u32, files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_bytes()).unwrap();
+        }
+        let body = builder.into_inner().unwrap().finish().unwrap();
+        let md5 = hex::encode(openssl::hash::hash(openssl::hash::MessageDigest::md5(), &body).unwrap());
+        let mut cvd = format!("ClamAV-VDB:16 Oct 2026 08-00 +0000:{}:3:90:{}:dsig:tester:1792137600", version, md5)
+            .into_bytes();
+        cvd.resize(CVD_HEADER_SIZE, b' ');
+        cvd.extend_from_slice(&body);
+        cvd
+    }
+
+    // 带文件头和尾部数字签名的 .cdiff
+    fn build_cdiff(version: u32, script: &str) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(script.as_bytes()).unwrap();
+        let mut cdiff = format!("ClamAV-Diff:{}:0:", version).into_bytes();
+        cdiff.extend(encoder.finish().unwrap());
+        cdiff.extend_from_slice(b":dsig");
+        cdiff
+    }
+
+    // ClamAV 源码中 freshclam 单元测试使用的病毒库，带官方数字签名
+    fn clamav_test_file(name: &str) -> Vec<u8> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("clamav/unit_tests/input/freshclam_testfiles");
+        std::fs::read(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_verify_clamav_signatures() {
+        for version in 1..=6 {
+            let cvd = clamav_test_file(&format!("test-{}.cvd", version));
+            assert_eq!(verify_cvd(&cvd).unwrap().version, version);
+        }
+        for version in 2..=6 {
+            verify_cdiff(&clamav_test_file(&format!("test-{}.cdiff", version))).unwrap();
+        }
+
+        // 修改正文、文件头中的 MD5 或签名都无法通过校验
+        let mut cvd = clamav_test_file("test-1.cvd");
+        let last = cvd.len() - 1;
+        cvd[last] ^= 1;
+        assert!(verify_cvd(&cvd).is_err());
+        let cvd = clamav_test_file("test-1.cvd");
+        let body_md5 = hex::encode(openssl::hash::hash(openssl::hash::MessageDigest::md5(), b"forged").unwrap());
+        let header = String::from_utf8_lossy(&cvd[..CVD_HEADER_SIZE]).to_string();
+        let original_md5 = header.split(':').nth(5).unwrap().to_string();
+        let mut forged = header.replace(&original_md5, &body_md5).into_bytes();
+        forged.extend_from_slice(b"forged");
+        assert!(verify_cvd(&forged).is_err());
+        assert!(verify_cvd(&build_cvd(1, &[("daily.ndb", BASE_NDB)])).is_err());
+
+        let mut cdiff = clamav_test_file("test-2.cdiff");
+        cdiff[20] ^= 1;
+        assert!(verify_cdiff(&cdiff).is_err());
+        assert!(verify_cdiff(&build_cdiff(2, "OPEN daily.ndb\nCLOSE\n")).is_err());
+        let mut unsigned = build_cdiff(2, "OPEN daily.ndb\nCLOSE\n");
+        unsigned.truncate(unsigned.len() - b":dsig".len());
+        assert!(verify_cdiff(&unsigned).is_err());
+    }
+
+    const BASE_NDB: &str = "Test.A-1:0:*:616161\nTest.B-1:0:*:626262\nTest.C-1:0:*:636363\n";
+
+    #[test]
+    fn test_parse_cdiff_script() {
+        let script = CdiffScript::parse(&build_cdiff(
+            7,
+            "# comment\nOPEN daily.ndb\nADD Test.D-1:0:*:646464\nDEL 2 Test.B-1\nXCHG 3 Test.C-1 Test.C-2:0:*:63636363\nCLOSE\nUNLINK daily.fp\n",
+        ))
+        .unwrap();
+        assert_eq!(script.version, Some(7));
+        assert_eq!(script.commands.len(), 6);
+        assert_eq!(
+            script.commands[3],
+            CdiffCommand::Xchg {
+                line: 3,
+                prefix: "Test.C-1".to_string(),
+                replacement: "Test.C-2:0:*:63636363".to_string(),
+            }
+        );
+
+        assert!(CdiffScript::parse(&build_cdiff(7, "OPEN ../etc/passwd\n")).is_err());
+        assert!(CdiffScript::parse(&build_cdiff(7, "DEL 0 Test\n")).is_err());
+        assert!(CdiffScript::parse(&build_cdiff(7, "TRUNCATE daily.ndb\n")).is_err());
+    }
+
+    #[test]
+    fn test_apply_cdiff_produces_cld() {
+        let base = build_cvd(1, &[("daily.ndb", BASE_NDB), ("daily.hdb", "44d88612fea8a8f36de82e1278abb02f:68:Test.Hash-1\n")]);
+        let mut database = UnpackedDatabase::from_bytes(&base).unwrap();
+
+        let step2 = build_cdiff(
+            2,
+            "OPEN daily.ndb\nDEL 2 Test.B-1\nXCHG 3 Test.C-1 Test.C-2:0:*:63636363\nADD Test.D-1:0:*:646464\nCLOSE\n",
+        );
+        database.apply(&CdiffScript::parse(&step2).unwrap(), 2).unwrap();
+        let step3 = build_cdiff(3, "MOVE daily.ndb main.ndb 1 Test.A-1 1 Test.A-1\nUNLINK daily.hdb\n");
+        database.apply(&CdiffScript::parse(&step3).unwrap(), 3).unwrap();
+
+        assert_eq!(database.header.version, 3);
+        assert_eq!(database.header.signature_count, 3);
+        assert_eq!(database.file("main.ndb").unwrap(), b"Test.A-1:0:*:616161\n");
+        assert!(database.file("daily.hdb").is_none());
+
+        let cvd = parse_cvd(&database.to_cld().unwrap()).unwrap();
+        assert_eq!(cvd.header.version, 3);
+        let mut names: Vec<String> = cvd.signatures.iter().map(|s| s.id.clone()).collect();
+        names.sort();
+        assert_eq!(names, ["Test.A-1", "Test.C-2", "Test.D-1"]);
+        assert!(cvd.hash_signatures.is_empty());
+    }
+
+    #[test]
+    fn test_apply_cdiff_rejects_mismatches() {
+        let base = build_cvd(1, &[("daily.ndb", BASE_NDB)]);
+
+        // 行内容与脚本不符说明本地病毒库与镜像不一致
+        let mut database = UnpackedDatabase::from_bytes(&base).unwrap();
+        let script = CdiffScript::parse(&build_cdiff(2, "OPEN daily.ndb\nDEL 2 Test.C-1\nCLOSE\n")).unwrap();
+        assert!(database.apply(&script, 2).is_err());
+
+        // 版本不连续
+        let script = CdiffScript::parse(&build_cdiff(3, "OPEN daily.ndb\nADD Test.D-1:0:*:646464\nCLOSE\n")).unwrap();
+        assert!(database.apply(&script, 3).is_err());
+        assert!(database.apply(&script, 2).is_err());
+
+        let script = CdiffScript::parse(&build_cdiff(2, "OPEN daily.ndb\nADD Test.D-1:0:*:646464\n")).unwrap();
+        assert!(database.apply(&script, 2).is_err());
+    }
+
+    const ETAG: &str = "\"v1\"";
+
+    // 按路径返回固定内容，其余请求返回 404。If-Range 与 ETAG 相同时按 Range 返回 206；
+    // cut 为 Some(n) 时只发送文件前 n 字节以内的部分后断开连接，每个路径的前 failures 次请求返回 503。
+    // 返回地址和收到的请求
+    async fn serve_files(
+        files: HashMap<String, Vec<u8>>,
+        cut: Option<usize>,
+        failures: usize,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        tokio::spawn(async move {
+            let mut served: HashMap<String, usize> = HashMap::new();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let n = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+                log.lock().unwrap().push(request.clone());
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let header_value = |name: &str| {
+                    request
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name).map(|v| v.trim().to_string()))
+                };
+                let range_start = header_value("range: bytes=")
+                    .filter(|_| header_value("if-range:").as_deref() == Some(ETAG))
+                    .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+
+                let count = served.entry(path.clone()).or_default();
+                *count += 1;
+                let end = |len: usize| cut.unwrap_or(len).min(len);
+
+                let (status, extra, body, sent) = match (files.get(&path), range_start) {
+                    (Some(_), _) if *count <= failures => ("503 Service Unavailable", String::new(), Vec::new(), 0),
+                    (Some(body), Some(start)) => (
+                        "206 Partial Content",
+                        format!("Content-Range: bytes {}-{}/{}\r\n", start, body.len() - 1, body.len()),
+                        body[start..].to_vec(),
+                        end(body.len()).saturating_sub(start),
+                    ),
+                    (Some(body), None) => ("200 OK", String::new(), body.clone(), end(body.len())),
+                    (None, _) => ("404 Not Found", String::new(), Vec::new(), 0),
+                };
+                let header = format!(
+                    "HTTP/1.1 {}\r\nETag: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    ETAG,
+                    extra,
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.write_all(&body[..sent]).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (format!("http://{}", addr), requests)
+    }
+
+    #[tokio::test]
+    async fn test_perform_update_applies_cdiffs() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        std::fs::write(database_path.join("daily.cvd"), build_cvd(1, &[("daily.ndb", BASE_NDB)])).unwrap();
+
+        let remote_ndb = "Test.A-1:0:*:616161\nTest.B-1:0:*:626262\nTest.C-1:0:*:636363\nTest.D-1:0:*:646464\nTest.E-1:0:*:656565\n";
+        let files = HashMap::from([
+            ("/daily.cvd".to_string(), build_cvd(3, &[("daily.ndb", remote_ndb)])),
+            ("/daily-2.cdiff".to_string(), build_cdiff(2, "OPEN daily.ndb\nADD Test.D-1:0:*:646464\nCLOSE\n")),
+            ("/daily-3.cdiff".to_string(), build_cdiff(3, "OPEN daily.ndb\nADD Test.E-1:0:*:656565\nCLOSE\n")),
+        ]);
+        let (mirror, _) = serve_files(files, None, 0).await;
+
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据没有数字签名
+        updater.set_verify_signatures(false);
+        let info = updater.perform_update().await.unwrap();
+
+        assert!(!database_path.join("daily.cvd").exists());
+        let cvd = parse_cvd(&std::fs::read(database_path.join("daily.cld")).unwrap()).unwrap();
+        assert_eq!(cvd.header.version, 3);
+        assert_eq!(cvd.signatures.len(), 5);
+        assert_eq!((info.signatures_added, info.signatures_removed, info.total_signatures), (2, 0, 5));
+        assert!(info.download_size > 0);
+        // 增量更新只下载 .cdiff，不下载完整病毒库
+        assert!(info.download_size < std::fs::metadata(database_path.join("daily.cld")).unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_perform_update_falls_back_to_full_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        std::fs::write(database_path.join("daily.cvd"), build_cvd(1, &[("daily.ndb", BASE_NDB)])).unwrap();
+
+        // 缺少 daily-3.cdiff 时改为下载完整的 daily.cvd
+        let remote = build_cvd(3, &[("daily.ndb", "Test.Z-1:0:*:7a7a7a\n")]);
+        let files = HashMap::from([
+            ("/daily.cvd".to_string(), remote.clone()),
+            ("/daily-2.cdiff".to_string(), build_cdiff(2, "OPEN daily.ndb\nADD Test.D-1:0:*:646464\nCLOSE\n")),
+        ]);
+        let (mirror, _) = serve_files(files, None, 0).await;
+
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        updater.set_event_tx(tx);
+        updater.perform_update().await.unwrap();
+
+        assert!(!database_path.join("daily.cld").exists());
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), remote);
+
+        // 旧版本的 3 条特征码全部删除，新增 1 条
+        let history = updater.get_update_history();
+        assert_eq!((history[0].signatures_added, history[0].signatures_removed, history[0].total_signatures), (1, 3, 1));
+        // 历史保存在病毒库目录中，重新创建更新器后仍然可见
+        let reloaded = DatabaseUpdater::new(String::new(), database_path.clone(), dir.path().join("backup"));
+        assert_eq!(reloaded.get_update_history().len(), 1);
+        assert_eq!(reloaded.get_update_history()[0].total_signatures, 1);
+        drop(updater);
+        let mut completed = None;
+        while let Some(event) = rx.recv().await {
+            if let UpdateEvent::Completed(info) = event {
+                completed = Some(info);
+            }
+        }
+        assert_eq!(completed.unwrap().signatures_removed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_perform_update_verifies_signatures() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        std::fs::write(database_path.join("daily.cvd"), clamav_test_file("test-1.cvd")).unwrap();
+
+        let files = HashMap::from([
+            ("/daily.cvd".to_string(), clamav_test_file("test-3.cvd")),
+            ("/daily-2.cdiff".to_string(), clamav_test_file("test-2.cdiff")),
+            ("/daily-3.cdiff".to_string(), clamav_test_file("test-3.cdiff")),
+        ]);
+        let (mirror, _) = serve_files(files, None, 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试病毒库不含 EICAR 特征码
+        updater.set_validate_after_update(false);
+        updater.perform_update().await.unwrap();
+        let cld = std::fs::read(database_path.join("daily.cld")).unwrap();
+        assert_eq!(CvdHeader::parse(&cld).unwrap().version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_perform_update_rejects_unsigned_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let current = build_cvd(1, &[("daily.ndb", BASE_NDB)]);
+        std::fs::write(database_path.join("daily.cvd"), &current).unwrap();
+
+        // 未签名的增量更新被拒绝后改为完整下载，完整病毒库同样没有签名
+        let files = HashMap::from([
+            ("/daily.cvd".to_string(), build_cvd(2, &[("daily.ndb", "Test.Z-1:0:*:7a7a7a\n")])),
+            ("/daily-2.cdiff".to_string(), build_cdiff(2, "OPEN daily.ndb\nADD Test.D-1:0:*:646464\nCLOSE\n")),
+        ]);
+        let (mirror, requests) = serve_files(files, None, 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        updater.set_validate_after_update(false);
+
+        let error = updater.perform_update().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("数字签名"));
+        assert!(requests.lock().unwrap().iter().any(|r| r.starts_with("get /daily-2.cdiff")));
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), current);
+        assert!(!database_path.join("daily.cld").exists());
+        assert!(updater.get_update_history().is_empty());
+    }
+
+    fn download_payload() -> Vec<u8> {
+        (0..(3 * 1024 * 1024 + 512 * 1024)).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_download_streams_with_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let payload = download_payload();
+        let (mirror, _) = serve_files(HashMap::from([("/daily.cvd".to_string(), payload.clone())]), None, 0).await;
+
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        updater.set_event_tx(tx);
+        let info = updater.perform_update().await.unwrap();
+        drop(updater);
+
+        let mut progress = Vec::new();
+        while let Some(event) = rx.recv().await {
+            if let UpdateEvent::Progress(downloaded, total) = event {
+                progress.push((downloaded, total));
+            }
+        }
+        let size = payload.len() as u64;
+        assert!(progress.len() >= 3);
+        assert!(progress.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(progress.last(), Some(&(size, size)));
+        assert_eq!(info.download_size, size);
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), payload);
+        assert!(!database_path.join("daily.cvd.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_checks_space_for_announced_size() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 镜像声明的文件大小超过可用空间，不发送正文
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let n = stream.read(&mut request).await.unwrap_or(0);
+                log.lock().unwrap().push(String::from_utf8_lossy(&request[..n]).to_lowercase());
+                let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", u64::MAX / 4);
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let mut updater = DatabaseUpdater::new(format!("http://{}", addr), database_path.clone(), dir.path().join("backup"));
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+
+        let error = updater.perform_update().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("磁盘空间不足"));
+        // 空间不足时不重试，也不留下 .part 文件
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.iter().filter(|r| r.starts_with("get /daily.cvd")).count(), 1);
+        assert!(!database_path.join("daily.cvd.part").exists());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let payload = download_payload();
+        let files = HashMap::from([("/daily.cvd".to_string(), payload.clone())]);
+
+        // 连接总在传输到一半时断开，重试后更新仍失败但保留已下载的部分
+        let (mirror, _) = serve_files(files.clone(), Some(payload.len() / 2), 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        assert!(updater.perform_update().await.is_err());
+        let status = updater.get_status();
+        assert!(!status.in_progress);
+        assert!(status.error.is_some());
+        assert_eq!(
+            std::fs::metadata(database_path.join("daily.cvd.part")).unwrap().len(),
+            payload.len() as u64 / 2
+        );
+
+        let (mirror, requests) = serve_files(files, None, 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        let info = updater.perform_update().await.unwrap();
+
+        let range = format!("range: bytes={}-", payload.len() / 2);
+        assert!(requests.lock().unwrap().iter().any(|r| r.contains(&range)));
+        assert_eq!(info.download_size, (payload.len() - payload.len() / 2) as u64);
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), payload);
+        assert!(!database_path.join("daily.cvd.part").exists());
+        assert!(!database_path.join("daily.cvd.part.validator").exists());
+    }
+
+    #[tokio::test]
+    async fn test_stale_partial_download_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        std::fs::write(database_path.join("daily.cvd.part"), b"stale partial content").unwrap();
+        std::fs::write(database_path.join("daily.cvd.part.validator"), "\"v0\"").unwrap();
+
+        let payload = download_payload();
+        let (mirror, _) = serve_files(HashMap::from([("/daily.cvd".to_string(), payload.clone())]), None, 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        updater.perform_update().await.unwrap();
+
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), payload);
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_rollback_without_external_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        let backup_path = dir.path().join("backup");
+        std::fs::create_dir_all(database_path.join(".tmp-update")).unwrap();
+        std::fs::write(database_path.join("main.cvd"), b"main-v1").unwrap();
+        std::fs::write(database_path.join("daily.cvd"), b"daily-v1").unwrap();
+        std::fs::write(database_path.join("bytecode.cvd.part"), b"partial").unwrap();
+        std::fs::write(database_path.join(".tmp-update/daily.cvd"), b"in-progress").unwrap();
+
+        let updater = DatabaseUpdater::new(String::new(), database_path.clone(), backup_path.clone());
+        let version = updater.backup_current_database().unwrap().unwrap();
+        assert!(backup_path.join(backup_file_name(&version)).is_file());
+
+        std::fs::write(database_path.join("main.cvd"), b"main-v2").unwrap();
+        std::fs::remove_file(database_path.join("daily.cvd")).unwrap();
+        std::fs::write(database_path.join("daily.cld"), b"daily-v2").unwrap();
+
+        let (entry, plan) = updater.rollback_plan(&version).unwrap();
+        assert_eq!(entry.id, version);
+        let mut restore = plan.restore.clone();
+        restore.sort();
+        assert_eq!(restore, vec![database_path.join("daily.cvd"), database_path.join("main.cvd")]);
+        assert_eq!(plan.remove, vec![database_path.join("daily.cld")]);
+        assert_eq!(std::fs::read(database_path.join("main.cvd")).unwrap(), b"main-v2");
+
+        updater.rollback(&version).await.unwrap();
+        assert_eq!(std::fs::read(database_path.join("main.cvd")).unwrap(), b"main-v1");
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), b"daily-v1");
+        assert!(!database_path.join("daily.cld").exists());
+        assert!(database_path.join("bytecode.cvd.part").exists());
+        assert!(updater.rollback("19700101_000000").await.is_err());
+    }
+
+    #[test]
+    fn test_restore_ignores_entries_outside_database_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_file = dir.path().join("backup.tar.gz");
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            std::fs::File::create(&backup_file).unwrap(),
+            flate2::Compression::default(),
+        ));
+        for (name, content) in [("database/main.cvd", &b"main"[..]), ("database/../../escape.cvd", b"escape")] {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let database_path = dir.path().join("nested").join("database");
+        assert_eq!(restore_backup(&backup_file, &database_path).unwrap(), 1);
+        assert_eq!(std::fs::read(database_path.join("main.cvd")).unwrap(), b"main");
+        assert!(!dir.path().join("escape.cvd").exists());
+
+        // 没有病毒库文件时不生成备份
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(create_backup(empty.path(), &dir.path().join("empty.tar.gz")).unwrap(), 0);
+        assert!(!dir.path().join("empty.tar.gz").exists());
+    }
+
+    fn write_backups(dir: &std::path::Path, ages_in_days: &[i64]) -> Vec<String> {
+        let now = chrono::Local::now();
+        ages_in_days
+            .iter()
+            .map(|days| {
+                let id = (now - chrono::Duration::days(*days)).format("%Y%m%d_%H%M%S").to_string();
+                std::fs::write(dir.join(backup_file_name(&id)), b"backup").unwrap();
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_backup_retention_prunes_old_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = write_backups(dir.path(), &[40, 3, 2, 1]);
+
+        let mut index = BackupIndex::load(dir.path()).unwrap();
+        assert_eq!(index.entries().iter().map(|e| e.id.clone()).collect::<Vec<_>>(), ids);
+        assert_eq!(index.find("latest").unwrap().id, ids[3]);
+
+        let by_age = BackupRetentionConfig { keep: 0, max_age_days: 30 };
+        let removed = index.prune(&by_age, chrono::Utc::now()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, ids[0]);
+        assert!(!dir.path().join(backup_file_name(&ids[0])).exists());
+
+        let by_count = BackupRetentionConfig { keep: 2, max_age_days: 0 };
+        index.prune(&by_count, chrono::Utc::now()).unwrap();
+        index.save().unwrap();
+        let index = BackupIndex::load(dir.path()).unwrap();
+        assert_eq!(index.entries().iter().map(|e| e.id.clone()).collect::<Vec<_>>(), &ids[2..]);
+
+        // 最新的备份即使超过保留天数也不删除
+        let dir = tempfile::tempdir().unwrap();
+        write_backups(dir.path(), &[100]);
+        let mut index = BackupIndex::load(dir.path()).unwrap();
+        assert!(index.prune(&by_age, chrono::Utc::now()).unwrap().is_empty());
+        assert_eq!(index.entries().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_backups_and_rollback_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        let backup_path = dir.path().join("backup");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let mut updater = DatabaseUpdater::new(String::new(), database_path.clone(), backup_path.clone());
+        updater.set_backup_retention(BackupRetentionConfig { keep: 2, max_age_days: 0 });
+
+        for version in 1..=3 {
+            std::fs::write(database_path.join("daily.cvd"), build_cvd(version, &[("daily.ndb", BASE_NDB)])).unwrap();
+            updater.backup_current_database().unwrap();
+        }
+
+        let backups = updater.list_backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].versions.get("daily"), Some(&2));
+        assert_eq!(backups[1].versions.get("daily"), Some(&3));
+        assert_eq!(backups[1].files, 1);
+
+        std::fs::write(database_path.join("daily.cvd"), build_cvd(4, &[("daily.ndb", BASE_NDB)])).unwrap();
+        let restored = updater.rollback("latest").await.unwrap();
+        assert_eq!(restored.id, backups[1].id);
+        let header = UnpackedDatabase::from_bytes(&std::fs::read(database_path.join("daily.cvd")).unwrap())
+            .unwrap()
+            .header;
+        assert_eq!(header.version, 3);
+
+        updater.rollback(&backups[0].id).await.unwrap();
+        assert!(updater.rollback("no-such-backup").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_downloads_retry_and_aggregate_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let payload = download_payload();
+        let files = HashMap::from([
+            ("/main.cvd".to_string(), payload.clone()),
+            ("/daily.cvd".to_string(), payload[..1024 * 1024].to_vec()),
+            ("/bytecode.cvd".to_string(), payload[..4096].to_vec()),
+        ]);
+        // 每个文件的第一次请求返回 503，重试后成功
+        let (mirror, requests) = serve_files(files, None, 1).await;
+
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据不是有效的病毒库，也没有数字签名
+        updater.set_verify_signatures(false);
+        updater.set_validate_after_update(false);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+        updater.set_event_tx(tx);
+        let info = updater.perform_update().await.unwrap();
+        drop(updater);
+
+        let expected = (payload.len() + 1024 * 1024 + 4096) as u64;
+        assert_eq!(info.download_size, expected);
+        assert_eq!(std::fs::read(database_path.join("main.cvd")).unwrap(), payload);
+        assert_eq!(std::fs::metadata(database_path.join("daily.cvd")).unwrap().len(), 1024 * 1024);
+        assert_eq!(std::fs::metadata(database_path.join("bytecode.cvd")).unwrap().len(), 4096);
+        assert_eq!(requests.lock().unwrap().len(), 6);
+
+        let mut last = None;
+        while let Some(event) = rx.recv().await {
+            if let UpdateEvent::Progress(downloaded, total) = event {
+                assert!(downloaded <= total);
+                last = Some((downloaded, total));
+            }
+        }
+        assert_eq!(last, Some((expected, expected)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_validation_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+        let current = build_cvd(1, &[("daily.ndb", BASE_NDB)]);
+        std::fs::write(database_path.join("daily.cvd"), &current).unwrap();
+
+        // 新版本只剩 1 条特征码，少于当前的 3 条
+        let files = HashMap::from([("/daily.cvd".to_string(), build_cvd(3, &[("daily.ndb", "Test.Z-1:0:*:7a7a7a\n")]))]);
+        let (mirror, _) = serve_files(files, None, 0).await;
+        let mut updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+        // 测试数据没有数字签名
+        updater.set_verify_signatures(false);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        updater.set_event_tx(tx);
+
+        let error = updater.perform_update().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("少于更新前"));
+        assert_eq!(std::fs::read(database_path.join("daily.cvd")).unwrap(), current);
+        assert!(updater.get_update_history().is_empty());
+        assert!(!updater.get_status().in_progress);
+        drop(updater);
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        assert!(events.iter().any(|e| matches!(e, UpdateEvent::Failed(_))));
+        assert!(!events.iter().any(|e| matches!(e, UpdateEvent::Completed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_first_download_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("database");
+        std::fs::create_dir_all(&database_path).unwrap();
+
+        let files = HashMap::from([("/daily.cvd".to_string(), b"not a database".to_vec())]);
+        let (mirror, _) = serve_files(files, None, 0).await;
+        let updater = DatabaseUpdater::new(mirror, database_path.clone(), dir.path().join("backup"));
+
+        assert!(updater.perform_update().await.is_err());
+        assert!(!database_path.join("daily.cvd").exists());
+    }
+}
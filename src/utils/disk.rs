@@ -0,0 +1,70 @@
+use crate::utils::format_bytes;
+use std::path::{Path, PathBuf};
+
+// 在所需空间之外预留的余量，避免把文件系统写满
+pub const FREE_SPACE_RESERVE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DiskSpaceError {
+    Insufficient {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+    Unavailable {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for DiskSpaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiskSpaceError::Insufficient { path, required, available } => write!(
+                f,
+                "磁盘空间不足: {:?} 需要 {}，可用 {}",
+                path,
+                format_bytes(*required),
+                format_bytes(*available)
+            ),
+            DiskSpaceError::Unavailable { path, reason } => {
+                write!(f, "无法获取磁盘空间信息 {:?}: {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiskSpaceError {}
+
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.exists())
+}
+
+pub fn available_space(path: &Path) -> Result<u64, DiskSpaceError> {
+    let target = existing_ancestor(path).ok_or_else(|| DiskSpaceError::Unavailable {
+        path: path.to_path_buf(),
+        reason: "路径不存在".to_string(),
+    })?;
+
+    let stat = nix::sys::statvfs::statvfs(target).map_err(|e| DiskSpaceError::Unavailable {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+pub fn ensure_free_space(path: &Path, required: u64) -> Result<(), DiskSpaceError> {
+    let available = available_space(path)?;
+    let required = required.saturating_add(FREE_SPACE_RESERVE);
+
+    if available < required {
+        return Err(DiskSpaceError::Insufficient {
+            path: path.to_path_buf(),
+            required,
+            available,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+const DEFAULT_SMOOTHING: f64 = 0.3;
+
+pub fn format_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+
+    if total_secs == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+
+    let days = total_secs / 86400;
+    let hours = (total_secs % 86400) / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn format_duration_secs(secs: u64) -> String {
+    format_duration(Duration::from_secs(secs))
+}
+
+pub struct EtaEstimator {
+    total: u64,
+    done: u64,
+    smoothing: f64,
+    rate: Option<f64>,
+    last_update: Instant,
+}
+
+impl EtaEstimator {
+    pub fn new(total: u64) -> Self {
+        Self::with_smoothing(total, DEFAULT_SMOOTHING)
+    }
+
+    pub fn with_smoothing(total: u64, smoothing: f64) -> Self {
+        Self {
+            total,
+            done: 0,
+            smoothing: smoothing.clamp(0.01, 1.0),
+            rate: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn set_total(&mut self, total: u64) {
+        self.total = total;
+    }
+
+    pub fn update(&mut self, done: u64) {
+        self.update_at(done, Instant::now());
+    }
+
+    // 以指数移动平均平滑瞬时速率，避免个别大文件导致剩余时间剧烈跳动
+    pub fn update_at(&mut self, done: u64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_update).as_secs_f64();
+        if elapsed <= 0.0 || done < self.done {
+            self.done = done;
+            return;
+        }
+
+        let instant_rate = (done - self.done) as f64 / elapsed;
+        self.rate = Some(match self.rate {
+            Some(rate) => self.smoothing * instant_rate + (1.0 - self.smoothing) * rate,
+            None => instant_rate,
+        });
+
+        self.done = done;
+        self.last_update = now;
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate.unwrap_or(0.0)
+    }
+
+    pub fn done(&self) -> u64 {
+        self.done
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.done as f64 / self.total as f64 * 100.0).min(100.0)
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate?;
+        if self.done >= self.total {
+            return Some(Duration::ZERO);
+        }
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64((self.total - self.done) as f64 / rate))
+    }
+}
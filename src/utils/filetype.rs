@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+const MAGIC_READ_SIZE: usize = 512;
+
+// 只统计常见的邮件头，避免把 "key: value" 格式的普通文本误判为邮件
+const MAIL_HEADERS: [&str; 12] = [
+    "from", "to", "cc", "subject", "date", "received", "return-path", "message-id",
+    "mime-version", "delivered-to", "content-type", "reply-to",
+];
+const MAIL_ORIGIN_HEADERS: [&str; 5] = ["from", "received", "return-path", "message-id", "delivered-to"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(schemars::JsonSchema))]
+pub enum FileKind {
+    Elf,
+    Pe,
+    MachO,
+    Pdf,
+    Zip,
+    Ooxml,
+    Ole2,
+    Gzip,
+    Bzip2,
+    Xz,
+    Tar,
+    Script,
+    Mail,
+    Image,
+    Media,
+    Text,
+    Unknown,
+}
+
+impl FileKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileKind::Elf => "ELF",
+            FileKind::Pe => "PE",
+            FileKind::MachO => "Mach-O",
+            FileKind::Pdf => "PDF",
+            FileKind::Zip => "ZIP",
+            FileKind::Ooxml => "OOXML",
+            FileKind::Ole2 => "OLE2",
+            FileKind::Gzip => "GZIP",
+            FileKind::Bzip2 => "BZIP2",
+            FileKind::Xz => "XZ",
+            FileKind::Tar => "TAR",
+            FileKind::Script => "Script",
+            FileKind::Mail => "Mail",
+            FileKind::Image => "Image",
+            FileKind::Media => "Media",
+            FileKind::Text => "Text",
+            FileKind::Unknown => "Unknown",
+        }
+    }
+
+    pub fn is_executable(&self) -> bool {
+        matches!(self, FileKind::Elf | FileKind::Pe | FileKind::MachO | FileKind::Script)
+    }
+
+    // 图片和音视频格式不含可执行内容，可按配置跳过启发式检测
+    pub fn is_benign(&self) -> bool {
+        matches!(self, FileKind::Image | FileKind::Media)
+    }
+
+    pub fn is_archive(&self) -> bool {
+        matches!(
+            self,
+            FileKind::Zip | FileKind::Ooxml | FileKind::Gzip | FileKind::Bzip2 | FileKind::Xz | FileKind::Tar
+        )
+    }
+
+    // 签名目标支持名称和ClamAV数字两种写法，空值/Generic/any/0表示匹配任意类型。
+    // 无法识别的目标 (HTML、Java 等) 不做类型限制，宁可多报也不丢弃命中
+    pub fn matches_target(&self, target: &str) -> bool {
+        match target.trim().to_lowercase().as_str() {
+            "" | "0" | "any" | "generic" | "*" => true,
+            "1" | "pe" => *self == FileKind::Pe,
+            "2" | "ole2" | "ole" => matches!(self, FileKind::Ole2 | FileKind::Ooxml),
+            "6" | "elf" => *self == FileKind::Elf,
+            "5" | "graphics" | "image" => *self == FileKind::Image,
+            "4" | "mail" => *self == FileKind::Mail,
+            "7" | "ascii" | "text" => matches!(self, FileKind::Text | FileKind::Script | FileKind::Mail),
+            "9" | "macho" | "mach-o" => *self == FileKind::MachO,
+            "10" | "pdf" => *self == FileKind::Pdf,
+            "script" => *self == FileKind::Script,
+            "zip" => matches!(self, FileKind::Zip | FileKind::Ooxml),
+            "archive" => self.is_archive(),
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+pub fn detect_file_type(path: &Path) -> Result<FileKind, anyhow::Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = vec![0u8; MAGIC_READ_SIZE];
+    let mut read = 0;
+
+    while read < buffer.len() {
+        let n = file.read(&mut buffer[read..])?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    buffer.truncate(read);
+
+    Ok(detect_file_type_from_bytes(&buffer))
+}
+
+pub fn detect_file_type_from_bytes(data: &[u8]) -> FileKind {
+    if data.starts_with(b"\x7fELF") {
+        return FileKind::Elf;
+    }
+    if data.starts_with(b"MZ") && is_pe(data) {
+        return FileKind::Pe;
+    }
+    if is_macho(data) {
+        return FileKind::MachO;
+    }
+    if data.starts_with(b"%PDF-") {
+        return FileKind::Pdf;
+    }
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        if is_ooxml(data) {
+            return FileKind::Ooxml;
+        }
+        return FileKind::Zip;
+    }
+    if data.starts_with(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1]) {
+        return FileKind::Ole2;
+    }
+    if data.starts_with(&[0x1f, 0x8b]) {
+        return FileKind::Gzip;
+    }
+    if data.starts_with(b"BZh") {
+        return FileKind::Bzip2;
+    }
+    if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        return FileKind::Xz;
+    }
+    if data.len() >= 262 && &data[257..262] == b"ustar" {
+        return FileKind::Tar;
+    }
+    if data.starts_with(b"#!") || data.starts_with(b"<?php") {
+        return FileKind::Script;
+    }
+    if is_mail(data) {
+        return FileKind::Mail;
+    }
+    if is_image(data) {
+        return FileKind::Image;
+    }
+    if is_media(data) {
+        return FileKind::Media;
+    }
+    if !data.is_empty() && is_text(data) {
+        return FileKind::Text;
+    }
+
+    FileKind::Unknown
+}
+
+fn is_pe(data: &[u8]) -> bool {
+    // DOS头0x3c处为PE头偏移；头部不完整时仍按MZ视为PE
+    if data.len() < 0x40 {
+        return true;
+    }
+    let offset = u32::from_le_bytes([data[0x3c], data[0x3d], data[0x3e], data[0x3f]]) as usize;
+    match data.get(offset..offset + 4) {
+        Some(sig) => sig == b"PE\0\0",
+        None => true,
+    }
+}
+
+fn is_macho(data: &[u8]) -> bool {
+    if data.len() < 4 {
+        return false;
+    }
+    let magic = [data[0], data[1], data[2], data[3]];
+    matches!(
+        magic,
+        [0xfe, 0xed, 0xfa, 0xce]
+            | [0xce, 0xfa, 0xed, 0xfe]
+            | [0xfe, 0xed, 0xfa, 0xcf]
+            | [0xcf, 0xfa, 0xed, 0xfe]
+    ) || (magic == [0xca, 0xfe, 0xba, 0xbe]
+        && data.len() >= 8
+        && u32::from_be_bytes([data[4], data[5], data[6], data[7]]) < 30)
+}
+
+fn is_image(data: &[u8]) -> bool {
+    data.starts_with(b"\x89PNG\r\n\x1a\n")
+        || data.starts_with(&[0xff, 0xd8, 0xff])
+        || data.starts_with(b"GIF87a")
+        || data.starts_with(b"GIF89a")
+        || (data.starts_with(b"BM") && data.len() >= 14)
+        || (data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP"))
+}
+
+fn is_media(data: &[u8]) -> bool {
+    data.starts_with(b"ID3")
+        || data.starts_with(&[0xff, 0xfb])
+        || data.starts_with(b"OggS")
+        || data.starts_with(b"fLaC")
+        || data.starts_with(&[0x1a, 0x45, 0xdf, 0xa3])
+        || data.get(4..8) == Some(b"ftyp")
+        || (data.starts_with(b"RIFF") && matches!(data.get(8..12), Some(b"WAVE") | Some(b"AVI ")))
+}
+
+fn is_ooxml(data: &[u8]) -> bool {
+    const MARKERS: [&[u8]; 4] = [b"[Content_Types].xml", b"word/", b"xl/", b"ppt/"];
+    MARKERS
+        .iter()
+        .any(|marker| data.windows(marker.len()).any(|w| w == *marker))
+}
+
+// mbox 以 "From 发件人 日期" 分隔行开头；单封邮件 (.eml) 以邮件头开头
+fn is_mail(data: &[u8]) -> bool {
+    if data.starts_with(b"From ") {
+        return data.contains(&b'\n');
+    }
+
+    let text = String::from_utf8_lossy(data);
+    let mut lines = text.lines();
+    // 缓冲区末尾的行可能不完整，不参与判断
+    lines.next_back();
+
+    let mut known = 0;
+    let mut has_origin = false;
+    for line in lines {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            continue;
+        }
+        let name = match line.split_once(':') {
+            Some((name, _)) if !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') => {
+                name.to_ascii_lowercase()
+            }
+            _ => return false,
+        };
+        if MAIL_HEADERS.contains(&name.as_str()) {
+            known += 1;
+        }
+        has_origin |= MAIL_ORIGIN_HEADERS.contains(&name.as_str());
+    }
+    known >= 2 && has_origin
+}
+
+fn is_text(data: &[u8]) -> bool {
+    let printable = data
+        .iter()
+        .filter(|&&b| b == b'\n' || b == b'\r' || b == b'\t' || (0x20..0x7f).contains(&b) || b >= 0x80)
+        .count();
+    !data.contains(&0) && printable * 100 / data.len() >= 95
+}
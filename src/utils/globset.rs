@@ -0,0 +1,129 @@
+use ::globset::{Candidate, GlobBuilder, GlobSet, GlobSetBuilder};
+use anyhow::Context;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GlobOptions {
+    pub case_insensitive: bool,
+    pub literal_separator: bool,
+    pub prefix_match: bool,
+}
+
+impl Default for GlobOptions {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            literal_separator: true,
+            prefix_match: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobMatcher {
+    set: GlobSet,
+    patterns: Vec<String>,
+    options: GlobOptions,
+}
+
+impl GlobMatcher {
+    pub fn new<I, S>(patterns: I, options: GlobOptions) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut builder = GlobSetBuilder::new();
+        let mut compiled = Vec::new();
+
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim();
+            if pattern.is_empty() {
+                continue;
+            }
+
+            builder.add(Self::build_glob(pattern, &options)?);
+
+            // 不含通配符的路径按目录前缀处理，与原 starts_with 语义保持一致
+            if options.prefix_match && !Self::is_glob(pattern) {
+                let prefix = format!("{}/**", pattern.trim_end_matches('/'));
+                builder.add(Self::build_glob(&prefix, &options)?);
+            }
+
+            compiled.push(pattern.to_string());
+        }
+
+        let set = builder.build().context("无法编译通配符规则")?;
+
+        Ok(Self {
+            set,
+            patterns: compiled,
+            options,
+        })
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            set: GlobSet::empty(),
+            patterns: Vec::new(),
+            options: GlobOptions::default(),
+        }
+    }
+
+    fn build_glob(pattern: &str, options: &GlobOptions) -> Result<::globset::Glob, anyhow::Error> {
+        GlobBuilder::new(pattern)
+            .case_insensitive(options.case_insensitive)
+            .literal_separator(options.literal_separator)
+            .backslash_escape(true)
+            .build()
+            .with_context(|| format!("无效的通配符规则: {}", pattern))
+    }
+
+    pub fn is_glob(pattern: &str) -> bool {
+        pattern.contains(['*', '?', '[', '{'])
+    }
+
+    pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        self.set.is_match_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    pub fn matching_patterns<P: AsRef<Path>>(&self, path: P) -> Vec<&str> {
+        if self.patterns.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matched: Vec<&str> = Vec::new();
+        let hits = self.set.matches_candidate(&Candidate::new(path.as_ref()));
+        let mut glob_index = 0;
+
+        for pattern in &self.patterns {
+            let globs = if self.options.prefix_match && !Self::is_glob(pattern) { 2 } else { 1 };
+            if (glob_index..glob_index + globs).any(|i| hits.contains(&i)) {
+                matched.push(pattern.as_str());
+            }
+            glob_index += globs;
+        }
+
+        matched
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+}
+
+impl Default for GlobMatcher {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+// 消息目录随程序编译，每种语言一个 YAML 文件，键为 模块.消息，值中的 {name} 为参数
+const ZH_CATALOG: &str = include_str!("../../locales/zh.yaml");
+const EN_CATALOG: &str = include_str!("../../locales/en.yaml");
+
+const UNSET: u8 = u8::MAX;
+static LANGUAGE: AtomicU8 = AtomicU8::new(UNSET);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Zh,
+    En,
+}
+
+impl Language {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::Zh => "zh",
+            Language::En => "en",
+        }
+    }
+
+    // 按 LC_ALL、LC_MESSAGES、LANG 的顺序取第一个非空的值，未设置或为 C/POSIX 时使用中文
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_locale(&value))
+            .unwrap_or(Language::Zh)
+    }
+
+    // zh_CN.UTF-8 等中文 locale 为中文，其余语言使用英文
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let name = locale.split(['.', '@']).next().unwrap_or_default();
+        match name {
+            "" | "C" | "POSIX" => None,
+            name if name.to_lowercase().starts_with("zh") => Some(Language::Zh),
+            _ => Some(Language::En),
+        }
+    }
+
+    fn catalog(&self) -> &'static HashMap<String, String> {
+        static ZH: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static EN: OnceLock<HashMap<String, String>> = OnceLock::new();
+        match self {
+            Language::Zh => ZH.get_or_init(|| parse_catalog(self, ZH_CATALOG)),
+            Language::En => EN.get_or_init(|| parse_catalog(self, EN_CATALOG)),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        if lower.starts_with("zh") {
+            Ok(Language::Zh)
+        } else if lower.starts_with("en") {
+            Ok(Language::En)
+        } else {
+            Err(anyhow::anyhow!("不支持的语言: {} (可选 zh, en)", s))
+        }
+    }
+}
+
+fn parse_catalog(language: &Language, content: &str) -> HashMap<String, String> {
+    serde_yaml::from_str(content).unwrap_or_else(|e| {
+        log::error!("无法解析 {} 消息目录: {}", language.as_str(), e);
+        HashMap::new()
+    })
+}
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+// 未调用 set_language 时按环境变量选择
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        UNSET => {
+            let language = Language::from_env();
+            set_language(language);
+            language
+        }
+        value if value == Language::En as u8 => Language::En,
+        _ => Language::Zh,
+    }
+}
+
+// 当前语言的目录中没有该消息时使用中文，中文目录也没有时返回键名
+pub fn text(key: &'static str) -> &'static str {
+    language()
+        .catalog()
+        .get(key)
+        .or_else(|| Language::Zh.catalog().get(key))
+        .map_or(key, String::as_str)
+}
+
+pub fn format(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = text(key).to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    message
+}
+
+// t!("scan.completed") 返回当前语言的消息；t!("scan.files_scanned", count = n) 替换消息中的 {count}
+#[macro_export]
+macro_rules! t {
+    ($key:literal) => {
+        $crate::utils::i18n::text($key)
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::utils::i18n::format($key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+}
@@ -0,0 +1,99 @@
+use log::Level;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+
+pub const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+pub struct JournaldLogger {
+    socket: UnixDatagram,
+    identifier: String,
+}
+
+impl JournaldLogger {
+    pub fn is_available() -> bool {
+        Path::new(JOURNALD_SOCKET).exists()
+    }
+
+    pub fn connect(identifier: &str) -> Result<Self, anyhow::Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket
+            .connect(JOURNALD_SOCKET)
+            .map_err(|e| anyhow::anyhow!("无法连接journald: {}", e))?;
+        Ok(Self {
+            socket,
+            identifier: identifier.to_string(),
+        })
+    }
+
+    pub fn send(&self, record: &log::Record) {
+        let payload = Self::encode_record(&self.identifier, record);
+        let _ = self.socket.send(&payload);
+    }
+
+    pub fn priority(level: Level) -> u8 {
+        match level {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        }
+    }
+
+    pub fn encode_record(identifier: &str, record: &log::Record) -> Vec<u8> {
+        let mut payload = Vec::new();
+
+        Self::append_field(&mut payload, "MESSAGE", &record.args().to_string());
+        Self::append_field(&mut payload, "PRIORITY", &Self::priority(record.level()).to_string());
+        Self::append_field(&mut payload, "SYSLOG_IDENTIFIER", identifier);
+        Self::append_field(&mut payload, "TARGET", record.target());
+        if let Some(file) = record.file() {
+            Self::append_field(&mut payload, "CODE_FILE", file);
+        }
+        if let Some(line) = record.line() {
+            Self::append_field(&mut payload, "CODE_LINE", &line.to_string());
+        }
+        if let Some(module) = record.module_path() {
+            Self::append_field(&mut payload, "CODE_MODULE", module);
+        }
+
+        struct Visitor<'a>(&'a mut Vec<u8>);
+        impl<'kvs, 'a> log::kv::VisitSource<'kvs> for Visitor<'a> {
+            fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+                if let Some(name) = JournaldLogger::field_name(key.as_str()) {
+                    JournaldLogger::append_field(self.0, &name, &value.to_string());
+                }
+                Ok(())
+            }
+        }
+        let _ = record.key_values().visit(&mut Visitor(&mut payload));
+
+        payload
+    }
+
+    // journald字段名只允许大写字母、数字和下划线，且不能以下划线开头
+    fn field_name(key: &str) -> Option<String> {
+        let name: String = key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        let name = name.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit()).to_string();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
+    fn append_field(payload: &mut Vec<u8>, name: &str, value: &str) {
+        payload.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            payload.push(b'\n');
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            payload.extend_from_slice(value.as_bytes());
+        } else {
+            payload.push(b'=');
+            payload.extend_from_slice(value.as_bytes());
+        }
+        payload.push(b'\n');
+    }
+}
@@ -1,10 +1,236 @@
+use crate::config::LoggingConfig;
 use anyhow::Context;
 use fern::Dispatch;
 use log::{Level, LevelFilter};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use chrono::Local;
+use chrono::{Local, NaiveDate};
+
+const LOG_FILE_NAME: &str = "virus-scanner.log";
+
+// 使用该 target 的日志只写入日志文件或 journald，不输出到控制台，用于命令已经向用户输出过的内容
+pub const FILE_ONLY_TARGET: &str = "virus_scanner::file_only";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Self {
+        match format.to_lowercase().as_str() {
+            "json" => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+struct FieldCollector(Vec<(String, serde_json::Value)>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(v) = value.to_i64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_u64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_f64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_bool() {
+            serde_json::Value::from(v)
+        } else {
+            serde_json::Value::from(value.to_string())
+        };
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+fn collect_fields(record: &log::Record) -> Vec<(String, serde_json::Value)> {
+    let mut collector = FieldCollector(Vec::new());
+    let _ = record.key_values().visit(&mut collector);
+    collector.0
+}
+
+pub fn format_json_record(message: &std::fmt::Arguments, record: &log::Record) -> String {
+    let mut object = serde_json::Map::new();
+    object.insert("timestamp".to_string(), Local::now().to_rfc3339().into());
+    object.insert("level".to_string(), record.level().to_string().into());
+    object.insert("target".to_string(), record.target().into());
+    object.insert("message".to_string(), message.to_string().into());
+
+    for (key, value) in collect_fields(record) {
+        object.entry(key).or_insert(value);
+    }
+
+    serde_json::Value::Object(object).to_string()
+}
+
+pub fn format_text_record(message: &std::fmt::Arguments, record: &log::Record) -> String {
+    let mut line = format!(
+        "[{}][{}][{}] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        record.level(),
+        record.target(),
+        message
+    );
+
+    for (key, value) in collect_fields(record) {
+        match value {
+            serde_json::Value::String(v) => line.push_str(&format!(" {}={:?}", key, v)),
+            v => line.push_str(&format!(" {}={}", key, v)),
+        }
+    }
+
+    line
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    pub max_size_bytes: u64,
+    pub max_files: usize,
+    pub daily: bool,
+    pub compress: bool,
+}
+
+impl RotationPolicy {
+    pub fn from_config(config: &LoggingConfig) -> Self {
+        Self {
+            max_size_bytes: config.max_size_mb.max(1) * 1024 * 1024,
+            max_files: config.max_files,
+            daily: config.rotate_daily,
+            compress: config.compress_rotated,
+        }
+    }
+}
+
+pub struct RotatingFileWriter {
+    dir: PathBuf,
+    file_name: String,
+    policy: RotationPolicy,
+    file: File,
+    current_size: u64,
+    current_date: NaiveDate,
+}
+
+impl RotatingFileWriter {
+    pub fn new(dir: PathBuf, file_name: &str, policy: RotationPolicy) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(file_name);
+        let file = Self::open(&path)?;
+        let current_size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        Ok(Self {
+            dir,
+            file_name: file_name.to_string(),
+            policy,
+            file,
+            current_size,
+            current_date: Local::now().date_naive(),
+        })
+    }
+
+    fn open(path: &Path) -> Result<File, anyhow::Error> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("无法创建日志文件: {:?}", path))
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.file_name)
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        if self.current_size == 0 {
+            return false;
+        }
+        if self.policy.daily && Local::now().date_naive() != self.current_date {
+            return true;
+        }
+        self.current_size + incoming as u64 > self.policy.max_size_bytes
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+
+        let active = self.path();
+        let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
+        let mut rotated = self.dir.join(format!("{}.{}", self.file_name, timestamp));
+        let mut suffix = 1;
+        while rotated.exists() || PathBuf::from(format!("{}.gz", rotated.display())).exists() {
+            rotated = self.dir.join(format!("{}.{}_{}", self.file_name, timestamp, suffix));
+            suffix += 1;
+        }
+
+        std::fs::rename(&active, &rotated)?;
+        self.file = Self::open(&active).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        self.current_size = 0;
+        self.current_date = Local::now().date_naive();
+
+        if self.policy.compress {
+            if let Err(e) = Self::compress(&rotated) {
+                eprintln!("压缩日志失败 {:?}: {}", rotated, e);
+            }
+        }
+
+        self.apply_retention();
+        Ok(())
+    }
+
+    fn compress(path: &Path) -> std::io::Result<()> {
+        let gz_path = PathBuf::from(format!("{}.gz", path.display()));
+        let mut input = File::open(path)?;
+        let output = File::create(&gz_path)?;
+        let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+        std::io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?;
+        std::fs::remove_file(path)
+    }
+
+    // 按文件名中的时间戳排序，仅保留最新的 max_files 个归档
+    fn apply_retention(&self) {
+        let prefix = format!("{}.", self.file_name);
+        let mut archives: Vec<PathBuf> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|n| n.starts_with(&prefix))
+                        .unwrap_or(false)
+                })
+                .collect(),
+            Err(_) => return,
+        };
+
+        archives.sort();
+        while archives.len() > self.policy.max_files {
+            let oldest = archives.remove(0);
+            let _ = std::fs::remove_file(&oldest);
+        }
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.needs_rotation(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.current_size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
 
 pub struct Logger;
 
@@ -15,37 +241,159 @@ impl Logger {
         max_size_mb: u64,
         max_files: usize,
     ) -> Result<(), anyhow::Error> {
-        std::fs::create_dir_all(&log_dir)?;
+        let policy = RotationPolicy {
+            max_size_bytes: max_size_mb.max(1) * 1024 * 1024,
+            max_files,
+            daily: false,
+            compress: false,
+        };
+        Self::init_with_rotation(log_dir, level, policy, LogFormat::Text, Vec::new())
+    }
+
+    pub fn init_from_config(config: &LoggingConfig) -> Result<(), anyhow::Error> {
+        #[cfg(unix)]
+        if config.journald {
+            if crate::utils::journald::JournaldLogger::is_available() {
+                return Self::init_journald(
+                    Self::get_level_filter(&config.level),
+                    Self::parse_module_levels(&config.module_levels),
+                );
+            }
+            eprintln!("journald不可用，日志将写入文件: {:?}", config.log_dir);
+        }
+
+        Self::init_with_rotation(
+            config.log_dir.clone(),
+            Self::get_level_filter(&config.level),
+            RotationPolicy::from_config(config),
+            LogFormat::parse(&config.format),
+            Self::parse_module_levels(&config.module_levels),
+        )
+    }
 
-        let log_file = log_dir.join(format!(
-            "virus-scanner_{}.log",
-            Local::now().format("%Y%m%d")
-        ));
+    // 命令行使用。日志文件 (或 journald) 按 config.level 记录，控制台只输出 console 及以上级别的日志，
+    // 输出到标准错误，标准输出留给命令结果。日志目录不可写时 (如普通用户运行) 只输出到控制台
+    pub fn init_cli(config: &LoggingConfig, console: LevelFilter) -> Result<(), anyhow::Error> {
+        let level = Self::get_level_filter(&config.level).max(console);
+        let format = LogFormat::parse(&config.format);
+        let root = Dispatch::new().level(level);
+        let root = Self::apply_module_levels(root, Self::parse_module_levels(&config.module_levels));
+
+        let console_output = Dispatch::new()
+            .level(console)
+            .filter(|metadata| metadata.target() != FILE_ONLY_TARGET)
+            .format(|out, message, record| out.finish(format_args!("{}", format_text_record(message, record))))
+            .chain(std::io::stderr());
+
+        #[cfg(unix)]
+        if config.journald {
+            if crate::utils::journald::JournaldLogger::is_available() {
+                let journald = crate::utils::journald::JournaldLogger::connect("virus-scanner")?;
+                root.chain(fern::Output::call(move |record| journald.send(record)))
+                    .chain(console_output)
+                    .apply()?;
+                return Ok(());
+            }
+            eprintln!("journald不可用，日志将写入文件: {:?}", config.log_dir);
+        }
+
+        let file_output = match RotatingFileWriter::new(config.log_dir.clone(), LOG_FILE_NAME, RotationPolicy::from_config(config)) {
+            Ok(writer) => Some(
+                Dispatch::new()
+                    .format(move |out, message, record| {
+                        let line = match format {
+                            LogFormat::Json => format_json_record(message, record),
+                            LogFormat::Text => format_text_record(message, record),
+                        };
+                        out.finish(format_args!("{}", line))
+                    })
+                    .chain(Box::new(writer) as Box<dyn Write + Send>),
+            ),
+            Err(e) => {
+                if console >= LevelFilter::Warn {
+                    eprintln!("无法写入日志文件，日志只输出到控制台: {:#}", e);
+                }
+                None
+            }
+        };
+        let root = match file_output {
+            Some(file_output) => root.chain(file_output),
+            None => root,
+        };
+        root.chain(console_output).apply()?;
+        Ok(())
+    }
+
+    // 模块名不含 "::" 时视为本crate的子模块，例如 scanner -> virus_scanner::scanner
+    pub fn parse_module_levels(levels: &HashMap<String, String>) -> Vec<(String, LevelFilter)> {
+        let mut parsed: Vec<(String, LevelFilter)> = levels
+            .iter()
+            .map(|(module, level)| {
+                let target = if module.contains("::") {
+                    module.clone()
+                } else {
+                    format!("virus_scanner::{}", module)
+                };
+                (target, Self::get_level_filter(level))
+            })
+            .collect();
+        parsed.sort();
+        parsed
+    }
+
+    fn apply_module_levels(mut dispatch: Dispatch, module_levels: Vec<(String, LevelFilter)>) -> Dispatch {
+        for (target, level) in module_levels {
+            dispatch = dispatch.level_for(target, level);
+        }
+        dispatch
+    }
+
+    pub fn init_with_rotation(
+        log_dir: PathBuf,
+        level: LevelFilter,
+        policy: RotationPolicy,
+        format: LogFormat,
+        module_levels: Vec<(String, LevelFilter)>,
+    ) -> Result<(), anyhow::Error> {
+        let writer = RotatingFileWriter::new(log_dir, LOG_FILE_NAME, policy)?;
+        let log_file = writer.path();
 
         let dispatcher = Dispatch::new()
-            .format(|out, message, record| {
-                out.finish(format_args!(
-                    "[{}][{}][{}] {}",
-                    Local::now().format("%Y-%m-%d %H:%M:%S"),
-                    record.level(),
-                    record.target(),
-                    message
-                ))
+            .format(move |out, message, record| {
+                let line = match format {
+                    LogFormat::Json => format_json_record(message, record),
+                    LogFormat::Text => format_text_record(message, record),
+                };
+                out.finish(format_args!("{}", line))
             })
-            .level(level)
-            .chain(
-                fern::log_file(&log_file)
-                    .context(format!("无法创建日志文件: {:?}", log_file))?,
-            )
-            .chain(std::io::stdout());
+            .level(level);
 
-        dispatcher.apply()?;
+        Self::apply_module_levels(dispatcher, module_levels)
+            .chain(Box::new(writer) as Box<dyn Write + Send>)
+            .chain(std::io::stdout())
+            .apply()?;
 
         log::info!("日志系统已初始化，输出目录: {:?}", log_file);
 
         Ok(())
     }
 
+    #[cfg(unix)]
+    pub fn init_journald(
+        level: LevelFilter,
+        module_levels: Vec<(String, LevelFilter)>,
+    ) -> Result<(), anyhow::Error> {
+        let journald = crate::utils::journald::JournaldLogger::connect("virus-scanner")?;
+
+        Self::apply_module_levels(Dispatch::new().level(level), module_levels)
+            .chain(fern::Output::call(move |record| journald.send(record)))
+            .apply()?;
+
+        log::info!("日志系统已初始化，输出到journald");
+
+        Ok(())
+    }
+
     pub fn get_level_filter(level: &str) -> LevelFilter {
         match level.to_uppercase().as_str() {
             "DEBUG" => LevelFilter::Debug,
@@ -53,6 +401,7 @@ impl Logger {
             "WARN" => LevelFilter::Warn,
             "ERROR" => LevelFilter::Error,
             "TRACE" => LevelFilter::Trace,
+            "OFF" => LevelFilter::Off,
             _ => LevelFilter::Info,
         }
     }
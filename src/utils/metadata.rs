@@ -0,0 +1,105 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMetadata {
+    pub size: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub inode: u64,
+    pub device: u64,
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+    pub accessed: Option<u64>,
+    // 纳秒精度的修改时间和状态变更时间 (ctime)，用于判断文件在两次读取之间是否变化
+    pub mtime_ns: i128,
+    pub ctime_ns: i128,
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+}
+
+impl FileMetadata {
+    pub fn from_std(metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (mode, uid, gid, inode, device, mtime_ns, ctime_ns) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                metadata.mode(),
+                metadata.uid(),
+                metadata.gid(),
+                metadata.ino(),
+                metadata.dev(),
+                metadata.mtime() as i128 * 1_000_000_000 + metadata.mtime_nsec() as i128,
+                metadata.ctime() as i128 * 1_000_000_000 + metadata.ctime_nsec() as i128,
+            )
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid, inode, device, mtime_ns, ctime_ns) = (0, 0, 0, 0, 0, 0, 0);
+
+        Self {
+            size: metadata.len(),
+            mode,
+            uid,
+            gid,
+            inode,
+            device,
+            created: Self::unix_secs(metadata.created()),
+            modified: Self::unix_secs(metadata.modified()),
+            accessed: Self::unix_secs(metadata.accessed()),
+            mtime_ns,
+            ctime_ns,
+            is_file: metadata.is_file(),
+            is_dir: metadata.is_dir(),
+            is_symlink: metadata.file_type().is_symlink(),
+        }
+    }
+
+    fn unix_secs(time: std::io::Result<SystemTime>) -> Option<u64> {
+        time.ok()?.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+    }
+
+    // 同一个文件且内容未被修改过 (写入会同时更新 mtime 和不能手动设置的 ctime)
+    pub fn same_content(&self, other: &FileMetadata) -> bool {
+        (self.device, self.inode, self.size, self.mtime_ns, self.ctime_ns)
+            == (other.device, other.inode, other.size, other.mtime_ns, other.ctime_ns)
+    }
+
+    pub fn permissions_string(&self) -> String {
+        let mut perms = String::with_capacity(9);
+        for shift in [6, 3, 0] {
+            let bits = (self.mode >> shift) & 0o7;
+            perms.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            perms.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            perms.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+        }
+        perms
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.mode & 0o111 != 0
+    }
+
+    pub fn owner_name(&self) -> Option<String> {
+        users::get_user_by_uid(self.uid).map(|u| u.name().to_string_lossy().to_string())
+    }
+
+    pub fn group_name(&self) -> Option<String> {
+        users::get_group_by_gid(self.gid).map(|g| g.name().to_string_lossy().to_string())
+    }
+}
+
+pub async fn stat_file<P: AsRef<Path>>(path: P) -> Result<FileMetadata, anyhow::Error> {
+    let metadata = tokio::fs::metadata(path.as_ref())
+        .await
+        .map_err(|e| anyhow::anyhow!("无法获取文件信息 {:?}: {}", path.as_ref(), e))?;
+    Ok(FileMetadata::from_std(&metadata))
+}
+
+pub async fn lstat_file<P: AsRef<Path>>(path: P) -> Result<FileMetadata, anyhow::Error> {
+    let metadata = tokio::fs::symlink_metadata(path.as_ref())
+        .await
+        .map_err(|e| anyhow::anyhow!("无法获取文件信息 {:?}: {}", path.as_ref(), e))?;
+    Ok(FileMetadata::from_std(&metadata))
+}
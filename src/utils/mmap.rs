@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::ops::Deref;
+
+// 只读私有映射；文件在映射期间被截断时访问越界页会触发 SIGBUS，调用方应只用于短时扫描
+pub struct MappedFile {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// 映射为只读且生命周期内不修改，可以跨线程共享
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    #[cfg(unix)]
+    pub fn map(file: &File) -> Result<Self, anyhow::Error> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())?;
+        if len == 0 {
+            return Err(anyhow::anyhow!("空文件无法映射"));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(anyhow::anyhow!("内存映射失败: {}", std::io::Error::last_os_error()));
+        }
+
+        // 扫描为顺序读取，提示内核预读
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn map(_file: &File) -> Result<Self, anyhow::Error> {
+        Err(anyhow::anyhow!("当前平台不支持内存映射"))
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
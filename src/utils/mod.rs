@@ -1,4 +1,25 @@
 pub mod logging;
+pub mod disk;
+pub mod duration;
+pub mod filetype;
+pub mod globset;
+pub mod i18n;
+#[cfg(unix)]
+pub mod journald;
+pub mod metadata;
+pub mod mmap;
+pub mod ratelimit;
+#[cfg(unix)]
+pub mod service;
+pub mod xattr;
+
+pub use disk::{available_space, ensure_free_space, DiskSpaceError};
+pub use duration::{format_duration, format_duration_secs, EtaEstimator};
+pub use filetype::{detect_file_type, detect_file_type_from_bytes, FileKind};
+pub use self::globset::{GlobMatcher, GlobOptions};
+pub use metadata::{lstat_file, stat_file, FileMetadata};
+pub use mmap::MappedFile;
+pub use ratelimit::{KeyedRateLimiter, RateLimiter};
 
 use path_absolutize::Absolutize;
 use std::path::{Path, PathBuf};
@@ -27,7 +48,7 @@ pub fn drop_privileges() -> Result<(), anyhow::Error> {
         #[cfg(not(any(target_os = "macos", target_os = "ios")))]
         {
             use nix::unistd::{Gid, Group};
-            let gid = Gid::from_raw(nobody.primary_group_id().as_raw());
+            let gid = Gid::from_raw(nobody.primary_group_id());
             let _ = nix::unistd::setgroups(&[]);
             nix::unistd::setgid(gid)?;
         }
@@ -37,6 +58,39 @@ pub fn drop_privileges() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+// 把本进程所有线程的 I/O 调度类设为 idle，磁盘空闲时才处理扫描读取。
+// 之后创建的线程继承创建者的优先级
+#[cfg(target_os = "linux")]
+pub fn set_idle_io_priority() -> Result<(), anyhow::Error> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+    for entry in std::fs::read_dir("/proc/self/task")? {
+        let tid: libc::c_int = match entry?.file_name().to_string_lossy().parse() {
+            Ok(tid) => tid,
+            Err(_) => continue,
+        };
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                tid,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if ret != 0 {
+            return Err(anyhow::anyhow!("设置I/O优先级失败: {}", std::io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_idle_io_priority() -> Result<(), anyhow::Error> {
+    Err(anyhow::anyhow!("当前平台不支持设置I/O优先级"))
+}
+
 pub fn format_bytes(size: u64) -> String {
     let units = ["B", "KB", "MB", "GB", "TB"];
     let mut size = size as f64;
@@ -69,6 +123,40 @@ pub fn get_file_hash(path: &Path) -> Result<String, anyhow::Error> {
     Ok(format!("{:08x}", hasher.finalize()))
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDigests {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+pub fn get_file_digests(path: &Path) -> Result<FileDigests, anyhow::Error> {
+    use openssl::hash::{Hasher, MessageDigest};
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut md5 = Hasher::new(MessageDigest::md5())?;
+    let mut sha1 = Hasher::new(MessageDigest::sha1())?;
+    let mut sha256 = Hasher::new(MessageDigest::sha256())?;
+    let mut buffer = vec![0u8; 8192];
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        md5.update(&buffer[..bytes_read])?;
+        sha1.update(&buffer[..bytes_read])?;
+        sha256.update(&buffer[..bytes_read])?;
+    }
+
+    Ok(FileDigests {
+        md5: hex::encode(md5.finish()?),
+        sha1: hex::encode(sha1.finish()?),
+        sha256: hex::encode(sha256.finish()?),
+    })
+}
+
 pub fn get_file_size(path: &Path) -> Result<u64, anyhow::Error> {
     let metadata = std::fs::metadata(path)?;
     Ok(metadata.len())
@@ -118,6 +206,37 @@ pub fn normalize_path(path: &Path) -> Result<PathBuf, anyhow::Error> {
     Ok(path.absolutize()?.to_path_buf())
 }
 
+pub const PSEUDO_FILESYSTEMS: [&str; 3] = ["/proc", "/sys", "/dev"];
+
+pub fn is_pseudo_filesystem(path: &Path) -> bool {
+    PSEUDO_FILESYSTEMS.iter().any(|p| path.starts_with(p))
+}
+
+// 解析符号链接后再检查，防止通过链接逃逸出扫描根目录或进入伪文件系统
+pub fn safe_canonicalize(path: &Path, roots: &[PathBuf]) -> Result<PathBuf, anyhow::Error> {
+    let canonical = std::fs::canonicalize(path)
+        .map_err(|e| anyhow::anyhow!("无法解析路径 {:?}: {}", path, e))?;
+
+    if is_pseudo_filesystem(&canonical) {
+        return Err(anyhow::anyhow!("拒绝访问伪文件系统路径: {:?} -> {:?}", path, canonical));
+    }
+
+    if !roots.is_empty() {
+        let inside = roots.iter().any(|root| {
+            let root = std::fs::canonicalize(root)
+                .or_else(|_| normalize_path(root))
+                .unwrap_or_else(|_| root.clone());
+            canonical.starts_with(&root)
+        });
+
+        if !inside {
+            return Err(anyhow::anyhow!("路径超出允许的根目录: {:?} -> {:?}", path, canonical));
+        }
+    }
+
+    Ok(canonical)
+}
+
 pub fn create_directory(path: &Path, recursive: bool) -> Result<(), anyhow::Error> {
     if recursive {
         std::fs::create_dir_all(path)?;
@@ -152,8 +271,12 @@ pub fn quarantine_file(path: &Path, quarantine_dir: &Path) -> Result<PathBuf, an
     let quarantine_path = quarantine_dir.join(format!("{}_{}", timestamp, file_name.to_string_lossy()));
 
     std::fs::create_dir_all(quarantine_dir)?;
+    ensure_free_space(quarantine_dir, get_file_size(path)?)?;
     copy_file(path, &quarantine_path)?;
     delete_file(path)?;
 
     Ok(quarantine_path)
 }
+
+#[cfg(test)]
+mod tests;
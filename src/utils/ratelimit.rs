@@ -0,0 +1,130 @@
+use dashmap::DashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<BucketState>,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u64, refill_per_sec: f64) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            refill_per_sec: refill_per_sec.max(f64::MIN_POSITIVE),
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn per_second(rate: u64) -> Self {
+        Self::new(rate, rate as f64)
+    }
+
+    fn refill(&self, state: &mut BucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        state.last_refill = now;
+    }
+
+    pub fn try_acquire(&self, tokens: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+
+        if state.tokens >= tokens as f64 {
+            state.tokens -= tokens as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    // 令牌不足时预扣（允许为负）并返回需要等待的时长，超过桶容量的大请求也能按速率放行
+    pub fn reserve(&self, tokens: u64) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+
+        state.tokens -= tokens as f64;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.refill_per_sec)
+        }
+    }
+
+    pub async fn acquire(&self, tokens: u64) {
+        let wait = self.reserve(tokens);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn available(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.tokens.max(0.0) as u64
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity as u64
+    }
+}
+
+pub struct KeyedRateLimiter<K: Eq + Hash> {
+    capacity: u64,
+    refill_per_sec: f64,
+    buckets: DashMap<K, RateLimiter>,
+}
+
+impl<K: Eq + Hash + Clone> KeyedRateLimiter<K> {
+    pub fn new(capacity: u64, refill_per_sec: f64) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+            buckets: DashMap::new(),
+        }
+    }
+
+    pub fn try_acquire(&self, key: &K, tokens: u64) -> bool {
+        self.buckets
+            .entry(key.clone())
+            .or_insert_with(|| RateLimiter::new(self.capacity, self.refill_per_sec))
+            .try_acquire(tokens)
+    }
+
+    pub async fn acquire(&self, key: &K, tokens: u64) {
+        let wait = self
+            .buckets
+            .entry(key.clone())
+            .or_insert_with(|| RateLimiter::new(self.capacity, self.refill_per_sec))
+            .reserve(tokens);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    // 清理已回满的桶，避免长期运行时键无限增长
+    pub fn cleanup(&self) {
+        self.buckets
+            .retain(|_, limiter| limiter.available() < limiter.capacity());
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
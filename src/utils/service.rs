@@ -0,0 +1,75 @@
+use anyhow::Context;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// 向 systemd 发送状态 (sd_notify 协议)，如 READY=1、WATCHDOG=1、STOPPING=1。
+// 不是由 systemd 以 Type=notify 启动时 NOTIFY_SOCKET 不存在，直接返回 false
+pub fn notify(state: &str) -> bool {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    match send_notify(&socket.to_string_lossy(), state) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("无法通知 systemd ({}): {:#}", state, e);
+            false
+        }
+    }
+}
+
+fn send_notify(socket: &str, state: &str) -> Result<(), anyhow::Error> {
+    let datagram = UnixDatagram::unbound()?;
+    // 以 @ 开头的是抽象命名空间套接字
+    if let Some(name) = socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            datagram.send_to_addr(state.as_bytes(), &addr)?;
+            return Ok(());
+        }
+        #[cfg(not(target_os = "linux"))]
+        return Err(anyhow::anyhow!("不支持抽象命名空间套接字: @{}", name));
+    }
+    datagram.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+// 单元文件设置了 WatchdogSec 时返回超时时间，需要在超时前发送 WATCHDOG=1
+pub fn watchdog_timeout() -> Option<Duration> {
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) {
+        if pid != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+// 进程运行期间持有 PID 文件，退出时删除
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    // PID 文件中的进程仍在运行时返回错误，上次异常退出残留的 PID 文件会被覆盖
+    pub fn create(path: &Path) -> Result<Self, anyhow::Error> {
+        if let Some(pid) = crate::monitor::control::running_pid(path) {
+            if pid != std::process::id() {
+                return Err(anyhow::anyhow!("服务已在运行 (PID {})", pid));
+            }
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+        }
+        std::fs::write(path, format!("{}\n", std::process::id())).with_context(|| format!("无法写入PID文件: {:?}", path))?;
+        Ok(Self { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
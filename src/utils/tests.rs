@@ -0,0 +1,389 @@
+use crate::utils::i18n::Language;
+use crate::utils::journald::JournaldLogger;
+use crate::utils::logging::{format_json_record, Logger, RotatingFileWriter, RotationPolicy};
+use crate::utils::xattr::{has_valid_clean_marker, load_marker_key, write_clean_marker, CleanMarker};
+use crate::utils::{available_space, ensure_free_space, DiskSpaceError, format_duration, EtaEstimator, safe_canonicalize, stat_file, FileMetadata, detect_file_type_from_bytes, FileKind, GlobMatcher, GlobOptions, KeyedRateLimiter, RateLimiter};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_executables() {
+        assert_eq!(detect_file_type_from_bytes(b"\x7fELF\x02\x01\x01"), FileKind::Elf);
+        assert_eq!(detect_file_type_from_bytes(b"MZ\x90\x00"), FileKind::Pe);
+        assert_eq!(detect_file_type_from_bytes(&[0xcf, 0xfa, 0xed, 0xfe, 0x07]), FileKind::MachO);
+        assert_eq!(detect_file_type_from_bytes(b"#!/bin/sh\necho hi\n"), FileKind::Script);
+    }
+
+    #[test]
+    fn test_detect_documents_and_archives() {
+        assert_eq!(detect_file_type_from_bytes(b"%PDF-1.7\n"), FileKind::Pdf);
+        assert_eq!(detect_file_type_from_bytes(b"PK\x03\x04\x14\x00data"), FileKind::Zip);
+        assert_eq!(
+            detect_file_type_from_bytes(b"PK\x03\x04\x14\x00[Content_Types].xml"),
+            FileKind::Ooxml
+        );
+        assert_eq!(
+            detect_file_type_from_bytes(&[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1]),
+            FileKind::Ole2
+        );
+        assert_eq!(detect_file_type_from_bytes(&[0x1f, 0x8b, 0x08]), FileKind::Gzip);
+    }
+
+    #[test]
+    fn test_detect_benign_media() {
+        assert_eq!(detect_file_type_from_bytes(b"\x89PNG\r\n\x1a\n\x00\x00"), FileKind::Image);
+        assert_eq!(detect_file_type_from_bytes(&[0xff, 0xd8, 0xff, 0xe0]), FileKind::Image);
+        assert_eq!(detect_file_type_from_bytes(b"RIFF\x00\x00\x00\x00WEBPVP8 "), FileKind::Image);
+        assert_eq!(detect_file_type_from_bytes(b"ID3\x04\x00"), FileKind::Media);
+        assert_eq!(detect_file_type_from_bytes(b"\x00\x00\x00\x18ftypmp42"), FileKind::Media);
+        assert!(FileKind::Image.is_benign() && !FileKind::Script.is_benign());
+        assert_eq!(detect_file_type_from_bytes(b"<?php system($_GET['c']);"), FileKind::Script);
+    }
+
+    #[test]
+    fn test_detect_text_and_unknown() {
+        assert_eq!(detect_file_type_from_bytes(b"hello world\n"), FileKind::Text);
+        assert_eq!(detect_file_type_from_bytes(&[0x00, 0x01, 0x02, 0x03]), FileKind::Unknown);
+        assert_eq!(detect_file_type_from_bytes(b""), FileKind::Unknown);
+    }
+
+    #[test]
+    fn test_detect_mail() {
+        let eml = b"Received: from mx.example.org\r\n\tby mail.example.com\r\nFrom: a@example.org\r\nSubject: hi\r\n\r\nbody\r\n";
+        assert_eq!(detect_file_type_from_bytes(eml), FileKind::Mail);
+        assert_eq!(
+            detect_file_type_from_bytes(b"From a@example.org Thu Jan  1 00:00:00 2026\nFrom: a@example.org\n"),
+            FileKind::Mail
+        );
+        assert!(FileKind::Mail.matches_target("4") && FileKind::Mail.matches_target("text"));
+        // 普通的 "key: value" 文本不是邮件
+        assert_eq!(detect_file_type_from_bytes(b"name: scanner\nversion: 1\nsubject: x\n"), FileKind::Text);
+        assert_eq!(detect_file_type_from_bytes(b"From: a@example.org\nnot a header line\n\n"), FileKind::Text);
+    }
+
+    #[test]
+    fn test_matches_target() {
+        assert!(FileKind::Elf.matches_target("Generic"));
+        assert!(FileKind::Elf.matches_target("6"));
+        assert!(FileKind::Pe.matches_target("PE"));
+        assert!(!FileKind::Elf.matches_target("1"));
+        assert!(FileKind::Ooxml.matches_target("ole2"));
+        // HTML (3)、Java (11) 等未支持的目标不限制类型
+        assert!(FileKind::Text.matches_target("3"));
+        assert!(FileKind::Zip.matches_target("11"));
+    }
+
+    #[test]
+    fn test_glob_matcher_prefix_and_wildcards() {
+        let matcher = GlobMatcher::new(
+            ["/proc", "/home/*/node_modules/**", "**/*.log"],
+            GlobOptions::default(),
+        ).unwrap();
+
+        assert!(matcher.is_match("/proc"));
+        assert!(matcher.is_match("/proc/1/status"));
+        assert!(!matcher.is_match("/process"));
+        assert!(matcher.is_match("/home/alice/node_modules/x/index.js"));
+        assert!(!matcher.is_match("/home/alice/src/node_modules.txt"));
+        assert!(matcher.is_match("/var/tmp/app.log"));
+        assert_eq!(matcher.matching_patterns("/proc/1"), vec!["/proc"]);
+    }
+
+    #[test]
+    fn test_glob_matcher_case_sensitivity() {
+        let sensitive = GlobMatcher::new(["*.EXE"], GlobOptions::default()).unwrap();
+        assert!(!sensitive.is_match("setup.exe"));
+
+        let options = GlobOptions { case_insensitive: true, ..GlobOptions::default() };
+        let insensitive = GlobMatcher::new(["*.EXE"], options).unwrap();
+        assert!(insensitive.is_match("setup.exe"));
+        assert!(GlobMatcher::empty().is_empty());
+    }
+
+    #[test]
+    fn test_rate_limiter_bucket() {
+        let limiter = RateLimiter::new(3, 1.0);
+        assert!(limiter.try_acquire(2));
+        assert!(limiter.try_acquire(1));
+        assert!(!limiter.try_acquire(1));
+        assert!(limiter.reserve(2) > std::time::Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_keyed_rate_limiter_isolated_keys() {
+        let limiter = KeyedRateLimiter::new(1, 0.1);
+        assert!(limiter.try_acquire(&"a", 1));
+        assert!(!limiter.try_acquire(&"a", 1));
+        assert!(limiter.try_acquire(&"b", 1));
+        assert_eq!(limiter.len(), 2);
+    }
+
+    #[test]
+    fn test_clean_marker_roundtrip() {
+        let marker = CleanMarker {
+            db_version: "27000".to_string(),
+            size: 42,
+            mtime_ns: 1_700_000_000_123_456_789,
+            sha256: "ab".repeat(32),
+            mac: "cd".repeat(32),
+        };
+
+        assert_eq!(CleanMarker::decode(&marker.encode()), Some(marker));
+        assert_eq!(CleanMarker::decode("infected|1|2|3|4|5"), None);
+        assert_eq!(CleanMarker::decode("clean|1|2|3|4"), None);
+        assert_eq!(CleanMarker::decode("garbage"), None);
+    }
+
+    #[test]
+    fn test_clean_marker_rejects_forged_and_modified_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key = load_marker_key(&dir.path().join("keys/marker.key")).unwrap();
+        assert_eq!(load_marker_key(&dir.path().join("keys/marker.key")).unwrap(), key);
+        let path = dir.path().join("sample.bin");
+        std::fs::write(&path, b"clean content").unwrap();
+
+        let scanned = FileMetadata::from_std(&std::fs::metadata(&path).unwrap());
+        let marker = CleanMarker::for_file(&path, "27000", &key, &scanned).unwrap();
+        assert!(marker.verify_mac(&key));
+        assert!(!marker.verify_mac(&[7u8; 32]));
+        // 没有密钥时无法为其他内容伪造标记
+        let forged = CleanMarker {
+            sha256: "00".repeat(32),
+            ..marker.clone()
+        };
+        assert!(!forged.verify_mac(&key));
+
+        // 测试环境的文件系统不支持 user.* 扩展属性时只校验签名
+        if write_clean_marker(&path, "27000", &key, &scanned).is_err() {
+            return;
+        }
+        assert!(has_valid_clean_marker(&path, "27000", &key));
+        assert!(!has_valid_clean_marker(&path, "27001", &key));
+        assert!(!has_valid_clean_marker(&path, "27000", &[7u8; 32]));
+
+        // 内容改为同样大小并恢复修改时间后标记失效
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, b"EVIL! content").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        assert!(!has_valid_clean_marker(&path, "27000", &key));
+    }
+
+    #[test]
+    fn test_clean_marker_refuses_files_changed_since_scan() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key = [9u8; 32];
+        let path = dir.path().join("sample.bin");
+        std::fs::write(&path, b"clean content").unwrap();
+        let scanned = FileMetadata::from_std(&std::fs::metadata(&path).unwrap());
+
+        // 扫描之后被替换成同样大小的内容并恢复修改时间，ctime 仍然变化
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(&path, b"EVIL! content").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        assert!(CleanMarker::for_file(&path, "27000", &key, &scanned).is_err());
+        assert!(write_clean_marker(&path, "27000", &key, &scanned).is_err());
+
+        // 被重命名的新文件替换
+        let replacement = dir.path().join("replacement.bin");
+        std::fs::write(&replacement, b"clean content").unwrap();
+        std::fs::rename(&replacement, &path).unwrap();
+        assert!(CleanMarker::for_file(&path, "27000", &key, &scanned).is_err());
+    }
+
+    #[test]
+    fn test_safe_canonicalize_guards() {
+        let root = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        let inner = root.path().join("inner.bin");
+        std::fs::write(&inner, b"data").unwrap();
+        let escape = root.path().join("escape");
+        std::os::unix::fs::symlink(outside.path(), &escape).unwrap();
+        let proc_link = root.path().join("proc_link");
+        std::os::unix::fs::symlink("/proc/self", &proc_link).unwrap();
+
+        let roots = vec![root.path().to_path_buf()];
+        assert!(safe_canonicalize(&inner, &roots).is_ok());
+        assert!(safe_canonicalize(&escape, &roots).is_err());
+        assert!(safe_canonicalize(&proc_link, &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stat_file_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("tool.sh");
+        std::fs::write(&file, b"#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o754)).unwrap();
+
+        let metadata = stat_file(&file).await.unwrap();
+        assert_eq!(metadata.size, 10);
+        assert!(metadata.is_file);
+        assert!(metadata.is_executable());
+        assert_eq!(metadata.permissions_string(), "rwxr-xr--");
+        assert!(metadata.modified.is_some());
+        assert!(stat_file(dir.path().join("missing")).await.is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        use std::time::Duration;
+
+        assert_eq!(format_duration(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 5s");
+        assert_eq!(format_duration(Duration::from_secs(4320)), "1h 12m");
+        assert_eq!(format_duration(Duration::from_secs(90000)), "1d 1h");
+    }
+
+    #[test]
+    fn test_eta_estimator() {
+        use std::time::{Duration, Instant};
+
+        let mut eta = EtaEstimator::with_smoothing(100, 0.5);
+        let start = Instant::now();
+        assert!(eta.eta().is_none());
+
+        eta.update_at(10, start + Duration::from_secs(1));
+        assert!((eta.rate() - 10.0).abs() < 0.1);
+
+        eta.update_at(40, start + Duration::from_secs(2));
+        assert!((eta.rate() - 20.0).abs() < 0.1);
+        assert_eq!(eta.percent(), 40.0);
+        let remaining = eta.eta().unwrap().as_secs_f64();
+        assert!((remaining - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_disk_space_preflight() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let missing_child = dir.path().join("not/yet/created");
+
+        assert!(available_space(&missing_child).unwrap() > 0);
+        assert!(ensure_free_space(dir.path(), 1).is_ok());
+        assert!(matches!(
+            ensure_free_space(dir.path(), u64::MAX / 2),
+            Err(DiskSpaceError::Insufficient { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rotating_writer_rotation_and_retention() {
+        use std::io::Write;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let policy = RotationPolicy {
+            max_size_bytes: 100,
+            max_files: 1,
+            daily: false,
+            compress: true,
+        };
+        let mut writer = RotatingFileWriter::new(dir.path().to_path_buf(), "test.log", policy).unwrap();
+
+        for _ in 0..3 {
+            writer.write_all(&[b'x'; 60]).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert!(names.contains(&"test.log".to_string()));
+        assert_eq!(names.iter().filter(|n| n.starts_with("test.log.")).count(), 1);
+        assert!(names.iter().any(|n| n.ends_with(".gz")));
+        assert_eq!(std::fs::metadata(writer.path()).unwrap().len(), 60);
+    }
+
+    #[test]
+    fn test_json_log_record() {
+        let fields: &[(&str, &str)] = &[("scan_id", "SCN0001"), ("path", "/tmp/x")];
+        let record = log::Record::builder()
+            .level(log::Level::Warn)
+            .target("virus_scanner::scanner")
+            .key_values(&fields)
+            .build();
+
+        let line = format_json_record(&format_args!("发现威胁"), &record);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["target"], "virus_scanner::scanner");
+        assert_eq!(value["message"], "发现威胁");
+        assert_eq!(value["scan_id"], "SCN0001");
+        assert_eq!(value["path"], "/tmp/x");
+    }
+
+    #[test]
+    fn test_journald_encoding() {
+        let fields: &[(&str, &str)] = &[("file-path", "/tmp/a\nb")];
+        let record = log::Record::builder()
+            .level(log::Level::Error)
+            .target("virus_scanner::scanner")
+            .args(format_args!("扫描失败"))
+            .key_values(&fields)
+            .build();
+
+        let payload = JournaldLogger::encode_record("virus-scanner", &record);
+        let text = String::from_utf8_lossy(&payload);
+        assert!(text.contains("MESSAGE=扫描失败\n"));
+        assert!(text.contains("PRIORITY=3\n"));
+        assert!(text.contains("SYSLOG_IDENTIFIER=virus-scanner\n"));
+
+        let mut binary_field = b"FILE_PATH\n".to_vec();
+        binary_field.extend_from_slice(&8u64.to_le_bytes());
+        binary_field.extend_from_slice(b"/tmp/a\nb\n");
+        assert!(payload.windows(binary_field.len()).any(|w| w == binary_field.as_slice()));
+    }
+
+    #[test]
+    fn test_parse_module_levels() {
+        let mut levels = std::collections::HashMap::new();
+        levels.insert("scanner".to_string(), "debug".to_string());
+        levels.insert("hyper::client".to_string(), "warn".to_string());
+
+        let parsed = Logger::parse_module_levels(&levels);
+        assert_eq!(
+            parsed,
+            vec![
+                ("hyper::client".to_string(), log::LevelFilter::Warn),
+                ("virus_scanner::scanner".to_string(), log::LevelFilter::Debug),
+            ]
+        );
+    }
+
+    // 两种语言的目录必须有相同的消息和参数
+    #[test]
+    fn test_message_catalogs_match() {
+        let parse = |content: &str| -> std::collections::HashMap<String, String> { serde_yaml::from_str(content).unwrap() };
+        let zh = parse(include_str!("../../locales/zh.yaml"));
+        let en = parse(include_str!("../../locales/en.yaml"));
+        let placeholders = |message: &str| -> Vec<String> {
+            let mut names: Vec<String> = message
+                .split('{')
+                .skip(1)
+                .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut keys: Vec<&String> = zh.keys().collect();
+        keys.sort();
+        let mut en_keys: Vec<&String> = en.keys().collect();
+        en_keys.sort();
+        assert_eq!(keys, en_keys);
+        for key in keys {
+            assert_eq!(placeholders(&zh[key]), placeholders(&en[key]), "{}", key);
+        }
+
+        assert_eq!(Language::from_locale("zh_CN.UTF-8"), Some(Language::Zh));
+        assert_eq!(Language::from_locale("en_US.UTF-8"), Some(Language::En));
+        assert_eq!(Language::from_locale("de_DE@euro"), Some(Language::En));
+        assert_eq!(Language::from_locale("C.UTF-8"), None);
+        assert_eq!("zh-CN".parse::<Language>().unwrap(), Language::Zh);
+        assert!("fr".parse::<Language>().is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::utils::FileMetadata;
+use anyhow::Context;
+use openssl::hash::{Hasher, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use std::path::Path;
+
+pub const VERDICT_XATTR: &str = "user.virus_scanner.verdict";
+
+const MARKER_PREFIX: &str = "clean";
+const MARKER_KEY_LEN: usize = 32;
+
+// user.* 扩展属性和修改时间都可以由文件属主任意设置，标记本身不可信：
+// 标记记录内容的 SHA256，并用只有扫描器能读取的密钥计算 HMAC，跳过文件前重新计算内容哈希并校验签名。
+// 计算哈希前后文件都必须与扫描开始时一致，否则签名的可能是没有扫描过的内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct CleanMarker {
+    pub db_version: String,
+    pub size: u64,
+    pub mtime_ns: i128,
+    pub sha256: String,
+    pub mac: String,
+}
+
+impl CleanMarker {
+    pub fn for_file(path: &Path, db_version: &str, key: &[u8], scanned: &FileMetadata) -> Result<Self, anyhow::Error> {
+        let unchanged = || -> Result<std::fs::Metadata, anyhow::Error> {
+            let metadata = std::fs::metadata(path)?;
+            if !FileMetadata::from_std(&metadata).same_content(scanned) {
+                return Err(anyhow::anyhow!("文件在扫描后被修改，不写入扫描标记: {:?}", path));
+            }
+            Ok(metadata)
+        };
+
+        let metadata = unchanged()?;
+        let sha256 = sha256_file(path)?;
+        unchanged()?;
+        let mut marker = Self {
+            db_version: db_version.to_string(),
+            size: metadata.len(),
+            mtime_ns: Self::mtime_ns(&metadata),
+            sha256,
+            mac: String::new(),
+        };
+        marker.mac = marker.compute_mac(key)?;
+        Ok(marker)
+    }
+
+    fn mtime_ns(metadata: &std::fs::Metadata) -> i128 {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            metadata.mtime() as i128 * 1_000_000_000 + metadata.mtime_nsec() as i128
+        }
+        #[cfg(not(unix))]
+        {
+            metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos() as i128)
+                .unwrap_or(0)
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            MARKER_PREFIX, self.db_version, self.size, self.mtime_ns, self.sha256
+        )
+    }
+
+    fn compute_mac(&self, key: &[u8]) -> Result<String, anyhow::Error> {
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(self.payload().as_bytes())?;
+        Ok(hex::encode(signer.sign_to_vec()?))
+    }
+
+    pub fn verify_mac(&self, key: &[u8]) -> bool {
+        match self.compute_mac(key) {
+            Ok(expected) => expected.len() == self.mac.len() && openssl::memcmp::eq(expected.as_bytes(), self.mac.as_bytes()),
+            Err(_) => false,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}|{}", self.payload(), self.mac)
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.split('|').collect();
+        if parts.len() != 6 || parts[0] != MARKER_PREFIX {
+            return None;
+        }
+        Some(Self {
+            db_version: parts[1].to_string(),
+            size: parts[2].parse().ok()?,
+            mtime_ns: parts[3].parse().ok()?,
+            sha256: parts[4].to_string(),
+            mac: parts[5].to_string(),
+        })
+    }
+}
+
+// 读取标记签名密钥，文件不存在时生成新密钥并以 0600 权限保存
+pub fn load_marker_key(path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if path.exists() {
+        let key = std::fs::read(path).with_context(|| format!("无法读取扫描标记密钥: {:?}", path))?;
+        if key.len() < MARKER_KEY_LEN {
+            return Err(anyhow::anyhow!("扫描标记密钥过短: 至少需要 {} 字节", MARKER_KEY_LEN));
+        }
+        return Ok(key);
+    }
+
+    let mut key = vec![0u8; MARKER_KEY_LEN];
+    openssl::rand::rand_bytes(&mut key)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .with_context(|| format!("无法写入扫描标记密钥: {:?}", tmp))?;
+    file.write_all(&key)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path).with_context(|| format!("无法写入扫描标记密钥: {:?}", path))?;
+    log::info!("已生成扫描标记密钥: {:?}", path);
+    Ok(key)
+}
+
+// scanned 是扫描开始时的文件信息
+pub fn write_clean_marker(path: &Path, db_version: &str, key: &[u8], scanned: &FileMetadata) -> Result<(), anyhow::Error> {
+    let marker = CleanMarker::for_file(path, db_version, key, scanned)?;
+    set_xattr(path, VERDICT_XATTR, marker.encode().as_bytes())
+}
+
+pub fn read_clean_marker(path: &Path) -> Result<Option<CleanMarker>, anyhow::Error> {
+    Ok(get_xattr(path, VERDICT_XATTR)?
+        .and_then(|value| CleanMarker::decode(&String::from_utf8_lossy(&value))))
+}
+
+// 版本、大小和修改时间先做快速排除，之后签名有效且重新计算的内容哈希一致才视为有效。
+// 写入扩展属性本身会更新 ctime，因此不能用 ctime 判断文件是否变化
+pub fn has_valid_clean_marker(path: &Path, db_version: &str, key: &[u8]) -> bool {
+    let marker = match read_clean_marker(path) {
+        Ok(Some(marker)) => marker,
+        _ => return false,
+    };
+
+    if marker.db_version != db_version {
+        return false;
+    }
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+
+    if marker.size != metadata.len() || marker.mtime_ns != CleanMarker::mtime_ns(&metadata) {
+        return false;
+    }
+
+    if !marker.verify_mac(key) {
+        log::warn!("扫描标记签名无效，重新扫描: {:?}", path);
+        return false;
+    }
+
+    sha256_file(path).map(|sha256| sha256 == marker.sha256).unwrap_or(false)
+}
+
+fn sha256_file(path: &Path) -> Result<String, anyhow::Error> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Hasher::new(MessageDigest::sha256())?;
+    let mut buffer = vec![0u8; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read])?;
+    }
+    Ok(hex::encode(hasher.finish()?))
+}
+
+pub fn remove_clean_marker(path: &Path) -> Result<(), anyhow::Error> {
+    remove_xattr(path, VERDICT_XATTR)
+}
+
+#[cfg(target_os = "linux")]
+fn path_to_cstring(path: &Path) -> Result<std::ffi::CString, anyhow::Error> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(std::ffi::CString::new(path.as_os_str().as_bytes())?)
+}
+
+#[cfg(target_os = "linux")]
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> Result<(), anyhow::Error> {
+    let c_path = path_to_cstring(path)?;
+    let c_name = std::ffi::CString::new(name)?;
+
+    let ret = unsafe {
+        libc::setxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+
+    if ret != 0 {
+        return Err(anyhow::anyhow!(
+            "无法写入扩展属性 {:?}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn get_xattr(path: &Path, name: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let c_path = path_to_cstring(path)?;
+    let c_name = std::ffi::CString::new(name)?;
+    let mut buffer = vec![0u8; 512];
+
+    let len = unsafe {
+        libc::getxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+        )
+    };
+
+    if len < 0 {
+        let err = std::io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENODATA) | Some(libc::ENOTSUP) => Ok(None),
+            _ => Err(anyhow::anyhow!("无法读取扩展属性 {:?}: {}", path, err)),
+        };
+    }
+
+    buffer.truncate(len as usize);
+    Ok(Some(buffer))
+}
+
+#[cfg(target_os = "linux")]
+fn remove_xattr(path: &Path, name: &str) -> Result<(), anyhow::Error> {
+    let c_path = path_to_cstring(path)?;
+    let c_name = std::ffi::CString::new(name)?;
+
+    let ret = unsafe { libc::removexattr(c_path.as_ptr(), c_name.as_ptr()) };
+    if ret != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ENODATA) {
+            return Err(anyhow::anyhow!("无法删除扩展属性 {:?}: {}", path, err));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> Result<(), anyhow::Error> {
+    Err(anyhow::anyhow!("扩展属性仅在Linux系统上可用"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_xattr(_path: &Path, _name: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    Ok(None)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remove_xattr(_path: &Path, _name: &str) -> Result<(), anyhow::Error> {
+    Ok(())
+}